@@ -7,7 +7,7 @@ fn main() {
     let mut led = LED::new(17);
 
     // on_time = 2 secs, off_time=3 secs
-    led.blink(2.0, 3.0);
+    led.blink(2.0, 3.0).unwrap();
 
     // prevent program from exiting immediately
     led.wait();
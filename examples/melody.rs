@@ -0,0 +1,21 @@
+//! Play a short jingle on a TonalBuzzer
+
+use rust_gpiozero::*;
+use std::time::Duration;
+
+fn main() {
+    // Create a new TonalBuzzer attached to Pin 17
+    let mut buzzer = TonalBuzzer::new(17);
+
+    let beat = Duration::from_millis(1000);
+    let melody: Vec<(Tone, Duration)> = ["C4", "E4", "G4", "C5"]
+        .iter()
+        .filter_map(|note| Tone::from_note(note))
+        .map(|tone| (tone, beat / 2))
+        .chain(vec![(Tone::rest(), beat / 2), (Tone::from_midi(72), beat)])
+        .collect();
+
+    // play twice as fast as written, blocking until done
+    buzzer.set_tempo(120.0);
+    buzzer.play_melody(&melody, false);
+}
@@ -0,0 +1,14 @@
+//! Flash a message in Morse code on an LED
+
+use rust_gpiozero::*;
+
+fn main() {
+    // Create a new LED attached to Pin 17
+    let mut led = LED::new(17);
+
+    // send SOS twice at 12 words per minute
+    MorseCode::with_wpm(12.0).transmit(&mut led, "SOS", Some(2));
+
+    // prevent program from exiting immediately
+    led.wait();
+}
@@ -8,7 +8,7 @@ fn main() {
 
     // blink the LED 5 times
     led.set_blink_count(5);
-    led.blink(2.0, 2.0, 1.0, 1.0);
+    led.blink(2.0, 2.0, 1.0, 1.0).unwrap();
 
     // wait for key press to exit
     let _ = io::stdin().read(&mut [0u8]).unwrap();
@@ -0,0 +1,203 @@
+//! Derive macros for rust_gpiozero
+//!
+//! Use the macros through the `rust_gpiozero` crate, which re-exports them.
+extern crate proc_macro;
+
+use proc_macro::{Delimiter, TokenStream, TokenTree};
+
+/// Derives `CompositeDevice` and `Inspect` for a struct whose fields are devices.
+///
+/// Every named field becomes a child under its own name, in declaration order. Fields that
+/// are not devices can be left out with `#[composite(skip)]`.
+///
+/// Only structs with named fields and no type or lifetime parameters are supported; enums,
+/// unions, tuple, unit and generic structs are rejected with a `compile_error!` naming the
+/// form. The derive does not generate configuration or serialization code: the values of
+/// the children are available as JSON through `CompositeDevice::to_json`, and a composite
+/// is built from a configuration file by registering a `drivers::Driver` for it.
+#[proc_macro_derive(CompositeDevice, attributes(composite))]
+pub fn derive_composite_device(input: TokenStream) -> TokenStream {
+    match expand(input) {
+        Ok(code) => code.parse().unwrap(),
+        Err(message) => format!("compile_error!({:?});", message).parse().unwrap(),
+    }
+}
+
+/// The name and the device fields of the struct a derive is applied to. Every other form of
+/// item is rejected with an error naming it.
+fn parse_struct(input: TokenStream, derive: &str) -> Result<(String, Vec<String>), String> {
+    let tokens: Vec<TokenTree> = input.into_iter().collect();
+    let position = tokens
+        .iter()
+        .position(|t| {
+            matches!(t, TokenTree::Ident(i) if ["struct", "enum", "union"].contains(&i.to_string().as_str()))
+        })
+        .ok_or(format!("{} can only be derived for structs", derive))?;
+    let item = tokens[position].to_string();
+    let name = match tokens.get(position + 1) {
+        Some(TokenTree::Ident(name)) => name.to_string(),
+        _ => return Err(format!("expected the name of the {}", item)),
+    };
+    if item != "struct" {
+        return Err(format!(
+            "{} cannot be derived for {} `{}`: it needs a struct with named fields",
+            derive, item, name
+        ));
+    }
+    let body = match tokens.get(position + 2) {
+        Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => group.stream(),
+        Some(TokenTree::Punct(p)) if p.as_char() == '<' => {
+            return Err(format!(
+                "{} cannot be derived for generic struct `{}<..>`: \
+                 implement the trait by hand for structs with type or lifetime parameters",
+                derive, name
+            ))
+        }
+        Some(TokenTree::Ident(i)) if i.to_string() == "where" => {
+            return Err(format!(
+                "{} cannot be derived for struct `{}` with a where clause",
+                derive, name
+            ))
+        }
+        Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis => {
+            return Err(format!(
+                "{} cannot be derived for tuple struct `{}`: it needs named fields",
+                derive, name
+            ))
+        }
+        _ => {
+            return Err(format!(
+                "{} cannot be derived for unit struct `{}`: it needs named fields",
+                derive, name
+            ))
+        }
+    };
+    Ok((name, field_names(body)?))
+}
+
+fn expand(input: TokenStream) -> Result<String, String> {
+    let (name, fields) = parse_struct(input, "CompositeDevice")?;
+    let names: Vec<String> = fields.iter().map(|f| format!("{:?}", f)).collect();
+    let child: Vec<String> = fields
+        .iter()
+        .map(|f| format!("{:?} => Some(&self.{}),", f, f))
+        .collect();
+    let child_mut: Vec<String> = fields
+        .iter()
+        .map(|f| format!("{:?} => Some(&mut self.{}),", f, f))
+        .collect();
+
+    Ok(format!(
+        "impl ::rust_gpiozero::CompositeDevice for {name} {{
+            fn names(&self) -> &'static [&'static str] {{
+                &[{names}]
+            }}
+
+            fn child(&self, name: &str) -> Option<&dyn ::rust_gpiozero::Component> {{
+                match name {{
+                    {child}
+                    _ => None,
+                }}
+            }}
+
+            fn child_mut(&mut self, name: &str) -> Option<&mut dyn ::rust_gpiozero::Component> {{
+                match name {{
+                    {child_mut}
+                    _ => None,
+                }}
+            }}
+        }}
+
+        impl ::rust_gpiozero::Inspect for {name} {{
+            fn value(&self) -> String {{
+                ::rust_gpiozero::CompositeDevice::to_json(self).to_string()
+            }}
+        }}",
+        name = name,
+        names = names.join(", "),
+        child = child.join("\n"),
+        child_mut = child_mut.join("\n"),
+    ))
+}
+
+/// Derives `CompositeOutputDevice` for a struct whose fields are output devices. The struct
+/// must also derive `CompositeDevice`, and the same fields can be left out with
+/// `#[composite(skip)]`.
+#[proc_macro_derive(CompositeOutputDevice, attributes(composite))]
+pub fn derive_composite_output_device(input: TokenStream) -> TokenStream {
+    match expand_output(input) {
+        Ok(code) => code.parse().unwrap(),
+        Err(message) => format!("compile_error!({:?});", message).parse().unwrap(),
+    }
+}
+
+fn expand_output(input: TokenStream) -> Result<String, String> {
+    let (name, fields) = parse_struct(input, "CompositeOutputDevice")?;
+    let output_mut: Vec<String> = fields
+        .iter()
+        .map(|f| format!("{:?} => Some(&mut self.{}),", f, f))
+        .collect();
+
+    Ok(format!(
+        "impl ::rust_gpiozero::CompositeOutputDevice for {name} {{
+            fn output_mut(
+                &mut self,
+                name: &str,
+            ) -> Option<&mut dyn ::rust_gpiozero::OutputComponent> {{
+                match name {{
+                    {output_mut}
+                    _ => None,
+                }}
+            }}
+        }}",
+        name = name,
+        output_mut = output_mut.join("\n"),
+    ))
+}
+
+/// The names of the fields in the body of a struct, except those marked `#[composite(skip)]`.
+fn field_names(body: TokenStream) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut skip = false;
+    let mut expect_name = true;
+    let mut depth = 0;
+    let mut arrow = false;
+    let mut tokens = body.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        // the '>' of '->' in function types does not close a generic argument list
+        let after_dash = arrow;
+        arrow = matches!(&token, TokenTree::Punct(p) if p.as_char() == '-');
+        match token {
+            TokenTree::Punct(p) if p.as_char() == '#' && expect_name => {
+                if let Some(TokenTree::Group(attribute)) = tokens.next() {
+                    let text = attribute.stream().to_string().replace(' ', "");
+                    if text == "composite(skip)" {
+                        skip = true;
+                    } else if text.starts_with("composite") {
+                        return Err(format!("unknown attribute #[{}]", text));
+                    }
+                }
+            }
+            TokenTree::Ident(i) if expect_name && i.to_string() == "pub" => {
+                // pub(crate) and similar
+                if let Some(TokenTree::Group(g)) = tokens.peek() {
+                    if g.delimiter() == Delimiter::Parenthesis {
+                        tokens.next();
+                    }
+                }
+            }
+            TokenTree::Ident(field) if expect_name => {
+                if !skip {
+                    fields.push(field.to_string());
+                }
+                skip = false;
+                expect_name = false;
+            }
+            TokenTree::Punct(p) if p.as_char() == '<' => depth += 1,
+            TokenTree::Punct(p) if p.as_char() == '>' && depth > 0 && !after_dash => depth -= 1,
+            TokenTree::Punct(p) if p.as_char() == ',' && depth == 0 => expect_name = true,
+            _ => {}
+        }
+    }
+    Ok(fields)
+}
@@ -0,0 +1,212 @@
+//! Audit log of output commands
+//!
+//! When enabled, every command sent to an output device (on, off, toggle, a new value or
+//! a background pattern) is recorded with the pin, the value, and where the command came
+//! from: the source set with `with_source`, the thread and the calling line of code. The
+//! log answers questions like "what turned the heater on at 02:13?" and can be exported as
+//! JSON lines for long-term storage.
+//!
+//! The log is off by default and costs a single atomic load per command while disabled.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::audit;
+//! use rust_gpiozero::*;
+//!
+//! audit::enable();
+//! audit::label(17, "heater");
+//!
+//! let mut heater = LED::new(17);
+//! audit::with_source("thermostat", || heater.on());
+//!
+//! audit::export("/var/log/greenhouse-audit.jsonl").unwrap();
+//! ```
+use crate::json::Json;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::panic::Location;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A command sent to an output device.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditRecord {
+    /// When the command was sent
+    pub time: SystemTime,
+    /// The GPIO pin of the device
+    pub pin: u8,
+    /// The label of the pin set with `label`, or `GPIO<pin>`
+    pub device: String,
+    /// The command: `on`, `off`, `value` or `pattern`
+    pub command: String,
+    /// The value written: 1.0 for on, 0.0 for off, the duty cycle for PWM devices.
+    /// None for patterns, which change the value in the background
+    pub value: Option<f64>,
+    /// The innermost source set with `with_source` on the calling thread, or None
+    pub source: Option<String>,
+    /// The name of the calling thread, or its id if it has no name
+    pub thread: String,
+    /// The file and line the command was called from
+    pub location: String,
+}
+
+impl AuditRecord {
+    /// The record as a JSON object, with the time in seconds since the Unix epoch.
+    pub fn to_json(&self) -> Json {
+        let time = self
+            .time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        Json::object(vec![
+            ("time", Json::Number((time * 1000.0).round() / 1000.0)),
+            ("pin", Json::Number(f64::from(self.pin))),
+            ("device", Json::from(self.device.as_str())),
+            ("command", Json::from(self.command.as_str())),
+            ("value", self.value.map_or(Json::Null, Json::Number)),
+            (
+                "source",
+                self.source
+                    .as_ref()
+                    .map_or(Json::Null, |s| Json::from(s.as_str())),
+            ),
+            ("thread", Json::from(self.thread.as_str())),
+            ("location", Json::from(self.location.as_str())),
+        ])
+    }
+}
+
+struct AuditLog {
+    records: VecDeque<AuditRecord>,
+    capacity: usize,
+    labels: Vec<(u8, String)>,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+static LOG: Mutex<AuditLog> = Mutex::new(AuditLog {
+    records: VecDeque::new(),
+    capacity: 10_000,
+    labels: Vec::new(),
+});
+
+thread_local! {
+    static SOURCES: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Start recording output commands.
+pub fn enable() {
+    ENABLED.store(true, Ordering::SeqCst)
+}
+
+/// Stop recording output commands. Records already taken are kept.
+pub fn disable() {
+    ENABLED.store(false, Ordering::SeqCst)
+}
+
+/// Returns ``True`` if output commands are being recorded.
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Set how many records are kept in memory; the oldest are dropped first. Defaults to 10000.
+pub fn set_capacity(capacity: usize) {
+    let mut log = LOG.lock().unwrap();
+    log.capacity = capacity;
+    while log.records.len() > capacity {
+        log.records.pop_front();
+    }
+}
+
+/// Name the device on a pin in the records, e.g. `heater` instead of `GPIO17`.
+pub fn label(pin: u8, name: &str) {
+    let mut log = LOG.lock().unwrap();
+    log.labels.retain(|(p, _)| *p != pin);
+    log.labels.push((pin, name.to_string()));
+}
+
+/// Run `f` with `source` recorded as the origin of the commands it sends on this thread.
+/// Sources nest; the innermost one is recorded.
+pub fn with_source<R, F: FnOnce() -> R>(source: &str, f: F) -> R {
+    struct Pop;
+    impl Drop for Pop {
+        fn drop(&mut self) {
+            SOURCES.with(|sources| sources.borrow_mut().pop());
+        }
+    }
+    SOURCES.with(|sources| sources.borrow_mut().push(source.to_string()));
+    let _pop = Pop;
+    f()
+}
+
+/// The records in memory, oldest first.
+pub fn records() -> Vec<AuditRecord> {
+    LOG.lock().unwrap().records.iter().cloned().collect()
+}
+
+/// Drop all records in memory.
+pub fn clear() {
+    LOG.lock().unwrap().records.clear()
+}
+
+/// The records in memory as JSON lines: one JSON object per line, oldest first.
+pub fn to_json_lines() -> String {
+    LOG.lock()
+        .unwrap()
+        .records
+        .iter()
+        .map(|record| format!("{}\n", record.to_json()))
+        .collect()
+}
+
+/// Append the records in memory to a JSON lines file and drop them from memory.
+/// * `path` - The file to append to, created if it does not exist
+pub fn export<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    let mut log = LOG.lock().unwrap();
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    for record in &log.records {
+        writeln!(file, "{}", record.to_json())?;
+    }
+    log.records.clear();
+    Ok(())
+}
+
+/// Record a command if the log is enabled.
+pub(crate) fn record(pin: u8, command: &str, value: Option<f64>, location: &Location) {
+    if !is_enabled() {
+        return;
+    }
+    let source = SOURCES.with(|sources| sources.borrow().last().cloned());
+    let current = thread::current();
+    let thread = match current.name() {
+        Some(name) => name.to_string(),
+        None => format!("{:?}", current.id()),
+    };
+    let mut log = LOG.lock().unwrap();
+    let device = match log.labels.iter().find(|(p, _)| *p == pin) {
+        Some((_, name)) => name.clone(),
+        None => format!("GPIO{}", pin),
+    };
+    if log.capacity == 0 {
+        return;
+    }
+    while log.records.len() >= log.capacity {
+        log.records.pop_front();
+    }
+    log.records.push_back(AuditRecord {
+        time: SystemTime::now(),
+        pin,
+        device,
+        command: command.to_string(),
+        value,
+        source,
+        thread,
+        location: format!("{}:{}", location.file(), location.line()),
+    });
+}
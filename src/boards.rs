@@ -0,0 +1,2271 @@
+//! Composite devices built from several LEDs, buttons and other devices, such as `LEDBoard` and `ButtonBoard`
+use crate::devices::{Component, OutputComponent};
+use crate::expander::PinFactory;
+use crate::i2c_devices::{ADSChannel, ADSGain, ADS1015, CAP1208};
+use crate::input_devices::{Bias, Button, DigitalInputDevice, DigitalInputDeviceR};
+use crate::output_devices::{
+    sleep_while_running, Buzzer, DigitalOutputDevice, Motor, OutputDevice, TimingError, LED, PWMLED,
+};
+use crate::{CompositeDevice, CompositeOutputDevice};
+use std::ops::{Index, IndexMut};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// A device that can be part of an `LEDBoard`: a single `LED`, a `PWMLED` or another board.
+/// Besides switching on and off as an `OutputComponent`, it can be dimmed and blinked.
+pub trait BoardItem: OutputComponent {
+    /// Set the brightness of every LED in the device between 0.0 and 1.0.
+    /// LEDs without PWM are lit for any value above 0.0.
+    fn set_brightness(&mut self, value: f64);
+
+    /// Number of LEDs in the device.
+    fn len(&self) -> usize {
+        1
+    }
+
+    /// Returns ``True`` if the device contains no LEDs.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Make the device turn on and off repeatedly in the background.
+    /// Fading is only supported by PWM LEDs.
+    /// * `on_time` - Number of seconds on
+    /// * `off_time` - Number of seconds off
+    /// * `fade_in_time` - Number of seconds to spend fading in
+    /// * `fade_out_time` - Number of seconds to spend fading out
+    /// * `n` - Number of times to blink. If this is None, blink indefinitely.
+    fn blink(
+        &mut self,
+        on_time: f32,
+        off_time: f32,
+        fade_in_time: f32,
+        fade_out_time: f32,
+        n: Option<i32>,
+    ) -> Result<(), TimingError>;
+
+    /// Pause or resume a blink or pulse running in the background.
+    fn set_paused(&mut self, paused: bool);
+
+    /// Suspend a blink or pulse running in the background, turning its LEDs off until
+    /// `resume` is called.
+    fn pause(&mut self) {
+        self.set_paused(true)
+    }
+
+    /// Resume a paused blink or pulse in phase.
+    fn resume(&mut self) {
+        self.set_paused(false)
+    }
+}
+
+impl BoardItem for LED {
+    fn set_brightness(&mut self, value: f64) {
+        if value > 0.0 {
+            LED::on(self)
+        } else {
+            LED::off(self)
+        }
+    }
+
+    fn blink(
+        &mut self,
+        on_time: f32,
+        off_time: f32,
+        fade_in_time: f32,
+        fade_out_time: f32,
+        n: Option<i32>,
+    ) -> Result<(), TimingError> {
+        if fade_in_time > 0.0 || fade_out_time > 0.0 {
+            println!("Fading is only supported by PWM LEDs");
+            return Ok(());
+        }
+        self.blinker(on_time, off_time, n)
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        if paused {
+            LED::pause(self)
+        } else {
+            LED::resume(self)
+        }
+    }
+}
+
+impl BoardItem for PWMLED {
+    fn set_brightness(&mut self, value: f64) {
+        self.set_value(value)
+    }
+
+    fn blink(
+        &mut self,
+        on_time: f32,
+        off_time: f32,
+        fade_in_time: f32,
+        fade_out_time: f32,
+        n: Option<i32>,
+    ) -> Result<(), TimingError> {
+        self.blinker(on_time, off_time, fade_in_time, fade_out_time, n)
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        if paused {
+            PWMLED::pause(self)
+        } else {
+            PWMLED::resume(self)
+        }
+    }
+}
+
+/// A collection of LEDs which can be controlled together or individually.
+/// Items may be plain LEDs, PWM LEDs or other boards, and may be given names.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut board = LEDBoard::new(&[5, 6, 13]);
+/// board.add_named("status", LED::new(19));
+/// board.on();
+/// board[0].off();
+/// board["status"].toggle();
+/// println!("{:?}", board.value());
+/// ```
+pub struct LEDBoard {
+    items: Vec<(Option<String>, Box<dyn BoardItem>)>,
+}
+
+impl LEDBoard {
+    /// Returns an LEDBoard with an `LED` on each of the given pins
+    /// * `pins` - The GPIO pins the LEDs are attached to
+    pub fn new(pins: &[u8]) -> LEDBoard {
+        let mut board = LEDBoard::empty();
+        for &pin in pins {
+            board.add(LED::new(pin));
+        }
+        board
+    }
+
+    /// Returns an LEDBoard with an `LED` on each of the given pins of a GPIO expander or
+    /// another `PinFactory`
+    /// * `factory` - The provider of the pins, e.g. an `MCP23017`
+    /// * `pins` - The numbers of the pins on the factory
+    pub fn with_factory<F: PinFactory>(factory: &F, pins: &[u8]) -> LEDBoard {
+        let mut board = LEDBoard::empty();
+        for &pin in pins {
+            board.add(LED::with_factory(factory, pin));
+        }
+        board
+    }
+
+    /// Returns an LEDBoard with a `PWMLED` on each of the given pins, allowing brightness control and pulsing
+    /// * `pins` - The GPIO pins the LEDs are attached to
+    pub fn new_pwm(pins: &[u8]) -> LEDBoard {
+        let mut board = LEDBoard::empty();
+        for &pin in pins {
+            board.add(PWMLED::new(pin));
+        }
+        board
+    }
+
+    /// Returns an LEDBoard with a named `LED` on each of the given pins
+    /// * `leds` - Pairs of name and GPIO pin
+    pub fn new_named(leds: &[(&str, u8)]) -> LEDBoard {
+        let mut board = LEDBoard::empty();
+        for &(name, pin) in leds {
+            board.add_named(name, LED::new(pin));
+        }
+        board
+    }
+
+    /// Returns an LEDBoard without any items
+    pub fn empty() -> LEDBoard {
+        LEDBoard { items: Vec::new() }
+    }
+
+    /// Append an item (an LED or another board) to the board.
+    pub fn add<T: BoardItem + 'static>(&mut self, item: T) {
+        self.items.push((None, Box::new(item)));
+    }
+
+    /// Append a named item (an LED or another board) to the board.
+    pub fn add_named<T: BoardItem + 'static>(&mut self, name: &str, item: T) {
+        self.items.push((Some(name.to_string()), Box::new(item)));
+    }
+
+    /// The item at the given position, or None if there is no such item.
+    pub fn get(&mut self, index: usize) -> Option<&mut dyn BoardItem> {
+        match self.items.get_mut(index) {
+            Some((_, item)) => Some(item.as_mut()),
+            None => None,
+        }
+    }
+
+    /// The item with the given name, or None if there is no such item.
+    pub fn get_named(&mut self, name: &str) -> Option<&mut dyn BoardItem> {
+        match self
+            .items
+            .iter_mut()
+            .find(|(n, _)| n.as_deref() == Some(name))
+        {
+            Some((_, item)) => Some(item.as_mut()),
+            None => None,
+        }
+    }
+
+    /// The names of the items, in order. Unnamed items are None.
+    pub fn names(&self) -> Vec<Option<&str>> {
+        self.items.iter().map(|(name, _)| name.as_deref()).collect()
+    }
+
+    /// Number of items directly on this board (a nested board counts once).
+    pub fn item_count(&self) -> usize {
+        self.items.len()
+    }
+
+    /// The value of every LED on the board, in order, with nested boards flattened.
+    pub fn value(&self) -> Vec<f64> {
+        Component::values(self)
+    }
+
+    /// Returns ``True`` if any LED on the board is lit.
+    pub fn is_active(&self) -> bool {
+        Component::is_active(self)
+    }
+
+    /// Turn all the LEDs on.
+    pub fn on(&mut self) {
+        OutputComponent::on(self)
+    }
+
+    /// Turn all the LEDs off.
+    pub fn off(&mut self) {
+        OutputComponent::off(self)
+    }
+
+    /// Reverse the state of all the LEDs.
+    pub fn toggle(&mut self) {
+        OutputComponent::toggle(self)
+    }
+
+    /// Set the state of each item; `true` turns it on and `false` turns it off.
+    /// Extra values are ignored and items without a value are left unchanged.
+    pub fn set_value(&mut self, values: &[bool]) {
+        for ((_, item), &value) in self.items.iter_mut().zip(values) {
+            if value {
+                item.on()
+            } else {
+                item.off()
+            }
+        }
+    }
+
+    /// Make every LED fade in and out repeatedly. Requires PWM LEDs.
+    /// * `fade_in_time` - Number of seconds to spend fading in
+    /// * `fade_out_time` - Number of seconds to spend fading out
+    /// * `n` - Number of times to pulse. If this is None, pulse indefinitely.
+    ///
+    /// Returns an error if a time is negative, NaN or infinite.
+    pub fn pulse(
+        &mut self,
+        fade_in_time: f32,
+        fade_out_time: f32,
+        n: Option<i32>,
+    ) -> Result<(), TimingError> {
+        self.blink(0.0, 0.0, fade_in_time, fade_out_time, n)
+    }
+}
+
+impl BoardItem for LEDBoard {
+    fn set_brightness(&mut self, value: f64) {
+        for (_, item) in self.items.iter_mut() {
+            item.set_brightness(value)
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.items.iter().map(|(_, item)| item.len()).sum()
+    }
+
+    fn blink(
+        &mut self,
+        on_time: f32,
+        off_time: f32,
+        fade_in_time: f32,
+        fade_out_time: f32,
+        n: Option<i32>,
+    ) -> Result<(), TimingError> {
+        for (_, item) in self.items.iter_mut() {
+            item.blink(on_time, off_time, fade_in_time, fade_out_time, n)?;
+        }
+        Ok(())
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        for (_, item) in self.items.iter_mut() {
+            item.set_paused(paused)
+        }
+    }
+}
+
+impl Component for LEDBoard {
+    fn values(&self) -> Vec<f64> {
+        self.items
+            .iter()
+            .flat_map(|(_, item)| item.values())
+            .collect()
+    }
+
+    fn is_active(&self) -> bool {
+        self.items.iter().any(|(_, item)| item.is_active())
+    }
+
+    fn close(&mut self) {
+        OutputComponent::off(self)
+    }
+}
+
+impl OutputComponent for LEDBoard {
+    fn on(&mut self) {
+        for (_, item) in self.items.iter_mut() {
+            item.on()
+        }
+    }
+
+    fn off(&mut self) {
+        for (_, item) in self.items.iter_mut() {
+            item.off()
+        }
+    }
+
+    fn toggle(&mut self) {
+        for (_, item) in self.items.iter_mut() {
+            item.toggle()
+        }
+    }
+}
+
+impl Index<usize> for LEDBoard {
+    type Output = dyn BoardItem;
+
+    fn index(&self, index: usize) -> &(dyn BoardItem + 'static) {
+        self.items[index].1.as_ref()
+    }
+}
+
+impl IndexMut<usize> for LEDBoard {
+    fn index_mut(&mut self, index: usize) -> &mut (dyn BoardItem + 'static) {
+        self.items[index].1.as_mut()
+    }
+}
+
+impl Index<&str> for LEDBoard {
+    type Output = dyn BoardItem;
+
+    fn index(&self, name: &str) -> &(dyn BoardItem + 'static) {
+        match self.items.iter().find(|(n, _)| n.as_deref() == Some(name)) {
+            Some((_, item)) => item.as_ref(),
+            None => panic!("No item named {}", name),
+        }
+    }
+}
+
+impl IndexMut<&str> for LEDBoard {
+    fn index_mut(&mut self, name: &str) -> &mut (dyn BoardItem + 'static) {
+        match self
+            .items
+            .iter_mut()
+            .find(|(n, _)| n.as_deref() == Some(name))
+        {
+            Some((_, item)) => item.as_mut(),
+            None => panic!("No item named {}", name),
+        }
+    }
+}
+
+/// A row of LEDs forming a bar graph, lit in proportion to a value.
+///
+/// Positive values light the LEDs from the first pin, negative values from the last.
+/// With PWM LEDs the last lit LED shows the fractional part of the value.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut graph = LEDBarGraph::new_pwm(&[5, 6, 13, 19, 26]);
+/// // lights the first two LEDs fully and the third at half brightness
+/// graph.set_value(0.5);
+/// ```
+pub struct LEDBarGraph {
+    leds: Vec<Box<dyn BoardItem>>,
+    pwm: bool,
+    value: f64,
+}
+
+impl LEDBarGraph {
+    /// Returns an LEDBarGraph with an `LED` on each of the given pins
+    /// * `pins` - The GPIO pins the LEDs are attached to, from the bottom of the graph to the top
+    pub fn new(pins: &[u8]) -> LEDBarGraph {
+        LEDBarGraph {
+            leds: pins
+                .iter()
+                .map(|&pin| Box::new(LED::new(pin)) as Box<dyn BoardItem>)
+                .collect(),
+            pwm: false,
+            value: 0.0,
+        }
+    }
+
+    /// Returns an LEDBarGraph with an `LED` on each of the given pins of a GPIO expander,
+    /// a shift register or another `PinFactory`
+    /// * `factory` - The provider of the pins, e.g. a `ShiftRegister`
+    /// * `pins` - The numbers of the pins on the factory, from the bottom of the graph to the top
+    pub fn with_factory<F: PinFactory>(factory: &F, pins: &[u8]) -> LEDBarGraph {
+        LEDBarGraph {
+            leds: pins
+                .iter()
+                .map(|&pin| Box::new(LED::with_factory(factory, pin)) as Box<dyn BoardItem>)
+                .collect(),
+            pwm: false,
+            value: 0.0,
+        }
+    }
+
+    /// Returns an LEDBarGraph with a `PWMLED` on each of the given pins, so that the
+    /// topmost lit LED can be partially lit
+    /// * `pins` - The GPIO pins the LEDs are attached to, from the bottom of the graph to the top
+    pub fn new_pwm(pins: &[u8]) -> LEDBarGraph {
+        LEDBarGraph {
+            leds: pins
+                .iter()
+                .map(|&pin| Box::new(PWMLED::new(pin)) as Box<dyn BoardItem>)
+                .collect(),
+            pwm: true,
+            value: 0.0,
+        }
+    }
+
+    /// Number of LEDs in the graph.
+    pub fn len(&self) -> usize {
+        self.leds.len()
+    }
+
+    /// Returns ``True`` if the graph has no LEDs.
+    pub fn is_empty(&self) -> bool {
+        self.leds.is_empty()
+    }
+
+    /// The value last displayed, between -1.0 and 1.0.
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// Display a value between -1.0 and 1.0. Negative values light the graph from the other end.
+    pub fn set_value(&mut self, value: f64) {
+        if !(-1.0..=1.0).contains(&value) {
+            println!("Value must be between -1.0 and 1.0");
+            return;
+        }
+        self.value = value;
+        let count = self.leds.len();
+        let lit = value.abs() * count as f64;
+        for index in 0..count {
+            let mut level = (lit - index as f64).clamp(0.0, 1.0);
+            if !self.pwm {
+                level = level.floor();
+            }
+            let led = if value < 0.0 {
+                count - 1 - index
+            } else {
+                index
+            };
+            self.leds[led].set_brightness(level);
+        }
+    }
+
+    /// The number of lit LEDs, negative when lit from the other end. With PWM LEDs
+    /// this may be fractional.
+    pub fn lit_count(&self) -> f64 {
+        self.value * self.leds.len() as f64
+    }
+
+    /// Light the given number of LEDs; negative counts light the graph from the other end.
+    pub fn set_lit_count(&mut self, count: f64) {
+        if self.leds.is_empty() {
+            return;
+        }
+        self.set_value(count / self.leds.len() as f64)
+    }
+
+    /// Light every LED.
+    pub fn on(&mut self) {
+        self.set_value(1.0)
+    }
+
+    /// Turn every LED off.
+    pub fn off(&mut self) {
+        self.set_value(0.0)
+    }
+
+    /// The LED at the given position, or None if there is no such LED.
+    pub fn get(&mut self, index: usize) -> Option<&mut dyn BoardItem> {
+        match self.leds.get_mut(index) {
+            Some(led) => Some(led.as_mut()),
+            None => None,
+        }
+    }
+}
+
+/// Three LEDs in a red, amber and green traffic light arrangement.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut lights = TrafficLights::new(2, 3, 4);
+/// lights.green.on();
+/// lights.amber.blink(0.5, 0.5).unwrap();
+/// ```
+#[derive(CompositeDevice, CompositeOutputDevice)]
+pub struct TrafficLights {
+    /// The red LED
+    pub red: LED,
+    /// The amber LED
+    pub amber: LED,
+    /// The green LED
+    pub green: LED,
+}
+
+impl TrafficLights {
+    /// Returns TrafficLights with the LEDs on the given pins
+    /// * `red` - The GPIO pin of the red LED
+    /// * `amber` - The GPIO pin of the amber LED
+    /// * `green` - The GPIO pin of the green LED
+    pub fn new(red: u8, amber: u8, green: u8) -> TrafficLights {
+        TrafficLights {
+            red: LED::new(red),
+            amber: LED::new(amber),
+            green: LED::new(green),
+        }
+    }
+
+    /// Returns TrafficLights for the Low Voltage Labs PI-TRAFFIC pHAT (red 9, amber 10, green 11)
+    pub fn pi_traffic() -> TrafficLights {
+        TrafficLights::new(9, 10, 11)
+    }
+
+    /// The states of the red, amber and green LEDs.
+    pub fn value(&self) -> (bool, bool, bool) {
+        (self.red.value(), self.amber.value(), self.green.value())
+    }
+
+    /// Set the states of the red, amber and green LEDs.
+    pub fn set_value(&mut self, red: bool, amber: bool, green: bool) {
+        for (led, state) in [
+            (&mut self.red, red),
+            (&mut self.amber, amber),
+            (&mut self.green, green),
+        ] {
+            if state {
+                led.on()
+            } else {
+                led.off()
+            }
+        }
+    }
+
+    /// Returns ``True`` if any of the LEDs is lit.
+    pub fn is_active(&self) -> bool {
+        self.red.is_active() || self.amber.is_active() || self.green.is_active()
+    }
+
+    /// Turn all the LEDs on.
+    pub fn on(&mut self) {
+        OutputComponent::on(self)
+    }
+
+    /// Turn all the LEDs off.
+    pub fn off(&mut self) {
+        OutputComponent::off(self)
+    }
+
+    /// Reverse the state of all the LEDs.
+    pub fn toggle(&mut self) {
+        OutputComponent::toggle(self)
+    }
+
+    /// Make all the LEDs turn on and off repeatedly in the background.
+    /// * `on_time` - Number of seconds on
+    /// * `off_time` - Number of seconds off
+    /// * `n` - Number of times to blink. If this is None, blink indefinitely.
+    ///
+    /// Returns an error if a time is negative, NaN or infinite.
+    pub fn blink(
+        &mut self,
+        on_time: f32,
+        off_time: f32,
+        n: Option<i32>,
+    ) -> Result<(), TimingError> {
+        BoardItem::blink(self, on_time, off_time, 0.0, 0.0, n)
+    }
+
+    fn leds(&mut self) -> [&mut LED; 3] {
+        [&mut self.red, &mut self.amber, &mut self.green]
+    }
+}
+
+impl BoardItem for TrafficLights {
+    fn set_brightness(&mut self, value: f64) {
+        for led in self.leds() {
+            led.set_brightness(value)
+        }
+    }
+
+    fn len(&self) -> usize {
+        3
+    }
+
+    fn blink(
+        &mut self,
+        on_time: f32,
+        off_time: f32,
+        fade_in_time: f32,
+        fade_out_time: f32,
+        n: Option<i32>,
+    ) -> Result<(), TimingError> {
+        for led in self.leds() {
+            BoardItem::blink(led, on_time, off_time, fade_in_time, fade_out_time, n)?;
+        }
+        Ok(())
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        for led in self.leds() {
+            BoardItem::set_paused(led, paused)
+        }
+    }
+}
+
+/// The Ryanteck Traffic HAT: traffic lights on pins 24, 23 and 22, a buzzer on pin 5
+/// and a button on pin 25.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut hat = TrafficHat::new();
+/// hat.button.wait_for_press(None);
+/// hat.lights.red.on();
+/// hat.buzzer.beep(0.2, 0.2).unwrap();
+/// ```
+pub struct TrafficHat {
+    /// The traffic lights
+    pub lights: TrafficLights,
+    /// The buzzer
+    pub buzzer: Buzzer,
+    /// The button
+    pub button: Button,
+}
+
+impl TrafficHat {
+    /// Returns a TrafficHat using the pins of the Ryanteck Traffic HAT
+    pub fn new() -> TrafficHat {
+        TrafficHat {
+            lights: TrafficLights::new(24, 23, 22),
+            buzzer: Buzzer::new(5),
+            button: Button::new(25),
+        }
+    }
+
+    /// Turn the lights and the buzzer off.
+    pub fn off(&mut self) {
+        self.lights.off();
+        self.buzzer.off();
+    }
+}
+
+impl Default for TrafficHat {
+    fn default() -> Self {
+        TrafficHat::new()
+    }
+}
+
+/// The Pi Supply FishDish: traffic lights on pins 9, 22 and 4, a buzzer on pin 8 and a
+/// button on pin 7.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut fish = FishDish::new();
+/// fish.button.wait_for_press(None);
+/// fish.lights.green.on();
+/// fish.buzzer.beep(0.1, 0.1).unwrap();
+/// ```
+pub struct FishDish {
+    /// The traffic lights
+    pub lights: TrafficLights,
+    /// The buzzer
+    pub buzzer: Buzzer,
+    /// The button
+    pub button: Button,
+}
+
+impl FishDish {
+    /// Returns a FishDish using the pins of the board
+    pub fn new() -> FishDish {
+        FishDish {
+            lights: TrafficLights::new(9, 22, 4),
+            buzzer: Buzzer::new(8),
+            // the button pulls the pin high, against an external pull-down resistor
+            button: Button::new_with_pulldown(7),
+        }
+    }
+
+    /// Turn the lights and the buzzer off.
+    pub fn off(&mut self) {
+        self.lights.off();
+        self.buzzer.off();
+    }
+}
+
+impl Default for FishDish {
+    fn default() -> Self {
+        FishDish::new()
+    }
+}
+
+/// The four LEDs of an Explorer HAT, named by their colour.
+#[derive(CompositeDevice, CompositeOutputDevice)]
+pub struct ExplorerLights {
+    /// The blue LED, on pin 4
+    pub blue: LED,
+    /// The yellow LED, on pin 17
+    pub yellow: LED,
+    /// The red LED, on pin 27
+    pub red: LED,
+    /// The green LED, on pin 5
+    pub green: LED,
+}
+
+/// The four sinking outputs of an Explorer HAT, which switch the load to ground when on.
+#[derive(CompositeDevice, CompositeOutputDevice)]
+pub struct ExplorerOutputs {
+    /// Output 1, on pin 6
+    pub one: DigitalOutputDevice,
+    /// Output 2, on pin 12
+    pub two: DigitalOutputDevice,
+    /// Output 3, on pin 13
+    pub three: DigitalOutputDevice,
+    /// Output 4, on pin 16
+    pub four: DigitalOutputDevice,
+}
+
+/// The four 5V tolerant inputs of an Explorer HAT.
+#[derive(CompositeDevice)]
+pub struct ExplorerInputs {
+    /// Input 1, on pin 23
+    pub one: DigitalInputDevice,
+    /// Input 2, on pin 22
+    pub two: DigitalInputDevice,
+    /// Input 3, on pin 24
+    pub three: DigitalInputDevice,
+    /// Input 4, on pin 25
+    pub four: DigitalInputDevice,
+}
+
+/// The two motor channels of an Explorer HAT Pro.
+#[derive(CompositeDevice)]
+pub struct ExplorerMotors {
+    /// Motor 1, on pins 19 and 20
+    pub one: Motor,
+    /// Motor 2, on pins 21 and 26
+    pub two: Motor,
+}
+
+/// The four analog inputs of an Explorer HAT Pro, on an ADS1015 reading 0V to 5V.
+#[derive(CompositeDevice)]
+pub struct ExplorerAnalog {
+    /// Analog input 1
+    pub one: ADSChannel,
+    /// Analog input 2
+    pub two: ADSChannel,
+    /// Analog input 3
+    pub three: ADSChannel,
+    /// Analog input 4
+    pub four: ADSChannel,
+}
+
+/// The Pimoroni Explorer HAT Pro: four LEDs, four outputs, four inputs, two motor
+/// channels, four analog inputs and eight capacitive touch pads.
+///
+/// The parts are named as on the board, so each connector is a field, e.g.
+/// `hat.output.two`, and can also be found by name with `child`. The touch pads are pads 0
+/// to 7 of a CAP1208: pads 0 to 3 are the pads numbered 1 to 4, and pads 4 to 7 the
+/// crocodile clip pads numbered 5 to 8.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut hat = ExplorerHat::new();
+/// hat.light.red.on();
+/// hat.motor.one.forward();
+/// hat.touch.when_touched(0, || println!("pad 1"));
+/// println!("{:.2}V", hat.analog.one.voltage());
+/// println!("{}", hat.to_json());
+/// ```
+#[derive(CompositeDevice)]
+pub struct ExplorerHat {
+    /// The LEDs
+    pub light: ExplorerLights,
+    /// The sinking outputs
+    pub output: ExplorerOutputs,
+    /// The buffered inputs
+    pub input: ExplorerInputs,
+    /// The motor channels
+    pub motor: ExplorerMotors,
+    /// The analog inputs
+    pub analog: ExplorerAnalog,
+    /// The capacitive touch pads
+    pub touch: CAP1208,
+}
+
+impl ExplorerHat {
+    /// Returns an ExplorerHat using the pins and I2C devices of the Explorer HAT Pro
+    pub fn new() -> ExplorerHat {
+        let adc = ADS1015::new();
+        let analog = |channel: u8| {
+            let mut channel = adc.channel(channel);
+            channel.set_gain(ADSGain::V6_144);
+            channel
+        };
+        ExplorerHat {
+            light: ExplorerLights {
+                blue: LED::new(4),
+                yellow: LED::new(17),
+                red: LED::new(27),
+                green: LED::new(5),
+            },
+            output: ExplorerOutputs {
+                one: DigitalOutputDevice::new(6),
+                two: DigitalOutputDevice::new(12),
+                three: DigitalOutputDevice::new(13),
+                four: DigitalOutputDevice::new(16),
+            },
+            input: ExplorerInputs {
+                one: DigitalInputDevice::new(23),
+                two: DigitalInputDevice::new(22),
+                three: DigitalInputDevice::new(24),
+                four: DigitalInputDevice::new(25),
+            },
+            motor: ExplorerMotors {
+                one: Motor::new(19, 20),
+                two: Motor::new(21, 26),
+            },
+            analog: ExplorerAnalog {
+                one: analog(3),
+                two: analog(2),
+                three: analog(1),
+                four: analog(0),
+            },
+            touch: CAP1208::new(),
+        }
+    }
+
+    /// Turn the lights and outputs off and stop the motors.
+    pub fn off(&mut self) {
+        self.light.off();
+        self.output.off();
+        self.motor.one.stop();
+        self.motor.two.stop();
+    }
+}
+
+impl Default for ExplorerHat {
+    fn default() -> Self {
+        ExplorerHat::new()
+    }
+}
+
+/// The three relays of an Automation HAT, each with a normally open and a normally closed
+/// contact.
+#[derive(CompositeDevice, CompositeOutputDevice)]
+pub struct AutomationRelays {
+    /// Relay 1, on pin 13
+    pub one: DigitalOutputDevice,
+    /// Relay 2, on pin 19
+    pub two: DigitalOutputDevice,
+    /// Relay 3, on pin 16
+    pub three: DigitalOutputDevice,
+}
+
+/// The three sinking outputs of an Automation HAT, rated for 24V loads.
+#[derive(CompositeDevice, CompositeOutputDevice)]
+pub struct AutomationOutputs {
+    /// Output 1, on pin 5
+    pub one: DigitalOutputDevice,
+    /// Output 2, on pin 12
+    pub two: DigitalOutputDevice,
+    /// Output 3, on pin 6
+    pub three: DigitalOutputDevice,
+}
+
+/// The three buffered inputs of an Automation HAT, active at 3V to 24V.
+#[derive(CompositeDevice)]
+pub struct AutomationInputs {
+    /// Input 1, on pin 26
+    pub one: DigitalInputDeviceR,
+    /// Input 2, on pin 20
+    pub two: DigitalInputDeviceR,
+    /// Input 3, on pin 21
+    pub three: DigitalInputDeviceR,
+}
+
+/// The analog inputs of an Automation HAT, on an ADS1015. Inputs 1 to 3 read up to
+/// 25.85V through a divider, and input 4 up to 3.3V.
+#[derive(CompositeDevice)]
+pub struct AutomationAnalog {
+    /// Analog input 1, up to 25.85V
+    pub one: ADSChannel,
+    /// Analog input 2, up to 25.85V
+    pub two: ADSChannel,
+    /// Analog input 3, up to 25.85V
+    pub three: ADSChannel,
+    /// Analog input 4, up to 3.3V
+    pub four: ADSChannel,
+}
+
+/// The Pimoroni Automation HAT: three relays, three sinking outputs, three buffered inputs
+/// and four analog inputs.
+///
+/// The parts are named as on the board, so each connector is a field, e.g.
+/// `hat.relay.one`, and can also be found by name with `child`. The indicator LEDs, driven
+/// by an SN3218, are left as the HAT sets them.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut hat = AutomationHat::new();
+/// if hat.input.one.is_active() {
+///     hat.relay.one.on();
+/// }
+/// println!("{:.1}V", hat.analog.one.voltage());
+/// ```
+#[derive(CompositeDevice)]
+pub struct AutomationHat {
+    /// The relays
+    pub relay: AutomationRelays,
+    /// The sinking outputs
+    pub output: AutomationOutputs,
+    /// The buffered inputs
+    pub input: AutomationInputs,
+    /// The analog inputs
+    pub analog: AutomationAnalog,
+}
+
+impl AutomationHat {
+    /// Returns an AutomationHat using the pins and ADC of the Automation HAT
+    pub fn new() -> AutomationHat {
+        let adc = ADS1015::new();
+        let analog = |channel: u8, max_voltage: f64| {
+            let mut channel = adc.channel(channel);
+            channel.set_gain(ADSGain::V4_096);
+            channel.set_divider(max_voltage / 3.3);
+            channel
+        };
+        AutomationHat {
+            relay: AutomationRelays {
+                one: DigitalOutputDevice::new(13),
+                two: DigitalOutputDevice::new(19),
+                three: DigitalOutputDevice::new(16),
+            },
+            output: AutomationOutputs {
+                one: DigitalOutputDevice::new(5),
+                two: DigitalOutputDevice::new(12),
+                three: DigitalOutputDevice::new(6),
+            },
+            // the inputs are driven by buffers, so the pins need no pull resistor
+            input: AutomationInputs {
+                one: DigitalInputDeviceR::new(26, Bias::Floating),
+                two: DigitalInputDeviceR::new(20, Bias::Floating),
+                three: DigitalInputDeviceR::new(21, Bias::Floating),
+            },
+            analog: AutomationAnalog {
+                one: analog(0, 25.85),
+                two: analog(1, 25.85),
+                three: analog(2, 25.85),
+                four: analog(3, 3.3),
+            },
+        }
+    }
+
+    /// Turn the relays and outputs off.
+    pub fn off(&mut self) {
+        self.relay.off();
+        self.output.off();
+    }
+}
+
+impl Default for AutomationHat {
+    fn default() -> Self {
+        AutomationHat::new()
+    }
+}
+
+/// An LEDBoard of named LEDs, which are PWM LEDs if `pwm` is set.
+fn named_leds(leds: &[(&str, u8)], pwm: bool) -> LEDBoard {
+    let mut board = LEDBoard::empty();
+    for &(name, pin) in leds {
+        if pwm {
+            board.add_named(name, PWMLED::new(pin))
+        } else {
+            board.add_named(name, LED::new(pin))
+        }
+    }
+    board
+}
+
+/// The Ryanteck SnowPi: a snowman with LEDs for arms, eyes and a nose.
+///
+/// The arms are a board named "left" and "right", each with LEDs named "top", "middle" and
+/// "bottom". The eyes are named "left" and "right".
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut snowman = SnowPi::new_pwm();
+/// snowman.eyes.on();
+/// snowman.arms["left"].blink(0.5, 0.5, 0.0, 0.0, None).unwrap();
+/// snowman.nose.set_brightness(0.5);
+/// ```
+pub struct SnowPi {
+    /// The arms, named "left" and "right"
+    pub arms: LEDBoard,
+    /// The eyes, named "left" and "right"
+    pub eyes: LEDBoard,
+    /// The nose
+    pub nose: Box<dyn BoardItem>,
+}
+
+impl SnowPi {
+    /// Returns a SnowPi with plain LEDs
+    pub fn new() -> SnowPi {
+        SnowPi::build(false)
+    }
+
+    /// Returns a SnowPi with PWM LEDs, allowing brightness control and pulsing
+    pub fn new_pwm() -> SnowPi {
+        SnowPi::build(true)
+    }
+
+    fn build(pwm: bool) -> SnowPi {
+        let mut arms = LEDBoard::empty();
+        arms.add_named(
+            "left",
+            named_leds(&[("top", 17), ("middle", 18), ("bottom", 4)], pwm),
+        );
+        arms.add_named(
+            "right",
+            named_leds(&[("top", 7), ("middle", 8), ("bottom", 9)], pwm),
+        );
+        let nose: Box<dyn BoardItem> = if pwm {
+            Box::new(PWMLED::new(25))
+        } else {
+            Box::new(LED::new(25))
+        };
+        SnowPi {
+            arms,
+            eyes: named_leds(&[("left", 23), ("right", 24)], pwm),
+            nose,
+        }
+    }
+
+    /// The value of every LED: the left arm from the top, the right arm, the eyes and the nose.
+    pub fn value(&self) -> Vec<f64> {
+        Component::values(self)
+    }
+
+    /// Returns ``True`` if any LED is lit.
+    pub fn is_active(&self) -> bool {
+        Component::is_active(self)
+    }
+
+    /// Turn all the LEDs on.
+    pub fn on(&mut self) {
+        OutputComponent::on(self)
+    }
+
+    /// Turn all the LEDs off.
+    pub fn off(&mut self) {
+        OutputComponent::off(self)
+    }
+
+    /// Reverse the state of all the LEDs.
+    pub fn toggle(&mut self) {
+        OutputComponent::toggle(self)
+    }
+
+    /// Make all the LEDs turn on and off repeatedly in the background.
+    /// * `on_time` - Number of seconds on
+    /// * `off_time` - Number of seconds off
+    /// * `n` - Number of times to blink. If this is None, blink indefinitely.
+    ///
+    /// Returns an error if a time is negative, NaN or infinite.
+    pub fn blink(
+        &mut self,
+        on_time: f32,
+        off_time: f32,
+        n: Option<i32>,
+    ) -> Result<(), TimingError> {
+        BoardItem::blink(self, on_time, off_time, 0.0, 0.0, n)
+    }
+
+    /// Make all the LEDs fade in and out repeatedly. Requires `new_pwm`.
+    /// * `fade_in_time` - Number of seconds to spend fading in
+    /// * `fade_out_time` - Number of seconds to spend fading out
+    /// * `n` - Number of times to pulse. If this is None, pulse indefinitely.
+    ///
+    /// Returns an error if a time is negative, NaN or infinite.
+    pub fn pulse(
+        &mut self,
+        fade_in_time: f32,
+        fade_out_time: f32,
+        n: Option<i32>,
+    ) -> Result<(), TimingError> {
+        BoardItem::blink(self, 0.0, 0.0, fade_in_time, fade_out_time, n)
+    }
+
+    fn parts(&mut self) -> [&mut dyn BoardItem; 3] {
+        [&mut self.arms, &mut self.eyes, self.nose.as_mut()]
+    }
+}
+
+impl Default for SnowPi {
+    fn default() -> Self {
+        SnowPi::new()
+    }
+}
+
+impl BoardItem for SnowPi {
+    fn set_brightness(&mut self, value: f64) {
+        for part in self.parts().iter_mut() {
+            part.set_brightness(value)
+        }
+    }
+
+    fn len(&self) -> usize {
+        9
+    }
+
+    fn blink(
+        &mut self,
+        on_time: f32,
+        off_time: f32,
+        fade_in_time: f32,
+        fade_out_time: f32,
+        n: Option<i32>,
+    ) -> Result<(), TimingError> {
+        for part in self.parts().iter_mut() {
+            part.blink(on_time, off_time, fade_in_time, fade_out_time, n)?;
+        }
+        Ok(())
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        for part in self.parts().iter_mut() {
+            part.set_paused(paused)
+        }
+    }
+}
+
+impl Component for SnowPi {
+    fn values(&self) -> Vec<f64> {
+        let mut values = self.arms.value();
+        values.extend(self.eyes.value());
+        values.extend(self.nose.values());
+        values
+    }
+
+    fn is_active(&self) -> bool {
+        self.arms.is_active() || self.eyes.is_active() || self.nose.is_active()
+    }
+
+    fn close(&mut self) {
+        OutputComponent::off(self)
+    }
+}
+
+impl OutputComponent for SnowPi {
+    fn on(&mut self) {
+        for part in self.parts().iter_mut() {
+            part.on()
+        }
+    }
+
+    fn off(&mut self) {
+        for part in self.parts().iter_mut() {
+            part.off()
+        }
+    }
+
+    fn toggle(&mut self) {
+        for part in self.parts().iter_mut() {
+            part.toggle()
+        }
+    }
+}
+
+/// The GPIO pins of the ENER314 board, shared by every `Energenie` socket.
+struct EnergenieMaster {
+    mode: OutputDevice,
+    enable: OutputDevice,
+    signal: [OutputDevice; 4],
+}
+
+impl EnergenieMaster {
+    fn new() -> EnergenieMaster {
+        let mut master = EnergenieMaster {
+            mode: OutputDevice::new(24),
+            enable: OutputDevice::new(25),
+            signal: [
+                OutputDevice::new(17),
+                OutputDevice::new(22),
+                OutputDevice::new(23),
+                OutputDevice::new(27),
+            ],
+        };
+        // the mode pin selects ASK (on-off keying), which the sockets expect
+        master.mode.off();
+        master.enable.off();
+        master
+    }
+
+    fn transmit(&mut self, socket: u8, enable: bool) {
+        let mut code = 8 * u8::from(enable) + (8 - socket);
+        for pin in self.signal.iter_mut() {
+            if code & 1 == 1 {
+                pin.on()
+            } else {
+                pin.off()
+            }
+            code >>= 1;
+        }
+        // let the encoder settle, then key the transmitter long enough for the socket to hear it
+        thread::sleep(Duration::from_millis(100));
+        self.enable.on();
+        thread::sleep(Duration::from_millis(250));
+        self.enable.off();
+    }
+}
+
+static ENERGENIE_MASTER: Mutex<Weak<Mutex<EnergenieMaster>>> = Mutex::new(Weak::new());
+
+/// A socket switched by the Energenie Pi-mote (ENER314) RF transmitter board.
+///
+/// The sockets cannot report their state, so `value` returns the last state sent.
+/// Switching a socket takes about 350ms; all sockets share the transmitter and are
+/// switched one at a time.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut lamp = Energenie::new(1);
+/// let mut heater = Energenie::new(2);
+/// lamp.on();
+/// heater.off();
+/// ```
+pub struct Energenie {
+    socket: u8,
+    value: bool,
+    master: Arc<Mutex<EnergenieMaster>>,
+}
+
+impl Energenie {
+    /// Returns an Energenie socket, switched off.
+    /// * `socket` - Which socket to control, from 1 to 4
+    pub fn new(socket: u8) -> Energenie {
+        if !(1..=4).contains(&socket) {
+            panic!("socket must be between 1 and 4, not {}", socket);
+        }
+        let master = {
+            let mut shared = ENERGENIE_MASTER.lock().unwrap();
+            match shared.upgrade() {
+                Some(master) => master,
+                None => {
+                    let master = Arc::new(Mutex::new(EnergenieMaster::new()));
+                    *shared = Arc::downgrade(&master);
+                    master
+                }
+            }
+        };
+        let mut energenie = Energenie {
+            socket,
+            value: false,
+            master,
+        };
+        energenie.off();
+        energenie
+    }
+
+    /// The socket number, from 1 to 4.
+    pub fn socket(&self) -> u8 {
+        self.socket
+    }
+
+    /// Returns ``True`` if the socket was last switched on.
+    pub fn value(&self) -> bool {
+        self.value
+    }
+
+    /// Returns ``True`` if the socket was last switched on.
+    pub fn is_active(&self) -> bool {
+        self.value
+    }
+
+    /// Switch the socket on or off.
+    pub fn set_value(&mut self, value: bool) {
+        self.master.lock().unwrap().transmit(self.socket, value);
+        self.value = value;
+    }
+
+    /// Switch the socket on.
+    pub fn on(&mut self) {
+        self.set_value(true)
+    }
+
+    /// Switch the socket off.
+    pub fn off(&mut self) {
+        self.set_value(false)
+    }
+
+    /// Reverse the state of the socket.
+    pub fn toggle(&mut self) {
+        let value = !self.value;
+        self.set_value(value)
+    }
+}
+/// Segments of a character on a seven-segment display, bit 0 is segment a and bit 6 is segment g.
+fn font(c: char) -> Option<u8> {
+    let segments = match c {
+        ' ' => 0x00,
+        '0' | 'O' | 'D' => 0x3f,
+        '1' | 'I' => 0x06,
+        '2' | 'Z' => 0x5b,
+        '3' => 0x4f,
+        '4' => 0x66,
+        '5' | 'S' | 's' => 0x6d,
+        '6' => 0x7d,
+        '7' => 0x07,
+        '8' | 'B' => 0x7f,
+        '9' | 'g' => 0x6f,
+        'A' | 'a' => 0x77,
+        'b' => 0x7c,
+        'C' => 0x39,
+        'c' => 0x58,
+        'd' => 0x5e,
+        'E' | 'e' => 0x79,
+        'F' | 'f' => 0x71,
+        'G' => 0x3d,
+        'H' => 0x76,
+        'h' => 0x74,
+        'i' => 0x04,
+        'J' | 'j' => 0x1e,
+        'L' | 'l' => 0x38,
+        'N' | 'n' => 0x54,
+        'o' => 0x5c,
+        'P' | 'p' => 0x73,
+        'Q' | 'q' => 0x67,
+        'R' | 'r' => 0x50,
+        'T' | 't' => 0x78,
+        'U' => 0x3e,
+        'u' | 'v' | 'V' => 0x1c,
+        'Y' | 'y' => 0x6e,
+        '-' => 0x40,
+        '_' => 0x08,
+        '=' => 0x48,
+        '\'' => 0x02,
+        '"' => 0x22,
+        '°' => 0x63,
+        _ => return None,
+    };
+    Some(segments)
+}
+
+/// The character and decimal point of each digit showing `text`. A `.` lights the decimal
+/// point of the preceding character and does not take a digit of its own.
+pub(crate) fn split_digits(text: &str) -> Vec<(char, bool)> {
+    let mut digits: Vec<(char, bool)> = Vec::new();
+    let mut previous_dot = true;
+    for c in text.chars() {
+        if c == '.' {
+            match digits.last_mut() {
+                Some(last) if !previous_dot => last.1 = true,
+                _ => digits.push((' ', true)),
+            }
+            previous_dot = true;
+        } else {
+            digits.push((c, false));
+            previous_dot = false;
+        }
+    }
+    digits
+}
+
+/// The segments of each digit showing `text`, with the decimal point in bit 7, or the
+/// first character that cannot be shown.
+pub(crate) fn text_segments(custom: &[(char, u8)], text: &str) -> Result<Vec<u8>, char> {
+    split_digits(text)
+        .into_iter()
+        .map(|(c, dot)| match char_segments(custom, c) {
+            Some(segments) => Ok(segments | if dot { 0x80 } else { 0 }),
+            None => Err(c),
+        })
+        .collect()
+}
+
+pub(crate) fn char_segments(custom: &[(char, u8)], c: char) -> Option<u8> {
+    match custom.iter().find(|(custom, _)| *custom == c) {
+        Some((_, segments)) => Some(*segments),
+        None => font(c),
+    }
+}
+
+fn write_segments(pins: &mut [OutputDevice], segments: u8) {
+    for (bit, pin) in pins.iter_mut().enumerate() {
+        if segments & (1 << bit) != 0 {
+            pin.on()
+        } else {
+            pin.off()
+        }
+    }
+}
+
+fn segment_outputs(pins: &[u8]) -> Vec<OutputDevice> {
+    if pins.len() != 7 && pins.len() != 8 {
+        panic!(
+            "A seven-segment display needs 7 or 8 pins (a-g and the decimal point), {} given",
+            pins.len()
+        );
+    }
+    pins.iter().map(|&pin| OutputDevice::new(pin)).collect()
+}
+
+/// A seven-segment display driven directly from GPIO pins, one pin per segment.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// // segments a to g, then the decimal point
+/// let mut display = LEDCharDisplay::new(&[17, 4, 24, 22, 27, 23, 5, 6]);
+/// display.set_value('7');
+/// display.set_decimal_point(true);
+/// ```
+pub struct LEDCharDisplay {
+    segments: Vec<OutputDevice>,
+    custom: Vec<(char, u8)>,
+    value: char,
+    decimal_point: bool,
+}
+
+impl LEDCharDisplay {
+    /// Returns an LEDCharDisplay for a common cathode display
+    /// * `pins` - The GPIO pins of segments a to g, optionally followed by the decimal point
+    pub fn new(pins: &[u8]) -> LEDCharDisplay {
+        let mut display = LEDCharDisplay {
+            segments: segment_outputs(pins),
+            custom: Vec::new(),
+            value: ' ',
+            decimal_point: false,
+        };
+        display.refresh();
+        display
+    }
+
+    /// Set to ``False`` for a common anode display, where a segment lights when its pin is low.
+    pub fn set_active_high(&mut self, value: bool) {
+        for segment in self.segments.iter_mut() {
+            segment.set_active_high(value)
+        }
+        self.refresh();
+    }
+
+    /// Define or replace the segments shown for a character.
+    /// * `c` - The character
+    /// * `segments` - Bit 0 is segment a, bit 6 is segment g
+    pub fn define_char(&mut self, c: char, segments: u8) {
+        self.custom.retain(|(custom, _)| *custom != c);
+        self.custom.push((c, segments & 0x7f));
+        self.refresh();
+    }
+
+    /// The character currently shown.
+    pub fn value(&self) -> char {
+        self.value
+    }
+
+    /// Show a character.
+    pub fn set_value(&mut self, c: char) {
+        if char_segments(&self.custom, c).is_none() {
+            println!(
+                "Character '{}' cannot be shown on a seven-segment display",
+                c
+            );
+            return;
+        }
+        self.value = c;
+        self.refresh();
+    }
+
+    /// Returns ``True`` if the decimal point is lit.
+    pub fn decimal_point(&self) -> bool {
+        self.decimal_point
+    }
+
+    /// Light or clear the decimal point. Has no effect on displays configured without one.
+    pub fn set_decimal_point(&mut self, lit: bool) {
+        self.decimal_point = lit;
+        self.refresh();
+    }
+
+    /// Returns ``True`` if any segment is lit.
+    pub fn is_active(&self) -> bool {
+        self.segments.iter().any(|segment| segment.is_active())
+    }
+
+    /// Blank the display.
+    pub fn off(&mut self) {
+        self.value = ' ';
+        self.decimal_point = false;
+        self.refresh();
+    }
+
+    fn refresh(&mut self) {
+        let mut segments = char_segments(&self.custom, self.value).unwrap_or(0);
+        if self.decimal_point {
+            segments |= 0x80;
+        }
+        write_segments(&mut self.segments, segments);
+    }
+}
+
+struct Multiplexer {
+    segments: Vec<OutputDevice>,
+    digits: Vec<OutputDevice>,
+    frame: Vec<u8>,
+    refresh_rate: f64,
+}
+
+impl Multiplexer {
+    fn blank(&mut self) {
+        for digit in self.digits.iter_mut() {
+            digit.off()
+        }
+    }
+}
+
+/// A multi-digit seven-segment display sharing its segment pins between digits.
+/// A background thread lights one digit at a time, fast enough for all of them to appear lit.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut display = LEDMultiCharDisplay::new(&[17, 4, 24, 22, 27, 23, 5, 6], &[26, 19, 13, 12]);
+/// display.set_value("12.34");
+/// ```
+pub struct LEDMultiCharDisplay {
+    multiplexer: Arc<Mutex<Multiplexer>>,
+    custom: Vec<(char, u8)>,
+    value: String,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl LEDMultiCharDisplay {
+    /// Returns an LEDMultiCharDisplay refreshing 100 times per second
+    /// * `segment_pins` - The GPIO pins of segments a to g, optionally followed by the decimal point
+    /// * `digit_pins` - The GPIO pins selecting each digit, from left to right. A digit is selected when its pin is high.
+    pub fn new(segment_pins: &[u8], digit_pins: &[u8]) -> LEDMultiCharDisplay {
+        let multiplexer = Arc::new(Mutex::new(Multiplexer {
+            segments: segment_outputs(segment_pins),
+            digits: digit_pins
+                .iter()
+                .map(|&pin| OutputDevice::new(pin))
+                .collect(),
+            frame: vec![0; digit_pins.len()],
+            refresh_rate: 100.0,
+        }));
+        let running = Arc::new(AtomicBool::new(true));
+
+        let shared = Arc::clone(&multiplexer);
+        let still_running = Arc::clone(&running);
+        let handle = thread::spawn(move || {
+            let mut digit = 0;
+            while still_running.load(Ordering::SeqCst) {
+                let period = {
+                    let mut guard = shared.lock().unwrap();
+                    let multiplexer = &mut *guard;
+                    multiplexer.blank();
+                    if multiplexer.digits.is_empty() {
+                        Duration::from_millis(100)
+                    } else {
+                        digit %= multiplexer.digits.len();
+                        write_segments(&mut multiplexer.segments, multiplexer.frame[digit]);
+                        multiplexer.digits[digit].on();
+                        // at most an hour, so a vanishingly small rate cannot overflow
+                        let period =
+                            1.0 / (multiplexer.refresh_rate * multiplexer.digits.len() as f64);
+                        Duration::from_secs_f64(period.min(3600.0))
+                    }
+                };
+                digit += 1;
+                sleep_while_running(&still_running, period);
+            }
+            shared.lock().unwrap().blank();
+        });
+
+        LEDMultiCharDisplay {
+            multiplexer,
+            custom: Vec::new(),
+            value: String::new(),
+            running,
+            handle: Some(handle),
+        }
+    }
+
+    /// Number of digits of the display.
+    pub fn len(&self) -> usize {
+        self.multiplexer.lock().unwrap().digits.len()
+    }
+
+    /// Returns ``True`` if the display has no digits.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The text currently shown.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Show text on the display, aligned to the right. A `.` lights the decimal point
+    /// of the preceding character and does not take a digit of its own.
+    pub fn set_value(&mut self, text: &str) {
+        let frame = match text_segments(&self.custom, text) {
+            Ok(frame) => frame,
+            Err(c) => {
+                println!(
+                    "Character '{}' cannot be shown on a seven-segment display",
+                    c
+                );
+                return;
+            }
+        };
+
+        let mut multiplexer = self.multiplexer.lock().unwrap();
+        let digits = multiplexer.digits.len();
+        if frame.len() > digits {
+            println!("Text '{}' needs more than {} digits", text, digits);
+            return;
+        }
+        let mut padded = vec![0; digits - frame.len()];
+        padded.extend(frame);
+        multiplexer.frame = padded;
+        self.value = text.to_string();
+    }
+
+    /// Define or replace the segments shown for a character.
+    /// * `c` - The character
+    /// * `segments` - Bit 0 is segment a, bit 6 is segment g
+    pub fn define_char(&mut self, c: char, segments: u8) {
+        self.custom.retain(|(custom, _)| *custom != c);
+        self.custom.push((c, segments & 0x7f));
+    }
+
+    /// Number of times per second every digit is lit.
+    pub fn refresh_rate(&self) -> f64 {
+        self.multiplexer.lock().unwrap().refresh_rate
+    }
+
+    /// Set the number of times per second every digit is lit. Rates below 50 are likely to flicker.
+    pub fn set_refresh_rate(&mut self, refresh_rate: f64) {
+        if !(refresh_rate.is_finite() && refresh_rate > 0.0) {
+            println!("Refresh rate must be a finite number greater than 0");
+            return;
+        }
+        self.multiplexer.lock().unwrap().refresh_rate = refresh_rate;
+    }
+
+    /// Set to ``False`` for a common anode display, where a segment lights when its pin is low.
+    pub fn set_segments_active_high(&mut self, value: bool) {
+        for segment in self.multiplexer.lock().unwrap().segments.iter_mut() {
+            segment.set_active_high(value)
+        }
+    }
+
+    /// Set to ``False`` when a digit is selected by driving its pin low.
+    pub fn set_digits_active_high(&mut self, value: bool) {
+        let mut multiplexer = self.multiplexer.lock().unwrap();
+        for digit in multiplexer.digits.iter_mut() {
+            digit.set_active_high(value)
+        }
+        multiplexer.blank();
+    }
+
+    /// Blank the display.
+    pub fn off(&mut self) {
+        self.set_value("")
+    }
+
+    /// Stop the refresh thread and blank the display.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+impl Drop for LEDMultiCharDisplay {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+type ButtonCallback = Arc<Mutex<Option<Box<dyn FnMut(usize) + Send>>>>;
+
+/// A collection of buttons, such as a control panel, with callbacks that report which button changed.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut panel = ButtonBoard::new_named(&[("start", 17), ("stop", 27)]);
+/// panel.when_pressed(|index| println!("button {} pressed", index));
+/// println!("{:?}", panel.value());
+/// ```
+pub struct ButtonBoard {
+    buttons: Vec<(Option<String>, Button)>,
+    pressed: ButtonCallback,
+    released: ButtonCallback,
+    listening: bool,
+}
+
+impl ButtonBoard {
+    /// Returns a ButtonBoard with a `Button` on each of the given pins
+    /// * `pins` - The GPIO pins the buttons are attached to
+    pub fn new(pins: &[u8]) -> ButtonBoard {
+        ButtonBoard::from_buttons(pins.iter().map(|&pin| (None, Button::new(pin))).collect())
+    }
+
+    /// Returns a ButtonBoard with a named `Button` on each of the given pins
+    /// * `buttons` - Pairs of name and GPIO pin
+    pub fn new_named(buttons: &[(&str, u8)]) -> ButtonBoard {
+        ButtonBoard::from_buttons(
+            buttons
+                .iter()
+                .map(|&(name, pin)| (Some(name.to_string()), Button::new(pin)))
+                .collect(),
+        )
+    }
+
+    fn from_buttons(buttons: Vec<(Option<String>, Button)>) -> ButtonBoard {
+        ButtonBoard {
+            buttons,
+            pressed: Arc::default(),
+            released: Arc::default(),
+            listening: false,
+        }
+    }
+
+    /// Number of buttons on the board.
+    pub fn len(&self) -> usize {
+        self.buttons.len()
+    }
+
+    /// Returns ``True`` if the board has no buttons.
+    pub fn is_empty(&self) -> bool {
+        self.buttons.is_empty()
+    }
+
+    /// Whether each button is pressed, in order.
+    pub fn value(&self) -> Vec<bool> {
+        self.buttons
+            .iter()
+            .map(|(_, button)| button.is_pressed())
+            .collect()
+    }
+
+    /// Returns ``True`` if any button is pressed.
+    pub fn is_pressed(&self) -> bool {
+        self.buttons.iter().any(|(_, button)| button.is_pressed())
+    }
+
+    /// Returns ``True`` if every button is pressed.
+    pub fn all_pressed(&self) -> bool {
+        !self.buttons.is_empty() && self.buttons.iter().all(|(_, button)| button.is_pressed())
+    }
+
+    /// The positions of the buttons that are pressed.
+    pub fn pressed(&self) -> Vec<usize> {
+        self.buttons
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, button))| button.is_pressed())
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// The button at the given position, or None if there is no such button.
+    pub fn get(&mut self, index: usize) -> Option<&mut Button> {
+        self.buttons.get_mut(index).map(|(_, button)| button)
+    }
+
+    /// The button with the given name, or None if there is no such button.
+    pub fn get_named(&mut self, name: &str) -> Option<&mut Button> {
+        self.buttons
+            .iter_mut()
+            .find(|(n, _)| n.as_deref() == Some(name))
+            .map(|(_, button)| button)
+    }
+
+    /// The name of the button at the given position, if it has one.
+    pub fn name(&self, index: usize) -> Option<&str> {
+        self.buttons
+            .get(index)
+            .and_then(|(name, _)| name.as_deref())
+    }
+
+    /// Set the function called from a background thread when any button is pressed.
+    /// It receives the position of the button.
+    pub fn when_pressed<C>(&mut self, callback: C)
+    where
+        C: FnMut(usize) + Send + 'static,
+    {
+        *self.pressed.lock().unwrap() = Some(Box::new(callback));
+        self.listen();
+    }
+
+    /// Set the function called from a background thread when any button is released.
+    /// It receives the position of the button.
+    pub fn when_released<C>(&mut self, callback: C)
+    where
+        C: FnMut(usize) + Send + 'static,
+    {
+        *self.released.lock().unwrap() = Some(Box::new(callback));
+        self.listen();
+    }
+
+    fn listen(&mut self) {
+        if self.listening {
+            return;
+        }
+        self.listening = true;
+        for (index, (_, button)) in self.buttons.iter_mut().enumerate() {
+            let pressed = Arc::clone(&self.pressed);
+            button.when_pressed(move || {
+                if let Some(callback) = pressed.lock().unwrap().as_mut() {
+                    callback(index)
+                }
+            });
+            let released = Arc::clone(&self.released);
+            button.when_released(move || {
+                if let Some(callback) = released.lock().unwrap().as_mut() {
+                    callback(index)
+                }
+            });
+        }
+    }
+}
+
+impl Component for ButtonBoard {
+    fn values(&self) -> Vec<f64> {
+        self.value()
+            .iter()
+            .map(|&pressed| if pressed { 1.0 } else { 0.0 })
+            .collect()
+    }
+}
+
+impl Index<usize> for ButtonBoard {
+    type Output = Button;
+
+    fn index(&self, index: usize) -> &Button {
+        &self.buttons[index].1
+    }
+}
+
+impl IndexMut<usize> for ButtonBoard {
+    fn index_mut(&mut self, index: usize) -> &mut Button {
+        &mut self.buttons[index].1
+    }
+}
+
+/// Returns the labels, or `defaults` if none are given, checking that there are not too many.
+fn strip_labels(labels: &[&str], defaults: &[&str], device: &str) -> Vec<String> {
+    if labels.len() > defaults.len() {
+        panic!(
+            "{} has {} strips, {} labels given",
+            device,
+            defaults.len(),
+            labels.len()
+        );
+    }
+    let labels = if labels.is_empty() { defaults } else { labels };
+    labels.iter().map(|label| label.to_string()).collect()
+}
+
+/// The Pi Hut STATUS Zero: three strips, each with a red and a green LED, which can be labelled.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut status = StatusZero::new(&["kitchen", "garage"]);
+/// status["kitchen"]["green"].on();
+/// status["garage"].blink(0.5, 0.5, 0.0, 0.0, None).unwrap();
+/// ```
+pub struct StatusZero {
+    strips: Vec<(String, LEDBoard)>,
+}
+
+impl StatusZero {
+    /// Returns a StatusZero with a strip for each label, from the top. Without labels, all
+    /// three strips are used and named "one", "two" and "three".
+    /// * `labels` - Names of the strips, at most three
+    pub fn new(labels: &[&str]) -> StatusZero {
+        let pins = [(17, 4), (22, 27), (9, 10)];
+        let strips = strip_labels(labels, &["one", "two", "three"], "STATUS Zero")
+            .into_iter()
+            .zip(pins.iter())
+            .map(|(label, &(red, green))| {
+                (
+                    label,
+                    LEDBoard::new_named(&[("red", red), ("green", green)]),
+                )
+            })
+            .collect();
+        StatusZero { strips }
+    }
+
+    /// The labels of the strips, in order.
+    pub fn labels(&self) -> Vec<&str> {
+        self.strips
+            .iter()
+            .map(|(label, _)| label.as_str())
+            .collect()
+    }
+
+    /// The strip with the given label, or None if there is no such strip.
+    pub fn get(&mut self, label: &str) -> Option<&mut LEDBoard> {
+        self.strips
+            .iter_mut()
+            .find(|(l, _)| l == label)
+            .map(|(_, strip)| strip)
+    }
+
+    /// The value of every LED, red then green for each strip.
+    pub fn value(&self) -> Vec<f64> {
+        Component::values(self)
+    }
+
+    /// Returns ``True`` if any LED is lit.
+    pub fn is_active(&self) -> bool {
+        Component::is_active(self)
+    }
+
+    /// Turn all the LEDs on.
+    pub fn on(&mut self) {
+        OutputComponent::on(self)
+    }
+
+    /// Turn all the LEDs off.
+    pub fn off(&mut self) {
+        OutputComponent::off(self)
+    }
+
+    /// Reverse the state of all the LEDs.
+    pub fn toggle(&mut self) {
+        OutputComponent::toggle(self)
+    }
+}
+
+impl BoardItem for StatusZero {
+    fn set_brightness(&mut self, value: f64) {
+        for (_, strip) in self.strips.iter_mut() {
+            strip.set_brightness(value)
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.strips
+            .iter()
+            .map(|(_, strip)| BoardItem::len(strip))
+            .sum()
+    }
+
+    fn blink(
+        &mut self,
+        on_time: f32,
+        off_time: f32,
+        fade_in_time: f32,
+        fade_out_time: f32,
+        n: Option<i32>,
+    ) -> Result<(), TimingError> {
+        for (_, strip) in self.strips.iter_mut() {
+            strip.blink(on_time, off_time, fade_in_time, fade_out_time, n)?;
+        }
+        Ok(())
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        for (_, strip) in self.strips.iter_mut() {
+            strip.set_paused(paused)
+        }
+    }
+}
+
+impl Component for StatusZero {
+    fn values(&self) -> Vec<f64> {
+        self.strips
+            .iter()
+            .flat_map(|(_, strip)| strip.values())
+            .collect()
+    }
+
+    fn is_active(&self) -> bool {
+        self.strips.iter().any(|(_, strip)| strip.is_active())
+    }
+
+    fn close(&mut self) {
+        OutputComponent::off(self)
+    }
+}
+
+impl OutputComponent for StatusZero {
+    fn on(&mut self) {
+        for (_, strip) in self.strips.iter_mut() {
+            strip.on()
+        }
+    }
+
+    fn off(&mut self) {
+        for (_, strip) in self.strips.iter_mut() {
+            strip.off()
+        }
+    }
+
+    fn toggle(&mut self) {
+        for (_, strip) in self.strips.iter_mut() {
+            strip.toggle()
+        }
+    }
+}
+
+impl Index<&str> for StatusZero {
+    type Output = LEDBoard;
+
+    fn index(&self, label: &str) -> &LEDBoard {
+        match self.strips.iter().find(|(l, _)| l == label) {
+            Some((_, strip)) => strip,
+            None => panic!("No strip labelled {}", label),
+        }
+    }
+}
+
+impl IndexMut<&str> for StatusZero {
+    fn index_mut(&mut self, label: &str) -> &mut LEDBoard {
+        match self.get(label) {
+            Some(strip) => strip,
+            None => panic!("No strip labelled {}", label),
+        }
+    }
+}
+
+/// One strip of a `StatusBoard`: a red and a green LED and a button.
+pub struct StatusStrip {
+    /// The LEDs, named "red" and "green"
+    pub lights: LEDBoard,
+    /// The button next to the LEDs
+    pub button: Button,
+}
+
+type StripCallback = Arc<Mutex<Option<Box<dyn FnMut(&str) + Send>>>>;
+
+/// The Pi Hut STATUS board: five strips, each with a red and a green LED and a button,
+/// which can be labelled.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut status = StatusBoard::new(&["kitchen", "garage"]);
+/// status["kitchen"].lights.on();
+/// status.when_pressed(|label| println!("{} acknowledged", label));
+/// ```
+pub struct StatusBoard {
+    strips: Vec<(String, StatusStrip)>,
+    pressed: StripCallback,
+    released: StripCallback,
+    listening: bool,
+}
+
+impl StatusBoard {
+    /// Returns a StatusBoard with a strip for each label, from the top. Without labels, all
+    /// five strips are used and named "one" to "five".
+    /// * `labels` - Names of the strips, at most five
+    pub fn new(labels: &[&str]) -> StatusBoard {
+        let pins = [
+            (17, 4, 14),
+            (22, 27, 19),
+            (9, 10, 15),
+            (5, 11, 26),
+            (13, 6, 18),
+        ];
+        let strips = strip_labels(
+            labels,
+            &["one", "two", "three", "four", "five"],
+            "STATUS board",
+        )
+        .into_iter()
+        .zip(pins.iter())
+        .map(|(label, &(red, green, button))| {
+            let strip = StatusStrip {
+                lights: LEDBoard::new_named(&[("red", red), ("green", green)]),
+                button: Button::new(button),
+            };
+            (label, strip)
+        })
+        .collect();
+        StatusBoard {
+            strips,
+            pressed: Arc::default(),
+            released: Arc::default(),
+            listening: false,
+        }
+    }
+
+    /// The labels of the strips, in order.
+    pub fn labels(&self) -> Vec<&str> {
+        self.strips
+            .iter()
+            .map(|(label, _)| label.as_str())
+            .collect()
+    }
+
+    /// The strip with the given label, or None if there is no such strip.
+    pub fn get(&mut self, label: &str) -> Option<&mut StatusStrip> {
+        self.strips
+            .iter_mut()
+            .find(|(l, _)| l == label)
+            .map(|(_, strip)| strip)
+    }
+
+    /// The value of every LED, red then green for each strip.
+    pub fn value(&self) -> Vec<f64> {
+        self.strips
+            .iter()
+            .flat_map(|(_, strip)| strip.lights.values())
+            .collect()
+    }
+
+    /// Turn all the LEDs on.
+    pub fn on(&mut self) {
+        for (_, strip) in self.strips.iter_mut() {
+            strip.lights.on()
+        }
+    }
+
+    /// Turn all the LEDs off.
+    pub fn off(&mut self) {
+        for (_, strip) in self.strips.iter_mut() {
+            strip.lights.off()
+        }
+    }
+
+    /// Set the function called from a background thread when any button is pressed.
+    /// It receives the label of the strip.
+    pub fn when_pressed<C>(&mut self, callback: C)
+    where
+        C: FnMut(&str) + Send + 'static,
+    {
+        *self.pressed.lock().unwrap() = Some(Box::new(callback));
+        self.listen();
+    }
+
+    /// Set the function called from a background thread when any button is released.
+    /// It receives the label of the strip.
+    pub fn when_released<C>(&mut self, callback: C)
+    where
+        C: FnMut(&str) + Send + 'static,
+    {
+        *self.released.lock().unwrap() = Some(Box::new(callback));
+        self.listen();
+    }
+
+    fn listen(&mut self) {
+        if self.listening {
+            return;
+        }
+        self.listening = true;
+        for (label, strip) in self.strips.iter_mut() {
+            let pressed = Arc::clone(&self.pressed);
+            let pressed_label = label.clone();
+            strip.button.when_pressed(move || {
+                if let Some(callback) = pressed.lock().unwrap().as_mut() {
+                    callback(&pressed_label)
+                }
+            });
+            let released = Arc::clone(&self.released);
+            let released_label = label.clone();
+            strip.button.when_released(move || {
+                if let Some(callback) = released.lock().unwrap().as_mut() {
+                    callback(&released_label)
+                }
+            });
+        }
+    }
+}
+
+impl Index<&str> for StatusBoard {
+    type Output = StatusStrip;
+
+    fn index(&self, label: &str) -> &StatusStrip {
+        match self.strips.iter().find(|(l, _)| l == label) {
+            Some((_, strip)) => strip,
+            None => panic!("No strip labelled {}", label),
+        }
+    }
+}
+
+impl IndexMut<&str> for StatusBoard {
+    fn index_mut(&mut self, label: &str) -> &mut StatusStrip {
+        match self.get(label) {
+            Some(strip) => strip,
+            None => panic!("No strip labelled {}", label),
+        }
+    }
+}
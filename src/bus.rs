@@ -0,0 +1,663 @@
+//! Sharing, retries and error accounting for devices on the I2C and SPI buses
+//!
+//! Several devices on one bus may be used from different threads. Each I2C bus is opened
+//! once and shared through `SharedI2c`, and the chip selects of an SPI bus share a lock
+//! through `SharedSpi`: a device holds the bus for a whole transaction, so the transfers
+//! of another device never come in between.
+//!
+//! Loose jumper wires, long cables and electrical noise make bus transfers fail now and
+//! then: the slave does not acknowledge its address, a checksum does not match, or the
+//! transfer times out. A `BusLink` wraps every transfer of a device, retries transient
+//! failures with an increasing delay, counts errors, and tells the application when the
+//! device stops answering and when it comes back.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::*;
+//! use rppal::i2c::I2c;
+//! use std::time::Duration;
+//!
+//! let mut i2c = I2c::new().unwrap();
+//! i2c.set_slave_address(0x48).unwrap();
+//!
+//! let mut link = BusLink::new(RetryPolicy::new(3, Duration::from_millis(5)));
+//! link.when_comm_lost(|| println!("sensor stopped answering, check the wiring"));
+//! link.when_comm_restored(|| println!("sensor is back"));
+//!
+//! let reading = link.run(|| i2c.smbus_read_word(0x00));
+//! println!("{:?}, {:?}", reading, link.stats());
+//! ```
+use rppal::gpio::{self, Gpio, InputPin, Level, OutputPin};
+use rppal::i2c::{self, I2c};
+use rppal::spi::{self, Bus, Mode, SlaveSelect, Spi};
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How a `BusLink` retries failed transfers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Number of retries after the first attempt
+    pub retries: u32,
+    /// Delay before the first retry
+    pub backoff: Duration,
+    /// Each retry waits this many times longer than the one before
+    pub multiplier: f64,
+    /// Longest delay between two attempts
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Returns a RetryPolicy doubling the delay after every retry, up to one second
+    /// * `retries` - Number of retries after the first attempt
+    /// * `backoff` - Delay before the first retry
+    pub fn new(retries: u32, backoff: Duration) -> RetryPolicy {
+        RetryPolicy {
+            retries,
+            backoff,
+            multiplier: 2.0,
+            max_backoff: Duration::from_secs(1),
+        }
+    }
+
+    /// Returns a RetryPolicy that never retries
+    pub fn none() -> RetryPolicy {
+        RetryPolicy::new(0, Duration::from_secs(0))
+    }
+
+    /// The delay before the given retry, counting from 0.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = self
+            .multiplier
+            .max(1.0)
+            .powi(retry.min(i32::MAX as u32) as i32);
+        // clamp in f64, as the delay of a late retry can be too long for a Duration
+        let seconds = self.backoff.as_secs_f64() * factor;
+        if seconds < self.max_backoff.as_secs_f64() {
+            Duration::from_secs_f64(seconds)
+        } else {
+            self.max_backoff
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy::new(3, Duration::from_millis(2))
+    }
+}
+
+/// Linux error numbers reported by the bus drivers for failures worth retrying: EIO, ENXIO
+/// (no acknowledge), EAGAIN, EBADMSG (checksum mismatch), ETIMEDOUT and EREMOTEIO.
+const TRANSIENT_ERRNO: [i32; 6] = [5, 6, 11, 74, 110, 121];
+
+/// Errors of bus transfers that can tell whether trying again may help.
+pub trait TransientError {
+    /// Returns ``True`` if the error may go away by itself, such as a missing acknowledge,
+    /// a checksum mismatch or a timeout. Configuration errors are never transient.
+    fn is_transient(&self) -> bool;
+}
+
+impl TransientError for io::Error {
+    fn is_transient(&self) -> bool {
+        match self.raw_os_error() {
+            Some(code) => TRANSIENT_ERRNO.contains(&code),
+            None => matches!(
+                self.kind(),
+                io::ErrorKind::TimedOut | io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock
+            ),
+        }
+    }
+}
+
+impl TransientError for rppal::i2c::Error {
+    fn is_transient(&self) -> bool {
+        match self {
+            rppal::i2c::Error::Io(error) => error.is_transient(),
+            _ => false,
+        }
+    }
+}
+
+impl TransientError for rppal::spi::Error {
+    fn is_transient(&self) -> bool {
+        match self {
+            rppal::spi::Error::Io(error) => error.is_transient(),
+            _ => false,
+        }
+    }
+}
+
+/// Counters of a `BusLink`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BusStats {
+    /// Transfers requested with `BusLink::run`
+    pub transfers: u64,
+    /// Failed attempts, including those that succeeded on a retry
+    pub errors: u64,
+    /// Attempts repeated after a transient error
+    pub retries: u64,
+    /// Transfers that failed after all retries
+    pub failures: u64,
+    /// Times the device stopped answering
+    pub comm_lost: u64,
+}
+
+/// What a device whose transport is lost does with the changes asked of it until the
+/// transport comes back.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OfflinePolicy {
+    /// Fail every change straight away
+    #[default]
+    Reject,
+    /// Accept changes and make the last one when the device is reattached, e.g. so a relay
+    /// switched off during an outage is off once the link returns
+    Buffer,
+}
+
+type CommCallback = Box<dyn FnMut() + Send>;
+
+/// Runs the transfers of one bus device with retries and keeps its error counters.
+///
+/// The device counts as lost when a transfer fails after all retries. While it is lost every
+/// transfer is attempted only once, so a disconnected device does not slow the application
+/// down with retries; the first successful transfer restores it.
+pub struct BusLink {
+    policy: RetryPolicy,
+    stats: BusStats,
+    connected: bool,
+    when_comm_lost: Option<CommCallback>,
+    when_comm_restored: Option<CommCallback>,
+}
+
+impl Default for BusLink {
+    fn default() -> Self {
+        BusLink::new(RetryPolicy::default())
+    }
+}
+
+impl BusLink {
+    /// Returns a BusLink retrying failed transfers according to the policy
+    pub fn new(policy: RetryPolicy) -> BusLink {
+        BusLink {
+            policy,
+            stats: BusStats::default(),
+            connected: true,
+            when_comm_lost: None,
+            when_comm_restored: None,
+        }
+    }
+
+    /// The retry policy.
+    pub fn policy(&self) -> RetryPolicy {
+        self.policy
+    }
+
+    /// Change the retry policy.
+    pub fn set_policy(&mut self, policy: RetryPolicy) {
+        self.policy = policy;
+    }
+
+    /// The error counters.
+    pub fn stats(&self) -> BusStats {
+        self.stats
+    }
+
+    /// Reset the error counters to zero.
+    pub fn reset_stats(&mut self) {
+        self.stats = BusStats::default();
+    }
+
+    /// Returns ``True`` unless the last transfer failed after all retries.
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// Set the function called when the device stops answering.
+    pub fn when_comm_lost<C>(&mut self, callback: C)
+    where
+        C: FnMut() + Send + 'static,
+    {
+        self.when_comm_lost = Some(Box::new(callback));
+    }
+
+    /// Set the function called when a lost device answers again.
+    pub fn when_comm_restored<C>(&mut self, callback: C)
+    where
+        C: FnMut() + Send + 'static,
+    {
+        self.when_comm_restored = Some(Box::new(callback));
+    }
+
+    /// Run a transfer, retrying it while it fails with a transient error.
+    /// * `transfer` - The bus operation, called once per attempt
+    pub fn run<T, E, F>(&mut self, mut transfer: F) -> Result<T, E>
+    where
+        E: TransientError,
+        F: FnMut() -> Result<T, E>,
+    {
+        self.stats.transfers += 1;
+        let retries = if self.connected {
+            self.policy.retries
+        } else {
+            0
+        };
+        let mut retry = 0;
+        loop {
+            match transfer() {
+                Ok(value) => {
+                    if !self.connected {
+                        self.connected = true;
+                        if let Some(callback) = self.when_comm_restored.as_mut() {
+                            callback();
+                        }
+                    }
+                    return Ok(value);
+                }
+                Err(error) => {
+                    self.stats.errors += 1;
+                    if error.is_transient() && retry < retries {
+                        thread::sleep(self.policy.delay(retry));
+                        self.stats.retries += 1;
+                        retry += 1;
+                        continue;
+                    }
+                    self.stats.failures += 1;
+                    if self.connected {
+                        self.connected = false;
+                        self.stats.comm_lost += 1;
+                        if let Some(callback) = self.when_comm_lost.as_mut() {
+                            callback();
+                        }
+                    }
+                    return Err(error);
+                }
+            }
+        }
+    }
+}
+
+/// The I2C buses in use, by number, each opened once and shared by every device on it.
+static I2C_BUSES: Mutex<Vec<(u8, Weak<Mutex<I2cBus>>)>> = Mutex::new(Vec::new());
+
+/// The locks of the SPI buses in use, by bus number.
+static SPI_BUSES: Mutex<Vec<(u8, Weak<Mutex<()>>)>> = Mutex::new(Vec::new());
+
+/// An open I2C bus and the device it last addressed.
+struct I2cBus {
+    i2c: I2c,
+    address: Option<u16>,
+}
+
+/// A device's share of an I2C bus.
+///
+/// Every device on a bus holds the same handle, so their transfers cannot interleave even
+/// when they are used from different threads: `lock` waits for the bus, addresses the
+/// device and keeps the bus until the guard is dropped, which makes a transaction of
+/// several transfers atomic.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::SharedI2c;
+///
+/// let sensor = SharedI2c::open(1, 0x48).unwrap();
+/// let display = SharedI2c::open(1, 0x3c).unwrap();
+///
+/// // select a register and read it back, with no transfer to the display in between
+/// let mut bus = sensor.lock().unwrap();
+/// bus.write(&[0x00]).unwrap();
+/// let mut bytes = [0u8; 2];
+/// bus.read(&mut bytes).unwrap();
+/// ```
+pub struct SharedI2c {
+    bus: u8,
+    address: u16,
+    shared: Arc<Mutex<I2cBus>>,
+}
+
+impl SharedI2c {
+    /// Returns the device at the address given, opening the bus unless another device
+    /// already holds it
+    /// * `bus` - The I2C bus number, 1 on most Raspberry Pi models
+    /// * `address` - The 7-bit address of the device
+    pub fn open(bus: u8, address: u16) -> Result<SharedI2c, i2c::Error> {
+        let mut buses = I2C_BUSES.lock().unwrap();
+        buses.retain(|(_, shared)| shared.strong_count() > 0);
+        let shared = match buses
+            .iter()
+            .find(|(number, _)| *number == bus)
+            .and_then(|(_, shared)| shared.upgrade())
+        {
+            Some(shared) => shared,
+            None => {
+                let shared = Arc::new(Mutex::new(I2cBus {
+                    i2c: I2c::with_bus(bus)?,
+                    address: None,
+                }));
+                buses.push((bus, Arc::downgrade(&shared)));
+                shared
+            }
+        };
+        Ok(SharedI2c {
+            bus,
+            address,
+            shared,
+        })
+    }
+
+    /// The bus number.
+    pub fn bus(&self) -> u8 {
+        self.bus
+    }
+
+    /// The 7-bit address.
+    pub fn address(&self) -> u16 {
+        self.address
+    }
+
+    /// Wait for the bus and address the device, keeping the bus until the guard is dropped.
+    pub fn lock(&self) -> Result<I2cGuard<'_>, i2c::Error> {
+        let mut bus = self.shared.lock().unwrap();
+        if bus.address != Some(self.address) {
+            bus.address = None;
+            bus.i2c.set_slave_address(self.address)?;
+            bus.address = Some(self.address);
+        }
+        Ok(I2cGuard { bus })
+    }
+}
+
+impl fmt::Debug for SharedI2c {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedI2c")
+            .field("bus", &self.bus)
+            .field("address", &self.address)
+            .finish()
+    }
+}
+
+/// An I2C bus held by one device, addressed to it, returned by `SharedI2c::lock`.
+pub struct I2cGuard<'a> {
+    bus: MutexGuard<'a, I2cBus>,
+}
+
+impl Deref for I2cGuard<'_> {
+    type Target = I2c;
+
+    fn deref(&self) -> &I2c {
+        &self.bus.i2c
+    }
+}
+
+impl DerefMut for I2cGuard<'_> {
+    fn deref_mut(&mut self) -> &mut I2c {
+        &mut self.bus.i2c
+    }
+}
+
+/// A device on an SPI chip select, sharing the lock of its bus with the devices on the
+/// other chip selects.
+///
+/// The kernel runs each transfer atomically, but a transaction of several transfers, such
+/// as selecting a register and reading it, can be split by another device on the bus. The
+/// guard returned by `lock` keeps the bus until it is dropped.
+pub struct SharedSpi {
+    spi: Spi,
+    bus: Bus,
+    slave_select: SlaveSelect,
+    lock: Arc<Mutex<()>>,
+}
+
+impl SharedSpi {
+    /// Returns the device on the chip select given
+    /// * `bus` - The SPI bus, `Bus::Spi0` on most boards
+    /// * `slave_select` - The chip select the device is connected to
+    /// * `clock_speed` - The clock in Hz, within what the device accepts
+    /// * `mode` - The clock polarity and phase the device expects
+    pub fn open(
+        bus: Bus,
+        slave_select: SlaveSelect,
+        clock_speed: u32,
+        mode: Mode,
+    ) -> Result<SharedSpi, spi::Error> {
+        let spi = Spi::new(bus, slave_select, clock_speed, mode)?;
+        let mut locks = SPI_BUSES.lock().unwrap();
+        locks.retain(|(_, lock)| lock.strong_count() > 0);
+        let lock = match locks
+            .iter()
+            .find(|(number, _)| *number == bus as u8)
+            .and_then(|(_, lock)| lock.upgrade())
+        {
+            Some(lock) => lock,
+            None => {
+                let lock = Arc::new(Mutex::new(()));
+                locks.push((bus as u8, Arc::downgrade(&lock)));
+                lock
+            }
+        };
+        Ok(SharedSpi {
+            spi,
+            bus,
+            slave_select,
+            lock,
+        })
+    }
+
+    /// The bus.
+    pub fn bus(&self) -> Bus {
+        self.bus
+    }
+
+    /// The chip select.
+    pub fn slave_select(&self) -> SlaveSelect {
+        self.slave_select
+    }
+
+    /// Wait for the bus, keeping it until the guard is dropped.
+    pub fn lock(&mut self) -> SpiGuard<'_> {
+        SpiGuard {
+            _bus: self.lock.lock().unwrap(),
+            spi: &mut self.spi,
+        }
+    }
+}
+
+impl fmt::Debug for SharedSpi {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedSpi")
+            .field("bus", &self.bus)
+            .field("slave_select", &self.slave_select)
+            .finish()
+    }
+}
+
+/// An SPI bus held by one device, returned by `SharedSpi::lock`.
+pub struct SpiGuard<'a> {
+    _bus: MutexGuard<'a, ()>,
+    spi: &'a mut Spi,
+}
+
+impl Deref for SpiGuard<'_> {
+    type Target = Spi;
+
+    fn deref(&self) -> &Spi {
+        self.spi
+    }
+}
+
+impl DerefMut for SpiGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Spi {
+        self.spi
+    }
+}
+
+/// How an SPI device is wired: to a chip select of a hardware bus, or to any free GPIO
+/// pins, clocked by the crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpiBus {
+    /// A chip select of a hardware SPI bus
+    Hardware {
+        /// The bus, `Bus::Spi0` on the header pins 19, 21 and 23
+        bus: Bus,
+        /// The chip select the device is wired to
+        slave_select: SlaveSelect,
+        /// The clock in Hz
+        clock_speed: u32,
+    },
+    /// GPIO pins driven in software, for when a HAT occupies the hardware SPI pins. The
+    /// clock runs at 1MHz at most, slower when the thread is interrupted, which the SPI
+    /// devices of the crate do not mind.
+    Software {
+        /// The pin of the clock line (SCLK)
+        clock: u8,
+        /// The pin data goes out on (MOSI)
+        mosi: u8,
+        /// The pin data comes in on (MISO), `None` for devices that are only written
+        miso: Option<u8>,
+        /// The pin of the chip select, held low during a transfer
+        select: u8,
+    },
+}
+
+/// Half a period of the software SPI clock.
+const SOFTWARE_SPI_HALF_PERIOD: Duration = Duration::from_nanos(500);
+
+/// SPI bit-banged on GPIO pins, most significant bit first.
+pub(crate) struct SoftwareSpi {
+    clock: OutputPin,
+    mosi: OutputPin,
+    miso: Option<InputPin>,
+    select: OutputPin,
+    mode: Mode,
+}
+
+impl SoftwareSpi {
+    fn open(
+        clock: u8,
+        mosi: u8,
+        miso: Option<u8>,
+        select: u8,
+        mode: Mode,
+    ) -> Result<SoftwareSpi, gpio::Error> {
+        let gpio = Gpio::new()?;
+        let mut spi = SoftwareSpi {
+            clock: gpio.get(clock)?.into_output(),
+            mosi: gpio.get(mosi)?.into_output(),
+            miso: match miso {
+                Some(pin) => Some(gpio.get(pin)?.into_input()),
+                None => None,
+            },
+            select: gpio.get(select)?.into_output(),
+            mode,
+        };
+        spi.select.set_high();
+        let idle = spi.idle_level();
+        spi.clock.write(idle);
+        Ok(spi)
+    }
+
+    /// The level of the clock between transfers, high in modes 2 and 3.
+    fn idle_level(&self) -> Level {
+        if matches!(self.mode, Mode::Mode2 | Mode::Mode3) {
+            Level::High
+        } else {
+            Level::Low
+        }
+    }
+
+    /// Clock `write` out while clocking as many bytes into `read`, which may be shorter.
+    fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> usize {
+        let idle = self.idle_level();
+        // in modes 1 and 3 data changes on the first edge and is read on the second
+        let late = matches!(self.mode, Mode::Mode1 | Mode::Mode3);
+        self.select.set_low();
+        for (index, &byte) in write.iter().enumerate() {
+            let mut received = 0u8;
+            for bit in (0..8).rev() {
+                if late {
+                    self.clock.write(!idle);
+                }
+                self.mosi.write(if byte & (1 << bit) != 0 {
+                    Level::High
+                } else {
+                    Level::Low
+                });
+                half_period();
+                self.clock.write(if late { idle } else { !idle });
+                let high = self.miso.as_ref().is_some_and(|miso| miso.is_high());
+                received = received << 1 | u8::from(high);
+                half_period();
+                if !late {
+                    self.clock.write(idle);
+                }
+            }
+            if let Some(slot) = read.get_mut(index) {
+                *slot = received;
+            }
+        }
+        self.select.set_high();
+        write.len()
+    }
+}
+
+/// Wait half a clock period; sleeping would take far longer.
+fn half_period() {
+    let start = Instant::now();
+    while start.elapsed() < SOFTWARE_SPI_HALF_PERIOD {
+        std::hint::spin_loop();
+    }
+}
+
+/// The SPI device of a driver, on a hardware bus or on GPIO pins.
+pub(crate) enum SpiPort {
+    Hardware(SharedSpi),
+    Software(Box<SoftwareSpi>),
+}
+
+impl SpiPort {
+    pub(crate) fn open(bus: SpiBus, mode: Mode) -> Result<SpiPort, Box<dyn Error + Send + Sync>> {
+        Ok(match bus {
+            SpiBus::Hardware {
+                bus,
+                slave_select,
+                clock_speed,
+            } => SpiPort::Hardware(SharedSpi::open(bus, slave_select, clock_speed, mode)?),
+            SpiBus::Software {
+                clock,
+                mosi,
+                miso,
+                select,
+            } => SpiPort::Software(Box::new(SoftwareSpi::open(
+                clock, mosi, miso, select, mode,
+            )?)),
+        })
+    }
+
+    /// Returns ``True`` if the device can be read from.
+    pub(crate) fn can_read(&self) -> bool {
+        match self {
+            SpiPort::Hardware(_) => true,
+            SpiPort::Software(spi) => spi.miso.is_some(),
+        }
+    }
+
+    pub(crate) fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<usize, spi::Error> {
+        match self {
+            SpiPort::Hardware(spi) => spi.lock().transfer(read, write),
+            SpiPort::Software(spi) => Ok(spi.transfer(read, write)),
+        }
+    }
+
+    pub(crate) fn write(&mut self, write: &[u8]) -> Result<usize, spi::Error> {
+        match self {
+            SpiPort::Hardware(spi) => spi.lock().write(write),
+            SpiPort::Software(spi) => Ok(spi.transfer(&mut [], write)),
+        }
+    }
+}
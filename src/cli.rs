@@ -0,0 +1,115 @@
+//! Command line tools for bringing up Raspberry Pi builds
+//!
+//! The `gpiozero` binary runs these tools. A crate providing drivers can offer the same
+//! tools with its drivers included by registering them before calling `main`:
+//!
+//! ```no_run
+//! # use rust_gpiozero::drivers::Driver;
+//! use rust_gpiozero::{cli, drivers};
+//!
+//! fn main() {
+//!     drivers::register(MyAdcDriver);
+//!     cli::main();
+//! }
+//! # struct MyAdcDriver;
+//! # impl Driver for MyAdcDriver {
+//! #     fn kind(&self) -> &str { "my_adc" }
+//! #     fn build(&self, _: &rust_gpiozero::DeviceConfig, _: &mut rust_gpiozero::DeviceTree)
+//! #         -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+//! # }
+//! ```
+use crate::{diagnostics, drivers, i2c, spi};
+use std::env;
+use std::process;
+
+const USAGE: &str = "usage: gpiozero <command>
+
+commands:
+  doctor            check the board, permissions and interfaces, and how to fix them
+  i2c-scan [BUS]    list the addresses answering on an I2C bus (default 1)
+  spi-probe         probe every enabled SPI chip select
+  drivers           list the registered device drivers";
+
+fn i2c_scan(bus: u8) -> Result<(), String> {
+    let found = i2c::scan(bus).map_err(|e| format!("cannot scan /dev/i2c-{}: {}", bus, e))?;
+    if found.is_empty() {
+        println!("no devices found on /dev/i2c-{}", bus);
+    }
+    for device in found {
+        if device.candidates.is_empty() {
+            println!("0x{:02x}  unknown device", device.address);
+        } else {
+            println!(
+                "0x{:02x}  {}",
+                device.address,
+                device.candidates.join(" / ")
+            );
+        }
+    }
+    Ok(())
+}
+
+fn spi_probe() -> Result<(), String> {
+    let found = spi::probe();
+    if found.is_empty() {
+        return Err("no SPI device nodes, is SPI enabled in /boot/config.txt?".to_string());
+    }
+    for device in found {
+        let state = if device.responds {
+            "responding"
+        } else {
+            "no response"
+        };
+        println!("{}  {}", device.path, state);
+    }
+    Ok(())
+}
+
+fn doctor() -> Result<(), String> {
+    let report = diagnostics::check();
+    println!("{}", report);
+    if report.is_ok() {
+        Ok(())
+    } else {
+        Err("the checks above found errors".to_string())
+    }
+}
+
+fn list_drivers() -> Result<(), String> {
+    let drivers = drivers::drivers();
+    if drivers.is_empty() {
+        println!("no drivers registered");
+    }
+    for driver in drivers {
+        println!("{:<16}  {}", driver.kind(), driver.description());
+    }
+    Ok(())
+}
+
+/// Run the command given by the program's arguments, and exit on errors.
+pub fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = match args.as_slice() {
+        ["i2c-scan"] => i2c_scan(1),
+        ["i2c-scan", bus] => match bus.parse() {
+            Ok(bus) => i2c_scan(bus),
+            Err(_) => Err(format!("'{}' is not a bus number", bus)),
+        },
+        ["doctor"] => doctor(),
+        ["spi-probe"] => spi_probe(),
+        ["drivers"] => list_drivers(),
+        ["help"] | ["--help"] | ["-h"] => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(error) = result {
+        eprintln!("error: {}", error);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,3591 @@
+//! Higher-level devices built from the devices of this crate, such as tanks and pumps
+use crate::devices::{AnalogInputDevice, Component, OutputComponent};
+use crate::input_devices::{Average, Bias, Button, DigitalInputDeviceR, SmoothedQueue};
+use crate::internal_devices::{local_time_of_day, utc_offset_seconds, SunTimes, TimeOfDay};
+use crate::output_devices::{sleep_while_running, DigitalOutputDevice, Motor, Servo, PWMLED};
+use crate::transaction::{Transaction, TransactionError};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::f64::consts::PI;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
+
+type DistanceFn = Box<dyn FnMut() -> Option<f64> + Send>;
+type AlarmCallback = Box<dyn FnMut(bool) + Send>;
+
+/// The shape of a tank, which maps the height of the liquid to how full the tank is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TankShape {
+    /// Straight sides: the volume is proportional to the height
+    Vertical,
+    /// A cylinder lying on its side
+    HorizontalCylinder,
+}
+
+impl TankShape {
+    /// The fraction of the volume filled when the liquid is at `height`, a fraction of the depth.
+    fn fill(&self, height: f64) -> f64 {
+        let height = height.clamp(0.0, 1.0);
+        match self {
+            TankShape::Vertical => height,
+            TankShape::HorizontalCylinder => {
+                // area of the circular segment below the surface of a circle of diameter 1
+                let r = 0.5;
+                let d = r - height;
+                let segment =
+                    r * r * (d / r).acos() - d * (2.0 * r * height - height * height).sqrt();
+                segment / (PI * r * r)
+            }
+        }
+    }
+}
+
+enum LevelSource {
+    Floats(Vec<(DigitalInputDeviceR, f64)>),
+    Distance {
+        read: DistanceFn,
+        empty_distance: f64,
+        full_distance: f64,
+        shape: TankShape,
+    },
+}
+
+/// The level of a tank, measured with float switches or a distance sensor above the liquid.
+///
+/// The level is reported as a percentage of the volume. Low and high alarms are raised when
+/// the level reaches their thresholds and cleared once it has moved back by the hysteresis,
+/// so a level rippling around a threshold does not toggle them. A pump drawing from the tank
+/// can be protected with `protect_pump`, which keeps it off while the low alarm is raised or
+/// the level cannot be read.
+///
+/// # Example
+///
+/// ```
+/// use rust_gpiozero::*;
+///
+/// // an ultrasonic sensor 1.2m above the bottom of the tank, 0.2m above the full level
+/// let mut distance = vec![1.15, 0.5, 0.25].into_iter();
+/// let mut tank = TankLevel::distance(move || distance.next(), 1.2, 0.2, TankShape::Vertical);
+/// tank.set_low_alarm(10.0);
+///
+/// assert_eq!(tank.percent_full().map(f64::round), Some(5.0));
+/// assert!(tank.is_low());
+/// assert!(!tank.pump_allowed());
+///
+/// tank.percent_full();
+/// assert!(tank.pump_allowed());
+/// ```
+pub struct TankLevel {
+    source: LevelSource,
+    level: Option<f64>,
+    low_alarm: f64,
+    high_alarm: f64,
+    hysteresis: f64,
+    low: bool,
+    high: bool,
+    when_low: Option<AlarmCallback>,
+    when_high: Option<AlarmCallback>,
+}
+
+impl TankLevel {
+    fn new(source: LevelSource) -> TankLevel {
+        TankLevel {
+            source,
+            level: None,
+            low_alarm: 10.0,
+            high_alarm: 90.0,
+            hysteresis: 5.0,
+            low: false,
+            high: false,
+            when_low: None,
+            when_high: None,
+        }
+    }
+
+    /// Returns a TankLevel reading float switches spaced evenly up the tank, the last one at
+    /// the full level. Each switch connects its pin to ground when submerged.
+    /// * `pins` - The GPIO pins of the switches, from the bottom of the tank up
+    pub fn floats(pins: &[u8]) -> TankLevel {
+        let count = pins.len() as f64;
+        TankLevel::from_floats(
+            pins.iter()
+                .enumerate()
+                .map(|(i, &pin)| {
+                    let level = 100.0 * (i + 1) as f64 / count;
+                    (DigitalInputDeviceR::new_active_low(pin), level)
+                })
+                .collect(),
+        )
+    }
+
+    /// Returns a TankLevel reading float switches, each active when submerged.
+    /// * `floats` - The switches and the level in percent at which each is submerged
+    pub fn from_floats(mut floats: Vec<(DigitalInputDeviceR, f64)>) -> TankLevel {
+        floats.sort_by(|a, b| a.1.total_cmp(&b.1));
+        TankLevel::new(LevelSource::Floats(floats))
+    }
+
+    /// Returns a TankLevel reading a distance sensor mounted above the liquid, looking down.
+    /// * `read` - Returns the distance to the surface in meters, or None if there is no reading
+    /// * `empty_distance` - The distance to the bottom of the tank
+    /// * `full_distance` - The distance to the surface when the tank is full
+    /// * `shape` - The shape of the tank
+    pub fn distance<F>(
+        read: F,
+        empty_distance: f64,
+        full_distance: f64,
+        shape: TankShape,
+    ) -> TankLevel
+    where
+        F: FnMut() -> Option<f64> + Send + 'static,
+    {
+        if empty_distance <= full_distance {
+            panic!("the empty distance must be greater than the full distance");
+        }
+        TankLevel::new(LevelSource::Distance {
+            read: Box::new(read),
+            empty_distance,
+            full_distance,
+            shape,
+        })
+    }
+
+    fn read(&mut self) -> Option<f64> {
+        match &mut self.source {
+            LevelSource::Floats(floats) => {
+                let submerged = floats
+                    .iter()
+                    .take_while(|(float, _)| float.is_active())
+                    .count();
+                // a float submerged above a dry one means a stuck or broken switch
+                if floats[submerged..]
+                    .iter()
+                    .any(|(float, _)| float.is_active())
+                {
+                    return None;
+                }
+                Some(match submerged {
+                    0 => 0.0,
+                    n => floats[n - 1].1,
+                })
+            }
+            LevelSource::Distance {
+                read,
+                empty_distance,
+                full_distance,
+                shape,
+            } => {
+                let distance = read()?;
+                if !distance.is_finite() {
+                    return None;
+                }
+                let height = (*empty_distance - distance) / (*empty_distance - *full_distance);
+                Some(100.0 * shape.fill(height))
+            }
+        }
+    }
+
+    /// Read the sensor and update the alarms. Returns the percentage of the tank that is full,
+    /// or None if the sensor gave no reading or the float switches disagree.
+    pub fn percent_full(&mut self) -> Option<f64> {
+        self.level = self.read();
+        if let Some(level) = self.level {
+            let low = if self.low {
+                level < self.low_alarm + self.hysteresis
+            } else {
+                level <= self.low_alarm
+            };
+            if low != self.low {
+                self.low = low;
+                if let Some(callback) = &mut self.when_low {
+                    callback(low)
+                }
+            }
+            let high = if self.high {
+                level > self.high_alarm - self.hysteresis
+            } else {
+                level >= self.high_alarm
+            };
+            if high != self.high {
+                self.high = high;
+                if let Some(callback) = &mut self.when_high {
+                    callback(high)
+                }
+            }
+        }
+        self.level
+    }
+
+    /// The result of the last `percent_full`.
+    pub fn level(&self) -> Option<f64> {
+        self.level
+    }
+
+    /// Set the level in percent at or below which the low alarm is raised. Defaults to 10.
+    pub fn set_low_alarm(&mut self, percent: f64) {
+        self.low_alarm = percent
+    }
+
+    /// Set the level in percent at or above which the high alarm is raised. Defaults to 90.
+    pub fn set_high_alarm(&mut self, percent: f64) {
+        self.high_alarm = percent
+    }
+
+    /// Set how far in percent the level must move back past a threshold before its alarm
+    /// clears. Defaults to 5.
+    pub fn set_hysteresis(&mut self, percent: f64) {
+        self.hysteresis = percent.max(0.0)
+    }
+
+    /// Returns ``True`` while the low alarm is raised.
+    pub fn is_low(&self) -> bool {
+        self.low
+    }
+
+    /// Returns ``True`` while the high alarm is raised.
+    pub fn is_high(&self) -> bool {
+        self.high
+    }
+
+    /// Set the function called with ``True`` when the low alarm is raised and ``False`` when it clears.
+    pub fn when_low<C>(&mut self, callback: C)
+    where
+        C: FnMut(bool) + Send + 'static,
+    {
+        self.when_low = Some(Box::new(callback));
+    }
+
+    /// Set the function called with ``True`` when the high alarm is raised and ``False`` when it clears.
+    pub fn when_high<C>(&mut self, callback: C)
+    where
+        C: FnMut(bool) + Send + 'static,
+    {
+        self.when_high = Some(Box::new(callback));
+    }
+
+    /// Returns ``True`` if a pump drawing from the tank may run: the last reading succeeded
+    /// and the low alarm is not raised.
+    pub fn pump_allowed(&self) -> bool {
+        self.level.is_some() && !self.low
+    }
+
+    /// Returns ``True`` if a pump filling the tank may run: the last reading succeeded and
+    /// the high alarm is not raised.
+    pub fn fill_allowed(&self) -> bool {
+        self.level.is_some() && !self.high
+    }
+
+    /// Read the level and turn off a pump drawing from the tank if it may not run, so it
+    /// never runs dry. Call it regularly while the pump runs. Returns `pump_allowed`.
+    pub fn protect_pump(&mut self, pump: &mut dyn OutputComponent) -> bool {
+        self.percent_full();
+        let allowed = self.pump_allowed();
+        if !allowed && pump.is_active() {
+            pump.off();
+        }
+        allowed
+    }
+
+    /// Read the level and turn off a pump filling the tank if it may not run, so the tank
+    /// never overflows. Returns `fill_allowed`.
+    pub fn protect_fill(&mut self, pump: &mut dyn OutputComponent) -> bool {
+        self.percent_full();
+        let allowed = self.fill_allowed();
+        if !allowed && pump.is_active() {
+            pump.off();
+        }
+        allowed
+    }
+}
+
+impl Component for TankLevel {
+    fn values(&self) -> Vec<f64> {
+        vec![self.level.unwrap_or(0.0) / 100.0]
+    }
+}
+
+/// Why a `PumpControllerR` stopped its pump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PumpStop {
+    /// `stop` was called
+    Requested,
+    /// The pump ran for the maximum runtime
+    MaxRuntime,
+    /// The flow meter saw no flow within the dry-run timeout. This latches a fault.
+    DryRun,
+    /// The level sensor reported the tank low or could not be read
+    LowLevel,
+    /// The pump used up its share of the duty-cycle window
+    DutyCycle,
+    /// A manual override ended
+    OverrideEnded,
+}
+
+/// Reasons a `PumpControllerR` refused to start its pump.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PumpError {
+    /// A dry-run fault is latched; see `reset_fault`
+    Fault,
+    /// The level sensor reports the tank low or cannot be read
+    LowLevel,
+    /// The pump has used up its share of the duty-cycle window
+    DutyCycle,
+    /// A manual override is in control of the pump
+    Overridden,
+}
+
+impl fmt::Display for PumpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PumpError::Fault => write!(f, "pump stopped after running dry; reset the fault first"),
+            PumpError::LowLevel => write!(f, "tank level is too low to run the pump"),
+            PumpError::DutyCycle => write!(f, "pump duty-cycle limit reached"),
+            PumpError::Overridden => write!(f, "pump is under manual override"),
+        }
+    }
+}
+
+impl Error for PumpError {}
+
+type StopCallback = Box<dyn FnMut(PumpStop) + Send>;
+
+struct PumpState {
+    relay: Box<dyn OutputComponent>,
+    level: Option<TankLevel>,
+    pulses: Arc<AtomicU64>,
+    flow_meter: bool,
+    max_runtime: Option<Duration>,
+    dry_run_timeout: Duration,
+    duty_limit: Option<(f64, Duration)>,
+    // when the current run started, and the last time flow was seen
+    run: Option<(Instant, Instant, u64)>,
+    history: VecDeque<(Instant, Instant)>,
+    fault: bool,
+    manual: Option<Instant>,
+    last_stop: Option<PumpStop>,
+    when_stopped: Option<StopCallback>,
+}
+
+impl PumpState {
+    fn runtime_in_window(&mut self, now: Instant, window: Duration) -> Duration {
+        let start = now.checked_sub(window).unwrap_or(now);
+        while let Some(&(_, end)) = self.history.front() {
+            if end > start {
+                break;
+            }
+            self.history.pop_front();
+        }
+        let mut total = Duration::from_secs(0);
+        for &(from, to) in &self.history {
+            total += to - from.max(start);
+        }
+        if let Some((from, _, _)) = self.run {
+            total += now - from.max(start);
+        }
+        total
+    }
+
+    fn duty_exceeded(&mut self, now: Instant) -> bool {
+        match self.duty_limit {
+            Some((fraction, window)) => {
+                self.runtime_in_window(now, window) >= window.mul_f64(fraction)
+            }
+            None => false,
+        }
+    }
+
+    fn level_ok(&mut self) -> bool {
+        match &mut self.level {
+            Some(level) => {
+                level.percent_full();
+                level.pump_allowed()
+            }
+            None => true,
+        }
+    }
+
+    fn startable(&mut self) -> Result<(), PumpError> {
+        if self.manual.is_some() {
+            return Err(PumpError::Overridden);
+        }
+        if self.fault {
+            return Err(PumpError::Fault);
+        }
+        if !self.level_ok() {
+            return Err(PumpError::LowLevel);
+        }
+        if self.duty_exceeded(Instant::now()) {
+            return Err(PumpError::DutyCycle);
+        }
+        Ok(())
+    }
+
+    fn start(&mut self) {
+        if self.run.is_none() {
+            let now = Instant::now();
+            self.run = Some((now, now, self.pulses.load(Ordering::SeqCst)));
+        }
+        self.relay.on();
+    }
+
+    /// Turn the pump off, returning the reason if this ended a run so that the caller can
+    /// call `when_stopped` once the state is unlocked.
+    fn stop(&mut self, reason: PumpStop) -> Option<PumpStop> {
+        self.relay.off();
+        self.manual = None;
+        let (from, _, _) = self.run.take()?;
+        self.history.push_back((from, Instant::now()));
+        self.last_stop = Some(reason);
+        if reason == PumpStop::DryRun {
+            self.fault = true;
+        }
+        Some(reason)
+    }
+
+    /// Checks run by the supervision thread while the pump runs. Returns the reason if the
+    /// pump was stopped.
+    fn supervise(&mut self) -> Option<PumpStop> {
+        let now = Instant::now();
+        if let Some(deadline) = self.manual {
+            if now >= deadline {
+                return self.stop(PumpStop::OverrideEnded);
+            }
+        }
+        let (started, last_flow, pulses) = self.run?;
+        if !self.level_ok() {
+            return self.stop(PumpStop::LowLevel);
+        }
+        if self.flow_meter {
+            let count = self.pulses.load(Ordering::SeqCst);
+            if count != pulses {
+                self.run = Some((started, now, count));
+            } else if now - last_flow >= self.dry_run_timeout {
+                return self.stop(PumpStop::DryRun);
+            }
+        }
+        if self.manual.is_some() {
+            return None;
+        }
+        if self
+            .max_runtime
+            .is_some_and(|max_runtime| now - started >= max_runtime)
+        {
+            self.stop(PumpStop::MaxRuntime)
+        } else if self.duty_exceeded(now) {
+            self.stop(PumpStop::DutyCycle)
+        } else {
+            None
+        }
+    }
+}
+
+/// Call `when_stopped` for a run that ended. The callback is taken out of the state so
+/// that it runs without the state locked.
+fn notify_stopped(state: &Mutex<PumpState>, stopped: Option<PumpStop>) {
+    let reason = match stopped {
+        Some(reason) => reason,
+        None => return,
+    };
+    let callback = state.lock().unwrap().when_stopped.take();
+    if let Some(mut callback) = callback {
+        callback(reason);
+        // keep a callback set while this one was running
+        let mut state = state.lock().unwrap();
+        if state.when_stopped.is_none() {
+            state.when_stopped = Some(callback);
+        }
+    }
+}
+
+/// A pump switched by a relay, protected against running dry, running too long and
+/// running too often.
+///
+/// A background thread supervises the pump while it runs and stops it when:
+///
+/// * the optional flow meter has not pulsed for the dry-run timeout, which latches a fault
+///   that must be cleared with `reset_fault` before the pump starts again
+/// * the optional `TankLevel` reports the tank low or cannot be read
+/// * it has run for the maximum runtime
+/// * it has used up its duty-cycle limit, such as 15 minutes per hour
+///
+/// A manual override holds the pump on or off for a limited time, regardless of `start`
+/// and `stop` and of the runtime and duty-cycle limits, but never of the dry-run and level
+/// protections.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+/// use std::time::Duration;
+///
+/// let mut pump = PumpControllerR::new(17);
+/// pump.set_flow_meter(27);
+/// pump.set_level_sensor(TankLevel::floats(&[5, 6, 13]));
+/// pump.set_max_runtime(Some(Duration::from_secs(300)));
+/// pump.set_duty_limit(0.25, Duration::from_secs(3600));
+/// pump.when_stopped(|reason| println!("pump stopped: {:?}", reason));
+///
+/// if let Err(e) = pump.start() {
+///     println!("{}", e);
+/// }
+/// ```
+pub struct PumpControllerR {
+    state: Arc<Mutex<PumpState>>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+    flow_meter: Option<DigitalInputDeviceR>,
+}
+
+impl PumpControllerR {
+    /// Returns a PumpControllerR switching the relay on the pin given
+    /// * `pin` - The GPIO pin which the relay is attached to
+    pub fn new(pin: u8) -> PumpControllerR {
+        PumpControllerR::from_relay(DigitalOutputDevice::new(pin))
+    }
+
+    /// Returns a PumpControllerR switching the relay given, e.g. one created with
+    /// `DigitalOutputDevice::new_active_low`.
+    /// * `relay` - The output switching the pump
+    pub fn from_relay<R: OutputComponent + 'static>(mut relay: R) -> PumpControllerR {
+        relay.off();
+        let state = Arc::new(Mutex::new(PumpState {
+            relay: Box::new(relay),
+            level: None,
+            pulses: Arc::new(AtomicU64::new(0)),
+            flow_meter: false,
+            max_runtime: None,
+            dry_run_timeout: Duration::from_secs(10),
+            duty_limit: None,
+            run: None,
+            history: VecDeque::new(),
+            fault: false,
+            manual: None,
+            last_stop: None,
+            when_stopped: None,
+        }));
+        let running = Arc::new(AtomicBool::new(true));
+        let handle = {
+            let state = Arc::clone(&state);
+            let running = Arc::clone(&running);
+            thread::spawn(move || {
+                while running.load(Ordering::SeqCst) {
+                    let stopped = state.lock().unwrap().supervise();
+                    notify_stopped(&state, stopped);
+                    sleep_while_running(&running, Duration::from_millis(100));
+                }
+            })
+        };
+        PumpControllerR {
+            state,
+            running,
+            handle: Some(handle),
+            flow_meter: None,
+        }
+    }
+
+    /// Count the pulses of a flow meter on a pin; the pump stops if it sees no pulse
+    /// within the dry-run timeout while running.
+    /// * `pin` - The GPIO pin which the flow meter's output is attached to
+    pub fn set_flow_meter(&mut self, pin: u8) {
+        let mut meter = DigitalInputDeviceR::new(pin, Bias::PullUp);
+        let pulses = {
+            let mut state = self.state.lock().unwrap();
+            state.flow_meter = true;
+            Arc::clone(&state.pulses)
+        };
+        meter.when_activated(move || {
+            pulses.fetch_add(1, Ordering::SeqCst);
+        });
+        self.flow_meter = Some(meter);
+    }
+
+    /// The flow meter pulses counted so far.
+    pub fn flow_pulses(&self) -> u64 {
+        self.state.lock().unwrap().pulses.load(Ordering::SeqCst)
+    }
+
+    /// Use a level sensor on the tank the pump draws from; the pump does not run while its
+    /// low alarm is raised or it cannot be read.
+    pub fn set_level_sensor(&mut self, level: TankLevel) {
+        self.state.lock().unwrap().level = Some(level);
+    }
+
+    /// Set the longest the pump runs after `start`, or None for no limit.
+    pub fn set_max_runtime(&mut self, max_runtime: Option<Duration>) {
+        self.state.lock().unwrap().max_runtime = max_runtime;
+    }
+
+    /// Set how long the pump may run without flow before it is stopped. Defaults to 10 seconds.
+    pub fn set_dry_run_timeout(&mut self, timeout: Duration) {
+        self.state.lock().unwrap().dry_run_timeout = timeout;
+    }
+
+    /// Limit the pump to a fraction of any window of time, e.g. 0.25 of an hour.
+    /// * `fraction` - The largest fraction of the window the pump may run, 0.0 to 1.0
+    /// * `window` - The length of the sliding window
+    pub fn set_duty_limit(&mut self, fraction: f64, window: Duration) {
+        if !(0.0..=1.0).contains(&fraction) {
+            println!("Duty fraction must be between 0.0 and 1.0");
+            return;
+        }
+        self.state.lock().unwrap().duty_limit = Some((fraction, window));
+    }
+
+    /// Remove the duty-cycle limit.
+    pub fn clear_duty_limit(&mut self) {
+        self.state.lock().unwrap().duty_limit = None;
+    }
+
+    /// Start the pump, unless a fault is latched, the tank is low, the duty-cycle limit is
+    /// reached or a manual override is active.
+    pub fn start(&mut self) -> Result<(), PumpError> {
+        let mut state = self.state.lock().unwrap();
+        state.startable()?;
+        state.start();
+        Ok(())
+    }
+
+    /// Check whether `start` would start the pump, without starting it.
+    pub fn can_start(&self) -> Result<(), PumpError> {
+        self.state.lock().unwrap().startable()
+    }
+
+    /// Stop the pump. Has no effect during a manual override.
+    pub fn stop(&mut self) {
+        let stopped = {
+            let mut state = self.state.lock().unwrap();
+            match state.manual {
+                None => state.stop(PumpStop::Requested),
+                Some(_) => None,
+            }
+        };
+        notify_stopped(&self.state, stopped);
+    }
+
+    /// Returns ``True`` if the pump is running.
+    pub fn is_running(&self) -> bool {
+        self.state.lock().unwrap().run.is_some()
+    }
+
+    /// Returns ``True`` if a dry-run fault is latched.
+    pub fn fault(&self) -> bool {
+        self.state.lock().unwrap().fault
+    }
+
+    /// Clear a latched dry-run fault, e.g. after refilling the tank or priming the pump.
+    pub fn reset_fault(&mut self) {
+        self.state.lock().unwrap().fault = false;
+    }
+
+    /// Why the pump last stopped, or None if it has not run yet.
+    pub fn last_stop(&self) -> Option<PumpStop> {
+        self.state.lock().unwrap().last_stop
+    }
+
+    /// Set the function called from a background thread whenever the pump stops.
+    pub fn when_stopped<C>(&mut self, callback: C)
+    where
+        C: FnMut(PumpStop) + Send + 'static,
+    {
+        self.state.lock().unwrap().when_stopped = Some(Box::new(callback));
+    }
+
+    /// Hold the pump on or off for at most `duration`, ignoring `start`, `stop`, the maximum
+    /// runtime and the duty-cycle limit. A manual run still stops when the pump runs dry or
+    /// the tank is low, and cannot start while a fault is latched or the tank is low.
+    /// * `on` - ``True`` to run the pump, ``False`` to keep it off
+    /// * `duration` - How long the override lasts; the pump is off when it ends
+    pub fn manual_override(&mut self, on: bool, duration: Duration) -> Result<(), PumpError> {
+        let stopped = {
+            let mut state = self.state.lock().unwrap();
+            let stopped = if on {
+                if state.fault {
+                    return Err(PumpError::Fault);
+                }
+                if !state.level_ok() {
+                    return Err(PumpError::LowLevel);
+                }
+                state.start();
+                None
+            } else {
+                state.stop(PumpStop::Requested)
+            };
+            state.manual = Some(Instant::now() + duration);
+            stopped
+        };
+        notify_stopped(&self.state, stopped);
+        Ok(())
+    }
+
+    /// End a manual override early. The pump is turned off.
+    pub fn clear_override(&mut self) {
+        let stopped = {
+            let mut state = self.state.lock().unwrap();
+            match state.manual {
+                Some(_) => state.stop(PumpStop::OverrideEnded),
+                None => None,
+            }
+        };
+        notify_stopped(&self.state, stopped);
+    }
+
+    /// Returns ``True`` while a manual override is active.
+    pub fn is_overridden(&self) -> bool {
+        self.state.lock().unwrap().manual.is_some()
+    }
+}
+
+impl Component for PumpControllerR {
+    fn values(&self) -> Vec<f64> {
+        vec![if self.is_running() { 1.0 } else { 0.0 }]
+    }
+
+    fn close(&mut self) {
+        let stopped = self.state.lock().unwrap().stop(PumpStop::Requested);
+        notify_stopped(&self.state, stopped);
+    }
+}
+
+impl Drop for PumpControllerR {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+        let stopped = self.state.lock().unwrap().stop(PumpStop::Requested);
+        notify_stopped(&self.state, stopped);
+    }
+}
+
+/// Which way a `Thermostat` drives its reading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThermostatMode {
+    /// The output raises the reading, like a heater: on below the setpoint
+    Heat,
+    /// The output lowers the reading, like a fan or dehumidifier: on above the setpoint
+    Cool,
+}
+
+/// Switches an output on and off around a setpoint, with a hysteresis band so the output
+/// does not chatter while the reading hovers near the setpoint.
+///
+/// # Example
+///
+/// ```
+/// use rust_gpiozero::*;
+///
+/// let mut thermostat = Thermostat::new(ThermostatMode::Heat, 20.0, 1.0);
+/// assert!(thermostat.update(19.0));
+/// assert!(thermostat.update(20.2)); // still inside the band
+/// assert!(!thermostat.update(20.6));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Thermostat {
+    mode: ThermostatMode,
+    setpoint: f64,
+    hysteresis: f64,
+    active: bool,
+}
+
+impl Thermostat {
+    /// Returns a Thermostat, initially off
+    /// * `mode` - Whether the output heats or cools
+    /// * `setpoint` - The reading to hold
+    /// * `hysteresis` - Width of the band around the setpoint in which the output keeps its state
+    pub fn new(mode: ThermostatMode, setpoint: f64, hysteresis: f64) -> Thermostat {
+        Thermostat {
+            mode,
+            setpoint,
+            hysteresis: hysteresis.abs(),
+            active: false,
+        }
+    }
+
+    /// Feed a reading and return whether the output should be on.
+    pub fn update(&mut self, reading: f64) -> bool {
+        let low = self.setpoint - self.hysteresis / 2.0;
+        let high = self.setpoint + self.hysteresis / 2.0;
+        match self.mode {
+            ThermostatMode::Heat if reading < low => self.active = true,
+            ThermostatMode::Heat if reading > high => self.active = false,
+            ThermostatMode::Cool if reading > high => self.active = true,
+            ThermostatMode::Cool if reading < low => self.active = false,
+            _ => {}
+        }
+        self.active
+    }
+
+    /// Returns ``True`` if the output should be on after the last reading.
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// The reading to hold.
+    pub fn setpoint(&self) -> f64 {
+        self.setpoint
+    }
+
+    /// Set the reading to hold.
+    pub fn set_setpoint(&mut self, setpoint: f64) {
+        self.setpoint = setpoint
+    }
+}
+
+/// Periods of the day when an output is on, such as the hours a grow light runs.
+///
+/// # Example
+///
+/// ```
+/// use rust_gpiozero::*;
+/// use std::time::Duration;
+///
+/// let mut schedule = DailySchedule::new();
+/// schedule.add_period((22, 0), (6, 30));
+/// assert!(schedule.is_on_at(Duration::from_secs(2 * 3600)));
+/// assert!(!schedule.is_on_at(Duration::from_secs(12 * 3600)));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DailySchedule {
+    periods: Vec<TimeOfDay>,
+    utc_offset: i32,
+}
+
+impl DailySchedule {
+    /// Returns a DailySchedule without any periods, so always off
+    pub fn new() -> DailySchedule {
+        DailySchedule::default()
+    }
+
+    /// Add a period when the output is on. A period ending before it starts runs over midnight.
+    /// * `start` - Hour and minute the period starts
+    /// * `end` - Hour and minute the period ends
+    pub fn add_period(&mut self, start: (u32, u32), end: (u32, u32)) {
+        self.periods.push(TimeOfDay::new(start, end));
+    }
+
+    /// Set the offset of local time from UTC, as for `TimeOfDay::set_utc_offset`.
+    /// Defaults to 0, so periods are in UTC.
+    pub fn set_utc_offset(&mut self, hours: f64) {
+        self.utc_offset = utc_offset_seconds(hours);
+    }
+
+    /// Returns ``True`` if a period covers the time of day given, in local time.
+    pub fn is_on_at(&self, time_of_day: Duration) -> bool {
+        self.periods
+            .iter()
+            .any(|period| period.is_active_at(time_of_day))
+    }
+
+    /// Returns ``True`` if a period covers the current time of day.
+    pub fn is_on(&self) -> bool {
+        self.is_on_at(local_time_of_day(self.utc_offset))
+    }
+}
+
+/// How a `Greenhouse` drives one of its actuators. Values are between 0.0 (off or closed)
+/// and 1.0 (on or fully open); switched outputs are on at 0.5 and above.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Strategy {
+    /// A fixed value set by the application
+    Manual(f64),
+    /// On or off following a thermostat on the temperature
+    Temperature(Thermostat),
+    /// On or off following a thermostat on the humidity
+    Humidity(Thermostat),
+    /// On during the periods of a schedule
+    Schedule(DailySchedule),
+    /// Opens in proportion to the temperature: closed at `closed` and below, fully open
+    /// at `open` and above
+    Proportional { closed: f64, open: f64 },
+}
+
+impl Strategy {
+    /// The value the actuator should take, or None if the strategy needs a missing reading.
+    fn demand(&mut self, temperature: Option<f64>, humidity: Option<f64>) -> Option<f64> {
+        let on = |active: bool| if active { 1.0 } else { 0.0 };
+        match self {
+            Strategy::Manual(value) => Some(value.clamp(0.0, 1.0)),
+            Strategy::Temperature(thermostat) => Some(on(thermostat.update(temperature?))),
+            Strategy::Humidity(thermostat) => Some(on(thermostat.update(humidity?))),
+            Strategy::Schedule(schedule) => Some(on(schedule.is_on())),
+            Strategy::Proportional { closed, open } => {
+                let temperature = temperature?;
+                Some(((temperature - *closed) / (*open - *closed)).clamp(0.0, 1.0))
+            }
+        }
+    }
+}
+
+type ReadingFn = Box<dyn FnMut() -> Option<f64> + Send>;
+type VentFn = Box<dyn FnMut(f64) + Send>;
+type GreenhouseInterlock = (String, Box<dyn Fn(&GreenhouseOutputs) -> bool + Send>);
+
+/// The requested states of the switched outputs of a `Greenhouse`, checked by its interlocks.
+/// Outputs that are not fitted are ``False``.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GreenhouseOutputs {
+    pub heater: bool,
+    pub fan: bool,
+    pub lights: bool,
+}
+
+struct Actuator {
+    output: Box<dyn OutputComponent>,
+    strategy: Strategy,
+}
+
+/// A greenhouse controller driving a heater, a fan, grow lights and a vent from temperature
+/// and humidity sensors.
+///
+/// Each actuator follows its own `Strategy`, such as a `Thermostat` for the heater, a
+/// humidity thermostat for the fan, a `DailySchedule` for the lights and proportional
+/// opening for the vent. Every `update` reads the sensors, works out the new states and
+/// applies the switched outputs as one `Transaction`, so an interlock such as "never heat
+/// with the fan running" blocks the whole change. An actuator whose strategy needs a
+/// reading the sensors could not provide is turned off.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+/// use std::thread::sleep;
+/// use std::time::Duration;
+///
+/// let mut lights = DailySchedule::new();
+/// lights.add_period((6, 0), (20, 0));
+///
+/// let mut greenhouse = Greenhouse::new(|| Some(21.5));
+/// greenhouse.set_heater(
+///     DigitalOutputDevice::new(17),
+///     Strategy::Temperature(Thermostat::new(ThermostatMode::Heat, 18.0, 1.0)),
+/// );
+/// greenhouse.set_fan(
+///     DigitalOutputDevice::new(27),
+///     Strategy::Temperature(Thermostat::new(ThermostatMode::Cool, 28.0, 2.0)),
+/// );
+/// greenhouse.set_lights(DigitalOutputDevice::new(22), Strategy::Schedule(lights));
+/// greenhouse.set_vent(Servo::new(18), Strategy::Proportional { closed: 24.0, open: 32.0 });
+/// greenhouse.interlock("no heating while ventilating", |o| !(o.heater && o.fan));
+///
+/// loop {
+///     if let Err(e) = greenhouse.update() {
+///         println!("{}", e);
+///     }
+///     sleep(Duration::from_secs(30));
+/// }
+/// ```
+pub struct Greenhouse {
+    temperature: ReadingFn,
+    humidity: Option<ReadingFn>,
+    readings: (Option<f64>, Option<f64>),
+    heater: Option<Actuator>,
+    fan: Option<Actuator>,
+    lights: Option<Actuator>,
+    vent: Option<(VentFn, Strategy, f64)>,
+    interlocks: Vec<GreenhouseInterlock>,
+}
+
+impl Greenhouse {
+    /// Returns a Greenhouse without actuators
+    /// * `temperature` - Returns the temperature, or None if the sensor gave no reading
+    pub fn new<F>(temperature: F) -> Greenhouse
+    where
+        F: FnMut() -> Option<f64> + Send + 'static,
+    {
+        Greenhouse {
+            temperature: Box::new(temperature),
+            humidity: None,
+            readings: (None, None),
+            heater: None,
+            fan: None,
+            lights: None,
+            vent: None,
+            interlocks: Vec::new(),
+        }
+    }
+
+    /// Use a humidity sensor, for `Strategy::Humidity`.
+    /// * `humidity` - Returns the relative humidity, or None if the sensor gave no reading
+    pub fn set_humidity_sensor<F>(&mut self, humidity: F)
+    where
+        F: FnMut() -> Option<f64> + Send + 'static,
+    {
+        self.humidity = Some(Box::new(humidity));
+    }
+
+    /// Fit a heater, e.g. a relay on a `DigitalOutputDevice`.
+    pub fn set_heater<O: OutputComponent + 'static>(&mut self, heater: O, strategy: Strategy) {
+        self.heater = Some(Actuator {
+            output: Box::new(heater),
+            strategy,
+        });
+    }
+
+    /// Fit a fan.
+    pub fn set_fan<O: OutputComponent + 'static>(&mut self, fan: O, strategy: Strategy) {
+        self.fan = Some(Actuator {
+            output: Box::new(fan),
+            strategy,
+        });
+    }
+
+    /// Fit grow lights.
+    pub fn set_lights<O: OutputComponent + 'static>(&mut self, lights: O, strategy: Strategy) {
+        self.lights = Some(Actuator {
+            output: Box::new(lights),
+            strategy,
+        });
+    }
+
+    /// Fit a vent opened by a servo, which moves from its minimum (closed) to its maximum
+    /// position (fully open).
+    pub fn set_vent(&mut self, mut servo: Servo, strategy: Strategy) {
+        self.set_vent_actuator(
+            move |opening| servo.set_value(opening * 2.0 - 1.0),
+            strategy,
+        )
+    }
+
+    /// Fit a vent or window moved by any actuator, such as a linear actuator on a `Motor`.
+    /// * `actuator` - Called with the opening between 0.0 (closed) and 1.0 (fully open)
+    ///   whenever it changes
+    pub fn set_vent_actuator<F>(&mut self, actuator: F, strategy: Strategy)
+    where
+        F: FnMut(f64) + Send + 'static,
+    {
+        self.vent = Some((Box::new(actuator), strategy, f64::NAN));
+    }
+
+    /// Change the strategy of an actuator, e.g. to `Strategy::Manual` for maintenance.
+    /// * `actuator` - `heater`, `fan`, `lights` or `vent`
+    pub fn set_strategy(&mut self, actuator: &str, strategy: Strategy) -> Result<(), String> {
+        let slot = match actuator {
+            "heater" => self.heater.as_mut().map(|a| &mut a.strategy),
+            "fan" => self.fan.as_mut().map(|a| &mut a.strategy),
+            "lights" => self.lights.as_mut().map(|a| &mut a.strategy),
+            "vent" => self.vent.as_mut().map(|v| &mut v.1),
+            _ => return Err(format!("unknown actuator '{}'", actuator)),
+        };
+        match slot {
+            Some(slot) => {
+                *slot = strategy;
+                Ok(())
+            }
+            None => Err(format!("no {} is fitted", actuator)),
+        }
+    }
+
+    /// Add a check that the requested states must pass before any switched output changes.
+    /// * `name` - Name reported when the interlock trips
+    /// * `check` - Receives the requested states of the heater, fan and lights
+    pub fn interlock<F>(&mut self, name: &str, check: F)
+    where
+        F: Fn(&GreenhouseOutputs) -> bool + Send + 'static,
+    {
+        self.interlocks.push((name.to_string(), Box::new(check)));
+    }
+
+    /// Read the sensors and drive every actuator according to its strategy. If an interlock
+    /// trips, none of the switched outputs change and the vent keeps its position.
+    pub fn update(&mut self) -> Result<(), TransactionError> {
+        let temperature = (self.temperature)().filter(|t| t.is_finite());
+        let humidity = match &mut self.humidity {
+            Some(read) => read().filter(|h| h.is_finite()),
+            None => None,
+        };
+        self.readings = (temperature, humidity);
+
+        let demand = |actuator: &mut Option<Actuator>| match actuator {
+            Some(actuator) => actuator
+                .strategy
+                .demand(temperature, humidity)
+                .is_some_and(|value| value >= 0.5),
+            None => false,
+        };
+        let requested = GreenhouseOutputs {
+            heater: demand(&mut self.heater),
+            fan: demand(&mut self.fan),
+            lights: demand(&mut self.lights),
+        };
+        let vent = match &mut self.vent {
+            Some((_, strategy, _)) => strategy.demand(temperature, humidity).unwrap_or(0.0),
+            None => 0.0,
+        };
+
+        let states = [requested.heater, requested.fan, requested.lights];
+        let actuators = [&mut self.heater, &mut self.fan, &mut self.lights];
+        // where each fitted output is in the transaction, for the interlocks
+        let mut positions = [None; 3];
+        let mut count = 0;
+        let mut transaction = Transaction::new();
+        for (i, (actuator, active)) in IntoIterator::into_iter(actuators).zip(states).enumerate() {
+            if let Some(actuator) = actuator {
+                positions[i] = Some(count);
+                count += 1;
+                transaction = transaction.set(&mut actuator.output, active);
+            }
+        }
+        for (name, check) in &self.interlocks {
+            transaction = transaction.interlock(name, move |states: &[bool]| {
+                let state = |position: Option<usize>| position.is_some_and(|i| states[i]);
+                check(&GreenhouseOutputs {
+                    heater: state(positions[0]),
+                    fan: state(positions[1]),
+                    lights: state(positions[2]),
+                })
+            });
+        }
+        transaction.apply()?;
+
+        if let Some((actuator, _, opening)) = &mut self.vent {
+            if *opening != vent {
+                actuator(vent);
+                *opening = vent;
+            }
+        }
+        Ok(())
+    }
+
+    /// The temperature read by the last `update`.
+    pub fn temperature(&self) -> Option<f64> {
+        self.readings.0
+    }
+
+    /// The humidity read by the last `update`.
+    pub fn humidity(&self) -> Option<f64> {
+        self.readings.1
+    }
+
+    /// The states of the heater, fan and lights.
+    pub fn outputs(&self) -> GreenhouseOutputs {
+        let active = |actuator: &Option<Actuator>| {
+            actuator
+                .as_ref()
+                .is_some_and(|actuator| actuator.output.is_active())
+        };
+        GreenhouseOutputs {
+            heater: active(&self.heater),
+            fan: active(&self.fan),
+            lights: active(&self.lights),
+        }
+    }
+
+    /// The opening of the vent set by the last `update`, or None if no vent is fitted.
+    pub fn vent_opening(&self) -> Option<f64> {
+        self.vent
+            .as_ref()
+            .map(|(_, _, opening)| if opening.is_nan() { 0.0 } else { *opening })
+    }
+}
+
+impl Component for Greenhouse {
+    fn values(&self) -> Vec<f64> {
+        let outputs = self.outputs();
+        let on = |active: bool| if active { 1.0 } else { 0.0 };
+        vec![
+            on(outputs.heater),
+            on(outputs.fan),
+            on(outputs.lights),
+            self.vent_opening().unwrap_or(0.0),
+        ]
+    }
+
+    fn close(&mut self) {
+        for actuator in
+            IntoIterator::into_iter([&mut self.heater, &mut self.fan, &mut self.lights]).flatten()
+        {
+            actuator.output.off();
+        }
+    }
+}
+
+/// The gains of a `Pid` controller.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PidGains {
+    /// Proportional gain, in output per unit of error
+    pub kp: f64,
+    /// Integral gain, in output per unit of error per second
+    pub ki: f64,
+    /// Derivative gain, in output per unit of error per second of change
+    pub kd: f64,
+}
+
+/// A PID controller driving a measurement towards a setpoint.
+///
+/// The output is clamped to the output limits, 0.0 to 1.0 by default, and so is the
+/// integral term, which keeps it from winding up while the output is saturated. The
+/// derivative term acts on the measurement rather than the error, so changing the
+/// setpoint does not kick the output.
+///
+/// # Example
+///
+/// ```
+/// use rust_gpiozero::*;
+/// use std::time::Duration;
+///
+/// let mut pid = Pid::new(PidGains { kp: 0.5, ki: 0.01, kd: 0.0 }, 37.5);
+/// assert_eq!(pid.update(30.0, Duration::from_secs(1)), 1.0);
+/// assert!(pid.update(37.4, Duration::from_secs(1)) < 0.2);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Pid {
+    gains: PidGains,
+    setpoint: f64,
+    limits: (f64, f64),
+    integral: f64,
+    last_measurement: Option<f64>,
+}
+
+impl Pid {
+    /// Returns a Pid with output limits of 0.0 and 1.0
+    /// * `gains` - The proportional, integral and derivative gains
+    /// * `setpoint` - The value the measurement is driven towards
+    pub fn new(gains: PidGains, setpoint: f64) -> Pid {
+        Pid {
+            gains,
+            setpoint,
+            limits: (0.0, 1.0),
+            integral: 0.0,
+            last_measurement: None,
+        }
+    }
+
+    /// The gains of the controller.
+    pub fn gains(&self) -> PidGains {
+        self.gains
+    }
+
+    /// Set the gains of the controller.
+    pub fn set_gains(&mut self, gains: PidGains) {
+        self.gains = gains;
+    }
+
+    /// The value the measurement is driven towards.
+    pub fn setpoint(&self) -> f64 {
+        self.setpoint
+    }
+
+    /// Set the value the measurement is driven towards.
+    pub fn set_setpoint(&mut self, setpoint: f64) {
+        self.setpoint = setpoint;
+    }
+
+    /// Set the range the output is clamped to.
+    /// * `min` - The lowest output
+    /// * `max` - The highest output, greater than `min`
+    pub fn set_output_limits(&mut self, min: f64, max: f64) {
+        if min >= max {
+            println!("Output limits must have min less than max");
+            return;
+        }
+        self.limits = (min, max);
+        self.integral = self.integral.clamp(min, max);
+    }
+
+    /// Forget the integral term and the last measurement.
+    pub fn reset(&mut self) {
+        self.integral = 0.0;
+        self.last_measurement = None;
+    }
+
+    /// Returns the output for a new measurement.
+    /// * `measurement` - The current value of the measurement
+    /// * `dt` - The time since the previous measurement
+    pub fn update(&mut self, measurement: f64, dt: Duration) -> f64 {
+        let (min, max) = self.limits;
+        let error = self.setpoint - measurement;
+        let dt = dt.as_secs_f64();
+        let mut derivative = 0.0;
+        if dt > 0.0 {
+            self.integral = (self.integral + self.gains.ki * error * dt).clamp(min, max);
+            if let Some(last) = self.last_measurement {
+                derivative = -self.gains.kd * (measurement - last) / dt;
+            }
+        }
+        self.last_measurement = Some(measurement);
+        (self.gains.kp * error + self.integral + derivative).clamp(min, max)
+    }
+}
+
+/// Finds PID gains by switching an output fully on and off around the setpoint and
+/// measuring the oscillation that follows, the relay method of Åström and Hägglund.
+///
+/// The first full oscillation is discarded as the process settles; the amplitude and period
+/// of the following ones are averaged into the ultimate gain and period, which are turned
+/// into gains with the Ziegler-Nichols "no overshoot" rule.
+///
+/// # Example
+///
+/// ```
+/// use rust_gpiozero::*;
+/// use std::time::{Duration, Instant};
+///
+/// let mut autotune = RelayAutotune::new(37.5, 0.2, 3);
+/// let start = Instant::now();
+/// let (mut temperature, mut heating) = (30.0, false);
+/// for second in 0..600 {
+///     // a heater whose effect lags one second behind the relay
+///     temperature += if heating { 0.25 } else { -0.15 };
+///     heating = autotune.update_at(start + Duration::from_secs(second), temperature);
+/// }
+/// assert!(autotune.gains().is_some());
+/// ```
+#[derive(Debug, Clone)]
+pub struct RelayAutotune {
+    setpoint: f64,
+    hysteresis: f64,
+    cycles: usize,
+    on: bool,
+    last_on: Option<Instant>,
+    settled: bool,
+    high: f64,
+    low: f64,
+    oscillations: Vec<(f64, f64)>,
+}
+
+impl RelayAutotune {
+    /// Returns a RelayAutotune
+    /// * `setpoint` - The value the output oscillates around
+    /// * `hysteresis` - How far the measurement must pass the setpoint before the output
+    ///   switches, larger than the noise of the measurement
+    /// * `cycles` - The number of oscillations averaged
+    pub fn new(setpoint: f64, hysteresis: f64, cycles: usize) -> RelayAutotune {
+        RelayAutotune {
+            setpoint,
+            hysteresis: hysteresis.abs(),
+            cycles: cycles.max(1),
+            on: false,
+            last_on: None,
+            settled: false,
+            high: f64::NEG_INFINITY,
+            low: f64::INFINITY,
+            oscillations: Vec::new(),
+        }
+    }
+
+    /// Returns ``True`` if the output should be on, given a new measurement.
+    /// * `at` - When the measurement was taken
+    /// * `measurement` - The current value of the measurement
+    pub fn update_at(&mut self, at: Instant, measurement: f64) -> bool {
+        self.high = self.high.max(measurement);
+        self.low = self.low.min(measurement);
+        if self.on && measurement > self.setpoint + self.hysteresis {
+            self.on = false;
+        } else if !self.on && measurement < self.setpoint - self.hysteresis {
+            self.on = true;
+            if let Some(last_on) = self.last_on {
+                if self.settled {
+                    let amplitude = (self.high - self.low) / 2.0;
+                    let period = at.duration_since(last_on).as_secs_f64();
+                    self.oscillations.push((amplitude, period));
+                }
+                self.settled = true;
+            }
+            self.last_on = Some(at);
+            self.high = measurement;
+            self.low = measurement;
+        }
+        self.on
+    }
+
+    /// Returns ``True`` once enough oscillations have been measured.
+    pub fn is_done(&self) -> bool {
+        self.oscillations.len() >= self.cycles
+    }
+
+    /// The gains found, or None until enough oscillations have been measured.
+    pub fn gains(&self) -> Option<PidGains> {
+        if !self.is_done() {
+            return None;
+        }
+        let count = self.oscillations.len() as f64;
+        let amplitude = self.oscillations.iter().map(|o| o.0).sum::<f64>() / count;
+        let period = self.oscillations.iter().map(|o| o.1).sum::<f64>() / count;
+        if amplitude <= 0.0 || period <= 0.0 {
+            return None;
+        }
+        // the relay swings the output by 0.5 either side of its middle
+        let ultimate_gain = 4.0 * 0.5 / (PI * amplitude);
+        Some(PidGains {
+            kp: 0.2 * ultimate_gain,
+            ki: 0.4 * ultimate_gain / period,
+            kd: 0.2 * ultimate_gain * period / 3.0,
+        })
+    }
+}
+
+/// Reasons an `Incubator` turned its heater off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IncubatorFault {
+    /// The temperature sensor could not be read
+    SensorFailure,
+    /// The temperature reached the safety limit; latched until `reset_fault`
+    OverTemperature,
+}
+
+impl fmt::Display for IncubatorFault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IncubatorFault::SensorFailure => write!(f, "temperature sensor could not be read"),
+            IncubatorFault::OverTemperature => {
+                write!(
+                    f,
+                    "temperature reached the safety limit; reset the fault first"
+                )
+            }
+        }
+    }
+}
+
+impl Error for IncubatorFault {}
+
+/// Switches a heater on for a fraction of every window of time.
+struct SlowPwm {
+    heater: Box<dyn OutputComponent>,
+    duty: f64,
+    window: Duration,
+    watchdog: Duration,
+    fed: Instant,
+    start: Instant,
+    on: bool,
+}
+
+impl SlowPwm {
+    fn set_duty(&mut self, duty: f64) {
+        self.duty = duty;
+        self.fed = Instant::now();
+        self.tick();
+    }
+
+    fn tick(&mut self) {
+        let now = Instant::now();
+        let window = self.window.as_secs_f64();
+        let phase = now.duration_since(self.start).as_secs_f64() % window;
+        let starved = now.duration_since(self.fed) > self.watchdog;
+        let on = !starved && phase < self.duty * window;
+        if on != self.on {
+            if on {
+                self.heater.on()
+            } else {
+                self.heater.off()
+            }
+            self.on = on;
+        }
+    }
+}
+
+/// A precision temperature controller for incubators and sous-vide cookers, driving a
+/// heater through a solid-state relay.
+///
+/// Every `update` reads the temperature and works out the heater's duty cycle with a `Pid`
+/// controller. A background thread switches the relay on for that fraction of every window
+/// of a few seconds, which suits the zero-crossing SSRs that cannot follow fast PWM.
+///
+/// Safety comes first:
+///
+/// * a temperature at or above the safety limit turns the heater off and latches a fault
+///   that must be cleared with `reset_fault`
+/// * a sensor that cannot be read turns the heater off until it can be read again
+/// * above the setpoint plus the overshoot limit the heater is off and the integral term is
+///   cleared, so the controller does not push past the setpoint while recovering
+/// * a watchdog turns the heater off if `update` stops being called, such as when the
+///   program hangs
+///
+/// `start_autotune` finds the gains with a `RelayAutotune` and switches to them when done.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+/// use std::thread::sleep;
+/// use std::time::Duration;
+///
+/// let mut sous_vide = Incubator::new(DigitalOutputDevice::new(17), || Some(55.2), 56.5);
+/// sous_vide.set_max_temperature(60.0);
+/// sous_vide.start_autotune(0.3, 4);
+///
+/// loop {
+///     match sous_vide.update() {
+///         Ok(duty) => println!("{:?} heater at {:.0}%", sous_vide.temperature(), duty * 100.0),
+///         Err(fault) => println!("{}", fault),
+///     }
+///     sleep(Duration::from_secs(1));
+/// }
+/// ```
+pub struct Incubator {
+    sensor: ReadingFn,
+    pid: Pid,
+    autotune: Option<RelayAutotune>,
+    output: Arc<Mutex<SlowPwm>>,
+    max_temperature: f64,
+    overshoot: f64,
+    fault: Option<IncubatorFault>,
+    temperature: Option<f64>,
+    last_update: Option<Instant>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Incubator {
+    /// Returns an Incubator with a safety limit 5 degrees above the setpoint, an overshoot
+    /// limit of 0.5 degrees, a 5 second window and a 30 second watchdog. The default gains
+    /// are cautious; tune them or run `start_autotune`.
+    /// * `heater` - The output switching the heater's relay
+    /// * `sensor` - Reads the temperature, or returns None if it cannot be read
+    /// * `setpoint` - The temperature to hold
+    pub fn new<H, S>(mut heater: H, sensor: S, setpoint: f64) -> Incubator
+    where
+        H: OutputComponent + 'static,
+        S: FnMut() -> Option<f64> + Send + 'static,
+    {
+        heater.off();
+        let now = Instant::now();
+        let output = Arc::new(Mutex::new(SlowPwm {
+            heater: Box::new(heater),
+            duty: 0.0,
+            window: Duration::from_secs(5),
+            watchdog: Duration::from_secs(30),
+            fed: now,
+            start: now,
+            on: false,
+        }));
+        let running = Arc::new(AtomicBool::new(true));
+        let handle = {
+            let output = Arc::clone(&output);
+            let running = Arc::clone(&running);
+            thread::spawn(move || {
+                while running.load(Ordering::SeqCst) {
+                    output.lock().unwrap().tick();
+                    sleep_while_running(&running, Duration::from_millis(50));
+                }
+            })
+        };
+        Incubator {
+            sensor: Box::new(sensor),
+            pid: Pid::new(
+                PidGains {
+                    kp: 0.2,
+                    ki: 0.002,
+                    kd: 0.0,
+                },
+                setpoint,
+            ),
+            autotune: None,
+            output,
+            max_temperature: setpoint + 5.0,
+            overshoot: 0.5,
+            fault: None,
+            temperature: None,
+            last_update: None,
+            running,
+            handle: Some(handle),
+        }
+    }
+
+    /// The temperature to hold.
+    pub fn setpoint(&self) -> f64 {
+        self.pid.setpoint()
+    }
+
+    /// Set the temperature to hold. The safety limit is not moved.
+    pub fn set_setpoint(&mut self, setpoint: f64) {
+        self.pid.set_setpoint(setpoint);
+    }
+
+    /// The gains of the PID controller.
+    pub fn gains(&self) -> PidGains {
+        self.pid.gains()
+    }
+
+    /// Set the gains of the PID controller.
+    pub fn set_gains(&mut self, gains: PidGains) {
+        self.pid.set_gains(gains);
+    }
+
+    /// Set the temperature at which the heater is turned off and a fault latched.
+    pub fn set_max_temperature(&mut self, max_temperature: f64) {
+        self.max_temperature = max_temperature;
+    }
+
+    /// Set how far above the setpoint the heater is held off and the integral term cleared.
+    /// * `overshoot` - Degrees above the setpoint, 0.0 or more
+    pub fn set_overshoot_limit(&mut self, overshoot: f64) {
+        if overshoot < 0.0 {
+            println!("Overshoot limit must be 0 or more");
+            return;
+        }
+        self.overshoot = overshoot;
+    }
+
+    /// Set the time-proportioning window: the heater is on for the duty cycle's share of
+    /// every window. Defaults to 5 seconds.
+    pub fn set_window(&mut self, window: Duration) {
+        if window.is_zero() {
+            println!("Window must be greater than 0");
+            return;
+        }
+        self.output.lock().unwrap().window = window;
+    }
+
+    /// Set how long the heater may be driven without an `update` before the watchdog turns
+    /// it off. Defaults to 30 seconds.
+    pub fn set_watchdog(&mut self, timeout: Duration) {
+        self.output.lock().unwrap().watchdog = timeout;
+    }
+
+    /// Find the gains by switching the heater fully on and off around the setpoint; the new
+    /// gains are used once the oscillations have been measured.
+    /// * `hysteresis` - How far the temperature must pass the setpoint before the heater
+    ///   switches, larger than the noise of the sensor
+    /// * `cycles` - The number of oscillations averaged
+    pub fn start_autotune(&mut self, hysteresis: f64, cycles: usize) {
+        self.autotune = Some(RelayAutotune::new(self.pid.setpoint(), hysteresis, cycles));
+    }
+
+    /// Returns ``True`` while the gains are being found.
+    pub fn is_autotuning(&self) -> bool {
+        self.autotune.is_some()
+    }
+
+    /// Read the temperature and set the heater's duty cycle. Returns the duty cycle, or the
+    /// fault that turned the heater off.
+    pub fn update(&mut self) -> Result<f64, IncubatorFault> {
+        let now = Instant::now();
+        let dt = self
+            .last_update
+            .replace(now)
+            .map_or(Duration::ZERO, |last| now.duration_since(last));
+        if let Some(fault) = self.fault {
+            self.set_duty(0.0);
+            return Err(fault);
+        }
+        let temperature = match (self.sensor)() {
+            Some(temperature) if temperature.is_finite() => temperature,
+            _ => {
+                self.temperature = None;
+                self.set_duty(0.0);
+                return Err(IncubatorFault::SensorFailure);
+            }
+        };
+        self.temperature = Some(temperature);
+        if temperature >= self.max_temperature {
+            self.fault = Some(IncubatorFault::OverTemperature);
+            self.autotune = None;
+            self.pid.reset();
+            self.set_duty(0.0);
+            return Err(IncubatorFault::OverTemperature);
+        }
+        let duty = if let Some(autotune) = &mut self.autotune {
+            let on = autotune.update_at(now, temperature);
+            if let Some(gains) = autotune.gains() {
+                self.pid.set_gains(gains);
+                self.pid.reset();
+                self.autotune = None;
+            }
+            if on {
+                1.0
+            } else {
+                0.0
+            }
+        } else if temperature > self.pid.setpoint() + self.overshoot {
+            self.pid.reset();
+            0.0
+        } else {
+            self.pid.update(temperature, dt)
+        };
+        self.set_duty(duty);
+        Ok(duty)
+    }
+
+    fn set_duty(&mut self, duty: f64) {
+        self.output.lock().unwrap().set_duty(duty);
+    }
+
+    /// The temperature read by the last `update`, or None if it could not be read.
+    pub fn temperature(&self) -> Option<f64> {
+        self.temperature
+    }
+
+    /// The heater's duty cycle, between 0.0 and 1.0.
+    pub fn duty(&self) -> f64 {
+        self.output.lock().unwrap().duty
+    }
+
+    /// The latched fault, if any.
+    pub fn fault(&self) -> Option<IncubatorFault> {
+        self.fault
+    }
+
+    /// Clear a latched fault so the heater can run again.
+    pub fn reset_fault(&mut self) {
+        self.fault = None;
+    }
+}
+
+impl Component for Incubator {
+    fn values(&self) -> Vec<f64> {
+        vec![self.duty()]
+    }
+
+    fn close(&mut self) {
+        self.autotune = None;
+        self.set_duty(0.0);
+    }
+}
+
+impl Drop for Incubator {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+        self.output.lock().unwrap().heater.off();
+    }
+}
+
+/// The position of a `CoopDoor`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoorState {
+    /// Resting on the open limit switch
+    Open,
+    /// Resting on the closed limit switch
+    Closed,
+    /// Moving towards the open limit switch
+    Opening,
+    /// Moving towards the closed limit switch
+    Closing,
+    /// Stopped between the limit switches
+    Stopped,
+}
+
+/// Reasons a `CoopDoor` stopped its motor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoorFault {
+    /// The motor drew more than the current limit
+    Stalled,
+    /// The door did not reach its limit switch within the travel timeout
+    Timeout,
+    /// Both limit switches are active at once, so one of them is faulty
+    LimitSwitches,
+}
+
+impl fmt::Display for DoorFault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DoorFault::Stalled => write!(f, "door motor stalled"),
+            DoorFault::Timeout => write!(f, "door did not reach its limit switch in time"),
+            DoorFault::LimitSwitches => write!(f, "both door limit switches are active"),
+        }
+    }
+}
+
+impl Error for DoorFault {}
+
+/// How long after the motor starts its inrush current is ignored.
+const INRUSH_TIME: Duration = Duration::from_millis(500);
+
+type FaultCallback = Box<dyn FnMut(DoorFault) + Send>;
+
+struct DoorControl {
+    motor: Motor,
+    open_limit: DigitalInputDeviceR,
+    closed_limit: DigitalInputDeviceR,
+    schedule: Option<SunTimes>,
+    day: Option<bool>,
+    current: Option<(ReadingFn, f64)>,
+    travel_timeout: Duration,
+    motion: Option<(bool, Instant)>,
+    fault: Option<DoorFault>,
+    when_fault: Option<FaultCallback>,
+}
+
+impl DoorControl {
+    fn state(&self) -> DoorState {
+        match self.motion {
+            Some((true, _)) => DoorState::Opening,
+            Some((false, _)) => DoorState::Closing,
+            None if self.open_limit.is_active() => DoorState::Open,
+            None if self.closed_limit.is_active() => DoorState::Closed,
+            None => DoorState::Stopped,
+        }
+    }
+
+    fn command(&mut self, open: bool) -> Result<(), DoorFault> {
+        if let Some(fault) = self.fault {
+            return Err(fault);
+        }
+        self.start(open);
+        Ok(())
+    }
+
+    fn start(&mut self, open: bool) {
+        let limit = if open {
+            &self.open_limit
+        } else {
+            &self.closed_limit
+        };
+        if limit.is_active() {
+            self.stop();
+            return;
+        }
+        if open {
+            self.motor.forward()
+        } else {
+            self.motor.backward()
+        }
+        self.motion = Some((open, Instant::now()));
+    }
+
+    fn stop(&mut self) {
+        self.motor.stop();
+        self.motion = None;
+    }
+
+    fn toggle(&mut self) {
+        if self.fault.is_some() {
+            return;
+        }
+        match self.state() {
+            DoorState::Opening | DoorState::Closing => self.stop(),
+            DoorState::Open => self.start(false),
+            DoorState::Closed | DoorState::Stopped => self.start(true),
+        }
+    }
+
+    /// Stop the door and latch the fault, returning it so that the caller can call
+    /// `when_fault` once the control is unlocked.
+    fn trip(&mut self, fault: DoorFault) -> Option<DoorFault> {
+        let closing = matches!(self.motion, Some((false, _)));
+        self.stop();
+        self.fault = Some(fault);
+        // back off from whatever blocked the door, which may be a hen
+        if closing && fault == DoorFault::Stalled {
+            self.start(true);
+        }
+        Some(fault)
+    }
+
+    /// Checks run by the supervision thread. Returns the fault if one was tripped.
+    fn supervise(&mut self) -> Option<DoorFault> {
+        let open = self.open_limit.is_active();
+        let closed = self.closed_limit.is_active();
+        if open && closed {
+            if self.fault != Some(DoorFault::LimitSwitches) {
+                return self.trip(DoorFault::LimitSwitches);
+            }
+            return None;
+        }
+
+        if let Some(schedule) = &self.schedule {
+            let day = schedule.is_day_at(SystemTime::now());
+            if self.day.replace(day) != Some(day) && self.fault.is_none() {
+                self.start(day);
+            }
+        }
+
+        let (opening, started) = self.motion?;
+        if (opening && open) || (!opening && closed) {
+            self.stop();
+            return None;
+        }
+        let elapsed = started.elapsed();
+        if elapsed > self.travel_timeout {
+            return self.trip(DoorFault::Timeout);
+        }
+        if elapsed > INRUSH_TIME {
+            if let Some((read, limit)) = &mut self.current {
+                if read().is_some_and(|current| current > *limit) {
+                    return self.trip(DoorFault::Stalled);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Call `when_fault` for a fault just tripped. The callback is taken out of the control so
+/// that it runs without the control locked.
+fn notify_fault(control: &Mutex<DoorControl>, fault: Option<DoorFault>) {
+    let fault = match fault {
+        Some(fault) => fault,
+        None => return,
+    };
+    let callback = control.lock().unwrap().when_fault.take();
+    if let Some(mut callback) = callback {
+        callback(fault);
+        // keep a callback set while this one was running
+        let mut control = control.lock().unwrap();
+        if control.when_fault.is_none() {
+            control.when_fault = Some(callback);
+        }
+    }
+}
+
+/// A chicken coop door driven by a motor or linear actuator between two limit switches.
+///
+/// The door opens at sunrise and closes at sunset when given a `SunTimes` schedule, and a
+/// button opens, closes or stops it by hand; the schedule takes over again at the next
+/// sunrise or sunset. A background thread stops the motor at the limit switches and
+/// watches for trouble:
+///
+/// * a door that has not reached its limit switch within the travel timeout is stopped
+/// * with a current sensor fitted, a motor drawing more than the current limit is stopped;
+///   a door stalling while closing opens again so it does not crush whatever is in the way
+/// * both limit switches active at once means one of them is faulty, and the motor is
+///   stopped
+///
+/// Faults are latched: the door does not move on its own again until `reset_fault`.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+/// use std::time::Duration;
+///
+/// let mut sun = SunTimes::new(52.2, 0.12);
+/// sun.set_offsets(Duration::from_secs(15 * 60), Duration::from_secs(30 * 60));
+///
+/// let mut door = CoopDoor::new(Motor::new(17, 18), 5, 6);
+/// door.set_schedule(sun);
+/// door.set_button(26);
+/// door.set_travel_timeout(Duration::from_secs(20));
+/// door.when_fault(|fault| println!("coop door: {}", fault));
+/// ```
+pub struct CoopDoor {
+    control: Arc<Mutex<DoorControl>>,
+    button: Option<Button>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl CoopDoor {
+    /// Returns a CoopDoor with a travel timeout of 30 seconds. The limit switches are wired
+    /// between their pins and ground.
+    /// * `motor` - The motor, which opens the door when driven forward
+    /// * `open_limit` - The GPIO pin which the limit switch at the open position is attached to
+    /// * `closed_limit` - The GPIO pin which the limit switch at the closed position is attached to
+    pub fn new(mut motor: Motor, open_limit: u8, closed_limit: u8) -> CoopDoor {
+        motor.stop();
+        let control = Arc::new(Mutex::new(DoorControl {
+            motor,
+            open_limit: DigitalInputDeviceR::new_active_low(open_limit),
+            closed_limit: DigitalInputDeviceR::new_active_low(closed_limit),
+            schedule: None,
+            day: None,
+            current: None,
+            travel_timeout: Duration::from_secs(30),
+            motion: None,
+            fault: None,
+            when_fault: None,
+        }));
+        let running = Arc::new(AtomicBool::new(true));
+        let handle = {
+            let control = Arc::clone(&control);
+            let running = Arc::clone(&running);
+            thread::spawn(move || {
+                while running.load(Ordering::SeqCst) {
+                    let fault = control.lock().unwrap().supervise();
+                    notify_fault(&control, fault);
+                    sleep_while_running(&running, Duration::from_millis(20));
+                }
+            })
+        };
+        CoopDoor {
+            control,
+            button: None,
+            running,
+            handle: Some(handle),
+        }
+    }
+
+    /// Open the door at sunrise and close it at sunset. The door is moved to match the
+    /// schedule straight away.
+    pub fn set_schedule(&mut self, schedule: SunTimes) {
+        let mut control = self.control.lock().unwrap();
+        control.schedule = Some(schedule);
+        control.day = None;
+    }
+
+    /// Use a button wired between a pin and ground to open, close or stop the door by hand.
+    /// Pressing it while the door moves stops it; otherwise the door closes if it is open
+    /// and opens if it is not.
+    /// * `pin` - The GPIO pin which the button is attached to
+    pub fn set_button(&mut self, pin: u8) {
+        let mut button = Button::new(pin);
+        let control = Arc::clone(&self.control);
+        button.when_pressed(move || control.lock().unwrap().toggle());
+        self.button = Some(button);
+    }
+
+    /// Watch the motor current; a current above the limit, after the inrush when the motor
+    /// starts, means the door is stalled.
+    /// * `read` - Reads the motor current, or returns None if it cannot be read
+    /// * `limit` - The highest current of a free-running door, in the units of `read`
+    pub fn set_current_sense<F>(&mut self, read: F, limit: f64)
+    where
+        F: FnMut() -> Option<f64> + Send + 'static,
+    {
+        self.control.lock().unwrap().current = Some((Box::new(read), limit));
+    }
+
+    /// Set how long the door may take to reach a limit switch. Defaults to 30 seconds.
+    pub fn set_travel_timeout(&mut self, timeout: Duration) {
+        self.control.lock().unwrap().travel_timeout = timeout;
+    }
+
+    /// Set the function called from a background thread when a fault stops the motor.
+    pub fn when_fault<C>(&mut self, callback: C)
+    where
+        C: FnMut(DoorFault) + Send + 'static,
+    {
+        self.control.lock().unwrap().when_fault = Some(Box::new(callback));
+    }
+
+    /// Open the door, unless a fault is latched.
+    pub fn open_door(&mut self) -> Result<(), DoorFault> {
+        self.control.lock().unwrap().command(true)
+    }
+
+    /// Close the door, unless a fault is latched.
+    pub fn close_door(&mut self) -> Result<(), DoorFault> {
+        self.control.lock().unwrap().command(false)
+    }
+
+    /// Stop the door where it is.
+    pub fn stop(&mut self) {
+        self.control.lock().unwrap().stop()
+    }
+
+    /// The position of the door.
+    pub fn state(&self) -> DoorState {
+        self.control.lock().unwrap().state()
+    }
+
+    /// The latched fault, if any.
+    pub fn fault(&self) -> Option<DoorFault> {
+        self.control.lock().unwrap().fault
+    }
+
+    /// Clear a latched fault so the door can move again. The schedule moves the door to
+    /// match it straight away.
+    pub fn reset_fault(&mut self) {
+        let mut control = self.control.lock().unwrap();
+        control.fault = None;
+        control.day = None;
+    }
+
+    /// Shut down the device and release all associated resources. The door stops where
+    /// it is.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+impl Component for CoopDoor {
+    fn values(&self) -> Vec<f64> {
+        let value = match self.state() {
+            DoorState::Open => 1.0,
+            DoorState::Closed => 0.0,
+            _ => 0.5,
+        };
+        vec![value]
+    }
+
+    fn is_active(&self) -> bool {
+        self.state() != DoorState::Closed
+    }
+
+    fn close(&mut self) {
+        self.stop()
+    }
+}
+
+impl Drop for CoopDoor {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+        self.control.lock().unwrap().stop();
+    }
+}
+
+/// An event reported by an `Enclosure`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EnclosureAlert {
+    /// The air sensor read the level given, at or above the alarm level
+    Smoke(f64),
+    /// The temperature reached the level given, at or above the safety limit
+    OverTemperature(f64),
+    /// The temperature sensor could not be read
+    SensorFailure,
+    /// The door was opened
+    DoorOpened,
+    /// The door was closed
+    DoorClosed,
+}
+
+/// The requested states of the heater and fan of an `Enclosure`, checked by its interlocks,
+/// with the state of the door. Outputs that are not fitted are ``False``.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EnclosureOutputs {
+    /// The heater is to be on
+    pub heater: bool,
+    /// The fan is to be on
+    pub fan: bool,
+    /// The door is open
+    pub door_open: bool,
+}
+
+type AlertCallback = Box<dyn FnMut(EnclosureAlert) + Send>;
+type EnclosureInterlock = (String, Box<dyn Fn(&EnclosureOutputs) -> bool + Send>);
+
+/// A 3D printer enclosure controller keeping the air at printing temperature and the
+/// printer safe.
+///
+/// A heater and a fan each follow a `Thermostat`; every `update` reads the sensors and
+/// applies both as one `Transaction`, so an interlock blocks the whole change. The heater is
+/// held off while the door switch reports the door open.
+///
+/// A smoke or VOC reading at the alarm level, or a temperature at the safety limit, raises
+/// a latched alarm: the power relay feeding the printer opens, the heater and fan turn off
+/// so they do not feed a fire, and the lights show the scene named `alarm` if there is one.
+/// Nothing turns back on until `reset_alarm`. Alarms, a failed temperature sensor and the
+/// door opening and closing are reported to the `when_alert` callback.
+///
+/// Lighting scenes are named brightness levels for the enclosure's LEDs, such as a bright
+/// scene for inspecting a print and a dim one for a camera.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+/// use std::thread::sleep;
+/// use std::time::Duration;
+///
+/// let mut enclosure = Enclosure::new(|| Some(38.5));
+/// enclosure.set_heater(
+///     DigitalOutputDevice::new(17),
+///     Thermostat::new(ThermostatMode::Heat, 40.0, 2.0),
+/// );
+/// enclosure.set_fan(
+///     DigitalOutputDevice::new(27),
+///     Thermostat::new(ThermostatMode::Cool, 50.0, 4.0),
+/// );
+/// enclosure.set_air_sensor(|| Some(0.1), 0.6);
+/// enclosure.set_power_relay(DigitalOutputDevice::new(22));
+/// enclosure.set_door_switch(5);
+/// enclosure.set_lights(vec![PWMLED::new(12), PWMLED::new(13)]);
+/// enclosure.add_scene("work", &[1.0, 1.0]);
+/// enclosure.add_scene("camera", &[0.4, 0.0]);
+/// enclosure.add_scene("alarm", &[1.0, 0.0]);
+/// enclosure.set_scene("camera").unwrap();
+/// enclosure.when_alert(|alert| println!("enclosure: {:?}", alert));
+///
+/// loop {
+///     if let Err(e) = enclosure.update() {
+///         println!("{}", e);
+///     }
+///     sleep(Duration::from_secs(5));
+/// }
+/// ```
+pub struct Enclosure {
+    temperature: ReadingFn,
+    air: Option<(ReadingFn, f64)>,
+    readings: (Option<f64>, Option<f64>),
+    heater: Option<(Box<dyn OutputComponent>, Thermostat)>,
+    fan: Option<(Box<dyn OutputComponent>, Thermostat)>,
+    power: Option<Box<dyn OutputComponent>>,
+    door: Option<DigitalInputDeviceR>,
+    door_open: Option<bool>,
+    lights: Vec<PWMLED>,
+    scenes: Vec<(String, Vec<f64>)>,
+    scene: Option<String>,
+    max_temperature: f64,
+    alarm: Option<EnclosureAlert>,
+    sensor_failed: bool,
+    interlocks: Vec<EnclosureInterlock>,
+    when_alert: Option<AlertCallback>,
+}
+
+impl Enclosure {
+    /// Returns an Enclosure without outputs, with a safety limit of 70 degrees
+    /// * `temperature` - Returns the temperature, or None if the sensor gave no reading
+    pub fn new<F>(temperature: F) -> Enclosure
+    where
+        F: FnMut() -> Option<f64> + Send + 'static,
+    {
+        Enclosure {
+            temperature: Box::new(temperature),
+            air: None,
+            readings: (None, None),
+            heater: None,
+            fan: None,
+            power: None,
+            door: None,
+            door_open: None,
+            lights: Vec::new(),
+            scenes: Vec::new(),
+            scene: None,
+            max_temperature: 70.0,
+            alarm: None,
+            sensor_failed: false,
+            interlocks: Vec::new(),
+            when_alert: None,
+        }
+    }
+
+    /// Fit a heater, switched by a heating thermostat.
+    pub fn set_heater<O: OutputComponent + 'static>(&mut self, heater: O, thermostat: Thermostat) {
+        self.heater = Some((Box::new(heater), thermostat));
+    }
+
+    /// Fit a fan, switched by a cooling thermostat.
+    pub fn set_fan<O: OutputComponent + 'static>(&mut self, fan: O, thermostat: Thermostat) {
+        self.fan = Some((Box::new(fan), thermostat));
+    }
+
+    /// Fit a smoke or VOC sensor.
+    /// * `read` - Returns the air quality reading, higher for worse air, or None if the
+    ///   sensor gave no reading
+    /// * `alarm_level` - The reading at which the alarm is raised
+    pub fn set_air_sensor<F>(&mut self, read: F, alarm_level: f64)
+    where
+        F: FnMut() -> Option<f64> + Send + 'static,
+    {
+        self.air = Some((Box::new(read), alarm_level));
+    }
+
+    /// Set the temperature at which the alarm is raised. Defaults to 70 degrees.
+    pub fn set_max_temperature(&mut self, max_temperature: f64) {
+        self.max_temperature = max_temperature;
+    }
+
+    /// Fit a relay feeding power to the printer; it is closed unless the alarm is raised.
+    pub fn set_power_relay<O: OutputComponent + 'static>(&mut self, mut relay: O) {
+        if self.alarm.is_none() {
+            relay.on();
+        } else {
+            relay.off();
+        }
+        self.power = Some(Box::new(relay));
+    }
+
+    /// Use a door switch wired between a pin and ground, closed while the door is closed.
+    /// * `pin` - The GPIO pin which the door switch is attached to
+    pub fn set_door_switch(&mut self, pin: u8) {
+        self.door = Some(DigitalInputDeviceR::new_active_low(pin));
+        self.door_open = None;
+    }
+
+    /// Fit the LEDs lighting the enclosure, for scenes.
+    pub fn set_lights(&mut self, lights: Vec<PWMLED>) {
+        self.lights = lights;
+        self.scene = None;
+    }
+
+    /// Add or replace a lighting scene.
+    /// * `name` - The name of the scene; a scene named `alarm` is shown while the alarm is raised
+    /// * `levels` - The brightness of each LED in order, from 0.0 to 1.0; LEDs without a
+    ///   level are turned off
+    pub fn add_scene(&mut self, name: &str, levels: &[f64]) {
+        let levels = levels.iter().map(|level| level.clamp(0.0, 1.0)).collect();
+        self.scenes.retain(|(scene, _)| scene != name);
+        self.scenes.push((name.to_string(), levels));
+    }
+
+    /// Show a lighting scene. While the alarm is raised the scene is shown once the alarm is
+    /// reset.
+    pub fn set_scene(&mut self, name: &str) -> Result<(), String> {
+        if !self.scenes.iter().any(|(scene, _)| scene == name) {
+            return Err(format!("no scene named '{}'", name));
+        }
+        self.scene = Some(name.to_string());
+        if self.alarm.is_none() {
+            self.show(name);
+        }
+        Ok(())
+    }
+
+    /// The scene chosen with `set_scene`.
+    pub fn scene(&self) -> Option<&str> {
+        self.scene.as_deref()
+    }
+
+    fn show(&mut self, name: &str) {
+        if let Some((_, levels)) = self.scenes.iter().find(|(scene, _)| scene == name) {
+            for (i, light) in self.lights.iter_mut().enumerate() {
+                light.set_value(levels.get(i).cloned().unwrap_or(0.0));
+            }
+        }
+    }
+
+    /// Add an interlock checked before the heater and fan are changed.
+    /// * `name` - Name reported when the interlock trips
+    /// * `check` - Returns ``True`` if the requested states are allowed
+    pub fn interlock<F>(&mut self, name: &str, check: F)
+    where
+        F: Fn(&EnclosureOutputs) -> bool + Send + 'static,
+    {
+        self.interlocks.push((name.to_string(), Box::new(check)));
+    }
+
+    /// Set the function called with every alert.
+    pub fn when_alert<C>(&mut self, callback: C)
+    where
+        C: FnMut(EnclosureAlert) + Send + 'static,
+    {
+        self.when_alert = Some(Box::new(callback));
+    }
+
+    fn alert(&mut self, alert: EnclosureAlert) {
+        if let Some(callback) = &mut self.when_alert {
+            callback(alert)
+        }
+    }
+
+    fn raise(&mut self, alarm: EnclosureAlert) {
+        self.alarm = Some(alarm);
+        for (output, _) in IntoIterator::into_iter([&mut self.heater, &mut self.fan]).flatten() {
+            output.off();
+        }
+        if let Some(power) = &mut self.power {
+            power.off();
+        }
+        self.show("alarm");
+        self.alert(alarm);
+    }
+
+    /// Read the sensors, raise the alarm if needed and switch the heater and fan. If an
+    /// interlock trips, neither of them changes.
+    pub fn update(&mut self) -> Result<(), TransactionError> {
+        let temperature = (self.temperature)().filter(|t| t.is_finite());
+        let air = match &mut self.air {
+            Some((read, level)) => {
+                let level = *level;
+                read().filter(|a| a.is_finite()).map(|a| (a, level))
+            }
+            None => None,
+        };
+        self.readings = (temperature, air.map(|(a, _)| a));
+
+        if temperature.is_none() != self.sensor_failed {
+            self.sensor_failed = temperature.is_none();
+            if self.sensor_failed {
+                self.alert(EnclosureAlert::SensorFailure);
+            }
+        }
+        if self.alarm.is_none() {
+            if let Some((air, _)) = air.filter(|(air, level)| air >= level) {
+                self.raise(EnclosureAlert::Smoke(air));
+            } else if let Some(t) = temperature.filter(|&t| t >= self.max_temperature) {
+                self.raise(EnclosureAlert::OverTemperature(t));
+            }
+        }
+        let door_open = self.door.as_ref().is_some_and(|door| !door.is_active());
+        if self.door.is_some() && self.door_open.replace(door_open) == Some(!door_open) {
+            self.alert(if door_open {
+                EnclosureAlert::DoorOpened
+            } else {
+                EnclosureAlert::DoorClosed
+            });
+        }
+        if let Some(power) = &mut self.power {
+            let closed = self.alarm.is_none();
+            if power.is_active() != closed {
+                if closed {
+                    power.on()
+                } else {
+                    power.off()
+                }
+            }
+        }
+        if self.alarm.is_some() {
+            return Ok(());
+        }
+
+        let demand = |actuator: &mut Option<(Box<dyn OutputComponent>, Thermostat)>| match (
+            actuator,
+            temperature,
+        ) {
+            (Some((_, thermostat)), Some(t)) => thermostat.update(t),
+            _ => false,
+        };
+        let requested = EnclosureOutputs {
+            heater: demand(&mut self.heater) && !door_open,
+            fan: demand(&mut self.fan),
+            door_open,
+        };
+
+        let states = [requested.heater, requested.fan];
+        let mut positions = [None; 2];
+        let mut count = 0;
+        let mut transaction = Transaction::new();
+        let outputs = [&mut self.heater, &mut self.fan];
+        for (i, (output, active)) in IntoIterator::into_iter(outputs).zip(states).enumerate() {
+            if let Some((output, _)) = output {
+                positions[i] = Some(count);
+                count += 1;
+                transaction = transaction.set(output, active);
+            }
+        }
+        for (name, check) in &self.interlocks {
+            transaction = transaction.interlock(name, move |states: &[bool]| {
+                let state = |position: Option<usize>| position.is_some_and(|i| states[i]);
+                check(&EnclosureOutputs {
+                    heater: state(positions[0]),
+                    fan: state(positions[1]),
+                    door_open,
+                })
+            });
+        }
+        transaction.apply()
+    }
+
+    /// The latched alarm, if any.
+    pub fn alarm(&self) -> Option<EnclosureAlert> {
+        self.alarm
+    }
+
+    /// Clear the alarm: the power relay closes again and the chosen scene is shown. The
+    /// heater and fan follow their thermostats from the next `update`.
+    pub fn reset_alarm(&mut self) {
+        self.alarm = None;
+        if let Some(power) = &mut self.power {
+            power.on();
+        }
+        match self.scene.clone() {
+            Some(scene) => self.show(&scene),
+            None => {
+                for light in &mut self.lights {
+                    light.off();
+                }
+            }
+        }
+    }
+
+    /// The temperature read by the last `update`.
+    pub fn temperature(&self) -> Option<f64> {
+        self.readings.0
+    }
+
+    /// The air quality read by the last `update`.
+    pub fn air_quality(&self) -> Option<f64> {
+        self.readings.1
+    }
+
+    /// Returns ``True`` if the door switch reports the door open.
+    pub fn is_door_open(&self) -> bool {
+        self.door.as_ref().is_some_and(|door| !door.is_active())
+    }
+
+    /// The states of the heater and fan, with the state of the door.
+    pub fn outputs(&self) -> EnclosureOutputs {
+        let active = |output: &Option<(Box<dyn OutputComponent>, Thermostat)>| {
+            output
+                .as_ref()
+                .is_some_and(|(output, _)| output.is_active())
+        };
+        EnclosureOutputs {
+            heater: active(&self.heater),
+            fan: active(&self.fan),
+            door_open: self.is_door_open(),
+        }
+    }
+}
+
+impl Component for Enclosure {
+    fn values(&self) -> Vec<f64> {
+        let outputs = self.outputs();
+        let on = |active: bool| if active { 1.0 } else { 0.0 };
+        let power = self.power.as_ref().is_some_and(|power| power.is_active());
+        let mut values = vec![on(outputs.heater), on(outputs.fan), on(power)];
+        values.extend(self.lights.iter().map(|light| light.value()));
+        values
+    }
+
+    fn close(&mut self) {
+        for (output, _) in IntoIterator::into_iter([&mut self.heater, &mut self.fan]).flatten() {
+            output.off();
+        }
+        for light in &mut self.lights {
+            light.off();
+        }
+    }
+}
+
+/// A press of a `Doorbell`, as passed to its notifiers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DoorbellEvent {
+    /// When the button was pressed
+    pub time: SystemTime,
+    /// The number of presses rung so far, counting this one
+    pub count: u64,
+    /// ``True`` if the chime stayed silent because of quiet hours
+    pub quiet: bool,
+}
+
+/// Something told when a `Doorbell` rings, such as an MQTT, HTTP or webhook client.
+///
+/// Notifiers are called one after the other from a background thread of their own, so a
+/// slow network call delays the next notifier but not the chime. Any `FnMut(&DoorbellEvent)` closure is
+/// a notifier.
+pub trait Notifier: Send {
+    /// Called every time the doorbell rings.
+    fn notify(&mut self, event: &DoorbellEvent);
+}
+
+impl<F: FnMut(&DoorbellEvent) + Send> Notifier for F {
+    fn notify(&mut self, event: &DoorbellEvent) {
+        self(event)
+    }
+}
+
+struct Bell {
+    chime: Box<dyn OutputComponent>,
+    chime_time: Duration,
+    camera: Option<(Box<dyn OutputComponent>, Duration)>,
+    quiet_hours: Option<TimeOfDay>,
+    min_interval: Duration,
+    last_ring: Option<Instant>,
+    count: u64,
+}
+
+impl Bell {
+    /// Accept a press unless it comes too soon after the last one.
+    fn accept(&mut self) -> bool {
+        let now = Instant::now();
+        if self
+            .last_ring
+            .is_some_and(|last| now.duration_since(last) < self.min_interval)
+        {
+            return false;
+        }
+        self.last_ring = Some(now);
+        self.count += 1;
+        true
+    }
+}
+
+/// A doorbell: a button ringing a chime, with an optional camera trigger.
+///
+/// Presses coming sooner than the minimum interval after the last ring are ignored, so a
+/// visitor leaning on the button rings once. During quiet hours the chime stays silent but
+/// the camera is still triggered and the notifiers still told. Notifiers implement the
+/// `Notifier` trait, which keeps networking out of the doorbell itself.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+/// use std::time::Duration;
+///
+/// let mut doorbell = Doorbell::new(2, Buzzer::new(3));
+/// doorbell.set_camera(DigitalOutputDevice::new(4), Duration::from_millis(200));
+/// doorbell.set_quiet_hours(TimeOfDay::new((22, 0), (7, 0)));
+/// doorbell.add_notifier(|event: &DoorbellEvent| {
+///     println!("ding dong #{} (quiet: {})", event.count, event.quiet)
+/// });
+/// ```
+pub struct Doorbell {
+    bell: Arc<Mutex<Bell>>,
+    notifiers: Arc<Mutex<Vec<Box<dyn Notifier>>>>,
+    rings: mpsc::Sender<SystemTime>,
+    button: Option<Button>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+    notify_handle: Option<JoinHandle<()>>,
+}
+
+impl Doorbell {
+    /// Returns a Doorbell chiming for 1 second and ignoring presses within 5 seconds of a ring
+    /// * `button` - The GPIO pin which the button is attached to, wired to ground
+    /// * `chime` - The output sounding the chime, such as a `Buzzer` or a relay
+    pub fn new<C: OutputComponent + 'static>(button: u8, mut chime: C) -> Doorbell {
+        chime.off();
+        let bell = Arc::new(Mutex::new(Bell {
+            chime: Box::new(chime),
+            chime_time: Duration::from_secs(1),
+            camera: None,
+            quiet_hours: None,
+            min_interval: Duration::from_secs(5),
+            last_ring: None,
+            count: 0,
+        }));
+        let notifiers: Arc<Mutex<Vec<Box<dyn Notifier>>>> = Arc::default();
+        let (events, pending) = mpsc::channel::<DoorbellEvent>();
+        // ends once the ringing thread is gone and every pending event is told
+        let notify_handle = {
+            let notifiers = Arc::clone(&notifiers);
+            thread::spawn(move || {
+                for event in pending {
+                    for notifier in notifiers.lock().unwrap().iter_mut() {
+                        notifier.notify(&event);
+                    }
+                }
+            })
+        };
+        let (rings, received) = mpsc::channel::<SystemTime>();
+        let running = Arc::new(AtomicBool::new(true));
+        let handle = {
+            let bell = Arc::clone(&bell);
+            let running = Arc::clone(&running);
+            thread::spawn(move || {
+                while running.load(Ordering::SeqCst) {
+                    let time = match received.recv_timeout(Duration::from_millis(100)) {
+                        Ok(time) => time,
+                        Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                        Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                    };
+                    Doorbell::ring_once(&bell, &running, &events, time);
+                }
+            })
+        };
+
+        let mut doorbell = Doorbell {
+            bell,
+            notifiers,
+            rings,
+            button: None,
+            running,
+            handle: Some(handle),
+            notify_handle: Some(notify_handle),
+        };
+        let mut button_device = Button::new(button);
+        let bell = Arc::clone(&doorbell.bell);
+        let rings = Mutex::new(doorbell.rings.clone());
+        button_device.when_pressed(move || {
+            if bell.lock().unwrap().accept() {
+                let _ = rings.lock().unwrap().send(SystemTime::now());
+            }
+        });
+        doorbell.button = Some(button_device);
+        doorbell
+    }
+
+    /// Switch the chime and camera on and off for one ring. The notifiers are handed the
+    /// event on their own thread, with the bell unlocked, so they cannot hold up either.
+    fn ring_once(
+        bell: &Mutex<Bell>,
+        running: &AtomicBool,
+        events: &mpsc::Sender<DoorbellEvent>,
+        time: SystemTime,
+    ) {
+        let (event, chime_time, camera_time) = {
+            let mut bell = bell.lock().unwrap();
+            let quiet = bell.quiet_hours.as_ref().is_some_and(TimeOfDay::is_active);
+            if !quiet {
+                bell.chime.on();
+            }
+            if let Some((camera, _)) = &mut bell.camera {
+                camera.on();
+            }
+            let event = DoorbellEvent {
+                time,
+                count: bell.count,
+                quiet,
+            };
+            (
+                event,
+                bell.chime_time,
+                bell.camera.as_ref().map(|(_, pulse)| *pulse),
+            )
+        };
+        let started = Instant::now();
+        let _ = events.send(event);
+        let mut offs = vec![(chime_time, true)];
+        if let Some(pulse) = camera_time {
+            offs.push((pulse, false));
+        }
+        offs.sort_by_key(|&(at, _)| at);
+        for (at, chime) in offs {
+            sleep_while_running(running, at.saturating_sub(started.elapsed()));
+            let mut bell = bell.lock().unwrap();
+            if chime {
+                bell.chime.off();
+            } else if let Some((camera, _)) = &mut bell.camera {
+                camera.off();
+            }
+        }
+    }
+
+    /// Trigger a camera, e.g. through a relay or an optocoupler on its shutter release,
+    /// every time the doorbell rings.
+    /// * `camera` - The output triggering the camera
+    /// * `pulse` - How long the output is held on
+    pub fn set_camera<O: OutputComponent + 'static>(&mut self, mut camera: O, pulse: Duration) {
+        camera.off();
+        self.bell.lock().unwrap().camera = Some((Box::new(camera), pulse));
+    }
+
+    /// Set how long the chime sounds. Defaults to 1 second.
+    pub fn set_chime_time(&mut self, chime_time: Duration) {
+        self.bell.lock().unwrap().chime_time = chime_time;
+    }
+
+    /// Keep the chime silent while `quiet_hours` is active.
+    pub fn set_quiet_hours(&mut self, quiet_hours: TimeOfDay) {
+        self.bell.lock().unwrap().quiet_hours = Some(quiet_hours);
+    }
+
+    /// Set how soon after a ring further presses are ignored. Defaults to 5 seconds.
+    pub fn set_min_interval(&mut self, min_interval: Duration) {
+        self.bell.lock().unwrap().min_interval = min_interval;
+    }
+
+    /// Add a notifier told every time the doorbell rings.
+    pub fn add_notifier<N: Notifier + 'static>(&mut self, notifier: N) {
+        self.notifiers.lock().unwrap().push(Box::new(notifier));
+    }
+
+    /// Ring as if the button was pressed, e.g. from a remote. Returns ``False`` if the press
+    /// was ignored because it came too soon after the last ring.
+    pub fn ring(&mut self) -> bool {
+        if !self.bell.lock().unwrap().accept() {
+            return false;
+        }
+        let _ = self.rings.send(SystemTime::now());
+        true
+    }
+
+    /// The number of presses rung so far.
+    pub fn count(&self) -> u64 {
+        self.bell.lock().unwrap().count
+    }
+
+    /// Returns ``True`` while the chime is sounding.
+    pub fn is_ringing(&self) -> bool {
+        self.bell.lock().unwrap().chime.is_active()
+    }
+}
+
+impl Component for Doorbell {
+    fn values(&self) -> Vec<f64> {
+        vec![if self.is_ringing() { 1.0 } else { 0.0 }]
+    }
+
+    fn close(&mut self) {
+        let mut bell = self.bell.lock().unwrap();
+        bell.chime.off();
+        if let Some((camera, _)) = &mut bell.camera {
+            camera.off();
+        }
+    }
+}
+
+impl Drop for Doorbell {
+    fn drop(&mut self) {
+        self.button.take();
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+        Component::close(self);
+        if let Some(handle) = self.notify_handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// What a `TimedRelay` does when triggered while it is already on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetriggerPolicy {
+    /// Restart the full duration from the new trigger
+    Extend,
+    /// Keep the time already running and ignore the trigger
+    Ignore,
+}
+
+struct TimerState {
+    output: Box<dyn OutputComponent>,
+    duration: Duration,
+    policy: RetriggerPolicy,
+    warning: Option<(Duration, Duration)>,
+    off_at: Option<Instant>,
+    flash_until: Option<Instant>,
+    warned: bool,
+}
+
+impl TimerState {
+    /// Start or extend the timer, returning ``True`` unless the trigger was ignored.
+    fn trigger(&mut self) -> bool {
+        if self.off_at.is_some() && self.policy == RetriggerPolicy::Ignore {
+            return false;
+        }
+        self.off_at = Some(Instant::now() + self.duration);
+        self.flash_until = None;
+        self.warned = false;
+        self.output.on();
+        true
+    }
+
+    fn cancel(&mut self) {
+        self.off_at = None;
+        self.flash_until = None;
+        self.warned = false;
+        self.output.off();
+    }
+
+    fn supervise(&mut self) {
+        let off_at = match self.off_at {
+            Some(off_at) => off_at,
+            None => return,
+        };
+        let now = Instant::now();
+        if now >= off_at {
+            self.cancel();
+            return;
+        }
+        if let Some(until) = self.flash_until {
+            if now >= until {
+                self.flash_until = None;
+                self.output.on();
+            }
+        } else if let Some((before, flash)) = self.warning {
+            if !self.warned && off_at.duration_since(now) <= before {
+                self.warned = true;
+                self.flash_until = Some(now + flash);
+                self.output.off();
+            }
+        }
+    }
+}
+
+/// An output switched on for a set time whenever it is triggered, like the timer of a
+/// staircase light.
+///
+/// Triggers can come from buttons wired to the relay, from any input callback through
+/// `trigger_fn`, or from code calling `trigger`. A trigger while the output is on either
+/// restarts the time or is ignored, depending on the retrigger policy. An optional warning
+/// flash turns the output off briefly shortly before the time runs out, so someone on the
+/// stairs knows to press the button again.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+/// use std::time::Duration;
+///
+/// let mut light = TimedRelay::new(DigitalOutputDevice::new(17), Duration::from_secs(120));
+/// light.add_button(2);
+/// light.add_button(3);
+/// light.set_warning(Duration::from_secs(10), Duration::from_millis(300));
+///
+/// // a PIR motion sensor driving its output high on movement
+/// let mut motion = DigitalInputDevice::new(4);
+/// motion.when_activated(light.trigger_fn());
+/// ```
+pub struct TimedRelay {
+    state: Arc<Mutex<TimerState>>,
+    buttons: Vec<Button>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl TimedRelay {
+    /// Returns a TimedRelay whose output is off, extending the time on retrigger and with
+    /// no warning flash
+    /// * `output` - The output switched, such as a relay or a `LED`
+    /// * `duration` - How long the output stays on after a trigger
+    pub fn new<O: OutputComponent + 'static>(mut output: O, duration: Duration) -> TimedRelay {
+        output.off();
+        let state = Arc::new(Mutex::new(TimerState {
+            output: Box::new(output),
+            duration,
+            policy: RetriggerPolicy::Extend,
+            warning: None,
+            off_at: None,
+            flash_until: None,
+            warned: false,
+        }));
+        let running = Arc::new(AtomicBool::new(true));
+        let handle = {
+            let state = Arc::clone(&state);
+            let running = Arc::clone(&running);
+            thread::spawn(move || {
+                while running.load(Ordering::SeqCst) {
+                    state.lock().unwrap().supervise();
+                    sleep_while_running(&running, Duration::from_millis(10));
+                }
+            })
+        };
+        TimedRelay {
+            state,
+            buttons: Vec::new(),
+            running,
+            handle: Some(handle),
+        }
+    }
+
+    /// Trigger the relay from a button wired between a pin and ground.
+    /// * `pin` - The GPIO pin which the button is attached to
+    pub fn add_button(&mut self, pin: u8) {
+        let mut button = Button::new(pin);
+        button.when_pressed(self.trigger_fn());
+        self.buttons.push(button);
+    }
+
+    /// Returns a callback triggering the relay, to pass to the `when_activated` of another
+    /// device such as a motion sensor.
+    pub fn trigger_fn(&self) -> impl FnMut() + Send + 'static {
+        let state = Arc::clone(&self.state);
+        move || {
+            state.lock().unwrap().trigger();
+        }
+    }
+
+    /// Trigger the relay, turning the output on. Returns ``False`` if the trigger was
+    /// ignored because the output was already on and the policy is `Ignore`.
+    pub fn trigger(&mut self) -> bool {
+        self.state.lock().unwrap().trigger()
+    }
+
+    /// Turn the output off straight away.
+    pub fn cancel(&mut self) {
+        self.state.lock().unwrap().cancel()
+    }
+
+    /// The time left before the output turns off, or zero if it is off.
+    pub fn remaining(&self) -> Duration {
+        self.state
+            .lock()
+            .unwrap()
+            .off_at
+            .map_or(Duration::ZERO, |off_at| {
+                off_at.saturating_duration_since(Instant::now())
+            })
+    }
+
+    /// Returns ``True`` while the timer is running, including during the warning flash.
+    pub fn is_on(&self) -> bool {
+        self.state.lock().unwrap().off_at.is_some()
+    }
+
+    /// How long the output stays on after a trigger.
+    pub fn duration(&self) -> Duration {
+        self.state.lock().unwrap().duration
+    }
+
+    /// Set how long the output stays on after a trigger. A timer already running keeps
+    /// its time until the next trigger.
+    pub fn set_duration(&mut self, duration: Duration) {
+        self.state.lock().unwrap().duration = duration;
+    }
+
+    /// What a trigger does while the output is on.
+    pub fn policy(&self) -> RetriggerPolicy {
+        self.state.lock().unwrap().policy
+    }
+
+    /// Set what a trigger does while the output is on. Defaults to `Extend`.
+    pub fn set_policy(&mut self, policy: RetriggerPolicy) {
+        self.state.lock().unwrap().policy = policy;
+    }
+
+    /// Flash the output off once shortly before it turns off.
+    /// * `before` - How long before the end of the time the flash comes
+    /// * `flash` - How long the output is off during the flash
+    pub fn set_warning(&mut self, before: Duration, flash: Duration) {
+        if flash >= before {
+            println!("The flash must be shorter than the warning time");
+            return;
+        }
+        self.state.lock().unwrap().warning = Some((before, flash));
+    }
+
+    /// Stop flashing the output before it turns off.
+    pub fn clear_warning(&mut self) {
+        self.state.lock().unwrap().warning = None;
+    }
+}
+
+impl Component for TimedRelay {
+    fn values(&self) -> Vec<f64> {
+        vec![self.remaining().as_secs_f64()]
+    }
+
+    fn is_active(&self) -> bool {
+        self.is_on()
+    }
+
+    fn close(&mut self) {
+        self.cancel();
+    }
+}
+
+impl Drop for TimedRelay {
+    fn drop(&mut self) {
+        self.buttons.clear();
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+        Component::close(self);
+    }
+}
+
+/// Remove a dead zone of `dead_zone` at each end of a value between 0.0 and 1.0, stretching
+/// the rest over the full range.
+fn trim_ends(value: f64, dead_zone: f64) -> f64 {
+    ((value - dead_zone) / (1.0 - 2.0 * dead_zone)).clamp(0.0, 1.0)
+}
+
+/// A potentiometer, slider or other variable resistor read through any ADC channel.
+///
+/// The wiper is connected to the channel and the ends of the track to ground and the
+/// reference voltage of the converter. Cheap potentiometers do not reach the ends of their
+/// range, so a dead zone can be cut off each end.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let adc = MCP3008::new();
+/// let mut volume = Potentiometer::new(adc.channel(0));
+/// volume.set_dead_zone(0.02);
+/// volume.set_inverted(true);
+/// println!("{:.0}%", volume.value() * 100.0);
+/// ```
+pub struct Potentiometer<A: AnalogInputDevice> {
+    channel: A,
+    dead_zone: f64,
+    inverted: bool,
+}
+
+impl<A: AnalogInputDevice> Potentiometer<A> {
+    /// Returns a Potentiometer without a dead zone, reading 0.0 at the grounded end
+    /// * `channel` - The ADC channel the wiper is connected to
+    pub fn new(channel: A) -> Potentiometer<A> {
+        Potentiometer {
+            channel,
+            dead_zone: 0.0,
+            inverted: false,
+        }
+    }
+
+    /// The ADC channel.
+    pub fn channel(&mut self) -> &mut A {
+        &mut self.channel
+    }
+
+    /// Read the position between 0.0 and 1.0.
+    pub fn try_value(&self) -> Result<f64, Box<dyn Error + Send + Sync>> {
+        let value = trim_ends(self.channel.try_value()?, self.dead_zone);
+        Ok(if self.inverted { 1.0 - value } else { value })
+    }
+
+    /// The position between 0.0 and 1.0, or NaN if the channel cannot be read.
+    pub fn value(&self) -> f64 {
+        self.try_value().unwrap_or(f64::NAN)
+    }
+
+    /// The part of the range cut off each end.
+    pub fn dead_zone(&self) -> f64 {
+        self.dead_zone
+    }
+
+    /// Set the part of the range cut off each end, so the ends read exactly 0.0 and 1.0.
+    /// * `dead_zone` - Fraction of the range, from 0.0 to less than 0.5
+    pub fn set_dead_zone(&mut self, dead_zone: f64) {
+        if !(0.0..0.5).contains(&dead_zone) {
+            println!("Dead zone must be between 0.0 and 0.5");
+            return;
+        }
+        self.dead_zone = dead_zone
+    }
+
+    /// Returns ``True`` if the value is reversed.
+    pub fn is_inverted(&self) -> bool {
+        self.inverted
+    }
+
+    /// Reverse the value, for a potentiometer wired the other way round.
+    pub fn set_inverted(&mut self, inverted: bool) {
+        self.inverted = inverted
+    }
+}
+
+impl<A: AnalogInputDevice> Component for Potentiometer<A> {
+    fn values(&self) -> Vec<f64> {
+        vec![self.value()]
+    }
+}
+
+/// The calibration of one axis of a `Joystick`.
+#[derive(Debug, Clone, Copy)]
+struct Axis {
+    center: f64,
+    inverted: bool,
+}
+
+impl Axis {
+    /// The position between -1.0 and 1.0 of a reading between 0.0 and 1.0, each side of the
+    /// center scaled separately, with a dead zone around the center.
+    fn position(&self, value: f64, dead_zone: f64) -> f64 {
+        let offset = if value >= self.center {
+            (value - self.center) / (1.0 - self.center)
+        } else {
+            (value - self.center) / self.center
+        };
+        let magnitude = ((offset.abs() - dead_zone) / (1.0 - dead_zone)).clamp(0.0, 1.0);
+        let position = magnitude.copysign(offset);
+        if self.inverted {
+            -position
+        } else {
+            position
+        }
+    }
+}
+
+/// An analog thumb joystick with a push button, such as the KY-023, read through two ADC
+/// channels.
+///
+/// The stick rests near, but rarely exactly at, the middle of its range, so the center is
+/// calibrated with `calibrate_center` while it is released, and each side of it is scaled
+/// separately to reach -1.0 and 1.0. A dead zone around the center keeps the position at 0.0
+/// while the stick is released.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let adc = MCP3008::new();
+/// let mut stick = Joystick::new(adc.channel(0), adc.channel(1), 26);
+/// stick.calibrate_center().unwrap();
+/// stick.button().when_pressed(|| println!("fire"));
+/// loop {
+///     let (x, y) = stick.position();
+///     println!("{:+.2} {:+.2}", x, y);
+///     std::thread::sleep(std::time::Duration::from_millis(100));
+/// }
+/// ```
+pub struct Joystick<A: AnalogInputDevice> {
+    x: A,
+    y: A,
+    x_axis: Axis,
+    y_axis: Axis,
+    dead_zone: f64,
+    button: Button,
+}
+
+impl<A: AnalogInputDevice> Joystick<A> {
+    /// Returns a Joystick centered at 0.5 on both axes with a dead zone of 0.05
+    /// * `x` - The ADC channel of the horizontal axis
+    /// * `y` - The ADC channel of the vertical axis
+    /// * `button` - The GPIO pin which the button is attached to, wired to ground
+    pub fn new(x: A, y: A, button: u8) -> Joystick<A> {
+        let axis = Axis {
+            center: 0.5,
+            inverted: false,
+        };
+        Joystick {
+            x,
+            y,
+            x_axis: axis,
+            y_axis: axis,
+            dead_zone: 0.05,
+            button: Button::new(button),
+        }
+    }
+
+    /// Take the current readings of both axes as the center. Call it while the stick is
+    /// released.
+    pub fn calibrate_center(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let (x, y) = (self.x.try_value()?, self.y.try_value()?);
+        if !(0.0 < x && x < 1.0 && 0.0 < y && y < 1.0) {
+            return Err(format!("stick is not centered: x {:.3}, y {:.3}", x, y).into());
+        }
+        self.x_axis.center = x;
+        self.y_axis.center = y;
+        Ok(())
+    }
+
+    /// The calibrated centers of the x and y axes, between 0.0 and 1.0.
+    pub fn center(&self) -> (f64, f64) {
+        (self.x_axis.center, self.y_axis.center)
+    }
+
+    /// The horizontal position between -1.0 (left) and 1.0 (right), or NaN if it cannot be
+    /// read.
+    pub fn x(&self) -> f64 {
+        self.x_axis.position(self.x.value(), self.dead_zone)
+    }
+
+    /// The vertical position between -1.0 and 1.0, or NaN if it cannot be read.
+    pub fn y(&self) -> f64 {
+        self.y_axis.position(self.y.value(), self.dead_zone)
+    }
+
+    /// The x and y positions.
+    pub fn position(&self) -> (f64, f64) {
+        (self.x(), self.y())
+    }
+
+    /// The part of each half of an axis around the center that reads 0.0.
+    pub fn dead_zone(&self) -> f64 {
+        self.dead_zone
+    }
+
+    /// Set the part of each half of an axis around the center that reads 0.0.
+    /// * `dead_zone` - Fraction of the half, from 0.0 to less than 1.0
+    pub fn set_dead_zone(&mut self, dead_zone: f64) {
+        if !(0.0..1.0).contains(&dead_zone) {
+            println!("Dead zone must be between 0.0 and 1.0");
+            return;
+        }
+        self.dead_zone = dead_zone
+    }
+
+    /// Reverse the axes, for a stick mounted the other way round.
+    /// * `x` - ``True`` to reverse the horizontal axis
+    /// * `y` - ``True`` to reverse the vertical axis
+    pub fn set_inverted(&mut self, x: bool, y: bool) {
+        self.x_axis.inverted = x;
+        self.y_axis.inverted = y;
+    }
+
+    /// The push button of the stick.
+    pub fn button(&mut self) -> &mut Button {
+        &mut self.button
+    }
+
+    /// Returns ``True`` if the stick is pushed in.
+    pub fn is_pressed(&self) -> bool {
+        self.button.is_pressed()
+    }
+}
+
+impl<A: AnalogInputDevice> Component for Joystick<A> {
+    fn values(&self) -> Vec<f64> {
+        let (x, y) = self.position();
+        vec![x, y, if self.is_pressed() { 1.0 } else { 0.0 }]
+    }
+
+    fn is_active(&self) -> bool {
+        let (x, y) = self.position();
+        x != 0.0 || y != 0.0 || self.is_pressed()
+    }
+}
+
+/// The voltage of a single cell at points of its discharge, from full to empty, against the
+/// charge left in percent, at a light load.
+const LIPO_CELL_CURVE: [(f64, f64); 11] = [
+    (4.20, 100.0),
+    (4.06, 90.0),
+    (3.98, 80.0),
+    (3.92, 70.0),
+    (3.87, 60.0),
+    (3.82, 50.0),
+    (3.79, 40.0),
+    (3.77, 30.0),
+    (3.74, 20.0),
+    (3.68, 10.0),
+    (3.30, 0.0),
+];
+
+const NIMH_CELL_CURVE: [(f64, f64); 7] = [
+    (1.40, 100.0),
+    (1.30, 90.0),
+    (1.25, 70.0),
+    (1.22, 50.0),
+    (1.18, 30.0),
+    (1.12, 10.0),
+    (1.00, 0.0),
+];
+
+const LEAD_ACID_CELL_CURVE: [(f64, f64); 6] = [
+    (2.12, 100.0),
+    (2.08, 80.0),
+    (2.03, 60.0),
+    (1.98, 40.0),
+    (1.93, 20.0),
+    (1.75, 0.0),
+];
+
+/// The number of readings a `BatteryMonitor` averages.
+const BATTERY_QUEUE_LEN: usize = 10;
+
+/// The chemistry of a battery, which sets the shape of its discharge curve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chemistry {
+    /// Lithium polymer or lithium ion, 3.7V nominal per cell
+    LiPo,
+    /// Nickel-metal hydride, 1.2V nominal per cell
+    NiMH,
+    /// Sealed lead acid, 2V nominal per cell
+    LeadAcid,
+}
+
+impl Chemistry {
+    fn cell_curve(self) -> &'static [(f64, f64)] {
+        match self {
+            Chemistry::LiPo => &LIPO_CELL_CURVE,
+            Chemistry::NiMH => &NIMH_CELL_CURVE,
+            Chemistry::LeadAcid => &LEAD_ACID_CELL_CURVE,
+        }
+    }
+}
+
+/// The charge left in percent at a voltage, interpolated along a curve sorted from full to
+/// empty.
+fn charge_left(curve: &[(f64, f64)], voltage: f64) -> f64 {
+    if voltage.is_nan() || curve.is_empty() {
+        return f64::NAN;
+    }
+    let (full, empty) = (curve[0], curve[curve.len() - 1]);
+    if voltage >= full.0 {
+        return full.1;
+    }
+    if voltage <= empty.0 {
+        return empty.1;
+    }
+    curve
+        .windows(2)
+        .find(|points| voltage >= points[1].0)
+        .map_or(empty.1, |points| {
+            let ((high, at_high), (low, at_low)) = (points[0], points[1]);
+            at_low + (voltage - low) / (high - low) * (at_high - at_low)
+        })
+}
+
+type ChargeCallback = Box<dyn FnMut(f64) + Send>;
+
+/// A level of charge a `BatteryMonitor` warns about.
+struct ChargeAlarm {
+    threshold: f64,
+    tripped: bool,
+    callback: Option<ChargeCallback>,
+}
+
+impl ChargeAlarm {
+    fn new(threshold: f64) -> ChargeAlarm {
+        ChargeAlarm {
+            threshold,
+            tripped: false,
+            callback: None,
+        }
+    }
+
+    /// Call the callback when the charge falls to the threshold. It is called again only
+    /// after the charge has risen 5% above it, e.g. after a recharge, so the sag of the
+    /// voltage under load does not repeat the warning.
+    fn check(&mut self, percentage: f64) {
+        if !self.tripped && percentage <= self.threshold {
+            self.tripped = true;
+            if let Some(callback) = self.callback.as_mut() {
+                callback(percentage);
+            }
+        } else if self.tripped && percentage > self.threshold + 5.0 {
+            self.tripped = false;
+        }
+    }
+}
+
+struct BatteryState {
+    readings: SmoothedQueue,
+    curve: Vec<(f64, f64)>,
+    low: ChargeAlarm,
+    critical: ChargeAlarm,
+}
+
+impl BatteryState {
+    fn voltage(&self) -> f64 {
+        self.readings.value().unwrap_or(f64::NAN)
+    }
+
+    fn percentage(&self) -> f64 {
+        charge_left(&self.curve, self.voltage())
+    }
+}
+
+/// Watches the voltage of a battery through a voltage divider on an ADC channel.
+///
+/// The battery is read once a second and the last 10 readings are averaged, so the dips of
+/// the voltage while motors start do not count as a flat battery. The charge left is found
+/// on the discharge curve of the battery's chemistry, or on a curve of your own measured with
+/// `set_discharge_curve`.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let adc = MCP3008::new();
+/// // a 2 cell LiPo through a divider of 20k and 10k, reading a third of its voltage
+/// let mut battery = BatteryMonitor::new(adc.channel(7), 3.0, Chemistry::LiPo, 2);
+/// battery.when_low(|percent| println!("battery at {:.0}%, heading home", percent));
+/// battery.when_critical(|_| {
+///     std::process::Command::new("sudo").arg("poweroff").status().unwrap();
+/// });
+/// println!("{:.2}V, {:.0}%", battery.voltage(), battery.percentage());
+/// ```
+pub struct BatteryMonitor {
+    divider_ratio: f64,
+    state: Arc<Mutex<BatteryState>>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl BatteryMonitor {
+    /// Returns a BatteryMonitor warning at 20% and 5% of charge left
+    /// * `channel` - The ADC channel reading the divided battery voltage
+    /// * `divider_ratio` - The battery voltage divided by the voltage at the channel, e.g. 2.0
+    ///   for two equal resistors, or (R1 + R2) / R2
+    /// * `chemistry` - The kind of battery
+    /// * `cells` - The number of cells in series
+    pub fn new<A: AnalogInputDevice + 'static>(
+        channel: A,
+        divider_ratio: f64,
+        chemistry: Chemistry,
+        cells: u32,
+    ) -> BatteryMonitor {
+        let cells = f64::from(cells.max(1));
+        let curve = chemistry
+            .cell_curve()
+            .iter()
+            .map(|&(voltage, percentage)| (voltage * cells, percentage))
+            .collect();
+        let state = Arc::new(Mutex::new(BatteryState {
+            // the mean of partial queues, so there is a voltage from the first reading; the
+            // alarms do the thresholds
+            readings: SmoothedQueue::new(BATTERY_QUEUE_LEN, true, Average::Mean, f64::INFINITY),
+            curve,
+            low: ChargeAlarm::new(20.0),
+            critical: ChargeAlarm::new(5.0),
+        }));
+        let running = Arc::new(AtomicBool::new(true));
+        let handle = {
+            let state = Arc::clone(&state);
+            let running = Arc::clone(&running);
+            thread::spawn(move || {
+                while running.load(Ordering::SeqCst) {
+                    let voltage = channel.voltage() * divider_ratio;
+                    if !voltage.is_nan() {
+                        let mut state = state.lock().unwrap();
+                        state.readings.push(voltage);
+                        let percentage = state.percentage();
+                        state.low.check(percentage);
+                        state.critical.check(percentage);
+                    }
+                    sleep_while_running(&running, Duration::from_secs(1));
+                }
+            })
+        };
+        BatteryMonitor {
+            divider_ratio,
+            state,
+            running,
+            handle: Some(handle),
+        }
+    }
+
+    /// The divider ratio the channel's voltage is multiplied by.
+    pub fn divider_ratio(&self) -> f64 {
+        self.divider_ratio
+    }
+
+    /// The battery voltage averaged over the last readings, or NaN before the first one.
+    pub fn voltage(&self) -> f64 {
+        self.state.lock().unwrap().voltage()
+    }
+
+    /// The charge left in percent, from 0.0 to 100.0, or NaN before the first reading.
+    pub fn percentage(&self) -> f64 {
+        self.state.lock().unwrap().percentage()
+    }
+
+    /// Replace the discharge curve of the chemistry with one measured on the battery.
+    /// * `curve` - Pairs of the battery voltage and the charge left in percent, in any order
+    pub fn set_discharge_curve(&mut self, curve: &[(f64, f64)]) {
+        if curve.len() < 2 {
+            println!("Discharge curve needs at least 2 points");
+            return;
+        }
+        let mut curve = curve.to_vec();
+        curve.sort_by(|a, b| b.0.total_cmp(&a.0));
+        self.state.lock().unwrap().curve = curve;
+    }
+
+    /// The charge in percent at which `when_low` is called.
+    pub fn low_threshold(&self) -> f64 {
+        self.state.lock().unwrap().low.threshold
+    }
+
+    /// Set the charge in percent at which `when_low` is called, 20.0 by default.
+    pub fn set_low_threshold(&mut self, percentage: f64) {
+        self.state.lock().unwrap().low.threshold = percentage;
+    }
+
+    /// The charge in percent at which `when_critical` is called.
+    pub fn critical_threshold(&self) -> f64 {
+        self.state.lock().unwrap().critical.threshold
+    }
+
+    /// Set the charge in percent at which `when_critical` is called, 5.0 by default.
+    pub fn set_critical_threshold(&mut self, percentage: f64) {
+        self.state.lock().unwrap().critical.threshold = percentage;
+    }
+
+    /// Returns ``True`` if the charge is at or below the low threshold.
+    pub fn is_low(&self) -> bool {
+        self.percentage() <= self.low_threshold()
+    }
+
+    /// Returns ``True`` if the charge is at or below the critical threshold.
+    pub fn is_critical(&self) -> bool {
+        self.percentage() <= self.critical_threshold()
+    }
+
+    /// Set the function called from a background thread with the charge left when it falls
+    /// to the low threshold.
+    pub fn when_low<C>(&mut self, callback: C)
+    where
+        C: FnMut(f64) + Send + 'static,
+    {
+        self.state.lock().unwrap().low.callback = Some(Box::new(callback));
+    }
+
+    /// Set the function called from a background thread with the charge left when it falls
+    /// to the critical threshold, the time to save work and shut down.
+    pub fn when_critical<C>(&mut self, callback: C)
+    where
+        C: FnMut(f64) + Send + 'static,
+    {
+        self.state.lock().unwrap().critical.callback = Some(Box::new(callback));
+    }
+
+    /// Shut down the device and release all associated resources.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+impl Component for BatteryMonitor {
+    fn values(&self) -> Vec<f64> {
+        vec![self.percentage() / 100.0]
+    }
+
+    fn is_active(&self) -> bool {
+        self.is_low()
+    }
+}
+
+impl Drop for BatteryMonitor {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
@@ -0,0 +1,223 @@
+//! Device configuration files
+//!
+//! A configuration describes the devices of an installation so that they can be
+//! checked and built without recompiling. The format is a small INI dialect: global
+//! settings come first, followed by one `[section]` per device.
+//!
+//! ```text
+//! # enable the buses used by the application
+//! i2c = true
+//!
+//! [heater]
+//! kind = relay
+//! pin = 17
+//! active_high = false
+//!
+//! [drive]
+//! kind = motor
+//! pins = 23, 24
+//! ```
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// The configuration of a single device.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceConfig {
+    /// Name of the device, taken from its section header
+    pub name: String,
+    /// Kind of device, such as `led` or `motor`
+    pub kind: String,
+    /// GPIO pins used by the device, in the order the device expects them
+    pub pins: Vec<u8>,
+    /// All other settings of the device, in file order
+    pub options: Vec<(String, String)>,
+}
+
+impl DeviceConfig {
+    /// Returns a DeviceConfig without any options
+    pub fn new(name: &str, kind: &str, pins: &[u8]) -> DeviceConfig {
+        DeviceConfig {
+            name: name.to_string(),
+            kind: kind.to_string(),
+            pins: pins.to_vec(),
+            options: Vec::new(),
+        }
+    }
+
+    /// The value of an option, or None if it is not set.
+    pub fn option(&self, key: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The `active_high` option: ``False`` for devices wired active-low, which should be
+    /// built with their `new_active_low` constructor. Defaults to ``True``.
+    pub fn active_high(&self) -> bool {
+        !matches!(
+            self.option("active_high"),
+            Some("false" | "no" | "off" | "0")
+        )
+    }
+
+    /// Set an option, replacing any previous value.
+    pub fn set_option(&mut self, key: &str, value: &str) {
+        match self.options.iter_mut().find(|(k, _)| k == key) {
+            Some(option) => option.1 = value.to_string(),
+            None => self.options.push((key.to_string(), value.to_string())),
+        }
+    }
+}
+
+/// A parsed configuration file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    /// Settings before the first device section, in file order
+    pub settings: Vec<(String, String)>,
+    /// The configured devices, in file order
+    pub devices: Vec<DeviceConfig>,
+}
+
+/// A syntax error in a configuration file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError {
+    /// Line of the error, starting at 1
+    pub line: usize,
+    /// Description of the problem
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for ConfigError {}
+
+impl Config {
+    /// Returns an empty Config
+    pub fn new() -> Config {
+        Config::default()
+    }
+
+    /// Read and parse a configuration file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Config, Box<dyn Error>> {
+        let text = fs::read_to_string(path)?;
+        Ok(Config::parse(&text)?)
+    }
+
+    /// Parse the text of a configuration file.
+    pub fn parse(text: &str) -> Result<Config, ConfigError> {
+        let mut config = Config::new();
+        let mut sections = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line_number = number + 1;
+            let error = |message: String| ConfigError {
+                line: line_number,
+                message,
+            };
+            let line = match line.find('#') {
+                Some(comment) => &line[..comment],
+                None => line,
+            }
+            .trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[') {
+                let name = match name.strip_suffix(']') {
+                    Some(name) => name.trim(),
+                    None => return Err(error("missing ']' after section name".to_string())),
+                };
+                if name.is_empty() {
+                    return Err(error("empty section name".to_string()));
+                }
+                if config.device(name).is_some() {
+                    return Err(error(format!("device '{}' is defined twice", name)));
+                }
+                config.devices.push(DeviceConfig::new(name, "", &[]));
+                sections.push(line_number);
+                continue;
+            }
+
+            let (key, value) = match line.find('=') {
+                Some(eq) => (line[..eq].trim(), line[eq + 1..].trim()),
+                None => return Err(error(format!("expected 'key = value', found '{}'", line))),
+            };
+            if key.is_empty() {
+                return Err(error("missing key before '='".to_string()));
+            }
+            let device = match config.devices.last_mut() {
+                Some(device) => device,
+                None => {
+                    config.settings.push((key.to_string(), value.to_string()));
+                    continue;
+                }
+            };
+            match key {
+                "kind" => device.kind = value.to_string(),
+                "pin" | "pins" => {
+                    device.pins = Vec::new();
+                    for pin in value.split(',') {
+                        match pin.trim().parse() {
+                            Ok(pin) => device.pins.push(pin),
+                            Err(_) => {
+                                return Err(error(format!("'{}' is not a pin number", pin.trim())))
+                            }
+                        }
+                    }
+                }
+                "active_high" => match value {
+                    "true" | "yes" | "on" | "1" | "false" | "no" | "off" | "0" => {
+                        device.set_option(key, value)
+                    }
+                    _ => {
+                        return Err(error(format!(
+                            "active_high must be true or false, found '{}'",
+                            value
+                        )))
+                    }
+                },
+                _ => device.set_option(key, value),
+            }
+        }
+
+        for (device, &line) in config.devices.iter().zip(&sections) {
+            if device.kind.is_empty() {
+                return Err(ConfigError {
+                    line,
+                    message: format!("device '{}' has no kind", device.name),
+                });
+            }
+        }
+        Ok(config)
+    }
+
+    /// The value of a global setting, or None if it is not set.
+    pub fn setting(&self, key: &str) -> Option<&str> {
+        self.settings
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns ``True`` if the global setting is `true`, `yes`, `on` or `1`.
+    pub fn enabled(&self, key: &str) -> bool {
+        matches!(self.setting(key), Some("true" | "yes" | "on" | "1"))
+    }
+
+    /// The device with the given name, or None if there is no such device.
+    pub fn device(&self, name: &str) -> Option<&DeviceConfig> {
+        self.devices.iter().find(|d| d.name == name)
+    }
+
+    /// Add a device to the configuration.
+    pub fn add_device(&mut self, device: DeviceConfig) {
+        self.devices.push(device);
+    }
+}
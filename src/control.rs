@@ -0,0 +1,303 @@
+//! Newline-delimited JSON control protocol for a `DeviceTree`
+//!
+//! `ControlServer` exposes the inspectable devices of a tree on a local Unix socket so that
+//! shell scripts and programs in other languages can read and change them. Every request
+//! and response is one JSON object on its own line. Requests may carry an `id`, which is
+//! copied into the response.
+//!
+//! | request | response |
+//! |---------|----------|
+//! | `{"cmd":"list"}` | `{"ok":true,"devices":[{"name":"fan","value":true}]}` |
+//! | `{"cmd":"get","device":"fan"}` | `{"ok":true,"value":true}` |
+//! | `{"cmd":"set","device":"fan","value":false}` | `{"ok":true}` |
+//! | `{"cmd":"subscribe"}` or `{"cmd":"subscribe","device":"fan"}` | `{"ok":true}` |
+//!
+//! Failed requests are answered with `{"ok":false,"error":"..."}`. After `subscribe` the
+//! server also sends `{"event":"value","device":...,"value":...}` whenever the value of a
+//! watched device changes, and `{"event":"log","device":...,"message":...,"time":...}` for
+//! every entry added to the event log.
+//!
+//! ```text
+//! $ echo '{"cmd":"set","device":"fan","value":true}' | socat - UNIX-CONNECT:/run/greenhouse.sock
+//! {"ok":true}
+//! ```
+use crate::json::Json;
+use crate::tree::DeviceTree;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, UNIX_EPOCH};
+
+/// How often subscribed sessions check for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Accepts connections on a Unix socket and runs a session thread for each of them.
+pub(crate) struct SocketServer {
+    path: PathBuf,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl SocketServer {
+    /// Bind the socket, replacing a socket file left at the path, and start accepting
+    /// connections. Any other file at the path is left alone and an error returned.
+    pub(crate) fn bind<F>(path: &Path, session: F) -> io::Result<SocketServer>
+    where
+        F: Fn(UnixStream, Arc<AtomicBool>) + Send + Sync + 'static,
+    {
+        // only replace a socket left behind, never a file that happens to have the path
+        match fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.file_type().is_socket() => fs::remove_file(path)?,
+            Ok(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} exists and is not a socket", path.display()),
+                ))
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        let listener = UnixListener::bind(path)?;
+        listener.set_nonblocking(true)?;
+        let running = Arc::new(AtomicBool::new(true));
+        let still_running = running.clone();
+        let session = Arc::new(session);
+        let handle = thread::spawn(move || {
+            while still_running.load(Ordering::SeqCst) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        let session = session.clone();
+                        let running = still_running.clone();
+                        thread::spawn(move || session(stream, running));
+                    }
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        thread::sleep(POLL_INTERVAL)
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+        Ok(SocketServer {
+            path: path.to_path_buf(),
+            running,
+            handle: Some(handle),
+        })
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub(crate) fn stop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+impl Drop for SocketServer {
+    fn drop(&mut self) {
+        self.stop()
+    }
+}
+
+/// Serves the control protocol for a `DeviceTree` on a Unix socket. The socket is removed
+/// when the server is dropped.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+/// use std::sync::{Arc, Mutex};
+///
+/// let mut tree = DeviceTree::default();
+/// tree.add_inspectable("fan", LED::new(17)).unwrap();
+/// let tree = Arc::new(Mutex::new(tree));
+/// let _server = ControlServer::serve(tree.clone(), "/run/greenhouse.sock").unwrap();
+/// ```
+pub struct ControlServer {
+    server: SocketServer,
+}
+
+impl ControlServer {
+    /// Start serving the tree on a Unix socket. A socket file left at the path is replaced;
+    /// any other file there is left alone and an error of kind `AlreadyExists` returned.
+    /// * `tree` - The device tree shared with the application
+    /// * `path` - Path of the socket
+    pub fn serve<P: AsRef<Path>>(
+        tree: Arc<Mutex<DeviceTree>>,
+        path: P,
+    ) -> io::Result<ControlServer> {
+        let server = SocketServer::bind(path.as_ref(), move |stream, running| {
+            let _ = session(stream, tree.clone(), running);
+        })?;
+        Ok(ControlServer { server })
+    }
+
+    /// Path of the socket.
+    pub fn path(&self) -> &Path {
+        self.server.path()
+    }
+
+    /// Stop accepting connections, end all sessions and remove the socket.
+    pub fn stop(&mut self) {
+        self.server.stop()
+    }
+}
+
+/// Answer one request line against the tree. Returns the response and, for `subscribe`,
+/// the devices to watch (an empty list watches every device).
+pub fn handle_request(tree: &mut DeviceTree, line: &str) -> (Json, Option<Vec<String>>) {
+    let mut subscription = None;
+    let request = Json::parse(line);
+    let result = match &request {
+        Err(error) => Err(format!("invalid JSON: {}", error)),
+        Ok(request) => {
+            let device = request.get("device").and_then(Json::as_str);
+            match (request.get("cmd").and_then(Json::as_str), device) {
+                (Some("list"), _) => {
+                    let names: Vec<String> = tree.names().iter().map(|n| n.to_string()).collect();
+                    let devices = names
+                        .iter()
+                        .map(|name| {
+                            let value = match tree.value(name) {
+                                Ok(value) => Json::from_text(&value),
+                                Err(_) => Json::Null,
+                            };
+                            Json::object(vec![("name", name.as_str().into()), ("value", value)])
+                        })
+                        .collect();
+                    Ok(vec![("devices", Json::Array(devices))])
+                }
+                (Some("get"), Some(device)) => tree
+                    .value(device)
+                    .map(|value| vec![("value", Json::from_text(&value))]),
+                (Some("set"), Some(device)) => match request.get("value") {
+                    Some(value) => tree.set_value(device, &value.to_text()).map(|_| vec![]),
+                    None => Err("missing \"value\"".to_string()),
+                },
+                (Some("subscribe"), device) => {
+                    subscription = Some(device.map(String::from).into_iter().collect());
+                    Ok(vec![])
+                }
+                (Some("get"), None) | (Some("set"), None) => Err("missing \"device\"".to_string()),
+                (Some(cmd), _) => Err(format!("unknown command '{}'", cmd)),
+                (None, _) => Err("missing \"cmd\"".to_string()),
+            }
+        }
+    };
+
+    let mut response: Vec<(&str, Json)> = Vec::new();
+    if let Some(id) = request.as_ref().ok().and_then(|r| r.get("id")) {
+        response.push(("id", id.clone()));
+    }
+    match result {
+        Ok(members) => {
+            response.push(("ok", true.into()));
+            response.extend(members);
+        }
+        Err(error) => {
+            response.push(("ok", false.into()));
+            response.push(("error", error.into()));
+            subscription = None;
+        }
+    }
+    (Json::object(response), subscription)
+}
+
+fn session(
+    stream: UnixStream,
+    tree: Arc<Mutex<DeviceTree>>,
+    running: Arc<AtomicBool>,
+) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(POLL_INTERVAL))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+    let mut watched: Option<Vec<String>> = None;
+    let mut last_values: Vec<(String, String)> = Vec::new();
+    let mut seen_events = 0;
+
+    while running.load(Ordering::SeqCst) {
+        // messages are collected while the tree is locked and written afterwards, so that
+        // a client that stops reading can never block the application
+        let mut messages: Vec<Json> = Vec::new();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => break,
+            Ok(_) if line.ends_with(b"\n") => {
+                let text = String::from_utf8_lossy(&line).trim().to_string();
+                line.clear();
+                if !text.is_empty() {
+                    let mut tree = tree.lock().unwrap();
+                    let (response, subscription) = handle_request(&mut tree, &text);
+                    messages.push(response);
+                    if let Some(devices) = subscription {
+                        watched = Some(devices);
+                        seen_events = tree.event_count();
+                        last_values.clear();
+                    }
+                }
+            }
+            Ok(_) => {}
+            Err(ref e)
+                if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => {
+            }
+            Err(e) => return Err(e),
+        }
+
+        if let Some(devices) = &watched {
+            let mut tree = tree.lock().unwrap();
+            for event in tree.events_since(seen_events) {
+                let time = event.time.duration_since(UNIX_EPOCH).unwrap_or_default();
+                messages.push(Json::object(vec![
+                    ("event", "log".into()),
+                    ("device", event.device.as_str().into()),
+                    ("message", event.message.as_str().into()),
+                    ("time", time.as_secs_f64().into()),
+                ]));
+            }
+            seen_events = tree.event_count();
+
+            let names: Vec<String> = if devices.is_empty() {
+                tree.names().iter().map(|n| n.to_string()).collect()
+            } else {
+                devices.clone()
+            };
+            for name in names {
+                let value = match tree.value(&name) {
+                    Ok(value) => value,
+                    Err(_) => continue,
+                };
+                match last_values.iter_mut().find(|(n, _)| *n == name) {
+                    Some((_, last)) if *last == value => continue,
+                    Some((_, last)) => *last = value.clone(),
+                    None => last_values.push((name.clone(), value.clone())),
+                }
+                messages.push(Json::object(vec![
+                    ("event", "value".into()),
+                    ("device", name.into()),
+                    ("value", Json::from_text(&value)),
+                ]));
+            }
+        }
+
+        if !messages.is_empty() {
+            let mut text = String::new();
+            for message in messages {
+                text.push_str(&message.to_string());
+                text.push('\n');
+            }
+            writer.write_all(text.as_bytes())?;
+        }
+    }
+    Ok(())
+}
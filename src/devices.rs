@@ -1,6 +1,24 @@
 //! Describes generic devices such as `GPIODevice` and `CompositeDevice`
 
+use crate::i2c_devices::{ADSChannel, CAP1208};
+use crate::input_devices::{
+    Button, DHTSensor, DigitalInputDevice, DigitalInputDeviceR, DistanceSensor, IRReceiver,
+    InputDevice, Keypad, LightSensor, MotionSensor, PWMInputDevice, PulseCounter, RotaryEncoder,
+    SmoothedInputDevice, SonarArray, Tachometer, WiegandReader, HX711,
+};
+use crate::internal_devices::{CPUTemperature, DiskUsage, LoadAverage, SunTimes, TimeOfDay};
+use crate::json::Json;
+use crate::output_devices::{
+    sleep_while_running, Buzzer, DigitalOutputDevice, Motor, OutputDevice, PWMOutputDevice, LED,
+    PWMLED, RGBLED,
+};
+use crate::spi_devices::MCPChannel;
 use rppal::gpio::{Gpio, Level, Pin};
+use std::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
 /// Represents a single device of any type; GPIO-based, SPI-based, I2C-based,
 /// etc.  It defines the basic services applicable to all devices
@@ -31,6 +49,7 @@ macro_rules! impl_device {
 pub struct GpioDevice {
     pin: Pin,
     active_state: bool,
+    #[allow(dead_code)]
     inactive_state: bool,
 }
 
@@ -77,3 +96,795 @@ impl GpioDevice {
     impl_device!();
     impl_gpio_device!();
 }
+
+/// The fastest rate in Hz at which a pin switched by a thread, e.g. for software PWM, still
+/// changes on time. Above it the scheduler's jitter becomes a large part of each period.
+pub const SOFTWARE_TOGGLE_RATE: f64 = 1_000.0;
+
+/// The fastest rate in Hz at which hardware PWM keeps a duty cycle resolution of 1%.
+pub const HARDWARE_TOGGLE_RATE: f64 = 1_000_000.0;
+
+/// What a device or pin can do, so code can adapt to the hardware it finds, e.g. fall back
+/// to software PWM or to polling, instead of failing when a feature is used.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Capabilities {
+    /// Hardware PWM is available, which keeps its timing whatever the load of the CPU
+    pub hardware_pwm: bool,
+    /// Changes are reported by interrupts rather than found by polling
+    pub interrupts: bool,
+    /// The fastest rate in Hz at which the output can be switched reliably, None if it is
+    /// not an output
+    pub max_toggle_rate: Option<f64>,
+    /// The number of bits in a reading of an analog input, None if it is not one
+    pub adc_bits: Option<u8>,
+}
+
+/// A device that can be a child of a `CompositeDevice`.
+pub trait Component: Send {
+    /// The values of the device as numbers: 1.0 or 0.0 for devices that are on or off,
+    /// the brightness or duty cycle for PWM devices, one value per LED for boards.
+    fn values(&self) -> Vec<f64>;
+
+    /// Returns ``True`` if the device is currently active and ``False`` otherwise.
+    fn is_active(&self) -> bool {
+        self.values().iter().any(|&value| value != 0.0)
+    }
+
+    /// Put the device in a safe state: outputs are turned off, inputs are left as they are.
+    fn close(&mut self) {}
+
+    /// What the device can do. Devices that report nothing return the default, with every
+    /// feature missing.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+}
+
+/// An output device that can be a child of a `CompositeOutputDevice`.
+pub trait OutputComponent: Component {
+    /// Turn the device on.
+    fn on(&mut self);
+
+    /// Turn the device off.
+    fn off(&mut self);
+
+    /// Reverse the state of the device.
+    fn toggle(&mut self);
+}
+
+/// A channel of an analog to digital converter (ADC), whichever chip or bus it is on, so
+/// that code reading a sensor works with any converter.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// fn watch_level<A: AnalogInputDevice>(tank: &mut A) {
+///     tank.when_below(0.2, |level| println!("tank low: {:.0}%", level * 100.0));
+///     println!("{:.2}V", tank.voltage());
+/// }
+///
+/// let mut spi_tank = MCP3008::new().channel(0);
+/// let mut i2c_tank = ADS1115::new().channel(0);
+/// watch_level(&mut spi_tank);
+/// watch_level(&mut i2c_tank);
+/// ```
+pub trait AnalogInputDevice: Send {
+    /// The number of bits in a raw reading.
+    fn bits(&self) -> u8;
+
+    /// Returns ``True`` if raw readings can be negative, as in the differential mode of
+    /// some converters.
+    fn is_signed(&self) -> bool {
+        false
+    }
+
+    /// Read the channel, returning the count from the converter.
+    fn raw_value(&self) -> Result<i32, Box<dyn Error + Send + Sync>>;
+
+    /// Read the channel, returning a value between 0.0 and 1.0, or -1.0 and 1.0 if readings
+    /// are signed.
+    fn try_value(&self) -> Result<f64, Box<dyn Error + Send + Sync>> {
+        let bits = if self.is_signed() {
+            self.bits() - 1
+        } else {
+            self.bits()
+        };
+        let full_scale = ((1_i64 << bits) - 1) as f64;
+        Ok((f64::from(self.raw_value()?) / full_scale).clamp(-1.0, 1.0))
+    }
+
+    /// Read the channel as `try_value` does, returning NaN if the read fails, which a
+    /// `Supervisor` reports as invalid.
+    fn value(&self) -> f64 {
+        self.try_value().unwrap_or(f64::NAN)
+    }
+
+    /// The voltage of a full-scale reading, i.e. a value of 1.0.
+    fn max_voltage(&self) -> f64;
+
+    /// Read the channel in volts.
+    fn try_voltage(&self) -> Result<f64, Box<dyn Error + Send + Sync>> {
+        Ok(self.try_value()? * self.max_voltage())
+    }
+
+    /// Read the channel in volts as `try_voltage` does, returning NaN if the read fails.
+    fn voltage(&self) -> f64 {
+        self.try_voltage().unwrap_or(f64::NAN)
+    }
+
+    /// Set the function called from a background thread with the value when it rises above
+    /// the threshold. The channel is read every 10ms once a function is set. After a call,
+    /// the value must fall below the threshold less the hysteresis before it is called again.
+    /// * `threshold` - The value, between 0.0 and 1.0 (-1.0 and 1.0 if readings are signed)
+    fn when_above<C>(&mut self, threshold: f64, callback: C)
+    where
+        C: FnMut(f64) + Send + 'static,
+        Self: Sized;
+
+    /// Set the function called from a background thread with the value when it falls below
+    /// the threshold. The channel is read every 10ms once a function is set. After a call,
+    /// the value must rise above the threshold plus the hysteresis before it is called again.
+    /// * `threshold` - The value, between 0.0 and 1.0 (-1.0 and 1.0 if readings are signed)
+    fn when_below<C>(&mut self, threshold: f64, callback: C)
+    where
+        C: FnMut(f64) + Send + 'static,
+        Self: Sized;
+
+    /// The band `when_above` and `when_below` must cross back through before they are
+    /// called again.
+    fn hysteresis(&self) -> f64;
+
+    /// Set the band `when_above` and `when_below` must cross back through before they are
+    /// called again, so a value hovering around a threshold does not call them over and
+    /// over. 0.0 by default.
+    /// * `hysteresis` - The width of the band, in the units of the value
+    fn set_hysteresis(&mut self, hysteresis: f64);
+}
+
+/// How often an `AnalogWatch` reads its channel.
+const ANALOG_WATCH_INTERVAL: Duration = Duration::from_millis(10);
+
+type ThresholdCallback = Box<dyn FnMut(f64) + Send>;
+
+/// A threshold of an `AnalogWatch` and whether the readings are past it.
+struct Threshold {
+    level: f64,
+    past: Option<bool>,
+    callback: ThresholdCallback,
+}
+
+impl Threshold {
+    /// Call the callback if the reading went past the threshold. The readings stay past it
+    /// until they come back by more than `hysteresis`.
+    /// * `beyond` - How far the reading is past the threshold, negative if it is short of it
+    fn check(&mut self, beyond: f64, hysteresis: f64, value: f64) {
+        let past = match self.past {
+            Some(true) => beyond >= -hysteresis,
+            _ => beyond > 0.0,
+        };
+        if self.past.replace(past) == Some(false) && past {
+            (self.callback)(value)
+        }
+    }
+}
+
+/// The thresholds of `when_above` and `when_below`, and the hysteresis band they share.
+#[derive(Default)]
+pub(crate) struct Thresholds {
+    above: Option<Threshold>,
+    below: Option<Threshold>,
+    hysteresis: f64,
+}
+
+impl Thresholds {
+    /// Set the function called when readings cross the threshold, upwards if `above`.
+    pub(crate) fn set<C>(&mut self, above: bool, level: f64, callback: C)
+    where
+        C: FnMut(f64) + Send + 'static,
+    {
+        let threshold = Some(Threshold {
+            level,
+            past: None,
+            callback: Box::new(callback),
+        });
+        if above {
+            self.above = threshold;
+        } else {
+            self.below = threshold;
+        }
+    }
+
+    /// Call the functions of the thresholds the reading has crossed. NaN readings are
+    /// ignored.
+    pub(crate) fn check(&mut self, value: f64) {
+        if value.is_nan() {
+            return;
+        }
+        let hysteresis = self.hysteresis;
+        if let Some(above) = &mut self.above {
+            above.check(value - above.level, hysteresis, value);
+        }
+        if let Some(below) = &mut self.below {
+            below.check(below.level - value, hysteresis, value);
+        }
+    }
+
+    pub(crate) fn hysteresis(&self) -> f64 {
+        self.hysteresis
+    }
+
+    pub(crate) fn set_hysteresis(&mut self, hysteresis: f64) {
+        if hysteresis < 0.0 {
+            println!("Hysteresis must not be negative");
+            return;
+        }
+        self.hysteresis = hysteresis;
+    }
+}
+
+/// The background thread behind `when_above` and `when_below` of an analog input, started
+/// when the first function is set.
+#[derive(Default)]
+pub(crate) struct AnalogWatch {
+    thresholds: Arc<Mutex<Thresholds>>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl AnalogWatch {
+    /// Set the function called when readings cross the threshold, upwards if `above`.
+    /// * `read` - Reads the channel, used by the thread if it is not running yet
+    pub(crate) fn set<C, R>(&mut self, above: bool, level: f64, callback: C, read: R)
+    where
+        C: FnMut(f64) + Send + 'static,
+        R: Fn() -> f64 + Send + 'static,
+    {
+        self.thresholds.lock().unwrap().set(above, level, callback);
+        if self.handle.is_some() {
+            return;
+        }
+        self.running.store(true, Ordering::SeqCst);
+        let thresholds = Arc::clone(&self.thresholds);
+        let running = Arc::clone(&self.running);
+        self.handle = Some(thread::spawn(move || {
+            while running.load(Ordering::SeqCst) {
+                let value = read();
+                thresholds.lock().unwrap().check(value);
+                sleep_while_running(&running, ANALOG_WATCH_INTERVAL);
+            }
+        }));
+    }
+}
+
+impl AnalogWatch {
+    pub(crate) fn hysteresis(&self) -> f64 {
+        self.thresholds.lock().unwrap().hysteresis()
+    }
+
+    pub(crate) fn set_hysteresis(&mut self, hysteresis: f64) {
+        self.thresholds.lock().unwrap().set_hysteresis(hysteresis)
+    }
+}
+
+impl Drop for AnalogWatch {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+/// A device made of several named children, such as a robot made of motors and sensors.
+///
+/// Implement it with `#[derive(CompositeDevice)]`, which also implements `Inspect` so the
+/// device can be added to a `DeviceTree` with `add_inspectable`. Every composite device is
+/// a `Component` itself: its values are those of its children in order, it is active if
+/// any child is active, and closing it closes the children in reverse order.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// #[derive(CompositeDevice)]
+/// struct Doorbell {
+///     button: Button,
+///     chime: Buzzer,
+///     light: LED,
+/// }
+///
+/// let mut doorbell = Doorbell {
+///     button: Button::new(2),
+///     chime: Buzzer::new(3),
+///     light: LED::new(4),
+/// };
+/// doorbell.light.on();
+/// println!("{:?} {}", doorbell.names(), doorbell.is_active());
+/// doorbell.close();
+/// ```
+pub trait CompositeDevice {
+    /// The names of the children, in order.
+    fn names(&self) -> &'static [&'static str];
+
+    /// The child with the given name, or None if there is no such child.
+    fn child(&self, name: &str) -> Option<&dyn Component>;
+
+    /// The child with the given name, or None if there is no such child.
+    fn child_mut(&mut self, name: &str) -> Option<&mut dyn Component>;
+
+    /// The values of the children as a JSON object, keyed by name.
+    fn to_json(&self) -> Json {
+        Json::Object(
+            self.names()
+                .iter()
+                .filter_map(|&name| self.child(name).map(|child| (name, child.values())))
+                .map(|(name, values)| {
+                    let value = match values.as_slice() {
+                        [value] => Json::Number(*value),
+                        values => Json::Array(values.iter().map(|&v| Json::Number(v)).collect()),
+                    };
+                    (name.to_string(), value)
+                })
+                .collect(),
+        )
+    }
+}
+
+/// A `CompositeDevice` whose children are all outputs, so that they can be switched together.
+///
+/// Implement it with `#[derive(CompositeDevice, CompositeOutputDevice)]`. Every composite
+/// output device is an `OutputComponent` itself: `on`, `off` and `toggle` apply to every
+/// child in order.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// #[derive(CompositeDevice, CompositeOutputDevice)]
+/// struct Lighthouse {
+///     lamp: PWMLED,
+///     horn: Buzzer,
+/// }
+///
+/// let mut lighthouse = Lighthouse {
+///     lamp: PWMLED::new(17),
+///     horn: Buzzer::new(18),
+/// };
+/// lighthouse.on();
+/// println!("{:?}", lighthouse.values());
+/// lighthouse.off();
+/// ```
+pub trait CompositeOutputDevice: CompositeDevice {
+    /// The child with the given name, or None if there is no such child.
+    fn output_mut(&mut self, name: &str) -> Option<&mut dyn OutputComponent>;
+}
+
+impl<T: CompositeDevice + Send> Component for T {
+    fn values(&self) -> Vec<f64> {
+        self.names()
+            .iter()
+            .filter_map(|&name| self.child(name))
+            .flat_map(|child| child.values())
+            .collect()
+    }
+
+    fn is_active(&self) -> bool {
+        self.names()
+            .iter()
+            .filter_map(|&name| self.child(name))
+            .any(|child| child.is_active())
+    }
+
+    fn close(&mut self) {
+        for &name in self.names().iter().rev() {
+            if let Some(child) = self.child_mut(name) {
+                child.close()
+            }
+        }
+    }
+}
+
+impl<T: CompositeOutputDevice + Send> OutputComponent for T {
+    fn on(&mut self) {
+        for &name in self.names() {
+            if let Some(output) = self.output_mut(name) {
+                output.on()
+            }
+        }
+    }
+
+    fn off(&mut self) {
+        for &name in self.names() {
+            if let Some(output) = self.output_mut(name) {
+                output.off()
+            }
+        }
+    }
+
+    fn toggle(&mut self) {
+        for &name in self.names() {
+            if let Some(output) = self.output_mut(name) {
+                output.toggle()
+            }
+        }
+    }
+}
+
+macro_rules! impl_component_digital_output {
+    ($($device:ty),*) => {
+        $(
+            impl Component for $device {
+                fn values(&self) -> Vec<f64> {
+                    vec![if <$device>::is_active(self) { 1.0 } else { 0.0 }]
+                }
+
+                fn close(&mut self) {
+                    <$device>::off(self)
+                }
+
+                fn capabilities(&self) -> Capabilities {
+                    Capabilities {
+                        max_toggle_rate: Some(SOFTWARE_TOGGLE_RATE),
+                        ..Capabilities::default()
+                    }
+                }
+            }
+
+            impl OutputComponent for $device {
+                fn on(&mut self) {
+                    <$device>::on(self)
+                }
+
+                fn off(&mut self) {
+                    <$device>::off(self)
+                }
+
+                fn toggle(&mut self) {
+                    <$device>::toggle(self)
+                }
+            }
+        )*
+    };
+}
+
+impl_component_digital_output!(OutputDevice, DigitalOutputDevice, LED, Buzzer);
+
+macro_rules! impl_component_pwm_output {
+    ($($device:ty),*) => {
+        $(
+            impl Component for $device {
+                fn values(&self) -> Vec<f64> {
+                    vec![<$device>::value(self)]
+                }
+
+                fn close(&mut self) {
+                    <$device>::off(self)
+                }
+
+                fn capabilities(&self) -> Capabilities {
+                    Capabilities {
+                        max_toggle_rate: Some(SOFTWARE_TOGGLE_RATE),
+                        ..Capabilities::default()
+                    }
+                }
+            }
+
+            impl OutputComponent for $device {
+                fn on(&mut self) {
+                    <$device>::on(self)
+                }
+
+                fn off(&mut self) {
+                    <$device>::off(self)
+                }
+
+                fn toggle(&mut self) {
+                    <$device>::toggle(self)
+                }
+            }
+        )*
+    };
+}
+
+impl_component_pwm_output!(PWMOutputDevice, PWMLED);
+
+impl Component for RGBLED {
+    fn values(&self) -> Vec<f64> {
+        let (red, green, blue) = self.value();
+        vec![red, green, blue]
+    }
+
+    fn close(&mut self) {
+        self.off()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            max_toggle_rate: Some(SOFTWARE_TOGGLE_RATE),
+            ..Capabilities::default()
+        }
+    }
+}
+
+impl OutputComponent for RGBLED {
+    fn on(&mut self) {
+        RGBLED::on(self)
+    }
+
+    fn off(&mut self) {
+        RGBLED::off(self)
+    }
+
+    fn toggle(&mut self) {
+        RGBLED::toggle(self)
+    }
+}
+
+impl Component for Motor {
+    fn values(&self) -> Vec<f64> {
+        vec![self.value()]
+    }
+
+    fn close(&mut self) {
+        self.stop()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            max_toggle_rate: Some(SOFTWARE_TOGGLE_RATE),
+            ..Capabilities::default()
+        }
+    }
+}
+
+macro_rules! impl_component_input {
+    ($($device:ty),*) => {
+        $(
+            impl Component for $device {
+                fn values(&self) -> Vec<f64> {
+                    vec![if <$device>::is_active(self) { 1.0 } else { 0.0 }]
+                }
+
+                fn capabilities(&self) -> Capabilities {
+                    Capabilities {
+                        interrupts: true,
+                        ..Capabilities::default()
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_component_input!(InputDevice, DigitalInputDevice, DigitalInputDeviceR, Button);
+
+impl Component for SunTimes {
+    fn values(&self) -> Vec<f64> {
+        vec![if SunTimes::is_active(self) { 1.0 } else { 0.0 }]
+    }
+}
+
+impl Component for TimeOfDay {
+    fn values(&self) -> Vec<f64> {
+        vec![if TimeOfDay::is_active(self) { 1.0 } else { 0.0 }]
+    }
+}
+
+impl Component for CPUTemperature {
+    fn values(&self) -> Vec<f64> {
+        vec![CPUTemperature::value(self)]
+    }
+
+    fn is_active(&self) -> bool {
+        CPUTemperature::is_active(self)
+    }
+}
+
+impl Component for LoadAverage {
+    fn values(&self) -> Vec<f64> {
+        vec![LoadAverage::value(self)]
+    }
+
+    fn is_active(&self) -> bool {
+        LoadAverage::is_active(self)
+    }
+}
+
+impl Component for DiskUsage {
+    fn values(&self) -> Vec<f64> {
+        vec![DiskUsage::value(self)]
+    }
+
+    fn is_active(&self) -> bool {
+        DiskUsage::is_active(self)
+    }
+}
+
+impl Component for DistanceSensor {
+    fn values(&self) -> Vec<f64> {
+        vec![DistanceSensor::value(self)]
+    }
+
+    fn is_active(&self) -> bool {
+        self.in_range()
+    }
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            interrupts: true,
+            ..Capabilities::default()
+        }
+    }
+}
+
+impl Component for Keypad {
+    fn values(&self) -> Vec<f64> {
+        vec![if Keypad::is_active(self) { 1.0 } else { 0.0 }]
+    }
+}
+
+impl Component for PWMInputDevice {
+    fn values(&self) -> Vec<f64> {
+        vec![self.duty_cycle()]
+    }
+
+    fn is_active(&self) -> bool {
+        PWMInputDevice::is_active(self)
+    }
+}
+
+impl Component for PulseCounter {
+    fn values(&self) -> Vec<f64> {
+        vec![self.pulses_per_second()]
+    }
+
+    fn is_active(&self) -> bool {
+        PulseCounter::is_active(self)
+    }
+}
+
+impl Component for Tachometer {
+    fn values(&self) -> Vec<f64> {
+        vec![self.rpm()]
+    }
+
+    fn is_active(&self) -> bool {
+        Tachometer::is_active(self)
+    }
+}
+
+impl Component for IRReceiver {
+    fn values(&self) -> Vec<f64> {
+        match self.last_code() {
+            Some((address, command)) => vec![f64::from(address), f64::from(command)],
+            None => vec![0.0, 0.0],
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        IRReceiver::is_active(self)
+    }
+}
+
+impl Component for WiegandReader {
+    fn values(&self) -> Vec<f64> {
+        vec![self.last_card().map_or(0.0, |card| card.id as f64)]
+    }
+
+    fn is_active(&self) -> bool {
+        false
+    }
+}
+
+impl Component for RotaryEncoder {
+    fn values(&self) -> Vec<f64> {
+        vec![RotaryEncoder::value(self)]
+    }
+
+    fn is_active(&self) -> bool {
+        RotaryEncoder::is_active(self)
+    }
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            interrupts: true,
+            ..Capabilities::default()
+        }
+    }
+}
+
+impl Component for SonarArray {
+    fn values(&self) -> Vec<f64> {
+        self.distances()
+    }
+
+    fn is_active(&self) -> bool {
+        self.sensors().iter().any(DistanceSensor::in_range)
+    }
+}
+
+impl Component for DHTSensor {
+    fn values(&self) -> Vec<f64> {
+        self.last_reading().map_or_else(Vec::new, |reading| {
+            vec![reading.temperature, reading.humidity]
+        })
+    }
+
+    fn is_active(&self) -> bool {
+        self.last_reading().is_some()
+    }
+}
+
+impl Component for HX711 {
+    fn values(&self) -> Vec<f64> {
+        self.last_value().into_iter().collect()
+    }
+}
+
+impl Component for ADSChannel {
+    fn values(&self) -> Vec<f64> {
+        vec![AnalogInputDevice::value(self)]
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            adc_bits: Some(self.bits()),
+            ..Capabilities::default()
+        }
+    }
+}
+
+impl Component for CAP1208 {
+    fn values(&self) -> Vec<f64> {
+        let pads = self.touched().unwrap_or_default();
+        pads.iter()
+            .map(|&touched| if touched { 1.0 } else { 0.0 })
+            .collect()
+    }
+}
+
+impl Component for MCPChannel {
+    fn values(&self) -> Vec<f64> {
+        vec![AnalogInputDevice::value(self)]
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            adc_bits: Some(self.bits()),
+            ..Capabilities::default()
+        }
+    }
+}
+
+impl Component for SmoothedInputDevice {
+    fn values(&self) -> Vec<f64> {
+        vec![SmoothedInputDevice::value(self)]
+    }
+
+    fn is_active(&self) -> bool {
+        SmoothedInputDevice::is_active(self)
+    }
+}
+
+impl Component for MotionSensor {
+    fn values(&self) -> Vec<f64> {
+        vec![MotionSensor::value(self)]
+    }
+
+    fn is_active(&self) -> bool {
+        self.motion_detected()
+    }
+}
+
+impl Component for LightSensor {
+    fn values(&self) -> Vec<f64> {
+        vec![LightSensor::value(self)]
+    }
+
+    fn is_active(&self) -> bool {
+        self.light_detected()
+    }
+}
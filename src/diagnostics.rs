@@ -0,0 +1,322 @@
+//! Checks of the environment a program needs to drive the pins
+//!
+//! Most failures on a new setup are not bugs but a missing interface or permission, which
+//! otherwise surface as a panic from the first device created. `check` looks at the board,
+//! `/dev/gpiomem`, the groups of the user, the I2C, SPI and 1-Wire interfaces and the
+//! overlays in `config.txt`, without claiming any pins, and says how to fix what it finds.
+//! The `gpiozero doctor` command prints the same report.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::diagnostics;
+//!
+//! let report = diagnostics::check();
+//! if !report.is_ok() {
+//!     eprintln!("{}", report);
+//!     std::process::exit(1);
+//! }
+//! ```
+use crate::tree::Platform;
+use rppal::gpio::Gpio;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::ErrorKind;
+use std::path::Path;
+
+/// Where the firmware reads `config.txt` from, newest first.
+const CONFIG_PATHS: [&str; 2] = ["/boot/firmware/config.txt", "/boot/config.txt"];
+
+/// How a check went.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    /// Nothing to do
+    Ok,
+    /// Only some devices are affected, e.g. an interface that is not enabled
+    Warning,
+    /// No device can work until this is fixed
+    Error,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Status::Ok => f.pad("ok"),
+            Status::Warning => f.pad("warning"),
+            Status::Error => f.pad("error"),
+        }
+    }
+}
+
+/// The outcome of one check.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+    /// What was checked, such as `I2C`
+    pub name: String,
+    /// How it went
+    pub status: Status,
+    /// What was found
+    pub detail: String,
+    /// How to fix it, when the status is not `Ok`
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &str, detail: String) -> Check {
+        Check {
+            name: name.to_string(),
+            status: Status::Ok,
+            detail,
+            fix: None,
+        }
+    }
+
+    fn failed(name: &str, status: Status, detail: String, fix: &str) -> Check {
+        Check {
+            name: name.to_string(),
+            status,
+            detail,
+            fix: Some(fix.to_string()),
+        }
+    }
+}
+
+/// The outcome of every check, printed one per line by `Display`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    /// The checks, in the order they ran
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    /// Returns ``True`` if no check found an error. Warnings only affect some devices.
+    pub fn is_ok(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| check.status != Status::Error)
+    }
+
+    /// The checks that did not pass.
+    pub fn problems(&self) -> impl Iterator<Item = &Check> {
+        self.checks
+            .iter()
+            .filter(|check| check.status != Status::Ok)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for check in &self.checks {
+            writeln!(
+                f,
+                "[{:<7}] {:<12} {}",
+                check.status, check.name, check.detail
+            )?;
+            if let Some(fix) = &check.fix {
+                writeln!(f, "{:23}fix: {}", "", fix)?;
+            }
+        }
+        let errors = self
+            .checks
+            .iter()
+            .filter(|check| check.status == Status::Error)
+            .count();
+        let warnings = self
+            .checks
+            .iter()
+            .filter(|check| check.status == Status::Warning)
+            .count();
+        write!(f, "{} errors, {} warnings", errors, warnings)
+    }
+}
+
+/// Run every check. Only reads system information, no pins are claimed.
+pub fn check() -> Report {
+    let groups = user_groups();
+    let config = boot_config();
+    let mut checks = vec![board(), gpiomem(&groups), gpio_backend()];
+    checks.extend(
+        ["gpio", "i2c", "spi"]
+            .iter()
+            .map(|group| membership(group, &groups)),
+    );
+    checks.push(interface(
+        "I2C",
+        &["/dev/i2c-1", "/dev/i2c-0"],
+        "sudo raspi-config nonint do_i2c 0, or add dtparam=i2c_arm=on to config.txt and reboot",
+    ));
+    checks.push(interface(
+        "SPI",
+        &["/dev/spidev0.0"],
+        "sudo raspi-config nonint do_spi 0, or add dtparam=spi=on to config.txt and reboot",
+    ));
+    checks.push(interface(
+        "1-Wire",
+        &["/sys/bus/w1/devices"],
+        "sudo raspi-config nonint do_onewire 0, or add dtoverlay=w1-gpio to config.txt and reboot",
+    ));
+    checks.push(interface(
+        "PWM",
+        &["/sys/class/pwm/pwmchip0"],
+        "add dtoverlay=pwm-2chan to config.txt and reboot; software PWM works without it",
+    ));
+    checks.push(overlays(config));
+    Report { checks }
+}
+
+fn board() -> Check {
+    let platform = Platform::detect();
+    match platform.model {
+        Some(model) => Check::ok("board", model.to_string()),
+        None => {
+            let found = fs::read_to_string("/proc/device-tree/model")
+                .map(|model| model.trim_end_matches('\0').to_string())
+                .unwrap_or_else(|_| "no device tree model".to_string());
+            Check::failed(
+                "board",
+                Status::Error,
+                format!("not a known Raspberry Pi ({})", found),
+                "run on a Raspberry Pi, or use the `inject` feature to test without one",
+            )
+        }
+    }
+}
+
+fn gpiomem(groups: &Groups) -> Check {
+    let path = "/dev/gpiomem";
+    match OpenOptions::new().read(true).write(true).open(path) {
+        Ok(_) => Check::ok("gpiomem", format!("{} is readable and writable", path)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Check::failed(
+            "gpiomem",
+            Status::Error,
+            format!("{} does not exist", path),
+            "use a Raspberry Pi OS kernel, which provides /dev/gpiomem",
+        ),
+        Err(e) if e.kind() == ErrorKind::PermissionDenied && !groups.has("gpio") => Check::failed(
+            "gpiomem",
+            Status::Error,
+            format!("permission denied opening {}", path),
+            "sudo usermod -aG gpio $USER, then log out and back in",
+        ),
+        Err(e) => Check::failed(
+            "gpiomem",
+            Status::Error,
+            format!("cannot open {}: {}", path, e),
+            "check the owner and mode of /dev/gpiomem: ls -l /dev/gpiomem",
+        ),
+    }
+}
+
+fn gpio_backend() -> Check {
+    match Gpio::new() {
+        Ok(_) => Check::ok("GPIO", "the GPIO backend is available".to_string()),
+        Err(e) => Check::failed(
+            "GPIO",
+            Status::Error,
+            format!("the GPIO backend is unavailable: {}", e),
+            "fix the board and gpiomem checks above first",
+        ),
+    }
+}
+
+/// The user and the names of their groups, from `/proc/self/status` and `/etc/group`.
+struct Groups {
+    root: bool,
+    names: Vec<String>,
+}
+
+impl Groups {
+    fn has(&self, group: &str) -> bool {
+        self.root || self.names.iter().any(|name| name == group)
+    }
+}
+
+fn user_groups() -> Groups {
+    let status = fs::read_to_string("/proc/self/status").unwrap_or_default();
+    let field = |name: &str| -> Vec<u32> {
+        status
+            .lines()
+            .find(|line| line.starts_with(name))
+            .map(|line| {
+                line[name.len()..]
+                    .split_whitespace()
+                    .filter_map(|id| id.parse().ok())
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let root = field("Uid:").get(1) == Some(&0);
+    let mut ids = field("Groups:");
+    ids.extend(field("Gid:").get(1));
+    let names = fs::read_to_string("/etc/group")
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let id: u32 = fields.nth(1)?.parse().ok()?;
+            if ids.contains(&id) {
+                Some(name.to_string())
+            } else {
+                None
+            }
+        })
+        .collect();
+    Groups { root, names }
+}
+
+fn membership(group: &str, groups: &Groups) -> Check {
+    let name = format!("group {}", group);
+    if groups.root {
+        Check::ok(&name, "running as root".to_string())
+    } else if groups.has(group) {
+        Check::ok(&name, "the user is a member".to_string())
+    } else {
+        Check::failed(
+            &name,
+            Status::Warning,
+            "the user is not a member".to_string(),
+            &format!("sudo usermod -aG {} $USER, then log out and back in", group),
+        )
+    }
+}
+
+fn interface(name: &str, paths: &[&str], fix: &str) -> Check {
+    match paths.iter().find(|path| Path::new(path).exists()) {
+        Some(path) => Check::ok(name, format!("enabled ({})", path)),
+        None => Check::failed(name, Status::Warning, "not enabled".to_string(), fix),
+    }
+}
+
+/// The path and contents of `config.txt`, if found.
+fn boot_config() -> Option<(&'static str, String)> {
+    CONFIG_PATHS
+        .iter()
+        .find_map(|path| fs::read_to_string(path).ok().map(|text| (*path, text)))
+}
+
+fn overlays(config: Option<(&'static str, String)>) -> Check {
+    let (path, text) = match config {
+        Some(config) => config,
+        None => {
+            return Check::failed(
+                "overlays",
+                Status::Warning,
+                "config.txt not found".to_string(),
+                "mount the boot partition at /boot/firmware or /boot",
+            )
+        }
+    };
+    let found: Vec<&str> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with("dtoverlay=") || line.starts_with("dtparam="))
+        .map(|line| line.split_once('=').map_or("", |(_, value)| value))
+        .filter(|value| !value.is_empty())
+        .collect();
+    if found.is_empty() {
+        Check::ok("overlays", format!("none in {}", path))
+    } else {
+        Check::ok("overlays", format!("{} in {}", found.join(", "), path))
+    }
+}
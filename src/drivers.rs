@@ -0,0 +1,100 @@
+//! Registration of device drivers provided by other crates
+//!
+//! A crate providing a device implements `Driver` and registers it with `register` when the
+//! program starts, before the configuration is loaded. Registered drivers take part in:
+//!
+//! * configuration files: devices of the driver's kind are validated by
+//!   `DeviceTree::validate` and created by `DeviceTree::build_drivers`
+//! * bus scans: `i2c::scan` lists the driver among the candidates for its addresses
+//! * the command line tools in `cli`, whose `drivers` command lists them
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::drivers::{self, Driver};
+//! use rust_gpiozero::*;
+//! use std::error::Error;
+//!
+//! struct Relay4Driver;
+//!
+//! impl Driver for Relay4Driver {
+//!     fn kind(&self) -> &str {
+//!         "relay4"
+//!     }
+//!
+//!     fn description(&self) -> &str {
+//!         "four channel relay board"
+//!     }
+//!
+//!     fn pins(&self, _device: &DeviceConfig) -> Option<(PinFunction, Option<usize>)> {
+//!         Some((PinFunction::Output, Some(4)))
+//!     }
+//!
+//!     fn build(&self, device: &DeviceConfig, tree: &mut DeviceTree) -> Result<(), Box<dyn Error>> {
+//!         tree.add_inspectable(&device.name, LEDBoard::new(&device.pins))?;
+//!         Ok(())
+//!     }
+//! }
+//!
+//! drivers::register(Relay4Driver);
+//! let mut tree = DeviceTree::new(Config::load("/etc/greenhouse.conf").unwrap());
+//! tree.build_drivers().unwrap();
+//! ```
+use crate::config::DeviceConfig;
+use crate::tree::{DeviceTree, PinFunction};
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+/// A driver for a kind of device, provided by this or another crate.
+pub trait Driver: Send + Sync {
+    /// The `kind` used for the device in configuration files.
+    fn kind(&self) -> &str;
+
+    /// A short description, shown by the command line tools and bus scans.
+    fn description(&self) -> &str {
+        self.kind()
+    }
+
+    /// The function and number of GPIO pins a configured device needs, checked by
+    /// `DeviceTree::validate`. A None count means one or more pins. Devices that only use
+    /// a bus return None.
+    fn pins(&self, _device: &DeviceConfig) -> Option<(PinFunction, Option<usize>)> {
+        None
+    }
+
+    /// The I2C addresses the device can be configured for.
+    fn i2c_addresses(&self) -> &[u16] {
+        &[]
+    }
+
+    /// Create a configured device and add it to the tree under the device's name.
+    fn build(&self, device: &DeviceConfig, tree: &mut DeviceTree) -> Result<(), Box<dyn Error>>;
+}
+
+static DRIVERS: Mutex<Vec<Arc<dyn Driver>>> = Mutex::new(Vec::new());
+
+/// Register a driver. Returns ``False`` if a driver for the same kind is already registered,
+/// in which case the first one is kept.
+pub fn register<D: Driver + 'static>(driver: D) -> bool {
+    let mut drivers = DRIVERS.lock().unwrap();
+    if drivers.iter().any(|d| d.kind() == driver.kind()) {
+        return false;
+    }
+    drivers.push(Arc::new(driver));
+    true
+}
+
+/// The registered drivers, in the order they were registered.
+pub fn drivers() -> Vec<Arc<dyn Driver>> {
+    DRIVERS.lock().unwrap().clone()
+}
+
+/// The driver registered for a kind, if any.
+pub fn driver(kind: &str) -> Option<Arc<dyn Driver>> {
+    DRIVERS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|d| d.kind() == kind)
+        .cloned()
+}
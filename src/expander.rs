@@ -0,0 +1,1403 @@
+//! GPIO expanders and other providers of pins
+//!
+//! Devices normally drive the pins of the Pi itself. A `PinFactory` hands out pins from
+//! somewhere else, such as the 16 pins of an MCP23017 on the I2C bus or the 8 pins of the
+//! PCF8574 found on relay boards and LCD backpacks, and the `with_factory` constructors of `OutputDevice`, `DigitalOutputDevice`, `LED`, `InputDevice`,
+//! `DigitalInputDevice`, `Button` and `LEDBoard` build the usual devices on them. The devices
+//! behave as they do on the pins of the Pi, except that expander pins have no PWM and `pin`
+//! returns the number of the pin on the expander.
+//!
+//! The 16 channels of a `PCA9685` PWM driver are outputs with PWM generated by the chip, for
+//! `PWMOutputDevice`, `PWMLED`, `Servo` and `Motor` as well. The outputs of a chain of
+//! 74HC595 `ShiftRegister`s are latched together, and `ShiftRegister::batch` turns the
+//! changes made to e.g. an `LEDBarGraph` into a single update.
+//!
+//! Edges of expander inputs are found by reading the port of the chip. Connect its INT pin to
+//! a GPIO pin and pass that to `set_interrupt_pin`, and the port is only read when a watched
+//! input changes; otherwise it is polled every 10ms while any input is watched.
+//!
+//! The pins of the PCF8574 and PCF8574A are quasi-bidirectional: there is no direction
+//! register, a pin written low is driven low and a pin written high is only pulled up by a
+//! weak current source, so whatever is connected can pull it low. Input pins are therefore
+//! kept high, are always pulled up, and cannot be pulled down. Outputs driven high source
+//! too little current for an LED; connect loads between the pin and 3V3 and use the
+//! `with_factory_active_low` constructors, as relay boards do.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::*;
+//!
+//! let mut expander = MCP23017::new();
+//! expander.set_interrupt_pin(17);
+//!
+//! // GPA0 and GPB0
+//! let mut led = LED::with_factory(&expander, 0);
+//! let mut button = Button::with_factory(&expander, 8);
+//! button.when_pressed(|| println!("pressed"));
+//! led.blink(0.5, 0.5).unwrap();
+//!
+//! // a relay board, whose relays switch on when their pin is low
+//! let relays = PCF8574::with_address(1, 0x27);
+//! let mut pump = DigitalOutputDevice::with_factory_active_low(&relays, 0);
+//! pump.on();
+//! ```
+use crate::bus::{BusLink, BusStats, RetryPolicy, SharedI2c};
+use crate::input_devices::Bias;
+use rppal::gpio::{Gpio, InputPin, Level, OutputPin, Trigger};
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often the port is read for edges without an interrupt pin.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// the registers of the MCP23008; the MCP23017 (with IOCON.BANK clear) has a pair of them, for
+// ports A and B, at twice the address
+const IODIR: u8 = 0x00;
+const IPOL: u8 = 0x01;
+const GPINTEN: u8 = 0x02;
+const INTCON: u8 = 0x04;
+const IOCON: u8 = 0x05;
+const GPPU: u8 = 0x06;
+const INTF: u8 = 0x07;
+const INTCAP: u8 = 0x08;
+const GPIO: u8 = 0x09;
+const OLAT: u8 = 0x0a;
+
+/// IOCON.MIRROR: a change on either port of an MCP23017 pulls both INT pins low.
+const IOCON_MIRROR: u16 = 0x4040;
+
+/// The function called with the new level on every edge of an input pin.
+pub type PinListener = Box<dyn FnMut(Level) + Send>;
+
+/// A pin that cannot be handed out or used as asked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PinError {
+    /// The provider has no pin with the number given
+    NoSuchPin(u8),
+    /// The pin is already used by another device
+    InUse(u8),
+    /// The pin cannot be used that way, with the reason
+    Unsupported(String),
+    /// The provider could not be reached, with the error
+    Bus(String),
+}
+
+impl fmt::Display for PinError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PinError::NoSuchPin(pin) => write!(f, "there is no pin {}", pin),
+            PinError::InUse(pin) => write!(f, "pin {} is already in use", pin),
+            PinError::Unsupported(reason) => write!(f, "{}", reason),
+            PinError::Bus(e) => write!(f, "bus error: {}", e),
+        }
+    }
+}
+
+impl Error for PinError {}
+
+impl From<rppal::i2c::Error> for PinError {
+    fn from(e: rppal::i2c::Error) -> Self {
+        PinError::Bus(e.to_string())
+    }
+}
+
+impl From<PinError> for rppal::gpio::Error {
+    fn from(e: PinError) -> Self {
+        rppal::gpio::Error::Io(io::Error::other(e))
+    }
+}
+
+/// A single pin handed out by a `PinFactory`, configured as an input or an output.
+pub trait PinDriver: Send {
+    /// The number of the pin on its provider.
+    fn pin(&self) -> u8;
+
+    /// Read the level of the pin.
+    fn read(&self) -> Level;
+
+    /// Drive an output pin to the level given.
+    fn write(&mut self, level: Level);
+
+    /// Drive an output pin to the level given, returning an error if the provider could not
+    /// be reached. Providers whose writes cannot fail keep this default, which calls `write`.
+    fn try_write(&mut self, level: Level) -> Result<(), PinError> {
+        self.write(level);
+        Ok(())
+    }
+
+    /// Call `listener` from a background thread on every edge of an input pin, or stop
+    /// calling it when `None`.
+    fn set_listener(&mut self, listener: Option<PinListener>) -> Result<(), PinError>;
+
+    /// Block until the next edge of an input pin and return its new level, or `None` if the
+    /// timeout is reached first.
+    /// * `timeout` - How long to wait. If this is None, then wait indefinitely.
+    fn wait_for_edge(&mut self, timeout: Option<Duration>) -> Result<Option<Level>, PinError>;
+
+    /// Drive an output pin with a PWM signal, for providers that generate one.
+    /// * `frequency` - The frequency in Hz. Providers with one frequency for all pins ignore it
+    /// * `duty_cycle` - The fraction of each period the pin is high, from 0.0 to 1.0
+    fn set_pwm(&mut self, frequency: f64, duty_cycle: f64) -> Result<(), PinError> {
+        let _ = (frequency, duty_cycle);
+        Err(PinError::Unsupported(format!(
+            "pin {} has no PWM",
+            self.pin()
+        )))
+    }
+
+    /// Stop the PWM signal of an output pin; the next `write` sets its level.
+    fn clear_pwm(&mut self) -> Result<(), PinError> {
+        Ok(())
+    }
+
+    /// Returns ``True`` if the pin is returned to an input when dropped.
+    fn reset_on_drop(&self) -> bool;
+
+    /// Choose whether the pin is returned to an input when dropped.
+    fn set_reset_on_drop(&mut self, reset_on_drop: bool);
+}
+
+/// A provider of pins, such as a GPIO expander.
+pub trait PinFactory {
+    /// Claim a pin as an output.
+    /// * `pin` - The number of the pin on the provider
+    /// * `level` - The level the pin is driven to from the moment it becomes an output
+    fn output_pin(&self, pin: u8, level: Level) -> Result<Box<dyn PinDriver>, PinError>;
+
+    /// Claim a pin as an input.
+    /// * `pin` - The number of the pin on the provider
+    /// * `bias` - The internal resistor to enable
+    fn input_pin(&self, pin: u8, bias: Bias) -> Result<Box<dyn PinDriver>, PinError>;
+}
+
+/// The chips of the MCP230xx and PCF857x families.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExpanderChip {
+    MCP23008,
+    MCP23017,
+    PCF8574,
+    PCF8574A,
+}
+
+impl ExpanderChip {
+    fn name(self) -> &'static str {
+        match self {
+            ExpanderChip::MCP23008 => "MCP23008",
+            ExpanderChip::MCP23017 => "MCP23017",
+            ExpanderChip::PCF8574 => "PCF8574",
+            ExpanderChip::PCF8574A => "PCF8574A",
+        }
+    }
+
+    fn pins(self) -> u8 {
+        match self {
+            ExpanderChip::MCP23017 => 16,
+            _ => 8,
+        }
+    }
+
+    /// Returns ``True`` for the PCF857x, which have no registers: a write sets the level
+    /// of every pin and a read returns them.
+    fn is_quasi_bidirectional(self) -> bool {
+        matches!(self, ExpanderChip::PCF8574 | ExpanderChip::PCF8574A)
+    }
+}
+
+type Listener = Arc<Mutex<PinListener>>;
+
+/// The I2C device of a chip and the registers it was last given, shared by its pins.
+struct ExpanderState {
+    i2c: SharedI2c,
+    link: BusLink,
+    chip: ExpanderChip,
+    claimed: u16,
+    inputs: u16,
+    pull_ups: u16,
+    latch: u16,
+    levels: u16,
+    listeners: Vec<Option<Listener>>,
+    waiters: Vec<usize>,
+    edges: Vec<u64>,
+    interrupt: bool,
+    polling: bool,
+}
+
+impl ExpanderState {
+    /// Write a register, both ports of it on an MCP23017.
+    fn write_register(&mut self, register: u8, value: u16) -> Result<(), rppal::i2c::Error> {
+        let ExpanderState {
+            i2c, link, chip, ..
+        } = self;
+        let [low, high] = value.to_le_bytes();
+        match chip {
+            ExpanderChip::MCP23017 => link.run(|| i2c.lock()?.write(&[register * 2, low, high]))?,
+            _ => link.run(|| i2c.lock()?.write(&[register, low]))?,
+        };
+        Ok(())
+    }
+
+    /// Read a register, both ports of it on an MCP23017.
+    fn read_register(&mut self, register: u8) -> Result<u16, rppal::i2c::Error> {
+        let ExpanderState {
+            i2c, link, chip, ..
+        } = self;
+        let mut read = [0u8; 2];
+        match chip {
+            ExpanderChip::MCP23017 => {
+                link.run(|| i2c.lock()?.write_read(&[register * 2], &mut read))?
+            }
+            _ => link.run(|| i2c.lock()?.write_read(&[register], &mut read[..1]))?,
+        };
+        Ok(u16::from_le_bytes(read))
+    }
+
+    /// Write the port of a PCF857x with the inputs high, as a pin written low can only read
+    /// low.
+    fn write_port(&mut self) -> Result<(), rppal::i2c::Error> {
+        let port = (self.latch | self.inputs) as u8;
+        let ExpanderState { i2c, link, .. } = self;
+        link.run(|| i2c.lock()?.write(&[port]))?;
+        Ok(())
+    }
+
+    /// Read the level of every pin.
+    fn read_port(&mut self) -> Result<u16, rppal::i2c::Error> {
+        if !self.chip.is_quasi_bidirectional() {
+            return self.read_register(GPIO);
+        }
+        let ExpanderState { i2c, link, .. } = self;
+        let mut read = [0u8; 1];
+        link.run(|| i2c.lock()?.read(&mut read))?;
+        Ok(u16::from(read[0]))
+    }
+
+    /// Write the levels of the outputs.
+    fn write_latch(&mut self) -> Result<(), rppal::i2c::Error> {
+        if self.chip.is_quasi_bidirectional() {
+            self.write_port()
+        } else {
+            self.write_register(OLAT, self.latch)
+        }
+    }
+
+    /// Write the direction, pull-up and output level of every pin, the levels first so an
+    /// output starts at its level.
+    fn write_config(&mut self) -> Result<(), rppal::i2c::Error> {
+        if self.chip.is_quasi_bidirectional() {
+            return self.write_port();
+        }
+        self.write_register(GPPU, self.pull_ups)?;
+        self.write_register(OLAT, self.latch)?;
+        self.write_register(IODIR, self.inputs)
+    }
+
+    /// Put the chip in a known state: every pin an input without pull-up or interrupt. The
+    /// pins of a PCF857x are pulled up whatever is written.
+    fn reset(&mut self) -> Result<(), rppal::i2c::Error> {
+        if self.chip.is_quasi_bidirectional() {
+            self.latch = 0xff;
+            self.write_port()?;
+            self.levels = self.read_port()?;
+            return Ok(());
+        }
+        let iocon = match self.chip {
+            ExpanderChip::MCP23017 => IOCON_MIRROR,
+            _ => 0,
+        };
+        self.write_register(IOCON, iocon)?;
+        self.write_register(GPINTEN, 0)?;
+        self.write_register(INTCON, 0)?;
+        self.write_register(IPOL, 0)?;
+        self.write_register(GPPU, 0)?;
+        self.write_register(IODIR, 0xffff)?;
+        self.latch = self.read_register(OLAT)?;
+        self.levels = self.read_register(GPIO)?;
+        Ok(())
+    }
+
+    /// The pins with a listener or a thread waiting for an edge.
+    fn watched(&self) -> u16 {
+        (0..self.chip.pins()).fold(0, |watched, pin| {
+            let index = usize::from(pin);
+            if self.listeners[index].is_some() || self.waiters[index] > 0 {
+                watched | 1 << pin
+            } else {
+                watched
+            }
+        })
+    }
+
+    /// Enable the interrupt on change of the watched pins. A PCF857x interrupts on a change
+    /// of any input.
+    fn update_watched(&mut self) -> Result<(), rppal::i2c::Error> {
+        if self.chip.is_quasi_bidirectional() {
+            return Ok(());
+        }
+        let watched = self.watched();
+        self.write_register(GPINTEN, watched)
+    }
+
+    /// Read the level of one pin into `levels`, so it is not taken for an edge.
+    fn refresh(&mut self, pin: u8) -> Result<(), rppal::i2c::Error> {
+        let bit = 1 << pin;
+        let port = self.read_port()?;
+        self.levels = (self.levels & !bit) | (port & bit);
+        Ok(())
+    }
+
+    /// Claim a pin as an output driven to `output`, or as an input if it is `None`.
+    fn claim(&mut self, pin: u8, output: Option<Level>, pull_up: bool) -> Result<(), PinError> {
+        if pin >= self.chip.pins() {
+            return Err(PinError::NoSuchPin(pin));
+        }
+        let bit = 1 << pin;
+        if self.claimed & bit != 0 {
+            return Err(PinError::InUse(pin));
+        }
+        match output {
+            None => self.inputs |= bit,
+            Some(level) => {
+                self.inputs &= !bit;
+                match level {
+                    Level::High => self.latch |= bit,
+                    Level::Low => self.latch &= !bit,
+                }
+            }
+        }
+        if pull_up {
+            self.pull_ups |= bit;
+        } else {
+            self.pull_ups &= !bit;
+        }
+        self.write_config()?;
+        self.refresh(pin)?;
+        self.claimed |= bit;
+        Ok(())
+    }
+
+    fn release(&mut self, pin: u8, reset: bool) {
+        let bit = 1 << pin;
+        self.claimed &= !bit;
+        self.listeners[usize::from(pin)] = None;
+        let mut result = self.update_watched();
+        if reset {
+            self.inputs |= bit;
+            self.pull_ups &= !bit;
+            result = result.and(self.write_config());
+        }
+        if result.is_err() {
+            println!("Could not release {} pin {}", self.chip.name(), pin);
+        }
+    }
+
+    /// Read the port and return the watched inputs that changed, with their new levels. With
+    /// `captured`, the levels an MCP230xx latched when the interrupt fired are read first,
+    /// so a pulse that is over by the time the port is read still counts as two edges.
+    fn changes(&mut self, captured: bool) -> Result<Vec<(u8, Level)>, rppal::i2c::Error> {
+        let watched = self.watched() & self.inputs;
+        let mut events = Vec::new();
+        if captured && !self.chip.is_quasi_bidirectional() {
+            let flags = self.read_register(INTF)?;
+            let capture = self.read_register(INTCAP)?;
+            self.record(flags & watched, capture, &mut events);
+        }
+        let port = self.read_port()?;
+        self.record(watched, port, &mut events);
+        Ok(events)
+    }
+
+    fn record(&mut self, mask: u16, port: u16, events: &mut Vec<(u8, Level)>) {
+        let changed = (port ^ self.levels) & mask;
+        for pin in 0..self.chip.pins() {
+            if changed & 1 << pin != 0 {
+                self.edges[usize::from(pin)] += 1;
+                events.push((pin, level_of(port, pin)));
+            }
+        }
+        self.levels = (self.levels & !mask) | (port & mask);
+    }
+}
+
+fn level_of(port: u16, pin: u8) -> Level {
+    if port & 1 << pin != 0 {
+        Level::High
+    } else {
+        Level::Low
+    }
+}
+
+/// A chip shared by its pins, with the condition their waiting threads are woken on.
+struct Expander {
+    state: Mutex<ExpanderState>,
+    edge: Condvar,
+}
+
+impl Expander {
+    /// Read the changes of the watched inputs, wake the threads waiting for an edge and call
+    /// the listeners, outside the lock so they can use the expander themselves.
+    fn dispatch(&self, captured: bool) {
+        let mut state = self.state.lock().unwrap();
+        let events = match state.changes(captured) {
+            Ok(events) if !events.is_empty() => events,
+            _ => return,
+        };
+        let listeners: Vec<(Listener, Level)> = events
+            .into_iter()
+            .filter_map(|(pin, level)| {
+                state.listeners[usize::from(pin)]
+                    .clone()
+                    .map(|listener| (listener, level))
+            })
+            .collect();
+        drop(state);
+        self.edge.notify_all();
+        for (listener, level) in listeners {
+            if let Ok(mut callback) = listener.lock() {
+                callback(level)
+            }
+        }
+    }
+
+    /// Poll the port from a background thread while inputs are watched and there is no
+    /// interrupt pin. The thread stops once neither is true or the expander is dropped.
+    fn watch(expander: &Arc<Expander>) {
+        let mut state = expander.state.lock().unwrap();
+        if state.polling || state.interrupt || state.watched() == 0 {
+            return;
+        }
+        state.polling = true;
+        let expander = Arc::downgrade(expander);
+        thread::spawn(move || loop {
+            match expander.upgrade() {
+                None => return,
+                Some(expander) => {
+                    {
+                        let mut state = expander.state.lock().unwrap();
+                        if state.interrupt || state.watched() == 0 {
+                            state.polling = false;
+                            return;
+                        }
+                    }
+                    expander.dispatch(false);
+                }
+            }
+            thread::sleep(POLL_INTERVAL);
+        });
+    }
+
+    fn open(
+        self: &Arc<Self>,
+        pin: u8,
+        output: Option<Level>,
+        pull_up: bool,
+    ) -> Result<Box<dyn PinDriver>, PinError> {
+        self.state.lock().unwrap().claim(pin, output, pull_up)?;
+        Ok(Box::new(ExpanderPin {
+            expander: Arc::clone(self),
+            pin,
+            reset_on_drop: true,
+        }))
+    }
+}
+
+/// A pin of an expander, released when dropped.
+struct ExpanderPin {
+    expander: Arc<Expander>,
+    pin: u8,
+    reset_on_drop: bool,
+}
+
+impl PinDriver for ExpanderPin {
+    fn pin(&self) -> u8 {
+        self.pin
+    }
+
+    // the last level seen if the chip cannot be read
+    fn read(&self) -> Level {
+        let mut state = self.expander.state.lock().unwrap();
+        let port = match state.read_port() {
+            Ok(port) => port,
+            Err(_) => state.levels,
+        };
+        level_of(port, self.pin)
+    }
+
+    fn write(&mut self, level: Level) {
+        if self.try_write(level).is_err() {
+            let state = self.expander.state.lock().unwrap();
+            println!("Could not write to {} pin {}", state.chip.name(), self.pin);
+        }
+    }
+
+    fn try_write(&mut self, level: Level) -> Result<(), PinError> {
+        let mut state = self.expander.state.lock().unwrap();
+        let bit = 1 << self.pin;
+        let latch = match level {
+            Level::High => state.latch | bit,
+            Level::Low => state.latch & !bit,
+        };
+        state.latch = latch;
+        state.write_latch()?;
+        Ok(())
+    }
+
+    fn set_listener(&mut self, listener: Option<PinListener>) -> Result<(), PinError> {
+        {
+            let mut state = self.expander.state.lock().unwrap();
+            state.listeners[usize::from(self.pin)] =
+                listener.map(|listener| Arc::new(Mutex::new(listener)));
+            state.refresh(self.pin)?;
+            state.update_watched()?;
+        }
+        Expander::watch(&self.expander);
+        Ok(())
+    }
+
+    fn wait_for_edge(&mut self, timeout: Option<Duration>) -> Result<Option<Level>, PinError> {
+        let deadline = timeout.and_then(|t| Instant::now().checked_add(t));
+        let index = usize::from(self.pin);
+        let start = {
+            let mut state = self.expander.state.lock().unwrap();
+            state.waiters[index] += 1;
+            if let Err(e) = state.refresh(self.pin).and_then(|_| state.update_watched()) {
+                state.waiters[index] -= 1;
+                return Err(e.into());
+            }
+            state.edges[index]
+        };
+        Expander::watch(&self.expander);
+        let mut state = self.expander.state.lock().unwrap();
+        let level = loop {
+            if state.edges[index] != start {
+                break Some(level_of(state.levels, self.pin));
+            }
+            state = match deadline {
+                None => self.expander.edge.wait(state).unwrap(),
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    None => break None,
+                    Some(remaining) => self.expander.edge.wait_timeout(state, remaining).unwrap().0,
+                },
+            };
+        };
+        state.waiters[index] -= 1;
+        state.update_watched()?;
+        Ok(level)
+    }
+
+    fn reset_on_drop(&self) -> bool {
+        self.reset_on_drop
+    }
+
+    fn set_reset_on_drop(&mut self, reset_on_drop: bool) {
+        self.reset_on_drop = reset_on_drop
+    }
+}
+
+impl Drop for ExpanderPin {
+    fn drop(&mut self) {
+        self.expander
+            .state
+            .lock()
+            .unwrap()
+            .release(self.pin, self.reset_on_drop)
+    }
+}
+
+macro_rules! impl_expander {
+    ($($article:literal $chip:ident => $address:literal, $addresses:literal);*) => {
+        $(
+            #[doc = concat!("Represents ", $article, " ", stringify!($chip), " GPIO expander on the I2C bus, handing out its pins as a `PinFactory`.")]
+            pub struct $chip {
+                expander: Arc<Expander>,
+                interrupt: Option<InputPin>,
+            }
+
+            impl $chip {
+                #[doc = concat!("Returns ", $article, " ", stringify!($chip), " on I2C bus 1 at address ", stringify!($address), ", with its address pins connected to ground")]
+                pub fn new() -> $chip {
+                    $chip::with_address(1, $address)
+                }
+
+                #[doc = concat!("Returns ", $article, " ", stringify!($chip), " on the bus and at the address given")]
+                /// * `bus` - The I2C bus number, 1 on most Raspberry Pi models
+                #[doc = concat!("* `address` - ", $addresses, ", set by the A0 to A2 pins")]
+                pub fn with_address(bus: u8, address: u16) -> $chip {
+                    let i2c = match SharedI2c::open(bus, address) {
+                        Err(e) => panic!("{:?}", e),
+                        Ok(i2c) => i2c,
+                    };
+                    let chip = ExpanderChip::$chip;
+                    let pins = usize::from(chip.pins());
+                    let mut state = ExpanderState {
+                        i2c,
+                        link: BusLink::default(),
+                        chip,
+                        claimed: 0,
+                        inputs: 0xffff,
+                        pull_ups: 0,
+                        latch: 0,
+                        levels: 0,
+                        listeners: vec![None; pins],
+                        waiters: vec![0; pins],
+                        edges: vec![0; pins],
+                        interrupt: false,
+                        polling: false,
+                    };
+                    if let Err(e) = state.reset() {
+                        panic!("{:?}", e)
+                    }
+                    $chip {
+                        expander: Arc::new(Expander {
+                            state: Mutex::new(state),
+                            edge: Condvar::new(),
+                        }),
+                        interrupt: None,
+                    }
+                }
+
+                /// The number of pins of the chip.
+                pub fn pins(&self) -> u8 {
+                    ExpanderChip::$chip.pins()
+                }
+
+                /// Read the port only when a watched input changes, instead of polling it.
+                /// The INT pin of the chip is active low, so the GPIO pin is pulled up.
+                /// * `pin` - The GPIO pin the INT pin of the chip is connected to
+                pub fn set_interrupt_pin(&mut self, pin: u8) {
+                    let mut interrupt = match Gpio::new() {
+                        Err(e) => panic!("{:?}", e),
+                        Ok(gpio) => match gpio.get(pin) {
+                            Err(e) => panic!("{:?}", e),
+                            Ok(pin) => pin.into_input_pullup(),
+                        },
+                    };
+                    let expander = Arc::downgrade(&self.expander);
+                    let armed = interrupt.set_async_interrupt(Trigger::FallingEdge, move |_| {
+                        if let Some(expander) = expander.upgrade() {
+                            expander.dispatch(true)
+                        }
+                    });
+                    if let Err(e) = armed {
+                        panic!("{:?}", e)
+                    }
+                    self.interrupt = Some(interrupt);
+                    self.expander.state.lock().unwrap().interrupt = true;
+                    // reading the port clears an interrupt raised before the pin was armed
+                    self.expander.dispatch(false);
+                }
+
+                /// The error counters of the I2C transfers.
+                pub fn bus_stats(&self) -> BusStats {
+                    self.expander.state.lock().unwrap().link.stats()
+                }
+
+                /// Change how failed I2C transfers are retried.
+                pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+                    self.expander.state.lock().unwrap().link.set_policy(policy)
+                }
+            }
+
+            impl PinFactory for $chip {
+                fn output_pin(&self, pin: u8, level: Level) -> Result<Box<dyn PinDriver>, PinError> {
+                    self.expander.open(pin, Some(level), false)
+                }
+
+                fn input_pin(&self, pin: u8, bias: Bias) -> Result<Box<dyn PinDriver>, PinError> {
+                    match bias {
+                        Bias::PullDown => Err(PinError::Unsupported(format!(
+                            "the {} has no pull-down resistors",
+                            stringify!($chip)
+                        ))),
+                        Bias::PullUp => self.expander.open(pin, None, true),
+                        Bias::Floating => self.expander.open(pin, None, false),
+                    }
+                }
+            }
+
+            impl Default for $chip {
+                fn default() -> Self {
+                    $chip::new()
+                }
+            }
+
+            // pins outlive the chip, so they go back to polling without its interrupt pin
+            impl Drop for $chip {
+                fn drop(&mut self) {
+                    if self.interrupt.take().is_some() {
+                        self.expander.state.lock().unwrap().interrupt = false;
+                        Expander::watch(&self.expander);
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_expander!(
+    "an" MCP23008 => 0x20, "0x20 to 0x27";
+    "an" MCP23017 => 0x20, "0x20 to 0x27";
+    "a" PCF8574 => 0x20, "0x20 to 0x27";
+    "a" PCF8574A => 0x38, "0x38 to 0x3f"
+);
+
+const MODE1: u8 = 0x00;
+const MODE2: u8 = 0x01;
+const LED0_ON_L: u8 = 0x06;
+const ALL_LED_ON_L: u8 = 0xfa;
+const PRE_SCALE: u8 = 0xfe;
+const MODE1_RESTART: u8 = 0x80;
+const MODE1_AI: u8 = 0x20;
+const MODE1_SLEEP: u8 = 0x10;
+const MODE1_ALLCALL: u8 = 0x01;
+const MODE2_OUTDRV: u8 = 0x04;
+// bit 12 of an ON or OFF count turns a channel fully on or off
+const FULL: u16 = 0x1000;
+const PCA9685_CHANNELS: u8 = 16;
+const PCA9685_ALL_CALL: u16 = 0x70;
+const PCA9685_OSCILLATOR: f64 = 25_000_000.0;
+
+/// The I2C device of a PCA9685 and the duty cycle of each channel, shared by its channels.
+struct PwmState {
+    i2c: SharedI2c,
+    link: BusLink,
+    claimed: u16,
+    duty: [f64; 16],
+    oscillator: f64,
+    prescale: u8,
+}
+
+impl PwmState {
+    /// The prescale value giving the frequency closest to `hz`, or `None` if out of range.
+    fn prescale_for(&self, hz: f64) -> Option<u8> {
+        let prescale = (self.oscillator / (4096.0 * hz)).round() - 1.0;
+        if (3.0..=255.0).contains(&prescale) {
+            Some(prescale as u8)
+        } else {
+            None
+        }
+    }
+
+    fn frequency(&self) -> f64 {
+        self.oscillator / (4096.0 * (f64::from(self.prescale) + 1.0))
+    }
+
+    /// The prescaler can only be written while the oscillator is asleep.
+    fn write_prescale(&mut self, prescale: u8) -> Result<(), rppal::i2c::Error> {
+        let PwmState { i2c, link, .. } = self;
+        let awake = MODE1_AI | MODE1_ALLCALL;
+        let mut bus = i2c.lock()?;
+        link.run(|| bus.write(&[MODE1, awake | MODE1_SLEEP]))?;
+        link.run(|| bus.write(&[PRE_SCALE, prescale]))?;
+        link.run(|| bus.write(&[MODE1, awake]))?;
+        thread::sleep(Duration::from_micros(500));
+        link.run(|| bus.write(&[MODE1, awake | MODE1_RESTART]))?;
+        self.prescale = prescale;
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<(), rppal::i2c::Error> {
+        let PwmState { i2c, link, .. } = self;
+        link.run(|| i2c.lock()?.write(&[MODE2, MODE2_OUTDRV]))?;
+        link.run(|| {
+            i2c.lock()?
+                .write(&[ALL_LED_ON_L, 0, 0, 0, (FULL >> 8) as u8])
+        })?;
+        let prescale = self.prescale;
+        self.write_prescale(prescale)
+    }
+
+    /// Pulses start at a different count on each channel, spreading the current drawn
+    /// by many LEDs or servos over the period.
+    fn write_channel(&mut self, channel: u8, duty_cycle: f64) -> Result<(), rppal::i2c::Error> {
+        let offset = u16::from(channel) * (4096 / u16::from(PCA9685_CHANNELS));
+        let (on, off) = if duty_cycle <= 0.0 {
+            (0, FULL)
+        } else if duty_cycle >= 1.0 {
+            (FULL, 0)
+        } else {
+            let width = ((duty_cycle * 4096.0).round() as u16).clamp(1, 4095);
+            (offset, (offset + width) % 4096)
+        };
+        let [on_low, on_high] = on.to_le_bytes();
+        let [off_low, off_high] = off.to_le_bytes();
+        let register = LED0_ON_L + 4 * channel;
+        let PwmState { i2c, link, .. } = self;
+        link.run(|| {
+            i2c.lock()?
+                .write(&[register, on_low, on_high, off_low, off_high])
+        })?;
+        self.duty[usize::from(channel)] = duty_cycle.clamp(0.0, 1.0);
+        Ok(())
+    }
+}
+
+/// Represents a PCA9685 16-channel, 12-bit PWM driver on the I2C bus, handing out its
+/// channels as a `PinFactory`, as found on most servo and motor HATs.
+///
+/// All channels share one frequency, 50Hz by default for servos, set with `set_frequency`.
+/// The frequency a device asks for is ignored and its duty cycle applied at the frequency of
+/// the chip, so a `PWMLED` is as bright as on a pin of the Pi; a `Servo` keeps its pulse
+/// widths only while the frequency matches its frame width. The internal oscillator is
+/// only accurate to a few percent, which moves servos off position; `calibrate` or
+/// `calibrate_with_pin` correct for it.
+///
+/// Channels cannot be inputs.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let pwm = PCA9685::new();
+/// let mut servo = Servo::with_factory(&pwm, 0);
+/// let mut led = PWMLED::with_factory(&pwm, 15);
+/// servo.mid();
+/// led.set_value(0.25);
+/// ```
+pub struct PCA9685 {
+    state: Arc<Mutex<PwmState>>,
+}
+
+impl PCA9685 {
+    /// Returns a PCA9685 on I2C bus 1 at address 0x40, with all channels off
+    pub fn new() -> PCA9685 {
+        PCA9685::with_address(1, 0x40)
+    }
+
+    /// Returns a PCA9685 on the bus and at the address given, with all channels off
+    /// * `bus` - The I2C bus number, 1 on most Raspberry Pi models
+    /// * `address` - 0x40 to 0x7f, set by the A0 to A5 pins
+    pub fn with_address(bus: u8, address: u16) -> PCA9685 {
+        let i2c = match SharedI2c::open(bus, address) {
+            Err(e) => panic!("{:?}", e),
+            Ok(i2c) => i2c,
+        };
+        let mut state = PwmState {
+            i2c,
+            link: BusLink::default(),
+            claimed: 0,
+            duty: [0.0; 16],
+            oscillator: PCA9685_OSCILLATOR,
+            prescale: 0,
+        };
+        state.prescale = state.prescale_for(50.0).unwrap_or(121);
+        if let Err(e) = state.reset() {
+            panic!("{:?}", e)
+        }
+        PCA9685 {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// Turn off every channel of every PCA9685 on the bus at once, through the all-call
+    /// address the chips answer to, e.g. to stop all servos and motors in an emergency.
+    /// * `bus` - The I2C bus number, 1 on most Raspberry Pi models
+    pub fn all_call_off(bus: u8) -> Result<(), rppal::i2c::Error> {
+        let i2c = SharedI2c::open(bus, PCA9685_ALL_CALL)?;
+        i2c.lock()?
+            .write(&[ALL_LED_ON_L, 0, 0, 0, (FULL >> 8) as u8])?;
+        Ok(())
+    }
+
+    /// The number of channels of the chip.
+    pub fn channels(&self) -> u8 {
+        PCA9685_CHANNELS
+    }
+
+    /// Turn off every channel of the chip.
+    pub fn all_off(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        let PwmState { i2c, link, .. } = &mut *state;
+        if link
+            .run(|| {
+                i2c.lock()?
+                    .write(&[ALL_LED_ON_L, 0, 0, 0, (FULL >> 8) as u8])
+            })
+            .is_err()
+        {
+            println!("Could not turn off the PCA9685");
+            return;
+        }
+        state.duty = [0.0; 16];
+    }
+
+    /// The frequency of all channels in Hz, as the prescaler of the chip allows.
+    pub fn frequency(&self) -> f64 {
+        self.state.lock().unwrap().frequency()
+    }
+
+    /// Set the frequency of all channels, keeping their duty cycles.
+    /// * `hz` - Frequency in Hz, between about 24 and 1526Hz
+    pub fn set_frequency(&mut self, hz: f64) {
+        let mut state = self.state.lock().unwrap();
+        match state.prescale_for(hz) {
+            None => println!(
+                "Frequency must be between {:.0} and {:.0}Hz",
+                state.oscillator / (4096.0 * 256.0),
+                state.oscillator / (4096.0 * 4.0)
+            ),
+            Some(prescale) => {
+                if state.write_prescale(prescale).is_err() {
+                    println!("Could not set the frequency of the PCA9685");
+                }
+            }
+        }
+    }
+
+    /// The frequency of the oscillator of the chip in Hz, nominally 25MHz.
+    pub fn oscillator_frequency(&self) -> f64 {
+        self.state.lock().unwrap().oscillator
+    }
+
+    /// Set the frequency of the oscillator, e.g. of an external clock on the EXTCLK pin or as
+    /// measured, and keep the frequency of the channels as close as it can to what it was.
+    /// * `hz` - Frequency in Hz
+    pub fn set_oscillator_frequency(&mut self, hz: f64) {
+        if !hz.is_finite() || hz <= 0.0 {
+            println!("Oscillator frequency must be positive");
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        let frequency = state.frequency();
+        state.oscillator = hz;
+        if let Some(prescale) = state.prescale_for(frequency) {
+            if state.write_prescale(prescale).is_err() {
+                println!("Could not set the frequency of the PCA9685");
+            }
+        }
+    }
+
+    /// Correct the oscillator frequency from the frequency measured on a channel, e.g. with
+    /// an oscilloscope or a frequency counter.
+    /// * `measured` - The frequency of the channels in Hz, as measured
+    pub fn calibrate(&mut self, measured: f64) {
+        if !measured.is_finite() || measured <= 0.0 {
+            println!("Measured frequency must be positive");
+            return;
+        }
+        let prescale = f64::from(self.state.lock().unwrap().prescale);
+        self.set_oscillator_frequency(measured * 4096.0 * (prescale + 1.0));
+    }
+
+    /// Measure the frequency of a free channel wired to a GPIO pin for about a second, and
+    /// correct the oscillator frequency from it. Returns the oscillator frequency found, or
+    /// `None` if the channel is in use or too few edges were seen.
+    /// * `channel` - The channel to measure, which is driven at 50% while measuring
+    /// * `pin` - The GPIO pin the channel is connected to
+    pub fn calibrate_with_pin(&mut self, channel: u8, pin: u8) -> Option<f64> {
+        if channel >= PCA9685_CHANNELS {
+            println!("Channel must be between 0 and {}", PCA9685_CHANNELS - 1);
+            return None;
+        }
+        if self.state.lock().unwrap().claimed & (1 << channel) != 0 {
+            println!("Channel {} is in use", channel);
+            return None;
+        }
+        let mut input = match Gpio::new() {
+            Err(e) => panic!("{:?}", e),
+            Ok(gpio) => match gpio.get(pin) {
+                Err(e) => panic!("{:?}", e),
+                Ok(pin) => pin.into_input(),
+            },
+        };
+        if let Err(e) = input.set_interrupt(Trigger::RisingEdge) {
+            panic!("{:?}", e)
+        }
+        if self
+            .state
+            .lock()
+            .unwrap()
+            .write_channel(channel, 0.5)
+            .is_err()
+        {
+            println!("Could not write to PCA9685 channel {}", channel);
+            return None;
+        }
+        let end = Instant::now() + Duration::from_secs(1);
+        let mut first = None;
+        let mut last = None;
+        let mut edges = 0u32;
+        while let Some(remaining) = end.checked_duration_since(Instant::now()) {
+            match input.poll_interrupt(false, Some(remaining)) {
+                Ok(Some(_)) => {
+                    let now = Instant::now();
+                    first.get_or_insert(now);
+                    last = Some(now);
+                    edges += 1;
+                }
+                _ => break,
+            }
+        }
+        if self
+            .state
+            .lock()
+            .unwrap()
+            .write_channel(channel, 0.0)
+            .is_err()
+        {
+            println!("Could not write to PCA9685 channel {}", channel);
+        }
+        match (first, last) {
+            (Some(first), Some(last)) if edges >= 3 => {
+                let measured = f64::from(edges - 1) / last.duration_since(first).as_secs_f64();
+                self.calibrate(measured);
+                Some(self.oscillator_frequency())
+            }
+            _ => {
+                println!("Too few edges seen on pin {}", pin);
+                None
+            }
+        }
+    }
+
+    /// The error counters of the I2C transfers.
+    pub fn bus_stats(&self) -> BusStats {
+        self.state.lock().unwrap().link.stats()
+    }
+
+    /// Change how failed I2C transfers are retried.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.state.lock().unwrap().link.set_policy(policy)
+    }
+}
+
+impl PinFactory for PCA9685 {
+    fn output_pin(&self, pin: u8, level: Level) -> Result<Box<dyn PinDriver>, PinError> {
+        if pin >= PCA9685_CHANNELS {
+            return Err(PinError::NoSuchPin(pin));
+        }
+        let mut state = self.state.lock().unwrap();
+        if state.claimed & (1 << pin) != 0 {
+            return Err(PinError::InUse(pin));
+        }
+        let duty_cycle = match level {
+            Level::High => 1.0,
+            Level::Low => 0.0,
+        };
+        state.write_channel(pin, duty_cycle)?;
+        state.claimed |= 1 << pin;
+        Ok(Box::new(PwmChannel {
+            state: Arc::clone(&self.state),
+            channel: pin,
+            reset_on_drop: true,
+        }))
+    }
+
+    fn input_pin(&self, _pin: u8, _bias: Bias) -> Result<Box<dyn PinDriver>, PinError> {
+        Err(PinError::Unsupported(
+            "the channels of the PCA9685 are outputs".to_string(),
+        ))
+    }
+}
+
+impl Default for PCA9685 {
+    fn default() -> Self {
+        PCA9685::new()
+    }
+}
+
+/// A channel of a PCA9685, turned off when dropped.
+struct PwmChannel {
+    state: Arc<Mutex<PwmState>>,
+    channel: u8,
+    reset_on_drop: bool,
+}
+
+impl PwmChannel {
+    fn unsupported(&self) -> PinError {
+        PinError::Unsupported(format!("PCA9685 channel {} is an output", self.channel))
+    }
+}
+
+impl PinDriver for PwmChannel {
+    fn pin(&self) -> u8 {
+        self.channel
+    }
+
+    fn read(&self) -> Level {
+        match self.state.lock().unwrap().duty[usize::from(self.channel)] > 0.0 {
+            true => Level::High,
+            false => Level::Low,
+        }
+    }
+
+    fn write(&mut self, level: Level) {
+        if self.try_write(level).is_err() {
+            println!("Could not write to PCA9685 channel {}", self.channel);
+        }
+    }
+
+    fn try_write(&mut self, level: Level) -> Result<(), PinError> {
+        let duty_cycle = match level {
+            Level::High => 1.0,
+            Level::Low => 0.0,
+        };
+        self.set_pwm(0.0, duty_cycle)
+    }
+
+    fn set_listener(&mut self, _listener: Option<PinListener>) -> Result<(), PinError> {
+        Err(self.unsupported())
+    }
+
+    fn wait_for_edge(&mut self, _timeout: Option<Duration>) -> Result<Option<Level>, PinError> {
+        Err(self.unsupported())
+    }
+
+    fn set_pwm(&mut self, _frequency: f64, duty_cycle: f64) -> Result<(), PinError> {
+        let mut state = self.state.lock().unwrap();
+        state.write_channel(self.channel, duty_cycle)?;
+        Ok(())
+    }
+
+    fn reset_on_drop(&self) -> bool {
+        self.reset_on_drop
+    }
+
+    fn set_reset_on_drop(&mut self, reset_on_drop: bool) {
+        self.reset_on_drop = reset_on_drop
+    }
+}
+
+impl Drop for PwmChannel {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        state.claimed &= !(1 << self.channel);
+        if self.reset_on_drop && state.write_channel(self.channel, 0.0).is_err() {
+            println!("Could not turn off PCA9685 channel {}", self.channel);
+        }
+    }
+}
+
+/// The levels of the data, clock and latch pins of a shift register at each step of
+/// shifting out the outputs given, the last output first, so that output 0 ends up on QA of
+/// the first register. The outputs change when the latch pin rises in the last steps.
+pub(crate) fn shift_steps(outputs: &[bool]) -> Vec<[bool; 3]> {
+    let mut steps = Vec::with_capacity(outputs.len() * 2 + 3);
+    for &bit in outputs.iter().rev() {
+        steps.push([bit, false, false]);
+        steps.push([bit, true, false]);
+    }
+    let data = outputs.first().copied().unwrap_or(false);
+    steps.push([data, false, false]);
+    steps.push([data, false, true]);
+    steps.push([data, false, false]);
+    steps
+}
+
+/// The data, clock and latch pins of a chain of shift registers and the outputs they
+/// were last given, shared by the pins of the chain.
+struct ShiftState {
+    pins: [OutputPin; 3],
+    levels: [bool; 3],
+    outputs: Vec<bool>,
+    claimed: Vec<bool>,
+    batches: usize,
+    pending: bool,
+}
+
+impl ShiftState {
+    /// Shift out the outputs and latch them, unless in a batch.
+    fn update(&mut self) {
+        if self.batches > 0 {
+            self.pending = true;
+            return;
+        }
+        self.pending = false;
+        for step in shift_steps(&self.outputs) {
+            for (i, &level) in step.iter().enumerate() {
+                if self.levels[i] != level {
+                    self.pins[i].write(if level { Level::High } else { Level::Low });
+                    self.levels[i] = level;
+                }
+            }
+        }
+    }
+}
+
+/// Represents a chain of 74HC595 shift registers, handing out their outputs as a
+/// `PinFactory` to drive many LEDs from three GPIO pins.
+///
+/// Connect QH' of each register to SER of the next. Pin 0 is QA of the register whose SER
+/// is connected to the data pin, pin 8 is QA of the next, and so on. Every change is
+/// shifted out to the whole chain and latched at once; inside `batch` the changes are
+/// shifted out together when it ends, so e.g. a bar graph never shows half an update.
+///
+/// The outputs cannot be read back, so `read` returns the level last written, and they
+/// cannot be inputs.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let register = ShiftRegister::new(17, 27, 22, 2);
+/// let mut graph = LEDBarGraph::with_factory(&register, &(0..16).collect::<Vec<u8>>());
+/// register.batch(|| graph.set_value(0.75));
+/// ```
+pub struct ShiftRegister {
+    state: Arc<Mutex<ShiftState>>,
+}
+
+impl ShiftRegister {
+    /// Returns a chain of shift registers with all outputs low
+    /// * `data` - The GPIO pin connected to SER (DS) of the first register
+    /// * `clock` - The GPIO pin connected to SRCLK (SHCP) of every register
+    /// * `latch` - The GPIO pin connected to RCLK (STCP) of every register
+    /// * `registers` - The number of registers in the chain, 1 to 31
+    pub fn new(data: u8, clock: u8, latch: u8, registers: u8) -> ShiftRegister {
+        let registers = if (1..=31).contains(&registers) {
+            registers
+        } else {
+            println!("Registers must be between 1 and 31");
+            registers.clamp(1, 31)
+        };
+        let gpio = match Gpio::new() {
+            Err(e) => panic!("{:?}", e),
+            Ok(gpio) => gpio,
+        };
+        let pin = |pin: u8| match gpio.get(pin) {
+            Err(e) => panic!("{:?}", e),
+            Ok(pin) => {
+                let mut pin = pin.into_output();
+                pin.set_low();
+                pin
+            }
+        };
+        let pins = usize::from(registers) * 8;
+        let mut state = ShiftState {
+            pins: [pin(data), pin(clock), pin(latch)],
+            levels: [false; 3],
+            outputs: vec![false; pins],
+            claimed: vec![false; pins],
+            batches: 0,
+            pending: false,
+        };
+        state.update();
+        ShiftRegister {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// The number of outputs of the chain.
+    pub fn pins(&self) -> u8 {
+        self.state.lock().unwrap().outputs.len() as u8
+    }
+
+    /// The level last written to each output, from pin 0.
+    pub fn value(&self) -> Vec<bool> {
+        self.state.lock().unwrap().outputs.clone()
+    }
+
+    /// Run `f`, holding back the changes it makes to the outputs and latching them all at
+    /// once when it returns. Batches may be nested; the outermost one latches.
+    pub fn batch<R, F: FnOnce() -> R>(&self, f: F) -> R {
+        // latches even if `f` panics, so the chain is never left holding changes back
+        struct Batch<'a>(&'a Mutex<ShiftState>);
+        impl Drop for Batch<'_> {
+            fn drop(&mut self) {
+                let mut state = self.0.lock().unwrap_or_else(|e| e.into_inner());
+                state.batches -= 1;
+                if state.batches == 0 && state.pending {
+                    state.update();
+                }
+            }
+        }
+        self.state.lock().unwrap().batches += 1;
+        let _batch = Batch(&self.state);
+        f()
+    }
+}
+
+impl PinFactory for ShiftRegister {
+    fn output_pin(&self, pin: u8, level: Level) -> Result<Box<dyn PinDriver>, PinError> {
+        let mut state = self.state.lock().unwrap();
+        let index = usize::from(pin);
+        match state.claimed.get(index) {
+            None => return Err(PinError::NoSuchPin(pin)),
+            Some(true) => return Err(PinError::InUse(pin)),
+            Some(false) => (),
+        }
+        state.claimed[index] = true;
+        state.outputs[index] = level == Level::High;
+        state.update();
+        Ok(Box::new(ShiftPin {
+            state: Arc::clone(&self.state),
+            pin,
+            reset_on_drop: true,
+        }))
+    }
+
+    fn input_pin(&self, _pin: u8, _bias: Bias) -> Result<Box<dyn PinDriver>, PinError> {
+        Err(PinError::Unsupported(
+            "the pins of a shift register are outputs".to_string(),
+        ))
+    }
+}
+
+/// An output of a shift register, driven low when dropped.
+struct ShiftPin {
+    state: Arc<Mutex<ShiftState>>,
+    pin: u8,
+    reset_on_drop: bool,
+}
+
+impl ShiftPin {
+    fn unsupported(&self) -> PinError {
+        PinError::Unsupported(format!("shift register pin {} is an output", self.pin))
+    }
+}
+
+impl PinDriver for ShiftPin {
+    fn pin(&self) -> u8 {
+        self.pin
+    }
+
+    fn read(&self) -> Level {
+        match self.state.lock().unwrap().outputs[usize::from(self.pin)] {
+            true => Level::High,
+            false => Level::Low,
+        }
+    }
+
+    fn write(&mut self, level: Level) {
+        let mut state = self.state.lock().unwrap();
+        let high = level == Level::High;
+        if state.outputs[usize::from(self.pin)] != high {
+            state.outputs[usize::from(self.pin)] = high;
+            state.update();
+        }
+    }
+
+    fn set_listener(&mut self, _listener: Option<PinListener>) -> Result<(), PinError> {
+        Err(self.unsupported())
+    }
+
+    fn wait_for_edge(&mut self, _timeout: Option<Duration>) -> Result<Option<Level>, PinError> {
+        Err(self.unsupported())
+    }
+
+    fn reset_on_drop(&self) -> bool {
+        self.reset_on_drop
+    }
+
+    fn set_reset_on_drop(&mut self, reset_on_drop: bool) {
+        self.reset_on_drop = reset_on_drop
+    }
+}
+
+impl Drop for ShiftPin {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        let index = usize::from(self.pin);
+        state.claimed[index] = false;
+        if self.reset_on_drop && state.outputs[index] {
+            state.outputs[index] = false;
+            state.update();
+        }
+    }
+}
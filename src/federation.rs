@@ -0,0 +1,962 @@
+//! Several Pis working together over the network
+//!
+//! A `FederationNode` serves the devices of a `DeviceTree` over TCP with the same
+//! newline-delimited JSON requests as `ControlServer`, and announces itself on the local
+//! network with mDNS as a `_gpiozero._tcp` service. A coordinator finds the nodes with
+//! `Federation::discover` and reads and drives their devices through `RemoteDevice`, which
+//! is a `Component` and `OutputComponent`, so a composite can span machines: sensors in the
+//! greenhouse, relays in the shed.
+//!
+//! Changes can also be scheduled for a moment in time with `Federation::schedule`, so a
+//! light show or a set of irrigation zones switches on every node at once. Each node keeps
+//! the changes it is sent and makes them by its own clock; the coordinator measures how far
+//! each node's clock is from its own and shifts the times to match, which makes up for the
+//! few milliseconds NTP leaves between them.
+//!
+//! When a node stops answering, its `RemoteDevice`s report `SensorHealth::Disconnected`
+//! and, according to their `OfflinePolicy`, reject changes or keep the last one. A
+//! background thread tries the node again every second and reattaches the devices as soon as
+//! it answers, making any buffered change.
+//!
+//! A node controls real pins, so it only answers requests carrying the token it was started
+//! with, and `serve` only listens on the loopback interface. Listening on the network with
+//! `serve_on` lets every host that can reach the port, and knows the token, switch the
+//! outputs of the tree; the token is sent in the clear, so keep nodes on a trusted network.
+//!
+//! mDNS goes through the Avahi daemon that Raspberry Pi OS runs, using the
+//! `avahi-publish-service` and `avahi-browse` programs from the `avahi-utils` package. Where
+//! they are not installed, nodes still serve their devices and coordinators add them with
+//! `add_peer`.
+//!
+//! # Example
+//!
+//! On each node:
+//!
+//! ```no_run
+//! use rust_gpiozero::*;
+//! use std::sync::{Arc, Mutex};
+//!
+//! let mut tree = DeviceTree::default();
+//! tree.add_inspectable("pump", DigitalOutputDevice::new(17)).unwrap();
+//! let tree = Arc::new(Mutex::new(tree));
+//! let address = ([0, 0, 0, 0], DEFAULT_FEDERATION_PORT).into();
+//! let _node = FederationNode::serve_on(tree, "shed", address, "s3cret-garden").unwrap();
+//! std::thread::park();
+//! ```
+//!
+//! On the coordinator:
+//!
+//! ```no_run
+//! use rust_gpiozero::*;
+//!
+//! let mut federation = Federation::new("s3cret-garden");
+//! federation.discover().unwrap();
+//! let mut pump = federation.device("shed", "pump").unwrap();
+//! pump.on();
+//! ```
+use crate::bus::OfflinePolicy;
+use crate::control::handle_request;
+use crate::devices::{Component, OutputComponent};
+use crate::health::SensorHealth;
+use crate::json::Json;
+use crate::output_devices::sleep_while_running;
+use crate::tree::DeviceTree;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle, Thread};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// The mDNS service type nodes announce themselves as.
+pub const FEDERATION_SERVICE: &str = "_gpiozero._tcp";
+
+/// The TCP port nodes listen on unless told otherwise.
+pub const DEFAULT_FEDERATION_PORT: u16 = 7531;
+
+/// The reply to a request without the token of the node.
+const UNAUTHORIZED: &str = "missing or wrong token";
+
+/// How often the node checks for new connections and for being stopped.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long a request to a node may take.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often a `RemoteDevice` tries to reach a node that stopped answering.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The number of round trips `Federation::sync_clock` times to measure a clock offset.
+const CLOCK_SAMPLES: usize = 8;
+
+/// Why a request to another node failed.
+#[derive(Debug)]
+pub enum FederationError {
+    /// The node could not be reached, or the connection broke
+    Io(io::Error),
+    /// No node with the name has been discovered or added
+    UnknownPeer(String),
+    /// The node answered with the error given, e.g. for an unknown device
+    Remote(String),
+    /// The node answered with something other than a response
+    Protocol(String),
+    /// The node stopped answering and has not been reached again yet
+    Offline(String),
+}
+
+impl fmt::Display for FederationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FederationError::Io(error) => write!(f, "{}", error),
+            FederationError::UnknownPeer(name) => write!(f, "no node named '{}'", name),
+            FederationError::Remote(error) => write!(f, "{}", error),
+            FederationError::Protocol(message) => write!(f, "unexpected response: {}", message),
+            FederationError::Offline(name) => write!(f, "node '{}' is not answering", name),
+        }
+    }
+}
+
+impl Error for FederationError {}
+
+impl From<io::Error> for FederationError {
+    fn from(error: io::Error) -> FederationError {
+        FederationError::Io(error)
+    }
+}
+
+/// Serves a `DeviceTree` to the other Pis of a federation. The node stops serving and
+/// withdraws its announcement when it is dropped.
+pub struct FederationNode {
+    name: String,
+    address: SocketAddr,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+    scheduler: Option<JoinHandle<()>>,
+    announcer: Option<Child>,
+}
+
+/// A change to a device of the tree, waiting for its time.
+struct Scheduled {
+    at: SystemTime,
+    device: String,
+    value: String,
+}
+
+type Schedule = Arc<Mutex<Vec<Scheduled>>>;
+
+/// Make the scheduled changes of the tree as their times come, logging those that fail.
+fn run_schedule(tree: &Mutex<DeviceTree>, schedule: &Mutex<Vec<Scheduled>>, running: &AtomicBool) {
+    while running.load(Ordering::SeqCst) {
+        let next = schedule
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|change| change.at)
+            .min();
+        let wait = match next {
+            Some(at) => at.duration_since(SystemTime::now()).unwrap_or_default(),
+            None => {
+                thread::park();
+                continue;
+            }
+        };
+        // sleep until shortly before the change, then spin so it is made on time
+        if wait > Duration::from_millis(2) {
+            thread::park_timeout(wait - Duration::from_millis(1));
+            continue;
+        }
+        while SystemTime::now() < next.unwrap() {
+            std::hint::spin_loop();
+        }
+        let due: Vec<Scheduled> = {
+            let mut schedule = schedule.lock().unwrap();
+            let now = SystemTime::now();
+            let (due, pending) = schedule.drain(..).partition(|change| change.at <= now);
+            *schedule = pending;
+            due
+        };
+        let mut tree = tree.lock().unwrap();
+        for change in due {
+            if let Err(e) = tree.set_value(&change.device, &change.value) {
+                tree.log_event(&change.device, &format!("scheduled change failed: {}", e));
+            }
+        }
+    }
+}
+
+impl FederationNode {
+    /// Start serving the tree on the loopback interface only, e.g. to other programs on the
+    /// same Pi or through an SSH tunnel. Use `serve_on` to serve other Pis.
+    /// * `tree` - The device tree shared with the application
+    /// * `name` - The name other nodes know this one by, e.g. `shed`
+    /// * `port` - The TCP port, normally `DEFAULT_FEDERATION_PORT`; 0 picks a free one
+    /// * `token` - The secret every request must carry, shared with the coordinators
+    pub fn serve(
+        tree: Arc<Mutex<DeviceTree>>,
+        name: &str,
+        port: u16,
+        token: &str,
+    ) -> io::Result<FederationNode> {
+        FederationNode::serve_on(tree, name, (Ipv4Addr::LOCALHOST, port).into(), token)
+    }
+
+    /// Start serving the tree on the address given and, unless it is a loopback address,
+    /// announce the node with mDNS.
+    ///
+    /// Any host that can reach the address and knows the token can read and switch every
+    /// device of the tree. The token is not encrypted on the way, so only listen on a
+    /// network whose hosts are trusted, and on a single interface where the Pi has several.
+    /// * `tree` - The device tree shared with the application
+    /// * `name` - The name other nodes know this one by, e.g. `shed`
+    /// * `address` - The address and port to listen on, e.g. `0.0.0.0:7531` for every
+    ///   interface; port 0 picks a free one
+    /// * `token` - The secret every request must carry, shared with the coordinators
+    ///
+    /// Returns an error of kind `InvalidInput` if the token is empty.
+    pub fn serve_on(
+        tree: Arc<Mutex<DeviceTree>>,
+        name: &str,
+        address: SocketAddr,
+        token: &str,
+    ) -> io::Result<FederationNode> {
+        if token.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the token must not be empty",
+            ));
+        }
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        let address = listener.local_addr()?;
+        let running = Arc::new(AtomicBool::new(true));
+        let schedule: Schedule = Arc::default();
+        let scheduler = {
+            let tree = Arc::clone(&tree);
+            let schedule = Arc::clone(&schedule);
+            let running = Arc::clone(&running);
+            thread::spawn(move || run_schedule(&tree, &schedule, &running))
+        };
+        let handle = {
+            let running = Arc::clone(&running);
+            let scheduler = scheduler.thread().clone();
+            let token = token.to_string();
+            thread::spawn(move || {
+                while running.load(Ordering::SeqCst) {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            let node = Session {
+                                tree: Arc::clone(&tree),
+                                schedule: Arc::clone(&schedule),
+                                scheduler: scheduler.clone(),
+                                token: token.clone(),
+                            };
+                            let running = Arc::clone(&running);
+                            thread::spawn(move || {
+                                let _ = node.serve(stream, &running);
+                            });
+                        }
+                        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                            thread::sleep(POLL_INTERVAL)
+                        }
+                        Err(_) => break,
+                    }
+                }
+            })
+        };
+        let announcer = if address.ip().is_loopback() {
+            None
+        } else {
+            let announcer = Command::new("avahi-publish-service")
+                .arg(name)
+                .arg(FEDERATION_SERVICE)
+                .arg(address.port().to_string())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn();
+            if let Err(e) = &announcer {
+                println!("Could not announce node '{}' with mDNS: {}", name, e);
+            }
+            announcer.ok()
+        };
+        Ok(FederationNode {
+            name: name.to_string(),
+            address,
+            running,
+            handle: Some(handle),
+            scheduler: Some(scheduler),
+            announcer,
+        })
+    }
+
+    /// The name other nodes know this one by.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The TCP port the node listens on.
+    pub fn port(&self) -> u16 {
+        self.address.port()
+    }
+
+    /// The address the node listens on.
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Returns ``True`` if the node is announced with mDNS, which it never is while it only
+    /// listens on a loopback address.
+    pub fn is_announced(&self) -> bool {
+        self.announcer.is_some()
+    }
+
+    /// Stop accepting connections, end all sessions and withdraw the announcement.
+    pub fn stop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+        if let Some(scheduler) = self.scheduler.take() {
+            scheduler.thread().unpark();
+            let _ = scheduler.join();
+        }
+        if let Some(mut announcer) = self.announcer.take() {
+            let _ = announcer.kill();
+            let _ = announcer.wait();
+        }
+    }
+}
+
+impl Drop for FederationNode {
+    fn drop(&mut self) {
+        self.stop()
+    }
+}
+
+/// The parts of a node a connection from another node uses.
+struct Session {
+    tree: Arc<Mutex<DeviceTree>>,
+    schedule: Schedule,
+    scheduler: Thread,
+    token: String,
+}
+
+impl Session {
+    /// Answer requests from another node until it disconnects.
+    fn serve(&self, stream: TcpStream, running: &AtomicBool) -> io::Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(POLL_INTERVAL))?;
+        let mut writer = stream.try_clone()?;
+        let mut reader = BufReader::new(stream);
+        let mut line = Vec::new();
+        while running.load(Ordering::SeqCst) {
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) => break,
+                Ok(_) if line.ends_with(b"\n") => {
+                    let text = String::from_utf8_lossy(&line).trim().to_string();
+                    line.clear();
+                    if !text.is_empty() {
+                        let response = self.answer(&text);
+                        writer.write_all(format!("{}\n", response).as_bytes())?;
+                    }
+                }
+                Ok(_) => {}
+                Err(ref e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Answer a request: the control protocol, plus `time` to read the node's clock and
+    /// `at` to schedule a change. Subscriptions are only served locally, by a
+    /// `ControlServer`.
+    fn answer(&self, text: &str) -> Json {
+        let request = Json::parse(text).unwrap_or(Json::Null);
+        let token = request.get("token").and_then(Json::as_str).unwrap_or("");
+        let result = match request.get("cmd").and_then(Json::as_str) {
+            _ if !same_token(token, &self.token) => Err(UNAUTHORIZED.to_string()),
+            Some("time") => Ok(vec![("time", seconds(SystemTime::now()).into())]),
+            Some("at") => self.schedule(&request).map(|_| vec![]),
+            Some("subscribe") => Err("subscribe is not served to other nodes".to_string()),
+            _ => return handle_request(&mut self.tree.lock().unwrap(), text).0,
+        };
+        let mut response = Vec::new();
+        if let Some(id) = request.get("id") {
+            response.push(("id", id.clone()));
+        }
+        match result {
+            Ok(members) => {
+                response.push(("ok", true.into()));
+                response.extend(members);
+            }
+            Err(error) => {
+                response.push(("ok", false.into()));
+                response.push(("error", error.into()));
+            }
+        }
+        Json::object(response)
+    }
+
+    fn schedule(&self, request: &Json) -> Result<(), String> {
+        let device = request
+            .get("device")
+            .and_then(Json::as_str)
+            .ok_or("missing \"device\"")?;
+        let value = request.get("value").ok_or("missing \"value\"")?;
+        let at = request
+            .get("time")
+            .and_then(Json::as_f64)
+            .and_then(|time| Duration::try_from_secs_f64(time).ok())
+            .ok_or("missing \"time\"")?;
+        self.tree.lock().unwrap().value(device)?;
+        self.schedule.lock().unwrap().push(Scheduled {
+            at: UNIX_EPOCH + at,
+            device: device.to_string(),
+            value: value.to_text(),
+        });
+        self.scheduler.unpark();
+        Ok(())
+    }
+}
+
+/// Compare a token in a time that does not depend on how much of it is right.
+fn same_token(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+/// Seconds since the Unix epoch, the form times take in requests.
+fn seconds(time: SystemTime) -> f64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+/// Another node of a federation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Peer {
+    /// The name the node announced
+    pub name: String,
+    /// Where the node serves its devices
+    pub address: SocketAddr,
+}
+
+/// A connection to a node, opened when first needed and again after it breaks.
+struct PeerLink {
+    address: SocketAddr,
+    token: String,
+    connection: Option<(BufReader<TcpStream>, TcpStream)>,
+}
+
+impl PeerLink {
+    fn new(address: SocketAddr, token: &str) -> PeerLink {
+        PeerLink {
+            address,
+            token: token.to_string(),
+            connection: None,
+        }
+    }
+
+    /// Send a request with the token and return the members of the response, or the error
+    /// it carries.
+    fn request(&mut self, mut request: Json) -> Result<Json, FederationError> {
+        if let Json::Object(members) = &mut request {
+            members.push(("token".to_string(), self.token.as_str().into()));
+        }
+        let result = self.exchange(&request);
+        if let Err(FederationError::Io(_)) = result {
+            self.connection = None;
+        }
+        let response = result?;
+        match response.get("ok") {
+            Some(Json::Bool(true)) => Ok(response),
+            Some(Json::Bool(false)) => Err(FederationError::Remote(
+                response
+                    .get("error")
+                    .and_then(Json::as_str)
+                    .unwrap_or("request failed")
+                    .to_string(),
+            )),
+            _ => Err(FederationError::Protocol(response.to_string())),
+        }
+    }
+
+    fn exchange(&mut self, request: &Json) -> Result<Json, FederationError> {
+        if self.connection.is_none() {
+            let stream = TcpStream::connect_timeout(&self.address, TIMEOUT)?;
+            stream.set_read_timeout(Some(TIMEOUT))?;
+            stream.set_write_timeout(Some(TIMEOUT))?;
+            self.connection = Some((BufReader::new(stream.try_clone()?), stream));
+        }
+        let (reader, writer) = self.connection.as_mut().unwrap();
+        writer.write_all(format!("{}\n", request).as_bytes())?;
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        Json::parse(line.trim()).map_err(FederationError::Protocol)
+    }
+}
+
+/// How far the clock of a node is from the clock of the coordinator.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClockOffset {
+    /// Seconds the node's clock is ahead, negative if it is behind
+    pub offset: f64,
+    /// The round trip of the fastest exchange, which bounds the error of the offset to
+    /// half of it
+    pub round_trip: Duration,
+}
+
+/// The nodes of a federation, as seen by a coordinator.
+pub struct Federation {
+    peers: Vec<Peer>,
+    offsets: Vec<(String, ClockOffset)>,
+    token: String,
+}
+
+impl fmt::Debug for Federation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Federation")
+            .field("peers", &self.peers)
+            .field("offsets", &self.offsets)
+            .finish()
+    }
+}
+
+impl Federation {
+    /// Returns a Federation without any nodes; find them with `discover` or `add_peer`.
+    /// * `token` - The secret the nodes were started with
+    pub fn new(token: &str) -> Federation {
+        Federation {
+            peers: Vec::new(),
+            offsets: Vec::new(),
+            token: token.to_string(),
+        }
+    }
+
+    /// Look for nodes on the local network with mDNS, adding those not known yet. Returns
+    /// all the nodes that answered.
+    pub fn discover(&mut self) -> io::Result<Vec<Peer>> {
+        let output = Command::new("avahi-browse")
+            .args(["--resolve", "--parsable", "--terminate", FEDERATION_SERVICE])
+            .stderr(Stdio::null())
+            .output()?;
+        let mut found: Vec<Peer> = Vec::new();
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            // =;interface;protocol;name;type;domain;host;address;port;txt
+            let fields: Vec<&str> = line.split(';').collect();
+            if fields.len() < 9 || fields[0] != "=" {
+                continue;
+            }
+            let name = unescape(fields[3]);
+            let address = match (fields[7], fields[8].parse::<u16>()) {
+                (address, Ok(port)) => match (address, port).to_socket_addrs() {
+                    Ok(mut addresses) => addresses.next(),
+                    Err(_) => None,
+                },
+                _ => None,
+            };
+            if let Some(address) = address {
+                if !found.iter().any(|peer| peer.name == name) {
+                    found.push(Peer { name, address });
+                }
+            }
+        }
+        for peer in &found {
+            if !self.peers.iter().any(|known| known.name == peer.name) {
+                self.peers.push(peer.clone());
+            }
+        }
+        Ok(found)
+    }
+
+    /// Add a node by hand, e.g. on a network without mDNS. A node with the same name is
+    /// replaced.
+    /// * `name` - The name the node is known by
+    /// * `address` - Its address and port, e.g. `192.168.1.20:7531`
+    pub fn add_peer(&mut self, name: &str, address: SocketAddr) {
+        self.peers.retain(|peer| peer.name != name);
+        self.offsets.retain(|(peer, _)| peer != name);
+        self.peers.push(Peer {
+            name: name.to_string(),
+            address,
+        });
+    }
+
+    /// The nodes known so far.
+    pub fn peers(&self) -> &[Peer] {
+        &self.peers
+    }
+
+    /// The node with the name given.
+    pub fn peer(&self, name: &str) -> Option<&Peer> {
+        self.peers.iter().find(|peer| peer.name == name)
+    }
+
+    fn find(&self, name: &str) -> Result<&Peer, FederationError> {
+        self.peer(name)
+            .ok_or_else(|| FederationError::UnknownPeer(name.to_string()))
+    }
+
+    /// The devices of a node and their current values.
+    pub fn devices(&self, peer: &str) -> Result<Vec<(String, Json)>, FederationError> {
+        let mut link = PeerLink::new(self.find(peer)?.address, &self.token);
+        let response = link.request(Json::object(vec![("cmd", "list".into())]))?;
+        match response.get("devices") {
+            Some(Json::Array(devices)) => Ok(devices
+                .iter()
+                .filter_map(|device| {
+                    let name = device.get("name").and_then(Json::as_str)?;
+                    let value = device.get("value").cloned().unwrap_or(Json::Null);
+                    Some((name.to_string(), value))
+                })
+                .collect()),
+            _ => Err(FederationError::Protocol(response.to_string())),
+        }
+    }
+
+    /// A device of a node, checking that the node has it.
+    /// * `peer` - The name of the node
+    /// * `device` - The name of the device in the node's tree
+    pub fn device(&self, peer: &str, device: &str) -> Result<RemoteDevice, FederationError> {
+        let peer = self.find(peer)?.clone();
+        let state = Arc::new(Mutex::new(RemoteState {
+            link: PeerLink::new(peer.address, &self.token),
+            health: SensorHealth::Healthy,
+            policy: OfflinePolicy::default(),
+            pending: None,
+            when_health_changed: None,
+        }));
+        state.lock().unwrap().request(&peer, device, get())?;
+        let running = Arc::new(AtomicBool::new(true));
+        let handle = {
+            let state = Arc::clone(&state);
+            let running = Arc::clone(&running);
+            let (peer, device) = (peer.clone(), device.to_string());
+            thread::spawn(move || {
+                while running.load(Ordering::SeqCst) {
+                    sleep_while_running(&running, RECONNECT_INTERVAL);
+                    if state.lock().unwrap().health == SensorHealth::Disconnected {
+                        reattach(&state, &peer, &device);
+                    }
+                }
+            })
+        };
+        Ok(RemoteDevice {
+            peer,
+            device: device.to_string(),
+            state,
+            running,
+            handle: Some(handle),
+        })
+    }
+}
+
+impl Federation {
+    /// Measure how far the clock of a node is from this one, the way NTP does: the node's
+    /// time is compared with the middle of the round trip, and the fastest of several round
+    /// trips is kept. The offset is used by `schedule` until it is measured again.
+    pub fn sync_clock(&mut self, peer: &str) -> Result<ClockOffset, FederationError> {
+        let mut link = PeerLink::new(self.find(peer)?.address, &self.token);
+        let mut best: Option<ClockOffset> = None;
+        for _ in 0..CLOCK_SAMPLES {
+            let sent = SystemTime::now();
+            let started = Instant::now();
+            let response = link.request(Json::object(vec![("cmd", "time".into())]))?;
+            let round_trip = started.elapsed();
+            let time = response
+                .get("time")
+                .and_then(Json::as_f64)
+                .ok_or_else(|| FederationError::Protocol(response.to_string()))?;
+            let middle = seconds(sent) + round_trip.as_secs_f64() / 2.0;
+            let sample = ClockOffset {
+                offset: time - middle,
+                round_trip,
+            };
+            if best.is_none_or(|best| sample.round_trip < best.round_trip) {
+                best = Some(sample);
+            }
+        }
+        let offset = best.unwrap();
+        self.offsets.retain(|(name, _)| name != peer);
+        self.offsets.push((peer.to_string(), offset));
+        Ok(offset)
+    }
+
+    /// The clock offset last measured for a node.
+    pub fn clock_offset(&self, peer: &str) -> Option<ClockOffset> {
+        self.offsets
+            .iter()
+            .find(|(name, _)| name == peer)
+            .map(|(_, offset)| *offset)
+    }
+
+    /// Change the value of a device of a node at a moment by the coordinator's clock. The
+    /// node's clock offset is measured first if it has not been yet; measure it again with
+    /// `sync_clock` now and then on long runs, as clocks drift.
+    ///
+    /// Send the changes a few hundred milliseconds ahead so every node has them in time; a
+    /// change that arrives late is made straight away.
+    /// * `at` - When to make the change
+    /// * `peer` - The name of the node
+    /// * `device` - The name of the device in the node's tree
+    /// * `value` - The new value, e.g. ``True`` to turn on a relay
+    pub fn schedule<V: Into<Json>>(
+        &mut self,
+        at: SystemTime,
+        peer: &str,
+        device: &str,
+        value: V,
+    ) -> Result<(), FederationError> {
+        let offset = match self.clock_offset(peer) {
+            Some(offset) => offset,
+            None => self.sync_clock(peer)?,
+        };
+        let mut link = PeerLink::new(self.find(peer)?.address, &self.token);
+        link.request(Json::object(vec![
+            ("cmd", "at".into()),
+            ("time", (seconds(at) + offset.offset).into()),
+            ("device", device.into()),
+            ("value", value.into()),
+        ]))
+        .map(|_| ())
+    }
+}
+
+/// Undo the escaping of `avahi-browse --parsable`, which writes characters other than
+/// letters and digits as a backslash and their decimal code, e.g. `\032` for a space.
+fn unescape(text: &str) -> String {
+    let mut bytes = Vec::new();
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let code = tail
+            .get(..3)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| digits.parse::<u8>().ok());
+        match (byte, code) {
+            (b'\\', Some(code)) => {
+                bytes.push(code);
+                rest = &tail[3..];
+            }
+            (b'\\', None) if !tail.is_empty() => {
+                bytes.push(tail[0]);
+                rest = &tail[1..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+type HealthCallback = Box<dyn FnMut(SensorHealth) + Send>;
+
+/// The link of a `RemoteDevice` and what it knows about the node.
+struct RemoteState {
+    link: PeerLink,
+    health: SensorHealth,
+    policy: OfflinePolicy,
+    pending: Option<Json>,
+    when_health_changed: Option<HealthCallback>,
+}
+
+impl RemoteState {
+    fn set_health(&mut self, health: SensorHealth) {
+        if health != self.health {
+            self.health = health;
+            if let Some(callback) = self.when_health_changed.as_mut() {
+                callback(health);
+            }
+        }
+    }
+
+    /// Send a request about the device, marking the node disconnected if it cannot be
+    /// reached. Requests fail straight away while it is disconnected.
+    fn request(
+        &mut self,
+        peer: &Peer,
+        device: &str,
+        members: Vec<(&str, Json)>,
+    ) -> Result<Json, FederationError> {
+        if self.health == SensorHealth::Disconnected {
+            return Err(FederationError::Offline(peer.name.clone()));
+        }
+        let mut request = vec![("device", device.into())];
+        request.extend(members);
+        let result = self.link.request(Json::object(request));
+        if let Err(FederationError::Io(_)) = result {
+            self.set_health(SensorHealth::Disconnected);
+        }
+        result
+    }
+}
+
+fn get() -> Vec<(&'static str, Json)> {
+    vec![("cmd", "get".into())]
+}
+
+/// Try to reach the node of a disconnected device on a new connection, without holding the
+/// state locked while connecting, then make the buffered change and mark it healthy again.
+fn reattach(state: &Mutex<RemoteState>, peer: &Peer, device: &str) {
+    let token = state.lock().unwrap().link.token.clone();
+    let mut link = PeerLink::new(peer.address, &token);
+    let probe = Json::object(vec![("cmd", "get".into()), ("device", device.into())]);
+    if let Err(FederationError::Io(_)) = link.request(probe) {
+        return;
+    }
+    let mut state = state.lock().unwrap();
+    if let Some(value) = state.pending.take() {
+        let set = Json::object(vec![
+            ("cmd", "set".into()),
+            ("device", device.into()),
+            ("value", value.clone()),
+        ]);
+        match link.request(set) {
+            Err(FederationError::Io(_)) => {
+                state.pending = Some(value);
+                return;
+            }
+            Err(e) => println!("Could not set {} on {}: {}", device, peer.name, e),
+            Ok(_) => {}
+        }
+    }
+    state.link = link;
+    state.set_health(SensorHealth::Healthy);
+}
+
+/// A device in the tree of another node.
+///
+/// Values are exchanged as JSON: ``True`` and ``False`` for on/off devices, numbers for PWM
+/// outputs and sensors. Each request waits for the node's answer, for up to 5 seconds.
+///
+/// If the node cannot be reached the device becomes `SensorHealth::Disconnected`, and until
+/// it is reattached reads fail at once and changes are handled per its `OfflinePolicy`.
+pub struct RemoteDevice {
+    peer: Peer,
+    device: String,
+    state: Arc<Mutex<RemoteState>>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl RemoteDevice {
+    /// The node the device is on.
+    pub fn peer(&self) -> &Peer {
+        &self.peer
+    }
+
+    /// The name of the device in the node's tree.
+    pub fn name(&self) -> &str {
+        &self.device
+    }
+
+    /// `Healthy` while the node answers, `Disconnected` from the first request it did not
+    /// answer until it is reached again.
+    pub fn health(&self) -> SensorHealth {
+        self.state.lock().unwrap().health
+    }
+
+    /// Set the function called from a background thread when the node is lost or reattached.
+    pub fn when_health_changed<C>(&mut self, callback: C)
+    where
+        C: FnMut(SensorHealth) + Send + 'static,
+    {
+        self.state.lock().unwrap().when_health_changed = Some(Box::new(callback));
+    }
+
+    /// What is done with changes while the node is disconnected.
+    pub fn offline_policy(&self) -> OfflinePolicy {
+        self.state.lock().unwrap().policy
+    }
+
+    /// Set what is done with changes while the node is disconnected; `Reject` by default.
+    pub fn set_offline_policy(&mut self, policy: OfflinePolicy) {
+        let mut state = self.state.lock().unwrap();
+        state.policy = policy;
+        if policy == OfflinePolicy::Reject {
+            state.pending = None;
+        }
+    }
+
+    /// Read the current value of the device.
+    pub fn value(&self) -> Result<Json, FederationError> {
+        let response = self
+            .state
+            .lock()
+            .unwrap()
+            .request(&self.peer, &self.device, get())?;
+        Ok(response.get("value").cloned().unwrap_or(Json::Null))
+    }
+
+    /// Change the value of the device, e.g. ``True`` to turn on a relay. If the node cannot
+    /// be reached, the change is kept for when it is reattached if the policy is `Buffer`.
+    pub fn set_value<V: Into<Json>>(&self, value: V) -> Result<(), FederationError> {
+        let mut state = self.state.lock().unwrap();
+        let value = value.into();
+        if state.health == SensorHealth::Disconnected && state.policy == OfflinePolicy::Buffer {
+            state.pending = Some(value);
+            return Ok(());
+        }
+        let set = vec![("cmd", "set".into()), ("value", value.clone())];
+        match state.request(&self.peer, &self.device, set) {
+            Err(FederationError::Io(_)) if state.policy == OfflinePolicy::Buffer => {
+                state.pending = Some(value);
+                Ok(())
+            }
+            result => result.map(|_| ()),
+        }
+    }
+
+    fn set_or_report(&self, value: bool) {
+        if let Err(e) = self.set_value(value) {
+            println!("Could not set {} on {}: {}", self.device, self.peer.name, e);
+        }
+    }
+}
+
+impl Drop for RemoteDevice {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Component for RemoteDevice {
+    fn values(&self) -> Vec<f64> {
+        match self.value() {
+            Ok(Json::Bool(value)) => vec![if value { 1.0 } else { 0.0 }],
+            Ok(Json::Number(value)) => vec![value],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl OutputComponent for RemoteDevice {
+    fn on(&mut self) {
+        self.set_or_report(true)
+    }
+
+    fn off(&mut self) {
+        self.set_or_report(false)
+    }
+
+    fn toggle(&mut self) {
+        let active = self.is_active();
+        self.set_or_report(!active)
+    }
+}
@@ -0,0 +1,195 @@
+//! Health supervision for sensors
+//!
+//! A `Supervisor` watches the readings of a sensor and flags values that are frozen for too
+//! long, not a number, or outside the range the sensor can physically report. While the sensor
+//! is unhealthy, readings can be replaced by a fallback value stream so that control loops keep
+//! running on a sensible value.
+//!
+//! # Example
+//!
+//! ```
+//! use rust_gpiozero::*;
+//! use std::time::Duration;
+//!
+//! let mut supervisor = Supervisor::new();
+//! supervisor.set_range(-40.0, 125.0);
+//! supervisor.set_stuck_timeout(Duration::from_secs(600), 0.01);
+//! supervisor.set_fallback(std::iter::repeat(20.0));
+//! supervisor.when_health_changed(|health| println!("temperature sensor is {:?}", health));
+//!
+//! let temperature = supervisor.supervise(-127.0);
+//! assert_eq!(temperature, Some(20.0));
+//! ```
+use std::time::{Duration, Instant};
+
+/// The health of a sensor as judged by a `Supervisor`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SensorHealth {
+    /// Readings look plausible
+    Healthy,
+    /// The reading has not changed for longer than the stuck timeout
+    Stuck,
+    /// The reading is outside the physically possible range
+    OutOfRange,
+    /// The reading is NaN or infinite
+    Invalid,
+    /// The transport to the device is lost, e.g. a remote node or an I2C expander stopped
+    /// answering
+    Disconnected,
+}
+
+impl SensorHealth {
+    /// Returns ``True`` if the sensor is healthy.
+    pub fn is_healthy(&self) -> bool {
+        *self == SensorHealth::Healthy
+    }
+}
+
+type HealthCallback = Box<dyn FnMut(SensorHealth) + Send>;
+
+/// Watches a sensor's readings and reports its `SensorHealth`.
+pub struct Supervisor {
+    range: Option<(f64, f64)>,
+    stuck_timeout: Option<Duration>,
+    stuck_tolerance: f64,
+    reference: Option<(f64, Instant)>,
+    health: SensorHealth,
+    fallback: Option<Box<dyn Iterator<Item = f64> + Send>>,
+    when_health_changed: Option<HealthCallback>,
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Supervisor::new()
+    }
+}
+
+impl Supervisor {
+    /// Returns a Supervisor which only flags NaN and infinite readings until configured further
+    pub fn new() -> Supervisor {
+        Supervisor {
+            range: None,
+            stuck_timeout: None,
+            stuck_tolerance: 0.0,
+            reference: None,
+            health: SensorHealth::Healthy,
+            fallback: None,
+            when_health_changed: None,
+        }
+    }
+
+    /// Set the range of physically possible readings; anything outside it is `OutOfRange`.
+    /// * `min` - Lowest possible reading
+    /// * `max` - Highest possible reading
+    pub fn set_range(&mut self, min: f64, max: f64) {
+        self.range = Some((min.min(max), min.max(max)));
+    }
+
+    /// Flag the sensor as `Stuck` when its reading stays within `tolerance` of the same value for longer than `timeout`.
+    /// * `timeout` - How long a reading may stay the same
+    /// * `tolerance` - Largest change still considered "the same" reading
+    pub fn set_stuck_timeout(&mut self, timeout: Duration, tolerance: f64) {
+        self.stuck_timeout = Some(timeout);
+        self.stuck_tolerance = tolerance.abs();
+    }
+
+    /// Set a value stream used in place of the sensor's readings while it is unhealthy.
+    pub fn set_fallback<I>(&mut self, source: I)
+    where
+        I: IntoIterator<Item = f64>,
+        I::IntoIter: Send + 'static,
+    {
+        self.fallback = Some(Box::new(source.into_iter()));
+    }
+
+    /// Set the function called whenever the health of the sensor changes.
+    pub fn when_health_changed<C>(&mut self, callback: C)
+    where
+        C: FnMut(SensorHealth) + Send + 'static,
+    {
+        self.when_health_changed = Some(Box::new(callback));
+    }
+
+    /// The health of the sensor after the last reading.
+    pub fn health(&self) -> SensorHealth {
+        self.health
+    }
+
+    /// Judge a reading taken now and return the resulting health.
+    pub fn check(&mut self, value: f64) -> SensorHealth {
+        self.check_at(Instant::now(), value)
+    }
+
+    /// Judge a reading taken at the given time and return the resulting health.
+    pub fn check_at(&mut self, at: Instant, value: f64) -> SensorHealth {
+        let health = if !value.is_finite() {
+            SensorHealth::Invalid
+        } else if self
+            .range
+            .is_some_and(|(min, max)| value < min || value > max)
+        {
+            SensorHealth::OutOfRange
+        } else {
+            match self.reference {
+                Some((reference, since)) if (value - reference).abs() <= self.stuck_tolerance => {
+                    match self.stuck_timeout {
+                        Some(timeout) if at.duration_since(since) > timeout => SensorHealth::Stuck,
+                        _ => SensorHealth::Healthy,
+                    }
+                }
+                _ => {
+                    self.reference = Some((value, at));
+                    SensorHealth::Healthy
+                }
+            }
+        };
+
+        if health != self.health {
+            self.health = health;
+            if let Some(callback) = self.when_health_changed.as_mut() {
+                callback(health);
+            }
+        }
+        health
+    }
+
+    /// Judge a reading and return the value a control loop should use: the reading itself
+    /// while the sensor is healthy, otherwise the next fallback value, or None without a fallback.
+    pub fn supervise(&mut self, value: f64) -> Option<f64> {
+        if self.check(value).is_healthy() {
+            Some(value)
+        } else {
+            self.fallback.as_mut().and_then(|fallback| fallback.next())
+        }
+    }
+
+    /// Wrap a value stream so that every reading is supervised.
+    pub fn watch<I: IntoIterator<Item = f64>>(self, values: I) -> Supervised<I::IntoIter> {
+        Supervised {
+            values: values.into_iter(),
+            supervisor: self,
+        }
+    }
+}
+
+/// Iterator returned by `Supervisor::watch`, yielding the result of `Supervisor::supervise` for each reading.
+pub struct Supervised<I> {
+    values: I,
+    supervisor: Supervisor,
+}
+
+impl<I> Supervised<I> {
+    /// The health of the sensor after the last reading.
+    pub fn health(&self) -> SensorHealth {
+        self.supervisor.health()
+    }
+}
+
+impl<I: Iterator<Item = f64>> Iterator for Supervised<I> {
+    type Item = Option<f64>;
+
+    fn next(&mut self) -> Option<Option<f64>> {
+        let value = self.values.next()?;
+        Some(self.supervisor.supervise(value))
+    }
+}
@@ -0,0 +1,226 @@
+//! Helpers for the I2C bus
+//!
+//! `scan` finds the addresses that answer on a bus, like `i2cdetect`, and `identify` names
+//! the devices that commonly use an address. Several devices share addresses, so the
+//! candidates are a starting point for bring-up rather than a positive identification.
+//!
+//! `I2CDevice` is the base for drivers of chips the crate does not know about: it reads
+//! and writes registers with the retries and error counters of a `BusLink`, and runs
+//! transactions without other devices on the bus getting in between.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::i2c;
+//!
+//! for found in i2c::scan(1).unwrap() {
+//!     println!("0x{:02x}: {}", found.address, found.candidates.join(", "));
+//! }
+//! ```
+use crate::bus::{BusLink, BusStats, RetryPolicy, SharedI2c};
+use crate::drivers;
+use rppal::i2c::{Error, I2c};
+
+/// Devices known to the crate and the addresses they can be configured for.
+pub const KNOWN_DEVICES: &[(&str, &[u16])] = &[
+    (
+        "MCP23017 I/O expander",
+        &[0x20, 0x21, 0x22, 0x23, 0x24, 0x25, 0x26, 0x27],
+    ),
+    (
+        "PCF8574 I/O expander",
+        &[0x20, 0x21, 0x22, 0x23, 0x24, 0x25, 0x26, 0x27],
+    ),
+    (
+        "PCF8574A I/O expander",
+        &[0x38, 0x39, 0x3a, 0x3b, 0x3c, 0x3d, 0x3e, 0x3f],
+    ),
+    ("HD44780 character LCD backpack", &[0x27, 0x3f]),
+    ("CAP1208 capacitive touch controller", &[0x28]),
+    ("SSD1306 OLED display", &[0x3c, 0x3d]),
+    (
+        "PCA9685 PWM driver",
+        &[0x40, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47],
+    ),
+    ("ADS1115 ADC", &[0x48, 0x49, 0x4a, 0x4b]),
+    ("ADS1015 ADC", &[0x48, 0x49, 0x4a, 0x4b]),
+    ("TMP102 temperature sensor", &[0x48, 0x49, 0x4a, 0x4b]),
+    (
+        "AT24C EEPROM",
+        &[0x50, 0x51, 0x52, 0x53, 0x54, 0x55, 0x56, 0x57],
+    ),
+    ("Sense HAT LED matrix", &[0x46]),
+    ("Sense HAT humidity sensor (HTS221)", &[0x5f]),
+    ("Sense HAT pressure sensor (LPS25H)", &[0x5c]),
+    ("Sense HAT IMU (LSM9DS1)", &[0x1c, 0x6a]),
+    ("DS3231 real-time clock", &[0x68]),
+    ("MPU6050 IMU", &[0x68, 0x69]),
+    ("BME280 environment sensor", &[0x76, 0x77]),
+    ("BMP280 pressure sensor", &[0x76, 0x77]),
+];
+
+/// An address that answered during a `scan`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanResult {
+    /// The 7-bit address
+    pub address: u16,
+    /// Known devices using this address, see `identify`
+    pub candidates: Vec<String>,
+}
+
+/// The known devices that can use an address: those in `KNOWN_DEVICES`, then registered
+/// drivers declaring the address.
+pub fn identify(address: u16) -> Vec<String> {
+    let mut candidates: Vec<String> = KNOWN_DEVICES
+        .iter()
+        .filter(|(_, addresses)| addresses.contains(&address))
+        .map(|(name, _)| name.to_string())
+        .collect();
+    for driver in drivers::drivers() {
+        if driver.i2c_addresses().contains(&address) {
+            candidates.push(driver.description().to_string());
+        }
+    }
+    candidates
+}
+
+/// Find the addresses that answer on an I2C bus.
+///
+/// Like `i2cdetect`, EEPROM ranges are probed with a read and all other addresses with a
+/// quick write, which some write-only devices may misinterpret.
+/// * `bus` - The bus number, 1 on most Raspberry Pi models
+pub fn scan(bus: u8) -> Result<Vec<ScanResult>, Error> {
+    let mut i2c = I2c::with_bus(bus)?;
+    let mut found = Vec::new();
+    for address in 0x03..=0x77 {
+        i2c.set_slave_address(address)?;
+        let answered = if (0x30..=0x37).contains(&address) || (0x50..=0x5f).contains(&address) {
+            i2c.smbus_receive_byte().is_ok()
+        } else {
+            i2c.smbus_quick_command(false).is_ok()
+        };
+        if answered {
+            found.push(ScanResult {
+                address,
+                candidates: identify(address),
+            });
+        }
+    }
+    Ok(found)
+}
+
+/// A device at one address of an I2C bus, the base of drivers for chips the crate does not
+/// know about.
+///
+/// Every transfer runs through a `BusLink`, so transient errors are retried, and holds the
+/// bus, shared with every other device on it. `transaction` holds the bus for several
+/// transfers, e.g. to read a register after selecting it with another.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::i2c::I2CDevice;
+///
+/// // a TMP102 temperature sensor: 12 bits, big endian, in steps of 0.0625C
+/// let mut sensor = I2CDevice::new(1, 0x48);
+/// let mut bytes = [0u8; 2];
+/// sensor.read_registers(0x00, &mut bytes).unwrap();
+/// let celsius = f64::from(i16::from_be_bytes(bytes) >> 4) * 0.0625;
+/// // one-shot conversion: set the OS bit of the config register, keeping the others
+/// sensor.update_register(0x01, 0x80, 0x80).unwrap();
+/// ```
+pub struct I2CDevice {
+    i2c: SharedI2c,
+    link: BusLink,
+}
+
+impl I2CDevice {
+    /// Returns the device at the address given
+    /// * `bus` - The I2C bus number, 1 on most Raspberry Pi models
+    /// * `address` - The 7-bit address of the device
+    pub fn new(bus: u8, address: u16) -> I2CDevice {
+        match SharedI2c::open(bus, address) {
+            Err(e) => panic!("{:?}", e),
+            Ok(i2c) => I2CDevice {
+                i2c,
+                link: BusLink::default(),
+            },
+        }
+    }
+
+    /// The bus number.
+    pub fn bus(&self) -> u8 {
+        self.i2c.bus()
+    }
+
+    /// The 7-bit address.
+    pub fn address(&self) -> u16 {
+        self.i2c.address()
+    }
+
+    /// Run transfers without other devices on the bus getting in between. The whole
+    /// transaction is run again if a transfer in it fails with a transient error.
+    /// * `transaction` - The transfers, given the I2C device
+    pub fn transaction<T, F>(&mut self, mut transaction: F) -> Result<T, Error>
+    where
+        F: FnMut(&mut I2c) -> Result<T, Error>,
+    {
+        let I2CDevice { i2c, link } = self;
+        link.run(|| transaction(&mut *i2c.lock()?))
+    }
+
+    /// Read bytes, without selecting a register first.
+    pub fn read(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+        self.transaction(|i2c| i2c.read(buffer).map(|_| ()))
+    }
+
+    /// Write bytes, without selecting a register first.
+    pub fn write(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.transaction(|i2c| i2c.write(bytes).map(|_| ()))
+    }
+
+    /// Read a register.
+    pub fn read_register(&mut self, register: u8) -> Result<u8, Error> {
+        self.transaction(|i2c| i2c.smbus_read_byte(register))
+    }
+
+    /// Write a register.
+    pub fn write_register(&mut self, register: u8, value: u8) -> Result<(), Error> {
+        self.transaction(|i2c| i2c.smbus_write_byte(register, value))
+    }
+
+    /// Read consecutive registers from the one given, for chips that move to the next
+    /// register after each byte.
+    pub fn read_registers(&mut self, register: u8, buffer: &mut [u8]) -> Result<(), Error> {
+        self.transaction(|i2c| i2c.write_read(&[register], buffer))
+    }
+
+    /// Write consecutive registers from the one given.
+    pub fn write_registers(&mut self, register: u8, values: &[u8]) -> Result<(), Error> {
+        let mut bytes = Vec::with_capacity(values.len() + 1);
+        bytes.push(register);
+        bytes.extend_from_slice(values);
+        self.write(&bytes)
+    }
+
+    /// Change some bits of a register, leaving the others, in one transaction.
+    /// * `register` - The register
+    /// * `mask` - The bits to change
+    /// * `value` - The new value of the bits in the mask
+    pub fn update_register(&mut self, register: u8, mask: u8, value: u8) -> Result<(), Error> {
+        self.transaction(|i2c| {
+            let current = i2c.smbus_read_byte(register)?;
+            i2c.smbus_write_byte(register, current & !mask | value & mask)
+        })
+    }
+
+    /// The error counters of the transfers.
+    pub fn bus_stats(&self) -> BusStats {
+        self.link.stats()
+    }
+
+    /// Change how failed transfers are retried.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.link.set_policy(policy)
+    }
+}
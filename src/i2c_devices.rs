@@ -0,0 +1,1547 @@
+//! Devices on the I2C bus, such as the ADS1x15 analog to digital converters, the CAP1208
+//! capacitive touch controller, the MPU6050 motion sensor and the BME280 environmental
+//! sensor, which can also be wired to the SPI bus
+//!
+//! These converters leave the SPI bus free and have a programmable gain amplifier, so small
+//! voltages such as those of a thermocouple amplifier or a shunt resistor can be read
+//! without losing resolution. Each chip is opened once and hands out one `ADSChannel` per
+//! input, which implements `AnalogInputDevice` like the channels of the SPI converters.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::*;
+//!
+//! let adc = ADS1115::new();
+//! let mut shunt = adc.differential_channel(0, 1);
+//! shunt.set_gain(ADSGain::V0_256);
+//! println!("{:.4}V", shunt.voltage());
+//! ```
+use crate::bus::{BusLink, BusStats, RetryPolicy, SharedI2c, SharedSpi};
+use crate::devices::{AnalogInputDevice, AnalogWatch};
+use crate::output_devices::sleep_while_running;
+use crate::sense_hat::Axes;
+use rppal::gpio::{Gpio, InputPin, Trigger};
+use rppal::spi::{Bus, Mode, SlaveSelect};
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// The address of an ADS1x15 with its ADDR pin connected to ground.
+const ADS_ADDRESS: u16 = 0x48;
+
+const ADS_CONVERSION: u8 = 0x00;
+const ADS_CONFIG: u8 = 0x01;
+
+/// The config bit that starts a conversion when written and reads 1 once it is done.
+const ADS_START: u16 = 0x8000;
+
+/// Single-shot mode with the comparator disabled.
+const ADS_SINGLE_SHOT: u16 = 0x0103;
+
+/// The full-scale range of the programmable gain amplifier of an ADS1x15. Inputs must stay
+/// between ground and the supply voltage whatever the range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ADSGain {
+    /// ±6.144V
+    V6_144,
+    /// ±4.096V
+    V4_096,
+    /// ±2.048V, the default
+    V2_048,
+    /// ±1.024V
+    V1_024,
+    /// ±0.512V
+    V0_512,
+    /// ±0.256V
+    V0_256,
+}
+
+impl ADSGain {
+    /// The voltage that reads as full scale.
+    pub fn full_scale(self) -> f64 {
+        match self {
+            ADSGain::V6_144 => 6.144,
+            ADSGain::V4_096 => 4.096,
+            ADSGain::V2_048 => 2.048,
+            ADSGain::V1_024 => 1.024,
+            ADSGain::V0_512 => 0.512,
+            ADSGain::V0_256 => 0.256,
+        }
+    }
+
+    fn bits(self) -> u16 {
+        let code = match self {
+            ADSGain::V6_144 => 0,
+            ADSGain::V4_096 => 1,
+            ADSGain::V2_048 => 2,
+            ADSGain::V1_024 => 3,
+            ADSGain::V0_512 => 4,
+            ADSGain::V0_256 => 5,
+        };
+        code << 9
+    }
+}
+
+/// The chips of the ADS1x15 family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ADSChip {
+    ADS1015,
+    ADS1115,
+}
+
+impl ADSChip {
+    fn bits(self) -> u8 {
+        match self {
+            ADSChip::ADS1015 => 12,
+            ADSChip::ADS1115 => 16,
+        }
+    }
+
+    /// The data rates in samples per second, in the order of their config codes.
+    fn data_rates(self) -> &'static [u16] {
+        match self {
+            ADSChip::ADS1015 => &[128, 250, 490, 920, 1600, 2400, 3300],
+            ADSChip::ADS1115 => &[8, 16, 32, 64, 128, 250, 475, 860],
+        }
+    }
+
+    fn default_data_rate(self) -> u16 {
+        match self {
+            ADSChip::ADS1015 => 1600,
+            ADSChip::ADS1115 => 128,
+        }
+    }
+}
+
+/// The I2C device of a chip, shared by its channels.
+struct ADSBus {
+    i2c: SharedI2c,
+    link: BusLink,
+    data_rate: u16,
+}
+
+impl ADSBus {
+    /// The config code of the data rate.
+    fn rate_code(&self, chip: ADSChip) -> u16 {
+        let rates = chip.data_rates();
+        rates
+            .iter()
+            .position(|&rate| rate == self.data_rate)
+            .unwrap_or(rates.len() - 1) as u16
+    }
+
+    /// Start a conversion with the config given and wait for its result, as a 16 bit count
+    /// (the 12 bit ADS1015 reads in steps of 16).
+    fn convert(&mut self, config: u16) -> Result<i16, rppal::i2c::Error> {
+        let ADSBus {
+            i2c,
+            link,
+            data_rate,
+        } = self;
+        let conversion = Duration::from_secs_f64(1.0 / f64::from(*data_rate));
+        // other devices may use the bus while the chip converts; the lock of the chip keeps
+        // its own channels out
+        link.run(|| {
+            let [high, low] = (config | ADS_START).to_be_bytes();
+            i2c.lock()?.write(&[ADS_CONFIG, high, low])?;
+            let deadline = Instant::now() + conversion * 2 + Duration::from_millis(2);
+            thread::sleep(conversion);
+            let mut read = [0u8; 2];
+            loop {
+                i2c.lock()?.write_read(&[ADS_CONFIG], &mut read)?;
+                if u16::from_be_bytes(read) & ADS_START != 0 || Instant::now() >= deadline {
+                    break;
+                }
+                thread::sleep(Duration::from_micros(100));
+            }
+            i2c.lock()?.write_read(&[ADS_CONVERSION], &mut read)?;
+            Ok(i16::from_be_bytes(read))
+        })
+    }
+}
+
+/// One input of an ADS1x15 converter, read against ground or against another input.
+///
+/// Readings against ground are between 0.0 and 1.0 of the full-scale range of the gain.
+/// Differential readings are signed, between -1.0 and 1.0.
+pub struct ADSChannel {
+    chip: ADSChip,
+    bus: Arc<Mutex<ADSBus>>,
+    mux: u16,
+    differential: bool,
+    gain: Arc<Mutex<ADSGain>>,
+    divider: Arc<Mutex<f64>>,
+    watch: AnalogWatch,
+}
+
+impl ADSChannel {
+    /// Returns ``True`` if the channel reads against another input.
+    pub fn is_differential(&self) -> bool {
+        self.differential
+    }
+
+    /// The full-scale range.
+    pub fn gain(&self) -> ADSGain {
+        *self.gain.lock().unwrap()
+    }
+
+    /// Set the full-scale range. Choose the smallest range the input fits in for the best
+    /// resolution; readings beyond it are clipped to full scale.
+    pub fn set_gain(&mut self, gain: ADSGain) {
+        *self.gain.lock().unwrap() = gain
+    }
+
+    /// The ratio the input voltage is divided by before it reaches the chip.
+    pub fn divider(&self) -> f64 {
+        *self.divider.lock().unwrap()
+    }
+
+    /// Set the ratio of a voltage divider in front of the input, so `voltage` reads the
+    /// voltage before it, e.g. 24V inputs divided down to the range of the chip.
+    /// * `ratio` - The input voltage over the voltage at the chip, 1.0 or more
+    pub fn set_divider(&mut self, ratio: f64) {
+        if !(ratio >= 1.0 && ratio.is_finite()) {
+            println!("Divider ratio must be 1.0 or more");
+            return;
+        }
+        *self.divider.lock().unwrap() = ratio
+    }
+
+    /// Read the channel, returning the count from the converter, scaled to 16 bits.
+    pub fn read(&self) -> Result<i16, rppal::i2c::Error> {
+        let config = self.mux << 12 | self.gain().bits() | ADS_SINGLE_SHOT;
+        let mut bus = self.bus.lock().unwrap();
+        let code = bus.rate_code(self.chip);
+        bus.convert(config | code << 5)
+    }
+
+    /// A copy of the channel for the thread of its watch to read, sharing its gain.
+    fn reader(&self) -> impl Fn() -> f64 + Send + 'static {
+        let channel = ADSChannel {
+            chip: self.chip,
+            bus: Arc::clone(&self.bus),
+            mux: self.mux,
+            differential: self.differential,
+            gain: Arc::clone(&self.gain),
+            divider: Arc::clone(&self.divider),
+            watch: AnalogWatch::default(),
+        };
+        move || channel.value()
+    }
+}
+
+impl AnalogInputDevice for ADSChannel {
+    // a reading against ground loses the sign bit
+    fn bits(&self) -> u8 {
+        if self.differential {
+            self.chip.bits()
+        } else {
+            self.chip.bits() - 1
+        }
+    }
+
+    fn is_signed(&self) -> bool {
+        self.differential
+    }
+
+    fn raw_value(&self) -> Result<i32, Box<dyn Error + Send + Sync>> {
+        let raw = i32::from(self.read()?) >> (16 - self.chip.bits());
+        if self.differential {
+            Ok(raw)
+        } else {
+            Ok(raw.max(0))
+        }
+    }
+
+    fn max_voltage(&self) -> f64 {
+        self.gain().full_scale() * self.divider()
+    }
+
+    fn when_above<C>(&mut self, threshold: f64, callback: C)
+    where
+        C: FnMut(f64) + Send + 'static,
+    {
+        let read = self.reader();
+        self.watch.set(true, threshold, callback, read)
+    }
+
+    fn when_below<C>(&mut self, threshold: f64, callback: C)
+    where
+        C: FnMut(f64) + Send + 'static,
+    {
+        let read = self.reader();
+        self.watch.set(false, threshold, callback, read)
+    }
+
+    fn hysteresis(&self) -> f64 {
+        self.watch.hysteresis()
+    }
+
+    fn set_hysteresis(&mut self, hysteresis: f64) {
+        self.watch.set_hysteresis(hysteresis)
+    }
+}
+
+macro_rules! impl_ads_adc {
+    ($($adc:ident),*) => {
+        $(
+            #[doc = concat!("Represents an ", stringify!($adc), " analog to digital converter on the I2C bus.")]
+            pub struct $adc {
+                bus: Arc<Mutex<ADSBus>>,
+            }
+
+            impl $adc {
+                #[doc = concat!("Returns an ", stringify!($adc), " on I2C bus 1 at address 0x48, with its ADDR pin connected to ground")]
+                pub fn new() -> $adc {
+                    $adc::with_address(1, ADS_ADDRESS)
+                }
+
+                #[doc = concat!("Returns an ", stringify!($adc), " on the bus and at the address given")]
+                /// * `bus` - The I2C bus number, 1 on most Raspberry Pi models
+                /// * `address` - 0x48 to 0x4b, set by connecting the ADDR pin to GND, VDD,
+                ///   SDA or SCL
+                pub fn with_address(bus: u8, address: u16) -> $adc {
+                    let i2c = match SharedI2c::open(bus, address) {
+                        Err(e) => panic!("{:?}", e),
+                        Ok(i2c) => i2c,
+                    };
+                    $adc {
+                        bus: Arc::new(Mutex::new(ADSBus {
+                            i2c,
+                            link: BusLink::default(),
+                            data_rate: ADSChip::$adc.default_data_rate(),
+                        })),
+                    }
+                }
+
+                /// Returns the input given, read against ground, with a range of ±2.048V.
+                /// * `channel` - The input, from 0 to 3
+                pub fn channel(&self, channel: u8) -> ADSChannel {
+                    if channel > 3 {
+                        panic!("{} has channels 0 to 3, not {}", stringify!($adc), channel);
+                    }
+                    self.open(0b100 | u16::from(channel), false)
+                }
+
+                /// Returns the difference between two inputs, with a range of ±2.048V.
+                /// * `positive` - The input read, 0 to 2
+                /// * `negative` - The input it is read against: 1 or 3 for input 0, 3 for
+                ///   inputs 1 and 2
+                pub fn differential_channel(&self, positive: u8, negative: u8) -> ADSChannel {
+                    let mux = match (positive, negative) {
+                        (0, 1) => 0b000,
+                        (0, 3) => 0b001,
+                        (1, 3) => 0b010,
+                        (2, 3) => 0b011,
+                        _ => panic!(
+                            "{} cannot read input {} against input {}",
+                            stringify!($adc),
+                            positive,
+                            negative
+                        ),
+                    };
+                    self.open(mux, true)
+                }
+
+                fn open(&self, mux: u16, differential: bool) -> ADSChannel {
+                    ADSChannel {
+                        chip: ADSChip::$adc,
+                        bus: Arc::clone(&self.bus),
+                        mux,
+                        differential,
+                        gain: Arc::new(Mutex::new(ADSGain::V2_048)),
+                        divider: Arc::new(Mutex::new(1.0)),
+                        watch: AnalogWatch::default(),
+                    }
+                }
+
+                /// The number of samples per second of each conversion.
+                pub fn data_rate(&self) -> u16 {
+                    self.bus.lock().unwrap().data_rate
+                }
+
+                /// Set the samples per second of each conversion; slower rates average out
+                /// more noise. The ADS1115 runs at 8 to 860 and the ADS1015 at 128 to 3300
+                /// samples per second.
+                pub fn set_data_rate(&mut self, samples_per_second: u16) {
+                    if !ADSChip::$adc.data_rates().contains(&samples_per_second) {
+                        println!(
+                            "Data rate must be one of {:?}",
+                            ADSChip::$adc.data_rates()
+                        );
+                        return;
+                    }
+                    self.bus.lock().unwrap().data_rate = samples_per_second;
+                }
+
+                /// The error counters of the I2C transfers.
+                pub fn bus_stats(&self) -> BusStats {
+                    self.bus.lock().unwrap().link.stats()
+                }
+
+                /// Change how failed I2C transfers are retried.
+                pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+                    self.bus.lock().unwrap().link.set_policy(policy)
+                }
+            }
+
+            impl Default for $adc {
+                fn default() -> Self {
+                    $adc::new()
+                }
+            }
+        )*
+    };
+}
+
+impl_ads_adc!(ADS1015, ADS1115);
+
+/// The address of a CAP1208.
+const CAP1208_ADDRESS: u16 = 0x28;
+
+const CAP_MAIN_CONTROL: u8 = 0x00;
+const CAP_INPUT_STATUS: u8 = 0x03;
+const CAP_MULTIPLE_TOUCH: u8 = 0x2a;
+
+/// The bit of the main control register that latches touches until cleared.
+const CAP_INTERRUPT: u8 = 0x01;
+
+/// How often the touch thread reads the pads.
+const CAP_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+type TouchCallback = Box<dyn FnMut() + Send>;
+
+/// The functions called when pads of a CAP1208 are touched and released.
+#[derive(Default)]
+struct TouchCallbacks {
+    touched: [Option<TouchCallback>; 8],
+    released: [Option<TouchCallback>; 8],
+}
+
+impl fmt::Debug for TouchCallbacks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TouchCallbacks")
+            .field(
+                "touched",
+                &self.touched.iter().filter(|c| c.is_some()).count(),
+            )
+            .field(
+                "released",
+                &self.released.iter().filter(|c| c.is_some()).count(),
+            )
+            .finish()
+    }
+}
+
+/// The I2C device of a CAP1208.
+struct CapBus {
+    i2c: SharedI2c,
+    link: BusLink,
+}
+
+impl CapBus {
+    /// The touched pads as bits, clearing the latch so released pads read as untouched
+    /// next time.
+    fn touched(&mut self) -> Result<u8, rppal::i2c::Error> {
+        let CapBus { i2c, link } = self;
+        link.run(|| {
+            let i2c = i2c.lock()?;
+            let status = i2c.smbus_read_byte(CAP_INPUT_STATUS)?;
+            let control = i2c.smbus_read_byte(CAP_MAIN_CONTROL)?;
+            i2c.smbus_write_byte(CAP_MAIN_CONTROL, control & !CAP_INTERRUPT)?;
+            Ok(status)
+        })
+    }
+}
+
+/// Represents a CAP1208 capacitive touch controller with 8 pads, numbered 0 to 7, such as
+/// the pads of the Explorer HAT.
+///
+/// Touches are latched by the chip, so a tap between two reads is not missed. Callbacks
+/// are called from a background thread that reads the pads every 20ms.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut pads = CAP1208::new();
+/// pads.when_touched(0, || println!("pad 0 touched"));
+/// println!("{:?}", pads.touched());
+/// ```
+pub struct CAP1208 {
+    bus: Arc<Mutex<CapBus>>,
+    callbacks: Arc<Mutex<TouchCallbacks>>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl CAP1208 {
+    /// Returns a CAP1208 on I2C bus 1 at address 0x28
+    pub fn new() -> CAP1208 {
+        CAP1208::with_address(1, CAP1208_ADDRESS)
+    }
+
+    /// Returns a CAP1208 on the bus and at the address given, with several pads allowed
+    /// to be touched at once
+    /// * `bus` - The I2C bus number, 1 on most Raspberry Pi models
+    /// * `address` - The address of the chip, 0x28
+    pub fn with_address(bus: u8, address: u16) -> CAP1208 {
+        let i2c = match SharedI2c::open(bus, address) {
+            Err(e) => panic!("{:?}", e),
+            Ok(i2c) => i2c,
+        };
+        // the chip reports only one touch at a time unless multiple touch blocking is off
+        if let Err(e) = i2c
+            .lock()
+            .and_then(|i2c| i2c.smbus_write_byte(CAP_MULTIPLE_TOUCH, 0x00))
+        {
+            panic!("{:?}", e);
+        }
+        CAP1208 {
+            bus: Arc::new(Mutex::new(CapBus {
+                i2c,
+                link: BusLink::default(),
+            })),
+            callbacks: Arc::default(),
+            running: Arc::new(AtomicBool::new(false)),
+            handle: None,
+        }
+    }
+
+    /// Read which pads are touched, or were touched since the last read.
+    pub fn touched(&self) -> Result<[bool; 8], rppal::i2c::Error> {
+        let status = self.bus.lock().unwrap().touched()?;
+        let mut pads = [false; 8];
+        for (pad, touched) in pads.iter_mut().enumerate() {
+            *touched = status & (1 << pad) != 0;
+        }
+        Ok(pads)
+    }
+
+    /// Returns ``True`` if the pad is touched, ``False`` if it is not or cannot be read.
+    /// * `pad` - The pad, from 0 to 7
+    pub fn is_touched(&self, pad: u8) -> bool {
+        pad < 8 && self.touched().is_ok_and(|pads| pads[usize::from(pad)])
+    }
+
+    /// Set the function called from a background thread when a pad is touched.
+    /// * `pad` - The pad, from 0 to 7
+    pub fn when_touched<C>(&mut self, pad: u8, callback: C)
+    where
+        C: FnMut() + Send + 'static,
+    {
+        if pad > 7 {
+            println!("Pad must be between 0 and 7");
+            return;
+        }
+        self.callbacks.lock().unwrap().touched[usize::from(pad)] = Some(Box::new(callback));
+        self.listen();
+    }
+
+    /// Set the function called from a background thread when a pad is released.
+    /// * `pad` - The pad, from 0 to 7
+    pub fn when_released<C>(&mut self, pad: u8, callback: C)
+    where
+        C: FnMut() + Send + 'static,
+    {
+        if pad > 7 {
+            println!("Pad must be between 0 and 7");
+            return;
+        }
+        self.callbacks.lock().unwrap().released[usize::from(pad)] = Some(Box::new(callback));
+        self.listen();
+    }
+
+    /// Remove the functions set with `when_touched` and `when_released`.
+    pub fn clear_callbacks(&mut self) {
+        *self.callbacks.lock().unwrap() = TouchCallbacks::default();
+    }
+
+    fn listen(&mut self) {
+        if self.handle.is_some() {
+            return;
+        }
+        self.running.store(true, Ordering::SeqCst);
+        let bus = Arc::clone(&self.bus);
+        let callbacks = Arc::clone(&self.callbacks);
+        let running = Arc::clone(&self.running);
+        self.handle = Some(thread::spawn(move || {
+            let mut last = 0u8;
+            while running.load(Ordering::SeqCst) {
+                let read = bus.lock().unwrap().touched();
+                if let Ok(status) = read {
+                    let mut callbacks = callbacks.lock().unwrap();
+                    for pad in 0..8 {
+                        let bit = 1 << pad;
+                        let callback = if status & bit != 0 && last & bit == 0 {
+                            &mut callbacks.touched[pad]
+                        } else if status & bit == 0 && last & bit != 0 {
+                            &mut callbacks.released[pad]
+                        } else {
+                            continue;
+                        };
+                        if let Some(callback) = callback {
+                            callback()
+                        }
+                    }
+                    last = status;
+                }
+                sleep_while_running(&running, CAP_POLL_INTERVAL);
+            }
+        }));
+    }
+
+    /// The error counters of the I2C transfers.
+    pub fn bus_stats(&self) -> BusStats {
+        self.bus.lock().unwrap().link.stats()
+    }
+
+    /// Change how failed I2C transfers are retried.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.bus.lock().unwrap().link.set_policy(policy)
+    }
+}
+
+impl Default for CAP1208 {
+    fn default() -> Self {
+        CAP1208::new()
+    }
+}
+
+impl Drop for CAP1208 {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            handle.join().expect("Could not join spawned thread");
+        }
+    }
+}
+
+/// The address of an MPU6050 with its AD0 pin connected to ground.
+const MPU_ADDRESS: u16 = 0x68;
+
+const MPU_CONFIG: u8 = 0x1a;
+const MPU_GYRO_CONFIG: u8 = 0x1b;
+const MPU_ACCEL_CONFIG: u8 = 0x1c;
+const MPU_MOTION_THRESHOLD: u8 = 0x1f;
+const MPU_MOTION_DURATION: u8 = 0x20;
+const MPU_INT_PIN_CONFIG: u8 = 0x37;
+const MPU_INT_ENABLE: u8 = 0x38;
+const MPU_INT_STATUS: u8 = 0x3a;
+const MPU_ACCEL_OUT: u8 = 0x3b;
+const MPU_TEMP_OUT: u8 = 0x41;
+const MPU_GYRO_OUT: u8 = 0x43;
+const MPU_POWER: u8 = 0x6b;
+
+/// The interrupt bit of motion detection, in the enable and status registers.
+const MPU_MOTION: u8 = 0x40;
+
+/// The full-scale range of the accelerometer of an MPU6050.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MPUAccelRange {
+    /// ±2g, the default
+    G2,
+    /// ±4g
+    G4,
+    /// ±8g
+    G8,
+    /// ±16g
+    G16,
+}
+
+impl MPUAccelRange {
+    /// The acceleration that reads as full scale, in g.
+    pub fn full_scale(self) -> f64 {
+        match self {
+            MPUAccelRange::G2 => 2.0,
+            MPUAccelRange::G4 => 4.0,
+            MPUAccelRange::G8 => 8.0,
+            MPUAccelRange::G16 => 16.0,
+        }
+    }
+
+    fn bits(self) -> u8 {
+        match self {
+            MPUAccelRange::G2 => 0,
+            MPUAccelRange::G4 => 1,
+            MPUAccelRange::G8 => 2,
+            MPUAccelRange::G16 => 3,
+        }
+    }
+}
+
+/// The full-scale range of the gyroscope of an MPU6050.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MPUGyroRange {
+    /// ±250°/s, the default
+    Dps250,
+    /// ±500°/s
+    Dps500,
+    /// ±1000°/s
+    Dps1000,
+    /// ±2000°/s
+    Dps2000,
+}
+
+impl MPUGyroRange {
+    /// The rate of rotation that reads as full scale, in degrees per second.
+    pub fn full_scale(self) -> f64 {
+        match self {
+            MPUGyroRange::Dps250 => 250.0,
+            MPUGyroRange::Dps500 => 500.0,
+            MPUGyroRange::Dps1000 => 1000.0,
+            MPUGyroRange::Dps2000 => 2000.0,
+        }
+    }
+
+    fn bits(self) -> u8 {
+        match self {
+            MPUGyroRange::Dps250 => 0,
+            MPUGyroRange::Dps500 => 1,
+            MPUGyroRange::Dps1000 => 2,
+            MPUGyroRange::Dps2000 => 3,
+        }
+    }
+}
+
+/// The bandwidth of the digital low pass filter of an MPU6050, applied to both the
+/// accelerometer and the gyroscope. Narrower filters smooth out vibration at the cost of
+/// a delay of up to 19ms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MPUFilter {
+    /// 260Hz, no filtering, the default
+    Hz260,
+    /// 184Hz
+    Hz184,
+    /// 94Hz
+    Hz94,
+    /// 44Hz
+    Hz44,
+    /// 21Hz
+    Hz21,
+    /// 10Hz
+    Hz10,
+    /// 5Hz
+    Hz5,
+}
+
+impl MPUFilter {
+    const ALL: [MPUFilter; 7] = [
+        MPUFilter::Hz260,
+        MPUFilter::Hz184,
+        MPUFilter::Hz94,
+        MPUFilter::Hz44,
+        MPUFilter::Hz21,
+        MPUFilter::Hz10,
+        MPUFilter::Hz5,
+    ];
+
+    fn bits(self) -> u8 {
+        MPUFilter::ALL
+            .iter()
+            .position(|&filter| filter == self)
+            .unwrap_or(0) as u8
+    }
+}
+
+/// The I2C device of an MPU6050 and its settings.
+struct MPUBus {
+    i2c: SharedI2c,
+    link: BusLink,
+    accel_range: MPUAccelRange,
+    gyro_range: MPUGyroRange,
+    filter: MPUFilter,
+}
+
+impl MPUBus {
+    fn write(&mut self, register: u8, value: u8) -> Result<(), rppal::i2c::Error> {
+        let MPUBus { i2c, link, .. } = self;
+        link.run(|| i2c.lock()?.smbus_write_byte(register, value))
+    }
+
+    fn read(&mut self, register: u8) -> Result<u8, rppal::i2c::Error> {
+        let MPUBus { i2c, link, .. } = self;
+        link.run(|| i2c.lock()?.smbus_read_byte(register))
+    }
+
+    /// Read three consecutive big endian axes.
+    fn axes(&mut self, register: u8, full_scale: f64) -> Result<Axes, rppal::i2c::Error> {
+        let MPUBus { i2c, link, .. } = self;
+        let mut bytes = [0u8; 6];
+        link.run(|| i2c.lock()?.write_read(&[register], &mut bytes))?;
+        let axis = |at: usize| {
+            f64::from(i16::from_be_bytes([bytes[at], bytes[at + 1]])) * full_scale / 32768.0
+        };
+        Ok(Axes {
+            x: axis(0),
+            y: axis(2),
+            z: axis(4),
+        })
+    }
+}
+
+type MotionCallback = Box<dyn FnMut() + Send>;
+
+/// Represents an MPU6050 accelerometer and gyroscope on the I2C bus.
+///
+/// Motion detection runs on the chip: it raises its INT pin when the acceleration changes
+/// by more than a threshold, so `when_motion` needs the INT pin connected to a GPIO pin
+/// given to `set_interrupt_pin`, and costs no I2C traffic while nothing moves.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut imu = MPU6050::new();
+/// imu.set_filter(MPUFilter::Hz44);
+/// imu.set_interrupt_pin(17);
+/// imu.when_motion(0.1, || println!("moved"));
+/// let a = imu.acceleration().unwrap();
+/// println!("{:.2}g {:.2}g {:.2}g", a.x, a.y, a.z);
+/// ```
+pub struct MPU6050 {
+    bus: Arc<Mutex<MPUBus>>,
+    callback: Arc<Mutex<Option<MotionCallback>>>,
+    interrupt: Option<InputPin>,
+}
+
+impl MPU6050 {
+    /// Returns an MPU6050 on I2C bus 1 at address 0x68, with its AD0 pin connected to ground
+    pub fn new() -> MPU6050 {
+        MPU6050::with_address(1, MPU_ADDRESS)
+    }
+
+    /// Returns an MPU6050 on the bus and at the address given, woken up with the default
+    /// ranges and no filtering
+    /// * `bus` - The I2C bus number, 1 on most Raspberry Pi models
+    /// * `address` - 0x68, or 0x69 with the AD0 pin connected to 3V3
+    pub fn with_address(bus: u8, address: u16) -> MPU6050 {
+        let i2c = match SharedI2c::open(bus, address) {
+            Err(e) => panic!("{:?}", e),
+            Ok(i2c) => i2c,
+        };
+        let mut bus = MPUBus {
+            i2c,
+            link: BusLink::default(),
+            accel_range: MPUAccelRange::G2,
+            gyro_range: MPUGyroRange::Dps250,
+            filter: MPUFilter::Hz260,
+        };
+        // wake up clocked by the x gyroscope, steadier than the internal oscillator, with
+        // a latched interrupt that reading the status clears
+        let configured = bus
+            .write(MPU_POWER, 0x01)
+            .and_then(|_| bus.write(MPU_CONFIG, 0x00))
+            .and_then(|_| bus.write(MPU_GYRO_CONFIG, 0x00))
+            .and_then(|_| bus.write(MPU_ACCEL_CONFIG, 0x00))
+            .and_then(|_| bus.write(MPU_INT_PIN_CONFIG, 0x20))
+            .and_then(|_| bus.write(MPU_INT_ENABLE, 0x00));
+        if let Err(e) = configured {
+            panic!("{:?}", e)
+        }
+        MPU6050 {
+            bus: Arc::new(Mutex::new(bus)),
+            callback: Arc::default(),
+            interrupt: None,
+        }
+    }
+
+    /// The acceleration in g, including gravity.
+    pub fn acceleration(&self) -> Result<Axes, rppal::i2c::Error> {
+        let mut bus = self.bus.lock().unwrap();
+        let full_scale = bus.accel_range.full_scale();
+        bus.axes(MPU_ACCEL_OUT, full_scale)
+    }
+
+    /// The rate of rotation in degrees per second.
+    pub fn gyro(&self) -> Result<Axes, rppal::i2c::Error> {
+        let mut bus = self.bus.lock().unwrap();
+        let full_scale = bus.gyro_range.full_scale();
+        bus.axes(MPU_GYRO_OUT, full_scale)
+    }
+
+    /// The temperature of the chip in degrees Celsius.
+    pub fn temperature(&self) -> Result<f64, rppal::i2c::Error> {
+        let MPUBus { i2c, link, .. } = &mut *self.bus.lock().unwrap();
+        let mut bytes = [0u8; 2];
+        link.run(|| i2c.lock()?.write_read(&[MPU_TEMP_OUT], &mut bytes))?;
+        Ok(f64::from(i16::from_be_bytes(bytes)) / 340.0 + 36.53)
+    }
+
+    /// The full-scale range of the accelerometer.
+    pub fn accel_range(&self) -> MPUAccelRange {
+        self.bus.lock().unwrap().accel_range
+    }
+
+    /// Set the full-scale range of the accelerometer. Choose the smallest range the
+    /// movement fits in for the best resolution.
+    pub fn set_accel_range(&mut self, range: MPUAccelRange) {
+        let mut bus = self.bus.lock().unwrap();
+        // keep the high pass filter of motion detection
+        let config = bus.read(MPU_ACCEL_CONFIG).map(|config| config & 0x07);
+        match config.and_then(|config| bus.write(MPU_ACCEL_CONFIG, range.bits() << 3 | config)) {
+            Ok(()) => bus.accel_range = range,
+            Err(_) => println!("Could not set the accelerometer range"),
+        }
+    }
+
+    /// The full-scale range of the gyroscope.
+    pub fn gyro_range(&self) -> MPUGyroRange {
+        self.bus.lock().unwrap().gyro_range
+    }
+
+    /// Set the full-scale range of the gyroscope.
+    pub fn set_gyro_range(&mut self, range: MPUGyroRange) {
+        let mut bus = self.bus.lock().unwrap();
+        match bus.write(MPU_GYRO_CONFIG, range.bits() << 3) {
+            Ok(()) => bus.gyro_range = range,
+            Err(_) => println!("Could not set the gyroscope range"),
+        }
+    }
+
+    /// The bandwidth of the low pass filter.
+    pub fn filter(&self) -> MPUFilter {
+        self.bus.lock().unwrap().filter
+    }
+
+    /// Set the bandwidth of the low pass filter of both sensors.
+    pub fn set_filter(&mut self, filter: MPUFilter) {
+        let mut bus = self.bus.lock().unwrap();
+        match bus.write(MPU_CONFIG, filter.bits()) {
+            Ok(()) => bus.filter = filter,
+            Err(_) => println!("Could not set the filter"),
+        }
+    }
+
+    /// Read motion interrupts from the INT pin of the chip. The pin is active high, so
+    /// the GPIO pin is pulled down.
+    /// * `pin` - The GPIO pin the INT pin of the chip is connected to
+    pub fn set_interrupt_pin(&mut self, pin: u8) {
+        let mut interrupt = match Gpio::new() {
+            Err(e) => panic!("{:?}", e),
+            Ok(gpio) => match gpio.get(pin) {
+                Err(e) => panic!("{:?}", e),
+                Ok(pin) => pin.into_input_pulldown(),
+            },
+        };
+        let bus = Arc::downgrade(&self.bus);
+        let callback = Arc::clone(&self.callback);
+        let armed = interrupt.set_async_interrupt(Trigger::RisingEdge, move |_| {
+            let status = match bus.upgrade() {
+                Some(bus) => bus.lock().unwrap().read(MPU_INT_STATUS),
+                None => return,
+            };
+            if status.is_ok_and(|status| status & MPU_MOTION != 0) {
+                if let Some(callback) = &mut *callback.lock().unwrap() {
+                    callback()
+                }
+            }
+        });
+        if let Err(e) = armed {
+            panic!("{:?}", e)
+        }
+        self.interrupt = Some(interrupt);
+        // reading the status clears an interrupt latched before the pin was armed
+        let _ = self.bus.lock().unwrap().read(MPU_INT_STATUS);
+    }
+
+    /// Set the function called from a background thread when the chip detects motion.
+    /// Needs `set_interrupt_pin`.
+    /// * `threshold` - The change of acceleration that counts as motion, from 0.002g to 0.51g
+    pub fn when_motion<C>(&mut self, threshold: f64, callback: C)
+    where
+        C: FnMut() + Send + 'static,
+    {
+        if !(0.002..=0.51).contains(&threshold) {
+            println!("Threshold must be between 0.002 and 0.51");
+            return;
+        }
+        let mut bus = self.bus.lock().unwrap();
+        // the threshold counts in 2mg steps, against the acceleration through a 5Hz high
+        // pass filter, for at least 1ms
+        let hpf = bus
+            .read(MPU_ACCEL_CONFIG)
+            .map(|config| config & 0x18 | 0x01);
+        let configured = hpf
+            .and_then(|config| bus.write(MPU_ACCEL_CONFIG, config))
+            .and_then(|_| bus.write(MPU_MOTION_THRESHOLD, (threshold / 0.002).round() as u8))
+            .and_then(|_| bus.write(MPU_MOTION_DURATION, 1))
+            .and_then(|_| bus.write(MPU_INT_ENABLE, MPU_MOTION));
+        drop(bus);
+        if configured.is_err() {
+            println!("Could not enable motion detection");
+            return;
+        }
+        *self.callback.lock().unwrap() = Some(Box::new(callback));
+    }
+
+    /// Remove the function set with `when_motion` and turn motion detection off.
+    pub fn clear_callbacks(&mut self) {
+        *self.callback.lock().unwrap() = None;
+        if self
+            .bus
+            .lock()
+            .unwrap()
+            .write(MPU_INT_ENABLE, 0x00)
+            .is_err()
+        {
+            println!("Could not disable motion detection");
+        }
+    }
+
+    /// The error counters of the I2C transfers.
+    pub fn bus_stats(&self) -> BusStats {
+        self.bus.lock().unwrap().link.stats()
+    }
+
+    /// Change how failed I2C transfers are retried.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.bus.lock().unwrap().link.set_policy(policy)
+    }
+}
+
+impl Default for MPU6050 {
+    fn default() -> Self {
+        MPU6050::new()
+    }
+}
+
+impl Drop for MPU6050 {
+    fn drop(&mut self) {
+        if let Some(mut interrupt) = self.interrupt.take() {
+            let _ = interrupt.clear_async_interrupt();
+        }
+        // put the chip to sleep, where it draws a few µA
+        let _ = self.bus.lock().unwrap().write(MPU_POWER, 0x40);
+    }
+}
+
+/// The address of a BME280 with its SDO pin connected to ground.
+const BME_ADDRESS: u16 = 0x76;
+
+const BME_CALIBRATION: u8 = 0x88;
+const BME_CHIP_ID: u8 = 0xd0;
+const BME_RESET: u8 = 0xe0;
+const BME_HUMIDITY_CALIBRATION: u8 = 0xe1;
+const BME_CTRL_HUM: u8 = 0xf2;
+const BME_STATUS: u8 = 0xf3;
+const BME_CTRL_MEAS: u8 = 0xf4;
+const BME_CONFIG: u8 = 0xf5;
+const BME_DATA: u8 = 0xf7;
+
+/// The chip ID of a BME280; a BMP280 reads 0x58 and has no humidity sensor.
+const BME280_ID: u8 = 0x60;
+
+/// The status bit set while a measurement runs.
+const BME_MEASURING: u8 = 0x08;
+
+/// The standby times between measurements in normal mode, in the order of their codes;
+/// the BMP280 has 2s and 4s where the BME280 has 10ms and 20ms.
+const BME_STANDBY: [f64; 8] = [0.5, 62.5, 125.0, 250.0, 500.0, 1000.0, 10.0, 20.0];
+const BMP_STANDBY: [f64; 8] = [0.5, 62.5, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0];
+
+/// How many samples a BME280 averages for each reading of a sensor. More samples read
+/// with less noise, but take longer and draw more power.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BMEOversampling {
+    /// The sensor is not read
+    Skip,
+    /// 1 sample, the default
+    X1,
+    /// 2 samples
+    X2,
+    /// 4 samples
+    X4,
+    /// 8 samples
+    X8,
+    /// 16 samples
+    X16,
+}
+
+impl BMEOversampling {
+    /// The number of samples.
+    pub fn samples(self) -> u8 {
+        match self {
+            BMEOversampling::Skip => 0,
+            BMEOversampling::X1 => 1,
+            BMEOversampling::X2 => 2,
+            BMEOversampling::X4 => 4,
+            BMEOversampling::X8 => 8,
+            BMEOversampling::X16 => 16,
+        }
+    }
+
+    fn bits(self) -> u8 {
+        match self {
+            BMEOversampling::Skip => 0,
+            BMEOversampling::X1 => 1,
+            BMEOversampling::X2 => 2,
+            BMEOversampling::X4 => 3,
+            BMEOversampling::X8 => 4,
+            BMEOversampling::X16 => 5,
+        }
+    }
+}
+
+/// When a BME280 measures.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BMEMode {
+    /// Measure once for every reading and sleep in between, the default. Suits readings
+    /// a minute or more apart, as in a weather station.
+    Forced,
+    /// Measure continuously, waiting the standby time between measurements, so readings
+    /// return at once. The standby time is rounded down to one the chip supports.
+    Normal(Duration),
+}
+
+/// A reading of a BME280.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BMEReading {
+    /// The temperature in degrees Celsius
+    pub temperature: f64,
+    /// The air pressure in millibars (hPa)
+    pub pressure: f64,
+    /// The relative humidity in percent, None on a BMP280
+    pub humidity: Option<f64>,
+}
+
+/// The calibration of a BME280, read from the chip once.
+#[derive(Debug, Clone, Copy, Default)]
+struct BMECalibration {
+    t: [f64; 3],
+    p: [f64; 9],
+    h: [f64; 6],
+}
+
+impl BMECalibration {
+    fn parse(table: &[u8; 26], humidity: &[u8; 7]) -> BMECalibration {
+        let unsigned = |at: usize| f64::from(u16::from_le_bytes([table[at], table[at + 1]]));
+        let signed = |at: usize| f64::from(i16::from_le_bytes([table[at], table[at + 1]]));
+        let mut calibration = BMECalibration {
+            t: [unsigned(0), signed(2), signed(4)],
+            ..BMECalibration::default()
+        };
+        calibration.p[0] = unsigned(6);
+        for i in 1..9 {
+            calibration.p[i] = signed(6 + 2 * i);
+        }
+        // H4 and H5 are 12 bit signed values sharing the middle byte
+        let h4 = i16::from(humidity[3] as i8) << 4 | i16::from(humidity[4] & 0x0f);
+        let h5 = i16::from(humidity[5] as i8) << 4 | i16::from(humidity[4] >> 4);
+        calibration.h = [
+            f64::from(table[25]),
+            f64::from(i16::from_le_bytes([humidity[0], humidity[1]])),
+            f64::from(humidity[2]),
+            f64::from(h4),
+            f64::from(h5),
+            f64::from(humidity[6] as i8),
+        ];
+        calibration
+    }
+
+    /// The compensated reading of raw measurements, with the formulas of the datasheet.
+    fn compensate(&self, raw: &[u8; 8], humidity: bool) -> BMEReading {
+        let adc_p =
+            f64::from(u32::from(raw[0]) << 12 | u32::from(raw[1]) << 4 | u32::from(raw[2]) >> 4);
+        let adc_t =
+            f64::from(u32::from(raw[3]) << 12 | u32::from(raw[4]) << 4 | u32::from(raw[5]) >> 4);
+        let adc_h = f64::from(u16::from_be_bytes([raw[6], raw[7]]));
+        let [t1, t2, t3] = self.t;
+        let [p1, p2, p3, p4, p5, p6, p7, p8, p9] = self.p;
+        let [h1, h2, h3, h4, h5, h6] = self.h;
+
+        let var1 = (adc_t / 16384.0 - t1 / 1024.0) * t2;
+        let var2 = (adc_t / 131072.0 - t1 / 8192.0).powi(2) * t3;
+        let t_fine = var1 + var2;
+
+        let var1 = t_fine / 2.0 - 64000.0;
+        let var2 = var1 * var1 * p6 / 32768.0 + var1 * p5 * 2.0;
+        let var2 = var2 / 4.0 + p4 * 65536.0;
+        let var1 = (p3 * var1 * var1 / 524288.0 + p2 * var1) / 524288.0;
+        let var1 = (1.0 + var1 / 32768.0) * p1;
+        let pressure = if var1 == 0.0 {
+            0.0
+        } else {
+            let p = (1048576.0 - adc_p - var2 / 4096.0) * 6250.0 / var1;
+            p + (p9 * p * p / 2147483648.0 + p * p8 / 32768.0 + p7) / 16.0
+        };
+
+        let h = t_fine - 76800.0;
+        let h = (adc_h - (h4 * 64.0 + h5 / 16384.0 * h))
+            * (h2 / 65536.0 * (1.0 + h6 / 67108864.0 * h * (1.0 + h3 / 67108864.0 * h)));
+        let h = h * (1.0 - h1 * h / 524288.0);
+
+        BMEReading {
+            temperature: t_fine / 5120.0,
+            pressure: pressure / 100.0,
+            humidity: if humidity {
+                Some(h.clamp(0.0, 100.0))
+            } else {
+                None
+            },
+        }
+    }
+}
+
+/// The bus a BME280 is wired to.
+enum BMETransport {
+    I2c(SharedI2c),
+    Spi(SharedSpi),
+}
+
+/// The bus of a BME280 and its settings.
+struct BMEBus {
+    transport: BMETransport,
+    link: BusLink,
+    humidity: bool,
+    calibration: BMECalibration,
+    oversampling: [BMEOversampling; 3],
+    mode: BMEMode,
+}
+
+impl BMEBus {
+    fn read(
+        &mut self,
+        register: u8,
+        buffer: &mut [u8],
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let BMEBus {
+            transport, link, ..
+        } = self;
+        match transport {
+            BMETransport::I2c(i2c) => link.run(|| i2c.lock()?.write_read(&[register], buffer))?,
+            // the first byte clocked out is the address, with bit 7 set for a read
+            BMETransport::Spi(spi) => {
+                let mut write = vec![0u8; buffer.len() + 1];
+                write[0] = register | 0x80;
+                let mut read = vec![0u8; write.len()];
+                link.run(|| spi.lock().transfer(&mut read, &write))?;
+                buffer.copy_from_slice(&read[1..]);
+            }
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, register: u8, value: u8) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let BMEBus {
+            transport, link, ..
+        } = self;
+        match transport {
+            BMETransport::I2c(i2c) => link.run(|| i2c.lock()?.smbus_write_byte(register, value))?,
+            BMETransport::Spi(spi) => link
+                .run(|| spi.lock().write(&[register & 0x7f, value]))
+                .map(|_| ())?,
+        }
+        Ok(())
+    }
+
+    fn open(transport: BMETransport) -> Result<BMEBus, Box<dyn Error + Send + Sync>> {
+        let mut bus = BMEBus {
+            transport,
+            link: BusLink::default(),
+            humidity: false,
+            calibration: BMECalibration::default(),
+            oversampling: [BMEOversampling::X1; 3],
+            mode: BMEMode::Forced,
+        };
+        let mut id = [0u8];
+        bus.read(BME_CHIP_ID, &mut id)?;
+        bus.humidity = match id[0] {
+            BME280_ID => true,
+            0x56..=0x58 => false,
+            id => return Err(format!("chip ID 0x{:02x} is not a BME280 or BMP280", id).into()),
+        };
+        bus.write(BME_RESET, 0xb6)?;
+        thread::sleep(Duration::from_millis(3));
+        let mut table = [0u8; 26];
+        bus.read(BME_CALIBRATION, &mut table)?;
+        let mut humidity = [0u8; 7];
+        if bus.humidity {
+            bus.read(BME_HUMIDITY_CALIBRATION, &mut humidity)?;
+        }
+        bus.calibration = BMECalibration::parse(&table, &humidity);
+        bus.configure()?;
+        Ok(bus)
+    }
+
+    /// Write the oversampling and mode, starting a measurement in forced mode.
+    fn configure(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let [temperature, pressure, humidity] = self.oversampling;
+        let mode = match self.mode {
+            BMEMode::Forced => 0b01,
+            BMEMode::Normal(standby) => {
+                let times = if self.humidity {
+                    &BME_STANDBY
+                } else {
+                    &BMP_STANDBY
+                };
+                let ms = standby.as_secs_f64() * 1000.0;
+                let code = (0..8)
+                    .filter(|&code| times[code] <= ms)
+                    .max_by(|&a, &b| times[a].total_cmp(&times[b]))
+                    .unwrap_or(0);
+                self.write(BME_CONFIG, (code as u8) << 5)?;
+                0b11
+            }
+        };
+        if self.humidity {
+            // changes of the humidity oversampling only apply after a write of ctrl_meas
+            self.write(BME_CTRL_HUM, humidity.bits())?;
+        }
+        self.write(
+            BME_CTRL_MEAS,
+            temperature.bits() << 5 | pressure.bits() << 2 | mode,
+        )
+    }
+
+    fn measure(&mut self) -> Result<BMEReading, Box<dyn Error + Send + Sync>> {
+        if self.mode == BMEMode::Forced {
+            self.configure()?;
+            // the longest a measurement takes with the oversampling set, from the datasheet
+            let samples: f64 = self
+                .oversampling
+                .iter()
+                .map(|oversampling| 2.3 * f64::from(oversampling.samples()))
+                .sum();
+            thread::sleep(Duration::from_secs_f64((1.25 + samples + 1.15) / 1000.0));
+            let deadline = Instant::now() + Duration::from_millis(100);
+            let mut status = [0u8];
+            loop {
+                self.read(BME_STATUS, &mut status)?;
+                if status[0] & BME_MEASURING == 0 {
+                    break;
+                }
+                if Instant::now() >= deadline {
+                    return Err("the BME280 did not finish measuring".into());
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+        let mut raw = [0u8; 8];
+        let length = if self.humidity { 8 } else { 6 };
+        self.read(BME_DATA, &mut raw[..length])?;
+        Ok(self.calibration.compensate(&raw, self.humidity))
+    }
+}
+
+type ReadingCallback = Box<dyn FnMut(BMEReading) + Send>;
+
+/// Represents a Bosch BME280 temperature, pressure and humidity sensor, on the I2C or SPI
+/// bus. A BMP280, which has no humidity sensor, works the same way with `humidity` in
+/// each reading left empty.
+///
+/// Readings of either bus fail with the same boxed error, so code reading the sensor does
+/// not depend on how it is wired. The sensor warms itself a little when measuring often,
+/// so forced mode with readings far apart reads the air temperature best.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut sensor = BME280::new();
+/// let reading = sensor.read().unwrap();
+/// println!("{:.1}C {:.1}hPa", reading.temperature, reading.pressure);
+/// sensor.when_reading(60.0, |reading| println!("{:?}", reading.humidity));
+/// ```
+pub struct BME280 {
+    bus: Arc<Mutex<BMEBus>>,
+    callback: Arc<Mutex<Option<ReadingCallback>>>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+/// A BMP280 is read the same way as a BME280, without humidity.
+pub type BMP280 = BME280;
+
+impl BME280 {
+    /// Returns a BME280 on I2C bus 1 at address 0x76, with its SDO pin connected to ground
+    pub fn new() -> BME280 {
+        BME280::with_address(1, BME_ADDRESS)
+    }
+
+    /// Returns a BME280 on the I2C bus and at the address given, in forced mode with one
+    /// sample per reading
+    /// * `bus` - The I2C bus number, 1 on most Raspberry Pi models
+    /// * `address` - 0x76, or 0x77 with the SDO pin connected to 3V3
+    pub fn with_address(bus: u8, address: u16) -> BME280 {
+        let i2c = match SharedI2c::open(bus, address) {
+            Err(e) => panic!("{:?}", e),
+            Ok(i2c) => i2c,
+        };
+        BME280::open(BMETransport::I2c(i2c))
+    }
+
+    /// Returns a BME280 on the SPI bus given, in forced mode with one sample per reading
+    /// * `bus` - The SPI bus, `Bus::Spi0` on most boards
+    /// * `slave_select` - The chip select the CSB pin of the sensor is connected to
+    pub fn with_spi(bus: Bus, slave_select: SlaveSelect) -> BME280 {
+        match SharedSpi::open(bus, slave_select, 1_000_000, Mode::Mode0) {
+            Err(e) => panic!("{:?}", e),
+            Ok(spi) => BME280::open(BMETransport::Spi(spi)),
+        }
+    }
+
+    fn open(transport: BMETransport) -> BME280 {
+        match BMEBus::open(transport) {
+            Err(e) => panic!("{:?}", e),
+            Ok(bus) => BME280 {
+                bus: Arc::new(Mutex::new(bus)),
+                callback: Arc::default(),
+                running: Arc::new(AtomicBool::new(false)),
+                handle: None,
+            },
+        }
+    }
+
+    /// Returns ``True`` for a BME280, ``False`` for a BMP280 without humidity.
+    pub fn has_humidity(&self) -> bool {
+        self.bus.lock().unwrap().humidity
+    }
+
+    /// Read the temperature, pressure and humidity, measured together. In forced mode
+    /// this runs a measurement, taking up to 10ms with one sample per reading.
+    pub fn read(&self) -> Result<BMEReading, Box<dyn Error + Send + Sync>> {
+        self.bus.lock().unwrap().measure()
+    }
+
+    /// The temperature in degrees Celsius.
+    pub fn temperature(&self) -> Result<f64, Box<dyn Error + Send + Sync>> {
+        Ok(self.read()?.temperature)
+    }
+
+    /// The air pressure in millibars (hPa).
+    pub fn pressure(&self) -> Result<f64, Box<dyn Error + Send + Sync>> {
+        Ok(self.read()?.pressure)
+    }
+
+    /// The relative humidity in percent, or an error on a BMP280.
+    pub fn humidity(&self) -> Result<f64, Box<dyn Error + Send + Sync>> {
+        self.read()?
+            .humidity
+            .ok_or_else(|| "a BMP280 has no humidity sensor".into())
+    }
+
+    /// When the sensor measures.
+    pub fn mode(&self) -> BMEMode {
+        self.bus.lock().unwrap().mode
+    }
+
+    /// Set when the sensor measures.
+    pub fn set_mode(&mut self, mode: BMEMode) {
+        let mut bus = self.bus.lock().unwrap();
+        let previous = bus.mode;
+        bus.mode = mode;
+        if bus.configure().is_err() {
+            bus.mode = previous;
+            println!("Could not set the mode");
+        }
+    }
+
+    /// The oversampling of the temperature, pressure and humidity.
+    pub fn oversampling(&self) -> [BMEOversampling; 3] {
+        self.bus.lock().unwrap().oversampling
+    }
+
+    /// Set how many samples are averaged for each reading of each sensor. Temperature
+    /// cannot be skipped, as the other readings are compensated with it.
+    /// * `temperature` - The oversampling of the temperature
+    /// * `pressure` - The oversampling of the pressure
+    /// * `humidity` - The oversampling of the humidity, ignored by a BMP280
+    pub fn set_oversampling(
+        &mut self,
+        temperature: BMEOversampling,
+        pressure: BMEOversampling,
+        humidity: BMEOversampling,
+    ) {
+        if temperature == BMEOversampling::Skip {
+            println!("Temperature cannot be skipped");
+            return;
+        }
+        let mut bus = self.bus.lock().unwrap();
+        let previous = bus.oversampling;
+        bus.oversampling = [temperature, pressure, humidity];
+        if bus.configure().is_err() {
+            bus.oversampling = previous;
+            println!("Could not set the oversampling");
+        }
+    }
+
+    /// Set the function called from a background thread with a new reading at a regular
+    /// interval. Readings that fail are skipped.
+    /// * `interval` - Number of seconds between readings
+    pub fn when_reading<C>(&mut self, interval: f64, callback: C)
+    where
+        C: FnMut(BMEReading) + Send + 'static,
+    {
+        if !(interval > 0.0 && interval.is_finite()) {
+            println!("Interval must be greater than 0");
+            return;
+        }
+        self.clear_callbacks();
+        *self.callback.lock().unwrap() = Some(Box::new(callback));
+        self.running.store(true, Ordering::SeqCst);
+        let bus = Arc::clone(&self.bus);
+        let callback = Arc::clone(&self.callback);
+        let running = Arc::clone(&self.running);
+        let interval = Duration::from_secs_f64(interval);
+        self.handle = Some(thread::spawn(move || {
+            let mut next = Instant::now();
+            while running.load(Ordering::SeqCst) {
+                let reading = bus.lock().unwrap().measure();
+                if let Ok(reading) = reading {
+                    if let Some(callback) = &mut *callback.lock().unwrap() {
+                        callback(reading)
+                    }
+                }
+                next += interval;
+                let now = Instant::now();
+                if next < now {
+                    next = now;
+                }
+                sleep_while_running(&running, next - now);
+            }
+        }));
+    }
+
+    /// Stop the readings started by `when_reading`.
+    pub fn clear_callbacks(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            handle.join().expect("Could not join spawned thread");
+        }
+        *self.callback.lock().unwrap() = None;
+    }
+
+    /// The error counters of the bus transfers.
+    pub fn bus_stats(&self) -> BusStats {
+        self.bus.lock().unwrap().link.stats()
+    }
+
+    /// Change how failed bus transfers are retried.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.bus.lock().unwrap().link.set_policy(policy)
+    }
+}
+
+impl Default for BME280 {
+    fn default() -> Self {
+        BME280::new()
+    }
+}
+
+impl Drop for BME280 {
+    fn drop(&mut self) {
+        self.clear_callbacks();
+        // put the sensor to sleep, where it draws 0.1µA
+        let _ = self.bus.lock().unwrap().write(BME_CTRL_MEAS, 0x00);
+    }
+}
@@ -1,229 +1,4437 @@
 //! Input device component interfaces for devices such as `Button`
-use rppal::gpio::{Gpio, InputPin, Level, Trigger};
-use std::time::Duration;
+use crate::devices::AnalogInputDevice;
+use crate::expander::{PinDriver, PinError, PinFactory};
+use crate::output_devices::sleep_while_running;
+use crate::pin::{Input, Pin, Pull};
+use rppal::gpio::{Gpio, InputPin, IoPin, Level, Mode, PullUpDown, Trigger};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
-/// Represents a generic GPIO input device.
-#[derive(Debug)]
-pub struct InputDevice {
-    pin: InputPin,
-    active_state: bool,
-    inactive_state: bool,
+/// A change in the level of a GPIO pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    /// The pin went from low to high
+    Rising,
+    /// The pin went from high to low
+    Falling,
 }
 
-impl InputDevice {
-    /// Returns an InputDevice with the pin number given with the pin pulled to low by default
-    /// `is_active` property is adjusted accordingly so that
-    /// ``True`` still means active regardless of the :attr:`pull_up` setting
-    /// # Arguments
-    ///
-    /// * `pin` - The GPIO pin which the device is attached to
-    ///  
-    pub fn new(pin: u8) -> InputDevice {
-        match Gpio::new() {
-            Err(e) => panic!("{:?}", e),
-            Ok(gpio) => match gpio.get(pin) {
-                Err(e) => panic!("{:?}", e),
-                Ok(pin) => InputDevice {
-                    pin: pin.into_input_pulldown(),
-                    active_state: true,
-                    inactive_state: false,
-                },
-            },
+type Listener = Arc<Mutex<Box<dyn FnMut(Level) + Send>>>;
+
+/// The pin behind an `InputLine`: a GPIO pin of the Pi, or one handed out by a `PinFactory`
+/// with the trigger it was last given, as drivers report every edge.
+enum InputSource {
+    Gpio(InputPin),
+    Driver(Box<dyn PinDriver>, Trigger),
+}
+
+impl fmt::Debug for InputSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InputSource::Gpio(pin) => pin.fmt(f),
+            InputSource::Driver(pin, _) => f.debug_tuple("Driver").field(&pin.pin()).finish(),
         }
     }
-    /// Returns an InputDevice with the pin number given with the pin pulled high with an internal resistor by default
-    /// `is_active` property is adjusted accordingly so that
-    /// ``True`` still means active regardless of the :attr:`pull_up` setting
-    /// # Arguments
-    ///
-    /// * `pin` - The GPIO pin which the device is attached to
-    ///  
-    pub fn new_with_pullup(pin: u8) -> InputDevice {
-        match Gpio::new() {
+}
+
+/// Returns ``True`` if an edge to `level` matches `trigger`.
+fn triggers(trigger: Trigger, level: Level) -> bool {
+    matches!(
+        (trigger, level),
+        (Trigger::Both, _)
+            | (Trigger::RisingEdge, Level::High)
+            | (Trigger::FallingEdge, Level::Low)
+    )
+}
+
+/// The input pin of a device. All reads and interrupt polling go through here so that,
+/// with the `inject` feature enabled, synthetic levels and edges follow the same path as hardware ones.
+struct InputLine {
+    pin: InputSource,
+    listener: Option<(Trigger, Listener)>,
+    #[cfg(feature = "inject")]
+    trigger: Option<Trigger>,
+    #[cfg(feature = "inject")]
+    injected: Option<Level>,
+    #[cfg(feature = "inject")]
+    events: VecDeque<Edge>,
+}
+
+impl InputLine {
+    fn new(pin: InputPin) -> InputLine {
+        InputLine::with_source(InputSource::Gpio(pin))
+    }
+
+    /// Wrap a pin of a `PinFactory`, panicking if it could not be claimed.
+    fn with_driver(pin: Result<Box<dyn PinDriver>, PinError>) -> InputLine {
+        match pin {
             Err(e) => panic!("{:?}", e),
-            Ok(gpio) => match gpio.get(pin) {
-                Err(e) => panic!("{:?}", e),
-                Ok(pin) => InputDevice {
-                    pin: pin.into_input_pullup(),
-                    active_state: false,
-                    inactive_state: true,
-                },
-            },
+            Ok(pin) => InputLine::with_source(InputSource::Driver(pin, Trigger::Disabled)),
         }
     }
 
-    impl_device!();
-    impl_gpio_device!();
-    impl_io_device!();
-}
+    fn with_source(pin: InputSource) -> InputLine {
+        InputLine {
+            pin,
+            listener: None,
+            #[cfg(feature = "inject")]
+            trigger: None,
+            #[cfg(feature = "inject")]
+            injected: None,
+            #[cfg(feature = "inject")]
+            events: VecDeque::new(),
+        }
+    }
 
-macro_rules! impl_events_mixin {
-    () => {
-        /// Pause the program until the device is activated, or the timeout is reached.
-        fn wait_for(&mut self, timeout: Option<f32>, active: bool) {
-            match timeout {
-                None => {
-                    if active {
-                        self.pin.set_interrupt(Trigger::RisingEdge).unwrap();
-                        self.pin.poll_interrupt(true, None).unwrap();
-                    } else {
-                        self.pin.set_interrupt(Trigger::FallingEdge).unwrap();
-                        self.pin.poll_interrupt(true, None).unwrap();
+    fn pin(&self) -> u8 {
+        match &self.pin {
+            InputSource::Gpio(pin) => pin.pin(),
+            InputSource::Driver(pin, _) => pin.pin(),
+        }
+    }
+
+    fn level(&self) -> Level {
+        match &self.pin {
+            InputSource::Gpio(pin) => pin.read(),
+            InputSource::Driver(pin, _) => pin.read(),
+        }
+    }
+
+    #[cfg(not(feature = "inject"))]
+    fn read(&self) -> Level {
+        self.level()
+    }
+
+    #[cfg(feature = "inject")]
+    fn read(&self) -> Level {
+        self.injected.unwrap_or_else(|| self.level())
+    }
+
+    fn set_interrupt(&mut self, trigger: Trigger) -> rppal::gpio::Result<()> {
+        #[cfg(feature = "inject")]
+        {
+            self.trigger = Some(trigger);
+        }
+        match &mut self.pin {
+            InputSource::Gpio(pin) => pin.set_interrupt(trigger),
+            InputSource::Driver(_, interrupt) => {
+                *interrupt = trigger;
+                Ok(())
+            }
+        }
+    }
+
+    /// Waiting for an edge replaces the asynchronous interrupt of the pin, so the
+    /// listener is re-armed afterwards. Edges during the wait are not passed to it.
+    fn poll_interrupt(
+        &mut self,
+        reset: bool,
+        timeout: Option<Duration>,
+    ) -> rppal::gpio::Result<Option<Level>> {
+        let level = self.poll_pin(reset, timeout);
+        if let Some((trigger, listener)) = self.listener.clone() {
+            self.arm(trigger, listener)?;
+        }
+        level
+    }
+
+    #[cfg(not(feature = "inject"))]
+    fn poll_pin(
+        &mut self,
+        reset: bool,
+        timeout: Option<Duration>,
+    ) -> rppal::gpio::Result<Option<Level>> {
+        self.poll_source(reset, timeout)
+    }
+
+    /// Injected edges are delivered before waiting on the hardware.
+    #[cfg(feature = "inject")]
+    fn poll_pin(
+        &mut self,
+        reset: bool,
+        timeout: Option<Duration>,
+    ) -> rppal::gpio::Result<Option<Level>> {
+        while let Some(edge) = self.events.pop_front() {
+            let matches = matches!(
+                (self.trigger, edge),
+                (Some(Trigger::Both), _)
+                    | (Some(Trigger::RisingEdge), Edge::Rising)
+                    | (Some(Trigger::FallingEdge), Edge::Falling)
+            );
+            if matches {
+                return Ok(Some(match edge {
+                    Edge::Rising => Level::High,
+                    Edge::Falling => Level::Low,
+                }));
+            }
+        }
+        self.poll_source(reset, timeout)
+    }
+
+    /// Drivers report every edge, so those not matching the trigger are skipped.
+    fn poll_source(
+        &mut self,
+        reset: bool,
+        timeout: Option<Duration>,
+    ) -> rppal::gpio::Result<Option<Level>> {
+        match &mut self.pin {
+            InputSource::Gpio(pin) => pin.poll_interrupt(reset, timeout),
+            InputSource::Driver(pin, trigger) => {
+                let deadline = timeout.and_then(|t| Instant::now().checked_add(t));
+                loop {
+                    let remaining = match deadline {
+                        None => None,
+                        Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                            Some(remaining) => Some(remaining),
+                            None => return Ok(None),
+                        },
+                    };
+                    match pin.wait_for_edge(remaining)? {
+                        None => return Ok(None),
+                        Some(level) if triggers(*trigger, level) => return Ok(Some(level)),
+                        Some(_) => {}
                     }
                 }
-                Some(n) => {
-                    if active {
-                        self.pin.set_interrupt(Trigger::RisingEdge).unwrap();
-                        self.pin
-                            .poll_interrupt(true, Some(Duration::from_millis((n * 1000.0) as u64)))
-                            .unwrap();
-                    } else {
-                        self.pin.set_interrupt(Trigger::FallingEdge).unwrap();
-                        self.pin
-                            .poll_interrupt(true, Some(Duration::from_millis((n * 1000.0) as u64)))
-                            .unwrap();
+            }
+        }
+    }
+
+    /// Call `callback` from a background thread on every edge matching `trigger`.
+    fn set_async_interrupt<C>(&mut self, trigger: Trigger, callback: C) -> rppal::gpio::Result<()>
+    where
+        C: FnMut(Level) + Send + 'static,
+    {
+        let listener: Listener = Arc::new(Mutex::new(Box::new(callback)));
+        self.listener = Some((trigger, listener.clone()));
+        self.arm(trigger, listener)
+    }
+
+    fn arm(&mut self, trigger: Trigger, listener: Listener) -> rppal::gpio::Result<()> {
+        let callback = move |level| {
+            if let Ok(mut callback) = listener.lock() {
+                callback(level)
+            }
+        };
+        match &mut self.pin {
+            InputSource::Gpio(pin) => pin.set_async_interrupt(trigger, callback),
+            InputSource::Driver(pin, _) => pin
+                .set_listener(Some(Box::new(move |level| {
+                    if triggers(trigger, level) {
+                        callback(level)
                     }
+                })))
+                .map_err(rppal::gpio::Error::from),
+        }
+    }
+
+    fn clear_async_interrupt(&mut self) -> rppal::gpio::Result<()> {
+        self.listener = None;
+        match &mut self.pin {
+            InputSource::Gpio(pin) => pin.clear_async_interrupt(),
+            InputSource::Driver(pin, _) => pin.set_listener(None).map_err(rppal::gpio::Error::from),
+        }
+    }
+
+    fn reset_on_drop(&self) -> bool {
+        match &self.pin {
+            InputSource::Gpio(pin) => pin.reset_on_drop(),
+            InputSource::Driver(pin, _) => pin.reset_on_drop(),
+        }
+    }
+
+    fn set_reset_on_drop(&mut self, reset_on_drop: bool) {
+        match &mut self.pin {
+            InputSource::Gpio(pin) => pin.set_reset_on_drop(reset_on_drop),
+            InputSource::Driver(pin, _) => pin.set_reset_on_drop(reset_on_drop),
+        }
+    }
+
+    #[cfg(feature = "inject")]
+    fn inject_level(&mut self, level: Level) {
+        if self.read() != level {
+            self.inject_edge(match level {
+                Level::High => Edge::Rising,
+                Level::Low => Edge::Falling,
+            });
+        }
+    }
+
+    #[cfg(feature = "inject")]
+    fn inject_edge(&mut self, edge: Edge) {
+        self.injected = Some(match edge {
+            Edge::Rising => Level::High,
+            Edge::Falling => Level::Low,
+        });
+        self.events.push_back(edge);
+        if let Some((trigger, listener)) = &self.listener {
+            let matches = matches!(
+                (trigger, edge),
+                (Trigger::Both, _)
+                    | (Trigger::RisingEdge, Edge::Rising)
+                    | (Trigger::FallingEdge, Edge::Falling)
+            );
+            if matches {
+                if let Ok(mut callback) = listener.lock() {
+                    callback(self.read())
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "inject")]
+    fn clear_injection(&mut self) {
+        self.injected = None;
+        self.events.clear();
+    }
+}
+
+impl fmt::Debug for InputLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("InputLine")
+            .field("pin", &self.pin)
+            .field("listening", &self.listener.is_some())
+            .finish()
+    }
+}
+
+type Callback = Box<dyn FnMut() + Send>;
+
+/// The functions called when a device changes state.
+#[derive(Default)]
+struct Callbacks {
+    activated: Option<Callback>,
+    deactivated: Option<Callback>,
+}
+
+impl fmt::Debug for Callbacks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Callbacks")
+            .field("activated", &self.activated.is_some())
+            .field("deactivated", &self.deactivated.is_some())
+            .finish()
+    }
+}
+
+macro_rules! impl_callbacks {
+    () => {
+        /// Set the function called from a background thread when the device becomes active.
+        pub fn when_activated<C>(&mut self, callback: C)
+        where
+            C: FnMut() + Send + 'static,
+        {
+            self.callbacks.lock().unwrap().activated = Some(Box::new(callback));
+            self.listen();
+        }
+
+        /// Set the function called from a background thread when the device becomes inactive.
+        pub fn when_deactivated<C>(&mut self, callback: C)
+        where
+            C: FnMut() + Send + 'static,
+        {
+            self.callbacks.lock().unwrap().deactivated = Some(Box::new(callback));
+            self.listen();
+        }
+
+        /// Remove the functions set with `when_activated` and `when_deactivated`.
+        pub fn clear_callbacks(&mut self) {
+            *self.callbacks.lock().unwrap() = Callbacks::default();
+            if self.pin.clear_async_interrupt().is_err() {
+                println!("Could not clear interrupt for pin");
+            }
+        }
+
+        fn listen(&mut self) {
+            if self.pin.listener.is_some() {
+                return;
+            }
+            let callbacks = Arc::clone(&self.callbacks);
+            let active_state = self.active_state;
+            let result = self.pin.set_async_interrupt(Trigger::Both, move |level| {
+                let active = (level == Level::High) == active_state;
+                let mut callbacks = callbacks.lock().unwrap();
+                let callback = if active {
+                    &mut callbacks.activated
+                } else {
+                    &mut callbacks.deactivated
+                };
+                if let Some(callback) = callback {
+                    callback()
                 }
+            });
+            if let Err(e) = result {
+                panic!("{:?}", e)
             }
         }
     };
 }
 
-/// Represents a generic input device with typical on/off behaviour.
-/// Adds machinery to fire the active and inactive events for devices
-/// that operate in a typical digital manner: straight forward on / off
-/// states with (reasonably) clean transitions between the two.
+macro_rules! impl_input_reset {
+    () => {
+        /// Returns ``True`` if the pin is reset when the device is dropped.
+        pub fn reset_on_drop(&self) -> bool {
+            self.pin.reset_on_drop()
+        }
+
+        /// When enabled (the default), dropping the device stops any interrupt watching,
+        /// disables the pull resistor and returns the pin to its original mode.
+        pub fn set_reset_on_drop(&mut self, reset_on_drop: bool) {
+            self.pin.set_reset_on_drop(reset_on_drop)
+        }
+    };
+}
+
+macro_rules! impl_inject {
+    () => {
+        /// Test hook: make the device report `value` as if the pin had changed, emitting the
+        /// matching edge when the value differs from the current one. The injected value holds
+        /// until `clear_injection` is called.
+        #[cfg(feature = "inject")]
+        pub fn inject_value(&mut self, value: bool) {
+            let level = if self.value_to_state(value) {
+                Level::High
+            } else {
+                Level::Low
+            };
+            self.pin.inject_level(level)
+        }
+
+        /// Test hook: push a synthetic edge on the pin. The pin level follows the edge and
+        /// any waiter watching for that edge is released.
+        #[cfg(feature = "inject")]
+        pub fn inject_event(&mut self, edge: Edge) {
+            self.pin.inject_edge(edge)
+        }
+
+        /// Drop any injected value and pending injected events and go back to reading the pin.
+        #[cfg(feature = "inject")]
+        pub fn clear_injection(&mut self) {
+            self.pin.clear_injection()
+        }
+    };
+}
+
+/// Convert a timeout in seconds to a `Duration`. Negative and NaN timeouts do not wait,
+/// and infinite or overlong ones wait forever, like no timeout at all.
+pub(crate) fn timeout_duration(timeout: Option<f32>) -> Option<Duration> {
+    timeout.and_then(|t| Duration::try_from_secs_f32(t.max(0.0)).ok())
+}
+
+/// The internal resistor biasing an input pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bias {
+    /// Pulled high: the device connects the pin to ground when active
+    PullUp,
+    /// Pulled low: the device connects the pin to 3V3 when active
+    PullDown,
+    /// No internal resistor, for circuits with their own pull resistor
+    Floating,
+}
+
+/// The base digital input device: a pin with a chosen bias, read as active or inactive.
+/// `InputDevice`, `DigitalInputDevice` and `Button` are built on it.
+///
+/// By default a pin pulled up is active when low and any other pin is active when high.
+/// Circuits with an external pull resistor use `Bias::Floating` and give the active state
+/// with `new_with_active_state`.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// // a reed switch pulling GPIO 4 low, with a 10k resistor to 3V3
+/// let mut door = DigitalInputDeviceR::new_with_active_state(4, Bias::Floating, false);
+/// if door.wait_for_active(Some(5.0)) {
+///     println!("door closed");
+/// }
+/// ```
 #[derive(Debug)]
-pub struct DigitalInputDevice {
-    pin: InputPin,
+pub struct DigitalInputDeviceR {
+    pin: InputLine,
+    bias: Bias,
     active_state: bool,
     inactive_state: bool,
-    bounce_time: Option<f32>,
+    callbacks: Arc<Mutex<Callbacks>>,
 }
 
-impl DigitalInputDevice {
-    /// Returns a DigitalInputDevice with the pin number given with the pin pulled to low by default
-    /// `is_active` property is adjusted accordingly so that
-    /// ``True`` still means active regardless of the :attr:`pull_up` setting
+impl DigitalInputDeviceR {
+    /// Returns a DigitalInputDeviceR with the pin number and bias given. The device is
+    /// active when low if the pin is pulled up, and active when high otherwise.
     /// # Arguments
     ///
     /// * `pin` - The GPIO pin which the device is attached to
-    /// # Note: BCM pins 2 and 3 are i2c SDA and SCL respectively and include a fixed, 1.8 kohms pull-up to 3.3v
-    /// These pins are not suitable for use where no pullup resistor is required
-    /// Source: https://pinout.xyz/pinout/pin5_gpio3
-    pub fn new(pin: u8) -> DigitalInputDevice {
+    /// * `bias` - The internal resistor to enable
+    pub fn new(pin: u8, bias: Bias) -> DigitalInputDeviceR {
+        DigitalInputDeviceR::new_with_active_state(pin, bias, bias != Bias::PullUp)
+    }
+
+    /// Returns a DigitalInputDeviceR with the pin number, bias and active state given.
+    /// # Arguments
+    ///
+    /// * `pin` - The GPIO pin which the device is attached to
+    /// * `bias` - The internal resistor to enable
+    /// * `active_high` - ``True`` if the device is active when the pin is high
+    pub fn new_with_active_state(pin: u8, bias: Bias, active_high: bool) -> DigitalInputDeviceR {
         match Gpio::new() {
             Err(e) => panic!("{:?}", e),
             Ok(gpio) => match gpio.get(pin) {
                 Err(e) => panic!("{:?}", e),
-                Ok(pin) => DigitalInputDevice {
-                    pin: pin.into_input_pulldown(),
-                    active_state: true,
-                    inactive_state: false,
-                    bounce_time: None,
-                },
+                Ok(pin) => DigitalInputDeviceR::from_line(
+                    InputLine::new(match bias {
+                        Bias::PullUp => pin.into_input_pullup(),
+                        Bias::PullDown => pin.into_input_pulldown(),
+                        Bias::Floating => pin.into_input(),
+                    }),
+                    bias,
+                    active_high,
+                ),
             },
         }
     }
-    /// Returns a DigitalInputDevice with the pin number given with the pin pulled high with an internal resistor by default
-    /// `is_active` property is adjusted accordingly so that
-    /// ``True`` still means active regardless of the :attr:`pull_up` setting
+
+    /// Returns a DigitalInputDeviceR with the pin number given, wired active-low: the pin is
+    /// pulled high from the moment it becomes an input and the device is active when low.
     /// # Arguments
     ///
     /// * `pin` - The GPIO pin which the device is attached to
-    ///  
-    pub fn new_with_pullup(pin: u8) -> DigitalInputDevice {
-        match Gpio::new() {
-            Err(e) => panic!("{:?}", e),
-            Ok(gpio) => match gpio.get(pin) {
-                Err(e) => panic!("{:?}", e),
-                Ok(pin) => DigitalInputDevice {
-                    pin: pin.into_input_pullup(),
-                    active_state: false,
-                    inactive_state: true,
-                    bounce_time: None,
-                },
-            },
+    pub fn new_active_low(pin: u8) -> DigitalInputDeviceR {
+        DigitalInputDeviceR::new_with_active_state(pin, Bias::PullUp, false)
+    }
+
+    /// Returns a DigitalInputDeviceR reading a pin already configured as an input.
+    /// A pin pulled up is active when low, any other pin is active when high.
+    /// # Arguments
+    ///
+    /// * `pin` - The input pin, see the `pin` module
+    pub fn from_pin<P: Pull>(pin: Pin<Input<P>>) -> DigitalInputDeviceR {
+        let bias = match P::PULL_UP_DOWN {
+            PullUpDown::PullUp => Bias::PullUp,
+            PullUpDown::PullDown => Bias::PullDown,
+            PullUpDown::Off => Bias::Floating,
+        };
+        DigitalInputDeviceR::from_line(
+            InputLine::new(pin.into_input_pin()),
+            bias,
+            bias != Bias::PullUp,
+        )
+    }
+
+    /// Returns a DigitalInputDeviceR on a pin of a GPIO expander or another `PinFactory`.
+    /// A pin pulled up is active when low, any other pin is active when high.
+    /// # Arguments
+    ///
+    /// * `factory` - The provider of the pin, e.g. an `MCP23017`
+    /// * `pin` - The number of the pin on the factory
+    /// * `bias` - The internal resistor to enable
+    pub fn with_factory<F: PinFactory>(factory: &F, pin: u8, bias: Bias) -> DigitalInputDeviceR {
+        DigitalInputDeviceR::from_line(
+            InputLine::with_driver(factory.input_pin(pin, bias)),
+            bias,
+            bias != Bias::PullUp,
+        )
+    }
+
+    fn from_line(pin: InputLine, bias: Bias, active_high: bool) -> DigitalInputDeviceR {
+        DigitalInputDeviceR {
+            pin,
+            bias,
+            active_state: active_high,
+            inactive_state: !active_high,
+            callbacks: Arc::default(),
         }
     }
 
     impl_device!();
     impl_gpio_device!();
     impl_io_device!();
-    impl_events_mixin!();
+    impl_inject!();
+    impl_input_reset!();
+    impl_callbacks!();
 
-    /// Pause the program until the device is deactivated, or the timeout is reached.
-    pub fn wait_for_inactive(&mut self, timeout: Option<f32>) {
-        self.wait_for(timeout, false)
+    /// The internal resistor enabled on the pin.
+    pub fn bias(&self) -> Bias {
+        self.bias
     }
 
-    /// Pause the program until the device is activated, or the timeout is reached.
-    pub fn wait_for_active(&mut self, timeout: Option<f32>) {
-        self.wait_for(timeout, true)
+    /// Returns ``True`` if the device is active when the pin is high.
+    pub fn active_high(&self) -> bool {
+        self.active_state
     }
-}
 
-/// Represents a simple push button or switch.
-/// Connect one side of the button to a ground pin, and the other to any GPIO pin. The GPIO pin will be pulled high by default.
-/// Alternatively, connect one side of the button to the 3V3 pin, and the other to any GPIO pin,
-/// and then create a Button instance with Button::new_with_pulldown
-pub struct Button {
-    pin: InputPin,
-    active_state: bool,
-    inactive_state: bool,
-    // FIXME: Implement debouncing
-    #[allow(dead_code)]
-    bounce_time: Option<f32>,
-}
+    /// Pause the program until the device is active, or the timeout is reached.
+    /// Returns ``True`` if the device is active, ``False`` if the timeout was reached first.
+    /// * `timeout` - Number of seconds to wait. If this is None, then wait indefinitely.
+    pub fn wait_for_active(&mut self, timeout: Option<f32>) -> bool {
+        self.wait_until(true, timeout)
+    }
 
-impl Button {
-    /// Returns a Button with the pin number given and the pin pulled high with an internal resistor by default
-    /// * `pin` - The GPIO pin which the device is attached to
-    pub fn new(pin: u8) -> Button {
-        match Gpio::new() {
-            Err(e) => panic!("{:?}", e),
-            Ok(gpio) => match gpio.get(pin) {
-                Err(e) => panic!("{:?}", e),
-                Ok(pin) => Button {
-                    pin: pin.into_input_pullup(),
-                    active_state: false,
-                    inactive_state: true,
-                    bounce_time: None,
-                },
-            },
-        }
+    /// Pause the program until the device is inactive, or the timeout is reached.
+    /// Returns ``True`` if the device is inactive, ``False`` if the timeout was reached first.
+    /// * `timeout` - Number of seconds to wait. If this is None, then wait indefinitely.
+    pub fn wait_for_inactive(&mut self, timeout: Option<f32>) -> bool {
+        self.wait_until(false, timeout)
     }
-    /// Returns a Button with the pin number given and the pin pulled down with an internal resistor by default
-    /// * `pin` - The GPIO pin which the device is attached to
-    pub fn new_with_pulldown(pin: u8) -> Button {
-        match Gpio::new() {
-            Err(e) => panic!("{:?}", e),
-            Ok(gpio) => match gpio.get(pin) {
-                Err(e) => panic!("{:?}", e),
-                Ok(pin) => Button {
-                    pin: pin.into_input_pulldown(),
-                    active_state: true,
-                    inactive_state: false,
-                    bounce_time: None,
+
+    fn wait_until(&mut self, active: bool, timeout: Option<f32>) -> bool {
+        let deadline = timeout_duration(timeout).and_then(|t| Instant::now().checked_add(t));
+        let trigger = if self.value_to_state(active) {
+            Trigger::RisingEdge
+        } else {
+            Trigger::FallingEdge
+        };
+        if let Err(e) = self.pin.set_interrupt(trigger) {
+            panic!("{:?}", e)
+        }
+        loop {
+            if self.value() == active {
+                return true;
+            }
+            let remaining = match deadline {
+                None => None,
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(remaining) => Some(remaining),
+                    None => return false,
                 },
-            },
+            };
+            match self.pin.poll_interrupt(false, remaining) {
+                Err(e) => panic!("{:?}", e),
+                Ok(None) => return self.value() == active,
+                Ok(Some(_)) => {}
+            }
         }
     }
+}
 
-    impl_device!();
-    impl_gpio_device!();
-    impl_io_device!();
-    impl_events_mixin!();
+/// The methods of an input device wrapping a `DigitalInputDeviceR`, passed on to it.
+macro_rules! impl_input_wrapper {
+    () => {
+        /// Returns ``True`` if the device is currently active and ``False`` otherwise.
+        pub fn value(&self) -> bool {
+            self.0.value()
+        }
 
-    //// Pause the program until the device is deactivated, or the timeout is reached.
-    /// * `timeout` - Number of seconds to wait before proceeding. If this is None, then wait indefinitely until the device is inactive.
-    pub fn wait_for_release(&mut self, timeout: Option<f32>) {
-        self.wait_for(timeout, false)
-    }
+        /// Returns ``True`` if the device is currently active and ``False`` otherwise.
+        pub fn is_active(&self) -> bool {
+            self.0.is_active()
+        }
 
-    /// Pause the program until the device is activated, or the timeout is reached.
-    /// * `timeout` - Number of seconds to wait before proceeding. If this is None, then wait indefinitely until the device is active.
-    pub fn wait_for_press(&mut self, timeout: Option<f32>) {
-        self.wait_for(timeout, true)
+        /// Shut down the device and release all associated resources.
+        pub fn close(self) {
+            drop(self)
+        }
+
+        /// The `Pin` that the device is connected to.
+        pub fn pin(&self) -> u8 {
+            self.0.pin()
+        }
+
+        /// The internal resistor enabled on the pin.
+        pub fn bias(&self) -> Bias {
+            self.0.bias()
+        }
+
+        /// Returns ``True`` if the device is active when the pin is high.
+        pub fn active_high(&self) -> bool {
+            self.0.active_high()
+        }
+
+        /// Returns ``True`` if the pin is reset when the device is dropped.
+        pub fn reset_on_drop(&self) -> bool {
+            self.0.reset_on_drop()
+        }
+
+        /// When enabled (the default), dropping the device stops any interrupt watching,
+        /// disables the pull resistor and returns the pin to its original mode.
+        pub fn set_reset_on_drop(&mut self, reset_on_drop: bool) {
+            self.0.set_reset_on_drop(reset_on_drop)
+        }
+
+        /// Test hook: make the device report `value` as if the pin had changed, emitting the
+        /// matching edge when the value differs from the current one. The injected value holds
+        /// until `clear_injection` is called.
+        #[cfg(feature = "inject")]
+        pub fn inject_value(&mut self, value: bool) {
+            self.0.inject_value(value)
+        }
+
+        /// Test hook: push a synthetic edge on the pin. The pin level follows the edge and
+        /// any waiter watching for that edge is released.
+        #[cfg(feature = "inject")]
+        pub fn inject_event(&mut self, edge: Edge) {
+            self.0.inject_event(edge)
+        }
+
+        /// Drop any injected value and pending injected events and go back to reading the pin.
+        #[cfg(feature = "inject")]
+        pub fn clear_injection(&mut self) {
+            self.0.clear_injection()
+        }
+    };
+}
+
+/// The events of an input device wrapping a `DigitalInputDeviceR`, passed on to it.
+macro_rules! impl_input_wrapper_events {
+    () => {
+        /// Set the function called from a background thread when the device becomes active.
+        pub fn when_activated<C>(&mut self, callback: C)
+        where
+            C: FnMut() + Send + 'static,
+        {
+            self.0.when_activated(callback)
+        }
+
+        /// Set the function called from a background thread when the device becomes inactive.
+        pub fn when_deactivated<C>(&mut self, callback: C)
+        where
+            C: FnMut() + Send + 'static,
+        {
+            self.0.when_deactivated(callback)
+        }
+
+        /// Remove the functions set with `when_activated` and `when_deactivated`.
+        pub fn clear_callbacks(&mut self) {
+            self.0.clear_callbacks()
+        }
+
+        /// Pause the program until the device is active, or the timeout is reached.
+        /// Returns ``True`` if the device is active, ``False`` if the timeout was reached first.
+        /// * `timeout` - Number of seconds to wait. If this is None, then wait indefinitely.
+        pub fn wait_for_active(&mut self, timeout: Option<f32>) -> bool {
+            self.0.wait_for_active(timeout)
+        }
+
+        /// Pause the program until the device is inactive, or the timeout is reached.
+        /// Returns ``True`` if the device is inactive, ``False`` if the timeout was reached first.
+        /// * `timeout` - Number of seconds to wait. If this is None, then wait indefinitely.
+        pub fn wait_for_inactive(&mut self, timeout: Option<f32>) -> bool {
+            self.0.wait_for_inactive(timeout)
+        }
+    };
+}
+
+/// Represents a generic GPIO input device.
+#[derive(Debug)]
+pub struct InputDevice(DigitalInputDeviceR);
+
+impl InputDevice {
+    /// Returns an InputDevice with the pin number given with the pin pulled to low by default
+    /// `is_active` property is adjusted accordingly so that
+    /// ``True`` still means active regardless of the :attr:`pull_up` setting
+    /// # Arguments
+    ///
+    /// * `pin` - The GPIO pin which the device is attached to
+    ///  
+    pub fn new(pin: u8) -> InputDevice {
+        InputDevice(DigitalInputDeviceR::new(pin, Bias::PullDown))
+    }
+    /// Returns an InputDevice with the pin number given with the pin pulled high with an internal resistor by default
+    /// `is_active` property is adjusted accordingly so that
+    /// ``True`` still means active regardless of the :attr:`pull_up` setting
+    /// # Arguments
+    ///
+    /// * `pin` - The GPIO pin which the device is attached to
+    ///  
+    pub fn new_with_pullup(pin: u8) -> InputDevice {
+        InputDevice(DigitalInputDeviceR::new(pin, Bias::PullUp))
+    }
+
+    /// Returns a InputDevice with the pin number given, wired active-low: the device connects
+    /// the pin to ground when active, so the pin is pulled high from the moment it becomes an input.
+    /// Same as `new_with_pullup`.
+    /// # Arguments
+    ///
+    /// * `pin` - The GPIO pin which the device is attached to
+    pub fn new_active_low(pin: u8) -> InputDevice {
+        InputDevice::new_with_pullup(pin)
+    }
+
+    /// Returns an InputDevice reading a pin already configured as an input.
+    /// A pin pulled up is active when low, any other pin is active when high.
+    /// # Arguments
+    ///
+    /// * `pin` - The input pin, see the `pin` module
+    pub fn from_pin<P: Pull>(pin: Pin<Input<P>>) -> InputDevice {
+        InputDevice(DigitalInputDeviceR::from_pin(pin))
+    }
+
+    /// Returns an InputDevice on a pin of a GPIO expander or another `PinFactory`.
+    /// A pin pulled up is active when low, any other pin is active when high.
+    /// # Arguments
+    ///
+    /// * `factory` - The provider of the pin, e.g. an `MCP23017`
+    /// * `pin` - The number of the pin on the factory
+    /// * `bias` - The internal resistor to enable
+    pub fn with_factory<F: PinFactory>(factory: &F, pin: u8, bias: Bias) -> InputDevice {
+        InputDevice(DigitalInputDeviceR::with_factory(factory, pin, bias))
+    }
+
+    impl_input_wrapper!();
+}
+
+/// Represents a generic input device with typical on/off behaviour.
+/// Adds machinery to fire the active and inactive events for devices
+/// that operate in a typical digital manner: straight forward on / off
+/// states with (reasonably) clean transitions between the two.
+#[derive(Debug)]
+pub struct DigitalInputDevice(DigitalInputDeviceR);
+
+impl DigitalInputDevice {
+    /// Returns a DigitalInputDevice with the pin number given with the pin pulled to low by default
+    /// `is_active` property is adjusted accordingly so that
+    /// ``True`` still means active regardless of the :attr:`pull_up` setting
+    /// # Arguments
+    ///
+    /// * `pin` - The GPIO pin which the device is attached to
+    /// # Note: BCM pins 2 and 3 are i2c SDA and SCL respectively and include a fixed, 1.8 kohms pull-up to 3.3v
+    /// These pins are not suitable for use where no pullup resistor is required
+    /// Source: https://pinout.xyz/pinout/pin5_gpio3
+    pub fn new(pin: u8) -> DigitalInputDevice {
+        DigitalInputDevice(DigitalInputDeviceR::new(pin, Bias::PullDown))
+    }
+    /// Returns a DigitalInputDevice with the pin number given with the pin pulled high with an internal resistor by default
+    /// `is_active` property is adjusted accordingly so that
+    /// ``True`` still means active regardless of the :attr:`pull_up` setting
+    /// # Arguments
+    ///
+    /// * `pin` - The GPIO pin which the device is attached to
+    ///  
+    pub fn new_with_pullup(pin: u8) -> DigitalInputDevice {
+        DigitalInputDevice(DigitalInputDeviceR::new(pin, Bias::PullUp))
+    }
+
+    /// Returns a DigitalInputDevice with the pin number given, wired active-low: the device connects
+    /// the pin to ground when active, so the pin is pulled high from the moment it becomes an input.
+    /// Same as `new_with_pullup`.
+    /// # Arguments
+    ///
+    /// * `pin` - The GPIO pin which the device is attached to
+    pub fn new_active_low(pin: u8) -> DigitalInputDevice {
+        DigitalInputDevice::new_with_pullup(pin)
+    }
+
+    /// Returns a DigitalInputDevice on a pin of a GPIO expander or another `PinFactory`.
+    /// A pin pulled up is active when low, any other pin is active when high.
+    /// # Arguments
+    ///
+    /// * `factory` - The provider of the pin, e.g. an `MCP23017`
+    /// * `pin` - The number of the pin on the factory
+    /// * `bias` - The internal resistor to enable
+    pub fn with_factory<F: PinFactory>(factory: &F, pin: u8, bias: Bias) -> DigitalInputDevice {
+        DigitalInputDevice(DigitalInputDeviceR::with_factory(factory, pin, bias))
+    }
+
+    impl_input_wrapper!();
+    impl_input_wrapper_events!();
+}
+
+/// Represents a simple push button or switch.
+/// Connect one side of the button to a ground pin, and the other to any GPIO pin. The GPIO pin will be pulled high by default.
+/// Alternatively, connect one side of the button to the 3V3 pin, and the other to any GPIO pin,
+/// and then create a Button instance with Button::new_with_pulldown
+#[derive(Debug)]
+pub struct Button(DigitalInputDeviceR);
+
+impl Button {
+    /// Returns a Button with the pin number given and the pin pulled high with an internal resistor by default
+    /// * `pin` - The GPIO pin which the device is attached to
+    pub fn new(pin: u8) -> Button {
+        Button(DigitalInputDeviceR::new(pin, Bias::PullUp))
+    }
+    /// Returns a Button with the pin number given and the pin pulled down with an internal resistor by default
+    /// * `pin` - The GPIO pin which the device is attached to
+    pub fn new_with_pulldown(pin: u8) -> Button {
+        Button(DigitalInputDeviceR::new(pin, Bias::PullDown))
+    }
+
+    /// Returns a Button with the pin number given, wired active-low between the pin and
+    /// ground, with the pin pulled high from the moment it becomes an input. Same as `new`.
+    /// * `pin` - The GPIO pin which the device is attached to
+    pub fn new_active_low(pin: u8) -> Button {
+        Button::new(pin)
+    }
+
+    /// Returns a Button on a pin of a GPIO expander or another `PinFactory`, wired between
+    /// the pin and ground, with the pin pulled high.
+    /// * `factory` - The provider of the pin, e.g. an `MCP23017`
+    /// * `pin` - The number of the pin on the factory
+    pub fn with_factory<F: PinFactory>(factory: &F, pin: u8) -> Button {
+        Button(DigitalInputDeviceR::with_factory(
+            factory,
+            pin,
+            Bias::PullUp,
+        ))
+    }
+
+    impl_input_wrapper!();
+    impl_input_wrapper_events!();
+
+    /// Pause the program until the device is deactivated, or the timeout is reached.
+    /// Returns ``True`` if the button is released, ``False`` if the timeout was reached first.
+    /// * `timeout` - Number of seconds to wait before proceeding. If this is None, then wait indefinitely until the device is inactive.
+    pub fn wait_for_release(&mut self, timeout: Option<f32>) -> bool {
+        self.wait_for_inactive(timeout)
+    }
+
+    /// Pause the program until the device is activated, or the timeout is reached.
+    /// Returns ``True`` if the button is pressed, ``False`` if the timeout was reached first.
+    /// * `timeout` - Number of seconds to wait before proceeding. If this is None, then wait indefinitely until the device is active.
+    pub fn wait_for_press(&mut self, timeout: Option<f32>) -> bool {
+        self.wait_for_active(timeout)
+    }
+
+    /// Returns ``True`` if the button is currently pressed.
+    pub fn is_pressed(&self) -> bool {
+        self.is_active()
+    }
+
+    /// Set the function called from a background thread when the button is pressed.
+    pub fn when_pressed<C>(&mut self, callback: C)
+    where
+        C: FnMut() + Send + 'static,
+    {
+        self.when_activated(callback)
+    }
+
+    /// Set the function called from a background thread when the button is released.
+    pub fn when_released<C>(&mut self, callback: C)
+    where
+        C: FnMut() + Send + 'static,
+    {
+        self.when_deactivated(callback)
+    }
+}
+
+/// How the readings in the queue of a smoothed device are combined into its value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Average {
+    /// The arithmetic mean, which follows changes smoothly
+    Mean,
+    /// The middle reading, which ignores the odd wild reading completely
+    Median,
+}
+
+impl Average {
+    fn reduce(self, readings: &VecDeque<f64>) -> f64 {
+        match self {
+            Average::Mean => readings.iter().sum::<f64>() / readings.len() as f64,
+            Average::Median => {
+                let mut sorted: Vec<f64> = readings.iter().copied().collect();
+                sorted.sort_by(f64::total_cmp);
+                let middle = sorted.len() / 2;
+                if sorted.len().is_multiple_of(2) {
+                    (sorted[middle - 1] + sorted[middle]) / 2.0
+                } else {
+                    sorted[middle]
+                }
+            }
+        }
+    }
+}
+
+/// The last few readings of a smoothed device and whether they put it above its threshold.
+#[derive(Debug)]
+pub(crate) struct SmoothedQueue {
+    readings: VecDeque<f64>,
+    queue_len: usize,
+    partial: bool,
+    average: Average,
+    threshold: f64,
+    hysteresis: f64,
+    active: Option<bool>,
+}
+
+impl SmoothedQueue {
+    pub(crate) fn new(
+        queue_len: usize,
+        partial: bool,
+        average: Average,
+        threshold: f64,
+    ) -> SmoothedQueue {
+        SmoothedQueue {
+            readings: VecDeque::with_capacity(queue_len),
+            queue_len,
+            partial,
+            average,
+            threshold,
+            hysteresis: 0.0,
+            active: None,
+        }
+    }
+
+    /// Add a reading, dropping the oldest one if the queue is full. Returns the new state if
+    /// the device became active or inactive.
+    pub(crate) fn push(&mut self, reading: f64) -> Option<bool> {
+        while self.readings.len() >= self.queue_len {
+            self.readings.pop_front();
+        }
+        self.readings.push_back(reading);
+        let active = self.is_active()?;
+        let previous = self.active.replace(active);
+        match previous {
+            Some(previous) if previous != active => Some(active),
+            _ => None,
+        }
+    }
+
+    /// The average of the queue, or None until it is full unless partial values are allowed.
+    pub(crate) fn value(&self) -> Option<f64> {
+        let filled = self.readings.len() >= self.queue_len;
+        if self.readings.is_empty() || !(filled || self.partial) {
+            None
+        } else {
+            Some(self.average.reduce(&self.readings))
+        }
+    }
+
+    /// Active above the threshold, and once active until below the threshold less the
+    /// hysteresis.
+    fn is_active(&self) -> Option<bool> {
+        self.value().map(|value| match self.active {
+            Some(true) => value >= self.threshold - self.hysteresis,
+            _ => value > self.threshold,
+        })
+    }
+
+    fn set_queue_len(&mut self, queue_len: usize) {
+        self.queue_len = queue_len;
+        while self.readings.len() > queue_len {
+            self.readings.pop_front();
+        }
+    }
+}
+
+/// Call the callback for becoming active or inactive.
+fn fire(callbacks: &Mutex<Callbacks>, active: bool) {
+    let mut callbacks = callbacks.lock().unwrap();
+    let callback = if active {
+        &mut callbacks.activated
+    } else {
+        &mut callbacks.deactivated
+    };
+    if let Some(callback) = callback {
+        callback()
+    }
+}
+
+/// Pause until `done` returns ``True``, checking every 10ms. Returns ``False`` if the timeout
+/// was reached first.
+fn wait_until<F: Fn() -> bool>(done: F, timeout: Option<f32>) -> bool {
+    let deadline = timeout_duration(timeout).and_then(|t| Instant::now().checked_add(t));
+    loop {
+        if done() {
+            return true;
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return false;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+struct SmoothedState {
+    queue: SmoothedQueue,
+    sample_wait: Duration,
+}
+
+/// An input whose readings are taken by a background thread into a fixed-length queue and
+/// averaged, so that a noisy or flickering sensor gives a stable value and a stable
+/// `is_active`. It is the base of `MotionSensor`, and turns any reading, such as an ADC
+/// channel, into a device with a threshold and callbacks.
+///
+/// Until the queue is full, `value` is NaN and the device is neither active nor inactive,
+/// unless partial queues are allowed.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+/// use std::time::Duration;
+///
+/// let adc = MCP3008::new();
+/// let mut soil = SmoothedInputDevice::from_analog(adc.channel(2));
+/// soil.set_queue_len(20);
+/// soil.set_sample_wait(Duration::from_millis(500));
+/// soil.set_threshold(0.4);
+/// soil.set_hysteresis(0.05);
+/// soil.when_deactivated(|| println!("water the plants"));
+/// ```
+pub struct SmoothedInputDevice {
+    state: Arc<Mutex<SmoothedState>>,
+    callbacks: Arc<Mutex<Callbacks>>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl SmoothedInputDevice {
+    /// Returns a SmoothedInputDevice taking a reading every 10ms into a queue of 5, active
+    /// when the median is above 0.5
+    /// * `read` - Takes a reading, normally between 0.0 and 1.0; NaN readings are skipped
+    pub fn new<R>(mut read: R) -> SmoothedInputDevice
+    where
+        R: FnMut() -> f64 + Send + 'static,
+    {
+        let state = Arc::new(Mutex::new(SmoothedState {
+            queue: SmoothedQueue::new(5, false, Average::Median, 0.5),
+            sample_wait: Duration::from_millis(10),
+        }));
+        let callbacks: Arc<Mutex<Callbacks>> = Arc::default();
+        let running = Arc::new(AtomicBool::new(true));
+        let handle = {
+            let state = Arc::clone(&state);
+            let callbacks = Arc::clone(&callbacks);
+            let running = Arc::clone(&running);
+            thread::spawn(move || {
+                while running.load(Ordering::SeqCst) {
+                    let reading = read();
+                    let (changed, sample_wait) = {
+                        let mut state = state.lock().unwrap();
+                        let changed = if reading.is_nan() {
+                            None
+                        } else {
+                            state.queue.push(reading)
+                        };
+                        (changed, state.sample_wait)
+                    };
+                    if let Some(active) = changed {
+                        fire(&callbacks, active);
+                    }
+                    sleep_while_running(&running, sample_wait);
+                }
+            })
+        };
+        SmoothedInputDevice {
+            state,
+            callbacks,
+            running,
+            handle: Some(handle),
+        }
+    }
+
+    /// Returns a SmoothedInputDevice reading an analog input, with the defaults of `new`
+    /// * `channel` - The input, such as an ADC channel
+    pub fn from_analog<A: AnalogInputDevice + 'static>(channel: A) -> SmoothedInputDevice {
+        SmoothedInputDevice::new(move || channel.value())
+    }
+
+    /// The average of the queued readings, or NaN until the queue is full unless partial
+    /// queues are allowed.
+    pub fn value(&self) -> f64 {
+        self.state.lock().unwrap().queue.value().unwrap_or(f64::NAN)
+    }
+
+    /// Returns ``True`` if the value is above the threshold.
+    pub fn is_active(&self) -> bool {
+        self.state.lock().unwrap().queue.is_active() == Some(true)
+    }
+
+    /// The value above which the device is active.
+    pub fn threshold(&self) -> f64 {
+        self.state.lock().unwrap().queue.threshold
+    }
+
+    /// Set the value above which the device is active.
+    pub fn set_threshold(&mut self, threshold: f64) {
+        self.state.lock().unwrap().queue.threshold = threshold;
+    }
+
+    /// How far below the threshold the value must fall for an active device to become
+    /// inactive.
+    pub fn hysteresis(&self) -> f64 {
+        self.state.lock().unwrap().queue.hysteresis
+    }
+
+    /// Set how far below the threshold the value must fall for an active device to become
+    /// inactive, so a value hovering around the threshold does not switch it back and forth.
+    /// The device becomes active above `threshold` and inactive below `threshold - hysteresis`.
+    /// * `hysteresis` - 0.0 by default
+    pub fn set_hysteresis(&mut self, hysteresis: f64) {
+        if hysteresis < 0.0 {
+            println!("Hysteresis must not be negative");
+            return;
+        }
+        self.state.lock().unwrap().queue.hysteresis = hysteresis;
+    }
+
+    /// The number of readings averaged.
+    pub fn queue_len(&self) -> usize {
+        self.state.lock().unwrap().queue.queue_len
+    }
+
+    /// Set the number of readings averaged. Longer queues are steadier but slower to follow
+    /// changes.
+    /// * `queue_len` - At least 1
+    pub fn set_queue_len(&mut self, queue_len: usize) {
+        if queue_len == 0 {
+            println!("Queue length must be at least 1");
+            return;
+        }
+        self.state.lock().unwrap().queue.set_queue_len(queue_len);
+    }
+
+    /// The time between two readings.
+    pub fn sample_wait(&self) -> Duration {
+        self.state.lock().unwrap().sample_wait
+    }
+
+    /// Set the time between two readings.
+    pub fn set_sample_wait(&mut self, sample_wait: Duration) {
+        self.state.lock().unwrap().sample_wait = sample_wait;
+    }
+
+    /// Returns ``True`` if a value is given before the queue is full.
+    pub fn partial(&self) -> bool {
+        self.state.lock().unwrap().queue.partial
+    }
+
+    /// Set whether a value is given before the queue is full, e.g. to react right after
+    /// start-up when the queue is long.
+    pub fn set_partial(&mut self, partial: bool) {
+        self.state.lock().unwrap().queue.partial = partial;
+    }
+
+    /// How the queued readings are combined.
+    pub fn average(&self) -> Average {
+        self.state.lock().unwrap().queue.average
+    }
+
+    /// Set how the queued readings are combined.
+    pub fn set_average(&mut self, average: Average) {
+        self.state.lock().unwrap().queue.average = average;
+    }
+
+    /// Set the function called from a background thread when the device becomes active.
+    pub fn when_activated<C>(&mut self, callback: C)
+    where
+        C: FnMut() + Send + 'static,
+    {
+        self.callbacks.lock().unwrap().activated = Some(Box::new(callback));
+    }
+
+    /// Set the function called from a background thread when the device becomes inactive.
+    pub fn when_deactivated<C>(&mut self, callback: C)
+    where
+        C: FnMut() + Send + 'static,
+    {
+        self.callbacks.lock().unwrap().deactivated = Some(Box::new(callback));
+    }
+
+    /// Pause the program until the device is active, or the timeout is reached.
+    /// Returns ``False`` if the timeout was reached first.
+    /// * `timeout` - Number of seconds to wait. If this is None, then wait indefinitely.
+    pub fn wait_for_active(&self, timeout: Option<f32>) -> bool {
+        wait_until(|| self.is_active(), timeout)
+    }
+
+    /// Pause the program until the device is inactive, or the timeout is reached.
+    /// Returns ``False`` if the timeout was reached first.
+    /// * `timeout` - Number of seconds to wait. If this is None, then wait indefinitely.
+    pub fn wait_for_inactive(&self, timeout: Option<f32>) -> bool {
+        wait_until(
+            || self.state.lock().unwrap().queue.is_active() == Some(false),
+            timeout,
+        )
+    }
+
+    /// Shut down the device and release all associated resources.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+impl Drop for SmoothedInputDevice {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Represents a passive infra-red (PIR) motion sensor, like the HC-SR501.
+///
+/// Connect the output of the sensor to a GPIO pin; it goes high while motion is seen.
+/// Readings are taken 10 times a second into a `SmoothedInputDevice`; a longer queue ignores
+/// the short false triggers some sensors give.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut pir = MotionSensor::new(4);
+/// pir.when_motion(|| println!("someone is there"));
+/// pir.when_no_motion(|| println!("all quiet"));
+/// pir.wait_for_motion(None);
+/// ```
+pub struct MotionSensor {
+    pin: u8,
+    device: SmoothedInputDevice,
+}
+
+impl MotionSensor {
+    /// Returns a MotionSensor with the pin number given, reading 10 times a second into a
+    /// queue of 1, with the internal pull-down resistor enabled
+    /// * `pin` - The GPIO pin which the sensor output is attached to
+    pub fn new(pin: u8) -> MotionSensor {
+        let input = match Gpio::new() {
+            Err(e) => panic!("{:?}", e),
+            Ok(gpio) => match gpio.get(pin) {
+                Err(e) => panic!("{:?}", e),
+                Ok(pin) => pin.into_input_pulldown(),
+            },
+        };
+        let mut device = SmoothedInputDevice::new(move || if input.is_high() { 1.0 } else { 0.0 });
+        device.set_queue_len(1);
+        device.set_sample_wait(Duration::from_millis(100));
+        MotionSensor { pin, device }
+    }
+
+    /// The `Pin` that the device is connected to.
+    pub fn pin(&self) -> u8 {
+        self.pin
+    }
+
+    /// The fraction of the queued readings that saw motion, between 0.0 and 1.0.
+    pub fn value(&self) -> f64 {
+        self.device.value()
+    }
+
+    /// Returns ``True`` if motion is currently detected.
+    pub fn motion_detected(&self) -> bool {
+        self.device.is_active()
+    }
+
+    /// Returns ``True`` if motion is currently detected.
+    pub fn is_active(&self) -> bool {
+        self.device.is_active()
+    }
+
+    /// The smoothing of the readings, to change the queue length, threshold or sample rate.
+    pub fn smoothing(&mut self) -> &mut SmoothedInputDevice {
+        &mut self.device
+    }
+
+    /// Set the function called from a background thread when motion is detected.
+    pub fn when_motion<C>(&mut self, callback: C)
+    where
+        C: FnMut() + Send + 'static,
+    {
+        self.device.when_activated(callback)
+    }
+
+    /// Set the function called from a background thread when motion is no longer detected.
+    pub fn when_no_motion<C>(&mut self, callback: C)
+    where
+        C: FnMut() + Send + 'static,
+    {
+        self.device.when_deactivated(callback)
+    }
+
+    /// Pause the program until motion is detected, or the timeout is reached.
+    /// Returns ``False`` if the timeout was reached first.
+    /// * `timeout` - Number of seconds to wait. If this is None, then wait indefinitely.
+    pub fn wait_for_motion(&self, timeout: Option<f32>) -> bool {
+        self.device.wait_for_active(timeout)
+    }
+
+    /// Pause the program until motion is no longer detected, or the timeout is reached.
+    /// Returns ``False`` if the timeout was reached first.
+    /// * `timeout` - Number of seconds to wait. If this is None, then wait indefinitely.
+    pub fn wait_for_no_motion(&self, timeout: Option<f32>) -> bool {
+        self.device.wait_for_inactive(timeout)
+    }
+
+    /// Shut down the device and release all associated resources.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+/// Number of charge-time readings averaged by `LightSensor::value`.
+const LIGHT_SENSOR_QUEUE_LEN: usize = 5;
+
+struct LightState {
+    queue: SmoothedQueue,
+    charge_time_limit: Duration,
+}
+
+impl LightState {
+    fn value(&self) -> f64 {
+        self.queue.value().unwrap_or(0.0)
+    }
+}
+
+/// Represents a light dependent resistor (LDR) in series with a capacitor.
+///
+/// Connect one leg of the LDR to the 3V3 pin and the other leg to a GPIO pin and one leg of
+/// a 1µF capacitor. Connect the other leg of the capacitor to a ground pin. The sensor
+/// repeatedly drains the capacitor, then times how long the pin takes to read high as the
+/// capacitor charges through the LDR: the more light, the faster it charges.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut sensor = LightSensor::new(18);
+/// sensor.when_dark(|| println!("lights off"));
+/// sensor.when_light(|| println!("lights on"));
+/// println!("{:.2}", sensor.value());
+/// ```
+pub struct LightSensor {
+    pin: u8,
+    state: Arc<Mutex<LightState>>,
+    callbacks: Arc<Mutex<Callbacks>>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl LightSensor {
+    /// Returns a LightSensor with the pin number given, a charge time limit of 10ms and a
+    /// threshold of 0.1
+    /// * `pin` - The GPIO pin which the LDR and capacitor are attached to
+    pub fn new(pin: u8) -> LightSensor {
+        let mut io = match Gpio::new() {
+            Err(e) => panic!("{:?}", e),
+            Ok(gpio) => match gpio.get(pin) {
+                Err(e) => panic!("{:?}", e),
+                Ok(pin) => pin.into_io(Mode::Input),
+            },
+        };
+        io.set_pullupdown(PullUpDown::Off);
+
+        let state = Arc::new(Mutex::new(LightState {
+            queue: SmoothedQueue::new(LIGHT_SENSOR_QUEUE_LEN, true, Average::Mean, 0.1),
+            charge_time_limit: Duration::from_millis(10),
+        }));
+        let callbacks: Arc<Mutex<Callbacks>> = Arc::default();
+        let running = Arc::new(AtomicBool::new(true));
+
+        let handle = {
+            let state = Arc::clone(&state);
+            let callbacks = Arc::clone(&callbacks);
+            let running = Arc::clone(&running);
+            thread::spawn(move || {
+                while running.load(Ordering::SeqCst) {
+                    let limit = state.lock().unwrap().charge_time_limit;
+                    let charge_time = LightSensor::charge_time(&mut io, limit);
+                    let reading = 1.0 - charge_time.as_secs_f64() / limit.as_secs_f64();
+
+                    let changed = state.lock().unwrap().queue.push(reading);
+                    if let Some(light) = changed {
+                        fire(&callbacks, light);
+                    }
+                    sleep_while_running(&running, Duration::from_millis(10));
+                }
+            })
+        };
+
+        LightSensor {
+            pin,
+            state,
+            callbacks,
+            running,
+            handle: Some(handle),
+        }
+    }
+
+    /// Drain the capacitor, then time how long the pin takes to read high, up to `limit`.
+    fn charge_time(pin: &mut IoPin, limit: Duration) -> Duration {
+        pin.set_mode(Mode::Output);
+        pin.set_low();
+        thread::sleep(limit);
+        pin.set_mode(Mode::Input);
+        let start = Instant::now();
+        while pin.is_low() {
+            let elapsed = start.elapsed();
+            if elapsed >= limit {
+                return limit;
+            }
+            thread::yield_now();
+        }
+        start.elapsed().min(limit)
+    }
+
+    /// The `Pin` that the device is connected to.
+    pub fn pin(&self) -> u8 {
+        self.pin
+    }
+
+    /// The light level between 0.0 (dark) and 1.0 (bright), averaged over the last few readings.
+    pub fn value(&self) -> f64 {
+        self.state.lock().unwrap().value()
+    }
+
+    /// Returns ``True`` if the value is above the threshold.
+    pub fn light_detected(&self) -> bool {
+        self.state.lock().unwrap().queue.is_active() == Some(true)
+    }
+
+    /// Returns ``True`` if the device is currently active, i.e. light is detected.
+    pub fn is_active(&self) -> bool {
+        self.light_detected()
+    }
+
+    /// The value above which light is detected.
+    pub fn threshold(&self) -> f64 {
+        self.state.lock().unwrap().queue.threshold
+    }
+
+    /// Set the value above which light is detected.
+    /// * `threshold` - Value between 0.0 and 1.0
+    pub fn set_threshold(&mut self, threshold: f64) {
+        if !(0.0..=1.0).contains(&threshold) {
+            println!("Threshold must be between 0.0 and 1.0");
+            return;
+        }
+        self.state.lock().unwrap().queue.threshold = threshold;
+    }
+
+    /// The longest charge time measured. Charge times at or above the limit read as 0.0.
+    pub fn charge_time_limit(&self) -> Duration {
+        self.state.lock().unwrap().charge_time_limit
+    }
+
+    /// Set the longest charge time measured. Raise it for larger capacitors or darker rooms.
+    /// * `limit` - The charge time that reads as 0.0
+    pub fn set_charge_time_limit(&mut self, limit: Duration) {
+        if limit == Duration::from_secs(0) {
+            println!("Charge time limit must be greater than 0");
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.charge_time_limit = limit;
+        state.queue.readings.clear();
+    }
+
+    /// The number of readings averaged.
+    pub fn queue_len(&self) -> usize {
+        self.state.lock().unwrap().queue.queue_len
+    }
+
+    /// Set the number of readings averaged, 5 by default.
+    /// * `queue_len` - At least 1
+    pub fn set_queue_len(&mut self, queue_len: usize) {
+        if queue_len == 0 {
+            println!("Queue length must be at least 1");
+            return;
+        }
+        self.state.lock().unwrap().queue.set_queue_len(queue_len);
+    }
+
+    /// Set how the readings are combined, `Average::Mean` by default.
+    pub fn set_average(&mut self, average: Average) {
+        self.state.lock().unwrap().queue.average = average;
+    }
+
+    /// Set the function called from a background thread when light is detected.
+    pub fn when_light<C>(&mut self, callback: C)
+    where
+        C: FnMut() + Send + 'static,
+    {
+        self.callbacks.lock().unwrap().activated = Some(Box::new(callback));
+    }
+
+    /// Set the function called from a background thread when light is no longer detected.
+    pub fn when_dark<C>(&mut self, callback: C)
+    where
+        C: FnMut() + Send + 'static,
+    {
+        self.callbacks.lock().unwrap().deactivated = Some(Box::new(callback));
+    }
+
+    /// Pause the program until light is detected, or the timeout is reached.
+    /// Returns ``False`` if the timeout was reached first.
+    /// * `timeout` - Number of seconds to wait. If this is None, then wait indefinitely.
+    pub fn wait_for_light(&self, timeout: Option<f32>) -> bool {
+        self.wait_until(true, timeout)
+    }
+
+    /// Pause the program until light is no longer detected, or the timeout is reached.
+    /// Returns ``False`` if the timeout was reached first.
+    /// * `timeout` - Number of seconds to wait. If this is None, then wait indefinitely.
+    pub fn wait_for_dark(&self, timeout: Option<f32>) -> bool {
+        self.wait_until(false, timeout)
+    }
+
+    fn wait_until(&self, light: bool, timeout: Option<f32>) -> bool {
+        wait_until(|| self.light_detected() == light, timeout)
+    }
+
+    /// Shut down the device and release all associated resources.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+impl Drop for LightSensor {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Speed of sound in air at 20°C, in meters per second.
+const SPEED_OF_SOUND: f64 = 343.26;
+
+/// Number of distance readings `DistanceSensor` filters by default.
+const DISTANCE_SENSOR_QUEUE_LEN: usize = 9;
+
+#[derive(Default)]
+struct Echo {
+    rise: Option<Instant>,
+    pulse: Option<Duration>,
+}
+
+/// The echo of a `DistanceSensor` did not come back within its echo timeout: the sensor is
+/// disconnected, or nothing reflected the pulse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EchoTimeout;
+
+impl fmt::Display for EchoTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "no echo received from the distance sensor")
+    }
+}
+
+impl Error for EchoTimeout {}
+
+struct DistanceState {
+    readings: VecDeque<f64>,
+    window: usize,
+    filter: Average,
+    max_distance: f64,
+    threshold_distance: f64,
+    in_range: Option<bool>,
+    echo_timeout: Duration,
+    last_reading: Option<f64>,
+    failures: u32,
+}
+
+impl DistanceState {
+    fn distance(&self) -> f64 {
+        if self.readings.is_empty() {
+            return self.max_distance;
+        }
+        self.filter.reduce(&self.readings).min(self.max_distance)
+    }
+
+    fn push(&mut self, distance: f64) {
+        while self.readings.len() >= self.window {
+            self.readings.pop_front();
+        }
+        self.readings.push_back(distance);
+    }
+}
+
+/// The turns to trigger shared by the sensors of a `SonarArray`.
+struct SonarTurns {
+    turn: Mutex<SonarTurn>,
+    changed: Condvar,
+}
+
+struct SonarTurn {
+    next: usize,
+    count: usize,
+    spacing: Duration,
+    last_trigger: Option<Instant>,
+}
+
+impl SonarTurns {
+    /// Wait until sensor `index` may trigger. Returns ``False`` if the sensor stopped first.
+    fn wait(&self, index: usize, running: &AtomicBool) -> bool {
+        let mut turn = self.turn.lock().unwrap();
+        loop {
+            if !running.load(Ordering::SeqCst) {
+                return false;
+            }
+            // wake up now and then to notice the sensor stopping
+            let mut wait = Duration::from_millis(50);
+            if turn.next == index {
+                let now = Instant::now();
+                match turn.last_trigger.map(|last| last + turn.spacing) {
+                    Some(due) if due > now => wait = wait.min(due - now),
+                    _ => return true,
+                }
+            }
+            turn = self.changed.wait_timeout(turn, wait).unwrap().0;
+        }
+    }
+
+    /// Record that sensor `index` triggered and give the turn to the next sensor.
+    fn pass(&self, index: usize) {
+        let mut turn = self.turn.lock().unwrap();
+        turn.last_trigger = Some(Instant::now());
+        turn.next = (index + 1) % turn.count;
+        self.changed.notify_all();
+    }
+}
+
+/// Represents an HC-SR04 ultrasonic distance sensor.
+///
+/// Connect the trigger pin of the sensor to a GPIO pin and the echo pin, through a voltage
+/// divider bringing its 5V down to 3.3V, to another. A background thread triggers a
+/// measurement every 60ms; the width of each echo pulse is timed from the pin's interrupts,
+/// and the distance is the median of the last few readings; `set_filter` changes how many
+/// readings are used and whether their median or mean is taken, and `raw_distance` gives
+/// the latest reading alone.
+///
+/// A measurement whose echo does not come back within the echo timeout is not used;
+/// `try_distance` reports it as `EchoTimeout` and `consecutive_failures` counts them, so a
+/// dead sensor can be told apart from an empty room.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut sensor = DistanceSensor::new(24, 23);
+/// sensor.set_threshold_distance(0.2);
+/// sensor.set_filter(Average::Mean, 5);
+/// sensor.when_in_range(|| println!("something is close"));
+/// match sensor.try_distance() {
+///     Ok(distance) => println!("{:.2}m", distance),
+///     Err(e) => println!("{} ({} in a row)", e, sensor.consecutive_failures()),
+/// }
+/// ```
+pub struct DistanceSensor {
+    echo: InputLine,
+    trigger: u8,
+    state: Arc<Mutex<DistanceState>>,
+    callbacks: Arc<Mutex<Callbacks>>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl DistanceSensor {
+    /// Returns a DistanceSensor with a max distance of 1m and a threshold distance of 0.3m
+    /// * `echo` - The GPIO pin which the echo pin of the sensor is attached to
+    /// * `trigger` - The GPIO pin which the trigger pin of the sensor is attached to
+    pub fn new(echo: u8, trigger: u8) -> DistanceSensor {
+        DistanceSensor::with_turns(echo, trigger, None)
+    }
+
+    /// Returns a DistanceSensor triggering when `turns` gives it the turn, or every 60ms
+    /// if `turns` is None.
+    fn with_turns(
+        echo: u8,
+        trigger: u8,
+        turns: Option<(Arc<SonarTurns>, usize)>,
+    ) -> DistanceSensor {
+        let gpio = match Gpio::new() {
+            Err(e) => panic!("{:?}", e),
+            Ok(gpio) => gpio,
+        };
+        let mut echo = match gpio.get(echo) {
+            Err(e) => panic!("{:?}", e),
+            Ok(pin) => InputLine::new(pin.into_input_pulldown()),
+        };
+        let mut trigger_pin = match gpio.get(trigger) {
+            Err(e) => panic!("{:?}", e),
+            Ok(pin) => pin.into_output(),
+        };
+        trigger_pin.set_low();
+
+        let pulse = Arc::new((Mutex::new(Echo::default()), Condvar::new()));
+        {
+            let pulse = Arc::clone(&pulse);
+            let result = echo.set_async_interrupt(Trigger::Both, move |level| {
+                let now = Instant::now();
+                let (echo, arrived) = &*pulse;
+                let mut echo = echo.lock().unwrap();
+                match level {
+                    Level::High => echo.rise = Some(now),
+                    Level::Low => {
+                        if let Some(rise) = echo.rise.take() {
+                            echo.pulse = Some(now - rise);
+                            arrived.notify_all();
+                        }
+                    }
+                }
+            });
+            if let Err(e) = result {
+                panic!("{:?}", e)
+            }
+        }
+
+        let state = Arc::new(Mutex::new(DistanceState {
+            readings: VecDeque::with_capacity(DISTANCE_SENSOR_QUEUE_LEN),
+            window: DISTANCE_SENSOR_QUEUE_LEN,
+            filter: Average::Median,
+            max_distance: 1.0,
+            threshold_distance: 0.3,
+            in_range: None,
+            // the longest echo the sensor reports is about 38ms
+            echo_timeout: Duration::from_millis(50),
+            last_reading: None,
+            failures: 0,
+        }));
+        let callbacks: Arc<Mutex<Callbacks>> = Arc::default();
+        let running = Arc::new(AtomicBool::new(true));
+
+        let handle = {
+            let state = Arc::clone(&state);
+            let callbacks = Arc::clone(&callbacks);
+            let running = Arc::clone(&running);
+            thread::spawn(move || {
+                while running.load(Ordering::SeqCst) {
+                    if let Some((turns, index)) = &turns {
+                        if !turns.wait(*index, &running) {
+                            break;
+                        }
+                    }
+                    let (echo, arrived) = &*pulse;
+                    let timeout = state.lock().unwrap().echo_timeout;
+                    let width = {
+                        let mut echo = echo.lock().unwrap();
+                        *echo = Echo::default();
+                        trigger_pin.set_high();
+                        thread::sleep(Duration::from_micros(10));
+                        trigger_pin.set_low();
+                        if let Some((turns, index)) = &turns {
+                            turns.pass(*index);
+                        }
+                        let (mut echo, _) = arrived
+                            .wait_timeout_while(echo, timeout, |echo| echo.pulse.is_none())
+                            .unwrap();
+                        echo.pulse.take()
+                    };
+
+                    match width {
+                        None => {
+                            let mut state = state.lock().unwrap();
+                            state.last_reading = None;
+                            state.failures = state.failures.saturating_add(1);
+                        }
+                        Some(width) => {
+                            let distance = width.as_secs_f64() * SPEED_OF_SOUND / 2.0;
+                            let changed = {
+                                let mut state = state.lock().unwrap();
+                                state.last_reading = Some(distance);
+                                state.failures = 0;
+                                state.push(distance);
+                                let in_range = state.distance() < state.threshold_distance;
+                                let previous = state.in_range.replace(in_range);
+                                previous
+                                    .is_some_and(|previous| previous != in_range)
+                                    .then_some(in_range)
+                            };
+                            if let Some(in_range) = changed {
+                                let mut callbacks = callbacks.lock().unwrap();
+                                let callback = if in_range {
+                                    &mut callbacks.activated
+                                } else {
+                                    &mut callbacks.deactivated
+                                };
+                                if let Some(callback) = callback {
+                                    callback()
+                                }
+                            }
+                        }
+                    }
+                    if turns.is_none() {
+                        sleep_while_running(&running, Duration::from_millis(60));
+                    }
+                }
+            })
+        };
+
+        DistanceSensor {
+            echo,
+            trigger,
+            state,
+            callbacks,
+            running,
+            handle: Some(handle),
+        }
+    }
+
+    /// The `Pin` that the echo pin of the sensor is connected to.
+    pub fn echo(&self) -> u8 {
+        self.echo.pin()
+    }
+
+    /// The `Pin` that the trigger pin of the sensor is connected to.
+    pub fn trigger(&self) -> u8 {
+        self.trigger
+    }
+
+    /// The distance in meters, up to the max distance, filtered over the last few readings.
+    pub fn distance(&self) -> f64 {
+        self.state.lock().unwrap().distance()
+    }
+
+    /// The distance in meters of the latest reading that received an echo, unfiltered and
+    /// up to the max distance.
+    pub fn raw_distance(&self) -> f64 {
+        let state = self.state.lock().unwrap();
+        match state.readings.back() {
+            Some(distance) => distance.min(state.max_distance),
+            None => state.max_distance,
+        }
+    }
+
+    /// How the last few readings are combined into a distance.
+    pub fn filter(&self) -> (Average, usize) {
+        let state = self.state.lock().unwrap();
+        (state.filter, state.window)
+    }
+
+    /// Set how the last few readings are combined into a distance. Defaults to the median
+    /// of 9 readings.
+    /// * `filter` - Take the median or the mean of the readings, as a `SmoothedInputDevice`
+    ///   does; the median of an even number of readings is the mean of the middle two
+    /// * `window` - The number of readings, at least 1. A reading is taken every 60ms
+    pub fn set_filter(&mut self, filter: Average, window: usize) {
+        if window == 0 {
+            println!("Window must be at least 1 reading");
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.filter = filter;
+        state.window = window;
+        while state.readings.len() > window {
+            state.readings.pop_front();
+        }
+    }
+
+    /// The distance in meters, up to the max distance, or `EchoTimeout` if the echo of the
+    /// latest measurement did not come back.
+    pub fn try_distance(&self) -> Result<f64, EchoTimeout> {
+        let state = self.state.lock().unwrap();
+        if state.failures > 0 {
+            Err(EchoTimeout)
+        } else {
+            Ok(state.distance())
+        }
+    }
+
+    /// The distance in meters of the latest measurement alone, or None if its echo did not
+    /// come back or no measurement has been taken yet.
+    pub fn last_reading(&self) -> Option<f64> {
+        self.state.lock().unwrap().last_reading
+    }
+
+    /// The number of measurements in a row whose echo did not come back. A count that keeps
+    /// growing means the sensor is disconnected or faulty.
+    pub fn consecutive_failures(&self) -> u32 {
+        self.state.lock().unwrap().failures
+    }
+
+    /// How long a measurement waits for its echo. Defaults to 50ms.
+    pub fn echo_timeout(&self) -> Duration {
+        self.state.lock().unwrap().echo_timeout
+    }
+
+    /// Set how long a measurement waits for its echo before it counts as a failure.
+    /// * `timeout` - The sensor reports echoes of up to about 38ms, the time taken by
+    ///   sound to travel about 6.5m and back
+    pub fn set_echo_timeout(&mut self, timeout: Duration) {
+        self.state.lock().unwrap().echo_timeout = timeout;
+    }
+
+    /// The distance as a fraction of the max distance, between 0.0 and 1.0.
+    pub fn value(&self) -> f64 {
+        let state = self.state.lock().unwrap();
+        state.distance() / state.max_distance
+    }
+
+    /// The largest distance reported, in meters.
+    pub fn max_distance(&self) -> f64 {
+        self.state.lock().unwrap().max_distance
+    }
+
+    /// Set the largest distance reported, which `value` is scaled to.
+    /// * `max_distance` - Distance in meters, greater than 0.0
+    pub fn set_max_distance(&mut self, max_distance: f64) {
+        if max_distance <= 0.0 {
+            println!("Max distance must be greater than 0");
+            return;
+        }
+        self.state.lock().unwrap().max_distance = max_distance;
+    }
+
+    /// The distance below which the sensor is in range.
+    pub fn threshold_distance(&self) -> f64 {
+        self.state.lock().unwrap().threshold_distance
+    }
+
+    /// Set the distance below which the sensor is in range.
+    /// * `threshold_distance` - Distance in meters
+    pub fn set_threshold_distance(&mut self, threshold_distance: f64) {
+        self.state.lock().unwrap().threshold_distance = threshold_distance;
+    }
+
+    /// Returns ``True`` if the distance is below the threshold distance.
+    pub fn in_range(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.distance() < state.threshold_distance
+    }
+
+    /// Returns ``True`` if the device is currently active, i.e. in range.
+    pub fn is_active(&self) -> bool {
+        self.in_range()
+    }
+
+    /// Set the function called from a background thread when the distance drops below the
+    /// threshold distance.
+    pub fn when_in_range<C>(&mut self, callback: C)
+    where
+        C: FnMut() + Send + 'static,
+    {
+        self.callbacks.lock().unwrap().activated = Some(Box::new(callback));
+    }
+
+    /// Set the function called from a background thread when the distance rises to the
+    /// threshold distance or above.
+    pub fn when_out_of_range<C>(&mut self, callback: C)
+    where
+        C: FnMut() + Send + 'static,
+    {
+        self.callbacks.lock().unwrap().deactivated = Some(Box::new(callback));
+    }
+
+    /// Pause the program until the sensor is in range, or the timeout is reached.
+    /// Returns ``False`` if the timeout was reached first.
+    /// * `timeout` - Number of seconds to wait. If this is None, then wait indefinitely.
+    pub fn wait_for_in_range(&self, timeout: Option<f32>) -> bool {
+        self.wait_until(true, timeout)
+    }
+
+    /// Pause the program until the sensor is out of range, or the timeout is reached.
+    /// Returns ``False`` if the timeout was reached first.
+    /// * `timeout` - Number of seconds to wait. If this is None, then wait indefinitely.
+    pub fn wait_for_out_of_range(&self, timeout: Option<f32>) -> bool {
+        self.wait_until(false, timeout)
+    }
+
+    fn wait_until(&self, in_range: bool, timeout: Option<f32>) -> bool {
+        let deadline = timeout_duration(timeout).and_then(|t| Instant::now().checked_add(t));
+        loop {
+            if self.in_range() == in_range {
+                return true;
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Shut down the device and release all associated resources.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+impl Drop for DistanceSensor {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+        if self.echo.clear_async_interrupt().is_err() {
+            println!("Could not clear interrupt for pin");
+        }
+    }
+}
+
+/// Several HC-SR04 distance sensors taking turns, so that one sensor does not hear the
+/// echoes of another.
+///
+/// The sensors are triggered one after the other, round-robin, with the spacing between
+/// consecutive triggers; each sensor measures once every `len() * spacing`. The spacing
+/// should be at least the echo timeout of the sensors, 50ms by default, so an echo has died
+/// away before the next sensor listens.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// // front, left, right and rear sensors as (echo, trigger) pins
+/// let sonar = SonarArray::new(&[(24, 23), (25, 8), (7, 1), (12, 16)]);
+/// if let Some((index, distance)) = sonar.nearest() {
+///     println!("sensor {} sees something {:.2}m away", index, distance);
+/// }
+/// ```
+pub struct SonarArray {
+    sensors: Vec<DistanceSensor>,
+    turns: Arc<SonarTurns>,
+}
+
+impl SonarArray {
+    /// Returns a SonarArray triggering a sensor every 60ms
+    /// * `pins` - The echo and trigger pins of each sensor, in the order they take turns
+    pub fn new(pins: &[(u8, u8)]) -> SonarArray {
+        let turns = Arc::new(SonarTurns {
+            turn: Mutex::new(SonarTurn {
+                next: 0,
+                count: pins.len().max(1),
+                spacing: Duration::from_millis(60),
+                last_trigger: None,
+            }),
+            changed: Condvar::new(),
+        });
+        let sensors = pins
+            .iter()
+            .enumerate()
+            .map(|(index, &(echo, trigger))| {
+                DistanceSensor::with_turns(echo, trigger, Some((Arc::clone(&turns), index)))
+            })
+            .collect();
+        SonarArray { sensors, turns }
+    }
+
+    /// The time between the triggers of consecutive sensors.
+    pub fn spacing(&self) -> Duration {
+        self.turns.turn.lock().unwrap().spacing
+    }
+
+    /// Set the time between the triggers of consecutive sensors.
+    pub fn set_spacing(&mut self, spacing: Duration) {
+        self.turns.turn.lock().unwrap().spacing = spacing;
+        self.turns.changed.notify_all();
+    }
+
+    /// The number of sensors.
+    pub fn len(&self) -> usize {
+        self.sensors.len()
+    }
+
+    /// Returns ``True`` if the array has no sensors.
+    pub fn is_empty(&self) -> bool {
+        self.sensors.is_empty()
+    }
+
+    /// The sensors, in the order they take turns.
+    pub fn sensors(&self) -> &[DistanceSensor] {
+        &self.sensors
+    }
+
+    /// The sensor at `index`, or None if there is no such sensor.
+    pub fn sensor(&self, index: usize) -> Option<&DistanceSensor> {
+        self.sensors.get(index)
+    }
+
+    /// The sensor at `index`, to change its settings, or None if there is no such sensor.
+    pub fn sensor_mut(&mut self, index: usize) -> Option<&mut DistanceSensor> {
+        self.sensors.get_mut(index)
+    }
+
+    /// The distance in meters seen by each sensor.
+    pub fn distances(&self) -> Vec<f64> {
+        self.sensors.iter().map(DistanceSensor::distance).collect()
+    }
+
+    /// The index and distance of the sensor seeing the nearest thing, ignoring sensors whose
+    /// latest echo did not come back. None if no sensor has a distance.
+    pub fn nearest(&self) -> Option<(usize, f64)> {
+        self.sensors
+            .iter()
+            .enumerate()
+            .filter_map(|(index, sensor)| sensor.try_distance().ok().map(|d| (index, d)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    /// Shut down the device and release all associated resources.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+/// The levels of a rotary encoder's pins in the order they pass when it turns clockwise,
+/// as `(a << 1) | b`, starting from rest with both contacts open.
+const QUADRATURE: [u8; 4] = [0b11, 0b01, 0b00, 0b10];
+
+struct EncoderState {
+    levels: u8,
+    /// Quarter steps turned since the encoder was last at rest
+    quarters: i8,
+    steps: i64,
+    max_steps: u32,
+    wrap: bool,
+}
+
+impl EncoderState {
+    /// Record a new level of pin `a` or `b`. Returns +1 or -1 when a whole step was turned
+    /// clockwise or counter-clockwise.
+    fn update(&mut self, pin_a: bool, level: Level) -> Option<i64> {
+        let mask = if pin_a { 0b10 } else { 0b01 };
+        let levels = match level {
+            Level::High => self.levels | mask,
+            Level::Low => self.levels & !mask,
+        };
+        let position = |levels| QUADRATURE.iter().position(|&l| l == levels).unwrap_or(0);
+        let delta = (position(levels) + 4 - position(self.levels)) % 4;
+        self.levels = levels;
+        // a change of both pins at once is a missed edge, and says nothing of the direction
+        match delta {
+            1 => self.quarters += 1,
+            3 => self.quarters -= 1,
+            _ => {}
+        }
+        if levels != QUADRATURE[0] {
+            return None;
+        }
+        // back at rest: three quarters of a step is enough, so one missed edge does not lose it
+        let direction = match self.quarters {
+            q if q >= 3 => 1,
+            q if q <= -3 => -1,
+            _ => 0,
+        };
+        self.quarters = 0;
+        if direction == 0 {
+            return None;
+        }
+        self.set_steps(self.steps + direction);
+        Some(direction)
+    }
+
+    fn set_steps(&mut self, steps: i64) {
+        let max = i64::from(self.max_steps);
+        self.steps = if max == 0 {
+            steps
+        } else if self.wrap {
+            (steps + max).rem_euclid(2 * max + 1) - max
+        } else {
+            steps.clamp(-max, max)
+        };
+    }
+}
+
+#[derive(Default)]
+struct EncoderCallbacks {
+    rotated: Option<Callback>,
+    clockwise: Option<Callback>,
+    counter_clockwise: Option<Callback>,
+}
+
+/// Represents a rotary encoder, a knob turning in steps with a detent at each step, whose
+/// two pins close in turn as it rotates.
+///
+/// Connect the common pin of the encoder to ground and its `a` and `b` pins to two GPIO
+/// pins, which are pulled up. Every edge on either pin is decoded as a Gray code, so a fast
+/// spin is followed edge by edge, and a step is only counted once the encoder is back at
+/// rest, so contact bounce does not add steps. If clockwise turns count down, swap `a` and `b`.
+///
+/// `steps` counts the steps turned from the start, clockwise positive. With a maximum number
+/// of steps, `value` scales them between -1.0 and 1.0, and `steps` either stops at the
+/// maximum or wraps around to the other end.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut knob = RotaryEncoder::new(20, 21);
+/// knob.set_max_steps(20);
+/// knob.when_rotated_clockwise(|| println!("louder"));
+/// knob.when_rotated_counter_clockwise(|| println!("quieter"));
+/// println!("volume {:.0}%", (knob.value() + 1.0) * 50.0);
+/// ```
+pub struct RotaryEncoder {
+    a: InputLine,
+    b: InputLine,
+    state: Arc<Mutex<EncoderState>>,
+    callbacks: Arc<Mutex<EncoderCallbacks>>,
+}
+
+impl RotaryEncoder {
+    /// Returns a RotaryEncoder with a maximum of 16 steps either way, not wrapping
+    /// * `a` - The GPIO pin which the `a` pin of the encoder is attached to
+    /// * `b` - The GPIO pin which the `b` pin of the encoder is attached to
+    pub fn new(a: u8, b: u8) -> RotaryEncoder {
+        let gpio = match Gpio::new() {
+            Err(e) => panic!("{:?}", e),
+            Ok(gpio) => gpio,
+        };
+        let line = |pin: u8| match gpio.get(pin) {
+            Err(e) => panic!("{:?}", e),
+            Ok(pin) => InputLine::new(pin.into_input_pullup()),
+        };
+        let (mut a, mut b) = (line(a), line(b));
+
+        let bit = |line: &InputLine| u8::from(line.read() == Level::High);
+        let state = Arc::new(Mutex::new(EncoderState {
+            levels: (bit(&a) << 1) | bit(&b),
+            quarters: 0,
+            steps: 0,
+            max_steps: 16,
+            wrap: false,
+        }));
+        let callbacks: Arc<Mutex<EncoderCallbacks>> = Arc::default();
+
+        for (line, pin_a) in IntoIterator::into_iter([(&mut a, true), (&mut b, false)]) {
+            let state = Arc::clone(&state);
+            let callbacks = Arc::clone(&callbacks);
+            let result = line.set_async_interrupt(Trigger::Both, move |level| {
+                let direction = state.lock().unwrap().update(pin_a, level);
+                if let Some(direction) = direction {
+                    let mut callbacks = callbacks.lock().unwrap();
+                    let callbacks = &mut *callbacks;
+                    let turned = if direction > 0 {
+                        &mut callbacks.clockwise
+                    } else {
+                        &mut callbacks.counter_clockwise
+                    };
+                    for callback in
+                        IntoIterator::into_iter([turned, &mut callbacks.rotated]).flatten()
+                    {
+                        callback()
+                    }
+                }
+            });
+            if let Err(e) = result {
+                panic!("{:?}", e)
+            }
+        }
+
+        RotaryEncoder {
+            a,
+            b,
+            state,
+            callbacks,
+        }
+    }
+
+    /// The `Pin` that the `a` pin of the encoder is connected to.
+    pub fn a(&self) -> u8 {
+        self.a.pin()
+    }
+
+    /// The `Pin` that the `b` pin of the encoder is connected to.
+    pub fn b(&self) -> u8 {
+        self.b.pin()
+    }
+
+    /// The number of steps turned, clockwise positive.
+    pub fn steps(&self) -> i64 {
+        self.state.lock().unwrap().steps
+    }
+
+    /// Set the number of steps turned, within the maximum.
+    pub fn set_steps(&mut self, steps: i64) {
+        self.state.lock().unwrap().set_steps(steps)
+    }
+
+    /// The steps as a fraction of the maximum, between -1.0 and 1.0. Always 0.0 without a
+    /// maximum.
+    pub fn value(&self) -> f64 {
+        let state = self.state.lock().unwrap();
+        if state.max_steps == 0 {
+            0.0
+        } else {
+            state.steps as f64 / f64::from(state.max_steps)
+        }
+    }
+
+    /// Set the steps as a fraction of the maximum.
+    /// * `value` - Between -1.0 and 1.0
+    pub fn set_value(&mut self, value: f64) {
+        if !(-1.0..=1.0).contains(&value) {
+            println!("Value must be between -1.0 and 1.0");
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        let steps = (value * f64::from(state.max_steps)).round() as i64;
+        state.set_steps(steps);
+    }
+
+    /// The largest number of steps counted either way, or 0 for no limit.
+    pub fn max_steps(&self) -> u32 {
+        self.state.lock().unwrap().max_steps
+    }
+
+    /// Set the largest number of steps counted either way. Steps beyond it are clamped, or
+    /// wrapped if wrapping is on.
+    /// * `max_steps` - Number of steps, or 0 for no limit, in which case `value` is always 0.0
+    pub fn set_max_steps(&mut self, max_steps: u32) {
+        let mut state = self.state.lock().unwrap();
+        state.max_steps = max_steps;
+        let steps = state.steps;
+        state.set_steps(steps);
+    }
+
+    /// Returns ``True`` if turning past the maximum wraps around to the other end.
+    pub fn wrap(&self) -> bool {
+        self.state.lock().unwrap().wrap
+    }
+
+    /// Set whether turning past the maximum wraps around to the other end rather than
+    /// stopping there.
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.state.lock().unwrap().wrap = wrap;
+    }
+
+    /// Returns ``True`` if the encoder has been turned away from zero steps.
+    pub fn is_active(&self) -> bool {
+        self.steps() != 0
+    }
+
+    /// Set the function called from a background thread on every step, either way.
+    pub fn when_rotated<C>(&mut self, callback: C)
+    where
+        C: FnMut() + Send + 'static,
+    {
+        self.callbacks.lock().unwrap().rotated = Some(Box::new(callback));
+    }
+
+    /// Set the function called from a background thread on every clockwise step.
+    pub fn when_rotated_clockwise<C>(&mut self, callback: C)
+    where
+        C: FnMut() + Send + 'static,
+    {
+        self.callbacks.lock().unwrap().clockwise = Some(Box::new(callback));
+    }
+
+    /// Set the function called from a background thread on every counter-clockwise step.
+    pub fn when_rotated_counter_clockwise<C>(&mut self, callback: C)
+    where
+        C: FnMut() + Send + 'static,
+    {
+        self.callbacks.lock().unwrap().counter_clockwise = Some(Box::new(callback));
+    }
+
+    /// Shut down the device and release all associated resources.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+impl Drop for RotaryEncoder {
+    fn drop(&mut self) {
+        for line in IntoIterator::into_iter([&mut self.a, &mut self.b]) {
+            if line.clear_async_interrupt().is_err() {
+                println!("Could not clear interrupt for pin");
+            }
+        }
+    }
+}
+
+/// The keys of a 3x4 telephone keypad, one string per row.
+pub const KEYPAD_3X4: [&str; 4] = ["123", "456", "789", "*0#"];
+
+/// The keys of a 4x4 keypad, one string per row.
+pub const KEYPAD_4X4: [&str; 4] = ["123A", "456B", "789C", "*0#D"];
+
+/// How often a `Keypad` scans its keys.
+const KEYPAD_SCAN_INTERVAL: Duration = Duration::from_millis(5);
+
+/// The number of scans in a row a key must read the same before a `Keypad` believes it.
+const KEYPAD_STABLE_SCANS: u8 = 4;
+
+/// The most key presses a `Keypad` keeps for `read_key`.
+const KEYPAD_BUFFER_LEN: usize = 16;
+
+type KeyCallback = Box<dyn FnMut(char) + Send>;
+
+struct KeypadState {
+    pressed: Vec<char>,
+    buffer: VecDeque<char>,
+}
+
+#[derive(Default)]
+struct KeyCallbacks {
+    pressed: Option<KeyCallback>,
+    released: Option<KeyCallback>,
+}
+
+/// Represents a matrix keypad, such as a 3x4 or 4x4 membrane keypad.
+///
+/// The keys sit where a row wire crosses a column wire. A background thread scans the
+/// keypad every 5ms, pulling one row low at a time while the other rows float, and reads
+/// which of the columns, pulled up, follow it low. Pressing several keys at once does not
+/// short the rows together. A key must read the same for 20ms before a press or release
+/// is believed, so contact bounce is ignored.
+///
+/// Presses are kept, up to 16 of them, until `read_key` takes them, like the buffer of a
+/// keyboard.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut keypad = Keypad::new(&[5, 6, 13, 19], &[12, 16, 20], &KEYPAD_3X4);
+/// keypad.when_key_pressed(|key| println!("{} pressed", key));
+///
+/// let mut code = String::new();
+/// while let Some(key) = keypad.read_key(Some(10.0)) {
+///     if key == '#' {
+///         break;
+///     }
+///     code.push(key);
+/// }
+/// ```
+pub struct Keypad {
+    rows: Vec<u8>,
+    columns: Vec<u8>,
+    state: Arc<(Mutex<KeypadState>, Condvar)>,
+    callbacks: Arc<Mutex<KeyCallbacks>>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Keypad {
+    /// Returns a Keypad. Panics if the keys do not match the rows and columns.
+    /// * `rows` - The GPIO pins which the row wires are attached to, top to bottom
+    /// * `columns` - The GPIO pins which the column wires are attached to, left to right
+    /// * `keys` - The key at each position, one string per row, such as `KEYPAD_4X4`
+    pub fn new(rows: &[u8], columns: &[u8], keys: &[&str]) -> Keypad {
+        let layout: Vec<Vec<char>> = keys.iter().map(|row| row.chars().collect()).collect();
+        if layout.len() != rows.len() || layout.iter().any(|row| row.len() != columns.len()) {
+            panic!(
+                "Keypad keys must have {} rows of {} keys",
+                rows.len(),
+                columns.len()
+            );
+        }
+        let gpio = match Gpio::new() {
+            Err(e) => panic!("{:?}", e),
+            Ok(gpio) => gpio,
+        };
+        let mut row_pins: Vec<IoPin> = rows
+            .iter()
+            .map(|&pin| match gpio.get(pin) {
+                Err(e) => panic!("{:?}", e),
+                Ok(pin) => pin.into_io(Mode::Input),
+            })
+            .collect();
+        let column_pins: Vec<InputPin> = columns
+            .iter()
+            .map(|&pin| match gpio.get(pin) {
+                Err(e) => panic!("{:?}", e),
+                Ok(pin) => pin.into_input_pullup(),
+            })
+            .collect();
+
+        let state = Arc::new((
+            Mutex::new(KeypadState {
+                pressed: Vec::new(),
+                buffer: VecDeque::with_capacity(KEYPAD_BUFFER_LEN),
+            }),
+            Condvar::new(),
+        ));
+        let callbacks: Arc<Mutex<KeyCallbacks>> = Arc::default();
+        let running = Arc::new(AtomicBool::new(true));
+        let handle = {
+            let state = Arc::clone(&state);
+            let callbacks = Arc::clone(&callbacks);
+            let running = Arc::clone(&running);
+            thread::spawn(move || {
+                let keys: Vec<char> = layout.into_iter().flatten().collect();
+                // the believed state of each key, and how many scans in a row disagreed
+                let mut pressed = vec![false; keys.len()];
+                let mut disagreed = vec![0u8; keys.len()];
+                while running.load(Ordering::SeqCst) {
+                    let mut changes = Vec::new();
+                    for (row, row_pin) in row_pins.iter_mut().enumerate() {
+                        row_pin.set_mode(Mode::Output);
+                        row_pin.set_low();
+                        thread::sleep(Duration::from_micros(10));
+                        for (column, column_pin) in column_pins.iter().enumerate() {
+                            let key = row * column_pins.len() + column;
+                            if column_pin.is_low() == pressed[key] {
+                                disagreed[key] = 0;
+                                continue;
+                            }
+                            disagreed[key] += 1;
+                            if disagreed[key] >= KEYPAD_STABLE_SCANS {
+                                disagreed[key] = 0;
+                                pressed[key] = !pressed[key];
+                                changes.push((keys[key], pressed[key]));
+                            }
+                        }
+                        row_pin.set_mode(Mode::Input);
+                    }
+                    for (key, down) in changes {
+                        Keypad::key_changed(&state, &callbacks, key, down);
+                    }
+                    sleep_while_running(&running, KEYPAD_SCAN_INTERVAL);
+                }
+            })
+        };
+
+        Keypad {
+            rows: rows.to_vec(),
+            columns: columns.to_vec(),
+            state,
+            callbacks,
+            running,
+            handle: Some(handle),
+        }
+    }
+
+    fn key_changed(
+        state: &(Mutex<KeypadState>, Condvar),
+        callbacks: &Mutex<KeyCallbacks>,
+        key: char,
+        down: bool,
+    ) {
+        {
+            let (state, arrived) = state;
+            let mut state = state.lock().unwrap();
+            if down {
+                state.pressed.push(key);
+                if state.buffer.len() == KEYPAD_BUFFER_LEN {
+                    state.buffer.pop_front();
+                }
+                state.buffer.push_back(key);
+                arrived.notify_all();
+            } else {
+                state.pressed.retain(|&k| k != key);
+            }
+        }
+        let mut callbacks = callbacks.lock().unwrap();
+        let callback = if down {
+            &mut callbacks.pressed
+        } else {
+            &mut callbacks.released
+        };
+        if let Some(callback) = callback {
+            callback(key)
+        }
+    }
+
+    /// The GPIO pins of the rows.
+    pub fn rows(&self) -> &[u8] {
+        &self.rows
+    }
+
+    /// The GPIO pins of the columns.
+    pub fn columns(&self) -> &[u8] {
+        &self.columns
+    }
+
+    /// The keys currently held down, in the order they were pressed.
+    pub fn pressed_keys(&self) -> Vec<char> {
+        self.state.0.lock().unwrap().pressed.clone()
+    }
+
+    /// Returns ``True`` if any key is held down.
+    pub fn is_active(&self) -> bool {
+        !self.state.0.lock().unwrap().pressed.is_empty()
+    }
+
+    /// Take the oldest key press not read yet, waiting for one if there is none.
+    /// Returns None if the timeout was reached first.
+    /// * `timeout` - Number of seconds to wait. If this is None, then wait indefinitely.
+    pub fn read_key(&mut self, timeout: Option<f32>) -> Option<char> {
+        let (state, arrived) = &*self.state;
+        let deadline = timeout_duration(timeout).and_then(|t| Instant::now().checked_add(t));
+        let mut state = state.lock().unwrap();
+        loop {
+            if let Some(key) = state.buffer.pop_front() {
+                return Some(key);
+            }
+            state = match deadline {
+                None => arrived.wait(state).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return None;
+                    }
+                    arrived.wait_timeout(state, deadline - now).unwrap().0
+                }
+            };
+        }
+    }
+
+    /// Forget the key presses not read yet.
+    pub fn clear(&mut self) {
+        self.state.0.lock().unwrap().buffer.clear();
+    }
+
+    /// Set the function called from a background thread with the key pressed.
+    pub fn when_key_pressed<C>(&mut self, callback: C)
+    where
+        C: FnMut(char) + Send + 'static,
+    {
+        self.callbacks.lock().unwrap().pressed = Some(Box::new(callback));
+    }
+
+    /// Set the function called from a background thread with the key released.
+    pub fn when_key_released<C>(&mut self, callback: C)
+    where
+        C: FnMut(char) + Send + 'static,
+    {
+        self.callbacks.lock().unwrap().released = Some(Box::new(callback));
+    }
+
+    /// Shut down the device and release all associated resources.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+impl Drop for Keypad {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+/// How many samples of the pulse count a `PulseCounter` keeps per window.
+const PULSE_COUNTER_SAMPLES: u32 = 10;
+
+/// The window of a `PulseCounter` and the pulse counts sampled over it.
+type PulseSamples = (Duration, VecDeque<(Instant, u64)>);
+
+/// Represents a device sending pulses whose rate is measured, such as an anemometer, a flow
+/// meter or the tachometer output of a fan.
+///
+/// Every matching edge on the pin increments a counter from the pin's interrupt. A
+/// background thread samples the counter ten times per window, and the rate is the number
+/// of pulses counted over the last window.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+/// use std::time::Duration;
+///
+/// // an anemometer closing a reed switch to ground once per turn
+/// let mut anemometer = PulseCounter::new(5);
+/// anemometer.set_window(Duration::from_secs(5));
+/// println!("{:.1} turns per second", anemometer.pulses_per_second());
+/// ```
+#[derive(Debug)]
+pub struct PulseCounter {
+    pin: InputLine,
+    edge: Edge,
+    total: Arc<AtomicU64>,
+    samples: Arc<Mutex<PulseSamples>>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl PulseCounter {
+    /// Returns a PulseCounter counting falling edges, with the pin pulled up and a window
+    /// of 1 second. This suits open-collector outputs and switches to ground.
+    /// * `pin` - The GPIO pin which the device is attached to
+    pub fn new(pin: u8) -> PulseCounter {
+        PulseCounter::new_with_edge(pin, Bias::PullUp, Edge::Falling)
+    }
+
+    /// Returns a PulseCounter counting the edges given, with a window of 1 second.
+    /// * `pin` - The GPIO pin which the device is attached to
+    /// * `bias` - The internal resistor to enable
+    /// * `edge` - The edge counted as a pulse
+    pub fn new_with_edge(pin: u8, bias: Bias, edge: Edge) -> PulseCounter {
+        let mut pin = match Gpio::new() {
+            Err(e) => panic!("{:?}", e),
+            Ok(gpio) => match gpio.get(pin) {
+                Err(e) => panic!("{:?}", e),
+                Ok(pin) => InputLine::new(match bias {
+                    Bias::PullUp => pin.into_input_pullup(),
+                    Bias::PullDown => pin.into_input_pulldown(),
+                    Bias::Floating => pin.into_input(),
+                }),
+            },
+        };
+        let total = Arc::new(AtomicU64::new(0));
+        let trigger = match edge {
+            Edge::Rising => Trigger::RisingEdge,
+            Edge::Falling => Trigger::FallingEdge,
+        };
+        {
+            let total = Arc::clone(&total);
+            let result = pin.set_async_interrupt(trigger, move |_| {
+                total.fetch_add(1, Ordering::SeqCst);
+            });
+            if let Err(e) = result {
+                panic!("{:?}", e)
+            }
+        }
+
+        let samples = Arc::new(Mutex::new((Duration::from_secs(1), VecDeque::new())));
+        let running = Arc::new(AtomicBool::new(true));
+        let handle = {
+            let total = Arc::clone(&total);
+            let samples = Arc::clone(&samples);
+            let running = Arc::clone(&running);
+            thread::spawn(move || {
+                while running.load(Ordering::SeqCst) {
+                    let interval = {
+                        let mut samples = samples.lock().unwrap();
+                        let (window, samples) = &mut *samples;
+                        let now = Instant::now();
+                        samples.push_back((now, total.load(Ordering::SeqCst)));
+                        // keep one sample at least a window old to measure from
+                        while samples.len() > 2 && now.duration_since(samples[1].0) >= *window {
+                            samples.pop_front();
+                        }
+                        *window / PULSE_COUNTER_SAMPLES
+                    };
+                    sleep_while_running(&running, interval);
+                }
+            })
+        };
+
+        PulseCounter {
+            pin,
+            edge,
+            total,
+            samples,
+            running,
+            handle: Some(handle),
+        }
+    }
+
+    impl_gpio_device!();
+    impl_input_reset!();
+
+    /// The edge counted as a pulse.
+    pub fn edge(&self) -> Edge {
+        self.edge
+    }
+
+    /// The number of pulses counted since the device was created or reset.
+    pub fn total_pulses(&self) -> u64 {
+        self.total.load(Ordering::SeqCst)
+    }
+
+    /// The rate of pulses over the last window, or over the time since the device was
+    /// created or reset if that is shorter.
+    pub fn pulses_per_second(&self) -> f64 {
+        let samples = self.samples.lock().unwrap();
+        let (oldest, newest) = match (samples.1.front(), samples.1.back()) {
+            (Some(oldest), Some(newest)) => (*oldest, *newest),
+            _ => return 0.0,
+        };
+        let elapsed = newest.0.duration_since(oldest.0).as_secs_f64();
+        if elapsed <= 0.0 {
+            return 0.0;
+        }
+        newest.1.saturating_sub(oldest.1) as f64 / elapsed
+    }
+
+    /// The rate of pulses in Hz; the same as `pulses_per_second`.
+    pub fn value(&self) -> f64 {
+        self.pulses_per_second()
+    }
+
+    /// Returns ``True`` if any pulse arrived in the last window.
+    pub fn is_active(&self) -> bool {
+        self.pulses_per_second() > 0.0
+    }
+
+    /// The time the rate is measured over.
+    pub fn window(&self) -> Duration {
+        self.samples.lock().unwrap().0
+    }
+
+    /// Set the time the rate is measured over. A longer window gives a steadier rate for
+    /// slow pulses, a shorter one follows changes faster. Defaults to 1 second.
+    pub fn set_window(&mut self, window: Duration) {
+        if window.is_zero() {
+            println!("Window must be greater than 0");
+            return;
+        }
+        self.samples.lock().unwrap().0 = window;
+        if let Some(handle) = &self.handle {
+            handle.thread().unpark();
+        }
+    }
+
+    /// Set the pulse count back to zero and forget the rate.
+    pub fn reset(&mut self) {
+        let mut samples = self.samples.lock().unwrap();
+        samples.1.clear();
+        self.total.store(0, Ordering::SeqCst);
+    }
+
+    /// Test hook: push a synthetic edge on the pin, counted if it matches the edge counted.
+    #[cfg(feature = "inject")]
+    pub fn inject_event(&mut self, edge: Edge) {
+        self.pin.inject_edge(edge)
+    }
+
+    /// Shut down the device and release all associated resources.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+impl Drop for PulseCounter {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+        if self.pin.clear_async_interrupt().is_err() {
+            println!("Could not clear interrupt for pin");
+        }
+    }
+}
+
+/// The stall watch of a `Tachometer`.
+#[derive(Debug)]
+struct StallWatch {
+    interval: Duration,
+    last_total: u64,
+    last_change: Instant,
+    stalled: bool,
+}
+
+/// Represents a rotating device reporting its speed with pulses, such as the tachometer
+/// wire of a 4-pin PC fan.
+///
+/// The speed is worked out by a `PulseCounter`. When no pulse arrives for the stall
+/// interval the device is stalled and the `when_stalled` callback is called once, until
+/// pulses arrive again. A fan that never starts is reported as stalled too.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut fan = Motor::new(17, 18);
+/// // PC fans pull the tachometer wire low twice per revolution
+/// let mut tachometer = Tachometer::new(23);
+/// tachometer.when_stalled(|| println!("Fan stalled"));
+/// fan.forward();
+/// println!("{:.0} rpm", tachometer.rpm());
+/// ```
+pub struct Tachometer {
+    counter: PulseCounter,
+    pulses_per_revolution: u32,
+    watch: Arc<Mutex<StallWatch>>,
+    stalled_callback: Arc<Mutex<Option<Callback>>>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Tachometer {
+    /// Returns a Tachometer counting 2 pulses per revolution on falling edges, with the pin
+    /// pulled up and a stall interval of 1 second
+    /// * `pin` - The GPIO pin which the tachometer output is attached to
+    pub fn new(pin: u8) -> Tachometer {
+        Tachometer::from_counter(PulseCounter::new(pin))
+    }
+
+    /// Returns a Tachometer measuring the speed with the pulse counter given, counting 2
+    /// pulses per revolution with a stall interval of 1 second
+    pub fn from_counter(counter: PulseCounter) -> Tachometer {
+        let watch = Arc::new(Mutex::new(StallWatch {
+            interval: Duration::from_secs(1),
+            last_total: counter.total_pulses(),
+            last_change: Instant::now(),
+            stalled: false,
+        }));
+        let stalled_callback: Arc<Mutex<Option<Callback>>> = Arc::new(Mutex::new(None));
+        let running = Arc::new(AtomicBool::new(true));
+        let handle = {
+            let total = Arc::clone(&counter.total);
+            let watch = Arc::clone(&watch);
+            let stalled_callback = Arc::clone(&stalled_callback);
+            let running = Arc::clone(&running);
+            thread::spawn(move || {
+                while running.load(Ordering::SeqCst) {
+                    let stalled = {
+                        let mut watch = watch.lock().unwrap();
+                        let total = total.load(Ordering::SeqCst);
+                        let now = Instant::now();
+                        if total != watch.last_total {
+                            watch.last_total = total;
+                            watch.last_change = now;
+                            watch.stalled = false;
+                            false
+                        } else if !watch.stalled
+                            && now.duration_since(watch.last_change) >= watch.interval
+                        {
+                            watch.stalled = true;
+                            true
+                        } else {
+                            false
+                        }
+                    };
+                    if stalled {
+                        if let Some(callback) = stalled_callback.lock().unwrap().as_mut() {
+                            callback();
+                        }
+                    }
+                    sleep_while_running(&running, Duration::from_millis(20));
+                }
+            })
+        };
+        Tachometer {
+            counter,
+            pulses_per_revolution: 2,
+            watch,
+            stalled_callback,
+            running,
+            handle: Some(handle),
+        }
+    }
+
+    /// The pulse counter measuring the speed.
+    pub fn counter(&self) -> &PulseCounter {
+        &self.counter
+    }
+
+    /// The pulse counter measuring the speed, e.g. to change its window.
+    pub fn counter_mut(&mut self) -> &mut PulseCounter {
+        &mut self.counter
+    }
+
+    /// The number of pulses sent for each revolution.
+    pub fn pulses_per_revolution(&self) -> u32 {
+        self.pulses_per_revolution
+    }
+
+    /// Set the number of pulses sent for each revolution. Defaults to 2, as sent by PC fans.
+    pub fn set_pulses_per_revolution(&mut self, pulses: u32) {
+        if pulses == 0 {
+            println!("Pulses per revolution must be greater than 0");
+            return;
+        }
+        self.pulses_per_revolution = pulses;
+    }
+
+    /// The speed in revolutions per minute, measured over the window of the pulse counter.
+    pub fn rpm(&self) -> f64 {
+        if self.is_stalled() {
+            return 0.0;
+        }
+        self.counter.pulses_per_second() * 60.0 / f64::from(self.pulses_per_revolution)
+    }
+
+    /// The number of revolutions since the device was created or reset.
+    pub fn revolutions(&self) -> f64 {
+        self.counter.total_pulses() as f64 / f64::from(self.pulses_per_revolution)
+    }
+
+    /// The speed in revolutions per minute; the same as `rpm`.
+    pub fn value(&self) -> f64 {
+        self.rpm()
+    }
+
+    /// Returns ``True`` if the device is turning.
+    pub fn is_active(&self) -> bool {
+        !self.is_stalled() && self.counter.is_active()
+    }
+
+    /// Returns ``True`` if no pulse arrived during the last stall interval.
+    pub fn is_stalled(&self) -> bool {
+        self.watch.lock().unwrap().stalled
+    }
+
+    /// How long without a pulse before the device is stalled.
+    pub fn stall_interval(&self) -> Duration {
+        self.watch.lock().unwrap().interval
+    }
+
+    /// Set how long without a pulse before the device is stalled. It should be longer than
+    /// the time between two pulses at the slowest speed expected. Defaults to 1 second.
+    pub fn set_stall_interval(&mut self, interval: Duration) {
+        if interval.is_zero() {
+            println!("Stall interval must be greater than 0");
+            return;
+        }
+        self.watch.lock().unwrap().interval = interval;
+    }
+
+    /// Set the function called from a background thread when the device stalls.
+    pub fn when_stalled<C>(&mut self, callback: C)
+    where
+        C: FnMut() + Send + 'static,
+    {
+        *self.stalled_callback.lock().unwrap() = Some(Box::new(callback));
+    }
+
+    /// Set the pulse count back to zero and restart the stall interval.
+    pub fn reset(&mut self) {
+        self.counter.reset();
+        let mut watch = self.watch.lock().unwrap();
+        watch.last_total = 0;
+        watch.last_change = Instant::now();
+        watch.stalled = false;
+    }
+
+    /// Shut down the device and release all associated resources.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+impl Drop for Tachometer {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+/// The edge timings of a `PWMInputDevice`.
+#[derive(Debug)]
+struct PwmTiming {
+    rise: Option<Instant>,
+    fall: Option<Instant>,
+    high: Option<Duration>,
+    period: Option<Duration>,
+    timeout: Duration,
+}
+
+impl PwmTiming {
+    /// Returns ``True`` if no edge arrived within the timeout, so the signal is steady.
+    fn is_steady(&self) -> bool {
+        match self.rise.max(self.fall) {
+            Some(last) => last.elapsed() > self.timeout,
+            None => true,
+        }
+    }
+}
+
+/// Represents a device sending a PWM signal, such as an RC receiver or another controller,
+/// measuring its frequency, duty cycle and pulse width.
+///
+/// Both edges are timestamped from the pin's interrupt; the pulse width is the time from a
+/// rising edge to the next falling edge and the period the time between rising edges. When
+/// no edge arrives for the timeout the signal is steady: the frequency is 0.0 and the duty
+/// cycle 0.0 or 1.0 depending on the level of the pin. The timestamps come from an
+/// interrupt thread, so expect some tens of microseconds of jitter.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// // the throttle channel of an RC receiver, 1 to 2 ms pulses at 50 Hz
+/// let throttle = PWMInputDevice::new(17);
+/// if let Some(width) = throttle.pulse_width() {
+///     println!("{} us at {:.0} Hz", width.as_micros(), throttle.frequency());
+/// }
+/// ```
+#[derive(Debug)]
+pub struct PWMInputDevice {
+    pin: InputLine,
+    timing: Arc<Mutex<PwmTiming>>,
+}
+
+impl PWMInputDevice {
+    /// Returns a PWMInputDevice with the pin floating and a timeout of 100 milliseconds, for
+    /// a signal driven both high and low
+    /// * `pin` - The GPIO pin which the device is attached to
+    pub fn new(pin: u8) -> PWMInputDevice {
+        PWMInputDevice::new_with_bias(pin, Bias::Floating)
+    }
+
+    /// Returns a PWMInputDevice with a timeout of 100 milliseconds
+    /// * `pin` - The GPIO pin which the device is attached to
+    /// * `bias` - The internal resistor to enable, e.g. a pull-up for an open-collector output
+    pub fn new_with_bias(pin: u8, bias: Bias) -> PWMInputDevice {
+        let mut pin = match Gpio::new() {
+            Err(e) => panic!("{:?}", e),
+            Ok(gpio) => match gpio.get(pin) {
+                Err(e) => panic!("{:?}", e),
+                Ok(pin) => InputLine::new(match bias {
+                    Bias::PullUp => pin.into_input_pullup(),
+                    Bias::PullDown => pin.into_input_pulldown(),
+                    Bias::Floating => pin.into_input(),
+                }),
+            },
+        };
+        let timing = Arc::new(Mutex::new(PwmTiming {
+            rise: None,
+            fall: None,
+            high: None,
+            period: None,
+            timeout: Duration::from_millis(100),
+        }));
+        {
+            let timing = Arc::clone(&timing);
+            let result = pin.set_async_interrupt(Trigger::Both, move |level| {
+                let now = Instant::now();
+                let mut timing = timing.lock().unwrap();
+                match level {
+                    Level::High => {
+                        timing.period = timing.rise.map(|rise| now.duration_since(rise));
+                        timing.rise = Some(now);
+                    }
+                    Level::Low => {
+                        timing.high = timing.rise.map(|rise| now.duration_since(rise));
+                        timing.fall = Some(now);
+                    }
+                }
+            });
+            if let Err(e) = result {
+                panic!("{:?}", e)
+            }
+        }
+        PWMInputDevice { pin, timing }
+    }
+
+    impl_gpio_device!();
+    impl_input_reset!();
+
+    /// The frequency of the signal in Hz, or 0.0 if it is steady.
+    pub fn frequency(&self) -> f64 {
+        let timing = self.timing.lock().unwrap();
+        match timing.period {
+            Some(period) if !timing.is_steady() && !period.is_zero() => 1.0 / period.as_secs_f64(),
+            _ => 0.0,
+        }
+    }
+
+    /// The fraction of each period the signal is high, between 0.0 and 1.0.
+    pub fn duty_cycle(&self) -> f64 {
+        let timing = self.timing.lock().unwrap();
+        match (timing.high, timing.period) {
+            (Some(high), Some(period)) if !timing.is_steady() && !period.is_zero() => {
+                (high.as_secs_f64() / period.as_secs_f64()).clamp(0.0, 1.0)
+            }
+            _ => match self.pin.read() {
+                Level::High => 1.0,
+                Level::Low => 0.0,
+            },
+        }
+    }
+
+    /// The time the signal is high in each period, or None if it is steady.
+    pub fn pulse_width(&self) -> Option<Duration> {
+        let timing = self.timing.lock().unwrap();
+        if timing.is_steady() {
+            None
+        } else {
+            timing.high
+        }
+    }
+
+    /// The duty cycle of the signal; the same as `duty_cycle`.
+    pub fn value(&self) -> f64 {
+        self.duty_cycle()
+    }
+
+    /// Returns ``True`` if the duty cycle is above 0.0.
+    pub fn is_active(&self) -> bool {
+        self.duty_cycle() > 0.0
+    }
+
+    /// How long without an edge before the signal is steady.
+    pub fn timeout(&self) -> Duration {
+        self.timing.lock().unwrap().timeout
+    }
+
+    /// Set how long without an edge before the signal is steady. It must be longer than the
+    /// period of the slowest signal measured. Defaults to 100 milliseconds.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        if timeout.is_zero() {
+            println!("Timeout must be greater than 0");
+            return;
+        }
+        self.timing.lock().unwrap().timeout = timeout;
+    }
+
+    /// Test hook: push a synthetic edge on the pin, timestamped as it arrives.
+    #[cfg(feature = "inject")]
+    pub fn inject_event(&mut self, edge: Edge) {
+        self.pin.inject_edge(edge)
+    }
+
+    /// Shut down the device and release all associated resources.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+impl Drop for PWMInputDevice {
+    fn drop(&mut self) {
+        if self.pin.clear_async_interrupt().is_err() {
+            println!("Could not clear interrupt for pin");
+        }
+    }
+}
+
+/// A card read by a `WiegandReader`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WiegandCard {
+    /// The length of the frame, 26 or 34 bits
+    pub bits: u32,
+    /// The facility or site code
+    pub facility: u32,
+    /// The card number within the facility
+    pub number: u32,
+    /// The facility code and card number together, as printed on many cards
+    pub id: u64,
+}
+
+/// What a complete Wiegand frame decodes to.
+pub(crate) enum WiegandFrame {
+    Card(WiegandCard),
+    Key(char),
+    Invalid,
+}
+
+impl WiegandFrame {
+    fn decode(bits: u64, count: u32) -> WiegandFrame {
+        let ones = |value: u64| value.count_ones();
+        // frames wrap the data with an even parity bit over its first half and an odd
+        // parity bit over its second half
+        let card = |half: u32| {
+            let data = (bits >> 1) & ((1 << (2 * half)) - 1);
+            let mask = (1 << half) - 1;
+            let even = (bits >> (2 * half + 1)) & 1;
+            let odd = bits & 1;
+            if !(even + u64::from(ones(data >> half))).is_multiple_of(2)
+                || (odd + u64::from(ones(data & mask))).is_multiple_of(2)
+            {
+                return WiegandFrame::Invalid;
+            }
+            // 26 bit cards have an 8 bit facility code, 34 bit cards a 16 bit one
+            WiegandFrame::Card(WiegandCard {
+                bits: count,
+                facility: (data >> 16) as u32,
+                number: (data & 0xFFFF) as u32,
+                id: data,
+            })
+        };
+        let key = |value: u64| match value {
+            0..=9 => WiegandFrame::Key(char::from(b'0' + value as u8)),
+            10 => WiegandFrame::Key('*'),
+            11 => WiegandFrame::Key('#'),
+            _ => WiegandFrame::Invalid,
+        };
+        match count {
+            4 => key(bits),
+            // the key in the low nibble and its complement in the high one
+            8 if bits >> 4 == !bits & 0xF => key(bits & 0xF),
+            26 => card(12),
+            34 => card(16),
+            _ => WiegandFrame::Invalid,
+        }
+    }
+}
+
+/// The bits of a `WiegandReader` frame being received.
+#[derive(Debug)]
+pub(crate) struct WiegandBits {
+    bits: u64,
+    count: u32,
+    last_bit: Option<Instant>,
+    timeout: Duration,
+    last_card: Option<WiegandCard>,
+    errors: u64,
+}
+
+impl WiegandBits {
+    pub(crate) fn new(timeout: Duration) -> WiegandBits {
+        WiegandBits {
+            bits: 0,
+            count: 0,
+            last_bit: None,
+            timeout,
+            last_card: None,
+            errors: 0,
+        }
+    }
+
+    /// The number of bits received so far.
+    pub(crate) fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Add a bit received at the time given.
+    pub(crate) fn push(&mut self, bit: bool, at: Instant) {
+        self.bits = self.bits << 1 | u64::from(bit);
+        self.count += 1;
+        self.last_bit = Some(at);
+    }
+
+    /// Decode and clear the frame if no bit has arrived for the inter-frame timeout by `now`.
+    pub(crate) fn take(&mut self, now: Instant) -> Option<WiegandFrame> {
+        let complete = self
+            .last_bit
+            .is_some_and(|last| now.saturating_duration_since(last) >= self.timeout);
+        if !complete {
+            return None;
+        }
+        let decoded = if self.count <= 64 {
+            WiegandFrame::decode(self.bits, self.count)
+        } else {
+            WiegandFrame::Invalid
+        };
+        match decoded {
+            WiegandFrame::Card(card) => self.last_card = Some(card),
+            WiegandFrame::Invalid => self.errors += 1,
+            WiegandFrame::Key(_) => (),
+        }
+        self.bits = 0;
+        self.count = 0;
+        self.last_bit = None;
+        Some(decoded)
+    }
+}
+
+/// The callbacks of a `WiegandReader`.
+#[derive(Default)]
+struct WiegandCallbacks {
+    card: Option<Box<dyn FnMut(u64) + Send>>,
+    key: Option<KeyCallback>,
+}
+
+/// Represents a Wiegand reader such as an RFID reader or keypad on an access panel.
+///
+/// The reader pulls D0 low to send a 0 and D1 low to send a 1. A frame is complete when no
+/// bit arrives for the inter-frame timeout. 26 and 34 bit frames are decoded as cards and
+/// checked against their parity bits, and 4 and 8 bit frames as keys from a keypad
+/// sending one key per frame. Frames failing to decode are counted as errors.
+///
+/// The D0 and D1 lines of most readers are 5V, so connect them through a level shifter or
+/// a resistor divider.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut reader = WiegandReader::new(14, 15);
+/// reader.when_card_scanned(|id| println!("card {} scanned", id));
+/// reader.when_key_pressed(|key| println!("{} pressed", key));
+/// ```
+pub struct WiegandReader {
+    d0: InputLine,
+    d1: InputLine,
+    frame: Arc<Mutex<WiegandBits>>,
+    callbacks: Arc<Mutex<WiegandCallbacks>>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl WiegandReader {
+    /// Returns a WiegandReader with both lines pulled up and an inter-frame timeout of 25
+    /// milliseconds
+    /// * `d0` - The GPIO pin which the D0 (data 0) line is attached to
+    /// * `d1` - The GPIO pin which the D1 (data 1) line is attached to
+    pub fn new(d0: u8, d1: u8) -> WiegandReader {
+        let gpio = match Gpio::new() {
+            Err(e) => panic!("{:?}", e),
+            Ok(gpio) => gpio,
+        };
+        let line = |pin: u8| match gpio.get(pin) {
+            Err(e) => panic!("{:?}", e),
+            Ok(pin) => InputLine::new(pin.into_input_pullup()),
+        };
+        let (mut d0, mut d1) = (line(d0), line(d1));
+        let frame = Arc::new(Mutex::new(WiegandBits::new(Duration::from_millis(25))));
+        for (line, bit) in [(&mut d0, false), (&mut d1, true)] {
+            let frame = Arc::clone(&frame);
+            let result = line.set_async_interrupt(Trigger::FallingEdge, move |_| {
+                frame.lock().unwrap().push(bit, Instant::now());
+            });
+            if let Err(e) = result {
+                panic!("{:?}", e)
+            }
+        }
+
+        let callbacks = Arc::new(Mutex::new(WiegandCallbacks::default()));
+        let running = Arc::new(AtomicBool::new(true));
+        let handle = {
+            let frame = Arc::clone(&frame);
+            let callbacks = Arc::clone(&callbacks);
+            let running = Arc::clone(&running);
+            thread::spawn(move || {
+                while running.load(Ordering::SeqCst) {
+                    let decoded = frame.lock().unwrap().take(Instant::now());
+                    let mut callbacks = callbacks.lock().unwrap();
+                    match decoded {
+                        Some(WiegandFrame::Card(card)) => {
+                            if let Some(callback) = callbacks.card.as_mut() {
+                                callback(card.id);
+                            }
+                        }
+                        Some(WiegandFrame::Key(key)) => {
+                            if let Some(callback) = callbacks.key.as_mut() {
+                                callback(key);
+                            }
+                        }
+                        _ => (),
+                    }
+                    drop(callbacks);
+                    sleep_while_running(&running, Duration::from_millis(5));
+                }
+            })
+        };
+
+        WiegandReader {
+            d0,
+            d1,
+            frame,
+            callbacks,
+            running,
+            handle: Some(handle),
+        }
+    }
+
+    /// The GPIO pins of the D0 and D1 lines.
+    pub fn pins(&self) -> (u8, u8) {
+        (self.d0.pin(), self.d1.pin())
+    }
+
+    /// Set the function called from a background thread with the id of every card read.
+    pub fn when_card_scanned<C>(&mut self, callback: C)
+    where
+        C: FnMut(u64) + Send + 'static,
+    {
+        self.callbacks.lock().unwrap().card = Some(Box::new(callback));
+    }
+
+    /// Set the function called from a background thread with every key pressed on a
+    /// keypad sending 4 or 8 bit frames.
+    pub fn when_key_pressed<C>(&mut self, callback: C)
+    where
+        C: FnMut(char) + Send + 'static,
+    {
+        self.callbacks.lock().unwrap().key = Some(Box::new(callback));
+    }
+
+    /// The last card read, if any.
+    pub fn last_card(&self) -> Option<WiegandCard> {
+        self.frame.lock().unwrap().last_card
+    }
+
+    /// The number of frames which failed their parity check or had an unknown length.
+    pub fn errors(&self) -> u64 {
+        self.frame.lock().unwrap().errors
+    }
+
+    /// How long without a bit before a frame is complete.
+    pub fn timeout(&self) -> Duration {
+        self.frame.lock().unwrap().timeout
+    }
+
+    /// Set how long without a bit before a frame is complete. It must be longer than the
+    /// time between two bits, typically 2 milliseconds. Defaults to 25 milliseconds.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        if timeout.is_zero() {
+            println!("Timeout must be greater than 0");
+            return;
+        }
+        self.frame.lock().unwrap().timeout = timeout;
+    }
+
+    /// Shut down the device and release all associated resources.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+impl Drop for WiegandReader {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+        for line in [&mut self.d0, &mut self.d1] {
+            if line.clear_async_interrupt().is_err() {
+                println!("Could not clear interrupt for pin");
+            }
+        }
+    }
+}
+
+/// How long an NEC key stays held after the last frame or repeat code.
+pub(crate) const NEC_HOLD: Duration = Duration::from_millis(120);
+
+/// What a run of edges from an IR receiver decodes to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum NecEvent {
+    Code(u16, u8),
+    Repeat,
+}
+
+/// Where an `NecDecoder` is in a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NecState {
+    Idle,
+    Leader,
+    Space(u32, u32),
+    Bit(u32, u32),
+}
+
+/// Decodes NEC frames from the lengths of the bursts and spaces sent by a receiver.
+#[derive(Debug)]
+pub(crate) struct NecDecoder {
+    state: NecState,
+}
+
+impl NecDecoder {
+    pub(crate) fn new() -> NecDecoder {
+        NecDecoder {
+            state: NecState::Idle,
+        }
+    }
+
+    /// Returns ``True`` if `duration` is within 35% of `micros`.
+    fn near(duration: Duration, micros: u64) -> bool {
+        let micros = micros as f64;
+        let duration = duration.as_micros() as f64;
+        duration > micros * 0.65 && duration < micros * 1.35
+    }
+
+    /// Feed a burst (``True``) or a space (``False``) and its length.
+    pub(crate) fn feed(&mut self, burst: bool, duration: Duration) -> Option<NecEvent> {
+        let (state, event) = match (self.state, burst) {
+            (_, true) if NecDecoder::near(duration, 9000) => (NecState::Leader, None),
+            (NecState::Leader, false) if NecDecoder::near(duration, 4500) => {
+                (NecState::Space(0, 0), None)
+            }
+            (NecState::Leader, false) if NecDecoder::near(duration, 2250) => {
+                (NecState::Idle, Some(NecEvent::Repeat))
+            }
+            (NecState::Space(bits, count), true) if NecDecoder::near(duration, 560) => {
+                (NecState::Bit(bits, count), None)
+            }
+            (NecState::Bit(bits, count), false) => {
+                // bits are sent least significant first
+                let bit = if NecDecoder::near(duration, 1690) {
+                    1
+                } else if NecDecoder::near(duration, 560) {
+                    0
+                } else {
+                    self.state = NecState::Idle;
+                    return None;
+                };
+                let (bits, count) = (bits | bit << count, count + 1);
+                if count < 32 {
+                    (NecState::Space(bits, count), None)
+                } else {
+                    (NecState::Idle, NecDecoder::frame(bits))
+                }
+            }
+            _ => (NecState::Idle, None),
+        };
+        self.state = state;
+        event
+    }
+
+    /// Check the inverted command byte of a frame and split it into address and command.
+    /// Frames whose address byte is not followed by its inverse carry a 16 bit address.
+    fn frame(bits: u32) -> Option<NecEvent> {
+        let [address_low, address_high, command, inverse] = bits.to_le_bytes();
+        if command != !inverse {
+            return None;
+        }
+        let address = if address_low == !address_high {
+            u16::from(address_low)
+        } else {
+            u16::from_le_bytes([address_low, address_high])
+        };
+        Some(NecEvent::Code(address, command))
+    }
+}
+
+/// The last code of an `IRReceiver` and when it was last received or repeated.
+#[derive(Debug)]
+struct IRCode {
+    code: Option<(u16, u8)>,
+    seen: Option<Instant>,
+}
+
+/// The callbacks of an `IRReceiver`.
+#[derive(Default)]
+struct IRCallbacks {
+    received: Option<Box<dyn FnMut(u16, u8) + Send>>,
+    repeated: Option<Box<dyn FnMut(u16, u8) + Send>>,
+}
+
+/// Represents an infrared receiver module such as the TSOP38238, decoding the NEC protocol
+/// used by most remote controls.
+///
+/// The module demodulates the 38 kHz carrier and pulls its output low during each burst.
+/// The edges are timestamped from the pin's interrupt and decoded on a background thread.
+/// While a key is held the remote sends a repeat code every 108 milliseconds instead of
+/// the frame; these call the `when_code_repeated` callback with the code last received.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut receiver = IRReceiver::new(18);
+/// receiver.when_code_received(|address, command| {
+///     println!("address {:#04x} command {:#04x}", address, command)
+/// });
+/// receiver.when_code_repeated(|_, command| println!("{:#04x} held", command));
+/// ```
+pub struct IRReceiver {
+    pin: InputLine,
+    last: Arc<Mutex<IRCode>>,
+    callbacks: Arc<Mutex<IRCallbacks>>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl IRReceiver {
+    /// Returns an IRReceiver with the pin pulled up
+    /// * `pin` - The GPIO pin which the output of the receiver is attached to
+    pub fn new(pin: u8) -> IRReceiver {
+        let mut pin = match Gpio::new() {
+            Err(e) => panic!("{:?}", e),
+            Ok(gpio) => match gpio.get(pin) {
+                Err(e) => panic!("{:?}", e),
+                Ok(pin) => InputLine::new(pin.into_input_pullup()),
+            },
+        };
+        let (edges, received) = mpsc::channel::<(Level, Instant)>();
+        {
+            let edges = Mutex::new(edges);
+            let result = pin.set_async_interrupt(Trigger::Both, move |level| {
+                let _ = edges.lock().unwrap().send((level, Instant::now()));
+            });
+            if let Err(e) = result {
+                panic!("{:?}", e)
+            }
+        }
+
+        let last = Arc::new(Mutex::new(IRCode {
+            code: None,
+            seen: None,
+        }));
+        let callbacks = Arc::new(Mutex::new(IRCallbacks::default()));
+        let running = Arc::new(AtomicBool::new(true));
+        let handle = {
+            let last = Arc::clone(&last);
+            let callbacks = Arc::clone(&callbacks);
+            let running = Arc::clone(&running);
+            thread::spawn(move || {
+                let mut decoder = NecDecoder::new();
+                let mut previous: Option<Instant> = None;
+                while running.load(Ordering::SeqCst) {
+                    let (level, at) = match received.recv_timeout(Duration::from_millis(100)) {
+                        Ok(edge) => edge,
+                        Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                        Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                    };
+                    let event = previous.and_then(|previous| {
+                        // a rising edge ends a burst, as the output is low during bursts
+                        decoder.feed(level == Level::High, at.duration_since(previous))
+                    });
+                    previous = Some(at);
+                    let code = {
+                        let mut last = last.lock().unwrap();
+                        match event {
+                            Some(NecEvent::Code(address, command)) => {
+                                last.code = Some((address, command));
+                                last.seen = Some(at);
+                                Some((address, command, false))
+                            }
+                            Some(NecEvent::Repeat) => {
+                                let held = last
+                                    .seen
+                                    .is_some_and(|seen| at.duration_since(seen) < NEC_HOLD);
+                                match last.code {
+                                    Some((address, command)) if held => {
+                                        last.seen = Some(at);
+                                        Some((address, command, true))
+                                    }
+                                    _ => None,
+                                }
+                            }
+                            None => None,
+                        }
+                    };
+                    if let Some((address, command, repeat)) = code {
+                        let mut callbacks = callbacks.lock().unwrap();
+                        let callback = if repeat {
+                            callbacks.repeated.as_mut()
+                        } else {
+                            callbacks.received.as_mut()
+                        };
+                        if let Some(callback) = callback {
+                            callback(address, command);
+                        }
+                    }
+                }
+            })
+        };
+
+        IRReceiver {
+            pin,
+            last,
+            callbacks,
+            running,
+            handle: Some(handle),
+        }
+    }
+
+    impl_gpio_device!();
+
+    /// Set the function called from a background thread with the address and command of
+    /// every frame received.
+    pub fn when_code_received<C>(&mut self, callback: C)
+    where
+        C: FnMut(u16, u8) + Send + 'static,
+    {
+        self.callbacks.lock().unwrap().received = Some(Box::new(callback));
+    }
+
+    /// Set the function called from a background thread with the address and command last
+    /// received for every repeat code sent while a key is held.
+    pub fn when_code_repeated<C>(&mut self, callback: C)
+    where
+        C: FnMut(u16, u8) + Send + 'static,
+    {
+        self.callbacks.lock().unwrap().repeated = Some(Box::new(callback));
+    }
+
+    /// The address and command of the last frame received, if any.
+    pub fn last_code(&self) -> Option<(u16, u8)> {
+        self.last.lock().unwrap().code
+    }
+
+    /// Returns ``True`` while a key is held on the remote.
+    pub fn is_active(&self) -> bool {
+        self.last
+            .lock()
+            .unwrap()
+            .seen
+            .is_some_and(|seen| seen.elapsed() < NEC_HOLD)
+    }
+
+    /// Shut down the device and release all associated resources.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+impl Drop for IRReceiver {
+    fn drop(&mut self) {
+        if self.pin.clear_async_interrupt().is_err() {
+            println!("Could not clear interrupt for pin");
+        }
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// The kind of sensor attached to a `DHTSensor`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DHTModel {
+    /// The blue DHT11: 0-50°C and 20-80% humidity, one reading a second
+    DHT11,
+    /// The white DHT22 or AM2302: -40-80°C and 0-100% humidity, one reading every 2 seconds
+    DHT22,
+}
+
+impl DHTModel {
+    /// How long the host holds the line low to wake the sensor.
+    pub(crate) fn start_signal(self) -> Duration {
+        match self {
+            DHTModel::DHT11 => Duration::from_millis(20),
+            DHTModel::DHT22 => Duration::from_micros(1100),
+        }
+    }
+
+    /// The shortest time between readings the sensor supports.
+    fn min_interval(self) -> Duration {
+        match self {
+            DHTModel::DHT11 => Duration::from_secs(1),
+            DHTModel::DHT22 => Duration::from_secs(2),
+        }
+    }
+
+    /// Turn the 5 bytes sent by the sensor into a reading, checking the checksum in the last.
+    pub(crate) fn decode(self, bytes: [u8; 5]) -> Result<DHTReading, DHTError> {
+        let sum = bytes[..4]
+            .iter()
+            .fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+        if sum != bytes[4] {
+            return Err(DHTError::Checksum);
+        }
+        let (humidity, temperature) = match self {
+            DHTModel::DHT11 => {
+                let humidity = f64::from(bytes[0]) + f64::from(bytes[1]) / 10.0;
+                let temperature = f64::from(bytes[2]) + f64::from(bytes[3] & 0x7f) / 10.0;
+                let sign = if bytes[3] & 0x80 != 0 { -1.0 } else { 1.0 };
+                (humidity, sign * temperature)
+            }
+            DHTModel::DHT22 => {
+                let humidity = f64::from(u16::from_be_bytes([bytes[0], bytes[1]])) / 10.0;
+                let temperature = f64::from(u16::from_be_bytes([bytes[2] & 0x7f, bytes[3]])) / 10.0;
+                let sign = if bytes[2] & 0x80 != 0 { -1.0 } else { 1.0 };
+                (humidity, sign * temperature)
+            }
+        };
+        Ok(DHTReading {
+            temperature,
+            humidity,
+        })
+    }
+}
+
+/// A temperature and humidity reading from a `DHTSensor`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DHTReading {
+    /// The temperature in °C
+    pub temperature: f64,
+    /// The relative humidity in %
+    pub humidity: f64,
+}
+
+/// Problems reading a `DHTSensor`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DHTError {
+    /// The sensor did not answer, or stopped part way through: it is disconnected, or the
+    /// program was interrupted while timing the bits
+    Timeout,
+    /// The data did not match its checksum
+    Checksum,
+}
+
+impl fmt::Display for DHTError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DHTError::Timeout => write!(f, "no response from the DHT sensor"),
+            DHTError::Checksum => write!(f, "checksum mismatch in DHT sensor data"),
+        }
+    }
+}
+
+impl Error for DHTError {}
+
+/// The longest any level lasts while the sensor is sending.
+pub(crate) const DHT_LEVEL_TIMEOUT: Duration = Duration::from_micros(200);
+
+type ReadingCallback = Box<dyn FnMut(DHTReading) + Send>;
+
+/// Receive the 40 bits a DHT sensor sends once the host releases the line, timing each
+/// level with `level_time`. Each bit is a low level of about 50µs followed by a high level
+/// of about 27µs for a 0 and 70µs for a 1.
+pub(crate) fn dht_receive<F>(mut level_time: F) -> Result<[u8; 5], DHTError>
+where
+    F: FnMut(Level) -> Result<Duration, DHTError>,
+{
+    // the sensor answers with 80µs low and 80µs high before the first bit
+    level_time(Level::High)?;
+    level_time(Level::Low)?;
+    level_time(Level::High)?;
+
+    let mut bytes = [0u8; 5];
+    for bit in 0..40 {
+        let low = level_time(Level::Low)?;
+        let high = level_time(Level::High)?;
+        if high > low {
+            bytes[bit / 8] |= 0x80 >> (bit % 8);
+        }
+    }
+    Ok(bytes)
+}
+
+struct DHTLine {
+    io: IoPin,
+    model: DHTModel,
+    retries: u32,
+    last_attempt: Option<Instant>,
+    last: Option<DHTReading>,
+}
+
+impl DHTLine {
+    /// Read the sensor, retrying failed reads, or return the last reading if the sensor was
+    /// read too recently to be read again.
+    fn read(&mut self) -> Result<DHTReading, DHTError> {
+        let mut attempt = 0;
+        loop {
+            if let Some(last_attempt) = self.last_attempt {
+                let ready = last_attempt + self.model.min_interval();
+                match self.last {
+                    Some(reading) if attempt == 0 && Instant::now() < ready => return Ok(reading),
+                    _ => thread::sleep(ready.saturating_duration_since(Instant::now())),
+                }
+            }
+            self.last_attempt = Some(Instant::now());
+            let result = self.transfer().and_then(|bytes| self.model.decode(bytes));
+            match result {
+                Ok(reading) => {
+                    self.last = Some(reading);
+                    return Ok(reading);
+                }
+                Err(e) if attempt >= self.retries => return Err(e),
+                Err(_) => attempt += 1,
+            }
+        }
+    }
+
+    /// Wake the sensor and receive its 40 bits.
+    fn transfer(&mut self) -> Result<[u8; 5], DHTError> {
+        self.io.set_mode(Mode::Output);
+        self.io.set_low();
+        thread::sleep(self.model.start_signal());
+        self.io.set_mode(Mode::Input);
+        dht_receive(|level| self.level_time(level))
+    }
+
+    /// How long the line stays at the level given.
+    fn level_time(&self, level: Level) -> Result<Duration, DHTError> {
+        let start = Instant::now();
+        while self.io.read() == level {
+            if start.elapsed() > DHT_LEVEL_TIMEOUT {
+                return Err(DHTError::Timeout);
+            }
+        }
+        Ok(start.elapsed())
+    }
+}
+
+/// Represents a DHT11 or DHT22 (AM2302) temperature and humidity sensor.
+///
+/// Connect the data pin of the sensor to a GPIO pin, with a 10kΩ pull-up resistor to 3V3
+/// unless the sensor is on a breakout board that has one. The sensor sends its data as
+/// pulses tens of microseconds long, which are timed by busy-waiting; a read spoilt by the
+/// program being interrupted fails its checksum and is tried again.
+///
+/// The sensor can only be read once a second (DHT11) or every 2 seconds (DHT22), so reads
+/// sooner than that return the last reading.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+/// use std::time::Duration;
+///
+/// let mut sensor = DHTSensor::new(4, DHTModel::DHT22);
+/// println!("{:.1}°C", sensor.temperature().unwrap());
+/// sensor.set_interval(Duration::from_secs(60));
+/// sensor.when_reading(|reading| println!("{:.1}% humidity", reading.humidity));
+/// ```
+pub struct DHTSensor {
+    pin: u8,
+    line: Arc<Mutex<DHTLine>>,
+    callback: Arc<Mutex<Option<ReadingCallback>>>,
+    interval: Arc<Mutex<Duration>>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl DHTSensor {
+    /// Returns a DHTSensor with the pin number given, retrying a failed read 3 times
+    /// * `pin` - The GPIO pin which the data pin of the sensor is attached to
+    /// * `model` - The kind of sensor
+    pub fn new(pin: u8, model: DHTModel) -> DHTSensor {
+        let mut io = match Gpio::new() {
+            Err(e) => panic!("{:?}", e),
+            Ok(gpio) => match gpio.get(pin) {
+                Err(e) => panic!("{:?}", e),
+                Ok(pin) => pin.into_io(Mode::Input),
+            },
+        };
+        io.set_pullupdown(PullUpDown::PullUp);
+        DHTSensor {
+            pin,
+            line: Arc::new(Mutex::new(DHTLine {
+                io,
+                model,
+                retries: 3,
+                last_attempt: None,
+                last: None,
+            })),
+            callback: Arc::new(Mutex::new(None)),
+            interval: Arc::new(Mutex::new(model.min_interval())),
+            running: Arc::new(AtomicBool::new(true)),
+            handle: None,
+        }
+    }
+
+    /// The `Pin` that the device is connected to.
+    pub fn pin(&self) -> u8 {
+        self.pin
+    }
+
+    /// The kind of sensor.
+    pub fn model(&self) -> DHTModel {
+        self.line.lock().unwrap().model
+    }
+
+    /// Read the temperature and humidity. This can take several seconds if reads fail and
+    /// are retried.
+    pub fn read(&mut self) -> Result<DHTReading, DHTError> {
+        self.line.lock().unwrap().read()
+    }
+
+    /// Read the temperature in °C.
+    pub fn temperature(&mut self) -> Result<f64, DHTError> {
+        self.read().map(|reading| reading.temperature)
+    }
+
+    /// Read the relative humidity in %.
+    pub fn humidity(&mut self) -> Result<f64, DHTError> {
+        self.read().map(|reading| reading.humidity)
+    }
+
+    /// The last successful reading, without reading the sensor.
+    pub fn last_reading(&self) -> Option<DHTReading> {
+        self.line.lock().unwrap().last
+    }
+
+    /// The number of times a failed read is tried again.
+    pub fn retries(&self) -> u32 {
+        self.line.lock().unwrap().retries
+    }
+
+    /// Set the number of times a failed read is tried again. Defaults to 3.
+    pub fn set_retries(&mut self, retries: u32) {
+        self.line.lock().unwrap().retries = retries;
+    }
+
+    /// How often the sensor is read once `when_reading` is set.
+    pub fn interval(&self) -> Duration {
+        *self.interval.lock().unwrap()
+    }
+
+    /// Set how often the sensor is read once `when_reading` is set. Defaults to the shortest
+    /// interval the sensor supports.
+    /// * `interval` - At least 1 second for a DHT11 and 2 seconds for a DHT22
+    pub fn set_interval(&mut self, interval: Duration) {
+        let min_interval = self.model().min_interval();
+        if interval < min_interval {
+            println!(
+                "Interval must be at least {} seconds",
+                min_interval.as_secs()
+            );
+            return;
+        }
+        *self.interval.lock().unwrap() = interval;
+        if let Some(handle) = &self.handle {
+            handle.thread().unpark();
+        }
+    }
+
+    /// Read the sensor in the background at the interval set, calling the function from a
+    /// background thread with each successful reading.
+    pub fn when_reading<C>(&mut self, callback: C)
+    where
+        C: FnMut(DHTReading) + Send + 'static,
+    {
+        *self.callback.lock().unwrap() = Some(Box::new(callback));
+        if self.handle.is_some() {
+            return;
+        }
+        let line = Arc::clone(&self.line);
+        let callback = Arc::clone(&self.callback);
+        let interval = Arc::clone(&self.interval);
+        let running = Arc::clone(&self.running);
+        self.handle = Some(thread::spawn(move || {
+            while running.load(Ordering::SeqCst) {
+                let result = line.lock().unwrap().read();
+                if let Ok(reading) = result {
+                    if let Some(callback) = callback.lock().unwrap().as_mut() {
+                        callback(reading)
+                    }
+                }
+                let interval = *interval.lock().unwrap();
+                sleep_while_running(&running, interval);
+            }
+        }));
+    }
+
+    /// Shut down the device and release all associated resources.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+impl Drop for DHTSensor {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+/// The input and gain an `HX711` converts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HX711Gain {
+    /// Channel A with a gain of 128, a full scale of ±20mV
+    A128,
+    /// Channel B with a gain of 32, a full scale of ±80mV
+    B32,
+    /// Channel A with a gain of 64, a full scale of ±40mV
+    A64,
+}
+
+impl HX711Gain {
+    /// The clock pulses of a read that select the gain for the next conversion.
+    pub(crate) fn pulses(self) -> u32 {
+        match self {
+            HX711Gain::A128 => 25,
+            HX711Gain::B32 => 26,
+            HX711Gain::A64 => 27,
+        }
+    }
+}
+
+/// Problems reading an `HX711`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HX711Error {
+    /// No conversion was ready in time, e.g. because the HX711 is not connected
+    Timeout,
+}
+
+impl fmt::Display for HX711Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HX711Error::Timeout => write!(f, "no conversion ready from the HX711"),
+        }
+    }
+}
+
+impl Error for HX711Error {}
+
+/// How long the clock of an `HX711` is held high, and then low, for each bit. The datasheet
+/// asks for at least 0.2µs; held high for 60µs, the HX711 powers down.
+pub(crate) const HX711_PULSE: Duration = Duration::from_micros(1);
+
+/// How long the clock is held high to power an `HX711` down.
+const HX711_POWER_DOWN: Duration = Duration::from_micros(100);
+
+/// How long an `HX711` may take to finish a conversion, a little over one at 10 samples a
+/// second.
+const HX711_TIMEOUT: Duration = Duration::from_millis(150);
+
+/// How long the first conversion after an `HX711` powers up may take, a little over the
+/// 400ms of the datasheet at 10 samples a second.
+const HX711_SETTLE: Duration = Duration::from_millis(450);
+
+/// Clock the 24 bits of a conversion out of an HX711, the most significant first, then the
+/// pulses selecting the gain of the next conversion. `pulse` raises the clock, returns the
+/// level of the data line once it has, and lowers the clock again.
+pub(crate) fn hx711_shift<F>(gain: HX711Gain, mut pulse: F) -> i32
+where
+    F: FnMut() -> bool,
+{
+    let mut raw = 0u32;
+    for _ in 0..24 {
+        raw = raw << 1 | u32::from(pulse());
+    }
+    for _ in 24..gain.pulses() {
+        pulse();
+    }
+    // sign extend the 24 bit two's complement value
+    ((raw << 8) as i32) >> 8
+}
+
+/// Busy-wait, for delays too short for the scheduler.
+fn hold(duration: Duration) {
+    let start = Instant::now();
+    while start.elapsed() < duration {
+        std::hint::spin_loop();
+    }
+}
+
+/// Represents an HX711 load cell amplifier, a 24 bit ADC read over a data and a clock pin.
+///
+/// Raw readings are converted to a weight by subtracting the `offset`, measured with `tare`
+/// while the scale is empty, and dividing by the `scale`, the raw units per unit of weight,
+/// found by weighing something of known weight once.
+///
+/// The bits are clocked out by busy-waiting; if the program is interrupted with the clock
+/// high for 60µs the HX711 powers down, and the read returns garbage once.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut scale = HX711::new(5, 6);
+/// scale.tare(10).unwrap();
+/// // a 500g weight read as 211500 above the offset
+/// scale.set_scale(423.0);
+/// println!("{:.1}g", scale.value().unwrap());
+/// ```
+pub struct HX711 {
+    data: InputPin,
+    clock: IoPin,
+    gain: HX711Gain,
+    // the gain the next conversion uses, set by the pulses of the last read
+    selected: HX711Gain,
+    offset: i32,
+    scale: f64,
+    last: Option<i32>,
+    // the next conversion is the first since the HX711 powered up
+    settling: bool,
+}
+
+impl HX711 {
+    /// Returns an HX711 with the pin numbers given, reading channel A with a gain of 128
+    /// * `data` - The GPIO pin which DOUT is attached to
+    /// * `clock` - The GPIO pin which PD_SCK is attached to
+    pub fn new(data: u8, clock: u8) -> HX711 {
+        match Gpio::new() {
+            Err(e) => panic!("{:?}", e),
+            Ok(gpio) => {
+                let data = match gpio.get(data) {
+                    Err(e) => panic!("{:?}", e),
+                    Ok(pin) => pin.into_input(),
+                };
+                let clock = match gpio.get(clock) {
+                    Err(e) => panic!("{:?}", e),
+                    Ok(pin) => {
+                        // low before it becomes an output, so the HX711 is never powered down
+                        let mode = pin.mode();
+                        let mut clock = pin.into_io(mode);
+                        clock.set_low();
+                        clock.set_mode(Mode::Output);
+                        clock
+                    }
+                };
+                HX711 {
+                    data,
+                    clock,
+                    gain: HX711Gain::A128,
+                    selected: HX711Gain::A128,
+                    offset: 0,
+                    scale: 1.0,
+                    last: None,
+                    settling: true,
+                }
+            }
+        }
+    }
+
+    /// The input and gain read.
+    pub fn gain(&self) -> HX711Gain {
+        self.gain
+    }
+
+    /// Set the input and gain read. The conversion under way when it is changed is read and
+    /// thrown away by the next read.
+    pub fn set_gain(&mut self, gain: HX711Gain) {
+        self.gain = gain
+    }
+
+    /// Returns ``True`` if a conversion is ready to be read.
+    pub fn is_ready(&self) -> bool {
+        self.data.is_low()
+    }
+
+    fn convert(&mut self) -> Result<i32, HX711Error> {
+        self.power_up();
+        let timeout = if self.settling {
+            HX711_SETTLE
+        } else {
+            HX711_TIMEOUT
+        };
+        let start = Instant::now();
+        while self.data.is_high() {
+            if start.elapsed() > timeout {
+                return Err(HX711Error::Timeout);
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        let (data, clock) = (&self.data, &mut self.clock);
+        let raw = hx711_shift(self.gain, || {
+            clock.set_high();
+            hold(HX711_PULSE);
+            let bit = data.is_high();
+            clock.set_low();
+            hold(HX711_PULSE);
+            bit
+        });
+        self.settling = false;
+        let converted = self.selected;
+        self.selected = self.gain;
+        if converted == self.gain {
+            Ok(raw)
+        } else {
+            self.convert()
+        }
+    }
+
+    /// Read a conversion, waiting for the next one if none is ready: at most 100ms at 10
+    /// samples a second, or 400ms for the first conversion after the HX711 powers up.
+    pub fn read_raw(&mut self) -> Result<i32, HX711Error> {
+        let raw = self.convert()?;
+        self.last = Some(raw);
+        Ok(raw)
+    }
+
+    /// Read the weight: the raw reading less the `offset`, divided by the `scale`.
+    pub fn value(&mut self) -> Result<f64, HX711Error> {
+        self.read_raw().map(|raw| self.weight(raw))
+    }
+
+    /// The weight of the last reading, without reading the HX711.
+    pub fn last_value(&self) -> Option<f64> {
+        self.last.map(|raw| self.weight(raw))
+    }
+
+    fn weight(&self, raw: i32) -> f64 {
+        (f64::from(raw) - f64::from(self.offset)) / self.scale
+    }
+
+    /// Set the offset to the average of several readings, with the scale empty.
+    /// * `samples` - The number of readings to average
+    pub fn tare(&mut self, samples: u32) -> Result<(), HX711Error> {
+        let samples = samples.max(1);
+        let mut total = 0i64;
+        for _ in 0..samples {
+            total += i64::from(self.read_raw()?);
+        }
+        self.offset = (total / i64::from(samples)) as i32;
+        Ok(())
+    }
+
+    /// The raw reading of an empty scale.
+    pub fn offset(&self) -> i32 {
+        self.offset
+    }
+
+    /// Set the raw reading of an empty scale.
+    pub fn set_offset(&mut self, offset: i32) {
+        self.offset = offset
+    }
+
+    /// The raw units per unit of weight.
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    /// Set the raw units per unit of weight, e.g. the raw reading of a 1kg weight less the
+    /// offset, to read in kg. Defaults to 1.0.
+    pub fn set_scale(&mut self, scale: f64) {
+        if !scale.is_finite() || scale == 0.0 {
+            println!("Scale must be a finite number other than 0");
+            return;
+        }
+        self.scale = scale
+    }
+
+    /// Power the HX711 down, holding the clock high. The next read powers it up again,
+    /// which resets it to channel A with a gain of 128.
+    pub fn power_down(&mut self) {
+        self.clock.set_high();
+        hold(HX711_POWER_DOWN);
+    }
+
+    /// Power the HX711 up after `power_down`; its first conversion takes 400ms at 10
+    /// samples a second.
+    pub fn power_up(&mut self) {
+        if self.clock.read() == Level::High {
+            self.clock.set_low();
+            self.selected = HX711Gain::A128;
+            self.settling = true;
+        }
     }
 }
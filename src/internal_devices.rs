@@ -0,0 +1,805 @@
+//! Devices that are not attached to a pin, such as the time of day, sunrise and sunset, the
+//! temperature of the CPU, the load of the system or how full a disk is
+//!
+//! Internal devices report a state worked out by the Pi itself, and can be used anywhere an
+//! input would be, for example to open a coop door at sunrise, to run a fan while the CPU
+//! is hot, or to show the load of the Pi on a bar graph.
+use crate::output_devices::sleep_while_running;
+use std::f64::consts::PI;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Seconds from the Unix epoch to 2000-01-01 00:00 UTC.
+const Y2000: f64 = 946_684_800.0;
+
+/// Seconds in a day.
+const DAY: f64 = 86_400.0;
+
+/// The daylight of a day at a place on Earth.
+enum Daylight {
+    /// Sunrise and sunset, in seconds since the Unix epoch
+    Times(f64, f64),
+    /// The sun does not set
+    PolarDay,
+    /// The sun does not rise
+    PolarNight,
+}
+
+/// An internal device which is active between sunrise and sunset at a place on Earth.
+///
+/// The times are worked out with the sunrise equation and are good to a minute or two away
+/// from the poles. Offsets move the active period, for example to keep a coop door open
+/// until the hens have gone to roost half an hour after sunset.
+///
+/// # Example
+///
+/// ```
+/// use rust_gpiozero::*;
+/// use std::time::{Duration, SystemTime, UNIX_EPOCH};
+///
+/// let london = SunTimes::new(51.5, -0.13);
+/// // 20 March 2024, the spring equinox
+/// let midnight = UNIX_EPOCH + Duration::from_secs(1_710_892_800);
+/// let noon = midnight + Duration::from_secs(12 * 3600);
+/// let (sunrise, sunset) = london.times_at(noon).unwrap();
+/// let hour = |t: SystemTime| t.duration_since(midnight).unwrap().as_secs() / 3600;
+/// assert_eq!((hour(sunrise), hour(sunset)), (6, 18));
+/// assert!(london.is_day_at(noon));
+/// assert!(!london.is_day_at(midnight));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct SunTimes {
+    latitude: f64,
+    longitude: f64,
+    after_sunrise: Duration,
+    after_sunset: Duration,
+}
+
+impl SunTimes {
+    /// Returns a SunTimes for the place given
+    /// * `latitude` - Degrees north of the equator, negative in the south
+    /// * `longitude` - Degrees east of Greenwich, negative in the west
+    pub fn new(latitude: f64, longitude: f64) -> SunTimes {
+        SunTimes {
+            latitude: latitude.clamp(-90.0, 90.0),
+            longitude,
+            after_sunrise: Duration::ZERO,
+            after_sunset: Duration::ZERO,
+        }
+    }
+
+    /// Move the active period to start some time after sunrise and end some time after
+    /// sunset.
+    /// * `after_sunrise` - How long after sunrise the device becomes active
+    /// * `after_sunset` - How long after sunset the device becomes inactive
+    pub fn set_offsets(&mut self, after_sunrise: Duration, after_sunset: Duration) {
+        self.after_sunrise = after_sunrise;
+        self.after_sunset = after_sunset;
+    }
+
+    /// The sunrise and sunset of the local day containing the time given, without offsets,
+    /// or None if the sun does not rise or does not set that day.
+    pub fn times_at(&self, at: SystemTime) -> Option<(SystemTime, SystemTime)> {
+        match self.daylight(at) {
+            Daylight::Times(sunrise, sunset) => Some((to_time(sunrise), to_time(sunset))),
+            Daylight::PolarDay | Daylight::PolarNight => None,
+        }
+    }
+
+    /// Returns ``True`` if the time given is between sunrise and sunset, after offsets.
+    pub fn is_day_at(&self, at: SystemTime) -> bool {
+        match self.daylight(at) {
+            Daylight::Times(sunrise, sunset) => {
+                let at = seconds(at);
+                let start = sunrise + self.after_sunrise.as_secs_f64();
+                let end = sunset + self.after_sunset.as_secs_f64();
+                at >= start && at < end
+            }
+            Daylight::PolarDay => true,
+            Daylight::PolarNight => false,
+        }
+    }
+
+    /// Returns ``True`` if it is currently between sunrise and sunset, after offsets; the
+    /// same as `is_active`.
+    pub fn value(&self) -> bool {
+        self.is_day_at(SystemTime::now())
+    }
+
+    /// Returns ``True`` if it is currently between sunrise and sunset, after offsets.
+    pub fn is_active(&self) -> bool {
+        self.value()
+    }
+
+    fn daylight(&self, at: SystemTime) -> Daylight {
+        // the day number since 2000-01-01 of the local solar day
+        let local = seconds(at) + self.longitude / 360.0 * DAY;
+        let day = ((local - Y2000) / DAY).floor();
+        // mean solar noon, in days since 2000-01-01 12:00 UTC
+        let noon = day - self.longitude / 360.0;
+        let anomaly = (357.5291 + 0.985_600_28 * noon)
+            .rem_euclid(360.0)
+            .to_radians();
+        let centre =
+            1.9148 * anomaly.sin() + 0.02 * (2.0 * anomaly).sin() + 0.0003 * (3.0 * anomaly).sin();
+        let ecliptic = (anomaly.to_degrees() + centre + 180.0 + 102.9372)
+            .rem_euclid(360.0)
+            .to_radians();
+        let transit = noon + 0.0053 * anomaly.sin() - 0.0069 * (2.0 * ecliptic).sin();
+        let declination = (ecliptic.sin() * 23.4397_f64.to_radians().sin()).asin();
+        let latitude = self.latitude.to_radians();
+        let cos_hour_angle = ((-0.833_f64).to_radians().sin() - latitude.sin() * declination.sin())
+            / (latitude.cos() * declination.cos());
+        if cos_hour_angle < -1.0 {
+            return Daylight::PolarDay;
+        }
+        if cos_hour_angle > 1.0 || cos_hour_angle.is_nan() {
+            return Daylight::PolarNight;
+        }
+        let half_day = cos_hour_angle.acos() / (2.0 * PI);
+        let transit = Y2000 + DAY / 2.0 + transit * DAY;
+        Daylight::Times(transit - half_day * DAY, transit + half_day * DAY)
+    }
+}
+
+fn seconds(at: SystemTime) -> f64 {
+    match at.duration_since(UNIX_EPOCH) {
+        Ok(since) => since.as_secs_f64(),
+        Err(e) => -e.duration().as_secs_f64(),
+    }
+}
+
+fn to_time(seconds: f64) -> SystemTime {
+    if seconds >= 0.0 {
+        UNIX_EPOCH + Duration::from_secs_f64(seconds)
+    } else {
+        UNIX_EPOCH - Duration::from_secs_f64(-seconds)
+    }
+}
+
+/// An internal device which is active between two times of the day, such as quiet hours
+/// overnight.
+///
+/// # Example
+///
+/// ```
+/// use rust_gpiozero::*;
+/// use std::time::Duration;
+///
+/// let quiet_hours = TimeOfDay::new((22, 0), (7, 0));
+/// assert!(quiet_hours.is_active_at(Duration::from_secs(23 * 3600)));
+/// assert!(!quiet_hours.is_active_at(Duration::from_secs(12 * 3600)));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeOfDay {
+    start: u32,
+    end: u32,
+    utc_offset: i32,
+}
+
+impl TimeOfDay {
+    /// Returns a TimeOfDay in UTC. A period ending before it starts runs over midnight.
+    /// * `start` - Hour and minute the device becomes active
+    /// * `end` - Hour and minute the device becomes inactive
+    pub fn new(start: (u32, u32), end: (u32, u32)) -> TimeOfDay {
+        let seconds = |(hour, minute): (u32, u32)| ((hour % 24) * 60 + minute.min(59)) * 60;
+        TimeOfDay {
+            start: seconds(start),
+            end: seconds(end),
+            utc_offset: 0,
+        }
+    }
+
+    /// Set the offset of local time from UTC, e.g. 2.0 for central European summer time.
+    /// Defaults to 0, so the times are in UTC.
+    pub fn set_utc_offset(&mut self, hours: f64) {
+        self.utc_offset = utc_offset_seconds(hours);
+    }
+
+    /// Returns ``True`` if the time of day given, in local time, is between the start and
+    /// end times.
+    pub fn is_active_at(&self, time_of_day: Duration) -> bool {
+        let now = (time_of_day.as_secs() % 86_400) as u32;
+        if self.start <= self.end {
+            self.start <= now && now < self.end
+        } else {
+            now >= self.start || now < self.end
+        }
+    }
+
+    /// Returns ``True`` if the current time of day is between the start and end times.
+    pub fn value(&self) -> bool {
+        self.is_active_at(local_time_of_day(self.utc_offset))
+    }
+
+    /// Returns ``True`` if the current time of day is between the start and end times.
+    pub fn is_active(&self) -> bool {
+        self.value()
+    }
+}
+
+/// The offset from UTC in seconds of an offset given in hours.
+pub(crate) fn utc_offset_seconds(hours: f64) -> i32 {
+    (hours * 3600.0).round() as i32
+}
+
+/// The current time of day in a time zone `utc_offset` seconds ahead of UTC.
+pub(crate) fn local_time_of_day(utc_offset: i32) -> Duration {
+    let local = (seconds(SystemTime::now()) as i64 + i64::from(utc_offset)).rem_euclid(86_400);
+    Duration::from_secs(local as u64)
+}
+
+type InternalCallback = Box<dyn FnMut() + Send>;
+
+/// The functions of `when_activated` and `when_deactivated` of an internal device.
+#[derive(Default)]
+struct InternalCallbacks {
+    activated: Option<InternalCallback>,
+    deactivated: Option<InternalCallback>,
+}
+
+impl fmt::Debug for InternalCallbacks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("InternalCallbacks")
+            .field("activated", &self.activated.is_some())
+            .field("deactivated", &self.deactivated.is_some())
+            .finish()
+    }
+}
+
+/// The background thread behind the events of an internal device, which checks whether the
+/// device is active every `delay`, started when the first function is set.
+struct InternalWatch {
+    callbacks: Arc<Mutex<InternalCallbacks>>,
+    delay: Arc<Mutex<Duration>>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl InternalWatch {
+    fn new(delay: Duration) -> InternalWatch {
+        InternalWatch {
+            callbacks: Arc::new(Mutex::new(InternalCallbacks::default())),
+            delay: Arc::new(Mutex::new(delay)),
+            running: Arc::new(AtomicBool::new(false)),
+            handle: None,
+        }
+    }
+
+    /// Set the function called when the device becomes active, or inactive unless
+    /// `activated`.
+    /// * `is_active` - Checks the device, used by the thread if it is not running yet
+    fn set<R>(&mut self, activated: bool, callback: InternalCallback, is_active: R)
+    where
+        R: Fn() -> bool + Send + 'static,
+    {
+        {
+            let mut callbacks = self.callbacks.lock().unwrap();
+            if activated {
+                callbacks.activated = Some(callback);
+            } else {
+                callbacks.deactivated = Some(callback);
+            }
+        }
+        if self.handle.is_some() {
+            return;
+        }
+        self.running.store(true, Ordering::SeqCst);
+        let callbacks = Arc::clone(&self.callbacks);
+        let delay = Arc::clone(&self.delay);
+        let running = Arc::clone(&self.running);
+        self.handle = Some(thread::spawn(move || {
+            let mut was_active = None;
+            while running.load(Ordering::SeqCst) {
+                let active = is_active();
+                if was_active.replace(active) == Some(!active) {
+                    let mut callbacks = callbacks.lock().unwrap();
+                    let callback = if active {
+                        callbacks.activated.as_mut()
+                    } else {
+                        callbacks.deactivated.as_mut()
+                    };
+                    if let Some(callback) = callback {
+                        callback();
+                    }
+                }
+                let delay = *delay.lock().unwrap();
+                sleep_while_running(&running, delay);
+            }
+        }));
+    }
+
+    fn clear(&mut self) {
+        *self.callbacks.lock().unwrap() = InternalCallbacks::default();
+    }
+
+    fn delay(&self) -> Duration {
+        *self.delay.lock().unwrap()
+    }
+
+    fn set_delay(&mut self, delay: Duration) {
+        *self.delay.lock().unwrap() = delay;
+    }
+}
+
+impl Drop for InternalWatch {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            handle.join().expect("Could not join spawned thread");
+        }
+    }
+}
+
+macro_rules! impl_internal_events {
+    ($state:ident) => {
+        /// How often the device is checked for `when_activated` and `when_deactivated`.
+        pub fn event_delay(&self) -> Duration {
+            self.watch.delay()
+        }
+
+        /// Set how often the device is checked for `when_activated` and `when_deactivated`.
+        pub fn set_event_delay(&mut self, delay: Duration) {
+            self.watch.set_delay(delay)
+        }
+
+        /// Set the function called from a background thread when the device becomes active.
+        pub fn when_activated<C>(&mut self, callback: C)
+        where
+            C: FnMut() + Send + 'static,
+        {
+            let state = Arc::clone(&self.$state);
+            self.watch.set(true, Box::new(callback), move || {
+                state.lock().unwrap().is_active()
+            })
+        }
+
+        /// Set the function called from a background thread when the device becomes inactive.
+        pub fn when_deactivated<C>(&mut self, callback: C)
+        where
+            C: FnMut() + Send + 'static,
+        {
+            let state = Arc::clone(&self.$state);
+            self.watch.set(false, Box::new(callback), move || {
+                state.lock().unwrap().is_active()
+            })
+        }
+
+        /// Remove the functions set with `when_activated` and `when_deactivated`.
+        pub fn clear_callbacks(&mut self) {
+            self.watch.clear()
+        }
+    };
+}
+
+/// The thermal zone of the SoC on a Raspberry Pi.
+const CPU_THERMAL_ZONE: &str = "/sys/class/thermal/thermal_zone0/temp";
+
+/// How a `CPUTemperature` reads its sensor and scales the reading.
+#[derive(Debug, Clone)]
+struct ThermalZone {
+    path: PathBuf,
+    min_temp: f64,
+    max_temp: f64,
+    threshold: f64,
+}
+
+impl ThermalZone {
+    fn temperature(&self) -> Result<f64, io::Error> {
+        let text = fs::read_to_string(&self.path)?;
+        let millidegrees: f64 = text
+            .trim()
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(millidegrees / 1000.0)
+    }
+
+    fn is_active(&self) -> bool {
+        self.temperature()
+            .map(|temperature| temperature > self.threshold)
+            .unwrap_or(false)
+    }
+}
+
+/// An internal device which reads the temperature of the CPU, and is active when it is
+/// above a threshold.
+///
+/// The temperature is read from the kernel, `/sys/class/thermal/thermal_zone0/temp` by
+/// default. `when_activated` and `when_deactivated` are checked every 5 seconds by default,
+/// so a fan is not switched on and off by every short burst of work.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+/// use std::sync::{Arc, Mutex};
+///
+/// let fan = Arc::new(Mutex::new(DigitalOutputDevice::new(17)));
+/// let mut cpu = CPUTemperature::new();
+/// cpu.set_threshold(70.0);
+/// let on = Arc::clone(&fan);
+/// cpu.when_activated(move || on.lock().unwrap().on());
+/// let off = Arc::clone(&fan);
+/// cpu.when_deactivated(move || off.lock().unwrap().off());
+/// println!("{:.1}C, {:.0}% of the range", cpu.temperature().unwrap(), cpu.value() * 100.0);
+/// ```
+pub struct CPUTemperature {
+    zone: Arc<Mutex<ThermalZone>>,
+    watch: InternalWatch,
+}
+
+impl CPUTemperature {
+    /// Returns a CPUTemperature reading the thermal zone of the CPU, with values from 0C to
+    /// 100C and active above 80C
+    pub fn new() -> CPUTemperature {
+        CPUTemperature::with_sensor(CPU_THERMAL_ZONE)
+    }
+
+    /// Returns a CPUTemperature reading the sensor given, with values from 0C to 100C and
+    /// active above 80C
+    /// * `sensor_file` - A file holding the temperature in thousandths of a degree
+    ///   Celsius, such as the `temp` of a thermal zone
+    pub fn with_sensor<P: AsRef<Path>>(sensor_file: P) -> CPUTemperature {
+        CPUTemperature {
+            zone: Arc::new(Mutex::new(ThermalZone {
+                path: sensor_file.as_ref().to_path_buf(),
+                min_temp: 0.0,
+                max_temp: 100.0,
+                threshold: 80.0,
+            })),
+            watch: InternalWatch::new(Duration::from_secs(5)),
+        }
+    }
+
+    /// The temperature of the CPU in degrees Celsius.
+    pub fn temperature(&self) -> Result<f64, io::Error> {
+        self.zone.lock().unwrap().temperature()
+    }
+
+    /// The temperature scaled between the minimum and maximum temperatures, from 0.0 to
+    /// 1.0, or NaN if the sensor cannot be read.
+    pub fn value(&self) -> f64 {
+        let zone = self.zone.lock().unwrap();
+        match zone.temperature() {
+            Ok(temperature) => {
+                ((temperature - zone.min_temp) / (zone.max_temp - zone.min_temp)).clamp(0.0, 1.0)
+            }
+            Err(_) => f64::NAN,
+        }
+    }
+
+    /// Returns ``True`` if the temperature is above the threshold, ``False`` if it is not
+    /// or the sensor cannot be read.
+    pub fn is_active(&self) -> bool {
+        self.zone.lock().unwrap().is_active()
+    }
+
+    /// The temperatures with a value of 0.0 and 1.0.
+    pub fn range(&self) -> (f64, f64) {
+        let zone = self.zone.lock().unwrap();
+        (zone.min_temp, zone.max_temp)
+    }
+
+    /// Set the temperatures with a value of 0.0 and 1.0.
+    /// * `min_temp` - The temperature in degrees Celsius with a value of 0.0
+    /// * `max_temp` - The temperature in degrees Celsius with a value of 1.0
+    pub fn set_range(&mut self, min_temp: f64, max_temp: f64) {
+        if min_temp >= max_temp {
+            println!("The minimum temperature must be below the maximum temperature");
+            return;
+        }
+        let mut zone = self.zone.lock().unwrap();
+        zone.min_temp = min_temp;
+        zone.max_temp = max_temp;
+    }
+
+    /// The temperature in degrees Celsius above which the device is active.
+    pub fn threshold(&self) -> f64 {
+        self.zone.lock().unwrap().threshold
+    }
+
+    /// Set the temperature in degrees Celsius above which the device is active.
+    pub fn set_threshold(&mut self, threshold: f64) {
+        self.zone.lock().unwrap().threshold = threshold
+    }
+
+    impl_internal_events!(zone);
+}
+
+impl Default for CPUTemperature {
+    fn default() -> Self {
+        CPUTemperature::new()
+    }
+}
+
+/// The load averages the kernel keeps, over the last 1, 5 or 15 minutes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadPeriod {
+    /// The last minute, quick to follow a burst of work
+    OneMinute,
+    /// The last 5 minutes
+    FiveMinutes,
+    /// The last 15 minutes, the trend of the load
+    FifteenMinutes,
+}
+
+/// How a `LoadAverage` reads the load and scales it.
+#[derive(Debug, Clone)]
+struct LoadFile {
+    path: PathBuf,
+    period: LoadPeriod,
+    min_load: f64,
+    max_load: f64,
+    threshold: f64,
+}
+
+impl LoadFile {
+    fn load_average(&self) -> Result<f64, io::Error> {
+        let text = fs::read_to_string(&self.path)?;
+        let field = match self.period {
+            LoadPeriod::OneMinute => 0,
+            LoadPeriod::FiveMinutes => 1,
+            LoadPeriod::FifteenMinutes => 2,
+        };
+        text.split_whitespace()
+            .nth(field)
+            .and_then(|load| load.parse().ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not a load average"))
+    }
+
+    fn is_active(&self) -> bool {
+        self.load_average()
+            .map(|load| load > self.threshold)
+            .unwrap_or(false)
+    }
+}
+
+/// An internal device which reads the load average of the system, the number of tasks
+/// running or waiting to run, and is active when it is above a threshold.
+///
+/// The load is read from `/proc/loadavg`. A load of 1.0 keeps one core busy, so the
+/// maximum of the range may be raised to the number of cores. `when_activated` and
+/// `when_deactivated` are checked every 10 seconds by default.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut load = LoadAverage::new(LoadPeriod::OneMinute);
+/// load.set_range(0.0, 4.0);
+/// load.set_threshold(3.0);
+/// load.when_activated(|| println!("the Pi is busy"));
+/// println!("{:.2}, {:.0}% of the range", load.load_average().unwrap(), load.value() * 100.0);
+/// ```
+pub struct LoadAverage {
+    load: Arc<Mutex<LoadFile>>,
+    watch: InternalWatch,
+}
+
+impl LoadAverage {
+    /// Returns a LoadAverage over the period given, with values from 0.0 to 1.0 and active
+    /// above 0.8
+    /// * `period` - The period the load is averaged over
+    pub fn new(period: LoadPeriod) -> LoadAverage {
+        LoadAverage::with_file("/proc/loadavg", period)
+    }
+
+    /// Returns a LoadAverage reading the file given, with values from 0.0 to 1.0 and active
+    /// above 0.8
+    /// * `load_average_file` - A file in the format of `/proc/loadavg`
+    /// * `period` - The period the load is averaged over
+    pub fn with_file<P: AsRef<Path>>(load_average_file: P, period: LoadPeriod) -> LoadAverage {
+        LoadAverage {
+            load: Arc::new(Mutex::new(LoadFile {
+                path: load_average_file.as_ref().to_path_buf(),
+                period,
+                min_load: 0.0,
+                max_load: 1.0,
+                threshold: 0.8,
+            })),
+            watch: InternalWatch::new(Duration::from_secs(10)),
+        }
+    }
+
+    /// The load average over the period of the device.
+    pub fn load_average(&self) -> Result<f64, io::Error> {
+        self.load.lock().unwrap().load_average()
+    }
+
+    /// The load average scaled between the minimum and maximum loads, from 0.0 to 1.0, or
+    /// NaN if the load cannot be read.
+    pub fn value(&self) -> f64 {
+        let load = self.load.lock().unwrap();
+        match load.load_average() {
+            Ok(average) => {
+                ((average - load.min_load) / (load.max_load - load.min_load)).clamp(0.0, 1.0)
+            }
+            Err(_) => f64::NAN,
+        }
+    }
+
+    /// Returns ``True`` if the load average is above the threshold, ``False`` if it is not
+    /// or the load cannot be read.
+    pub fn is_active(&self) -> bool {
+        self.load.lock().unwrap().is_active()
+    }
+
+    /// The period the load is averaged over.
+    pub fn period(&self) -> LoadPeriod {
+        self.load.lock().unwrap().period
+    }
+
+    /// Set the period the load is averaged over.
+    pub fn set_period(&mut self, period: LoadPeriod) {
+        self.load.lock().unwrap().period = period
+    }
+
+    /// The loads with a value of 0.0 and 1.0.
+    pub fn range(&self) -> (f64, f64) {
+        let load = self.load.lock().unwrap();
+        (load.min_load, load.max_load)
+    }
+
+    /// Set the loads with a value of 0.0 and 1.0.
+    /// * `min_load` - The load average with a value of 0.0
+    /// * `max_load` - The load average with a value of 1.0
+    pub fn set_range(&mut self, min_load: f64, max_load: f64) {
+        if min_load >= max_load {
+            println!("The minimum load must be below the maximum load");
+            return;
+        }
+        let mut load = self.load.lock().unwrap();
+        load.min_load = min_load;
+        load.max_load = max_load;
+    }
+
+    /// The load average above which the device is active.
+    pub fn threshold(&self) -> f64 {
+        self.load.lock().unwrap().threshold
+    }
+
+    /// Set the load average above which the device is active.
+    pub fn set_threshold(&mut self, threshold: f64) {
+        self.load.lock().unwrap().threshold = threshold
+    }
+
+    impl_internal_events!(load);
+}
+
+/// How a `DiskUsage` finds the usage of its filesystem.
+#[derive(Debug, Clone)]
+struct Filesystem {
+    path: PathBuf,
+    threshold: f64,
+}
+
+impl Filesystem {
+    /// The percentage of the blocks in use, as reported by `df`.
+    fn usage(&self) -> Result<f64, io::Error> {
+        let output = Command::new("df")
+            .args(["-P", "-k"])
+            .arg(&self.path)
+            .stderr(Stdio::null())
+            .output()?;
+        if !output.status.success() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("df could not find {}", self.path.display()),
+            ));
+        }
+        // Filesystem 1024-blocks Used Available Capacity Mounted-on
+        let text = String::from_utf8_lossy(&output.stdout);
+        let fields: Vec<&str> = text
+            .lines()
+            .nth(1)
+            .map(|line| line.split_whitespace().collect())
+            .unwrap_or_default();
+        match (
+            fields.get(1).and_then(|blocks| blocks.parse::<f64>().ok()),
+            fields.get(2).and_then(|used| used.parse::<f64>().ok()),
+        ) {
+            (Some(blocks), Some(used)) if blocks > 0.0 => Ok(used * 100.0 / blocks),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "could not read the output of df",
+            )),
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.usage()
+            .map(|usage| usage > self.threshold)
+            .unwrap_or(false)
+    }
+}
+
+/// An internal device which reads how full a filesystem is, and is active when its usage
+/// is above a threshold.
+///
+/// The usage is the percentage of the blocks in use, as reported by `df`, of the
+/// filesystem holding the path given. `when_activated` and `when_deactivated` are checked
+/// every 30 seconds by default.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut disk = DiskUsage::new();
+/// disk.when_activated(|| println!("the SD card is almost full"));
+/// let data = DiskUsage::with_filesystem("/mnt/data");
+/// println!("{:.1}% and {:.1}% used", disk.usage().unwrap(), data.usage().unwrap());
+/// ```
+pub struct DiskUsage {
+    filesystem: Arc<Mutex<Filesystem>>,
+    watch: InternalWatch,
+}
+
+impl DiskUsage {
+    /// Returns a DiskUsage of the root filesystem, active above 90%
+    pub fn new() -> DiskUsage {
+        DiskUsage::with_filesystem("/")
+    }
+
+    /// Returns a DiskUsage of the filesystem holding the path given, active above 90%
+    /// * `filesystem` - A mount point, or any path on the filesystem
+    pub fn with_filesystem<P: AsRef<Path>>(filesystem: P) -> DiskUsage {
+        DiskUsage {
+            filesystem: Arc::new(Mutex::new(Filesystem {
+                path: filesystem.as_ref().to_path_buf(),
+                threshold: 90.0,
+            })),
+            watch: InternalWatch::new(Duration::from_secs(30)),
+        }
+    }
+
+    /// The percentage of the filesystem in use.
+    pub fn usage(&self) -> Result<f64, io::Error> {
+        self.filesystem.lock().unwrap().usage()
+    }
+
+    /// The usage from 0.0 to 1.0, or NaN if it cannot be read.
+    pub fn value(&self) -> f64 {
+        match self.usage() {
+            Ok(usage) => (usage / 100.0).clamp(0.0, 1.0),
+            Err(_) => f64::NAN,
+        }
+    }
+
+    /// Returns ``True`` if the usage is above the threshold, ``False`` if it is not or it
+    /// cannot be read.
+    pub fn is_active(&self) -> bool {
+        self.filesystem.lock().unwrap().is_active()
+    }
+
+    /// The percentage above which the device is active.
+    pub fn threshold(&self) -> f64 {
+        self.filesystem.lock().unwrap().threshold
+    }
+
+    /// Set the percentage above which the device is active.
+    pub fn set_threshold(&mut self, threshold: f64) {
+        if !(0.0..=100.0).contains(&threshold) {
+            println!("Threshold must be between 0.0 and 100.0");
+            return;
+        }
+        self.filesystem.lock().unwrap().threshold = threshold
+    }
+
+    impl_internal_events!(filesystem);
+}
+
+impl Default for DiskUsage {
+    fn default() -> Self {
+        DiskUsage::new()
+    }
+}
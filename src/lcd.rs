@@ -0,0 +1,419 @@
+//! Character LCDs driven by an HD44780 controller or a compatible one
+//!
+//! The 16x2 and 20x4 displays of most starter kits are wired either straight to six GPIO
+//! pins, using the 4-bit interface of the controller, or through a PCF8574 I2C backpack
+//! soldered to the back of the display, which only needs the two I2C pins. `CharLCD`
+//! drives both the same way. The R/W pin is never used, so it must be connected to ground
+//! when wiring the display directly.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::*;
+//!
+//! // a 16x2 display with a backpack at the usual address
+//! let mut lcd = CharLCD::with_backpack(1, 0x27, 16, 2);
+//! lcd.define_char(0, [0x0a, 0x1f, 0x1f, 0x1f, 0x0e, 0x04, 0x00, 0x00]);
+//! lcd.write_str("Hello \u{0}\nworld");
+//! lcd.set_cursor(12, 1);
+//! lcd.write_str("21°C");
+//! ```
+use crate::bus::{BusLink, BusStats, RetryPolicy, SharedI2c};
+use rppal::gpio::{Gpio, Level, OutputPin};
+use std::thread;
+use std::time::Duration;
+
+const LCD_CLEAR: u8 = 0x01;
+const LCD_HOME: u8 = 0x02;
+const LCD_ENTRY_MODE: u8 = 0x06;
+const LCD_DISPLAY_CONTROL: u8 = 0x08;
+const LCD_DISPLAY_ON: u8 = 0x04;
+const LCD_CURSOR_ON: u8 = 0x02;
+const LCD_BLINK_ON: u8 = 0x01;
+// 4-bit interface, two lines, 5x8 dots; one-line displays are addressed as two lines too
+const LCD_FUNCTION_SET: u8 = 0x28;
+const LCD_SET_CGRAM: u8 = 0x40;
+const LCD_SET_DDRAM: u8 = 0x80;
+
+// the pins of the PCF8574 on the common backpacks
+const BACKPACK_RS: u8 = 0x01;
+const BACKPACK_ENABLE: u8 = 0x04;
+const BACKPACK_BACKLIGHT: u8 = 0x08;
+
+fn level(high: bool) -> Level {
+    if high {
+        Level::High
+    } else {
+        Level::Low
+    }
+}
+
+/// How the controller is wired.
+enum LcdBus {
+    Gpio {
+        rs: OutputPin,
+        enable: OutputPin,
+        data: Box<[OutputPin; 4]>,
+        backlight: Option<OutputPin>,
+    },
+    Backpack {
+        i2c: SharedI2c,
+        link: BusLink,
+        backlight: bool,
+    },
+}
+
+impl LcdBus {
+    /// Clock 4 bits into the controller, the instruction register unless `rs` is set.
+    fn write4(&mut self, rs: bool, nibble: u8) {
+        match self {
+            LcdBus::Gpio {
+                rs: rs_pin,
+                enable,
+                data,
+                ..
+            } => {
+                rs_pin.write(level(rs));
+                for (bit, pin) in data.iter_mut().enumerate() {
+                    pin.write(level(nibble & (1 << bit) != 0));
+                }
+                enable.set_high();
+                thread::sleep(Duration::from_micros(1));
+                enable.set_low();
+            }
+            LcdBus::Backpack {
+                i2c,
+                link,
+                backlight,
+            } => {
+                let mut byte = nibble << 4;
+                if rs {
+                    byte |= BACKPACK_RS;
+                }
+                if *backlight {
+                    byte |= BACKPACK_BACKLIGHT;
+                }
+                // the chip updates its pins after each byte, which pulses enable
+                if link
+                    .run(|| i2c.lock()?.write(&[byte | BACKPACK_ENABLE, byte]))
+                    .is_err()
+                {
+                    println!("Could not write to the LCD backpack");
+                }
+            }
+        }
+    }
+
+    fn write8(&mut self, rs: bool, byte: u8) {
+        self.write4(rs, byte >> 4);
+        self.write4(rs, byte & 0x0f);
+        // the slowest of the usual instructions takes 37µs
+        thread::sleep(Duration::from_micros(50));
+    }
+
+    fn set_backlight(&mut self, on: bool) -> bool {
+        match self {
+            LcdBus::Gpio { backlight, .. } => match backlight {
+                Some(pin) => {
+                    pin.write(level(on));
+                    true
+                }
+                None => false,
+            },
+            LcdBus::Backpack {
+                i2c,
+                link,
+                backlight,
+            } => {
+                *backlight = on;
+                let byte = if on { BACKPACK_BACKLIGHT } else { 0 };
+                if link.run(|| i2c.lock()?.write(&[byte])).is_err() {
+                    println!("Could not write to the LCD backpack");
+                }
+                true
+            }
+        }
+    }
+}
+
+/// Represents a character LCD with an HD44780 controller, such as the 16x2 and 20x4
+/// displays of most starter kits.
+///
+/// Text is written from the cursor, which moves on to the start of the next row at the end
+/// of one and at a `\n`. Printable ASCII other than `\` and `~`, and `°`, are shown from
+/// the character ROM of the controller; `'\u{0}'` to `'\u{7}'` show the characters defined
+/// with `define_char`, and anything else is shown as `?`.
+pub struct CharLCD {
+    bus: LcdBus,
+    columns: u8,
+    rows: u8,
+    cursor: (u8, u8),
+    control: u8,
+    backlight: bool,
+}
+
+impl CharLCD {
+    /// Returns a CharLCD wired to GPIO pins in 4-bit mode, cleared
+    /// * `rs` - The GPIO pin the RS (register select) pin of the display is connected to
+    /// * `enable` - The GPIO pin the E (enable) pin of the display is connected to
+    /// * `data` - The GPIO pins D4 to D7 of the display are connected to
+    /// * `columns` - The number of characters per row, e.g. 16 or 20
+    /// * `rows` - The number of rows, e.g. 2 or 4
+    pub fn new(rs: u8, enable: u8, data: &[u8; 4], columns: u8, rows: u8) -> CharLCD {
+        let gpio = match Gpio::new() {
+            Err(e) => panic!("{:?}", e),
+            Ok(gpio) => gpio,
+        };
+        let pin = |pin: u8| match gpio.get(pin) {
+            Err(e) => panic!("{:?}", e),
+            Ok(pin) => {
+                let mut pin = pin.into_output();
+                pin.set_low();
+                pin
+            }
+        };
+        let bus = LcdBus::Gpio {
+            rs: pin(rs),
+            enable: pin(enable),
+            data: Box::new([pin(data[0]), pin(data[1]), pin(data[2]), pin(data[3])]),
+            backlight: None,
+        };
+        CharLCD::open(bus, columns, rows)
+    }
+
+    /// Returns a CharLCD on a PCF8574 I2C backpack, cleared and with the backlight on
+    /// * `bus` - The I2C bus number, 1 on most Raspberry Pi models
+    /// * `address` - 0x27 on most backpacks, or 0x3f with a PCF8574A
+    /// * `columns` - The number of characters per row, e.g. 16 or 20
+    /// * `rows` - The number of rows, e.g. 2 or 4
+    pub fn with_backpack(bus: u8, address: u16, columns: u8, rows: u8) -> CharLCD {
+        let i2c = match SharedI2c::open(bus, address) {
+            Err(e) => panic!("{:?}", e),
+            Ok(i2c) => i2c,
+        };
+        let bus = LcdBus::Backpack {
+            i2c,
+            link: BusLink::default(),
+            backlight: true,
+        };
+        let mut lcd = CharLCD::open(bus, columns, rows);
+        lcd.backlight = true;
+        lcd
+    }
+
+    fn open(bus: LcdBus, columns: u8, rows: u8) -> CharLCD {
+        let rows = if (1..=4).contains(&rows) {
+            rows
+        } else {
+            println!("Rows must be between 1 and 4");
+            rows.clamp(1, 4)
+        };
+        let columns = if (1..=40).contains(&columns) {
+            columns
+        } else {
+            println!("Columns must be between 1 and 40");
+            columns.clamp(1, 40)
+        };
+        let mut lcd = CharLCD {
+            bus,
+            columns,
+            rows,
+            cursor: (0, 0),
+            control: LCD_DISPLAY_ON,
+            backlight: false,
+        };
+        // the controller may be in 8-bit mode or halfway through a 4-bit transfer; three
+        // 8-bit function sets bring it to a known state before switching to 4 bits
+        thread::sleep(Duration::from_millis(50));
+        for wait in [4500, 4500, 150].iter() {
+            lcd.bus.write4(false, 0x03);
+            thread::sleep(Duration::from_micros(*wait));
+        }
+        lcd.bus.write4(false, 0x02);
+        thread::sleep(Duration::from_micros(150));
+        lcd.command(LCD_FUNCTION_SET);
+        lcd.command(LCD_DISPLAY_CONTROL | lcd.control);
+        lcd.command(LCD_ENTRY_MODE);
+        lcd.clear();
+        lcd
+    }
+
+    fn command(&mut self, command: u8) {
+        self.bus.write8(false, command);
+    }
+
+    /// The number of characters per row.
+    pub fn columns(&self) -> u8 {
+        self.columns
+    }
+
+    /// The number of rows.
+    pub fn rows(&self) -> u8 {
+        self.rows
+    }
+
+    /// Write text from the cursor. Text beyond the last row is dropped.
+    pub fn write_str(&mut self, text: &str) {
+        for c in text.chars() {
+            let (column, row) = self.cursor;
+            if c == '\n' {
+                self.set_cursor(0, row + 1);
+                continue;
+            }
+            if row >= self.rows {
+                return;
+            }
+            let code = match c {
+                '\u{0}'..='\u{7}' => c as u8,
+                // the ROM has a yen sign and arrows in place of `\` and `~`
+                ' '..='[' | ']'..='}' => c as u8,
+                '°' => 0xdf,
+                _ => b'?',
+            };
+            self.bus.write8(true, code);
+            if column + 1 < self.columns {
+                self.cursor = (column + 1, row);
+            } else {
+                self.set_cursor(0, row + 1);
+            }
+        }
+    }
+
+    /// Clear the display and move the cursor to the top left.
+    pub fn clear(&mut self) {
+        self.command(LCD_CLEAR);
+        thread::sleep(Duration::from_millis(2));
+        self.cursor = (0, 0);
+    }
+
+    /// Move the cursor to the top left, without clearing the display.
+    pub fn home(&mut self) {
+        self.command(LCD_HOME);
+        thread::sleep(Duration::from_millis(2));
+        self.cursor = (0, 0);
+    }
+
+    /// The column and row the next character is written at.
+    pub fn cursor(&self) -> (u8, u8) {
+        self.cursor
+    }
+
+    /// Move the cursor. A row past the last one leaves the cursor off the display, so
+    /// further text is dropped.
+    /// * `column` - The column, 0 being the leftmost
+    /// * `row` - The row, 0 being the top
+    pub fn set_cursor(&mut self, column: u8, row: u8) {
+        if column >= self.columns {
+            println!("Column must be between 0 and {}", self.columns - 1);
+            return;
+        }
+        self.cursor = (column, row);
+        if row < self.rows {
+            // rows 2 and 3 continue rows 0 and 1 in the memory of the controller
+            let offsets = [0x00, 0x40, self.columns, 0x40 + self.columns];
+            self.command(LCD_SET_DDRAM | (offsets[usize::from(row)] + column));
+        }
+    }
+
+    /// Show or hide an underline at the cursor.
+    pub fn set_cursor_visible(&mut self, visible: bool) {
+        self.set_control(LCD_CURSOR_ON, visible)
+    }
+
+    /// Blink the character at the cursor, or stop it blinking.
+    pub fn set_blink(&mut self, blink: bool) {
+        self.set_control(LCD_BLINK_ON, blink)
+    }
+
+    /// Returns ``True`` if the display shows its text.
+    pub fn is_display_on(&self) -> bool {
+        self.control & LCD_DISPLAY_ON != 0
+    }
+
+    /// Show or hide the text, which is kept while hidden.
+    pub fn set_display(&mut self, on: bool) {
+        self.set_control(LCD_DISPLAY_ON, on)
+    }
+
+    fn set_control(&mut self, flag: u8, on: bool) {
+        if on {
+            self.control |= flag;
+        } else {
+            self.control &= !flag;
+        }
+        self.command(LCD_DISPLAY_CONTROL | self.control);
+    }
+
+    /// Define one of the eight custom characters, shown by writing `'\u{0}'` to `'\u{7}'`.
+    /// Characters already on the display change too.
+    /// * `slot` - The character, 0 to 7
+    /// * `rows` - The 5 pixels of each row from the top, bit 4 being the leftmost
+    pub fn define_char(&mut self, slot: u8, rows: [u8; 8]) {
+        if slot > 7 {
+            println!("Slot must be between 0 and 7");
+            return;
+        }
+        self.command(LCD_SET_CGRAM | slot << 3);
+        for row in rows.iter() {
+            self.bus.write8(true, row & 0x1f);
+        }
+        let (column, row) = self.cursor;
+        self.set_cursor(column, row);
+    }
+
+    /// Returns ``True`` if the backlight is on.
+    pub fn backlight(&self) -> bool {
+        self.backlight
+    }
+
+    /// Turn the backlight on or off. A display wired to GPIO pins needs `set_backlight_pin`
+    /// first.
+    pub fn set_backlight(&mut self, on: bool) {
+        if self.bus.set_backlight(on) {
+            self.backlight = on;
+        } else {
+            println!("No backlight pin, see set_backlight_pin");
+        }
+    }
+
+    /// Switch the backlight of a display wired to GPIO pins from a pin, e.g. through a
+    /// transistor, and turn it on. Has no effect on a backpack, which has its own.
+    /// * `pin` - The GPIO pin switching the backlight, high for on
+    pub fn set_backlight_pin(&mut self, pin: u8) {
+        if let LcdBus::Gpio { backlight, .. } = &mut self.bus {
+            match Gpio::new() {
+                Err(e) => panic!("{:?}", e),
+                Ok(gpio) => match gpio.get(pin) {
+                    Err(e) => panic!("{:?}", e),
+                    Ok(pin) => *backlight = Some(pin.into_output()),
+                },
+            }
+            self.set_backlight(true);
+        }
+    }
+
+    /// The error counters of the I2C transfers to a backpack, all zero for a display wired
+    /// to GPIO pins.
+    pub fn bus_stats(&self) -> BusStats {
+        match &self.bus {
+            LcdBus::Backpack { link, .. } => link.stats(),
+            LcdBus::Gpio { .. } => BusStats::default(),
+        }
+    }
+
+    /// Change how failed I2C transfers to a backpack are retried.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        if let LcdBus::Backpack { link, .. } = &mut self.bus {
+            link.set_policy(policy)
+        }
+    }
+}
+
+impl Drop for CharLCD {
+    fn drop(&mut self) {
+        self.clear();
+        if self.backlight {
+            self.bus.set_backlight(false);
+        }
+    }
+}
@@ -16,7 +16,7 @@
 //!
 //! # Example : Blinking an LED
 //!
-//! ```
+//! ```no_run
 //! use rust_gpiozero::*;
 //!
 //! // Create a new LED attached to Pin 17
@@ -24,12 +24,42 @@
 //!
 //! // blink the LED
 //! // on_time: 2 seconds and off_time: 3 seconds
-//! led.blink(2.0,3.0);
+//! led.blink(2.0,3.0).unwrap();
 //! ```
 
+pub use self::boards::*;
+pub use self::bus::*;
+pub use self::composites::*;
+pub use self::config::*;
+pub use self::control::*;
 pub use self::devices::*;
+pub use self::expander::*;
+pub use self::federation::*;
+pub use self::health::*;
+pub use self::i2c_devices::*;
 pub use self::input_devices::*;
+pub use self::internal_devices::*;
+pub use self::lcd::*;
+pub use self::morse::*;
 pub use self::output_devices::*;
+pub use self::pipeline::*;
+pub use self::priority::*;
+pub use self::safety::*;
+pub use self::sense_hat::*;
+pub use self::session::*;
+pub use self::sink::*;
+pub use self::source::*;
+pub use self::spi_devices::*;
+pub use self::strips::*;
+pub use self::tones::*;
+pub use self::transaction::*;
+pub use self::tree::*;
+pub use self::wear::*;
+pub use self::webhook::*;
+pub use rust_gpiozero_derive::{CompositeDevice, CompositeOutputDevice};
+
+// lets the derive macros, which name this crate, be used inside it
+extern crate self as rust_gpiozero;
 
 #[macro_use]
 pub mod devices;
@@ -37,3 +67,41 @@ pub mod devices;
 pub mod output_devices;
 #[macro_use]
 pub mod input_devices;
+pub mod audit;
+pub mod boards;
+pub mod bus;
+pub mod cli;
+pub mod composites;
+pub mod config;
+pub mod control;
+pub mod diagnostics;
+pub mod drivers;
+pub mod expander;
+pub mod federation;
+pub mod health;
+pub mod i2c;
+pub mod i2c_devices;
+pub mod internal_devices;
+pub mod json;
+pub mod lcd;
+pub mod morse;
+pub mod pin;
+pub mod pipeline;
+pub mod priority;
+#[cfg(feature = "repl")]
+pub mod repl;
+pub mod safety;
+pub mod sense_hat;
+pub mod session;
+pub mod sink;
+pub mod source;
+pub mod spi;
+pub mod spi_devices;
+pub mod strips;
+pub mod tones;
+pub mod tools;
+pub mod transaction;
+pub mod tree;
+pub mod waveform;
+pub mod wear;
+pub mod webhook;
@@ -0,0 +1,137 @@
+//! Morse code transmitter for status LEDs and buzzers
+//!
+//! Text is converted into timed on/off pairs using the standard timing: a dah is three dits,
+//! symbols within a letter are separated by one dit, letters by three dits and words by seven.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::*;
+//!
+//! let mut led = LED::new(17);
+//! MorseCode::with_wpm(12.0).transmit(&mut led, "SOS", None);
+//! led.wait();
+//! ```
+use crate::output_devices::PlayPattern;
+use std::time::Duration;
+
+/// Returns the dots and dashes for a character, or None if it has no Morse representation.
+fn symbols(c: char) -> Option<&'static str> {
+    let code = match c.to_ascii_uppercase() {
+        'A' => ".-",
+        'B' => "-...",
+        'C' => "-.-.",
+        'D' => "-..",
+        'E' => ".",
+        'F' => "..-.",
+        'G' => "--.",
+        'H' => "....",
+        'I' => "..",
+        'J' => ".---",
+        'K' => "-.-",
+        'L' => ".-..",
+        'M' => "--",
+        'N' => "-.",
+        'O' => "---",
+        'P' => ".--.",
+        'Q' => "--.-",
+        'R' => ".-.",
+        'S' => "...",
+        'T' => "-",
+        'U' => "..-",
+        'V' => "...-",
+        'W' => ".--",
+        'X' => "-..-",
+        'Y' => "-.--",
+        'Z' => "--..",
+        '0' => "-----",
+        '1' => ".----",
+        '2' => "..---",
+        '3' => "...--",
+        '4' => "....-",
+        '5' => ".....",
+        '6' => "-....",
+        '7' => "--...",
+        '8' => "---..",
+        '9' => "----.",
+        '.' => ".-.-.-",
+        ',' => "--..--",
+        '?' => "..--..",
+        '\'' => ".----.",
+        '!' => "-.-.--",
+        '/' => "-..-.",
+        '(' => "-.--.",
+        ')' => "-.--.-",
+        '&' => ".-...",
+        ':' => "---...",
+        ';' => "-.-.-.",
+        '=' => "-...-",
+        '+' => ".-.-.",
+        '-' => "-....-",
+        '_' => "..--.-",
+        '"' => ".-..-.",
+        '$' => "...-..-",
+        '@' => ".--.-.",
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// Converts text into Morse code timings and plays them on an output device.
+#[derive(Debug, Clone, Copy)]
+pub struct MorseCode {
+    dit: Duration,
+}
+
+impl MorseCode {
+    /// Returns a MorseCode with the given dit length
+    /// * `dit` - Length of a dot; every other timing is a multiple of it
+    pub fn new(dit: Duration) -> MorseCode {
+        MorseCode { dit }
+    }
+
+    /// Returns a MorseCode with the dit length for the given speed, using the standard word "PARIS"
+    /// * `wpm` - Speed in words per minute
+    pub fn with_wpm(wpm: f32) -> MorseCode {
+        MorseCode::new(Duration::from_millis((1200.0 / wpm) as u64))
+    }
+
+    /// The length of a dot
+    pub fn dit(&self) -> Duration {
+        self.dit
+    }
+
+    /// Convert text into pairs of state (``True`` for on) and duration.
+    /// Characters without a Morse representation are skipped and runs of whitespace count as one word gap.
+    /// The sequence ends with a word gap so that it can be repeated.
+    /// * `text` - The message to encode
+    pub fn encode(&self, text: &str) -> Vec<(bool, Duration)> {
+        let mut pattern: Vec<(bool, Duration)> = Vec::new();
+        for word in text.split_whitespace() {
+            for code in word.chars().filter_map(symbols) {
+                for symbol in code.chars() {
+                    let length = if symbol == '-' { 3 } else { 1 };
+                    pattern.push((true, self.dit * length));
+                    pattern.push((false, self.dit));
+                }
+                // extend the last symbol gap to a letter gap
+                if let Some(gap) = pattern.last_mut() {
+                    gap.1 = self.dit * 3;
+                }
+            }
+            // extend the last letter gap to a word gap
+            if let Some(gap) = pattern.last_mut() {
+                gap.1 = self.dit * 7;
+            }
+        }
+        pattern
+    }
+
+    /// Play a message in the background on an `LED`, `Buzzer` or other output device.
+    /// * `device` - The device to transmit on
+    /// * `text` - The message to send
+    /// * `repeat` - Number of times to send the message. If this is None, repeat indefinitely.
+    pub fn transmit<D: PlayPattern>(&self, device: &mut D, text: &str, repeat: Option<u32>) {
+        device.play_pattern(&self.encode(text), repeat)
+    }
+}
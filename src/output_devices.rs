@@ -1,19 +1,376 @@
 //! Output device component interfaces for devices such as `LED`, `PWMLED`, etc
+use crate::audit;
+use crate::expander::{PinDriver, PinError, PinFactory};
+use crate::pin::{Output, Pin};
+use crate::safety;
+use crate::source::{ValueSink, ValueSource, SOURCE_DELAY};
+use crate::tones::Tone;
 use rppal::gpio::{Gpio, IoPin, Level, Mode};
+use rppal::pwm::{Channel, Polarity, Pwm};
+use std::error::Error;
+use std::fmt;
+use std::panic::Location;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::thread;
 use std::thread::JoinHandle;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+/// Counts of how an output has been switched, e.g. to assert behaviour in tests or to track
+/// the wear of a relay. PWM outputs count as on while their value is above 0.0.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OutputStats {
+    /// The number of times the output was turned on
+    pub on_count: u64,
+    /// The number of times the output was turned off
+    pub off_count: u64,
+    /// The total time the output has been on, including the time it has been on now
+    pub on_time: Duration,
+}
+
+/// Keeps the `OutputStats` of an output up to date as it is switched.
+#[derive(Debug, Default)]
+struct StatsCounter {
+    stats: OutputStats,
+    on_since: Option<Instant>,
+}
+
+impl StatsCounter {
+    fn record(&mut self, active: bool) {
+        match (active, self.on_since) {
+            (true, None) => {
+                self.stats.on_count += 1;
+                self.on_since = Some(Instant::now());
+            }
+            (false, Some(since)) => {
+                self.stats.off_count += 1;
+                self.stats.on_time += since.elapsed();
+                self.on_since = None;
+            }
+            _ => (),
+        }
+    }
+
+    fn stats(&self) -> OutputStats {
+        let mut stats = self.stats;
+        if let Some(since) = self.on_since {
+            stats.on_time += since.elapsed();
+        }
+        stats
+    }
+
+    fn reset(&mut self) {
+        self.stats = OutputStats::default();
+        if self.on_since.is_some() {
+            self.on_since = Some(Instant::now());
+        }
+    }
+}
+
+/// The pin of an output device: a GPIO pin of the Pi or one handed out by a `PinFactory`.
+enum OutputLine {
+    Gpio(IoPin),
+    Driver(Box<dyn PinDriver>),
+}
+
+impl OutputLine {
+    fn pin(&self) -> u8 {
+        match self {
+            OutputLine::Gpio(pin) => pin.pin(),
+            OutputLine::Driver(pin) => pin.pin(),
+        }
+    }
+
+    fn read(&self) -> Level {
+        match self {
+            OutputLine::Gpio(pin) => pin.read(),
+            OutputLine::Driver(pin) => pin.read(),
+        }
+    }
+
+    fn set_high(&mut self) {
+        match self {
+            OutputLine::Gpio(pin) => pin.set_high(),
+            OutputLine::Driver(pin) => pin.write(Level::High),
+        }
+    }
+
+    fn set_low(&mut self) {
+        match self {
+            OutputLine::Gpio(pin) => pin.set_low(),
+            OutputLine::Driver(pin) => pin.write(Level::Low),
+        }
+    }
+
+    /// Drive the pin to a level, returning an error if its provider could not be reached.
+    /// GPIO pins of the Pi cannot fail.
+    fn try_write(&mut self, level: Level) -> Result<(), PinError> {
+        match self {
+            OutputLine::Gpio(pin) => {
+                pin.write(level);
+                Ok(())
+            }
+            OutputLine::Driver(pin) => pin.try_write(level),
+        }
+    }
+
+    /// The pin its soft PWM is staggered by, or `None` when the factory generates the PWM.
+    fn phase(&self) -> Option<u8> {
+        match self {
+            OutputLine::Gpio(pin) => Some(pin.pin()),
+            OutputLine::Driver(_) => None,
+        }
+    }
+
+    fn clear_pwm(&mut self) -> rppal::gpio::Result<()> {
+        match self {
+            OutputLine::Gpio(pin) => pin.clear_pwm(),
+            OutputLine::Driver(pin) => pin.clear_pwm().map_err(rppal::gpio::Error::from),
+        }
+    }
+
+    fn set_pwm(&mut self, period: Duration, pulse_width: Duration) -> rppal::gpio::Result<()> {
+        match self {
+            OutputLine::Gpio(pin) => pin.set_pwm(period, pulse_width),
+            OutputLine::Driver(pin) => pin
+                .set_pwm(
+                    1.0 / period.as_secs_f64(),
+                    pulse_width.as_secs_f64() / period.as_secs_f64(),
+                )
+                .map_err(rppal::gpio::Error::from),
+        }
+    }
+
+    fn set_pwm_frequency(&mut self, frequency: f64, duty_cycle: f64) -> rppal::gpio::Result<()> {
+        match self {
+            OutputLine::Gpio(pin) => pin.set_pwm_frequency(frequency, duty_cycle),
+            OutputLine::Driver(pin) => pin
+                .set_pwm(frequency, duty_cycle)
+                .map_err(rppal::gpio::Error::from),
+        }
+    }
+
+    fn reset_on_drop(&self) -> bool {
+        match self {
+            OutputLine::Gpio(pin) => pin.reset_on_drop(),
+            OutputLine::Driver(pin) => pin.reset_on_drop(),
+        }
+    }
+
+    fn set_reset_on_drop(&mut self, reset_on_drop: bool) {
+        match self {
+            OutputLine::Gpio(pin) => pin.set_reset_on_drop(reset_on_drop),
+            OutputLine::Driver(pin) => pin.set_reset_on_drop(reset_on_drop),
+        }
+    }
+}
+
+impl fmt::Debug for OutputLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OutputLine::Gpio(pin) => pin.fmt(f),
+            OutputLine::Driver(pin) => f.debug_tuple("Driver").field(&pin.pin()).finish(),
+        }
+    }
+}
 
 /// Represents a generic GPIO output device.
 #[derive(Debug)]
 pub struct OutputDevice {
-    pin: IoPin,
+    pin: OutputLine,
     active_state: bool,
     inactive_state: bool,
+    safe_state: bool,
+    stats: StatsCounter,
+}
+/// A time given in seconds to a timing method such as `blink` or `pulse` that cannot be
+/// used, holding the name of the argument and the value passed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimingError {
+    /// The time was less than zero
+    Negative(&'static str, f32),
+    /// The time was NaN or infinite
+    NotFinite(&'static str, f32),
+    /// The time was too long to be held in a `Duration`
+    TooLong(&'static str, f32),
+}
+
+impl fmt::Display for TimingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimingError::Negative(name, seconds) => {
+                write!(f, "{} must not be negative, got {}", name, seconds)
+            }
+            TimingError::NotFinite(name, seconds) => {
+                write!(
+                    f,
+                    "{} must be a finite number of seconds, got {}",
+                    name, seconds
+                )
+            }
+            TimingError::TooLong(name, seconds) => {
+                write!(f, "{} of {} seconds is too long", name, seconds)
+            }
+        }
+    }
+}
+
+impl Error for TimingError {}
+
+/// Convert a time in seconds to a `Duration`, rejecting negative, NaN, infinite and
+/// overlong values rather than letting a cast turn them into something else.
+/// * `name` - The name of the argument, used in the error
+/// * `seconds` - The time in seconds
+///
+/// # Example
+///
+/// ```
+/// use rust_gpiozero::*;
+/// use std::time::Duration;
+///
+/// assert_eq!(checked_seconds("on_time", 0.25), Ok(Duration::from_millis(250)));
+/// assert_eq!(
+///     checked_seconds("on_time", -1.0),
+///     Err(TimingError::Negative("on_time", -1.0))
+/// );
+/// assert!(checked_seconds("off_time", f32::NAN).is_err());
+/// ```
+pub fn checked_seconds(name: &'static str, seconds: f32) -> Result<Duration, TimingError> {
+    if !seconds.is_finite() {
+        return Err(TimingError::NotFinite(name, seconds));
+    }
+    if seconds < 0.0 {
+        return Err(TimingError::Negative(name, seconds));
+    }
+    Duration::try_from_secs_f32(seconds).map_err(|_| TimingError::TooLong(name, seconds))
+}
+
+/// The state a device is left in when a finite `blink`, `pulse` or pattern finishes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndState {
+    /// Turn the device off, the default
+    Off,
+    /// Leave the device fully on
+    On,
+    /// Go back to the state the device had before the pattern started
+    Restore,
+}
+
+/// Sleep for `duration`, returning early if `running` is cleared and the sleeping thread is unparked.
+pub(crate) fn sleep_while_running(running: &AtomicBool, duration: Duration) {
+    let deadline = Instant::now() + duration;
+    while running.load(Ordering::SeqCst) {
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        thread::park_timeout(deadline - now);
+    }
 }
+
+/// Sleep for `duration` of time the pattern is not paused, returning early if `running` is
+/// cleared. `hold` is called with ``True`` when the pattern pauses and ``False`` when it
+/// resumes, so the step in progress carries on where it left off.
+fn sleep_pausable<H>(running: &AtomicBool, paused: &AtomicBool, duration: Duration, mut hold: H)
+where
+    H: FnMut(bool),
+{
+    let mut deadline = Instant::now() + duration;
+    while running.load(Ordering::SeqCst) {
+        let now = Instant::now();
+        if paused.load(Ordering::SeqCst) {
+            let remaining = deadline.saturating_duration_since(now);
+            hold(true);
+            while paused.load(Ordering::SeqCst) && running.load(Ordering::SeqCst) {
+                thread::park();
+            }
+            if !running.load(Ordering::SeqCst) {
+                return;
+            }
+            hold(false);
+            deadline = Instant::now() + remaining;
+            continue;
+        }
+        if now >= deadline {
+            break;
+        }
+        thread::park_timeout(deadline - now);
+    }
+}
+
+/// Soft PWM outputs running at the same frequency, and the slot each one starts its period in.
+struct PhaseGroup {
+    frequency: f64,
+    anchor: Instant,
+    slots: Vec<Option<u8>>,
+}
+
+/// The phase groups of all soft PWM outputs in the process.
+static PHASE_GROUPS: Mutex<Vec<PhaseGroup>> = Mutex::new(Vec::new());
+
+/// Take the pin out of its phase group, e.g. when its soft PWM stops.
+fn release_phase(pin: u8) {
+    let mut groups = PHASE_GROUPS.lock().unwrap();
+    for group in groups.iter_mut() {
+        for slot in group.slots.iter_mut() {
+            if *slot == Some(pin) {
+                *slot = None;
+            }
+        }
+    }
+    groups.retain(|group| group.slots.iter().any(Option::is_some));
+}
+
+/// Returns ``True`` if the pin holds a slot among the soft PWM outputs at the frequency given,
+/// i.e. its soft PWM is already running there.
+fn in_phase(pin: u8, frequency: f64) -> bool {
+    PHASE_GROUPS
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|group| group.frequency == frequency && group.slots.contains(&Some(pin)))
+}
+
+/// Wait until the pin's turn to start a soft PWM period at the frequency given, so outputs
+/// sharing a frequency don't all switch on at the same moment.
+///
+/// The first output of a frequency starts right away. The others take the first free slot,
+/// which start at 1/2, 1/4, 3/4, 1/8 ... of the period after the first, so the on-times stay
+/// spread however many outputs there are. Each soft PWM thread keeps its own time, so the
+/// phases wander slowly over a long run; they are spread again whenever an output restarts.
+fn wait_for_phase(pin: u8, frequency: f64) {
+    release_phase(pin);
+    let start = {
+        let mut groups = PHASE_GROUPS.lock().unwrap();
+        let now = Instant::now();
+        let group = match groups.iter().position(|group| group.frequency == frequency) {
+            Some(index) => &mut groups[index],
+            None => {
+                groups.push(PhaseGroup {
+                    frequency,
+                    anchor: now,
+                    slots: vec![Some(pin)],
+                });
+                return;
+            }
+        };
+        let slot = match group.slots.iter().position(Option::is_none) {
+            Some(slot) => slot,
+            None => {
+                group.slots.push(None);
+                group.slots.len() - 1
+            }
+        };
+        group.slots[slot] = Some(pin);
+        let period = Duration::from_secs_f64(1.0 / frequency);
+        let offset = period.mul_f64(f64::from((slot as u32).reverse_bits()) / 4_294_967_296.0);
+        let periods = ((now - group.anchor).as_secs_f64() / period.as_secs_f64()).ceil();
+        group.anchor + period.mul_f64(periods) + offset
+    };
+    spin_until(start);
+}
+
 #[macro_export]
 macro_rules! impl_io_device {
     () => {
@@ -62,15 +419,18 @@ macro_rules! impl_output_device {
         }
 
         /// Turns the device on.
+        #[track_caller]
         pub fn on(&mut self) {
             self.write_state(true)
         }
 
         /// Turns the device off.
+        #[track_caller]
         pub fn off(&mut self) {
             self.write_state(false)
         }
         /// Reverse the state of the device. If it's on, turn it off; if it's off, turn it on.
+        #[track_caller]
         pub fn toggle(&mut self) {
             if self.is_active() {
                 self.off()
@@ -78,19 +438,84 @@ macro_rules! impl_output_device {
                 self.on()
             }
         }
+        #[track_caller]
         fn write_state(&mut self, value: bool) {
+            let (command, level) = if value { ("on", 1.0) } else { ("off", 0.0) };
+            audit::record(self.pin.pin(), command, Some(level), Location::caller());
+            self.set_state(value)
+        }
+
+        /// Write the state like `on` and `off`, returning an error if the pin could not be
+        /// driven, e.g. for a `Transaction` to roll back.
+        #[track_caller]
+        pub(crate) fn try_write_state(&mut self, value: bool) -> Result<(), PinError> {
+            let (command, level) = if value { ("on", 1.0) } else { ("off", 0.0) };
+            audit::record(self.pin.pin(), command, Some(level), Location::caller());
+            let level = if self.value_to_state(value) {
+                Level::High
+            } else {
+                Level::Low
+            };
+            self.pin.try_write(level)?;
+            self.stats.record(value);
+            Ok(())
+        }
+
+        /// Write the state without recording a command, for background patterns.
+        fn set_state(&mut self, value: bool) {
+            self.stats.record(value);
             if self.value_to_state(value) {
                 self.pin.set_high()
             } else {
                 self.pin.set_low()
             }
         }
+
+        /// How often the device has been turned on and off, and for how long it has been on.
+        pub fn stats(&self) -> OutputStats {
+            self.stats.stats()
+        }
+
+        /// Set the counts of `stats` back to zero.
+        pub fn reset_stats(&mut self) {
+            self.stats.reset()
+        }
+
+        /// The state the device is forced to by `force_safe_states`, ``True`` for on.
+        pub fn safe_state(&self) -> bool {
+            self.safe_state
+        }
+
+        /// Set the state the device is forced to by `force_safe_states` and the safety panic
+        /// hook. Defaults to ``False`` (off).
+        pub fn set_safe_state(&mut self, value: bool) {
+            self.safe_state = value
+        }
+
+        /// Stop any PWM and drive the pin to the safe state.
+        pub(crate) fn force_safe_state(&mut self) {
+            if self.pin.clear_pwm().is_err() {
+                println!("Could not clear pwm for pin");
+            }
+            self.set_state(self.safe_state)
+        }
+
+        /// Returns ``True`` if the pin is reset when the device is dropped.
+        pub fn reset_on_drop(&self) -> bool {
+            self.pin.reset_on_drop()
+        }
+
+        /// When enabled (the default), dropping the device turns it off and returns the pin
+        /// to the mode it had before the device was created (normally input).
+        /// Disable it to leave the pin exactly as it is.
+        pub fn set_reset_on_drop(&mut self, reset_on_drop: bool) {
+            self.pin.set_reset_on_drop(reset_on_drop)
+        }
     };
 }
 
 impl OutputDevice {
     /// Returns an OutputDevice with the pin number given
-
     ///
     /// * `pin` - The GPIO pin which the device is attached to
     ///  
@@ -101,20 +526,109 @@ impl OutputDevice {
             Ok(gpio) => match gpio.get(pin) {
                 Err(e) => panic!("{:?}", e),
                 Ok(pin) => OutputDevice {
-                    pin: pin.into_io(Mode::Output),
+                    pin: OutputLine::Gpio(pin.into_io(Mode::Output)),
                     active_state: true,
                     inactive_state: false,
+                    safe_state: false,
+                    stats: StatsCounter::default(),
                 },
             },
         }
     }
 
+    /// Returns an OutputDevice with the pin number given, wired active-low. The pin is set
+    /// high before it becomes an output, so the device never starts out active.
+    ///
+    /// * `pin` - The GPIO pin which the device is attached to
+    pub fn new_active_low(pin: u8) -> OutputDevice {
+        match Gpio::new() {
+            Err(e) => panic!("{:?}", e),
+            Ok(gpio) => match gpio.get(pin) {
+                Err(e) => panic!("{:?}", e),
+                Ok(pin) => {
+                    let mode = pin.mode();
+                    let mut pin = pin.into_io(mode);
+                    pin.set_high();
+                    pin.set_mode(Mode::Output);
+                    OutputDevice {
+                        pin: OutputLine::Gpio(pin),
+                        active_state: false,
+                        inactive_state: true,
+                        safe_state: false,
+                        stats: StatsCounter::default(),
+                    }
+                }
+            },
+        }
+    }
+
+    /// Returns an OutputDevice driving a pin already configured as an output
+    ///
+    /// * `pin` - The output pin, see the `pin` module
+    pub fn from_pin(pin: Pin<Output>) -> OutputDevice {
+        OutputDevice {
+            pin: OutputLine::Gpio(pin.into_io()),
+            active_state: true,
+            inactive_state: false,
+            safe_state: false,
+            stats: StatsCounter::default(),
+        }
+    }
+
+    /// Returns an OutputDevice on a pin of a GPIO expander or another `PinFactory`,
+    /// driven low. `pin` returns the number of the pin on the factory.
+    ///
+    /// * `factory` - The provider of the pin, e.g. an `MCP23017`
+    /// * `pin` - The number of the pin on the factory
+    pub fn with_factory<F: PinFactory>(factory: &F, pin: u8) -> OutputDevice {
+        match factory.output_pin(pin, Level::Low) {
+            Err(e) => panic!("{:?}", e),
+            Ok(pin) => OutputDevice {
+                pin: OutputLine::Driver(pin),
+                active_state: true,
+                inactive_state: false,
+                safe_state: false,
+                stats: StatsCounter::default(),
+            },
+        }
+    }
+
+    /// Returns an OutputDevice on a pin of a GPIO expander or another `PinFactory`, wired
+    /// active-low, as on most relay boards. The pin is high from the moment it becomes an
+    /// output, so the device never starts out active.
+    ///
+    /// * `factory` - The provider of the pin, e.g. a `PCF8574`
+    /// * `pin` - The number of the pin on the factory
+    pub fn with_factory_active_low<F: PinFactory>(factory: &F, pin: u8) -> OutputDevice {
+        match factory.output_pin(pin, Level::High) {
+            Err(e) => panic!("{:?}", e),
+            Ok(pin) => OutputDevice {
+                pin: OutputLine::Driver(pin),
+                active_state: false,
+                inactive_state: true,
+                safe_state: false,
+                stats: StatsCounter::default(),
+            },
+        }
+    }
+
     impl_device!();
     impl_gpio_device!();
     impl_io_device!();
     impl_output_device!();
 }
 
+impl Drop for OutputDevice {
+    fn drop(&mut self) {
+        if self.pin.reset_on_drop() {
+            if self.pin.clear_pwm().is_err() {
+                println!("Could not clear pwm for pin");
+            }
+            self.set_state(false);
+        }
+    }
+}
+
 /// Represents a generic output device with typical on/off behaviour.
 /// Extends behaviour with a blink() method which uses a background
 /// thread to handle toggling the device state without further interaction.
@@ -124,60 +638,219 @@ pub struct DigitalOutputDevice {
     blinking: Arc<AtomicBool>,
     handle: Option<JoinHandle<()>>,
     blink_count: Option<i32>,
+    end_state: EndState,
+    paused: Arc<AtomicBool>,
+    source_delay: Arc<Mutex<Duration>>,
 }
 
 macro_rules! impl_digital_output_device {
     () => {
-        fn blinker(&mut self, on_time: f32, off_time: f32, n: Option<i32>) {
-            self.stop();
+        #[track_caller]
+        pub(crate) fn blinker(
+            &mut self,
+            on_time: f32,
+            off_time: f32,
+            n: Option<i32>,
+        ) -> Result<(), TimingError> {
+            let pattern = vec![
+                (true, checked_seconds("on_time", on_time)?),
+                (false, checked_seconds("off_time", off_time)?),
+            ];
+            self.pattern_runner(pattern, n.map(|n| n.max(0) as u32));
+            Ok(())
+        }
+
+        #[track_caller]
+        fn pattern_runner(&mut self, pattern: Vec<(bool, Duration)>, repeat: Option<u32>) {
+            let end_state = match self.end_state {
+                EndState::Off => false,
+                EndState::On => true,
+                EndState::Restore => self.is_active(),
+            };
+            self.stop_and_join();
+            if pattern.is_empty() {
+                return;
+            }
+
+            audit::record(self.pin(), "pattern", None, Location::caller());
+            let device = Arc::clone(&self.device);
+            let blinking = Arc::clone(&self.blinking);
+            let paused = Arc::clone(&self.paused);
+            blinking.store(true, Ordering::SeqCst);
+
+            self.handle = Some(thread::spawn(move || {
+                // check under the lock so a cancelled pattern never overwrites the state set
+                // by whoever cancelled it
+                let set_state = |state: bool| {
+                    let mut device = device.lock().unwrap();
+                    let running = blinking.load(Ordering::SeqCst);
+                    if running {
+                        device.set_state(state);
+                    }
+                    running
+                };
+                let mut count = 0;
+                while repeat.map_or(true, |end| count < end) {
+                    for &(state, duration) in &pattern {
+                        if !set_state(state) {
+                            return;
+                        }
+                        sleep_pausable(&blinking, &paused, duration, |hold| {
+                            set_state(state && !hold);
+                        });
+                    }
+                    count += 1;
+                }
+                set_state(end_state);
+            }));
+        }
+
+        /// The state the device is left in when a finite blink or pattern finishes.
+        pub fn end_state(&self) -> EndState {
+            self.end_state
+        }
+
+        /// Set the state the device is left in when a finite blink or pattern finishes.
+        /// Defaults to `EndState::Off`.
+        pub fn set_end_state(&mut self, end_state: EndState) {
+            self.end_state = end_state
+        }
+
+        /// Suspend a blink or pattern running in the background, turning the device off
+        /// until `resume` is called, e.g. during quiet hours.
+        pub fn pause(&mut self) {
+            if let Some(handle) = self.handle.as_ref().filter(|handle| !handle.is_finished()) {
+                self.paused.store(true, Ordering::SeqCst);
+                handle.thread().unpark();
+            }
+        }
 
+        /// Resume a paused blink or pattern in phase, with the rest of the step it was
+        /// paused in.
+        pub fn resume(&mut self) {
+            if self.paused.swap(false, Ordering::SeqCst) {
+                if let Some(handle) = &self.handle {
+                    handle.thread().unpark();
+                }
+            }
+        }
+
+        /// Returns ``True`` if a blink or pattern is paused.
+        pub fn is_paused(&self) -> bool {
+            self.paused.load(Ordering::SeqCst)
+        }
+
+        /// How often the device has been turned on and off, and for how long it has been on,
+        /// counting blinks and patterns too.
+        pub fn stats(&self) -> OutputStats {
+            self.device.lock().unwrap().stats()
+        }
+
+        /// Set the counts of `stats` back to zero.
+        pub fn reset_stats(&mut self) {
+            self.device.lock().unwrap().reset_stats()
+        }
+
+        /// The state the device is forced to by `force_safe_states`, ``True`` for on.
+        pub fn safe_state(&self) -> bool {
+            self.device.lock().unwrap().safe_state()
+        }
+
+        /// Set the state the device is forced to by `force_safe_states` and the safety panic
+        /// hook, e.g. ``True`` for a vent that should open if the program fails. Defaults to
+        /// ``False`` (off).
+        pub fn set_safe_state(&mut self, value: bool) {
+            self.device.lock().unwrap().set_safe_state(value)
+        }
+
+        /// Returns a function giving the current `stats`, e.g. for a `WearTracker`. The function
+        /// does not keep the device alive: once the device is dropped it returns empty stats.
+        pub fn stats_fn(&self) -> impl Fn() -> OutputStats + Send + 'static {
+            let device = Arc::downgrade(&self.device);
+            move || {
+                device
+                    .upgrade()
+                    .map_or_else(OutputStats::default, |device| {
+                        device.lock().unwrap().stats()
+                    })
+            }
+        }
+
+        /// Play a sequence of on/off states in the background, e.g. an SOS or heartbeat pattern.
+        /// Calling `on` or `off` cancels the pattern.
+        /// * `pattern` - Pairs of state (``True`` for on) and how long to hold that state
+        /// * `repeat` - Number of times to play the pattern. If this is None, repeat indefinitely.
+        #[track_caller]
+        pub fn play_pattern(&mut self, pattern: &[(bool, Duration)], repeat: Option<u32>) {
+            self.pattern_runner(pattern.to_vec(), repeat)
+        }
+
+        /// Follow a source in the background, reading a value every `source_delay`: the
+        /// device is on for any value but 0.0, such as the 1.0 of a pressed button, and NaN
+        /// values are skipped. Calling `on`, `off`, `blink` or `set_source` stops following
+        /// it, as does the end of the source.
+        /// * `source` - The values, e.g. `values` of a shared device
+        #[track_caller]
+        pub fn set_source<S: ValueSource + 'static>(&mut self, mut source: S) {
+            self.stop_and_join();
+            audit::record(self.pin(), "source", None, Location::caller());
             let device = Arc::clone(&self.device);
             let blinking = Arc::clone(&self.blinking);
+            let delay = Arc::clone(&self.source_delay);
+            blinking.store(true, Ordering::SeqCst);
 
             self.handle = Some(thread::spawn(move || {
-                blinking.store(true, Ordering::SeqCst);
-                match n {
-                    Some(end) => {
-                        for _ in 0..end {
+                let mut state = None;
+                while blinking.load(Ordering::SeqCst) {
+                    match source.next_value() {
+                        None => return,
+                        Some(value) if value.is_nan() => {}
+                        Some(value) => {
+                            let on = value != 0.0;
+                            let mut device = device.lock().unwrap();
                             if !blinking.load(Ordering::SeqCst) {
-                                device.lock().unwrap().off();
-                                break;
+                                return;
+                            }
+                            if state.replace(on) != Some(on) {
+                                device.set_state(on);
                             }
-                            device.lock().unwrap().on();
-                            thread::sleep(Duration::from_millis((on_time * 1000.0) as u64));
-                            device.lock().unwrap().off();
-                            thread::sleep(Duration::from_millis((off_time * 1000.0) as u64));
                         }
                     }
-                    None => loop {
-                        if !blinking.load(Ordering::SeqCst) {
-                            device.lock().unwrap().off();
-                            break;
-                        }
-                        device.lock().unwrap().on();
-                        thread::sleep(Duration::from_millis((on_time * 1000.0) as u64));
-                        device.lock().unwrap().off();
-                        thread::sleep(Duration::from_millis((off_time * 1000.0) as u64));
-                    },
+                    let delay = *delay.lock().unwrap();
+                    sleep_while_running(&blinking, delay);
                 }
             }));
         }
+
+        /// How long the device waits between two values of its source.
+        pub fn source_delay(&self) -> Duration {
+            *self.source_delay.lock().unwrap()
+        }
+
+        /// Set how long the device waits between two values of its source, 10ms by default.
+        pub fn set_source_delay(&mut self, delay: Duration) {
+            *self.source_delay.lock().unwrap() = delay
+        }
+
         /// Returns ``True`` if the device is currently active and ``False`` otherwise.
         pub fn is_active(&self) -> bool {
             Arc::clone(&self.device).lock().unwrap().is_active()
         }
         /// Turns the device on.
+        #[track_caller]
         pub fn on(&self) {
             self.stop();
             self.device.lock().unwrap().on()
         }
         /// Turns the device off.
+        #[track_caller]
         pub fn off(&self) {
             self.stop();
             self.device.lock().unwrap().off()
         }
 
         /// Reverse the state of the device. If it's on, turn it off; if it's off, turn it on.
+        #[track_caller]
         pub fn toggle(&mut self) {
             self.device.lock().unwrap().toggle()
         }
@@ -189,7 +862,29 @@ macro_rules! impl_digital_output_device {
 
         fn stop(&self) {
             self.blinking.clone().store(false, Ordering::SeqCst);
-            self.device.lock().unwrap().pin.set_low();
+            self.paused.store(false, Ordering::SeqCst);
+            if let Some(handle) = &self.handle {
+                handle.thread().unpark();
+            }
+            // the caller sets the state next, so an active-low device is never left active
+            // and a device turned on while on does not glitch off
+        }
+
+        /// Stop the background thread and wait for it to finish, so it never runs alongside
+        /// the next one, which shares its flag.
+        fn stop_and_join(&mut self) {
+            self.stop();
+            if let Some(handle) = self.handle.take() {
+                handle.join().expect("Could not join spawned thread");
+            }
+        }
+
+        /// Turn the device on or off like `on` and `off`, returning an error if the pin could
+        /// not be driven.
+        #[track_caller]
+        pub(crate) fn try_set_active(&mut self, active: bool) -> Result<(), PinError> {
+            self.stop();
+            self.device.lock().unwrap().try_write_state(active)
         }
 
         /// When ``True``, the `value` property is ``True`` when the device's
@@ -211,6 +906,18 @@ macro_rules! impl_digital_output_device {
             self.device.lock().unwrap().pin.pin()
         }
 
+        /// Returns ``True`` if the pin is reset when the device is dropped.
+        pub fn reset_on_drop(&self) -> bool {
+            self.device.lock().unwrap().reset_on_drop()
+        }
+
+        /// When enabled (the default), dropping the device stops any background blinking,
+        /// turns it off and returns the pin to the mode it had before the device was created.
+        /// Disable it to leave the pin as it is once the background thread has stopped.
+        pub fn set_reset_on_drop(&mut self, reset_on_drop: bool) {
+            self.device.lock().unwrap().set_reset_on_drop(reset_on_drop)
+        }
+
         /// Shut down the device and release all associated resources.
         pub fn close(self) {
             drop(self)
@@ -218,22 +925,75 @@ macro_rules! impl_digital_output_device {
 
         /// Block until background process is done
         pub fn wait(&mut self) {
-            self.handle
-                .take()
-                .expect("Called stop on non-running thread")
-                .join()
-                .expect("Could not join spawned thread");
+            if let Some(handle) = self.handle.take() {
+                handle.join().expect("Could not join spawned thread");
+            }
         }
     };
 }
 
 impl DigitalOutputDevice {
     pub fn new(pin: u8) -> DigitalOutputDevice {
+        let blinking = Arc::new(AtomicBool::new(false));
+        DigitalOutputDevice {
+            device: safety::register(OutputDevice::new(pin), &blinking),
+            blinking,
+            handle: None,
+            blink_count: None,
+            end_state: EndState::Off,
+            paused: Arc::new(AtomicBool::new(false)),
+            source_delay: Arc::new(Mutex::new(SOURCE_DELAY)),
+        }
+    }
+
+    /// Returns a DigitalOutputDevice with the pin number given, wired active-low. The pin is set high
+    /// before it becomes an output, so the device never starts out active.
+    /// * `pin` - The GPIO pin which the device is attached to
+    pub fn new_active_low(pin: u8) -> DigitalOutputDevice {
+        let blinking = Arc::new(AtomicBool::new(false));
+        DigitalOutputDevice {
+            device: safety::register(OutputDevice::new_active_low(pin), &blinking),
+            blinking,
+            handle: None,
+            blink_count: None,
+            end_state: EndState::Off,
+            paused: Arc::new(AtomicBool::new(false)),
+            source_delay: Arc::new(Mutex::new(SOURCE_DELAY)),
+        }
+    }
+
+    /// Returns a DigitalOutputDevice on a pin of a GPIO expander or another `PinFactory`.
+    /// * `factory` - The provider of the pin, e.g. an `MCP23017`
+    /// * `pin` - The number of the pin on the factory
+    pub fn with_factory<F: PinFactory>(factory: &F, pin: u8) -> DigitalOutputDevice {
+        let blinking = Arc::new(AtomicBool::new(false));
         DigitalOutputDevice {
-            device: Arc::new(Mutex::new(OutputDevice::new(pin))),
-            blinking: Arc::new(AtomicBool::new(false)),
+            device: safety::register(OutputDevice::with_factory(factory, pin), &blinking),
+            blinking,
             handle: None,
             blink_count: None,
+            end_state: EndState::Off,
+            paused: Arc::new(AtomicBool::new(false)),
+            source_delay: Arc::new(Mutex::new(SOURCE_DELAY)),
+        }
+    }
+
+    /// Returns a DigitalOutputDevice on a pin of a GPIO expander or another `PinFactory`, wired
+    /// active-low. The pin is high from the moment it becomes an output, so the device never
+    /// starts out active.
+    /// * `factory` - The provider of the pin, e.g. a `PCF8574`
+    /// * `pin` - The number of the pin on the factory
+    pub fn with_factory_active_low<F: PinFactory>(factory: &F, pin: u8) -> DigitalOutputDevice {
+        let blinking = Arc::new(AtomicBool::new(false));
+        let device = OutputDevice::with_factory_active_low(factory, pin);
+        DigitalOutputDevice {
+            device: safety::register(device, &blinking),
+            blinking,
+            handle: None,
+            blink_count: None,
+            end_state: EndState::Off,
+            paused: Arc::new(AtomicBool::new(false)),
+            source_delay: Arc::new(Mutex::new(SOURCE_DELAY)),
         }
     }
 
@@ -244,7 +1004,9 @@ impl DigitalOutputDevice {
     /// * `on_time` - Number of seconds on
     /// * `off_time` - Number of seconds off
     ///
-    pub fn blink(&mut self, on_time: f32, off_time: f32) {
+    /// Returns an error if a time is negative, NaN or infinite.
+    #[track_caller]
+    pub fn blink(&mut self, on_time: f32, off_time: f32) -> Result<(), TimingError> {
         match self.blink_count {
             None => self.blinker(on_time, off_time, None),
             Some(n) => self.blinker(on_time, off_time, Some(n)),
@@ -274,15 +1036,73 @@ pub struct LED {
     blinking: Arc<AtomicBool>,
     handle: Option<JoinHandle<()>>,
     blink_count: Option<i32>,
+    end_state: EndState,
+    paused: Arc<AtomicBool>,
+    source_delay: Arc<Mutex<Duration>>,
 }
 
 impl LED {
     pub fn new(pin: u8) -> LED {
+        let blinking = Arc::new(AtomicBool::new(false));
+        LED {
+            device: safety::register(OutputDevice::new(pin), &blinking),
+            blinking,
+            handle: None,
+            blink_count: None,
+            end_state: EndState::Off,
+            paused: Arc::new(AtomicBool::new(false)),
+            source_delay: Arc::new(Mutex::new(SOURCE_DELAY)),
+        }
+    }
+
+    /// Returns a LED with the pin number given, wired active-low. The pin is set high
+    /// before it becomes an output, so the device never starts out active.
+    /// * `pin` - The GPIO pin which the device is attached to
+    pub fn new_active_low(pin: u8) -> LED {
+        let blinking = Arc::new(AtomicBool::new(false));
         LED {
-            device: Arc::new(Mutex::new(OutputDevice::new(pin))),
-            blinking: Arc::new(AtomicBool::new(false)),
+            device: safety::register(OutputDevice::new_active_low(pin), &blinking),
+            blinking,
             handle: None,
             blink_count: None,
+            end_state: EndState::Off,
+            paused: Arc::new(AtomicBool::new(false)),
+            source_delay: Arc::new(Mutex::new(SOURCE_DELAY)),
+        }
+    }
+
+    /// Returns an LED on a pin of a GPIO expander or another `PinFactory`.
+    /// * `factory` - The provider of the pin, e.g. an `MCP23017`
+    /// * `pin` - The number of the pin on the factory
+    pub fn with_factory<F: PinFactory>(factory: &F, pin: u8) -> LED {
+        let blinking = Arc::new(AtomicBool::new(false));
+        LED {
+            device: safety::register(OutputDevice::with_factory(factory, pin), &blinking),
+            blinking,
+            handle: None,
+            blink_count: None,
+            end_state: EndState::Off,
+            paused: Arc::new(AtomicBool::new(false)),
+            source_delay: Arc::new(Mutex::new(SOURCE_DELAY)),
+        }
+    }
+
+    /// Returns an LED on a pin of a GPIO expander or another `PinFactory`, wired
+    /// active-low. The pin is high from the moment it becomes an output, so the device never
+    /// starts out active.
+    /// * `factory` - The provider of the pin, e.g. a `PCF8574`
+    /// * `pin` - The number of the pin on the factory
+    pub fn with_factory_active_low<F: PinFactory>(factory: &F, pin: u8) -> LED {
+        let blinking = Arc::new(AtomicBool::new(false));
+        let device = OutputDevice::with_factory_active_low(factory, pin);
+        LED {
+            device: safety::register(device, &blinking),
+            blinking,
+            handle: None,
+            blink_count: None,
+            end_state: EndState::Off,
+            paused: Arc::new(AtomicBool::new(false)),
+            source_delay: Arc::new(Mutex::new(SOURCE_DELAY)),
         }
     }
 
@@ -298,7 +1118,9 @@ impl LED {
     /// * `on_time` - Number of seconds on
     /// * `off_time` - Number of seconds off
     ///
-    pub fn blink(&mut self, on_time: f32, off_time: f32) {
+    /// Returns an error if a time is negative, NaN or infinite.
+    #[track_caller]
+    pub fn blink(&mut self, on_time: f32, off_time: f32) -> Result<(), TimingError> {
         match self.blink_count {
             None => self.blinker(on_time, off_time, None),
             Some(n) => self.blinker(on_time, off_time, Some(n)),
@@ -322,15 +1144,38 @@ pub struct Buzzer {
     blinking: Arc<AtomicBool>,
     handle: Option<JoinHandle<()>>,
     blink_count: Option<i32>,
+    end_state: EndState,
+    paused: Arc<AtomicBool>,
+    source_delay: Arc<Mutex<Duration>>,
 }
 
 impl Buzzer {
     pub fn new(pin: u8) -> Buzzer {
+        let blinking = Arc::new(AtomicBool::new(false));
+        Buzzer {
+            device: safety::register(OutputDevice::new(pin), &blinking),
+            blinking,
+            handle: None,
+            blink_count: None,
+            end_state: EndState::Off,
+            paused: Arc::new(AtomicBool::new(false)),
+            source_delay: Arc::new(Mutex::new(SOURCE_DELAY)),
+        }
+    }
+
+    /// Returns a Buzzer with the pin number given, wired active-low. The pin is set high
+    /// before it becomes an output, so the device never starts out active.
+    /// * `pin` - The GPIO pin which the device is attached to
+    pub fn new_active_low(pin: u8) -> Buzzer {
+        let blinking = Arc::new(AtomicBool::new(false));
         Buzzer {
-            device: Arc::new(Mutex::new(OutputDevice::new(pin))),
-            blinking: Arc::new(AtomicBool::new(false)),
+            device: safety::register(OutputDevice::new_active_low(pin), &blinking),
+            blinking,
             handle: None,
             blink_count: None,
+            end_state: EndState::Off,
+            paused: Arc::new(AtomicBool::new(false)),
+            source_delay: Arc::new(Mutex::new(SOURCE_DELAY)),
         }
     }
 
@@ -341,7 +1186,9 @@ impl Buzzer {
     /// * `on_time` - Number of seconds on
     /// * `off_time` - Number of seconds off
     ///
-    pub fn beep(&mut self, on_time: f32, off_time: f32) {
+    /// Returns an error if a time is negative, NaN or infinite.
+    #[track_caller]
+    pub fn beep(&mut self, on_time: f32, off_time: f32) -> Result<(), TimingError> {
         match self.blink_count {
             None => self.blinker(on_time, off_time, None),
             Some(n) => self.blinker(on_time, off_time, Some(n)),
@@ -354,21 +1201,251 @@ impl Buzzer {
     }
 }
 
+/// Represents a tonal buzzer, which can play notes of different frequencies using software PWM.
+///
+/// Connect the cathode (negative pin) of the buzzer to a ground pin;
+/// connect the other side to any GPIO pin.
+#[derive(Debug)]
+pub struct TonalBuzzer {
+    device: Arc<Mutex<OutputDevice>>,
+    playing: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+    tone: Option<Tone>,
+    tempo: f32,
+}
+
+/// Handle to a melody playing in the background, which can be used to cancel it.
+#[derive(Debug, Clone)]
+pub struct MelodyHandle {
+    playing: Arc<AtomicBool>,
+}
+
+impl MelodyHandle {
+    /// Stop the melody at the end of the current note.
+    pub fn cancel(&self) {
+        self.playing.store(false, Ordering::SeqCst);
+    }
+
+    /// Returns ``True`` while the melody is still playing.
+    pub fn is_playing(&self) -> bool {
+        self.playing.load(Ordering::SeqCst)
+    }
+}
+
+impl TonalBuzzer {
+    /// Returns a TonalBuzzer with the pin number given
+    /// * `pin` - The GPIO pin which the buzzer is attached to
+    pub fn new(pin: u8) -> TonalBuzzer {
+        let playing = Arc::new(AtomicBool::new(false));
+        TonalBuzzer {
+            device: safety::register(OutputDevice::new(pin), &playing),
+            playing,
+            handle: None,
+            tone: None,
+            tempo: 60.0,
+        }
+    }
+
+    fn sound(device: &Mutex<OutputDevice>, tone: Tone) {
+        let mut device = device.lock().unwrap();
+        if tone.is_rest() {
+            if device.pin.clear_pwm().is_err() {
+                println!("Could not clear pwm for pin");
+            }
+            device.set_state(false);
+        } else if device.pin.set_pwm_frequency(tone.frequency(), 0.5).is_err() {
+            println!("Could not play tone {}", tone);
+        } else {
+            device.stats.record(true);
+        }
+    }
+
+    /// Play the given tone until `stop` is called or another tone is played.
+    /// * `tone` - The tone to play
+    pub fn play(&mut self, tone: Tone) {
+        self.stop();
+        TonalBuzzer::sound(&self.device, tone);
+        if !tone.is_rest() {
+            self.tone = Some(tone);
+        }
+    }
+
+    /// Stop playing any tone or melody.
+    pub fn stop(&mut self) {
+        self.playing.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            handle.join().expect("Could not join spawned thread");
+        }
+        TonalBuzzer::sound(&self.device, Tone::rest());
+        self.tone = None;
+    }
+
+    /// The tone currently being played, or None if the buzzer is silent.
+    pub fn tone(&self) -> Option<Tone> {
+        self.tone
+    }
+
+    /// The frequency of the tone currently being played in Hz, or 0.0 if the buzzer is silent.
+    pub fn frequency(&self) -> f64 {
+        self.tone.map_or(0.0, |tone| tone.frequency())
+    }
+
+    /// Play a tone of the given frequency, replacing the current one.
+    /// * `hz` - Frequency in Hz
+    pub fn set_frequency(&mut self, hz: f64) {
+        if !(hz > 0.0 && hz.is_finite()) {
+            println!("Frequency must be greater than 0");
+            return;
+        }
+        self.play(Tone::from_frequency(hz))
+    }
+
+    /// Returns ``True`` if a tone or melody is currently playing.
+    pub fn is_active(&self) -> bool {
+        self.tone.is_some() || self.playing.load(Ordering::SeqCst)
+    }
+
+    /// How often a tone has started and stopped, and for how long tones have played.
+    pub fn stats(&self) -> OutputStats {
+        self.device.lock().unwrap().stats()
+    }
+
+    /// Set the counts of `stats` back to zero.
+    pub fn reset_stats(&mut self) {
+        self.device.lock().unwrap().reset_stats()
+    }
+
+    /// Returns a function giving the current `stats`, e.g. for a `WearTracker`. The function
+    /// does not keep the device alive: once the device is dropped it returns empty stats.
+    pub fn stats_fn(&self) -> impl Fn() -> OutputStats + Send + 'static {
+        let device = Arc::downgrade(&self.device);
+        move || {
+            device
+                .upgrade()
+                .map_or_else(OutputStats::default, |device| {
+                    device.lock().unwrap().stats()
+                })
+        }
+    }
+
+    /// Set the tempo used by `play_melody`. Note lengths in a melody are written for 60 beats
+    /// per minute, so one second is one beat; the default tempo is 60.
+    /// * `bpm` - Beats per minute
+    pub fn set_tempo(&mut self, bpm: f32) {
+        if bpm > 0.0 {
+            self.tempo = bpm
+        } else {
+            println!("Tempo must be greater than 0");
+        }
+    }
+
+    /// The tempo used by `play_melody` in beats per minute.
+    pub fn tempo(&self) -> f32 {
+        self.tempo
+    }
+
+    /// Play a sequence of notes. Use `Tone::rest()` for a pause.
+    /// * `melody` - Pairs of tone and note length at 60 beats per minute
+    /// * `background` - If ``True``, return immediately and play the melody on a background thread;
+    ///   otherwise block until it has finished
+    pub fn play_melody(&mut self, melody: &[(Tone, Duration)], background: bool) -> MelodyHandle {
+        self.stop();
+
+        let melody = melody.to_vec();
+        let scale = 60.0 / self.tempo;
+        let device = Arc::clone(&self.device);
+        let playing = Arc::clone(&self.playing);
+        playing.store(true, Ordering::SeqCst);
+
+        let handle = thread::spawn(move || {
+            for (tone, length) in melody {
+                if !playing.load(Ordering::SeqCst) {
+                    break;
+                }
+                TonalBuzzer::sound(&device, tone);
+                sleep_while_running(&playing, length.mul_f32(scale));
+            }
+            TonalBuzzer::sound(&device, Tone::rest());
+            playing.store(false, Ordering::SeqCst);
+        });
+
+        let melody_handle = MelodyHandle {
+            playing: Arc::clone(&self.playing),
+        };
+        if background {
+            self.handle = Some(handle);
+        } else {
+            handle.join().expect("Could not join spawned thread");
+        }
+        melody_handle
+    }
+
+    /// Block until the background melody is done
+    pub fn wait(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            handle.join().expect("Could not join spawned thread");
+        }
+    }
+
+    /// The `Pin` that the device is connected to.
+    pub fn pin(&self) -> u8 {
+        self.device.lock().unwrap().pin.pin()
+    }
+
+    /// Returns ``True`` if the pin is reset when the device is dropped.
+    pub fn reset_on_drop(&self) -> bool {
+        self.device.lock().unwrap().reset_on_drop()
+    }
+
+    /// When enabled (the default), dropping the buzzer silences it and returns the pin
+    /// to the mode it had before the device was created.
+    pub fn set_reset_on_drop(&mut self, reset_on_drop: bool) {
+        self.device.lock().unwrap().set_reset_on_drop(reset_on_drop)
+    }
+
+    /// Shut down the device and release all associated resources.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+impl Drop for TonalBuzzer {
+    fn drop(&mut self) {
+        self.playing.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
 /// Generic output device configured for software pulse-width modulation (PWM).
-/// The pulse width of the signal will be 100μs with a value range of [0,100] (where 0 is a constant low and 100 is a constant high) resulting in a frequenzy of 100 Hz.
+/// The signal runs at 100 Hz by default with a value range of [0.0,1.0] (where 0.0 is a constant low and 1.0 is a constant high).
+/// The frequency can be changed at any time with `set_frequency`.
+///
+/// Outputs running at the same frequency have their phases staggered, so that several LED
+/// channels are not all switched on at the start of each period. This spreads the current
+/// they draw and reduces the ripple on the supply.
 pub struct PWMOutputDevice {
     device: Arc<Mutex<OutputDevice>>,
     blinking: Arc<AtomicBool>,
     handle: Option<JoinHandle<()>>,
     blink_count: Option<i32>,
+    end_state: EndState,
+    paused: Arc<AtomicBool>,
+    source_delay: Arc<Mutex<Duration>>,
     active_state: bool,
     inactive_state: bool,
+    frequency: Arc<Mutex<f64>>,
+    value: Arc<Mutex<Option<f64>>>,
 }
 
 macro_rules! impl_pwm_device {
     () => {
         /// Set the duty cycle of the PWM device. 0.0 is off, 1.0 is fully on.
         /// Values in between may be specified for varying levels of power in the device.
+        #[track_caller]
         pub fn set_value(&mut self, duty: f64) {
             self.write_state(duty)
         }
@@ -378,105 +1455,345 @@ macro_rules! impl_pwm_device {
             self.blink_count = Some(n)
         }
 
-        fn blinker(
+        #[track_caller]
+        pub(crate) fn blinker(
             &mut self,
             on_time: f32,
             off_time: f32,
             fade_in_time: f32,
             fade_out_time: f32,
             n: Option<i32>,
-        ) {
-            let mut sequence: Vec<(f32, f32)> = Vec::new();
+        ) -> Result<(), TimingError> {
+            let on = checked_seconds("on_time", on_time)?;
+            let off = checked_seconds("off_time", off_time)?;
+            checked_seconds("fade_in_time", fade_in_time)?;
+            checked_seconds("fade_out_time", fade_out_time)?;
+            let mut sequence: Vec<(f64, Duration)> = Vec::new();
             let fps = 25.0;
+            let frame = Duration::from_millis((1000.0 / fps) as u64);
             // create sequence for fading in
             if fade_in_time > 0.0 {
-                for i in 0..fps as i32 * fade_in_time as i32 {
-                    sequence.push((i as f32 * (1.0 / fps) / fade_in_time, 1.0 / fps))
+                for i in 0..(fps * fade_in_time) as i32 {
+                    sequence.push((f64::from(i as f32 * (1.0 / fps) / fade_in_time), frame))
                 }
             }
 
             // allow to stay on for on_time
-            sequence.push((1.0, on_time));
+            sequence.push((1.0, on));
 
             // create sequence for fading out
             if fade_out_time > 0.0 {
-                for i in 0..fps as i32 * fade_out_time as i32 {
-                    sequence.push((1.0 - (i as f32 * (1.0 / fps) / fade_out_time), 1.0 / fps))
+                for i in 0..(fps * fade_out_time) as i32 {
+                    sequence.push((
+                        f64::from(1.0 - (i as f32 * (1.0 / fps) / fade_out_time)),
+                        frame,
+                    ))
                 }
             }
 
             // allow to stay off for off_time
-            sequence.push((0.0, off_time));
+            sequence.push((0.0, off));
 
+            self.pattern_runner(sequence, n.map(|n| n.max(0) as u32));
+            Ok(())
+        }
+
+        #[track_caller]
+        fn pattern_runner(&mut self, pattern: Vec<(f64, Duration)>, repeat: Option<u32>) {
+            let end_state = match self.end_state {
+                EndState::Off => 0.0,
+                EndState::On => 1.0,
+                EndState::Restore => self.value(),
+            };
+            self.stop();
+            if pattern.is_empty() {
+                return;
+            }
+
+            let (pin, phase) = {
+                let device = self.device.lock().unwrap();
+                (device.pin.pin(), device.pin.phase())
+            };
+            audit::record(pin, "pattern", None, Location::caller());
             let device = Arc::clone(&self.device);
             let blinking = Arc::clone(&self.blinking);
+            let frequency = Arc::clone(&self.frequency);
+            let current = Arc::clone(&self.value);
+            let paused = Arc::clone(&self.paused);
+            let active_high = self.active_high();
+            blinking.store(true, Ordering::SeqCst);
 
             self.handle = Some(thread::spawn(move || {
-                blinking.store(true, Ordering::SeqCst);
-                match n {
-                    Some(end) => {
-                        for _ in 0..end {
-                            for (value, delay) in &sequence {
-                                if !blinking.load(Ordering::SeqCst) {
-                                    // device.lock().unwrap().off();
-                                    break;
-                                }
-                                device
-                                    .lock()
-                                    .unwrap()
-                                    .pin
-                                    .set_pwm_frequency(100.0, f64::from(*value))
-                                    .unwrap();
-                                thread::sleep(Duration::from_millis((delay * 1000 as f32) as u64));
-                            }
+                if let Some(pin) = phase {
+                    wait_for_phase(pin, *frequency.lock().unwrap());
+                }
+                // check under the lock so a cancelled pattern never overwrites the value set
+                // by whoever cancelled it
+                let set_value = |value: f64| {
+                    let mut device = device.lock().unwrap();
+                    let running = blinking.load(Ordering::SeqCst);
+                    if running {
+                        let frequency = *frequency.lock().unwrap();
+                        let duty = if active_high { value } else { 1.0 - value };
+                        *current.lock().unwrap() = Some(value);
+                        device.stats.record(value > 0.0);
+                        device.pin.set_pwm_frequency(frequency, duty).unwrap();
+                    }
+                    running
+                };
+                let mut count = 0;
+                while repeat.map_or(true, |end| count < end) {
+                    for &(value, delay) in &pattern {
+                        if !set_value(value) {
+                            return;
                         }
+                        sleep_pausable(&blinking, &paused, delay, |hold| {
+                            set_value(if hold { 0.0 } else { value });
+                        });
                     }
-                    None => loop {
-                        for (value, delay) in &sequence {
+                    count += 1;
+                }
+                set_value(end_state);
+            }));
+        }
+
+        /// The state the device is left in when a finite blink, pulse or pattern finishes.
+        pub fn end_state(&self) -> EndState {
+            self.end_state
+        }
+
+        /// Set the state the device is left in when a finite blink, pulse or pattern
+        /// finishes. Defaults to `EndState::Off`.
+        pub fn set_end_state(&mut self, end_state: EndState) {
+            self.end_state = end_state
+        }
+
+        /// Suspend a blink or pattern running in the background, turning the device off
+        /// until `resume` is called, e.g. during quiet hours.
+        pub fn pause(&mut self) {
+            if let Some(handle) = self.handle.as_ref().filter(|handle| !handle.is_finished()) {
+                self.paused.store(true, Ordering::SeqCst);
+                handle.thread().unpark();
+            }
+        }
+
+        /// Resume a paused blink or pattern in phase, with the rest of the step it was
+        /// paused in.
+        pub fn resume(&mut self) {
+            if self.paused.swap(false, Ordering::SeqCst) {
+                if let Some(handle) = &self.handle {
+                    handle.thread().unpark();
+                }
+            }
+        }
+
+        /// Returns ``True`` if a blink or pattern is paused.
+        pub fn is_paused(&self) -> bool {
+            self.paused.load(Ordering::SeqCst)
+        }
+
+        /// How often the device has been turned on and off, and for how long it has been on,
+        /// counting blinks and patterns too.
+        pub fn stats(&self) -> OutputStats {
+            self.device.lock().unwrap().stats()
+        }
+
+        /// Set the counts of `stats` back to zero.
+        pub fn reset_stats(&mut self) {
+            self.device.lock().unwrap().reset_stats()
+        }
+
+        /// The state the device is forced to by `force_safe_states`, ``True`` for on.
+        pub fn safe_state(&self) -> bool {
+            self.device.lock().unwrap().safe_state()
+        }
+
+        /// Set the state the device is forced to by `force_safe_states` and the safety panic
+        /// hook, e.g. ``True`` for a vent that should open if the program fails. Defaults to
+        /// ``False`` (off).
+        pub fn set_safe_state(&mut self, value: bool) {
+            self.device.lock().unwrap().set_safe_state(value)
+        }
+
+        /// Returns a function giving the current `stats`, e.g. for a `WearTracker`. The function
+        /// does not keep the device alive: once the device is dropped it returns empty stats.
+        pub fn stats_fn(&self) -> impl Fn() -> OutputStats + Send + 'static {
+            let device = Arc::downgrade(&self.device);
+            move || {
+                device
+                    .upgrade()
+                    .map_or_else(OutputStats::default, |device| {
+                        device.lock().unwrap().stats()
+                    })
+            }
+        }
+
+        /// Play a sequence of duty cycles in the background, e.g. a heartbeat pattern.
+        /// Calling `on`, `off` or `set_value` cancels the pattern.
+        /// * `pattern` - Pairs of duty cycle (0.0 to 1.0) and how long to hold that value
+        /// * `repeat` - Number of times to play the pattern. If this is None, repeat indefinitely.
+        #[track_caller]
+        pub fn play_pattern(&mut self, pattern: &[(f64, Duration)], repeat: Option<u32>) {
+            if pattern
+                .iter()
+                .any(|&(value, _)| !(0.0..=1.0).contains(&value))
+            {
+                println!("Value must be between 0.0 and 1.0");
+                return;
+            }
+            self.pattern_runner(pattern.to_vec(), repeat)
+        }
+
+        /// Follow a source in the background, reading a value every `source_delay` and
+        /// setting the duty cycle to it, limited to between 0.0 and 1.0; NaN values are
+        /// skipped. Calling `on`, `off`, `set_value`, `blink`, `pulse` or `set_source` stops
+        /// following it, as does the end of the source.
+        /// * `source` - The values, e.g. `values` of a shared analog input
+        #[track_caller]
+        pub fn set_source<S: ValueSource + 'static>(&mut self, mut source: S) {
+            self.stop();
+            let (pin, phase) = {
+                let device = self.device.lock().unwrap();
+                (device.pin.pin(), device.pin.phase())
+            };
+            audit::record(pin, "source", None, Location::caller());
+            let device = Arc::clone(&self.device);
+            let blinking = Arc::clone(&self.blinking);
+            let frequency = Arc::clone(&self.frequency);
+            let current = Arc::clone(&self.value);
+            let delay = Arc::clone(&self.source_delay);
+            let active_high = self.active_high();
+            blinking.store(true, Ordering::SeqCst);
+
+            self.handle = Some(thread::spawn(move || {
+                if let Some(pin) = phase {
+                    wait_for_phase(pin, *frequency.lock().unwrap());
+                }
+                let mut last = None;
+                while blinking.load(Ordering::SeqCst) {
+                    match source.next_value() {
+                        None => return,
+                        Some(value) if value.is_nan() => {}
+                        Some(value) => {
+                            let value = value.clamp(0.0, 1.0);
+                            let mut device = device.lock().unwrap();
                             if !blinking.load(Ordering::SeqCst) {
-                                // device.lock().unwrap().off();
-                                break;
+                                return;
+                            }
+                            if last.replace(value) != Some(value) {
+                                let frequency = *frequency.lock().unwrap();
+                                let duty = if active_high { value } else { 1.0 - value };
+                                *current.lock().unwrap() = Some(value);
+                                device.stats.record(value > 0.0);
+                                device.pin.set_pwm_frequency(frequency, duty).unwrap();
                             }
-                            device
-                                .lock()
-                                .unwrap()
-                                .pin
-                                .set_pwm_frequency(100.0, f64::from(*value))
-                                .unwrap();
-                            thread::sleep(Duration::from_millis((delay * 1000 as f32) as u64));
                         }
-                    },
+                    }
+                    let delay = *delay.lock().unwrap();
+                    sleep_while_running(&blinking, delay);
                 }
             }));
         }
 
-        fn stop(&mut self) {
-            self.blinking.clone().store(false, Ordering::SeqCst);
-            if self.device.lock().unwrap().pin.clear_pwm().is_err() {
-                println!("Could not clear pwm for pin");
-            };
+        /// How long the device waits between two values of its source.
+        pub fn source_delay(&self) -> Duration {
+            *self.source_delay.lock().unwrap()
+        }
+
+        /// Set how long the device waits between two values of its source, 10ms by default.
+        pub fn set_source_delay(&mut self, delay: Duration) {
+            *self.source_delay.lock().unwrap() = delay
+        }
+
+        fn stop(&mut self) {
+            self.stop_worker();
+            let mut device = self.device.lock().unwrap();
+            if device.pin.clear_pwm().is_err() {
+                println!("Could not clear pwm for pin");
+            };
+            if let Some(pin) = device.pin.phase() {
+                release_phase(pin);
+            }
+        }
+
+        /// Stop the background thread, leaving the soft PWM running.
+        fn stop_worker(&mut self) {
+            self.blinking.clone().store(false, Ordering::SeqCst);
+            self.paused.store(false, Ordering::SeqCst);
+            // wait for the thread, so it never runs alongside the next one sharing its flag
+            if let Some(handle) = self.handle.take() {
+                handle.thread().unpark();
+                handle.join().expect("Could not join spawned thread");
+            }
+        }
+
+        #[track_caller]
+        fn write_state(&mut self, value: f64) {
+            if !(0.0..=1.0).contains(&value) {
+                println!("Value must be between 0.0 and 1.0");
+                return;
+            }
+            let pin = self.device.lock().unwrap().pin.pin();
+            audit::record(pin, "value", Some(value), Location::caller());
+            self.stop_worker();
+            *self.value.lock().unwrap() = Some(value);
+            self.device.lock().unwrap().stats.record(value > 0.0);
+            self.start_pwm(value)
+        }
+
+        /// Start the soft PWM, staggered against other outputs at the same frequency. Fully
+        /// on or off never switches, so it starts right away, and a soft PWM already running
+        /// in its slot keeps its phase when only the duty cycle changes.
+        fn start_pwm(&self, value: f64) {
+            let (frequency, duty) = (self.frequency(), self.duty(value));
+            let phase = self.device.lock().unwrap().pin.phase();
+            if let (Some(pin), true) = (phase, duty > 0.0 && duty < 1.0) {
+                if !in_phase(pin, frequency) {
+                    wait_for_phase(pin, frequency);
+                }
+            }
+            let mut device = self.device.lock().unwrap();
+            device.pin.set_pwm_frequency(frequency, duty).unwrap()
+        }
+
+        fn duty(&self, value: f64) -> f64 {
+            if self.active_high() {
+                value
+            } else {
+                1.0 - value
+            }
+        }
+
+        /// The duty cycle of the PWM device between 0.0 (off) and 1.0 (fully on).
+        /// While blinking or pulsing this follows the background sequence.
+        pub fn value(&self) -> f64 {
+            self.value.lock().unwrap().unwrap_or(0.0)
+        }
+
+        /// Returns ``True`` if the device is currently active (its value is above 0.0) and ``False`` otherwise.
+        pub fn is_active(&self) -> bool {
+            self.value() > 0.0
         }
 
-        fn write_state(&mut self, value: f64) {
-            if !(value >= 0.0 && value <= 1.0) {
-                println!("Value must be between 0.0 and 1.0");
+        /// The frequency of the PWM signal in Hz.
+        pub fn frequency(&self) -> f64 {
+            *self.frequency.lock().unwrap()
+        }
+
+        /// Set the frequency of the PWM signal. This takes effect immediately, including while
+        /// blinking or pulsing, e.g. raise it above 20 kHz to avoid audible motor whine.
+        /// * `hz` - Frequency in Hz
+        pub fn set_frequency(&mut self, hz: f64) {
+            if !(hz > 0.0 && hz.is_finite()) {
+                println!("Frequency must be greater than 0");
                 return;
             }
-            self.stop();
-            if self.active_high() {
-                self.device
-                    .lock()
-                    .unwrap()
-                    .pin
-                    .set_pwm_frequency(100.0, value)
-                    .unwrap()
-            } else {
-                self.device
-                    .lock()
-                    .unwrap()
-                    .pin
-                    .set_pwm_frequency(100.0, 1.0 - value)
-                    .unwrap()
+            *self.frequency.lock().unwrap() = hz;
+            let value = *self.value.lock().unwrap();
+            if let (Some(value), false) = (value, self.blinking.load(Ordering::SeqCst)) {
+                // restart so the output is spread among those at its new frequency
+                self.stop();
+                self.start_pwm(value)
             }
         }
 
@@ -489,6 +1806,8 @@ macro_rules! impl_pwm_device {
                 self.active_state = false;
                 self.inactive_state = true;
             }
+            // keep the pin's idle level correct when the device is dropped
+            self.device.lock().unwrap().set_active_high(value);
         }
         /// When ``True``, the `value` property is ``True`` when the device's
         /// `pin` is high. When ``False`` the `value` property is
@@ -499,15 +1818,36 @@ macro_rules! impl_pwm_device {
             self.active_state
         }
 
+        /// Returns ``True`` if the pin is reset when the device is dropped.
+        pub fn reset_on_drop(&self) -> bool {
+            self.device.lock().unwrap().reset_on_drop()
+        }
+
+        /// When enabled (the default), dropping the device stops any background blinking,
+        /// turns it off and returns the pin to the mode it had before the device was created.
+        /// Disable it to leave the pin as it is once the background thread has stopped.
+        pub fn set_reset_on_drop(&mut self, reset_on_drop: bool) {
+            self.device.lock().unwrap().set_reset_on_drop(reset_on_drop)
+        }
+
         /// Turns the device on.
+        #[track_caller]
         pub fn on(&mut self) {
             self.write_state(1.0)
         }
 
         /// Turns the device off.
+        #[track_caller]
         pub fn off(&mut self) {
             self.write_state(0.0)
         }
+
+        /// Reverse the state of the device, e.g. a value of 0.25 becomes 0.75.
+        #[track_caller]
+        pub fn toggle(&mut self) {
+            let value = self.value();
+            self.write_state(1.0 - value)
+        }
     };
 }
 
@@ -517,13 +1857,61 @@ impl PWMOutputDevice {
     /// * `pin` - The GPIO pin which the device is attached to
     ///  
     pub fn new(pin: u8) -> PWMOutputDevice {
+        let blinking = Arc::new(AtomicBool::new(false));
+        PWMOutputDevice {
+            device: safety::register(OutputDevice::new(pin), &blinking),
+            blinking,
+            handle: None,
+            blink_count: None,
+            end_state: EndState::Off,
+            paused: Arc::new(AtomicBool::new(false)),
+            source_delay: Arc::new(Mutex::new(SOURCE_DELAY)),
+            active_state: true,
+            inactive_state: false,
+            frequency: Arc::new(Mutex::new(100.0)),
+            value: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Returns a PWMOutputDevice with the pin number given, wired active-low. The pin is set
+    /// high before it becomes an output, so the device never starts out active.
+    ///
+    /// * `pin` - The GPIO pin which the device is attached to
+    pub fn new_active_low(pin: u8) -> PWMOutputDevice {
+        let blinking = Arc::new(AtomicBool::new(false));
+        PWMOutputDevice {
+            device: safety::register(OutputDevice::new_active_low(pin), &blinking),
+            blinking,
+            handle: None,
+            blink_count: None,
+            end_state: EndState::Off,
+            paused: Arc::new(AtomicBool::new(false)),
+            source_delay: Arc::new(Mutex::new(SOURCE_DELAY)),
+            active_state: false,
+            inactive_state: true,
+            frequency: Arc::new(Mutex::new(100.0)),
+            value: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Returns a PWMOutputDevice on a channel of a PWM driver such as a `PCA9685`. The
+    /// driver generates the PWM at its own frequency, which `set_frequency` does not change.
+    /// * `factory` - The provider of the pin, e.g. a `PCA9685`
+    /// * `pin` - The number of the channel on the factory
+    pub fn with_factory<F: PinFactory>(factory: &F, pin: u8) -> PWMOutputDevice {
+        let blinking = Arc::new(AtomicBool::new(false));
         PWMOutputDevice {
-            device: Arc::new(Mutex::new(OutputDevice::new(pin))),
-            blinking: Arc::new(AtomicBool::new(false)),
+            device: safety::register(OutputDevice::with_factory(factory, pin), &blinking),
+            blinking,
             handle: None,
             blink_count: None,
+            end_state: EndState::Off,
+            paused: Arc::new(AtomicBool::new(false)),
+            source_delay: Arc::new(Mutex::new(SOURCE_DELAY)),
             active_state: true,
             inactive_state: false,
+            frequency: Arc::new(Mutex::new(100.0)),
+            value: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -535,7 +1923,15 @@ impl PWMOutputDevice {
     /// * `fade_in_time` - Number of seconds to spend fading in
     /// * `fade_out_time` - Number of seconds to spend fading out
     ///
-    pub fn blink(&mut self, on_time: f32, off_time: f32, fade_in_time: f32, fade_out_time: f32) {
+    /// Returns an error if a time is negative, NaN or infinite.
+    #[track_caller]
+    pub fn blink(
+        &mut self,
+        on_time: f32,
+        off_time: f32,
+        fade_in_time: f32,
+        fade_out_time: f32,
+    ) -> Result<(), TimingError> {
         match self.blink_count {
             None => self.blinker(on_time, off_time, fade_in_time, fade_out_time, None),
             Some(n) => self.blinker(on_time, off_time, fade_in_time, fade_out_time, Some(n)),
@@ -546,7 +1942,9 @@ impl PWMOutputDevice {
     /// * `fade_in_time` - Number of seconds to spend fading in
     /// * `fade_out_time` - Number of seconds to spend fading out
     ///
-    pub fn pulse(&mut self, fade_in_time: f32, fade_out_time: f32) {
+    /// Returns an error if a time is negative, NaN or infinite.
+    #[track_caller]
+    pub fn pulse(&mut self, fade_in_time: f32, fade_out_time: f32) -> Result<(), TimingError> {
         self.blink(0.0, 0.0, fade_in_time, fade_out_time)
     }
 }
@@ -566,22 +1964,76 @@ impl PWMLED {
         PWMLED(PWMOutputDevice::new(pin))
     }
 
+    /// Returns a PWMLED with the pin number given, wired active-low (e.g. the cathode on the
+    /// pin and the anode on 3V3). The LED starts out off.
+    ///
+    /// * `pin` - The GPIO pin which the device is attached to
+    pub fn new_active_low(pin: u8) -> PWMLED {
+        PWMLED(PWMOutputDevice::new_active_low(pin))
+    }
+
+    /// Returns a PWMLED on a channel of a PWM driver such as a `PCA9685`.
+    /// * `factory` - The provider of the pin, e.g. a `PCA9685`
+    /// * `pin` - The number of the channel on the factory
+    pub fn with_factory<F: PinFactory>(factory: &F, pin: u8) -> PWMLED {
+        PWMLED(PWMOutputDevice::with_factory(factory, pin))
+    }
+
+    #[track_caller]
+    pub(crate) fn blinker(
+        &mut self,
+        on_time: f32,
+        off_time: f32,
+        fade_in_time: f32,
+        fade_out_time: f32,
+        n: Option<i32>,
+    ) -> Result<(), TimingError> {
+        self.0
+            .blinker(on_time, off_time, fade_in_time, fade_out_time, n)
+    }
+
+    /// The brightness of the LED between 0.0 (off) and 1.0 (fully on).
+    pub fn value(&self) -> f64 {
+        self.0.value()
+    }
+
+    /// Returns ``True`` if the LED is currently lit and ``False`` otherwise.
+    pub fn is_lit(&self) -> bool {
+        self.0.is_active()
+    }
+
+    /// Reverse the brightness of the LED, e.g. a value of 0.25 becomes 0.75.
+    #[track_caller]
+    pub fn toggle(&mut self) {
+        self.0.toggle()
+    }
+
     /// Make the device turn on and off repeatedly
     /// * `on_time` - Number of seconds on
     /// * `off_time` - Number of seconds off
     /// * `fade_in_time` - Number of seconds to spend fading in
     /// * `fade_out_time` - Number of seconds to spend fading out
     ///
-    pub fn blink(&mut self, on_time: f32, off_time: f32, fade_in_time: f32, fade_out_time: f32) {
+    /// Returns an error if a time is negative, NaN or infinite.
+    #[track_caller]
+    pub fn blink(
+        &mut self,
+        on_time: f32,
+        off_time: f32,
+        fade_in_time: f32,
+        fade_out_time: f32,
+    ) -> Result<(), TimingError> {
         self.0.blink(on_time, off_time, fade_in_time, fade_out_time)
     }
 
     /// Turns the device on.
+    #[track_caller]
     pub fn on(&mut self) {
         self.0.on();
     }
 
     /// Turns the device off.
+    #[track_caller]
     pub fn off(&mut self) {
         self.0.off();
     }
@@ -590,12 +2042,41 @@ impl PWMLED {
     /// * `fade_in_time` - Number of seconds to spend fading in
     /// * `fade_out_time` - Number of seconds to spend fading out
     ///
-    pub fn pulse(&mut self, fade_in_time: f32, fade_out_time: f32) {
-        self.0.pulse(fade_in_time, fade_out_time);
+    /// Returns an error if a time is negative, NaN or infinite.
+    #[track_caller]
+    pub fn pulse(&mut self, fade_in_time: f32, fade_out_time: f32) -> Result<(), TimingError> {
+        self.0.pulse(fade_in_time, fade_out_time)
+    }
+
+    /// Play a sequence of brightness values in the background, e.g. a heartbeat pattern.
+    /// * `pattern` - Pairs of brightness (0.0 to 1.0) and how long to hold that value
+    /// * `repeat` - Number of times to play the pattern. If this is None, repeat indefinitely.
+    #[track_caller]
+    pub fn play_pattern(&mut self, pattern: &[(f64, Duration)], repeat: Option<u32>) {
+        self.0.play_pattern(pattern, repeat);
+    }
+
+    /// Follow a source in the background, setting the brightness to each of its values,
+    /// limited to between 0.0 and 1.0.
+    /// * `source` - The values, e.g. `values` of a shared analog input
+    #[track_caller]
+    pub fn set_source<S: ValueSource + 'static>(&mut self, source: S) {
+        self.0.set_source(source);
+    }
+
+    /// How long the LED waits between two values of its source.
+    pub fn source_delay(&self) -> Duration {
+        self.0.source_delay()
+    }
+
+    /// Set how long the LED waits between two values of its source, 10ms by default.
+    pub fn set_source_delay(&mut self, delay: Duration) {
+        self.0.set_source_delay(delay)
     }
 
     /// Set the duty cycle of the PWM device. 0.0 is off, 1.0 is fully on.
     /// Values in between may be specified for varying levels of power in the device.
+    #[track_caller]
     pub fn set_value(&mut self, value: f64) {
         self.0.set_value(value);
     }
@@ -605,6 +2086,171 @@ impl PWMLED {
     pub fn set_blink_count(&mut self, n: i32) {
         self.0.blink_count = Some(n)
     }
+
+    /// The state the LED is left in when a finite blink, pulse or pattern finishes.
+    pub fn end_state(&self) -> EndState {
+        self.0.end_state
+    }
+
+    /// Set the state the LED is left in when a finite blink, pulse or pattern finishes.
+    /// Defaults to `EndState::Off`.
+    pub fn set_end_state(&mut self, end_state: EndState) {
+        self.0.end_state = end_state
+    }
+
+    /// Suspend a blink, pulse or pattern running in the background, turning the LED off
+    /// until `resume` is called.
+    pub fn pause(&mut self) {
+        self.0.pause()
+    }
+
+    /// Resume a paused blink, pulse or pattern in phase.
+    pub fn resume(&mut self) {
+        self.0.resume()
+    }
+
+    /// Returns ``True`` if a blink, pulse or pattern is paused.
+    pub fn is_paused(&self) -> bool {
+        self.0.is_paused()
+    }
+
+    /// How often the LED has been turned on and off, and for how long it has been lit.
+    pub fn stats(&self) -> OutputStats {
+        self.0.stats()
+    }
+
+    /// Set the counts of `stats` back to zero.
+    pub fn reset_stats(&mut self) {
+        self.0.reset_stats()
+    }
+
+    /// The state the LED is forced to by `force_safe_states`, ``True`` for fully on.
+    pub fn safe_state(&self) -> bool {
+        self.0.safe_state()
+    }
+
+    /// Set the state the LED is forced to by `force_safe_states` and the safety panic hook.
+    /// Defaults to ``False`` (off).
+    pub fn set_safe_state(&mut self, value: bool) {
+        self.0.set_safe_state(value)
+    }
+
+    /// Returns a function giving the current `stats`, e.g. for a `WearTracker`.
+    pub fn stats_fn(&self) -> impl Fn() -> OutputStats + Send + 'static {
+        self.0.stats_fn()
+    }
+
+    /// The frequency of the PWM signal in Hz.
+    pub fn frequency(&self) -> f64 {
+        self.0.frequency()
+    }
+
+    /// Set the frequency of the PWM signal, e.g. raise it to reduce visible flicker.
+    /// * `hz` - Frequency in Hz
+    pub fn set_frequency(&mut self, hz: f64) {
+        self.0.set_frequency(hz)
+    }
+
+    /// Returns ``True`` if the pin is reset when the device is dropped.
+    pub fn reset_on_drop(&self) -> bool {
+        self.0.reset_on_drop()
+    }
+
+    /// When enabled (the default), dropping the LED stops any background blinking,
+    /// turns it off and returns the pin to the mode it had before the device was created.
+    pub fn set_reset_on_drop(&mut self, reset_on_drop: bool) {
+        self.0.set_reset_on_drop(reset_on_drop)
+    }
+}
+
+/// Represents a full color LED component (composed of red, green, and blue LEDs),
+/// with the brightness of each color set by PWM.
+/// Connect the common cathode (longest leg) to a ground pin; connect each of the
+/// other legs (representing the red, green, and blue anodes) to any GPIO pins.
+/// You should use three limiting resistors (one per anode).
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut led = RGBLED::new(9, 10, 11);
+/// // purple
+/// led.set_value((1.0, 0.0, 1.0));
+/// ```
+pub struct RGBLED {
+    red: PWMLED,
+    green: PWMLED,
+    blue: PWMLED,
+}
+
+impl RGBLED {
+    /// Returns an RGBLED with the pins given
+    /// * `red` - The GPIO pin of the red LED
+    /// * `green` - The GPIO pin of the green LED
+    /// * `blue` - The GPIO pin of the blue LED
+    pub fn new(red: u8, green: u8, blue: u8) -> RGBLED {
+        RGBLED {
+            red: PWMLED::new(red),
+            green: PWMLED::new(green),
+            blue: PWMLED::new(blue),
+        }
+    }
+
+    /// Returns an RGBLED with a common anode, whose legs are active-low. The LED starts out off.
+    /// * `red` - The GPIO pin of the red LED
+    /// * `green` - The GPIO pin of the green LED
+    /// * `blue` - The GPIO pin of the blue LED
+    pub fn new_active_low(red: u8, green: u8, blue: u8) -> RGBLED {
+        RGBLED {
+            red: PWMLED::new_active_low(red),
+            green: PWMLED::new_active_low(green),
+            blue: PWMLED::new_active_low(blue),
+        }
+    }
+
+    /// Returns an RGBLED for the PiBorg LedBorg (red 17, green 27, blue 22)
+    pub fn ledborg() -> RGBLED {
+        RGBLED::new(17, 27, 22)
+    }
+
+    /// The brightness of the red, green and blue LEDs, each between 0.0 and 1.0.
+    pub fn value(&self) -> (f64, f64, f64) {
+        (self.red.value(), self.green.value(), self.blue.value())
+    }
+
+    /// Set the color of the LED as the brightness of the red, green and blue LEDs,
+    /// each between 0.0 and 1.0. For example, (1.0, 1.0, 0.0) is yellow.
+    #[track_caller]
+    pub fn set_value(&mut self, value: (f64, f64, f64)) {
+        self.red.set_value(value.0);
+        self.green.set_value(value.1);
+        self.blue.set_value(value.2);
+    }
+
+    /// Returns ``True`` if any of the LEDs is lit.
+    pub fn is_lit(&self) -> bool {
+        self.red.is_lit() || self.green.is_lit() || self.blue.is_lit()
+    }
+
+    /// Turn the LED on, making it white.
+    #[track_caller]
+    pub fn on(&mut self) {
+        self.set_value((1.0, 1.0, 1.0))
+    }
+
+    /// Turn the LED off.
+    #[track_caller]
+    pub fn off(&mut self) {
+        self.set_value((0.0, 0.0, 0.0))
+    }
+
+    /// Invert the color of the LED, e.g. red becomes cyan.
+    #[track_caller]
+    pub fn toggle(&mut self) {
+        let (red, green, blue) = self.value();
+        self.set_value((1.0 - red, 1.0 - green, 1.0 - blue))
+    }
 }
 
 struct MotorCompositeDevice(PWMOutputDevice, PWMOutputDevice);
@@ -615,6 +2261,7 @@ struct MotorCompositeDevice(PWMOutputDevice, PWMOutputDevice);
 ///  to the controller; connect the outputs of the controller board to the two terminals of the motor; connect the inputs of the controller board to two GPIO pins.
 pub struct Motor {
     devices: MotorCompositeDevice,
+    enable: Option<PWMOutputDevice>,
     speed: f64,
 }
 
@@ -627,6 +2274,43 @@ impl Motor {
         let backward = PWMOutputDevice::new(backward_pin);
         Motor {
             devices: MotorCompositeDevice(forward, backward),
+            enable: None,
+            speed: 1.0,
+        }
+    }
+
+    /// creates a Motor on two channels of a PWM driver such as a `PCA9685`
+    /// * `factory` - The provider of the pins, e.g. a `PCA9685`
+    /// * `forward_pin` - The channel the forward input of the motor driver chip is connected to
+    /// * `backward_pin` - The channel the backward input of the motor driver chip is connected to
+    pub fn with_factory<F: PinFactory>(factory: &F, forward_pin: u8, backward_pin: u8) -> Motor {
+        let forward = PWMOutputDevice::with_factory(factory, forward_pin);
+        let backward = PWMOutputDevice::with_factory(factory, backward_pin);
+        Motor {
+            devices: MotorCompositeDevice(forward, backward),
+            enable: None,
+            speed: 1.0,
+        }
+    }
+
+    /// creates a Motor for a driver chip with an enable input, such as the TB6612FNG of
+    /// motor HATs or an L298N, on channels of a PWM driver such as a `PCA9685`. The speed is
+    /// set by PWM on the enable input and the direction by the forward and backward inputs.
+    /// * `factory` - The provider of the pins, e.g. a `PCA9685`
+    /// * `forward_pin` - The channel the forward input of the motor driver chip is connected to
+    /// * `backward_pin` - The channel the backward input of the motor driver chip is connected to
+    /// * `enable_pin` - The channel the enable (PWM) input of the motor driver chip is connected to
+    pub fn with_factory_enable<F: PinFactory>(
+        factory: &F,
+        forward_pin: u8,
+        backward_pin: u8,
+        enable_pin: u8,
+    ) -> Motor {
+        let forward = PWMOutputDevice::with_factory(factory, forward_pin);
+        let backward = PWMOutputDevice::with_factory(factory, backward_pin);
+        Motor {
+            devices: MotorCompositeDevice(forward, backward),
+            enable: Some(PWMOutputDevice::with_factory(factory, enable_pin)),
             speed: 1.0,
         }
     }
@@ -635,37 +2319,98 @@ impl Motor {
     /// You can change the speed using `set_speed` before calling `forward`
     pub fn forward(&mut self) {
         self.devices.1.off();
-        self.devices.0.set_value(self.speed);
+        match &mut self.enable {
+            None => self.devices.0.set_value(self.speed),
+            Some(enable) => {
+                self.devices.0.on();
+                enable.set_value(self.speed);
+            }
+        }
     }
 
     /// Drive the motor backwards.
     /// You can change the speed using `set_speed` before calling `backward`
     pub fn backward(&mut self) {
         self.devices.0.off();
-        self.devices.1.set_value(self.speed);
+        match &mut self.enable {
+            None => self.devices.1.set_value(self.speed),
+            Some(enable) => {
+                self.devices.1.on();
+                enable.set_value(self.speed);
+            }
+        }
     }
 
     /// Stop the motor.
     pub fn stop(&mut self) {
         self.devices.0.off();
         self.devices.1.off();
+        if let Some(enable) = &mut self.enable {
+            enable.off();
+        }
     }
 
     /// The speed at which the motor should turn.
     /// Can be any value between 0.0 (stopped) and the default 1.0 (maximum speed)
     pub fn set_speed(&mut self, speed: f64) {
-        if !(speed >= 0.0 && speed <= 1.0) {
+        if !(0.0..=1.0).contains(&speed) {
             println!("Speed must be between 0.0 and 1.0");
             return;
         }
         self.speed = speed
     }
+
+    /// The speed and direction the motor is driven at, between -1.0 (full speed
+    /// backwards) and 1.0 (full speed forwards), 0.0 when stopped.
+    pub fn value(&self) -> f64 {
+        let direction = self.devices.0.value() - self.devices.1.value();
+        match &self.enable {
+            None => direction,
+            Some(enable) => direction * enable.value(),
+        }
+    }
+
+    /// Returns ``True`` if the motor is being driven.
+    pub fn is_active(&self) -> bool {
+        self.value() != 0.0
+    }
+
+    /// The frequency of the PWM signal driving the motor in Hz.
+    pub fn frequency(&self) -> f64 {
+        self.devices.0.frequency()
+    }
+
+    /// Set the frequency of the PWM signal driving the motor.
+    /// Raise it to 20 kHz or more to move motor whine above the audible range.
+    /// * `hz` - Frequency in Hz
+    pub fn set_frequency(&mut self, hz: f64) {
+        self.devices.0.set_frequency(hz);
+        self.devices.1.set_frequency(hz);
+        if let Some(enable) = &mut self.enable {
+            enable.set_frequency(hz);
+        }
+    }
+
+    /// Returns ``True`` if the pins are reset when the motor is dropped.
+    pub fn reset_on_drop(&self) -> bool {
+        self.devices.0.reset_on_drop()
+    }
+
+    /// When enabled (the default), dropping the motor stops it and returns both pins
+    /// to the mode they had before the device was created.
+    pub fn set_reset_on_drop(&mut self, reset_on_drop: bool) {
+        self.devices.0.set_reset_on_drop(reset_on_drop);
+        self.devices.1.set_reset_on_drop(reset_on_drop);
+        if let Some(enable) = &mut self.enable {
+            enable.set_reset_on_drop(reset_on_drop);
+        }
+    }
 }
 
 /// Represents a PWM-controlled servo motor connected to a GPIO pin.
 //reference :https://github.com/golemparts/rppal/blob/master/examples/gpio_servo_softpwm.rs
 pub struct Servo {
-    pin: IoPin,
+    pin: OutputLine,
     min_pulse_width: u64,
     max_pulse_width: u64,
     frame_width: u64,
@@ -683,7 +2428,7 @@ impl Servo {
             Ok(gpio) => match gpio.get(pin) {
                 Err(e) => panic!("{:?}", e),
                 Ok(pin) => Servo {
-                    pin: pin.into_io(Mode::Output),
+                    pin: OutputLine::Gpio(pin.into_io(Mode::Output)),
                     min_pulse_width: 1000,
                     max_pulse_width: 2000,
                     frame_width: 20,
@@ -692,6 +2437,24 @@ impl Servo {
         }
     }
 
+    /// Returns a Servo on a channel of a PWM driver such as a `PCA9685`, with the same
+    /// defaults as `new`. The pulse widths are kept only while the frequency of the driver
+    /// matches the frame width, e.g. 50Hz for the default of 20ms.
+    ///
+    /// * `factory` - The provider of the pin, e.g. a `PCA9685`
+    /// * `pin` - The number of the channel on the factory
+    pub fn with_factory<F: PinFactory>(factory: &F, pin: u8) -> Servo {
+        match factory.output_pin(pin, Level::Low) {
+            Err(e) => panic!("{:?}", e),
+            Ok(pin) => Servo {
+                pin: OutputLine::Driver(pin),
+                min_pulse_width: 1000,
+                max_pulse_width: 2000,
+                frame_width: 20,
+            },
+        }
+    }
+
     /// Set the servo to its minimum position.
     pub fn min(&mut self) {
         if self
@@ -735,6 +2498,27 @@ impl Servo {
         }
     }
 
+    /// Set the position of the servo between -1.0 (minimum) and 1.0 (maximum).
+    /// * `value` - The position, 0.0 being the neutral position
+    pub fn set_value(&mut self, value: f64) {
+        if !(-1.0..=1.0).contains(&value) {
+            println!("Value must be between -1.0 and 1.0");
+            return;
+        }
+        let span = (self.max_pulse_width - self.min_pulse_width) as f64;
+        let pulse_width = self.min_pulse_width as f64 + (value + 1.0) / 2.0 * span;
+        if self
+            .pin
+            .set_pwm(
+                Duration::from_millis(self.frame_width),
+                Duration::from_micros(pulse_width.round() as u64),
+            )
+            .is_err()
+        {
+            println!("Failed to set servo position")
+        }
+    }
+
     /// Set the servo's minimum pulse width
     pub fn set_min_pulse_width(&mut self, value: u64) {
         if value >= self.max_pulse_width {
@@ -772,4 +2556,274 @@ impl Servo {
     pub fn get_frame_width(&mut self) -> u64 {
         self.frame_width
     }
+
+    /// Returns ``True`` if the pin is reset when the servo is dropped.
+    pub fn reset_on_drop(&self) -> bool {
+        self.pin.reset_on_drop()
+    }
+
+    /// When enabled (the default), dropping the servo stops the control pulses and returns
+    /// the pin to the mode it had before the device was created.
+    pub fn set_reset_on_drop(&mut self, reset_on_drop: bool) {
+        self.pin.set_reset_on_drop(reset_on_drop)
+    }
+}
+
+impl Drop for Servo {
+    fn drop(&mut self) {
+        if self.pin.reset_on_drop() {
+            if self.pin.clear_pwm().is_err() {
+                println!("Could not clear pwm for pin");
+            }
+            self.pin.set_low();
+        }
+    }
+}
+
+macro_rules! impl_drop_background {
+    ($($device:ty),*) => {
+        $(
+            impl Drop for $device {
+                /// Stops and joins the background thread, so that it cannot switch the pin
+                /// after it is released. The pin itself is reset when the underlying
+                /// `OutputDevice` is dropped.
+                fn drop(&mut self) {
+                    self.blinking.store(false, Ordering::SeqCst);
+                    if let Some(handle) = self.handle.take() {
+                        handle.thread().unpark();
+                        let _ = handle.join();
+                    }
+                    if let Some(pin) = self.device.lock().ok().and_then(|device| device.pin.phase()) {
+                        release_phase(pin);
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_drop_background!(DigitalOutputDevice, LED, Buzzer, PWMOutputDevice);
+
+/// Output devices which can play an on/off pattern in the background.
+pub trait PlayPattern {
+    /// Play a sequence of on/off states in the background.
+    /// * `pattern` - Pairs of state (``True`` for on) and how long to hold that state
+    /// * `repeat` - Number of times to play the pattern. If this is None, repeat indefinitely.
+    fn play_pattern(&mut self, pattern: &[(bool, Duration)], repeat: Option<u32>);
+}
+
+macro_rules! impl_play_pattern {
+    ($($device:ty),*) => {
+        $(
+            impl PlayPattern for $device {
+                fn play_pattern(&mut self, pattern: &[(bool, Duration)], repeat: Option<u32>) {
+                    <$device>::play_pattern(self, pattern, repeat)
+                }
+            }
+        )*
+    };
+}
+
+impl_play_pattern!(DigitalOutputDevice, LED, Buzzer);
+
+macro_rules! impl_digital_value_sink {
+    ($($device:ty),*) => {
+        $(
+            impl ValueSink for $device {
+                fn write_value(&mut self, value: f64) {
+                    if value != 0.0 {
+                        self.on()
+                    } else {
+                        self.off()
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_digital_value_sink!(DigitalOutputDevice, LED, Buzzer);
+
+impl ValueSink for PWMOutputDevice {
+    fn write_value(&mut self, value: f64) {
+        self.set_value(value.clamp(0.0, 1.0))
+    }
+}
+
+impl ValueSink for PWMLED {
+    fn write_value(&mut self, value: f64) {
+        self.set_value(value.clamp(0.0, 1.0))
+    }
+}
+
+impl ValueSink for Motor {
+    fn write_value(&mut self, value: f64) {
+        let value = value.clamp(-1.0, 1.0);
+        if value == 0.0 {
+            self.stop();
+            return;
+        }
+        self.set_speed(value.abs());
+        if value > 0.0 {
+            self.forward()
+        } else {
+            self.backward()
+        }
+    }
+}
+
+impl ValueSink for Servo {
+    fn write_value(&mut self, value: f64) {
+        self.set_value(value.clamp(-1.0, 1.0))
+    }
+}
+
+/// The time from the start of one NEC frame or repeat code to the start of the next.
+pub(crate) const NEC_PERIOD: Duration = Duration::from_millis(108);
+
+/// The bursts (``True``) and spaces of the code sent while an NEC key is held, in
+/// microseconds.
+pub(crate) const NEC_REPEAT: [(bool, u64); 3] = [(true, 9000), (false, 2250), (true, 560)];
+
+/// The bursts (``True``) and spaces of an NEC frame in microseconds. Addresses up to 0xFF
+/// are sent followed by their inverse, larger ones as extended 16 bit addresses.
+pub(crate) fn nec_frame(address: u16, command: u8) -> Vec<(bool, u64)> {
+    let [address_low, address_high] = if address <= 0xFF {
+        [address as u8, !(address as u8)]
+    } else {
+        address.to_le_bytes()
+    };
+    let bits = u32::from_le_bytes([address_low, address_high, command, !command]);
+    let mut frame = vec![(true, 9000), (false, 4500)];
+    for i in 0..32 {
+        let space = if bits >> i & 1 == 1 { 1690 } else { 560 };
+        frame.push((true, 560));
+        frame.push((false, space));
+    }
+    frame.push((true, 560));
+    frame
+}
+
+/// Sends infrared remote control codes in the NEC protocol through an IR LED, e.g. to
+/// replay codes learnt with an `IRReceiver`.
+///
+/// The 38 kHz carrier comes from a hardware PWM channel, which is switched on for each
+/// burst and off for each space. Enable the channel with `dtoverlay=pwm` (GPIO 18) or
+/// `dtoverlay=pwm,pin=12,func=4` in `/boot/config.txt`, and drive the LED through a
+/// transistor, as a GPIO pin cannot supply the current it needs.
+///
+/// The timing of the bursts is kept by spinning the sending thread, so `send` blocks for
+/// the length of the frame, about 70 milliseconds.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut transmitter = IRTransmitter::new(18);
+/// // power button of a remote with address 0x04
+/// transmitter.send(0x04, 0x08).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct IRTransmitter {
+    pwm: Pwm,
+    pin: u8,
+    duty_cycle: f64,
+}
+
+impl IRTransmitter {
+    /// Returns an IRTransmitter with a 38 kHz carrier at a duty cycle of 1/3
+    /// * `pin` - The GPIO pin which the IR LED is attached to: 12 or 18 for the first PWM
+    ///   channel, 13 or 19 for the second
+    pub fn new(pin: u8) -> IRTransmitter {
+        let channel = match pin {
+            12 | 18 => Channel::Pwm0,
+            13 | 19 => Channel::Pwm1,
+            _ => panic!("GPIO {} has no hardware PWM channel", pin),
+        };
+        match Pwm::with_frequency(channel, 38_000.0, 0.0, Polarity::Normal, true) {
+            Err(e) => panic!("{:?}", e),
+            Ok(pwm) => IRTransmitter {
+                pwm,
+                pin,
+                duty_cycle: 1.0 / 3.0,
+            },
+        }
+    }
+
+    /// The GPIO pin which the IR LED is attached to.
+    pub fn pin(&self) -> u8 {
+        self.pin
+    }
+
+    /// Set the frequency of the carrier. Most receivers expect 38 kHz, some 36 or 40 kHz.
+    /// * `hz` - Frequency in Hz
+    ///
+    /// Returns an I/O error of kind `InvalidInput` if `hz` is not a positive, finite number.
+    pub fn set_carrier(&mut self, hz: f64) -> Result<(), rppal::pwm::Error> {
+        if !(hz > 0.0 && hz.is_finite()) {
+            return Err(rppal::pwm::Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("the carrier frequency must be greater than 0, got {}", hz),
+            )));
+        }
+        self.pwm.set_frequency(hz, 0.0)
+    }
+
+    /// Send an NEC frame. Addresses up to 0xFF are sent followed by their inverse, larger
+    /// ones as extended 16 bit addresses.
+    /// * `address` - The address of the device
+    /// * `command` - The command, e.g. the key pressed
+    pub fn send(&mut self, address: u16, command: u8) -> Result<(), rppal::pwm::Error> {
+        self.send_held(address, command, 0)
+    }
+
+    /// Send an NEC frame followed by repeat codes, as a remote does while a key is held.
+    /// * `address` - The address of the device
+    /// * `command` - The command, e.g. the key pressed
+    /// * `repeats` - The number of repeat codes sent after the frame, 108 milliseconds apart
+    pub fn send_held(
+        &mut self,
+        address: u16,
+        command: u8,
+        repeats: u32,
+    ) -> Result<(), rppal::pwm::Error> {
+        let mut start = Instant::now();
+        self.transmit(start, &nec_frame(address, command))?;
+        for _ in 0..repeats {
+            start += NEC_PERIOD;
+            self.transmit(start, &NEC_REPEAT)?;
+        }
+        Ok(())
+    }
+
+    /// Play bursts (``True``) and spaces of the lengths given in microseconds, starting at
+    /// `start`. Each edge is timed from the start so delays do not add up.
+    fn transmit(&self, start: Instant, timings: &[(bool, u64)]) -> Result<(), rppal::pwm::Error> {
+        let mut at = start;
+        for &(burst, micros) in timings {
+            spin_until(at);
+            let duty_cycle = if burst { self.duty_cycle } else { 0.0 };
+            if let Err(e) = self.pwm.set_duty_cycle(duty_cycle) {
+                let _ = self.pwm.set_duty_cycle(0.0);
+                return Err(e);
+            }
+            at += Duration::from_micros(micros);
+        }
+        spin_until(at);
+        self.pwm.set_duty_cycle(0.0)
+    }
+}
+
+/// Wait until `deadline`, sleeping while it is more than a millisecond away and spinning
+/// for the rest.
+fn spin_until(deadline: Instant) {
+    let margin = Duration::from_millis(1);
+    let now = Instant::now();
+    if deadline > now + margin {
+        thread::sleep(deadline - now - margin);
+    }
+    while Instant::now() < deadline {
+        std::hint::spin_loop();
+    }
 }
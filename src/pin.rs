@@ -0,0 +1,265 @@
+//! Typestate interface to single GPIO pins
+//!
+//! The devices in this crate configure their pins themselves and check how they are used at
+//! run time. `Pin` instead carries its function in its type: a `Pin<Output>` can be set high
+//! but not read as an input, a `Pin<Input<PullUp>>` can be read but not driven, and switching
+//! function consumes the pin and returns it in its new type. Misuse is a compile error:
+//!
+//! ```compile_fail
+//! use rust_gpiozero::pin::Pin;
+//!
+//! let input = Pin::new(4).into_input_pullup();
+//! input.set_high(); // no such method on an input
+//! ```
+//!
+//! A configured pin can be handed to the matching device, e.g. `OutputDevice::from_pin`,
+//! to get the dynamic API on top of it.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::pin::Pin;
+//!
+//! let mut led = Pin::new(17).into_output();
+//! led.set_high();
+//!
+//! let button = Pin::new(2).into_input_pullup();
+//! if button.is_low() {
+//!     println!("pressed");
+//! }
+//!
+//! // the same pin, now dimming the LED
+//! let mut led = led.into_pwm(100.0);
+//! led.set_duty_cycle(0.25).unwrap();
+//! ```
+use rppal::gpio::{Error, Gpio, InputPin, IoPin, Level, Mode, PullUpDown};
+use std::marker::PhantomData;
+
+/// A pin whose function has not been chosen yet. It keeps the mode it had before.
+pub struct Unconfigured;
+
+/// A pin configured as an input with the pull resistor `P`.
+pub struct Input<P>(PhantomData<P>);
+
+/// A pin configured as a digital output.
+pub struct Output;
+
+/// A pin configured as an output driven by software PWM.
+pub struct Pwm;
+
+/// Input bias with the internal pull-up resistor.
+pub struct PullUp;
+
+/// Input bias with the internal pull-down resistor.
+pub struct PullDown;
+
+/// Input without an internal pull resistor, for pins with an external one.
+pub struct Floating;
+
+/// The bias of an input pin: `PullUp`, `PullDown` or `Floating`.
+pub trait Pull {
+    /// The setting of the internal resistor.
+    const PULL_UP_DOWN: PullUpDown;
+}
+
+impl Pull for PullUp {
+    const PULL_UP_DOWN: PullUpDown = PullUpDown::PullUp;
+}
+
+impl Pull for PullDown {
+    const PULL_UP_DOWN: PullUpDown = PullUpDown::PullDown;
+}
+
+impl Pull for Floating {
+    const PULL_UP_DOWN: PullUpDown = PullUpDown::Off;
+}
+
+/// A GPIO pin whose function is part of its type.
+pub struct Pin<MODE> {
+    io: IoPin,
+    frequency: f64,
+    duty_cycle: f64,
+    mode: PhantomData<MODE>,
+}
+
+impl Pin<Unconfigured> {
+    /// Returns the Pin with the number given, without changing its mode
+    /// * `pin` - The GPIO pin number
+    pub fn new(pin: u8) -> Pin<Unconfigured> {
+        match Gpio::new() {
+            Err(e) => panic!("{:?}", e),
+            Ok(gpio) => match gpio.get(pin) {
+                Err(e) => panic!("{:?}", e),
+                Ok(pin) => {
+                    let mode = pin.mode();
+                    from_io(pin.into_io(mode))
+                }
+            },
+        }
+    }
+}
+
+fn from_io<M>(io: IoPin) -> Pin<M> {
+    Pin {
+        io,
+        frequency: 0.0,
+        duty_cycle: 0.0,
+        mode: PhantomData,
+    }
+}
+
+impl<MODE> Pin<MODE> {
+    /// Stop any PWM and hand the pin over to its new function. An output level is written
+    /// before the mode changes, so the pin never drives the level it had before.
+    fn reconfigure<M>(mut self, mode: Mode, pull: PullUpDown, level: Option<Level>) -> Pin<M> {
+        if self.io.clear_pwm().is_err() {
+            println!("Could not clear pwm for pin");
+        }
+        if let Some(level) = level {
+            self.io.write(level);
+        }
+        self.io.set_mode(mode);
+        self.io.set_pullupdown(pull);
+        from_io(self.io)
+    }
+
+    /// The GPIO pin number.
+    pub fn number(&self) -> u8 {
+        self.io.pin()
+    }
+
+    /// Make the pin an input with the internal resistor chosen by the type, e.g.
+    /// `pin.into_input::<PullUp>()`.
+    pub fn into_input<P: Pull>(self) -> Pin<Input<P>> {
+        self.reconfigure(Mode::Input, P::PULL_UP_DOWN, None)
+    }
+
+    /// Make the pin an input pulled high by the internal resistor.
+    pub fn into_input_pullup(self) -> Pin<Input<PullUp>> {
+        self.into_input()
+    }
+
+    /// Make the pin an input pulled low by the internal resistor.
+    pub fn into_input_pulldown(self) -> Pin<Input<PullDown>> {
+        self.into_input()
+    }
+
+    /// Make the pin an input without an internal resistor.
+    pub fn into_input_floating(self) -> Pin<Input<Floating>> {
+        self.into_input()
+    }
+
+    /// Make the pin an output, starting low.
+    pub fn into_output(self) -> Pin<Output> {
+        self.reconfigure(Mode::Output, PullUpDown::Off, Some(Level::Low))
+    }
+
+    /// Make the pin an output, starting high.
+    pub fn into_output_high(self) -> Pin<Output> {
+        self.reconfigure(Mode::Output, PullUpDown::Off, Some(Level::High))
+    }
+
+    /// Make the pin a software PWM output, starting at a duty cycle of 0.0.
+    /// * `frequency` - Frequency of the PWM signal in Hz
+    pub fn into_pwm(self, frequency: f64) -> Pin<Pwm> {
+        let mut pin: Pin<Pwm> = self.reconfigure(Mode::Output, PullUpDown::Off, Some(Level::Low));
+        pin.frequency = frequency;
+        pin
+    }
+
+    /// When enabled (the default), dropping the pin returns it to the mode it had before
+    /// it was taken and disables the pull resistor.
+    pub fn set_reset_on_drop(&mut self, reset_on_drop: bool) {
+        self.io.set_reset_on_drop(reset_on_drop)
+    }
+
+    pub(crate) fn into_io(self) -> IoPin {
+        self.io
+    }
+}
+
+impl<P: Pull> Pin<Input<P>> {
+    /// The level of the pin.
+    pub fn read(&self) -> Level {
+        self.io.read()
+    }
+
+    /// Returns ``True`` if the pin is high.
+    pub fn is_high(&self) -> bool {
+        self.io.is_high()
+    }
+
+    /// Returns ``True`` if the pin is low.
+    pub fn is_low(&self) -> bool {
+        self.io.is_low()
+    }
+
+    /// The pin as an rppal InputPin with the same bias, which supports interrupts.
+    pub(crate) fn into_input_pin(mut self) -> InputPin {
+        let number = self.io.pin();
+        self.io.set_reset_on_drop(false);
+        drop(self.io);
+        match Gpio::new().and_then(|gpio| gpio.get(number)) {
+            Err(e) => panic!("{:?}", e),
+            Ok(pin) => match P::PULL_UP_DOWN {
+                PullUpDown::PullUp => pin.into_input_pullup(),
+                PullUpDown::PullDown => pin.into_input_pulldown(),
+                PullUpDown::Off => pin.into_input(),
+            },
+        }
+    }
+}
+
+impl Pin<Output> {
+    /// Drive the pin to the level.
+    pub fn write(&mut self, level: Level) {
+        self.io.write(level)
+    }
+
+    /// Drive the pin high.
+    pub fn set_high(&mut self) {
+        self.io.set_high()
+    }
+
+    /// Drive the pin low.
+    pub fn set_low(&mut self) {
+        self.io.set_low()
+    }
+
+    /// Reverse the level of the pin.
+    pub fn toggle(&mut self) {
+        self.io.toggle()
+    }
+
+    /// Returns ``True`` if the pin is driven high.
+    pub fn is_set_high(&self) -> bool {
+        self.io.is_high()
+    }
+}
+
+impl Pin<Pwm> {
+    /// The duty cycle between 0.0 (always low) and 1.0 (always high).
+    pub fn duty_cycle(&self) -> f64 {
+        self.duty_cycle
+    }
+
+    /// Set the duty cycle between 0.0 (always low) and 1.0 (always high).
+    pub fn set_duty_cycle(&mut self, duty_cycle: f64) -> Result<(), Error> {
+        let duty_cycle = duty_cycle.clamp(0.0, 1.0);
+        self.io.set_pwm_frequency(self.frequency, duty_cycle)?;
+        self.duty_cycle = duty_cycle;
+        Ok(())
+    }
+
+    /// The frequency of the PWM signal in Hz.
+    pub fn frequency(&self) -> f64 {
+        self.frequency
+    }
+
+    /// Set the frequency of the PWM signal in Hz, keeping the duty cycle.
+    pub fn set_frequency(&mut self, frequency: f64) -> Result<(), Error> {
+        self.io.set_pwm_frequency(frequency, self.duty_cycle)?;
+        self.frequency = frequency;
+        Ok(())
+    }
+}
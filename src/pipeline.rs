@@ -0,0 +1,768 @@
+//! Sample-and-publish pipelines for sensors
+//!
+//! Most sensors end up with the same threads around them: one sampling at a fixed rate, a
+//! median or moving average to take out the noise, a summary every minute, and code sending
+//! the result to a log file, an MQTT broker or a metrics collector, with an alert when the
+//! value goes out of range. A `Pipeline` is declared once with these stages and runs them in
+//! a single background thread:
+//!
+//! sample every `every` → `filter` chain → `when_above`/`when_below` alerts → `aggregate`
+//! into a `Summary` per window → every sink
+//!
+//! Pipelines can also be declared in a configuration file, as sections of kind `pipeline`
+//! reading a device of a `DeviceTree`, and started with `Pipeline::start_configured`:
+//!
+//! ```text
+//! [soil_log]
+//! kind = pipeline
+//! device = soil                  # the device of the tree read
+//! every = 10                     # seconds between readings
+//! filters = reject 0 1, median 5
+//! aggregate = 300                # seconds per summary
+//! log = /var/log/soil.jsonl
+//! mqtt = localhost:1883 greenhouse/soil
+//! metrics = /var/lib/node_exporter/soil.prom
+//! below = 0.2                    # alerts are written to the event log of the tree
+//! hysteresis = 0.05
+//! alert_url = https://ntfy.sh/greenhouse
+//! queue = 100                    # samples each sink may fall behind by
+//! overflow = drop-oldest         # or drop-newest, or block and a number of seconds
+//! ```
+//!
+//! The other keys publishing samples are `http` with a URL and `sqlite` with a database
+//! file and a table. Every sink gets its own `SinkQueue`, so a dead network link loses
+//! samples according to the `Overflow` policy instead of stalling the readings; see the
+//! `sink` module.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::*;
+//! use std::time::Duration;
+//!
+//! let adc = MCP3008::new();
+//! let _soil = Pipeline::analog("soil", adc.channel(0))
+//!     .every(Duration::from_secs(10))
+//!     .filter(Filter::Median(5))
+//!     .when_below(0.2, |value| println!("soil is dry: {:.2}", value))
+//!     .aggregate(Duration::from_secs(300))
+//!     .log("/var/log/soil.jsonl")
+//!     .mqtt("localhost", "greenhouse/soil")
+//!     .backpressure(20, Overflow::DropOldest)
+//!     .start();
+//! ```
+use crate::config::DeviceConfig;
+use crate::devices::{AnalogInputDevice, Thresholds};
+use crate::json::{Json, ToJson};
+use crate::output_devices::sleep_while_running;
+use crate::sink::{
+    HttpSink, LogSink, MetricsSink, MqttSink, Overflow, Sink, SinkQueue, SinkStats, SqliteSink,
+};
+use crate::tools::Summary;
+use crate::tree::DeviceTree;
+use crate::webhook::Webhook;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How often a pipeline samples unless `every` is called.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How many samples each sink may fall behind by unless `backpressure` is called.
+const DEFAULT_CAPACITY: usize = 100;
+
+/// A value published by a `Pipeline` to its sinks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    /// The name of the pipeline
+    pub name: String,
+    /// When the reading was taken, or when the window of an aggregate ended
+    pub time: SystemTime,
+    /// The filtered reading, or the mean of the window
+    pub value: f64,
+    /// The statistics of the window, for pipelines that aggregate
+    pub summary: Option<Summary>,
+}
+
+impl Sample {
+    /// The sample as a JSON object with the fields `name`, `time` (seconds since the Unix
+    /// epoch) and `value`, and for aggregates `count`, `min`, `max` and `stddev`.
+    pub fn to_json(&self) -> Json {
+        let time = self
+            .time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let mut members = vec![
+            ("name", Json::String(self.name.clone())),
+            ("time", Json::Number((time * 1000.0).round() / 1000.0)),
+            ("value", Json::Number(self.value)),
+        ];
+        if let Some(summary) = self.summary {
+            members.push(("count", Json::Number(summary.count as f64)));
+            members.push(("min", Json::Number(summary.min)));
+            members.push(("max", Json::Number(summary.max)));
+            members.push(("stddev", Json::Number(summary.stddev)));
+        }
+        Json::object(members)
+    }
+}
+
+impl ToJson for Sample {
+    fn to_json(&self) -> Json {
+        Sample::to_json(self)
+    }
+}
+
+/// A stage of the filter chain of a `Pipeline`. Filters that drop a reading end the chain
+/// for it, so it is neither checked against the alerts nor published.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    /// Multiply by `gain` and add `offset`, e.g. to turn a voltage into °C
+    Scale { gain: f64, offset: f64 },
+    /// Limit readings to a range
+    Clamp { min: f64, max: f64 },
+    /// Drop readings outside a range, such as the impossible values of a glitching sensor
+    Reject { min: f64, max: f64 },
+    /// The median of the last readings given, taking out spikes
+    Median(usize),
+    /// The mean of the last readings given
+    Mean(usize),
+    /// An exponentially weighted moving average with the smoothing factor given, between
+    /// 0.0 and 1.0 (higher reacts faster)
+    Ewma(f64),
+    /// Drop readings within the distance given of the last reading passed on, so only
+    /// changes are published
+    Deadband(f64),
+}
+
+impl Filter {
+    /// Parse a filter written as its name and arguments, as in a configuration file:
+    /// `scale 1.8 32`, `clamp 0 100`, `reject 0 100`, `median 5`, `mean 5`, `ewma 0.2` or
+    /// `deadband 0.5`.
+    pub fn parse(text: &str) -> Result<Filter, String> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let number = |i: usize| -> Result<f64, String> {
+            match words.get(i).map(|word| word.parse::<f64>()) {
+                Some(Ok(number)) if number.is_finite() => Ok(number),
+                _ => Err(format!("'{}' needs a number as argument {}", text, i)),
+            }
+        };
+        let count = |i: usize| -> Result<usize, String> {
+            match words.get(i).map(|word| word.parse::<usize>()) {
+                Some(Ok(count)) if count > 0 => Ok(count),
+                _ => Err(format!("'{}' needs a count of readings", text)),
+            }
+        };
+        let arguments = match words.first() {
+            Some(&"scale") | Some(&"clamp") | Some(&"reject") => 2,
+            _ => 1,
+        };
+        if words.len() != arguments + 1 {
+            return Err(format!("'{}' should have {} argument(s)", text, arguments));
+        }
+        match words[0] {
+            "scale" => Ok(Filter::Scale {
+                gain: number(1)?,
+                offset: number(2)?,
+            }),
+            "clamp" => Ok(Filter::Clamp {
+                min: number(1)?,
+                max: number(2)?,
+            }),
+            "reject" => Ok(Filter::Reject {
+                min: number(1)?,
+                max: number(2)?,
+            }),
+            "median" => Ok(Filter::Median(count(1)?)),
+            "mean" => Ok(Filter::Mean(count(1)?)),
+            "ewma" => match number(1)? {
+                alpha if alpha > 0.0 && alpha <= 1.0 => Ok(Filter::Ewma(alpha)),
+                _ => Err(format!(
+                    "'{}' needs a smoothing factor above 0 up to 1",
+                    text
+                )),
+            },
+            "deadband" => Ok(Filter::Deadband(number(1)?.abs())),
+            other => Err(format!("unknown filter '{}'", other)),
+        }
+    }
+}
+
+type MapFn = Box<dyn FnMut(f64) -> f64 + Send>;
+
+/// A filter of the chain and the readings it remembers.
+enum Stage {
+    Filter {
+        filter: Filter,
+        history: VecDeque<f64>,
+        last: Option<f64>,
+    },
+    Map(MapFn),
+}
+
+impl Stage {
+    fn apply(&mut self, value: f64) -> Option<f64> {
+        let (filter, history, last) = match self {
+            Stage::Filter {
+                filter,
+                history,
+                last,
+            } => (*filter, history, last),
+            Stage::Map(map) => return Some(map(value)),
+        };
+        let mut remember = |value: f64, len: usize| {
+            history.push_back(value);
+            while history.len() > len {
+                history.pop_front();
+            }
+            history.iter().cloned().collect::<Vec<f64>>()
+        };
+        let result = match filter {
+            Filter::Scale { gain, offset } => value * gain + offset,
+            Filter::Clamp { min, max } => value.max(min).min(max),
+            Filter::Reject { min, max } if value < min || value > max => return None,
+            Filter::Reject { .. } => value,
+            Filter::Median(len) => {
+                let mut readings = remember(value, len);
+                readings.sort_by(f64::total_cmp);
+                let n = readings.len();
+                if n.is_multiple_of(2) {
+                    (readings[n / 2 - 1] + readings[n / 2]) / 2.0
+                } else {
+                    readings[n / 2]
+                }
+            }
+            Filter::Mean(len) => {
+                let readings = remember(value, len);
+                readings.iter().sum::<f64>() / readings.len() as f64
+            }
+            Filter::Ewma(alpha) => match *last {
+                Some(last) => last + alpha * (value - last),
+                None => value,
+            },
+            Filter::Deadband(delta) => match *last {
+                Some(last) if (value - last).abs() <= delta => return None,
+                _ => value,
+            },
+        };
+        *last = Some(result);
+        Some(result)
+    }
+}
+
+type ReadFn = Box<dyn FnMut() -> f64 + Send>;
+
+/// The counters of a running `Pipeline`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PipelineStats {
+    /// The number of readings taken
+    pub readings: u64,
+    /// The number of readings that failed, were dropped by a filter or were made NaN or
+    /// infinite by a stage
+    pub dropped: u64,
+    /// The number of samples handed to the sinks
+    pub published: u64,
+    /// The number of times a sink failed
+    pub errors: u64,
+    /// The number of samples a sink lost because it fell too far behind
+    pub overflowed: u64,
+}
+
+#[derive(Default)]
+struct PipelineState {
+    last: Option<Sample>,
+    stats: PipelineStats,
+}
+
+/// Declares the stages of a `Pipeline`, which starts running when `start` is called.
+pub struct PipelineBuilder {
+    name: String,
+    read: ReadFn,
+    interval: Duration,
+    stages: Vec<Stage>,
+    thresholds: Thresholds,
+    window: Option<Duration>,
+    sinks: Vec<Box<dyn Sink<Sample>>>,
+    capacity: usize,
+    overflow: Overflow,
+}
+
+impl PipelineBuilder {
+    /// Set the time between readings, 1 second by default.
+    pub fn every(mut self, interval: Duration) -> PipelineBuilder {
+        if interval.is_zero() {
+            println!("Interval must be greater than 0");
+            return self;
+        }
+        self.interval = interval;
+        self
+    }
+
+    /// Add a filter to the end of the chain.
+    pub fn filter(mut self, filter: Filter) -> PipelineBuilder {
+        self.stages.push(Stage::Filter {
+            filter,
+            history: VecDeque::new(),
+            last: None,
+        });
+        self
+    }
+
+    /// Add a function to the end of the chain, e.g. a calibration curve.
+    pub fn map<F>(mut self, map: F) -> PipelineBuilder
+    where
+        F: FnMut(f64) -> f64 + Send + 'static,
+    {
+        self.stages.push(Stage::Map(Box::new(map)));
+        self
+    }
+
+    /// Set the function called with the reading when the filtered readings rise above the
+    /// threshold. Alerts are checked on every reading, not only on the aggregates.
+    pub fn when_above<C>(mut self, threshold: f64, callback: C) -> PipelineBuilder
+    where
+        C: FnMut(f64) + Send + 'static,
+    {
+        self.thresholds.set(true, threshold, callback);
+        self
+    }
+
+    /// Set the function called with the reading when the filtered readings fall below the
+    /// threshold.
+    pub fn when_below<C>(mut self, threshold: f64, callback: C) -> PipelineBuilder
+    where
+        C: FnMut(f64) + Send + 'static,
+    {
+        self.thresholds.set(false, threshold, callback);
+        self
+    }
+
+    /// Set how far readings must come back past a threshold before its alert can be raised
+    /// again, in the units of the readings. 0.0 by default.
+    pub fn set_hysteresis(mut self, hysteresis: f64) -> PipelineBuilder {
+        self.thresholds.set_hysteresis(hysteresis);
+        self
+    }
+
+    /// Publish one sample per window instead of every reading, with the mean of the
+    /// window as its value and the statistics of the window as its summary.
+    pub fn aggregate(mut self, window: Duration) -> PipelineBuilder {
+        self.window = Some(window);
+        self
+    }
+
+    /// Publish to a function, e.g. one writing to a database.
+    pub fn sink<S>(self, mut sink: S) -> PipelineBuilder
+    where
+        S: FnMut(&Sample) + Send + 'static,
+    {
+        self.publish(move |sample: &Sample| -> Result<(), Box<dyn Error>> {
+            sink(sample);
+            Ok(())
+        })
+    }
+
+    /// Publish to a sink, see the `sink` module.
+    pub fn publish<S>(mut self, sink: S) -> PipelineBuilder
+    where
+        S: Sink<Sample> + 'static,
+    {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    /// Append every sample to a file as a line of JSON.
+    pub fn log<P: AsRef<Path>>(self, path: P) -> PipelineBuilder {
+        self.publish(LogSink::new(path))
+    }
+
+    /// Publish every sample as JSON to an MQTT broker with `mosquitto_pub`.
+    /// * `broker` - The host name of the broker, with `:port` unless it is 1883
+    /// * `topic` - The topic published to
+    pub fn mqtt(self, broker: &str, topic: &str) -> PipelineBuilder {
+        self.publish(MqttSink::new(broker, topic))
+    }
+
+    /// POST every sample as JSON to a URL.
+    pub fn http(self, url: &str) -> PipelineBuilder {
+        self.publish(HttpSink::new(url))
+    }
+
+    /// Insert every sample as a row of an SQLite table with `sqlite3`.
+    /// * `path` - The database file, which is created if needed
+    /// * `table` - The name of the table, see `SqliteSink`
+    pub fn sqlite<P: AsRef<Path>>(self, path: P, table: &str) -> PipelineBuilder {
+        self.publish(SqliteSink::new(path, table))
+    }
+
+    /// Write the last sample to a file in the Prometheus text format, for the textfile
+    /// collector of the node exporter.
+    pub fn metrics<P: AsRef<Path>>(self, path: P) -> PipelineBuilder {
+        self.publish(MetricsSink::new(path))
+    }
+
+    /// Send every sample to a webhook, as the event `sample`.
+    pub fn webhook(self, webhook: Webhook) -> PipelineBuilder {
+        self.sink(move |sample: &Sample| webhook.send(&sample.name, "sample", Some(sample.value)))
+    }
+
+    /// Set how many samples each sink may fall behind by, and what is given up when one
+    /// falls further behind. 100 samples and `Overflow::DropOldest` by default.
+    /// * `capacity` - The number of samples queued for each sink
+    /// * `overflow` - The policy applied when the queue of a sink is full
+    pub fn backpressure(mut self, capacity: usize, overflow: Overflow) -> PipelineBuilder {
+        if capacity == 0 {
+            println!("Capacity must be greater than 0");
+            return self;
+        }
+        self.capacity = capacity;
+        self.overflow = overflow;
+        self
+    }
+
+    /// Start sampling in a background thread.
+    pub fn start(self) -> Pipeline {
+        let PipelineBuilder {
+            name,
+            mut read,
+            interval,
+            mut stages,
+            mut thresholds,
+            window,
+            sinks,
+            capacity,
+            overflow,
+        } = self;
+        let queues: Arc<Vec<SinkQueue<Sample>>> = Arc::new(
+            sinks
+                .into_iter()
+                .map(|sink| SinkQueue::new(sink, capacity, overflow))
+                .collect(),
+        );
+        let state = Arc::new(Mutex::new(PipelineState::default()));
+        let running = Arc::new(AtomicBool::new(true));
+        let handle = {
+            let name = name.clone();
+            let state = Arc::clone(&state);
+            let running = Arc::clone(&running);
+            let queues = Arc::clone(&queues);
+            thread::spawn(move || {
+                let mut next = Instant::now();
+                let mut window_start = Instant::now();
+                let mut readings = Vec::new();
+                while running.load(Ordering::SeqCst) {
+                    let value = read();
+                    let filtered = if value.is_nan() {
+                        None
+                    } else {
+                        // a stage turning a reading into NaN or infinity drops it, so later
+                        // stages only see numbers
+                        stages.iter_mut().try_fold(value, |value, stage| {
+                            stage.apply(value).filter(|value| value.is_finite())
+                        })
+                    };
+                    {
+                        let mut state = state.lock().unwrap();
+                        state.stats.readings += 1;
+                        if filtered.is_none() {
+                            state.stats.dropped += 1;
+                        }
+                    }
+                    if let Some(value) = filtered {
+                        thresholds.check(value);
+                    }
+                    let sample = match (window, filtered) {
+                        (None, Some(value)) => Some(Sample {
+                            name: name.clone(),
+                            time: SystemTime::now(),
+                            value,
+                            summary: None,
+                        }),
+                        (None, None) => None,
+                        (Some(window), filtered) => {
+                            readings.extend(filtered);
+                            if window_start.elapsed() >= window {
+                                window_start = Instant::now();
+                                Summary::of(&readings.split_off(0)).map(|summary| Sample {
+                                    name: name.clone(),
+                                    time: SystemTime::now(),
+                                    value: summary.mean,
+                                    summary: Some(summary),
+                                })
+                            } else {
+                                None
+                            }
+                        }
+                    };
+                    if let Some(sample) = sample {
+                        for queue in queues.iter() {
+                            queue.push(sample.clone());
+                        }
+                        let mut state = state.lock().unwrap();
+                        state.stats.published += 1;
+                        state.last = Some(sample);
+                    }
+                    next += interval;
+                    let now = Instant::now();
+                    if next < now {
+                        // a slow sensor or a blocking sink made us miss readings; do not catch up
+                        next = now;
+                    }
+                    sleep_while_running(&running, next - now);
+                }
+            })
+        };
+        Pipeline {
+            name,
+            state,
+            queues,
+            running,
+            handle: Some(handle),
+        }
+    }
+}
+
+/// A running pipeline, sampling a sensor in a background thread and publishing the
+/// filtered readings. The thread stops when the pipeline is dropped.
+pub struct Pipeline {
+    name: String,
+    state: Arc<Mutex<PipelineState>>,
+    queues: Arc<Vec<SinkQueue<Sample>>>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Pipeline {
+    /// Returns a PipelineBuilder sampling once a second, without filters or sinks
+    /// * `name` - The name published with every sample
+    /// * `read` - Takes a reading; NaN means the reading failed
+    pub fn builder<R>(name: &str, read: R) -> PipelineBuilder
+    where
+        R: FnMut() -> f64 + Send + 'static,
+    {
+        PipelineBuilder {
+            name: name.to_string(),
+            read: Box::new(read),
+            interval: DEFAULT_INTERVAL,
+            stages: Vec::new(),
+            thresholds: Thresholds::default(),
+            window: None,
+            sinks: Vec::new(),
+            capacity: DEFAULT_CAPACITY,
+            overflow: Overflow::default(),
+        }
+    }
+
+    /// Returns a PipelineBuilder reading an analog input, from 0.0 to 1.0.
+    pub fn analog<A>(name: &str, device: A) -> PipelineBuilder
+    where
+        A: AnalogInputDevice + 'static,
+    {
+        Pipeline::builder(name, move || device.value())
+    }
+
+    /// Returns a PipelineBuilder reading a device of a tree by name. Values of ``True`` and
+    /// ``False`` read as 1.0 and 0.0.
+    pub fn from_tree(name: &str, tree: &Arc<Mutex<DeviceTree>>, device: &str) -> PipelineBuilder {
+        let tree = Arc::clone(tree);
+        let device = device.to_string();
+        Pipeline::builder(name, move || {
+            let value = tree.lock().unwrap().value(&device);
+            match value.as_deref() {
+                Ok("true") => 1.0,
+                Ok("false") => 0.0,
+                Ok(value) => value.parse().unwrap_or(f64::NAN),
+                Err(_) => f64::NAN,
+            }
+        })
+    }
+
+    /// Returns a PipelineBuilder declared by a configuration section of kind `pipeline`,
+    /// reading a device of the tree. Alerts are written to the event log of the tree, and
+    /// sent to `alert_url` if it is set.
+    pub fn from_config(
+        config: &DeviceConfig,
+        tree: &Arc<Mutex<DeviceTree>>,
+    ) -> Result<PipelineBuilder, Box<dyn Error>> {
+        let name = config.name.as_str();
+        let device = match config.option("device") {
+            Some(device) => device,
+            None => return Err(format!("{}: no device to read", name).into()),
+        };
+        let seconds = |key: &str| -> Result<Option<Duration>, Box<dyn Error>> {
+            match config.option(key).map(|value| value.parse::<f64>()) {
+                None => Ok(None),
+                Some(Ok(seconds)) if seconds > 0.0 && seconds.is_finite() => {
+                    Ok(Some(Duration::from_secs_f64(seconds)))
+                }
+                Some(_) => Err(format!("{}: {} must be a number of seconds", name, key).into()),
+            }
+        };
+        let number = |key: &str| -> Result<Option<f64>, Box<dyn Error>> {
+            match config.option(key).map(|value| value.parse::<f64>()) {
+                None => Ok(None),
+                Some(Ok(number)) if number.is_finite() => Ok(Some(number)),
+                Some(_) => Err(format!("{}: {} must be a number", name, key).into()),
+            }
+        };
+
+        let mut builder = Pipeline::from_tree(name, tree, device);
+        if let Some(interval) = seconds("every")? {
+            builder = builder.every(interval);
+        }
+        if let Some(filters) = config.option("filters") {
+            for filter in filters.split(',') {
+                let filter = Filter::parse(filter).map_err(|e| format!("{}: {}", name, e))?;
+                builder = builder.filter(filter);
+            }
+        }
+        let alert_url = config.option("alert_url").map(Webhook::new);
+        for (key, above) in [("above", true), ("below", false)] {
+            let threshold = match number(key)? {
+                Some(threshold) => threshold,
+                None => continue,
+            };
+            let tree = Arc::clone(tree);
+            let webhook = alert_url.clone();
+            let (name, event) = (name.to_string(), format!("{} {}", key, threshold));
+            let alert = move |value: f64| {
+                let message = format!("{} at {}", event, value);
+                tree.lock().unwrap().log_event(&name, &message);
+                if let Some(webhook) = &webhook {
+                    webhook.send(&name, &event, Some(value));
+                }
+            };
+            builder = if above {
+                builder.when_above(threshold, alert)
+            } else {
+                builder.when_below(threshold, alert)
+            };
+        }
+        if let Some(hysteresis) = number("hysteresis")? {
+            builder = builder.set_hysteresis(hysteresis);
+        }
+        if let Some(window) = seconds("aggregate")? {
+            builder = builder.aggregate(window);
+        }
+        if let Some(path) = config.option("log") {
+            builder = builder.log(path);
+        }
+        if let Some(mqtt) = config.option("mqtt") {
+            match mqtt.split_whitespace().collect::<Vec<_>>().as_slice() {
+                [broker, topic] => builder = builder.mqtt(broker, topic),
+                _ => return Err(format!("{}: mqtt must be a broker and a topic", name).into()),
+            }
+        }
+        if let Some(path) = config.option("metrics") {
+            builder = builder.metrics(path);
+        }
+        if let Some(url) = config.option("http") {
+            builder = builder.http(url);
+        }
+        if let Some(sqlite) = config.option("sqlite") {
+            match sqlite.split_whitespace().collect::<Vec<_>>().as_slice() {
+                [path, table] => builder = builder.sqlite(path, table),
+                _ => return Err(format!("{}: sqlite must be a file and a table", name).into()),
+            }
+        }
+        let capacity = match config.option("queue").map(|value| value.parse::<usize>()) {
+            None => DEFAULT_CAPACITY,
+            Some(Ok(capacity)) if capacity > 0 => capacity,
+            Some(_) => return Err(format!("{}: queue must be a number of samples", name).into()),
+        };
+        let overflow = match config.option("overflow") {
+            None => Overflow::default(),
+            Some(overflow) => Overflow::parse(overflow).map_err(|e| format!("{}: {}", name, e))?,
+        };
+        Ok(builder.backpressure(capacity, overflow))
+    }
+
+    /// Start every pipeline declared in the configuration of the tree.
+    pub fn start_configured(
+        tree: &Arc<Mutex<DeviceTree>>,
+    ) -> Result<Vec<Pipeline>, Box<dyn Error>> {
+        let configs: Vec<DeviceConfig> = tree
+            .lock()
+            .unwrap()
+            .config()
+            .devices
+            .iter()
+            .filter(|device| device.kind == "pipeline")
+            .cloned()
+            .collect();
+        let builders = configs
+            .iter()
+            .map(|config| Pipeline::from_config(config, tree))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(builders.into_iter().map(PipelineBuilder::start).collect())
+    }
+
+    /// The name published with every sample.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The last sample published, if any.
+    pub fn last(&self) -> Option<Sample> {
+        self.state.lock().unwrap().last.clone()
+    }
+
+    /// The value of the last sample published, or NaN if there is none yet.
+    pub fn value(&self) -> f64 {
+        self.last().map_or(f64::NAN, |sample| sample.value)
+    }
+
+    /// The counters of readings, samples and sink failures.
+    pub fn stats(&self) -> PipelineStats {
+        let mut stats = self.state.lock().unwrap().stats;
+        for sink in self.sink_stats() {
+            stats.errors += sink.failed;
+            stats.overflowed += sink.dropped;
+        }
+        stats
+    }
+
+    /// The counters of each sink, in the order they were added.
+    pub fn sink_stats(&self) -> Vec<SinkStats> {
+        self.queues.iter().map(SinkQueue::stats).collect()
+    }
+
+    /// The message of the last sink failure, if any.
+    pub fn last_error(&self) -> Option<String> {
+        self.queues
+            .iter()
+            .filter_map(SinkQueue::last_error_at)
+            .max_by_key(|(at, _)| *at)
+            .map(|(_, error)| error)
+    }
+
+    /// Wait until every sink has published the samples queued for it, or the timeout is
+    /// reached. Returns ``True`` if every queue is empty.
+    /// * `timeout` - How long to wait. If this is None, then wait indefinitely.
+    pub fn flush(&self, timeout: Option<Duration>) -> bool {
+        let deadline = timeout.and_then(|t| Instant::now().checked_add(t));
+        self.queues.iter().all(|queue| {
+            queue.flush(deadline.map(|deadline| deadline.saturating_duration_since(Instant::now())))
+        })
+    }
+
+    /// Stop sampling and wait for the thread to end.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+impl Drop for Pipeline {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
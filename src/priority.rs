@@ -0,0 +1,427 @@
+//! Command priorities for outputs
+//!
+//! When several parts of a program drive the same output, a `PriorityOutput` decides which
+//! one wins. Each source commands the output at its `Priority` and the highest priority with
+//! a command in force controls it, so an interlock trip at `Safety` always beats a manual
+//! switch at `Manual`, which beats an automation rule at `Automation`. Lower priority commands
+//! are remembered rather than lost: when the holder releases the output, the next one down
+//! takes over, and with none left the output returns to its default.
+//!
+//! An `Override` puts a `PriorityOutput` behind a push button, so a person at the machine can
+//! force an automated output on or off for a while, with an LED showing that the automation
+//! is overruled.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::*;
+//!
+//! let mut heater = PriorityOutput::new(DigitalOutputDevice::new(17));
+//! heater.command(Priority::Automation, true);
+//!
+//! // the over-temperature interlock trips
+//! heater.command(Priority::Safety, false);
+//! assert_eq!(heater.holder(), Some(Priority::Safety));
+//!
+//! // the thermostat is overruled until the interlock is reset
+//! assert!(!heater.command(Priority::Automation, true));
+//! heater.release(Priority::Safety);
+//! assert!(heater.is_active());
+//! ```
+use crate::devices::{Component, OutputComponent};
+use crate::input_devices::Button;
+use crate::output_devices::{sleep_while_running, LED};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Who commands an output, from lowest to highest priority.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Schedules, control loops and other automation rules
+    Automation,
+    /// A person overriding the automation, e.g. from a switch or a dashboard
+    Manual,
+    /// Interlocks and emergency stops
+    Safety,
+}
+
+impl Priority {
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// An output commanded at several priorities, following the highest one in force.
+pub struct PriorityOutput<O: OutputComponent> {
+    output: O,
+    commands: [Option<bool>; 3],
+    default: bool,
+}
+
+impl<O: OutputComponent> PriorityOutput<O> {
+    /// Returns a PriorityOutput turning the output off while no priority commands it
+    /// * `output` - The output, which is turned off
+    pub fn new(mut output: O) -> PriorityOutput<O> {
+        output.off();
+        PriorityOutput {
+            output,
+            commands: [None; 3],
+            default: false,
+        }
+    }
+
+    /// Command the output at a priority, replacing the previous command of that priority.
+    /// Returns ``True`` if the output follows the command now, ``False`` if a higher priority
+    /// holds it; the command then takes effect once the higher ones are released.
+    /// * `priority` - Who gives the command
+    /// * `active` - ``True`` to turn the output on, ``False`` to turn it off
+    pub fn command(&mut self, priority: Priority, active: bool) -> bool {
+        self.commands[priority.index()] = Some(active);
+        self.apply();
+        self.holder() == Some(priority)
+    }
+
+    /// Withdraw the command of a priority, handing the output to the highest priority still
+    /// commanding it, or to the default.
+    pub fn release(&mut self, priority: Priority) {
+        self.commands[priority.index()] = None;
+        self.apply();
+    }
+
+    /// Withdraw the commands of every priority, returning the output to its default.
+    pub fn release_all(&mut self) {
+        self.commands = [None; 3];
+        self.apply();
+    }
+
+    /// The priority controlling the output, or None if it is at its default.
+    pub fn holder(&self) -> Option<Priority> {
+        [Priority::Safety, Priority::Manual, Priority::Automation]
+            .iter()
+            .copied()
+            .find(|priority| self.commands[priority.index()].is_some())
+    }
+
+    /// The command in force at a priority, if any.
+    pub fn command_at(&self, priority: Priority) -> Option<bool> {
+        self.commands[priority.index()]
+    }
+
+    /// The state of the output while no priority commands it.
+    pub fn default_state(&self) -> bool {
+        self.default
+    }
+
+    /// Set the state of the output while no priority commands it, ``False`` by default.
+    pub fn set_default_state(&mut self, active: bool) {
+        self.default = active;
+        self.apply();
+    }
+
+    /// Returns ``True`` if the output is on.
+    pub fn is_active(&self) -> bool {
+        self.output.is_active()
+    }
+
+    /// The output.
+    pub fn output(&self) -> &O {
+        &self.output
+    }
+
+    fn apply(&mut self) {
+        let active = match self.holder() {
+            Some(priority) => self.commands[priority.index()].unwrap(),
+            None => self.default,
+        };
+        if active {
+            self.output.on()
+        } else {
+            self.output.off()
+        }
+    }
+}
+
+impl<O: OutputComponent> Component for PriorityOutput<O> {
+    fn values(&self) -> Vec<f64> {
+        self.output.values()
+    }
+
+    fn is_active(&self) -> bool {
+        self.output.is_active()
+    }
+
+    fn close(&mut self) {
+        self.output.close()
+    }
+}
+
+// as a child of a composite, the output is commanded at Automation priority
+impl<O: OutputComponent> OutputComponent for PriorityOutput<O> {
+    fn on(&mut self) {
+        self.command(Priority::Automation, true);
+    }
+
+    fn off(&mut self) {
+        self.command(Priority::Automation, false);
+    }
+
+    fn toggle(&mut self) {
+        let active = self.is_active();
+        self.command(Priority::Automation, !active);
+    }
+}
+
+/// The state an `Override` puts its output in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverrideMode {
+    /// The automation controls the output
+    Auto,
+    /// The output is forced on
+    On,
+    /// The output is forced off
+    Off,
+}
+
+impl OverrideMode {
+    /// The mode a press of the button moves to.
+    fn next(self) -> OverrideMode {
+        match self {
+            OverrideMode::Auto => OverrideMode::On,
+            OverrideMode::On => OverrideMode::Off,
+            OverrideMode::Off => OverrideMode::Auto,
+        }
+    }
+}
+
+type ModeCallback = Box<dyn FnMut(OverrideMode) + Send>;
+
+struct OverrideState<O: OutputComponent> {
+    output: PriorityOutput<O>,
+    mode: OverrideMode,
+    expires: Option<Instant>,
+    expiry: Option<Duration>,
+    indicator: Option<LED>,
+    when_mode_changed: Option<ModeCallback>,
+}
+
+impl<O: OutputComponent> OverrideState<O> {
+    fn set_mode(&mut self, mode: OverrideMode) {
+        match mode {
+            OverrideMode::Auto => self.output.release(Priority::Manual),
+            OverrideMode::On => {
+                self.output.command(Priority::Manual, true);
+            }
+            OverrideMode::Off => {
+                self.output.command(Priority::Manual, false);
+            }
+        }
+        self.expires = match (mode, self.expiry) {
+            (OverrideMode::Auto, _) | (_, None) => None,
+            (_, Some(expiry)) => Instant::now().checked_add(expiry),
+        };
+        if let Some(indicator) = self.indicator.as_mut() {
+            match mode {
+                OverrideMode::Auto => indicator.off(),
+                OverrideMode::On => indicator.on(),
+                OverrideMode::Off => {
+                    let _ = indicator.blink(0.5, 0.5);
+                }
+            }
+        }
+        if mode != self.mode {
+            self.mode = mode;
+            if let Some(callback) = self.when_mode_changed.as_mut() {
+                callback(mode);
+            }
+        }
+    }
+}
+
+/// A push button that overrides an automated output, such as the heater of a thermostat or
+/// the valve of an irrigation schedule.
+///
+/// Each press moves from `Auto` to forced `On`, to forced `Off` and back to `Auto`. The
+/// automation keeps commanding the output through `set_automatic` and takes over again when
+/// the override ends, either by the button or when it expires, after an hour by default, so
+/// a forgotten override does not leave the output on for days. The optional indicator LED is
+/// lit while the output is forced on and blinks while it is forced off.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+/// use std::time::Duration;
+///
+/// let mut pump = Override::new(DigitalOutputDevice::new(17), 2);
+/// pump.set_indicator(LED::new(27));
+/// pump.set_expiry(Some(Duration::from_secs(15 * 60)));
+/// pump.when_mode_changed(|mode| println!("pump override: {:?}", mode));
+///
+/// // the automation runs as usual; a press of the button overrules it
+/// pump.set_automatic(true);
+/// ```
+pub struct Override<O: OutputComponent + 'static> {
+    state: Arc<Mutex<OverrideState<O>>>,
+    button: Button,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl<O: OutputComponent + 'static> Override<O> {
+    /// Returns an Override in `Auto` mode, with an expiry of one hour and no indicator
+    /// * `output` - The automated output, which is turned off until the automation commands it
+    /// * `button` - The GPIO pin which the button is attached to, wired to ground
+    pub fn new(output: O, button: u8) -> Override<O> {
+        let state = Arc::new(Mutex::new(OverrideState {
+            output: PriorityOutput::new(output),
+            mode: OverrideMode::Auto,
+            expires: None,
+            expiry: Some(Duration::from_secs(3600)),
+            indicator: None,
+            when_mode_changed: None,
+        }));
+        let mut button = Button::new(button);
+        {
+            let state = Arc::clone(&state);
+            button.when_pressed(move || {
+                let mut state = state.lock().unwrap();
+                let mode = state.mode.next();
+                state.set_mode(mode);
+            });
+        }
+        let running = Arc::new(AtomicBool::new(true));
+        let handle = {
+            let state = Arc::clone(&state);
+            let running = Arc::clone(&running);
+            thread::spawn(move || {
+                while running.load(Ordering::SeqCst) {
+                    {
+                        let mut state = state.lock().unwrap();
+                        if state.expires.is_some_and(|at| Instant::now() >= at) {
+                            state.set_mode(OverrideMode::Auto);
+                        }
+                    }
+                    sleep_while_running(&running, Duration::from_millis(100));
+                }
+            })
+        };
+        Override {
+            state,
+            button,
+            running,
+            handle: Some(handle),
+        }
+    }
+
+    /// The command of the automation: the output follows it in `Auto` mode.
+    /// * `active` - ``True`` to turn the output on, ``False`` to turn it off
+    pub fn set_automatic(&mut self, active: bool) {
+        self.state
+            .lock()
+            .unwrap()
+            .output
+            .command(Priority::Automation, active);
+    }
+
+    /// The current mode.
+    pub fn mode(&self) -> OverrideMode {
+        self.state.lock().unwrap().mode
+    }
+
+    /// Change the mode as the button would, e.g. from a dashboard.
+    pub fn set_mode(&mut self, mode: OverrideMode) {
+        self.state.lock().unwrap().set_mode(mode)
+    }
+
+    /// How long a forced mode lasts, None if it lasts until the button is pressed again.
+    pub fn expiry(&self) -> Option<Duration> {
+        self.state.lock().unwrap().expiry
+    }
+
+    /// Set how long a forced mode lasts before the automation takes over again. Applies
+    /// from the next change of mode.
+    /// * `expiry` - The time, or None to keep the override until the button is pressed again
+    pub fn set_expiry(&mut self, expiry: Option<Duration>) {
+        self.state.lock().unwrap().expiry = expiry;
+    }
+
+    /// The time left before a forced mode expires, None in `Auto` mode or without expiry.
+    pub fn remaining(&self) -> Option<Duration> {
+        let expires = self.state.lock().unwrap().expires?;
+        Some(expires.saturating_duration_since(Instant::now()))
+    }
+
+    /// Set the LED showing the override, lit while forced on and blinking while forced off.
+    pub fn set_indicator(&mut self, indicator: LED) {
+        let mut state = self.state.lock().unwrap();
+        state.indicator = Some(indicator);
+        let mode = state.mode;
+        state.set_mode(mode);
+    }
+
+    /// Set the function called when the mode changes, by the button, `set_mode` or expiry.
+    pub fn when_mode_changed<C>(&mut self, callback: C)
+    where
+        C: FnMut(OverrideMode) + Send + 'static,
+    {
+        self.state.lock().unwrap().when_mode_changed = Some(Box::new(callback));
+    }
+
+    /// Returns ``True`` if the output is on.
+    pub fn is_active(&self) -> bool {
+        self.state.lock().unwrap().output.is_active()
+    }
+
+    /// The button of the override.
+    pub fn button(&mut self) -> &mut Button {
+        &mut self.button
+    }
+
+    /// Shut down the device and release all associated resources.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+impl<O: OutputComponent + 'static> Drop for Override<O> {
+    fn drop(&mut self) {
+        self.button.clear_callbacks();
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+impl<O: OutputComponent + 'static> Component for Override<O> {
+    fn values(&self) -> Vec<f64> {
+        self.state.lock().unwrap().output.values()
+    }
+
+    fn is_active(&self) -> bool {
+        Override::is_active(self)
+    }
+
+    fn close(&mut self) {
+        self.state.lock().unwrap().output.close()
+    }
+}
+
+// as a child of a composite, the automation switches the output
+impl<O: OutputComponent + 'static> OutputComponent for Override<O> {
+    fn on(&mut self) {
+        self.set_automatic(true)
+    }
+
+    fn off(&mut self) {
+        self.set_automatic(false)
+    }
+
+    fn toggle(&mut self) {
+        let active = self.is_active();
+        self.set_automatic(!active)
+    }
+}
@@ -0,0 +1,145 @@
+//! Interactive console for a running `DeviceTree`
+//!
+//! The REPL listens on a local Unix socket, so a headless service can be inspected while
+//! it runs. Connect with `repl::connect`, or any tool such as
+//! `socat - UNIX-CONNECT:/run/greenhouse.sock`, and type `help` for the list of commands.
+//!
+//! Requires the `repl` feature.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::*;
+//! use rust_gpiozero::repl::Repl;
+//! use std::sync::{Arc, Mutex};
+//!
+//! let mut tree = DeviceTree::default();
+//! tree.add_inspectable("fan", LED::new(17)).unwrap();
+//! tree.add_inspectable("door", Button::new(27)).unwrap();
+//! let tree = Arc::new(Mutex::new(tree));
+//!
+//! let _repl = Repl::serve(tree.clone(), "/tmp/greenhouse.sock").unwrap();
+//! // the application keeps running while the REPL is open
+//! std::thread::park();
+//! ```
+use crate::control::SocketServer;
+use crate::tree::DeviceTree;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+const HELP: &str = "commands:
+  list                  list the devices and their values
+  get NAME              show the value of a device
+  set NAME VALUE        change the value of a device
+  fire NAME EVENT       fire a test event, e.g. `fire door pressed`
+  tail [COUNT]          show the last entries of the event log (default 10)
+  help                  show this help
+  quit                  close the connection";
+
+/// Run one REPL command against the tree and return the text to print.
+pub fn execute(tree: &mut DeviceTree, line: &str) -> String {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let result = match words.as_slice() {
+        [] => Ok(String::new()),
+        ["help"] => Ok(HELP.to_string()),
+        ["list"] => {
+            let names: Vec<String> = tree.names().iter().map(|n| n.to_string()).collect();
+            Ok(names
+                .iter()
+                .map(|name| match tree.value(name) {
+                    Ok(value) => format!("{} = {}", name, value),
+                    Err(_) => name.clone(),
+                })
+                .collect::<Vec<String>>()
+                .join("\n"))
+        }
+        ["get", name] => tree.value(name),
+        ["set", name, value] => tree.set_value(name, value).map(|_| "ok".to_string()),
+        ["fire", name, event] => tree.fire(name, event).map(|_| "ok".to_string()),
+        ["tail"] => Ok(tail(tree, 10)),
+        ["tail", count] => match count.parse() {
+            Ok(count) => Ok(tail(tree, count)),
+            Err(_) => Err(format!("'{}' is not a count", count)),
+        },
+        _ => Err(format!("unknown command '{}', type help", line.trim())),
+    };
+    match result {
+        Ok(output) => output,
+        Err(error) => format!("error: {}", error),
+    }
+}
+
+fn tail(tree: &DeviceTree, count: usize) -> String {
+    tree.recent_events(count)
+        .iter()
+        .map(|event| event.to_string())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// A REPL server listening on a Unix socket. The socket is removed when the server is dropped.
+pub struct Repl {
+    server: SocketServer,
+}
+
+impl Repl {
+    /// Start serving the tree on a Unix socket. A socket file left at the path is replaced;
+    /// any other file there is left alone and an error of kind `AlreadyExists` returned.
+    /// * `tree` - The device tree shared with the application
+    /// * `path` - Path of the socket
+    pub fn serve<P: AsRef<Path>>(tree: Arc<Mutex<DeviceTree>>, path: P) -> io::Result<Repl> {
+        let server = SocketServer::bind(path.as_ref(), move |stream, _| {
+            let _ = session(stream, tree.clone());
+        })?;
+        Ok(Repl { server })
+    }
+
+    /// Path of the socket.
+    pub fn path(&self) -> &Path {
+        self.server.path()
+    }
+
+    /// Stop accepting connections and remove the socket. Open sessions end when their client disconnects.
+    pub fn stop(&mut self) {
+        self.server.stop()
+    }
+}
+
+fn session(stream: UnixStream, tree: Arc<Mutex<DeviceTree>>) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    let mut writer = stream.try_clone()?;
+    let reader = BufReader::new(stream);
+    write!(writer, "> ")?;
+    for line in reader.lines() {
+        let line = line?;
+        if matches!(line.trim(), "quit" | "exit") {
+            break;
+        }
+        let output = match tree.lock() {
+            Ok(mut tree) => execute(&mut tree, &line),
+            Err(_) => "error: device tree is unavailable".to_string(),
+        };
+        if !output.is_empty() {
+            writeln!(writer, "{}", output)?;
+        }
+        write!(writer, "> ")?;
+    }
+    Ok(())
+}
+
+/// Connect the terminal to a REPL socket until the input ends or the server closes the connection.
+pub fn connect<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    let stream = UnixStream::connect(path)?;
+    let mut reader = stream.try_clone()?;
+    let output = thread::spawn(move || io::copy(&mut reader, &mut io::stdout()));
+    let mut writer = stream;
+    for line in io::stdin().lock().lines() {
+        writeln!(writer, "{}", line?)?;
+    }
+    writer.shutdown(std::net::Shutdown::Write)?;
+    let _ = output.join();
+    Ok(())
+}
@@ -0,0 +1,120 @@
+//! Forcing outputs to a safe state when the program panics
+//!
+//! Normally a device is turned off when it is dropped, but a panic in a background thread, or
+//! a build with `panic = "abort"`, never drops the devices of the rest of the program, so a
+//! heater or motor can be left running. `install_safety_panic_hook` installs a panic hook that
+//! first stops every blink, pulse, pattern and melody and drives every output to its safe
+//! state, then runs the hook that was installed before it, e.g. the one printing the message.
+//!
+//! The safe state of an output is off unless it is changed with `set_safe_state`, e.g. for a
+//! vent that should open when the controller fails. Outputs that are dropped are forgotten.
+//! A bare `OutputDevice` is not shared with the hook, so it is only turned off when dropped;
+//! wrap it in a `DigitalOutputDevice` or `PWMOutputDevice` to protect it.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::*;
+//!
+//! install_safety_panic_hook();
+//!
+//! let mut heater = DigitalOutputDevice::new(17);
+//! let mut vent = DigitalOutputDevice::new(27);
+//! vent.set_safe_state(true);
+//! heater.on();
+//!
+//! // both are forced to their safe states before the panic is reported
+//! panic!("sensor thread died");
+//! ```
+use crate::output_devices::OutputDevice;
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long the hook waits for another thread to let go of a device.
+const LOCK_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// An output and the flag that keeps its background thread running.
+struct SafeOutput {
+    device: Weak<Mutex<OutputDevice>>,
+    running: Weak<AtomicBool>,
+}
+
+static OUTPUTS: Mutex<Vec<SafeOutput>> = Mutex::new(Vec::new());
+
+static HOOK_INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// Share the device so the panic hook can reach it, remembering the flag that keeps its
+/// background thread running.
+pub(crate) fn register(
+    device: OutputDevice,
+    running: &Arc<AtomicBool>,
+) -> Arc<Mutex<OutputDevice>> {
+    let device = Arc::new(Mutex::new(device));
+    let mut outputs = OUTPUTS.lock().unwrap_or_else(|e| e.into_inner());
+    outputs.retain(|output| output.device.strong_count() > 0);
+    outputs.push(SafeOutput {
+        device: Arc::downgrade(&device),
+        running: Arc::downgrade(running),
+    });
+    device
+}
+
+/// Lock a mutex even if it is poisoned, giving up if another thread holds it for too long,
+/// as the thread that panicked may never let go of it.
+fn lock<T>(mutex: &Mutex<T>) -> Option<MutexGuard<'_, T>> {
+    let start = Instant::now();
+    loop {
+        match mutex.try_lock() {
+            Ok(guard) => return Some(guard),
+            Err(TryLockError::Poisoned(e)) => return Some(e.into_inner()),
+            Err(TryLockError::WouldBlock) if start.elapsed() < LOCK_TIMEOUT => thread::yield_now(),
+            Err(TryLockError::WouldBlock) => return None,
+        }
+    }
+}
+
+/// Stop all background blinks, pulses, patterns and melodies and drive every output to its
+/// safe state. This is what the safety panic hook does, and can also be called on the way
+/// out of a program, e.g. when a watchdog expires.
+///
+/// Returns the number of outputs forced. An output locked by the thread that panicked
+/// cannot be reached and is left as it is.
+pub fn force_safe_states() -> usize {
+    let outputs = match lock(&OUTPUTS) {
+        Some(outputs) => outputs
+            .iter()
+            .filter_map(|output| Some((output.device.upgrade()?, output.running.upgrade())))
+            .collect::<Vec<_>>(),
+        None => return 0,
+    };
+    // stop every thread first, so none of them switches an output back on
+    for (_, running) in &outputs {
+        if let Some(running) = running {
+            running.store(false, Ordering::SeqCst);
+        }
+    }
+    let mut forced = 0;
+    for (device, _) in &outputs {
+        if let Some(mut device) = lock(device) {
+            device.force_safe_state();
+            forced += 1;
+        }
+    }
+    forced
+}
+
+/// Install a panic hook that calls `force_safe_states` before running the hook installed
+/// before it. Installing it more than once has no further effect.
+pub fn install_safety_panic_hook() {
+    if HOOK_INSTALLED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        force_safe_states();
+        previous(info)
+    }));
+}
@@ -0,0 +1,807 @@
+//! The Raspberry Pi Sense HAT: an 8x8 RGB LED matrix, a joystick and its sensors
+//!
+//! The kernel drives the matrix and the joystick once the `rpi-sense` overlay is loaded,
+//! which the HAT's EEPROM requests on Raspberry Pi OS: the matrix is a framebuffer device
+//! and the joystick an input event device. The humidity, pressure and motion sensors are
+//! read over I2C bus 1.
+//!
+//! The matrix implements `PixelStrip`, its 64 pixels numbered row by row from the top
+//! left, so the effects of an `Animator` play on it too.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::*;
+//!
+//! let mut hat = SenseHat::new();
+//! hat.stick.when_pressed(StickDirection::Middle, || println!("click"));
+//! let temperature = hat.temperature().unwrap();
+//! hat.display.show_message(&format!("{:.1}C", temperature), 0.08, Color::rgb(255, 0, 0));
+//! ```
+use crate::bus::{BusLink, BusStats, RetryPolicy, SharedI2c};
+use crate::input_devices::timeout_duration;
+use crate::spi_devices::text_columns;
+use crate::strips::{Color, PixelStrip};
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read};
+use std::mem;
+use std::os::raw::c_ulong;
+use std::os::unix::fs::{FileExt, OpenOptionsExt};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// The name the framebuffer of the matrix reports in sysfs.
+const MATRIX_NAME: &str = "RPi-Sense FB";
+
+/// The name the joystick reports in sysfs.
+const STICK_NAME: &str = "Raspberry Pi Sense HAT Joystick";
+
+/// `O_NONBLOCK`, so the joystick thread can notice it should stop.
+const O_NONBLOCK: i32 = 0o4000;
+
+/// The event type of key presses.
+const EV_KEY: u16 = 1;
+
+/// How many joystick events are kept for `events`, dropping the oldest.
+const STICK_QUEUE: usize = 64;
+
+const HTS221_ADDRESS: u16 = 0x5f;
+const LPS25H_ADDRESS: u16 = 0x5c;
+const LSM9DS1_ADDRESS: u16 = 0x6a;
+const LSM9DS1_MAG_ADDRESS: u16 = 0x1c;
+
+/// The bit of a register address that makes reads of the ST sensors auto-increment.
+const AUTO_INCREMENT: u8 = 0x80;
+
+/// The three axes of a motion reading, such as an acceleration, in the frame of the chip
+/// or board printed on it.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Axes {
+    /// Along the x axis
+    pub x: f64,
+    /// Along the y axis
+    pub y: f64,
+    /// Along the z axis
+    pub z: f64,
+}
+
+/// How the HAT is tilted and turned, in degrees.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Orientation {
+    /// Rotation about the x axis, between -180.0 and 180.0
+    pub roll: f64,
+    /// Rotation about the y axis, between -90.0 and 90.0
+    pub pitch: f64,
+    /// The compass heading of the x axis, between 0.0 and 360.0
+    pub yaw: f64,
+}
+
+/// Represents the 8x8 RGB LED matrix of a Sense HAT, with a framebuffer of its pixels.
+///
+/// The LEDs show colours in 5 or 6 bits per component, so close colours may look the
+/// same. `set_rotation` turns the picture to match how the Pi is mounted.
+pub struct SenseDisplay {
+    device: File,
+    pixels: [Color; 64],
+    rotation: u16,
+    brightness: f64,
+}
+
+impl SenseDisplay {
+    /// Returns the matrix of the Sense HAT, all off
+    pub fn new() -> SenseDisplay {
+        let path = match find_device("/sys/class/graphics", "fb", "name", MATRIX_NAME) {
+            Some(path) => path,
+            None => panic!("No Sense HAT framebuffer, add dtoverlay=rpi-sense to config.txt"),
+        };
+        match OpenOptions::new().write(true).open(path) {
+            Err(e) => panic!("{:?}", e),
+            Ok(device) => {
+                let mut display = SenseDisplay {
+                    device,
+                    pixels: [Color::BLACK; 64],
+                    rotation: 0,
+                    brightness: 1.0,
+                };
+                display.show();
+                display
+            }
+        }
+    }
+
+    /// The colour of the pixel at a column and row in the framebuffer, or None if off the
+    /// matrix.
+    pub fn pixel_at(&self, x: usize, y: usize) -> Option<Color> {
+        if x < 8 && y < 8 {
+            Some(self.pixels[y * 8 + x])
+        } else {
+            None
+        }
+    }
+
+    /// Set the colour of the pixel at a column and row in the framebuffer. Pixels off the
+    /// matrix are ignored.
+    /// * `x` - The column, 0 being the left
+    /// * `y` - The row, 0 being the top
+    /// * `color` - The colour
+    pub fn set_pixel_at(&mut self, x: usize, y: usize, color: Color) {
+        if x < 8 && y < 8 {
+            self.pixels[y * 8 + x] = color
+        }
+    }
+
+    /// Draw text in the framebuffer in a 5x7 font, from the column given, over what is
+    /// already drawn. Characters outside printable ASCII are drawn as `?`.
+    /// * `x` - The column of the left edge of the text, which may be off the matrix
+    /// * `text` - The text
+    /// * `color` - The colour of the text
+    pub fn draw_text(&mut self, x: isize, text: &str, color: Color) {
+        for (i, column) in text_columns(text).into_iter().enumerate() {
+            let at = x + i as isize;
+            if (0..8).contains(&at) {
+                for y in 0..7 {
+                    if column & (1 << y) != 0 {
+                        self.set_pixel_at(at as usize, y + 1, color);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Show a single character on a cleared matrix.
+    /// * `c` - The character
+    /// * `color` - The colour of the character
+    pub fn show_letter(&mut self, c: char, color: Color) {
+        self.clear();
+        let mut text = [0; 4];
+        self.draw_text(2, c.encode_utf8(&mut text), color);
+        self.show();
+    }
+
+    /// Scroll text across the matrix from right to left in a 5x7 font, until it has
+    /// scrolled off. Blocks while scrolling, and leaves the matrix blank.
+    /// * `text` - The text
+    /// * `column_time` - Number of seconds before moving the text one column along
+    /// * `color` - The colour of the text
+    pub fn show_message(&mut self, text: &str, column_time: f64, color: Color) {
+        if !column_time.is_finite() || column_time < 0.0 {
+            println!("Column time must be a positive number");
+            return;
+        }
+        let length = text_columns(text).len() as isize;
+        for x in (-length..=8).rev() {
+            self.clear();
+            self.draw_text(x, text, color);
+            self.show();
+            thread::sleep(Duration::from_secs_f64(column_time));
+        }
+    }
+
+    /// The rotation of the picture in degrees.
+    pub fn rotation(&self) -> u16 {
+        self.rotation
+    }
+
+    /// Turn the picture clockwise, taking effect on the next `show`.
+    /// * `degrees` - One of 0, 90, 180 and 270
+    pub fn set_rotation(&mut self, degrees: u16) {
+        if !matches!(degrees, 0 | 90 | 180 | 270) {
+            println!("Rotation must be 0, 90, 180 or 270 degrees");
+            return;
+        }
+        self.rotation = degrees
+    }
+}
+
+impl Default for SenseDisplay {
+    fn default() -> Self {
+        SenseDisplay::new()
+    }
+}
+
+impl PixelStrip for SenseDisplay {
+    fn len(&self) -> usize {
+        self.pixels.len()
+    }
+
+    fn pixel(&self, index: usize) -> Option<Color> {
+        self.pixels.get(index).copied()
+    }
+
+    fn set_pixel(&mut self, index: usize, color: Color) {
+        if let Some(pixel) = self.pixels.get_mut(index) {
+            *pixel = color;
+        }
+    }
+
+    // the framebuffer holds the pixels row by row in RGB565, as the matrix is mounted
+    fn show(&mut self) {
+        let mut frame = [0u8; 128];
+        for y in 0..8 {
+            for x in 0..8 {
+                let (from_x, from_y) = match self.rotation {
+                    90 => (y, 7 - x),
+                    180 => (7 - x, 7 - y),
+                    270 => (7 - y, x),
+                    _ => (x, y),
+                };
+                let color = self.pixels[from_y * 8 + from_x].scaled(self.brightness);
+                let rgb565 = u16::from(color.red >> 3) << 11
+                    | u16::from(color.green >> 2) << 5
+                    | u16::from(color.blue >> 3);
+                let at = (y * 8 + x) * 2;
+                frame[at..at + 2].copy_from_slice(&rgb565.to_le_bytes());
+            }
+        }
+        if self.device.write_all_at(&frame, 0).is_err() {
+            println!("Could not write to the Sense HAT matrix");
+        }
+    }
+
+    fn brightness(&self) -> f64 {
+        self.brightness
+    }
+
+    fn set_brightness(&mut self, brightness: f64) {
+        if !(0.0..=1.0).contains(&brightness) {
+            println!("Brightness must be between 0.0 and 1.0");
+            return;
+        }
+        self.brightness = brightness
+    }
+}
+
+impl Drop for SenseDisplay {
+    fn drop(&mut self) {
+        self.clear();
+        self.show();
+    }
+}
+
+/// A direction of the joystick of a Sense HAT, as seen with the HDMI port of the Pi at the
+/// bottom.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StickDirection {
+    /// Pushed up
+    Up,
+    /// Pushed down
+    Down,
+    /// Pushed left
+    Left,
+    /// Pushed right
+    Right,
+    /// Pressed in
+    Middle,
+}
+
+impl StickDirection {
+    /// Every direction, in the order of their index.
+    const ALL: [StickDirection; 5] = [
+        StickDirection::Up,
+        StickDirection::Down,
+        StickDirection::Left,
+        StickDirection::Right,
+        StickDirection::Middle,
+    ];
+
+    fn from_code(code: u16) -> Option<StickDirection> {
+        match code {
+            103 => Some(StickDirection::Up),
+            108 => Some(StickDirection::Down),
+            105 => Some(StickDirection::Left),
+            106 => Some(StickDirection::Right),
+            28 => Some(StickDirection::Middle),
+            _ => None,
+        }
+    }
+
+    fn index(self) -> usize {
+        StickDirection::ALL
+            .iter()
+            .position(|&direction| direction == self)
+            .unwrap_or(0)
+    }
+}
+
+/// What the joystick did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StickAction {
+    /// Pushed in a direction
+    Pressed,
+    /// Let go
+    Released,
+    /// Still held, repeated while held
+    Held,
+}
+
+/// A movement of the joystick of a Sense HAT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StickEvent {
+    /// When the kernel saw it
+    pub timestamp: SystemTime,
+    /// The direction
+    pub direction: StickDirection,
+    /// What the joystick did
+    pub action: StickAction,
+}
+
+type StickCallback = Box<dyn FnMut(StickEvent) + Send>;
+
+/// The joystick state shared with its thread.
+#[derive(Debug, Default)]
+struct StickState {
+    pressed: [bool; 5],
+    events: VecDeque<StickEvent>,
+}
+
+/// The functions called by the joystick thread, kept apart from the state so they can
+/// query the joystick.
+#[derive(Default)]
+struct StickCallbacks {
+    when_pressed: [Option<StickCallback>; 5],
+    when_released: [Option<StickCallback>; 5],
+    when_event: Option<StickCallback>,
+}
+
+impl fmt::Debug for StickCallbacks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StickCallbacks")
+            .field("when_event", &self.when_event.is_some())
+            .finish()
+    }
+}
+
+/// Represents the five-way joystick of a Sense HAT, read by a background thread.
+///
+/// Events are kept until taken with `events` or `wait_for_event`, and callbacks are called
+/// from the background thread as they arrive.
+pub struct SenseStick {
+    state: Arc<(Mutex<StickState>, Condvar)>,
+    callbacks: Arc<Mutex<StickCallbacks>>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl SenseStick {
+    /// Returns the joystick of the Sense HAT
+    pub fn new() -> SenseStick {
+        let path = match find_device("/sys/class/input", "event", "device/name", STICK_NAME) {
+            Some(path) => path,
+            None => panic!("No Sense HAT joystick, add dtoverlay=rpi-sense to config.txt"),
+        };
+        let mut device = match OpenOptions::new()
+            .read(true)
+            .custom_flags(O_NONBLOCK)
+            .open(path)
+        {
+            Err(e) => panic!("{:?}", e),
+            Ok(device) => device,
+        };
+        let state = Arc::new((Mutex::new(StickState::default()), Condvar::new()));
+        let callbacks = Arc::new(Mutex::new(StickCallbacks::default()));
+        let running = Arc::new(AtomicBool::new(true));
+        let handle = {
+            let state = Arc::clone(&state);
+            let callbacks = Arc::clone(&callbacks);
+            let running = Arc::clone(&running);
+            thread::spawn(move || {
+                // struct input_event: a timeval of two longs, then type, code and value
+                let long = mem::size_of::<c_ulong>();
+                let size = 2 * long + 8;
+                let mut buffer = vec![0u8; size * 16];
+                while running.load(Ordering::SeqCst) {
+                    let read = match device.read(&mut buffer) {
+                        Ok(read) => read,
+                        Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                            thread::sleep(Duration::from_millis(10));
+                            continue;
+                        }
+                        Err(_) => break,
+                    };
+                    for event in buffer[..read].chunks_exact(size) {
+                        let number = |at: usize| {
+                            let mut bytes = [0u8; 8];
+                            bytes[..long].copy_from_slice(&event[at..at + long]);
+                            u64::from_ne_bytes(bytes)
+                        };
+                        let kind = u16::from_ne_bytes([event[2 * long], event[2 * long + 1]]);
+                        let code = u16::from_ne_bytes([event[2 * long + 2], event[2 * long + 3]]);
+                        let value = i32::from_ne_bytes([
+                            event[2 * long + 4],
+                            event[2 * long + 5],
+                            event[2 * long + 6],
+                            event[2 * long + 7],
+                        ]);
+                        let direction = match StickDirection::from_code(code) {
+                            Some(direction) if kind == EV_KEY => direction,
+                            _ => continue,
+                        };
+                        let action = match value {
+                            0 => StickAction::Released,
+                            1 => StickAction::Pressed,
+                            _ => StickAction::Held,
+                        };
+                        let event = StickEvent {
+                            timestamp: UNIX_EPOCH
+                                + Duration::from_secs(number(0))
+                                + Duration::from_micros(number(long)),
+                            direction,
+                            action,
+                        };
+                        {
+                            let (lock, changed) = &*state;
+                            let mut state = lock.lock().unwrap();
+                            state.pressed[direction.index()] = action != StickAction::Released;
+                            if state.events.len() == STICK_QUEUE {
+                                state.events.pop_front();
+                            }
+                            state.events.push_back(event);
+                            changed.notify_all();
+                        }
+                        // the state is unlocked, so the callbacks can query the joystick
+                        let mut callbacks = callbacks.lock().unwrap();
+                        let callback = match action {
+                            StickAction::Pressed => {
+                                callbacks.when_pressed[direction.index()].as_mut()
+                            }
+                            StickAction::Released => {
+                                callbacks.when_released[direction.index()].as_mut()
+                            }
+                            StickAction::Held => None,
+                        };
+                        if let Some(callback) = callback {
+                            callback(event)
+                        }
+                        if let Some(callback) = &mut callbacks.when_event {
+                            callback(event)
+                        }
+                    }
+                }
+            })
+        };
+        SenseStick {
+            state,
+            callbacks,
+            running,
+            handle: Some(handle),
+        }
+    }
+
+    /// Returns ``True`` while the joystick is pushed in the direction given.
+    pub fn is_pressed(&self, direction: StickDirection) -> bool {
+        self.state.0.lock().unwrap().pressed[direction.index()]
+    }
+
+    /// Take the events that arrived since the last call, oldest first.
+    pub fn events(&self) -> Vec<StickEvent> {
+        self.state.0.lock().unwrap().events.drain(..).collect()
+    }
+
+    /// Pause the program until the joystick moves, returning the oldest event not yet
+    /// taken, or None on timeout.
+    /// * `timeout` - Number of seconds to wait before giving up, or None to wait forever
+    pub fn wait_for_event(&self, timeout: Option<f32>) -> Option<StickEvent> {
+        let deadline = timeout_duration(timeout).and_then(|t| Instant::now().checked_add(t));
+        let (lock, changed) = &*self.state;
+        let mut state = lock.lock().unwrap();
+        loop {
+            if let Some(event) = state.events.pop_front() {
+                return Some(event);
+            }
+            state = match deadline {
+                None => changed.wait(state).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return None;
+                    }
+                    changed.wait_timeout(state, deadline - now).unwrap().0
+                }
+            };
+        }
+    }
+
+    /// Set the function called from a background thread when the joystick is pushed in
+    /// the direction given.
+    pub fn when_pressed<C>(&mut self, direction: StickDirection, mut callback: C)
+    where
+        C: FnMut() + Send + 'static,
+    {
+        self.callbacks.lock().unwrap().when_pressed[direction.index()] =
+            Some(Box::new(move |_| callback()));
+    }
+
+    /// Set the function called from a background thread when the joystick is let go after
+    /// being pushed in the direction given.
+    pub fn when_released<C>(&mut self, direction: StickDirection, mut callback: C)
+    where
+        C: FnMut() + Send + 'static,
+    {
+        self.callbacks.lock().unwrap().when_released[direction.index()] =
+            Some(Box::new(move |_| callback()));
+    }
+
+    /// Set the function called from a background thread with every event of the joystick.
+    pub fn when_event<C>(&mut self, callback: C)
+    where
+        C: FnMut(StickEvent) + Send + 'static,
+    {
+        self.callbacks.lock().unwrap().when_event = Some(Box::new(callback));
+    }
+
+    /// Remove the functions set with `when_pressed`, `when_released` and `when_event`.
+    pub fn clear_callbacks(&mut self) {
+        *self.callbacks.lock().unwrap() = StickCallbacks::default();
+    }
+}
+
+impl Default for SenseStick {
+    fn default() -> Self {
+        SenseStick::new()
+    }
+}
+
+impl Drop for SenseStick {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.join().expect("Could not join spawned thread");
+        }
+    }
+}
+
+/// The calibration of the HTS221, read from the chip once.
+#[derive(Debug, Clone, Copy)]
+struct HTS221Calibration {
+    humidity: [(f64, f64); 2],
+    temperature: [(f64, f64); 2],
+}
+
+impl HTS221Calibration {
+    /// The value of a raw reading, on the line through the two calibration points.
+    fn interpolate(points: [(f64, f64); 2], raw: i16) -> f64 {
+        let [(raw0, value0), (raw1, value1)] = points;
+        value0 + (f64::from(raw) - raw0) * (value1 - value0) / (raw1 - raw0)
+    }
+}
+
+/// The sensors of a Sense HAT, each chip on its own I2C address.
+struct SenseSensors {
+    humidity: SharedI2c,
+    pressure: SharedI2c,
+    imu: SharedI2c,
+    magnetometer: SharedI2c,
+    link: BusLink,
+    calibration: HTS221Calibration,
+}
+
+impl SenseSensors {
+    fn open() -> Result<SenseSensors, rppal::i2c::Error> {
+        let humidity = SharedI2c::open(1, HTS221_ADDRESS)?;
+        let pressure = SharedI2c::open(1, LPS25H_ADDRESS)?;
+        let imu = SharedI2c::open(1, LSM9DS1_ADDRESS)?;
+        let magnetometer = SharedI2c::open(1, LSM9DS1_MAG_ADDRESS)?;
+        // power on with block data update, 1Hz humidity and 12.5Hz pressure
+        humidity.lock()?.smbus_write_byte(0x20, 0x85)?;
+        pressure.lock()?.smbus_write_byte(0x20, 0xb4)?;
+        // gyroscope at 119Hz and ±245dps, accelerometer at 119Hz and ±2g
+        let bus = imu.lock()?;
+        bus.smbus_write_byte(0x10, 0x60)?;
+        bus.smbus_write_byte(0x20, 0x60)?;
+        drop(bus);
+        // magnetometer at 10Hz, ±4 gauss, continuous
+        let bus = magnetometer.lock()?;
+        bus.smbus_write_byte(0x20, 0x70)?;
+        bus.smbus_write_byte(0x21, 0x00)?;
+        bus.smbus_write_byte(0x22, 0x00)?;
+        drop(bus);
+
+        let mut table = [0u8; 16];
+        humidity
+            .lock()?
+            .write_read(&[0x30 | AUTO_INCREMENT], &mut table)?;
+        let word = |at: usize| f64::from(i16::from_le_bytes([table[at], table[at + 1]]));
+        let t0 = f64::from(u16::from(table[5] & 0x03) << 8 | u16::from(table[2])) / 8.0;
+        let t1 = f64::from(u16::from(table[5] & 0x0c) << 6 | u16::from(table[3])) / 8.0;
+        let calibration = HTS221Calibration {
+            humidity: [
+                (word(6), f64::from(table[0]) / 2.0),
+                (word(10), f64::from(table[1]) / 2.0),
+            ],
+            temperature: [(word(12), t0), (word(14), t1)],
+        };
+        Ok(SenseSensors {
+            humidity,
+            pressure,
+            imu,
+            magnetometer,
+            link: BusLink::default(),
+            calibration,
+        })
+    }
+
+    /// Read consecutive 16 bit little endian registers.
+    fn words<const N: usize>(
+        link: &mut BusLink,
+        i2c: &SharedI2c,
+        register: u8,
+    ) -> Result<[i16; N], rppal::i2c::Error> {
+        let mut bytes = vec![0u8; N * 2];
+        link.run(|| i2c.lock()?.write_read(&[register], &mut bytes))?;
+        let mut words = [0i16; N];
+        for (word, pair) in words.iter_mut().zip(bytes.chunks_exact(2)) {
+            *word = i16::from_le_bytes([pair[0], pair[1]]);
+        }
+        Ok(words)
+    }
+}
+
+/// Represents a Sense HAT: its LED matrix, its joystick and its sensors.
+///
+/// The temperature is read by the humidity sensor, which sits close to the Pi and reads
+/// several degrees high; `temperature_from_pressure` reads the other sensor. Orientation
+/// is worked out from the accelerometer and magnetometer alone, so it is steady at rest
+/// but shaken by movement.
+pub struct SenseHat {
+    /// The LED matrix
+    pub display: SenseDisplay,
+    /// The joystick
+    pub stick: SenseStick,
+    sensors: SenseSensors,
+}
+
+impl SenseHat {
+    /// Returns the Sense HAT, with the matrix off
+    pub fn new() -> SenseHat {
+        match SenseSensors::open() {
+            Err(e) => panic!("{:?}", e),
+            Ok(sensors) => SenseHat {
+                display: SenseDisplay::new(),
+                stick: SenseStick::new(),
+                sensors,
+            },
+        }
+    }
+
+    /// The relative humidity in percent.
+    pub fn humidity(&mut self) -> Result<f64, rppal::i2c::Error> {
+        let SenseSensors {
+            humidity,
+            link,
+            calibration,
+            ..
+        } = &mut self.sensors;
+        let [raw] = SenseSensors::words::<1>(link, humidity, 0x28 | AUTO_INCREMENT)?;
+        let humidity = HTS221Calibration::interpolate(calibration.humidity, raw);
+        Ok(humidity.clamp(0.0, 100.0))
+    }
+
+    /// The temperature in degrees Celsius, from the humidity sensor.
+    pub fn temperature(&mut self) -> Result<f64, rppal::i2c::Error> {
+        let SenseSensors {
+            humidity,
+            link,
+            calibration,
+            ..
+        } = &mut self.sensors;
+        let [raw] = SenseSensors::words::<1>(link, humidity, 0x2a | AUTO_INCREMENT)?;
+        Ok(HTS221Calibration::interpolate(calibration.temperature, raw))
+    }
+
+    /// The air pressure in millibars (hPa).
+    pub fn pressure(&mut self) -> Result<f64, rppal::i2c::Error> {
+        let SenseSensors { pressure, link, .. } = &mut self.sensors;
+        let mut bytes = [0u8; 3];
+        link.run(|| {
+            pressure
+                .lock()?
+                .write_read(&[0x28 | AUTO_INCREMENT], &mut bytes)
+        })?;
+        let raw = i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8;
+        Ok(f64::from(raw) / 4096.0)
+    }
+
+    /// The temperature in degrees Celsius, from the pressure sensor.
+    pub fn temperature_from_pressure(&mut self) -> Result<f64, rppal::i2c::Error> {
+        let SenseSensors { pressure, link, .. } = &mut self.sensors;
+        let [raw] = SenseSensors::words::<1>(link, pressure, 0x2b | AUTO_INCREMENT)?;
+        Ok(42.5 + f64::from(raw) / 480.0)
+    }
+
+    /// The acceleration in g, including gravity.
+    pub fn acceleration(&mut self) -> Result<Axes, rppal::i2c::Error> {
+        let SenseSensors { imu, link, .. } = &mut self.sensors;
+        let [x, y, z] = SenseSensors::words::<3>(link, imu, 0x28)?;
+        let g = |raw: i16| f64::from(raw) * 0.000_061;
+        Ok(Axes {
+            x: g(x),
+            y: g(y),
+            z: g(z),
+        })
+    }
+
+    /// The rate of rotation in degrees per second.
+    pub fn gyroscope(&mut self) -> Result<Axes, rppal::i2c::Error> {
+        let SenseSensors { imu, link, .. } = &mut self.sensors;
+        let [x, y, z] = SenseSensors::words::<3>(link, imu, 0x18)?;
+        let dps = |raw: i16| f64::from(raw) * 0.008_75;
+        Ok(Axes {
+            x: dps(x),
+            y: dps(y),
+            z: dps(z),
+        })
+    }
+
+    /// The magnetic field in microteslas.
+    pub fn magnetometer(&mut self) -> Result<Axes, rppal::i2c::Error> {
+        let SenseSensors {
+            magnetometer, link, ..
+        } = &mut self.sensors;
+        let [x, y, z] = SenseSensors::words::<3>(link, magnetometer, 0x28 | AUTO_INCREMENT)?;
+        let microtesla = |raw: i16| f64::from(raw) * 0.014;
+        Ok(Axes {
+            x: microtesla(x),
+            y: microtesla(y),
+            z: microtesla(z),
+        })
+    }
+
+    /// The roll and pitch from gravity, and the heading from the magnetic field corrected
+    /// for the tilt.
+    pub fn orientation(&mut self) -> Result<Orientation, rppal::i2c::Error> {
+        let a = self.acceleration()?;
+        let m = self.magnetometer()?;
+        let roll = a.y.atan2(a.z);
+        let pitch = (-a.x).atan2(a.y.hypot(a.z));
+        let x = m.x * pitch.cos() + m.y * roll.sin() * pitch.sin() + m.z * roll.cos() * pitch.sin();
+        let y = m.y * roll.cos() - m.z * roll.sin();
+        Ok(Orientation {
+            roll: roll.to_degrees(),
+            pitch: pitch.to_degrees(),
+            yaw: (-y).atan2(x).to_degrees().rem_euclid(360.0),
+        })
+    }
+
+    /// The compass heading of the x axis in degrees from magnetic north, corrected for
+    /// the tilt.
+    pub fn compass(&mut self) -> Result<f64, rppal::i2c::Error> {
+        Ok(self.orientation()?.yaw)
+    }
+
+    /// The error counters of the sensor transfers.
+    pub fn bus_stats(&self) -> BusStats {
+        self.sensors.link.stats()
+    }
+
+    /// Change how failed sensor transfers are retried.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.sensors.link.set_policy(policy)
+    }
+}
+
+impl Default for SenseHat {
+    fn default() -> Self {
+        SenseHat::new()
+    }
+}
+
+/// The `/dev` path of the first device of a sysfs class whose attribute matches.
+fn find_device(class: &str, prefix: &str, attribute: &str, name: &str) -> Option<String> {
+    let mut devices: Vec<String> = fs::read_dir(class)
+        .ok()?
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|device| device.starts_with(prefix))
+        .collect();
+    devices.sort();
+    devices
+        .into_iter()
+        .find(|device| {
+            fs::read_to_string(format!("{}/{}/{}", class, device, attribute))
+                .is_ok_and(|found| found.trim() == name)
+        })
+        .map(|device| format!("/dev/{}", device))
+}
@@ -0,0 +1,304 @@
+//! Record interactions with a `DeviceTree` and replay them as a demo
+//!
+//! A `SessionRecorder` samples every inspectable device of a tree and records each change
+//! with its time. Because the devices are polled rather than followed edge by edge, a
+//! change that is undone within one sampling interval, such as a quick tap of a button, is
+//! not recorded; choose the interval shorter than the briefest change that matters. The
+//! resulting `Session` can be saved to a file and later played back:
+//! changes of output devices are applied again with the original timing, changes of
+//! read-only devices such as buttons are skipped. Looping the playback gives an unattended
+//! demo, for example for a museum exhibit.
+//!
+//! Session files hold one JSON object per line, so device names and values may contain
+//! any character:
+//!
+//! ```text
+//! # rust_gpiozero session v2
+//! {"time":1200,"device":"lamp","value":"1"}
+//! {"length":5000}
+//! ```
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::*;
+//! use std::sync::{Arc, Mutex};
+//! use std::time::Duration;
+//!
+//! let mut tree = DeviceTree::default();
+//! tree.add_inspectable("lamp", LED::new(17)).unwrap();
+//! tree.add_inspectable("button", Button::new(2)).unwrap();
+//! let tree = Arc::new(Mutex::new(tree));
+//!
+//! let recorder = SessionRecorder::start(tree.clone(), Duration::from_millis(10));
+//! // ... somebody plays with the exhibit ...
+//! let session = recorder.stop();
+//! session.save("/var/lib/exhibit/demo.session").unwrap();
+//!
+//! // later: loop the demo until stopped
+//! let demo = Session::load("/var/lib/exhibit/demo.session").unwrap();
+//! let mut playback = demo.play(tree, None);
+//! ```
+use crate::json::Json;
+use crate::output_devices::sleep_while_running;
+use crate::tree::DeviceTree;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+const HEADER: &str = "# rust_gpiozero session v2";
+
+/// A recorded change of a device's value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionEntry {
+    /// Time since the start of the recording
+    pub offset: Duration,
+    /// Name of the device in the tree
+    pub device: String,
+    /// The new value, as reported by `DeviceTree::value`
+    pub value: String,
+}
+
+/// A recorded sequence of device changes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Session {
+    entries: Vec<SessionEntry>,
+    length: Duration,
+}
+
+impl Session {
+    /// Returns a Session from entries sorted by offset and its total length
+    pub fn new(mut entries: Vec<SessionEntry>, length: Duration) -> Session {
+        entries.sort_by_key(|entry| entry.offset);
+        let last = entries.last().map_or(Duration::from_secs(0), |e| e.offset);
+        Session {
+            entries,
+            length: length.max(last),
+        }
+    }
+
+    /// The recorded changes, in order.
+    pub fn entries(&self) -> &[SessionEntry] {
+        &self.entries
+    }
+
+    /// Total length of the session, including the quiet time after the last change.
+    pub fn length(&self) -> Duration {
+        self.length
+    }
+
+    /// Parse the text of a session file.
+    pub fn parse(text: &str) -> Result<Session, Box<dyn Error>> {
+        let mut entries = Vec::new();
+        let mut length = Duration::from_secs(0);
+        for (number, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || format!("line {}: expected time, device and value", number + 1);
+            let json = Json::parse(line).map_err(|e| format!("line {}: {}", number + 1, e))?;
+            let millis = |key| {
+                json.get(key)
+                    .and_then(Json::as_f64)
+                    .filter(|ms| *ms >= 0.0)
+                    .map(|ms| Duration::from_millis(ms as u64))
+            };
+            if let Some(total) = millis("length") {
+                length = total;
+                continue;
+            }
+            let offset = millis("time").ok_or_else(invalid)?;
+            match (
+                json.get("device").and_then(Json::as_str),
+                json.get("value").and_then(Json::as_str),
+            ) {
+                (Some(device), Some(value)) => entries.push(SessionEntry {
+                    offset,
+                    device: device.to_string(),
+                    value: value.to_string(),
+                }),
+                _ => return Err(invalid().into()),
+            }
+        }
+        Ok(Session::new(entries, length))
+    }
+
+    /// Read a session file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Session, Box<dyn Error>> {
+        Session::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Write the session to a file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    /// Replay the changes of writable devices in the background with the recorded timing.
+    /// * `tree` - The tree to apply the changes to
+    /// * `repeat` - Number of times to play the session. If this is None, loop until stopped.
+    pub fn play(&self, tree: Arc<Mutex<DeviceTree>>, repeat: Option<u32>) -> Playback {
+        let session = self.clone();
+        let running = Arc::new(AtomicBool::new(true));
+        let still_running = Arc::clone(&running);
+        let handle = thread::spawn(move || {
+            let mut played = 0;
+            while still_running.load(Ordering::SeqCst) && repeat.is_none_or(|n| played < n) {
+                let start = Instant::now();
+                for entry in &session.entries {
+                    sleep_while_running(
+                        &still_running,
+                        entry.offset.saturating_sub(start.elapsed()),
+                    );
+                    if !still_running.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    // read-only devices such as buttons reject the change and are skipped
+                    let _ = tree.lock().unwrap().set_value(&entry.device, &entry.value);
+                }
+                sleep_while_running(
+                    &still_running,
+                    session.length.saturating_sub(start.elapsed()),
+                );
+                played += 1;
+            }
+        });
+        Playback {
+            running,
+            handle: Some(handle),
+        }
+    }
+}
+
+impl fmt::Display for Session {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        for entry in &self.entries {
+            let line = Json::object(vec![
+                ("time", Json::Number(entry.offset.as_millis() as f64)),
+                ("device", Json::String(entry.device.clone())),
+                ("value", Json::String(entry.value.clone())),
+            ]);
+            writeln!(f, "{}", line)?;
+        }
+        let length = Json::object(vec![(
+            "length",
+            Json::Number(self.length.as_millis() as f64),
+        )]);
+        writeln!(f, "{}", length)
+    }
+}
+
+/// A session being played back by `Session::play`.
+pub struct Playback {
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Playback {
+    /// Returns ``True`` while the session is still playing.
+    pub fn is_playing(&self) -> bool {
+        self.handle
+            .as_ref()
+            .is_some_and(|handle| !handle.is_finished())
+    }
+
+    /// Stop the playback. Devices keep the value they had at that moment.
+    pub fn stop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+
+    /// Block until the playback is done.
+    pub fn wait(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for Playback {
+    fn drop(&mut self) {
+        self.stop()
+    }
+}
+
+/// Records the changes of every inspectable device in a tree until stopped.
+pub struct SessionRecorder {
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<Session>>,
+}
+
+impl SessionRecorder {
+    /// Start recording in the background.
+    /// * `tree` - The tree to record
+    /// * `interval` - How often the devices are sampled. Changes undone within one interval
+    ///   are not recorded.
+    pub fn start(tree: Arc<Mutex<DeviceTree>>, interval: Duration) -> SessionRecorder {
+        let running = Arc::new(AtomicBool::new(true));
+        let still_running = Arc::clone(&running);
+        let handle = thread::spawn(move || {
+            let start = Instant::now();
+            let mut entries = Vec::new();
+            let mut last: Vec<(String, String)> = Vec::new();
+            while still_running.load(Ordering::SeqCst) {
+                {
+                    let mut tree = tree.lock().unwrap();
+                    let names: Vec<String> = tree.names().iter().map(|n| n.to_string()).collect();
+                    for name in names {
+                        let value = match tree.value(&name) {
+                            Ok(value) => value,
+                            Err(_) => continue,
+                        };
+                        match last.iter_mut().find(|(n, _)| *n == name) {
+                            Some((_, previous)) if *previous == value => continue,
+                            Some((_, previous)) => *previous = value.clone(),
+                            None => last.push((name.clone(), value.clone())),
+                        }
+                        entries.push(SessionEntry {
+                            offset: start.elapsed(),
+                            device: name,
+                            value,
+                        });
+                    }
+                }
+                sleep_while_running(&still_running, interval);
+            }
+            Session::new(entries, start.elapsed())
+        });
+        SessionRecorder {
+            running,
+            handle: Some(handle),
+        }
+    }
+
+    /// Stop recording and return the session.
+    pub fn stop(mut self) -> Session {
+        self.finish()
+    }
+
+    fn finish(&mut self) -> Session {
+        self.running.store(false, Ordering::SeqCst);
+        match self.handle.take() {
+            Some(handle) => {
+                handle.thread().unpark();
+                handle.join().unwrap_or_default()
+            }
+            None => Session::default(),
+        }
+    }
+}
+
+impl Drop for SessionRecorder {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
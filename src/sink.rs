@@ -0,0 +1,490 @@
+//! Publishers of readings, and the queues that keep them from stalling whoever produces them
+//!
+//! A `Sink` publishes items one at a time: a line in a log file, an MQTT message, an HTTP
+//! POST, a row of an SQLite database. Publishing takes as long as the disk or the network
+//! takes, which is forever when a link is down, so producers such as the sampling thread of
+//! a `Pipeline` hand their items to a `SinkQueue`, which publishes them from its own thread.
+//! When a sink falls behind and its queue is full, the `Overflow` policy of the queue decides
+//! what is given up: the oldest item, the new one, or a bounded wait of the producer.
+//!
+//! `LogSink`, `MqttSink`, `HttpSink` and `SqliteSink` publish anything with a JSON form.
+//! MQTT messages are sent with the `mosquitto_pub` program, `https://` URLs with `curl` and
+//! rows with `sqlite3`, which must be installed. Closures returning a `Result` are sinks too.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::json::Json;
+//! use rust_gpiozero::*;
+//! use std::time::Duration;
+//!
+//! let mqtt = SinkQueue::new(
+//!     MqttSink::new("localhost", "greenhouse/soil"),
+//!     100,
+//!     Overflow::DropOldest,
+//! );
+//! let adc = MCP3008::new();
+//! let soil = adc.channel(0);
+//! loop {
+//!     // returns at once, even while the broker cannot be reached
+//!     mqtt.push(Json::from(soil.value()));
+//!     std::thread::sleep(Duration::from_secs(10));
+//! }
+//! ```
+use crate::json::{Json, ToJson};
+use crate::pipeline::Sample;
+use crate::webhook;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// A publisher of items of type `T`.
+pub trait Sink<T>: Send {
+    /// Publish one item. An error counts the item as failed; it is not published again.
+    fn publish(&mut self, item: &T) -> Result<(), Box<dyn Error>>;
+}
+
+impl<T, F> Sink<T> for F
+where
+    F: FnMut(&T) -> Result<(), Box<dyn Error>> + Send,
+{
+    fn publish(&mut self, item: &T) -> Result<(), Box<dyn Error>> {
+        self(item)
+    }
+}
+
+impl<T> Sink<T> for Box<dyn Sink<T>> {
+    fn publish(&mut self, item: &T) -> Result<(), Box<dyn Error>> {
+        (**self).publish(item)
+    }
+}
+
+/// What a `SinkQueue` gives up when an item is pushed while it is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// Drop the oldest queued item, so the sink catches up with the latest items
+    #[default]
+    DropOldest,
+    /// Drop the new item, so the sink gets every item up to the time it fell behind
+    DropNewest,
+    /// Wait up to the time given for room, then drop the new item
+    Block(Duration),
+}
+
+impl Overflow {
+    /// Parse a policy as written in a configuration file: `drop-oldest`, `drop-newest`, or
+    /// `block` and the longest wait in seconds, e.g. `block 0.5`.
+    pub fn parse(text: &str) -> Result<Overflow, String> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        match words.as_slice() {
+            ["drop-oldest"] => Ok(Overflow::DropOldest),
+            ["drop-newest"] => Ok(Overflow::DropNewest),
+            ["block", seconds] => match seconds.parse::<f64>() {
+                Ok(seconds) if seconds >= 0.0 && seconds.is_finite() => {
+                    Ok(Overflow::Block(Duration::from_secs_f64(seconds)))
+                }
+                _ => Err(format!("cannot block for {} seconds", seconds)),
+            },
+            _ => Err(format!("unknown overflow policy '{}'", text.trim())),
+        }
+    }
+}
+
+/// The counters of a `SinkQueue`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SinkStats {
+    /// The number of items published
+    pub sent: u64,
+    /// The number of items the sink failed to publish
+    pub failed: u64,
+    /// The number of items dropped because the queue was full
+    pub dropped: u64,
+    /// The number of items waiting to be published
+    pub queued: usize,
+}
+
+struct QueueState<T> {
+    items: VecDeque<T>,
+    stats: SinkStats,
+    last_error: Option<(Instant, String)>,
+    busy: bool,
+    running: bool,
+}
+
+struct QueueShared<T> {
+    state: Mutex<QueueState<T>>,
+    changed: Condvar,
+    capacity: usize,
+    overflow: Overflow,
+}
+
+/// A bounded queue in front of a sink, publishing its items in order from a background
+/// thread.
+///
+/// Dropping the queue waits for the item being published and drops those still queued;
+/// call `flush` first to publish them.
+pub struct SinkQueue<T> {
+    shared: Arc<QueueShared<T>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl<T: Send + 'static> SinkQueue<T> {
+    /// Returns a SinkQueue publishing to the sink given
+    /// * `sink` - The publisher of the items
+    /// * `capacity` - The number of items the sink may fall behind by
+    /// * `overflow` - What is given up when the sink falls further behind
+    pub fn new<S>(mut sink: S, capacity: usize, overflow: Overflow) -> SinkQueue<T>
+    where
+        S: Sink<T> + 'static,
+    {
+        if capacity == 0 {
+            println!("Capacity must be greater than 0");
+        }
+        let shared = Arc::new(QueueShared {
+            state: Mutex::new(QueueState {
+                items: VecDeque::new(),
+                stats: SinkStats::default(),
+                last_error: None,
+                busy: false,
+                running: true,
+            }),
+            changed: Condvar::new(),
+            capacity: capacity.max(1),
+            overflow,
+        });
+        let handle = {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || loop {
+                let item = {
+                    let mut state = shared.state.lock().unwrap();
+                    loop {
+                        if !state.running {
+                            return;
+                        }
+                        if let Some(item) = state.items.pop_front() {
+                            state.busy = true;
+                            break item;
+                        }
+                        state = shared.changed.wait(state).unwrap();
+                    }
+                };
+                // wake producers waiting for room
+                shared.changed.notify_all();
+                let result = sink.publish(&item).map_err(|e| e.to_string());
+                let mut state = shared.state.lock().unwrap();
+                state.busy = false;
+                match result {
+                    Ok(()) => state.stats.sent += 1,
+                    Err(e) => {
+                        state.stats.failed += 1;
+                        state.last_error = Some((Instant::now(), e));
+                    }
+                }
+                drop(state);
+                shared.changed.notify_all();
+            })
+        };
+        SinkQueue {
+            shared,
+            handle: Some(handle),
+        }
+    }
+
+    /// Queue an item, applying the overflow policy if the queue is full. Returns ``False``
+    /// if the item was dropped.
+    pub fn push(&self, item: T) -> bool {
+        let shared = &self.shared;
+        let mut state = shared.state.lock().unwrap();
+        if state.items.len() >= shared.capacity {
+            match shared.overflow {
+                Overflow::DropOldest => {
+                    state.items.pop_front();
+                    state.stats.dropped += 1;
+                }
+                Overflow::DropNewest => {
+                    state.stats.dropped += 1;
+                    return false;
+                }
+                Overflow::Block(timeout) => {
+                    let deadline = Instant::now().checked_add(timeout);
+                    while state.items.len() >= shared.capacity {
+                        let remaining = deadline
+                            .and_then(|deadline| deadline.checked_duration_since(Instant::now()));
+                        match remaining {
+                            Some(remaining) if !remaining.is_zero() => {
+                                state = shared.changed.wait_timeout(state, remaining).unwrap().0
+                            }
+                            _ => {
+                                state.stats.dropped += 1;
+                                return false;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        state.items.push_back(item);
+        drop(state);
+        shared.changed.notify_all();
+        true
+    }
+
+    /// Wait until every queued item has been published, or the timeout is reached.
+    /// Returns ``True`` if the queue is empty.
+    /// * `timeout` - How long to wait. If this is None, then wait indefinitely.
+    pub fn flush(&self, timeout: Option<Duration>) -> bool {
+        let deadline = timeout.and_then(|t| Instant::now().checked_add(t));
+        let mut state = self.shared.state.lock().unwrap();
+        while state.busy || !state.items.is_empty() {
+            state = match deadline {
+                None => self.shared.changed.wait(state).unwrap(),
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(remaining) if !remaining.is_zero() => {
+                        self.shared
+                            .changed
+                            .wait_timeout(state, remaining)
+                            .unwrap()
+                            .0
+                    }
+                    _ => return false,
+                },
+            };
+        }
+        true
+    }
+
+    /// The counters of published, failed and dropped items.
+    pub fn stats(&self) -> SinkStats {
+        let state = self.shared.state.lock().unwrap();
+        SinkStats {
+            queued: state.items.len(),
+            ..state.stats
+        }
+    }
+
+    /// Why the last item the sink failed to publish failed.
+    pub fn last_error(&self) -> Option<String> {
+        self.last_error_at().map(|(_, error)| error)
+    }
+
+    /// The last failure with when it happened, to find the latest of several queues.
+    pub(crate) fn last_error_at(&self) -> Option<(Instant, String)> {
+        self.shared.state.lock().unwrap().last_error.clone()
+    }
+}
+
+impl<T> Drop for SinkQueue<T> {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().running = false;
+        self.shared.changed.notify_all();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Appends every item to a file as a line of JSON. The file is opened on the first item.
+pub struct LogSink {
+    path: PathBuf,
+    file: Option<File>,
+}
+
+impl LogSink {
+    /// Returns a LogSink appending to the file given, which is created if needed
+    pub fn new<P: AsRef<Path>>(path: P) -> LogSink {
+        LogSink {
+            path: path.as_ref().to_path_buf(),
+            file: None,
+        }
+    }
+}
+
+impl<T: ToJson> Sink<T> for LogSink {
+    fn publish(&mut self, item: &T) -> Result<(), Box<dyn Error>> {
+        if self.file.is_none() {
+            self.file = Some(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)?,
+            );
+        }
+        if let Some(file) = self.file.as_mut() {
+            writeln!(file, "{}", item.to_json())?;
+        }
+        Ok(())
+    }
+}
+
+/// Publishes every item as JSON to an MQTT broker with `mosquitto_pub`.
+pub struct MqttSink {
+    host: String,
+    port: String,
+    topic: String,
+}
+
+impl MqttSink {
+    /// Returns an MqttSink publishing to the topic given
+    /// * `broker` - The host name of the broker, with `:port` unless it is 1883
+    /// * `topic` - The topic published to
+    pub fn new(broker: &str, topic: &str) -> MqttSink {
+        let (host, port) = match broker.rsplit_once(':') {
+            Some((host, port)) => (host.to_string(), port.to_string()),
+            None => (broker.to_string(), "1883".to_string()),
+        };
+        MqttSink {
+            host,
+            port,
+            topic: topic.to_string(),
+        }
+    }
+}
+
+impl<T: ToJson> Sink<T> for MqttSink {
+    fn publish(&mut self, item: &T) -> Result<(), Box<dyn Error>> {
+        let output = Command::new("mosquitto_pub")
+            .args(["-h", &self.host, "-p", &self.port, "-t", &self.topic, "-m"])
+            .arg(item.to_json().to_string())
+            .output()
+            .map_err(|e| format!("could not run mosquitto_pub: {}", e))?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            Err(format!("mosquitto_pub failed: {}", message).into())
+        }
+    }
+}
+
+/// POSTs every item as JSON to a URL. Unlike a `Webhook`, which retries on its own, a
+/// failed POST is reported to the queue of the sink and not sent again.
+pub struct HttpSink {
+    url: String,
+    headers: Vec<(String, String)>,
+}
+
+impl HttpSink {
+    /// Returns an HttpSink posting to the URL given
+    /// * `url` - The `http://` or `https://` URL the items are POSTed to
+    pub fn new(url: &str) -> HttpSink {
+        HttpSink {
+            url: url.to_string(),
+            headers: Vec::new(),
+        }
+    }
+
+    /// Add a header sent with every item, such as an `Authorization` token.
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        self.headers.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+        self.headers.push((name.to_string(), value.to_string()));
+    }
+}
+
+impl<T: ToJson> Sink<T> for HttpSink {
+    fn publish(&mut self, item: &T) -> Result<(), Box<dyn Error>> {
+        webhook::post_once(&self.url, &self.headers, &item.to_json().to_string())?;
+        Ok(())
+    }
+}
+
+/// Inserts every item as a row of an SQLite table with `sqlite3`. The table has the
+/// columns `time`, in seconds since the Unix epoch, and `data`, the item as JSON, which the
+/// JSON functions of SQLite can query. It is created on the first item.
+pub struct SqliteSink {
+    path: PathBuf,
+    table: String,
+    created: bool,
+}
+
+impl SqliteSink {
+    /// Returns an SqliteSink inserting into the table given
+    /// * `path` - The database file, which is created if needed
+    /// * `table` - The name of the table
+    pub fn new<P: AsRef<Path>>(path: P, table: &str) -> SqliteSink {
+        SqliteSink {
+            path: path.as_ref().to_path_buf(),
+            table: format!("\"{}\"", table.replace('"', "\"\"")),
+            created: false,
+        }
+    }
+}
+
+impl<T: ToJson> Sink<T> for SqliteSink {
+    fn publish(&mut self, item: &T) -> Result<(), Box<dyn Error>> {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let data = item.to_json().to_string().replace('\'', "''");
+        let mut sql = String::new();
+        if !self.created {
+            sql += &format!(
+                "CREATE TABLE IF NOT EXISTS {} (time REAL, data TEXT);",
+                self.table
+            );
+        }
+        sql += &format!("INSERT INTO {} VALUES ({}, '{}');", self.table, time, data);
+        let output = Command::new("sqlite3")
+            .arg(&self.path)
+            .arg(sql)
+            .output()
+            .map_err(|e| format!("could not run sqlite3: {}", e))?;
+        if output.status.success() {
+            self.created = true;
+            Ok(())
+        } else {
+            let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            Err(format!("sqlite3 failed: {}", message).into())
+        }
+    }
+}
+
+/// Writes the last sample of a pipeline to a file in the Prometheus text format, for the
+/// textfile collector of the node exporter. The file is replaced as a whole, so the
+/// collector never reads half of it.
+pub struct MetricsSink {
+    path: PathBuf,
+    partial: PathBuf,
+}
+
+impl MetricsSink {
+    /// Returns a MetricsSink writing to the file given
+    pub fn new<P: AsRef<Path>>(path: P) -> MetricsSink {
+        let path = path.as_ref().to_path_buf();
+        let mut partial = path.clone().into_os_string();
+        partial.push(".tmp");
+        MetricsSink {
+            path,
+            partial: PathBuf::from(partial),
+        }
+    }
+}
+
+impl Sink<Sample> for MetricsSink {
+    fn publish(&mut self, sample: &Sample) -> Result<(), Box<dyn Error>> {
+        let label = Json::String(sample.name.clone()).to_string();
+        let mut metrics = vec![("value", sample.value)];
+        if let Some(summary) = sample.summary {
+            metrics.push(("min", summary.min));
+            metrics.push(("max", summary.max));
+            metrics.push(("stddev", summary.stddev));
+        }
+        let mut text = String::new();
+        for (metric, value) in metrics {
+            text += &format!("# TYPE gpiozero_pipeline_{} gauge\n", metric);
+            text += &format!(
+                "gpiozero_pipeline_{}{{pipeline={}}} {}\n",
+                metric, label, value
+            );
+        }
+        fs::write(&self.partial, text)?;
+        fs::rename(&self.partial, &self.path)?;
+        Ok(())
+    }
+}
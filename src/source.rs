@@ -0,0 +1,198 @@
+//! Linking devices through streams of values
+//!
+//! Every device with a value can be a `ValueSource`: `values` turns a shared device into an
+//! endless stream of its readings, and any iterator of `f64`, such as those of the `tools`
+//! module or an adapter like `map`, is a source too. Output devices take a source with
+//! `set_source` and follow it from a background thread, reading a value every
+//! `source_delay`, so an LED mirrors a button without any thread in the application.
+//! Devices without `set_source` implement `ValueSink` and are fed by a `SourcePump`.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::*;
+//! use std::sync::{Arc, Mutex};
+//! use std::time::Duration;
+//!
+//! let button = Arc::new(Mutex::new(Button::new(2)));
+//! let mut led = LED::new(17);
+//! led.set_source(values(&button));
+//!
+//! // a potentiometer dimming an LED the other way round
+//! let adc = MCP3008::new();
+//! let pot = Arc::new(Mutex::new(adc.channel(0)));
+//! let mut dim = PWMLED::new(18);
+//! dim.set_source_delay(Duration::from_millis(50));
+//! dim.set_source(values(&pot).map(|value| 1.0 - value));
+//!
+//! // a motor following the same knob, through a pump
+//! let motor = Arc::new(Mutex::new(Motor::new(23, 24)));
+//! let _pump = SourcePump::start(values(&pot).map(|value| value * 2.0 - 1.0), &motor);
+//! ```
+use crate::devices::Component;
+use crate::output_devices::sleep_while_running;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How long an output waits between two values of its source, unless set otherwise.
+pub const SOURCE_DELAY: Duration = Duration::from_millis(10);
+
+/// A stream of values for an output to follow. The stream ends when `next_value` returns
+/// None, leaving the output at the last value.
+///
+/// Every `Iterator` of `f64` that can be sent to another thread is a source.
+pub trait ValueSource: Send {
+    /// The next value, or None once the source is exhausted.
+    fn next_value(&mut self) -> Option<f64>;
+}
+
+impl<I: Iterator<Item = f64> + Send> ValueSource for I {
+    fn next_value(&mut self) -> Option<f64> {
+        self.next()
+    }
+}
+
+/// A device that can follow a `ValueSource`, given its values one at a time by a
+/// `SourcePump`.
+pub trait ValueSink: Send {
+    /// Set the device to a value of the source. NaN values are never given.
+    fn write_value(&mut self, value: f64);
+}
+
+/// The endless stream of readings of a device, returned by `values`.
+pub struct Values {
+    read: Box<dyn FnMut() -> f64 + Send>,
+}
+
+impl Values {
+    /// Returns the stream of the readings of a function, e.g. one reading a device kept by
+    /// a background thread of its own
+    /// * `read` - Returns the current value, or NaN if it could not be read
+    pub fn from_fn<R>(read: R) -> Values
+    where
+        R: FnMut() -> f64 + Send + 'static,
+    {
+        Values {
+            read: Box::new(read),
+        }
+    }
+}
+
+impl Iterator for Values {
+    type Item = f64;
+
+    fn next(&mut self) -> Option<f64> {
+        Some((self.read)())
+    }
+}
+
+impl fmt::Debug for Values {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Values").finish()
+    }
+}
+
+/// Returns the endless stream of the values of a shared device: 1.0 or 0.0 for buttons
+/// and other devices that are on or off, the reading of analog inputs and sensors. The
+/// first value is taken from devices with several, and NaN from those with none.
+/// * `device` - The device, shared with the stream
+pub fn values<D: Component + 'static>(device: &Arc<Mutex<D>>) -> Values {
+    let device = Arc::clone(device);
+    Values::from_fn(move || {
+        device
+            .lock()
+            .unwrap()
+            .values()
+            .first()
+            .copied()
+            .unwrap_or(f64::NAN)
+    })
+}
+
+/// A background thread feeding the values of a source to a shared device, every
+/// `source_delay`, until the source is exhausted or the pump is dropped.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+/// use std::sync::{Arc, Mutex};
+///
+/// let servo = Arc::new(Mutex::new(Servo::new(12)));
+/// let sweep = (0..=20).cycle().map(|step| f64::from(step) / 10.0 - 1.0);
+/// let _pump = SourcePump::start(sweep, &servo);
+/// ```
+pub struct SourcePump {
+    delay: Arc<Mutex<Duration>>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl SourcePump {
+    /// Returns a running SourcePump reading a value every 10ms
+    /// * `source` - The values
+    /// * `sink` - The device following them, shared with the pump
+    pub fn start<S, K>(mut source: S, sink: &Arc<Mutex<K>>) -> SourcePump
+    where
+        S: ValueSource + 'static,
+        K: ValueSink + 'static,
+    {
+        let delay = Arc::new(Mutex::new(SOURCE_DELAY));
+        let running = Arc::new(AtomicBool::new(true));
+        let handle = {
+            let delay = Arc::clone(&delay);
+            let running = Arc::clone(&running);
+            let sink = Arc::clone(sink);
+            thread::spawn(move || {
+                while running.load(Ordering::SeqCst) {
+                    match source.next_value() {
+                        None => break,
+                        Some(value) if value.is_nan() => {}
+                        Some(value) => sink.lock().unwrap().write_value(value),
+                    }
+                    let delay = *delay.lock().unwrap();
+                    sleep_while_running(&running, delay);
+                }
+                running.store(false, Ordering::SeqCst);
+            })
+        };
+        SourcePump {
+            delay,
+            running,
+            handle: Some(handle),
+        }
+    }
+
+    /// How long the pump waits between two values.
+    pub fn source_delay(&self) -> Duration {
+        *self.delay.lock().unwrap()
+    }
+
+    /// Set how long the pump waits between two values, 10ms by default.
+    pub fn set_source_delay(&mut self, delay: Duration) {
+        *self.delay.lock().unwrap() = delay
+    }
+
+    /// Returns ``True`` until the source is exhausted or the pump is stopped.
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Stop feeding the device, leaving it at the last value.
+    pub fn stop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            handle.join().expect("Could not join spawned thread");
+        }
+    }
+}
+
+impl Drop for SourcePump {
+    fn drop(&mut self) {
+        self.stop()
+    }
+}
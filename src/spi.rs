@@ -0,0 +1,196 @@
+//! Helpers for the SPI bus
+//!
+//! SPI has no addressing, so a device cannot be discovered the way an I2C device can.
+//! `probe` opens every enabled chip select and clocks a few bytes through it: a data line
+//! that reads back all zeros or all ones is most likely not connected to anything.
+//!
+//! `SPIDevice` is the base for drivers of chips the crate does not know about: it clocks
+//! transfers and register accesses with the retries and error counters of a `BusLink`,
+//! and runs transactions without other devices on the bus getting in between.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::spi;
+//!
+//! for found in spi::probe() {
+//!     println!("{}: {}", found.path, if found.responds { "responding" } else { "silent" });
+//! }
+//! ```
+use crate::bus::{BusLink, BusStats, RetryPolicy, SharedSpi};
+use rppal::spi::{Bus, Error, Mode, SlaveSelect, Spi};
+use std::path::Path;
+
+/// The result of probing one chip select.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProbeResult {
+    /// The device node, such as `/dev/spidev0.0`
+    pub path: String,
+    /// The bus
+    pub bus: Bus,
+    /// The chip select
+    pub slave_select: SlaveSelect,
+    /// Returns ``True`` if the data line carried anything other than all zeros or all ones
+    pub responds: bool,
+}
+
+/// Probe every chip select with a device node.
+///
+/// The probe sends an MCP3008 style start bit followed by zeros, which most devices ignore
+/// or answer harmlessly.
+pub fn probe() -> Vec<ProbeResult> {
+    let buses = [Bus::Spi0, Bus::Spi1, Bus::Spi2];
+    let selects = [SlaveSelect::Ss0, SlaveSelect::Ss1, SlaveSelect::Ss2];
+    let mut found = Vec::new();
+    for bus in buses.iter() {
+        for slave_select in selects.iter() {
+            let path = format!("/dev/spidev{}.{}", *bus as u8, *slave_select as u8);
+            if !Path::new(&path).exists() {
+                continue;
+            }
+            let responds = match Spi::new(*bus, *slave_select, 100_000, Mode::Mode0) {
+                Ok(spi) => {
+                    let write = [0x01, 0x80, 0x00, 0x00];
+                    let mut read = [0u8; 4];
+                    spi.transfer(&mut read, &write).is_ok()
+                        && read.iter().any(|&b| b != 0x00 && b != 0xff)
+                }
+                Err(_) => false,
+            };
+            found.push(ProbeResult {
+                path,
+                bus: *bus,
+                slave_select: *slave_select,
+                responds,
+            });
+        }
+    }
+    found
+}
+
+/// A device on one chip select of an SPI bus, the base of drivers for chips the crate does
+/// not know about.
+///
+/// Every transfer runs through a `BusLink`, so transient errors are retried, and holds the
+/// lock of the bus, shared with every other device on it. The register helpers follow the
+/// common convention of a first byte holding the register, with bit 7 set to read; chips
+/// that differ can change the read flag, or use `transfer`.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::spi::SPIDevice;
+/// use rppal::spi::{Bus, Mode, SlaveSelect};
+///
+/// // an ADXL345 accelerometer, which also sets bit 6 to read several registers
+/// let mut accelerometer = SPIDevice::new(Bus::Spi0, SlaveSelect::Ss1, 1_000_000, Mode::Mode3);
+/// accelerometer.set_read_flag(0xc0);
+/// accelerometer.write_register(0x2d, 0x08).unwrap();
+/// let mut axes = [0u8; 6];
+/// accelerometer.read_registers(0x32, &mut axes).unwrap();
+/// ```
+pub struct SPIDevice {
+    spi: SharedSpi,
+    read_flag: u8,
+    link: BusLink,
+}
+
+impl SPIDevice {
+    /// Returns the device on the chip select given
+    /// * `bus` - The SPI bus, `Bus::Spi0` on most boards
+    /// * `slave_select` - The chip select the device is connected to
+    /// * `clock_speed` - The clock in Hz, within what the device accepts
+    /// * `mode` - The clock polarity and phase the device expects
+    pub fn new(bus: Bus, slave_select: SlaveSelect, clock_speed: u32, mode: Mode) -> SPIDevice {
+        match SharedSpi::open(bus, slave_select, clock_speed, mode) {
+            Err(e) => panic!("{:?}", e),
+            Ok(spi) => SPIDevice {
+                spi,
+                read_flag: 0x80,
+                link: BusLink::default(),
+            },
+        }
+    }
+
+    /// The bus.
+    pub fn bus(&self) -> Bus {
+        self.spi.bus()
+    }
+
+    /// The chip select.
+    pub fn slave_select(&self) -> SlaveSelect {
+        self.spi.slave_select()
+    }
+
+    /// The bits set in the register byte to read, 0x80 by default.
+    pub fn read_flag(&self) -> u8 {
+        self.read_flag
+    }
+
+    /// Set the bits set in the register byte to read; they are cleared to write.
+    pub fn set_read_flag(&mut self, flag: u8) {
+        self.read_flag = flag
+    }
+
+    /// Run transfers without other devices on the bus getting in between. The whole
+    /// transaction is run again if a transfer in it fails with a transient error.
+    /// * `transaction` - The transfers, given the SPI device
+    pub fn transaction<T, F>(&mut self, mut transaction: F) -> Result<T, Error>
+    where
+        F: FnMut(&mut Spi) -> Result<T, Error>,
+    {
+        let SPIDevice { spi, link, .. } = self;
+        link.run(|| transaction(&mut spi.lock()))
+    }
+
+    /// Clock bytes out while clocking as many in, with the chip selected throughout.
+    pub fn transfer(&mut self, write: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut read = vec![0u8; write.len()];
+        self.transaction(|spi| spi.transfer(&mut read, write).map(|_| ()))?;
+        Ok(read)
+    }
+
+    /// Clock bytes out, ignoring what is clocked in.
+    pub fn write(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.transaction(|spi| spi.write(bytes).map(|_| ()))
+    }
+
+    /// Read a register.
+    pub fn read_register(&mut self, register: u8) -> Result<u8, Error> {
+        let mut value = [0u8];
+        self.read_registers(register, &mut value)?;
+        Ok(value[0])
+    }
+
+    /// Write a register.
+    pub fn write_register(&mut self, register: u8, value: u8) -> Result<(), Error> {
+        self.write_registers(register, &[value])
+    }
+
+    /// Read consecutive registers from the one given.
+    pub fn read_registers(&mut self, register: u8, buffer: &mut [u8]) -> Result<(), Error> {
+        let mut write = vec![0u8; buffer.len() + 1];
+        write[0] = register | self.read_flag;
+        let read = self.transfer(&write)?;
+        buffer.copy_from_slice(&read[1..]);
+        Ok(())
+    }
+
+    /// Write consecutive registers from the one given.
+    pub fn write_registers(&mut self, register: u8, values: &[u8]) -> Result<(), Error> {
+        let mut bytes = Vec::with_capacity(values.len() + 1);
+        bytes.push(register & !self.read_flag);
+        bytes.extend_from_slice(values);
+        self.write(&bytes)
+    }
+
+    /// The error counters of the transfers.
+    pub fn bus_stats(&self) -> BusStats {
+        self.link.stats()
+    }
+
+    /// Change how failed transfers are retried.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.link.set_policy(policy)
+    }
+}
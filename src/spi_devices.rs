@@ -0,0 +1,942 @@
+//! Devices on the SPI bus, such as the MCP3xxx family of analog to digital converters
+//! and the MAX7219 LED display driver
+//!
+//! The Pi has no analog inputs, so potentiometers, light dependent resistors and other
+//! analog sensors are read through a converter. Each chip is opened once and hands out one
+//! `MCPChannel` per input, which implements `AnalogInputDevice`. Transfers are retried with
+//! a `BusLink`, so a loose wire shows up in `bus_stats` rather than as a wild reading.
+//!
+//! When a HAT occupies the SPI pins, the chips can be wired to any free GPIO pins instead
+//! and clocked in software with `SpiBus::Software`.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::*;
+//!
+//! let adc = MCP3008::new();
+//! let mut pot = adc.channel(0);
+//! pot.when_above(0.9, |value| println!("turned up to {:.2}", value));
+//! println!("{:.2} ({:.2}V)", pot.value(), pot.voltage());
+//!
+//! // a second converter on pins of its own
+//! let other = MCP3008::with_spi(SpiBus::Software {
+//!     clock: 21,
+//!     mosi: 20,
+//!     miso: Some(19),
+//!     select: 16,
+//! });
+//! println!("{:.2}", other.channel(0).value());
+//! ```
+use crate::boards::{char_segments, split_digits};
+use crate::bus::{BusLink, BusStats, RetryPolicy, SpiBus, SpiPort};
+use crate::devices::{AnalogInputDevice, AnalogWatch};
+use rppal::spi::{Bus, Mode, SlaveSelect};
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// The SPI clock used by `new`, within the limits of every chip at 3.3V.
+const MCP_CLOCK_SPEED: u32 = 1_000_000;
+
+/// The reference voltage of a chip powered from the 3.3V pin of the Pi.
+const MCP_MAX_VOLTAGE: f64 = 3.3;
+
+/// The chips of the MCP3xxx family and how each is asked for a reading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MCPChip {
+    MCP3002,
+    MCP3004,
+    MCP3008,
+    MCP3202,
+    MCP3204,
+    MCP3208,
+}
+
+impl MCPChip {
+    fn name(self) -> &'static str {
+        match self {
+            MCPChip::MCP3002 => "MCP3002",
+            MCPChip::MCP3004 => "MCP3004",
+            MCPChip::MCP3008 => "MCP3008",
+            MCPChip::MCP3202 => "MCP3202",
+            MCPChip::MCP3204 => "MCP3204",
+            MCPChip::MCP3208 => "MCP3208",
+        }
+    }
+
+    fn channels(self) -> u8 {
+        match self {
+            MCPChip::MCP3002 | MCPChip::MCP3202 => 2,
+            MCPChip::MCP3004 | MCPChip::MCP3204 => 4,
+            MCPChip::MCP3008 | MCPChip::MCP3208 => 8,
+        }
+    }
+
+    fn bits(self) -> u8 {
+        match self {
+            MCPChip::MCP3002 | MCPChip::MCP3004 | MCPChip::MCP3008 => 10,
+            MCPChip::MCP3202 | MCPChip::MCP3204 | MCPChip::MCP3208 => 12,
+        }
+    }
+
+    /// The bytes clocked out to read a channel: a start bit, the single-ended or
+    /// differential bit and the channel, padded so the reading ends on a byte boundary.
+    fn command(self, channel: u8, differential: bool) -> [u8; 3] {
+        let single = u8::from(!differential);
+        match self {
+            // the 2 channel chips also take a bit asking for the reading MSB first
+            MCPChip::MCP3002 | MCPChip::MCP3202 => {
+                [0x01, single << 7 | (channel & 0x01) << 6 | 0x20, 0x00]
+            }
+            MCPChip::MCP3004 | MCPChip::MCP3008 => {
+                [0x01, single << 7 | (channel & 0x07) << 4, 0x00]
+            }
+            MCPChip::MCP3204 | MCPChip::MCP3208 => [
+                0x04 | single << 1 | (channel & 0x07) >> 2,
+                (channel & 0x03) << 6,
+                0x00,
+            ],
+        }
+    }
+
+    /// The reading in the bytes clocked in while sending `command`.
+    fn decode(self, read: [u8; 3]) -> u16 {
+        let (high, low) = (u16::from(read[1]), u16::from(read[2]));
+        match self {
+            MCPChip::MCP3002 => (high & 0x0f) << 6 | low >> 2,
+            MCPChip::MCP3004 | MCPChip::MCP3008 => (high & 0x03) << 8 | low,
+            MCPChip::MCP3202 | MCPChip::MCP3204 | MCPChip::MCP3208 => (high & 0x0f) << 8 | low,
+        }
+    }
+}
+
+/// The SPI device of a chip, shared by its channels.
+struct MCPBus {
+    spi: SpiPort,
+    link: BusLink,
+}
+
+/// One input of an MCP3xxx converter.
+///
+/// In single-ended mode the reading is the voltage of the input against ground. In
+/// differential mode it is the voltage of the input against the other input of its pair
+/// (0 and 1, 2 and 3, ...), and reads 0.0 when the other input is higher.
+pub struct MCPChannel {
+    chip: MCPChip,
+    bus: Arc<Mutex<MCPBus>>,
+    channel: u8,
+    differential: bool,
+    max_voltage: f64,
+    watch: AnalogWatch,
+}
+
+impl MCPChannel {
+    /// The input of the chip the channel reads.
+    pub fn channel(&self) -> u8 {
+        self.channel
+    }
+
+    /// Returns ``True`` if the channel reads against the other input of its pair.
+    pub fn is_differential(&self) -> bool {
+        self.differential
+    }
+
+    /// Read the channel, returning the count from the converter: 0 to 1023 for the 10 bit
+    /// chips and 0 to 4095 for the 12 bit ones.
+    pub fn read(&self) -> Result<u16, rppal::spi::Error> {
+        let write = self.chip.command(self.channel, self.differential);
+        let mut bus = self.bus.lock().unwrap();
+        let MCPBus { spi, link } = &mut *bus;
+        let mut read = [0u8; 3];
+        link.run(|| spi.transfer(&mut read, &write))?;
+        Ok(self.chip.decode(read))
+    }
+
+    /// Set the reference voltage of the chip, its VREF pin, which reads as full scale.
+    /// * `max_voltage` - The voltage in volts, 3.3 by default
+    pub fn set_max_voltage(&mut self, max_voltage: f64) {
+        if max_voltage <= 0.0 {
+            println!("Max voltage must be greater than 0");
+            return;
+        }
+        self.max_voltage = max_voltage
+    }
+
+    /// A copy of the channel for the thread of its watch to read.
+    fn reader(&self) -> impl Fn() -> f64 + Send + 'static {
+        let channel = MCPChannel {
+            chip: self.chip,
+            bus: Arc::clone(&self.bus),
+            channel: self.channel,
+            differential: self.differential,
+            max_voltage: self.max_voltage,
+            watch: AnalogWatch::default(),
+        };
+        move || channel.value()
+    }
+}
+
+impl AnalogInputDevice for MCPChannel {
+    fn bits(&self) -> u8 {
+        self.chip.bits()
+    }
+
+    fn raw_value(&self) -> Result<i32, Box<dyn Error + Send + Sync>> {
+        Ok(i32::from(self.read()?))
+    }
+
+    fn max_voltage(&self) -> f64 {
+        self.max_voltage
+    }
+
+    fn when_above<C>(&mut self, threshold: f64, callback: C)
+    where
+        C: FnMut(f64) + Send + 'static,
+    {
+        let read = self.reader();
+        self.watch.set(true, threshold, callback, read)
+    }
+
+    fn when_below<C>(&mut self, threshold: f64, callback: C)
+    where
+        C: FnMut(f64) + Send + 'static,
+    {
+        let read = self.reader();
+        self.watch.set(false, threshold, callback, read)
+    }
+
+    fn hysteresis(&self) -> f64 {
+        self.watch.hysteresis()
+    }
+
+    fn set_hysteresis(&mut self, hysteresis: f64) {
+        self.watch.set_hysteresis(hysteresis)
+    }
+}
+
+macro_rules! impl_mcp_adc {
+    ($($adc:ident),*) => {
+        $(
+            #[doc = concat!("Represents an ", stringify!($adc), " analog to digital converter on the SPI bus.")]
+            pub struct $adc {
+                bus: Arc<Mutex<MCPBus>>,
+            }
+
+            impl $adc {
+                #[doc = concat!("Returns an ", stringify!($adc), " on SPI0, chip select CE0, clocked at 1MHz")]
+                pub fn new() -> $adc {
+                    $adc::with_bus(Bus::Spi0, SlaveSelect::Ss0, MCP_CLOCK_SPEED)
+                }
+
+                #[doc = concat!("Returns an ", stringify!($adc), " on the bus and chip select given")]
+                /// * `bus` - The SPI bus, `Bus::Spi0` on the header pins 19, 21 and 23
+                /// * `slave_select` - The chip select the chip is wired to
+                /// * `clock_speed` - The SPI clock in Hz; lower it for long wires
+                pub fn with_bus(bus: Bus, slave_select: SlaveSelect, clock_speed: u32) -> $adc {
+                    $adc::with_spi(SpiBus::Hardware {
+                        bus,
+                        slave_select,
+                        clock_speed,
+                    })
+                }
+
+                #[doc = concat!("Returns an ", stringify!($adc), " on the SPI bus given, a hardware bus or any four free pins")]
+                /// * `spi` - How the chip is wired; a software bus needs a MISO pin
+                pub fn with_spi(spi: SpiBus) -> $adc {
+                    let spi = match SpiPort::open(spi, Mode::Mode0) {
+                        Err(e) => panic!("{:?}", e),
+                        Ok(spi) => spi,
+                    };
+                    if !spi.can_read() {
+                        panic!("{} needs a MISO pin to be read", MCPChip::$adc.name());
+                    }
+                    $adc {
+                            bus: Arc::new(Mutex::new(MCPBus {
+                                spi,
+                                link: BusLink::default(),
+                            })),
+                    }
+                }
+
+                /// The number of inputs of the chip.
+                pub fn channels(&self) -> u8 {
+                    MCPChip::$adc.channels()
+                }
+
+                /// Returns the input given, read against ground.
+                /// * `channel` - The input, from 0 to one less than `channels`
+                pub fn channel(&self, channel: u8) -> MCPChannel {
+                    self.open(channel, false)
+                }
+
+                /// Returns the input given, read against the other input of its pair.
+                /// * `channel` - The input, from 0 to one less than `channels`
+                pub fn differential_channel(&self, channel: u8) -> MCPChannel {
+                    self.open(channel, true)
+                }
+
+                fn open(&self, channel: u8, differential: bool) -> MCPChannel {
+                    let chip = MCPChip::$adc;
+                    if channel >= chip.channels() {
+                        panic!(
+                            "{} has channels 0 to {}, not {}",
+                            chip.name(),
+                            chip.channels() - 1,
+                            channel
+                        );
+                    }
+                    MCPChannel {
+                        chip,
+                        bus: Arc::clone(&self.bus),
+                        channel,
+                        differential,
+                        max_voltage: MCP_MAX_VOLTAGE,
+                        watch: AnalogWatch::default(),
+                    }
+                }
+
+                /// The error counters of the SPI transfers.
+                pub fn bus_stats(&self) -> BusStats {
+                    self.bus.lock().unwrap().link.stats()
+                }
+
+                /// Change how failed SPI transfers are retried.
+                pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+                    self.bus.lock().unwrap().link.set_policy(policy)
+                }
+            }
+
+            impl Default for $adc {
+                fn default() -> Self {
+                    $adc::new()
+                }
+            }
+        )*
+    };
+}
+
+impl_mcp_adc!(MCP3002, MCP3004, MCP3008, MCP3202, MCP3204, MCP3208);
+
+const MAX7219_CLOCK_SPEED: u32 = 1_000_000;
+const MAX7219_DIGIT0: u8 = 0x01;
+const MAX7219_DECODE_MODE: u8 = 0x09;
+const MAX7219_INTENSITY: u8 = 0x0a;
+const MAX7219_SCAN_LIMIT: u8 = 0x0b;
+const MAX7219_SHUTDOWN: u8 = 0x0c;
+const MAX7219_DISPLAY_TEST: u8 = 0x0f;
+
+/// The columns of a character in a 5x7 font, bit 0 at the top, for ASCII from space to `~`.
+/// Other characters are drawn as `?`.
+pub(crate) fn glyph(c: char) -> [u8; 5] {
+    const FONT: [[u8; 5]; 95] = [
+        [0x00, 0x00, 0x00, 0x00, 0x00],
+        [0x00, 0x00, 0x5f, 0x00, 0x00],
+        [0x00, 0x07, 0x00, 0x07, 0x00],
+        [0x14, 0x7f, 0x14, 0x7f, 0x14],
+        [0x24, 0x2a, 0x7f, 0x2a, 0x12],
+        [0x23, 0x13, 0x08, 0x64, 0x62],
+        [0x36, 0x49, 0x55, 0x22, 0x50],
+        [0x00, 0x05, 0x03, 0x00, 0x00],
+        [0x00, 0x1c, 0x22, 0x41, 0x00],
+        [0x00, 0x41, 0x22, 0x1c, 0x00],
+        [0x08, 0x2a, 0x1c, 0x2a, 0x08],
+        [0x08, 0x08, 0x3e, 0x08, 0x08],
+        [0x00, 0x50, 0x30, 0x00, 0x00],
+        [0x08, 0x08, 0x08, 0x08, 0x08],
+        [0x00, 0x60, 0x60, 0x00, 0x00],
+        [0x20, 0x10, 0x08, 0x04, 0x02],
+        [0x3e, 0x51, 0x49, 0x45, 0x3e],
+        [0x00, 0x42, 0x7f, 0x40, 0x00],
+        [0x42, 0x61, 0x51, 0x49, 0x46],
+        [0x21, 0x41, 0x45, 0x4b, 0x31],
+        [0x18, 0x14, 0x12, 0x7f, 0x10],
+        [0x27, 0x45, 0x45, 0x45, 0x39],
+        [0x3c, 0x4a, 0x49, 0x49, 0x30],
+        [0x01, 0x71, 0x09, 0x05, 0x03],
+        [0x36, 0x49, 0x49, 0x49, 0x36],
+        [0x06, 0x49, 0x49, 0x29, 0x1e],
+        [0x00, 0x36, 0x36, 0x00, 0x00],
+        [0x00, 0x56, 0x36, 0x00, 0x00],
+        [0x08, 0x14, 0x22, 0x41, 0x00],
+        [0x14, 0x14, 0x14, 0x14, 0x14],
+        [0x00, 0x41, 0x22, 0x14, 0x08],
+        [0x02, 0x01, 0x51, 0x09, 0x06],
+        [0x32, 0x49, 0x79, 0x41, 0x3e],
+        [0x7e, 0x11, 0x11, 0x11, 0x7e],
+        [0x7f, 0x49, 0x49, 0x49, 0x36],
+        [0x3e, 0x41, 0x41, 0x41, 0x22],
+        [0x7f, 0x41, 0x41, 0x22, 0x1c],
+        [0x7f, 0x49, 0x49, 0x49, 0x41],
+        [0x7f, 0x09, 0x09, 0x09, 0x01],
+        [0x3e, 0x41, 0x49, 0x49, 0x7a],
+        [0x7f, 0x08, 0x08, 0x08, 0x7f],
+        [0x00, 0x41, 0x7f, 0x41, 0x00],
+        [0x20, 0x40, 0x41, 0x3f, 0x01],
+        [0x7f, 0x08, 0x14, 0x22, 0x41],
+        [0x7f, 0x40, 0x40, 0x40, 0x40],
+        [0x7f, 0x02, 0x0c, 0x02, 0x7f],
+        [0x7f, 0x04, 0x08, 0x10, 0x7f],
+        [0x3e, 0x41, 0x41, 0x41, 0x3e],
+        [0x7f, 0x09, 0x09, 0x09, 0x06],
+        [0x3e, 0x41, 0x51, 0x21, 0x5e],
+        [0x7f, 0x09, 0x19, 0x29, 0x46],
+        [0x46, 0x49, 0x49, 0x49, 0x31],
+        [0x01, 0x01, 0x7f, 0x01, 0x01],
+        [0x3f, 0x40, 0x40, 0x40, 0x3f],
+        [0x1f, 0x20, 0x40, 0x20, 0x1f],
+        [0x3f, 0x40, 0x38, 0x40, 0x3f],
+        [0x63, 0x14, 0x08, 0x14, 0x63],
+        [0x07, 0x08, 0x70, 0x08, 0x07],
+        [0x61, 0x51, 0x49, 0x45, 0x43],
+        [0x00, 0x7f, 0x41, 0x41, 0x00],
+        [0x02, 0x04, 0x08, 0x10, 0x20],
+        [0x00, 0x41, 0x41, 0x7f, 0x00],
+        [0x04, 0x02, 0x01, 0x02, 0x04],
+        [0x40, 0x40, 0x40, 0x40, 0x40],
+        [0x00, 0x01, 0x02, 0x04, 0x00],
+        [0x20, 0x54, 0x54, 0x54, 0x78],
+        [0x7f, 0x48, 0x44, 0x44, 0x38],
+        [0x38, 0x44, 0x44, 0x44, 0x20],
+        [0x38, 0x44, 0x44, 0x48, 0x7f],
+        [0x38, 0x54, 0x54, 0x54, 0x18],
+        [0x08, 0x7e, 0x09, 0x01, 0x02],
+        [0x0c, 0x52, 0x52, 0x52, 0x3e],
+        [0x7f, 0x08, 0x04, 0x04, 0x78],
+        [0x00, 0x44, 0x7d, 0x40, 0x00],
+        [0x20, 0x40, 0x44, 0x3d, 0x00],
+        [0x7f, 0x10, 0x28, 0x44, 0x00],
+        [0x00, 0x41, 0x7f, 0x40, 0x00],
+        [0x7c, 0x04, 0x18, 0x04, 0x78],
+        [0x7c, 0x08, 0x04, 0x04, 0x78],
+        [0x38, 0x44, 0x44, 0x44, 0x38],
+        [0x7c, 0x14, 0x14, 0x14, 0x08],
+        [0x08, 0x14, 0x14, 0x18, 0x7c],
+        [0x7c, 0x08, 0x04, 0x04, 0x08],
+        [0x48, 0x54, 0x54, 0x54, 0x20],
+        [0x04, 0x3f, 0x44, 0x40, 0x20],
+        [0x3c, 0x40, 0x40, 0x20, 0x7c],
+        [0x1c, 0x20, 0x40, 0x20, 0x1c],
+        [0x3c, 0x40, 0x30, 0x40, 0x3c],
+        [0x44, 0x28, 0x10, 0x28, 0x44],
+        [0x0c, 0x50, 0x50, 0x50, 0x3c],
+        [0x44, 0x64, 0x54, 0x4c, 0x44],
+        [0x00, 0x08, 0x36, 0x41, 0x00],
+        [0x00, 0x00, 0x7f, 0x00, 0x00],
+        [0x00, 0x41, 0x36, 0x08, 0x00],
+        [0x08, 0x04, 0x08, 0x10, 0x08],
+    ];
+    match c {
+        ' '..='~' => FONT[c as usize - 0x20],
+        _ => FONT['?' as usize - 0x20],
+    }
+}
+
+/// The columns of `text` in the 5x7 font, with a blank column after each character.
+pub(crate) fn text_columns(text: &str) -> Vec<u8> {
+    let mut columns = Vec::with_capacity(text.len() * 6);
+    for c in text.chars() {
+        columns.extend_from_slice(&glyph(c));
+        columns.push(0);
+    }
+    columns
+}
+
+/// The SPI device of a chain of MAX7219s, the first chip being the one wired to the Pi.
+struct MAX7219Chain {
+    spi: SpiPort,
+    link: BusLink,
+    devices: usize,
+}
+
+impl MAX7219Chain {
+    fn open(spi: SpiBus, devices: usize) -> MAX7219Chain {
+        let devices = if devices == 0 {
+            println!("A chain needs at least one MAX7219");
+            1
+        } else {
+            devices
+        };
+        let mut chain = match SpiPort::open(spi, Mode::Mode0) {
+            Err(e) => panic!("{:?}", e),
+            Ok(spi) => MAX7219Chain {
+                spi,
+                link: BusLink::default(),
+                devices,
+            },
+        };
+        let setup = [
+            (MAX7219_DISPLAY_TEST, 0),
+            (MAX7219_SCAN_LIMIT, 7),
+            (MAX7219_DECODE_MODE, 0),
+            (MAX7219_INTENSITY, 7),
+        ];
+        for &(register, value) in setup.iter() {
+            chain.write_all(register, value);
+        }
+        for digit in 0..8 {
+            chain.write_all(MAX7219_DIGIT0 + digit, 0);
+        }
+        chain.write_all(MAX7219_SHUTDOWN, 1);
+        chain
+    }
+
+    /// Write a register of every chip in one transfer, `values[0]` to the first chip. The
+    /// first word clocked out ends up in the last chip of the chain.
+    fn write(&mut self, register: u8, values: &[u8]) {
+        let words: Vec<u8> = values
+            .iter()
+            .rev()
+            .flat_map(|&value| [register, value])
+            .collect();
+        let MAX7219Chain { spi, link, .. } = self;
+        if link.run(|| spi.write(&words)).is_err() {
+            println!("Could not write to the MAX7219");
+        }
+    }
+
+    fn write_all(&mut self, register: u8, value: u8) {
+        let values = vec![value; self.devices];
+        self.write(register, &values);
+    }
+
+    fn set_brightness(&mut self, brightness: f64) -> bool {
+        if !(0.0..=1.0).contains(&brightness) {
+            println!("Brightness must be between 0.0 and 1.0");
+            return false;
+        }
+        self.write_all(MAX7219_INTENSITY, (brightness * 15.0).round() as u8);
+        true
+    }
+}
+
+impl Drop for MAX7219Chain {
+    fn drop(&mut self) {
+        self.write_all(MAX7219_SHUTDOWN, 0);
+    }
+}
+
+/// The Code B font of a MAX7219 digit in decode mode.
+fn code_b(c: char) -> Option<u8> {
+    match c {
+        '0'..='9' => Some(c as u8 - b'0'),
+        '-' => Some(0x0a),
+        'E' | 'e' => Some(0x0b),
+        'H' | 'h' => Some(0x0c),
+        'L' | 'l' => Some(0x0d),
+        'P' | 'p' => Some(0x0e),
+        ' ' => Some(0x0f),
+        _ => None,
+    }
+}
+
+/// Represents seven-segment displays driven by a chain of MAX7219 or MAX7221 chips on the
+/// SPI bus, 8 digits per chip.
+///
+/// The first chip of the chain shows the leftmost digits, and on each chip digit 7 is the
+/// leftmost, as on the common 8-digit modules. Digits show the characters of
+/// `LEDCharDisplay`, drawn by the crate, unless `set_decode` hands them to the Code B
+/// font of the chip, which only has `0`-`9`, `-`, `E`, `H`, `L`, `P` and space.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut display = MAX7219Display::new(1);
+/// display.set_brightness(0.5);
+/// display.write_number(-12.345, 2);
+/// ```
+pub struct MAX7219Display {
+    chain: MAX7219Chain,
+    custom: Vec<(char, u8)>,
+    decode: Vec<bool>,
+    digits: Vec<(char, bool)>,
+    value: String,
+    brightness: f64,
+}
+
+impl MAX7219Display {
+    /// Returns the displays of a chain of chips on SPI0, chip select CE0, clocked at 1MHz
+    /// * `devices` - The number of chips in the chain
+    pub fn new(devices: usize) -> MAX7219Display {
+        MAX7219Display::with_bus(Bus::Spi0, SlaveSelect::Ss0, MAX7219_CLOCK_SPEED, devices)
+    }
+
+    /// Returns the displays of a chain of chips on the bus and chip select given
+    /// * `bus` - The SPI bus, `Bus::Spi0` on the header pins 19 and 23
+    /// * `slave_select` - The chip select LOAD (CS) of the chips is wired to
+    /// * `clock_speed` - The SPI clock in Hz, up to 10MHz; lower it for long chains
+    /// * `devices` - The number of chips in the chain
+    pub fn with_bus(
+        bus: Bus,
+        slave_select: SlaveSelect,
+        clock_speed: u32,
+        devices: usize,
+    ) -> MAX7219Display {
+        MAX7219Display::with_spi(
+            SpiBus::Hardware {
+                bus,
+                slave_select,
+                clock_speed,
+            },
+            devices,
+        )
+    }
+
+    /// Returns the displays of a chain of chips on the SPI bus given, a hardware bus or any
+    /// three free pins
+    /// * `spi` - How the chain is wired; it is only written, so a software bus needs no
+    ///   MISO pin
+    /// * `devices` - The number of chips in the chain
+    pub fn with_spi(spi: SpiBus, devices: usize) -> MAX7219Display {
+        let chain = MAX7219Chain::open(spi, devices);
+        let digits = chain.devices * 8;
+        MAX7219Display {
+            chain,
+            custom: Vec::new(),
+            decode: vec![false; digits],
+            digits: vec![(' ', false); digits],
+            value: String::new(),
+            brightness: 7.0 / 15.0,
+        }
+    }
+
+    /// Number of digits of the display.
+    pub fn len(&self) -> usize {
+        self.digits.len()
+    }
+
+    /// Returns ``True`` if the display has no digits.
+    pub fn is_empty(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    /// The text currently shown.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Show text on the display, aligned to the right. A `.` lights the decimal point
+    /// of the preceding character and does not take a digit of its own.
+    pub fn set_value(&mut self, text: &str) {
+        let digits = split_digits(text);
+        if digits.len() > self.len() {
+            println!("Text '{}' needs more than {} digits", text, self.len());
+            return;
+        }
+        let mut padded = vec![(' ', false); self.len() - digits.len()];
+        padded.extend(digits);
+        for (position, &(c, _)) in padded.iter().enumerate() {
+            let shown = if self.decode[position] {
+                code_b(c).is_some()
+            } else {
+                char_segments(&self.custom, c).is_some()
+            };
+            if !shown {
+                println!("Character '{}' cannot be shown on digit {}", c, position);
+                return;
+            }
+        }
+        self.digits = padded;
+        self.value = text.to_string();
+        self.refresh();
+    }
+
+    /// Show a number, aligned to the right.
+    /// * `value` - The number
+    /// * `decimals` - The number of digits after the decimal point
+    pub fn write_number(&mut self, value: f64, decimals: usize) {
+        self.set_value(&format!("{:.*}", decimals, value))
+    }
+
+    /// Define or replace the segments shown for a character on digits not in decode mode.
+    /// * `c` - The character
+    /// * `segments` - Bit 0 is segment a, bit 6 is segment g
+    pub fn define_char(&mut self, c: char, segments: u8) {
+        self.custom.retain(|(custom, _)| *custom != c);
+        self.custom.push((c, segments & 0x7f));
+        self.refresh();
+    }
+
+    /// Returns ``True`` if the chip draws the digit from its Code B font.
+    /// * `position` - The digit, 0 being the leftmost
+    pub fn decode(&self, position: usize) -> bool {
+        self.decode.get(position).copied().unwrap_or(false)
+    }
+
+    /// Choose whether the chip draws the digit from its Code B font. Blanks the digit if
+    /// the character it shows is not in the font it is switched to.
+    /// * `position` - The digit, 0 being the leftmost
+    /// * `decode` - ``True`` for the Code B font of the chip
+    pub fn set_decode(&mut self, position: usize, decode: bool) {
+        if position >= self.len() {
+            println!(
+                "Digit must be between 0 and {}",
+                self.len().saturating_sub(1)
+            );
+            return;
+        }
+        self.decode[position] = decode;
+        let mut modes = vec![0u8; self.chain.devices];
+        for (position, &decode) in self.decode.iter().enumerate() {
+            if decode {
+                let (device, digit) = self.locate(position);
+                modes[device] |= 1 << digit;
+            }
+        }
+        self.chain.write(MAX7219_DECODE_MODE, &modes);
+        self.refresh();
+    }
+
+    /// The brightness of the display, between 0.0 and 1.0.
+    pub fn brightness(&self) -> f64 {
+        self.brightness
+    }
+
+    /// Set the brightness of the display, in 16 steps. 0.0 is dim rather than off.
+    /// * `brightness` - Between 0.0 and 1.0
+    pub fn set_brightness(&mut self, brightness: f64) {
+        if self.chain.set_brightness(brightness) {
+            self.brightness = brightness;
+        }
+    }
+
+    /// Blank the display.
+    pub fn off(&mut self) {
+        self.digits = vec![(' ', false); self.len()];
+        self.value = String::new();
+        self.refresh();
+    }
+
+    /// The chip and the digit register of a position, 0 being the leftmost.
+    fn locate(&self, position: usize) -> (usize, u8) {
+        (position / 8, 7 - (position % 8) as u8)
+    }
+
+    fn refresh(&mut self) {
+        for digit in 0..8u8 {
+            let mut values = vec![0u8; self.chain.devices];
+            for (device, value) in values.iter_mut().enumerate() {
+                let position = device * 8 + usize::from(7 - digit);
+                let (c, dot) = self.digits[position];
+                let dot = if dot { 0x80 } else { 0 };
+                *value = if self.decode[position] {
+                    code_b(c).unwrap_or(0x0f) | dot
+                } else {
+                    let segments = char_segments(&self.custom, c).unwrap_or(0);
+                    // the chip wants segment a in bit 6 down to g in bit 0
+                    (0..7)
+                        .filter(|bit| segments & (1 << bit) != 0)
+                        .fold(dot, |value, bit| value | 0x40 >> bit)
+                };
+            }
+            self.chain.write(MAX7219_DIGIT0 + digit, &values);
+        }
+    }
+
+    /// The error counters of the SPI transfers.
+    pub fn bus_stats(&self) -> BusStats {
+        self.chain.link.stats()
+    }
+
+    /// Change how failed SPI transfers are retried.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.chain.link.set_policy(policy)
+    }
+}
+
+/// Represents 8x8 LED matrices driven by a chain of MAX7219 or MAX7221 chips on the SPI bus,
+/// forming one display 8 pixels high and 8 pixels wide per chip.
+///
+/// Drawing changes a framebuffer; `show` sends it to the chips. The first chip of the
+/// chain shows the leftmost 8 columns, and `(0, 0)` is the top left pixel. Modules that
+/// wire their matrix the other way round can be fixed with `set_flipped`.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut matrix = MAX7219Matrix::new(4);
+/// matrix.set_pixel(0, 0, true);
+/// matrix.show();
+/// matrix.scroll_text("Hello", 0.05);
+/// ```
+pub struct MAX7219Matrix {
+    chain: MAX7219Chain,
+    // one byte per column, bit 0 at the top
+    columns: Vec<u8>,
+    flipped: (bool, bool),
+    brightness: f64,
+}
+
+impl MAX7219Matrix {
+    /// Returns the matrices of a chain of chips on SPI0, chip select CE0, clocked at 1MHz
+    /// * `devices` - The number of chips in the chain
+    pub fn new(devices: usize) -> MAX7219Matrix {
+        MAX7219Matrix::with_bus(Bus::Spi0, SlaveSelect::Ss0, MAX7219_CLOCK_SPEED, devices)
+    }
+
+    /// Returns the matrices of a chain of chips on the bus and chip select given
+    /// * `bus` - The SPI bus, `Bus::Spi0` on the header pins 19 and 23
+    /// * `slave_select` - The chip select LOAD (CS) of the chips is wired to
+    /// * `clock_speed` - The SPI clock in Hz, up to 10MHz; lower it for long chains
+    /// * `devices` - The number of chips in the chain
+    pub fn with_bus(
+        bus: Bus,
+        slave_select: SlaveSelect,
+        clock_speed: u32,
+        devices: usize,
+    ) -> MAX7219Matrix {
+        MAX7219Matrix::with_spi(
+            SpiBus::Hardware {
+                bus,
+                slave_select,
+                clock_speed,
+            },
+            devices,
+        )
+    }
+
+    /// Returns the matrices of a chain of chips on the SPI bus given, a hardware bus or any
+    /// three free pins
+    /// * `spi` - How the chain is wired; it is only written, so a software bus needs no
+    ///   MISO pin
+    /// * `devices` - The number of chips in the chain
+    pub fn with_spi(spi: SpiBus, devices: usize) -> MAX7219Matrix {
+        let chain = MAX7219Chain::open(spi, devices);
+        let width = chain.devices * 8;
+        MAX7219Matrix {
+            chain,
+            columns: vec![0; width],
+            flipped: (false, false),
+            brightness: 7.0 / 15.0,
+        }
+    }
+
+    /// The width of the display in pixels.
+    pub fn width(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// The height of the display in pixels.
+    pub fn height(&self) -> usize {
+        8
+    }
+
+    /// Returns ``True`` if the pixel is lit in the framebuffer.
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        y < 8
+            && self
+                .columns
+                .get(x)
+                .is_some_and(|column| column & (1 << y) != 0)
+    }
+
+    /// Light or clear a pixel in the framebuffer. Pixels off the display are ignored.
+    /// * `x` - The column, 0 being the leftmost
+    /// * `y` - The row, 0 being the top
+    /// * `lit` - ``True`` to light the pixel
+    pub fn set_pixel(&mut self, x: usize, y: usize, lit: bool) {
+        if let (Some(column), true) = (self.columns.get_mut(x), y < 8) {
+            if lit {
+                *column |= 1 << y;
+            } else {
+                *column &= !(1 << y);
+            }
+        }
+    }
+
+    /// Clear the framebuffer.
+    pub fn clear(&mut self) {
+        self.columns.iter_mut().for_each(|column| *column = 0);
+    }
+
+    /// Draw text in the framebuffer in a 5x7 font, from the column given. Characters
+    /// outside printable ASCII are drawn as `?`.
+    /// * `x` - The column of the left edge of the text, which may be off the display
+    /// * `text` - The text
+    pub fn draw_text(&mut self, x: isize, text: &str) {
+        for (i, column) in text_columns(text).into_iter().enumerate() {
+            let at = x + i as isize;
+            if at >= 0 && (at as usize) < self.width() {
+                self.columns[at as usize] = column;
+            }
+        }
+    }
+
+    /// Send the framebuffer to the chips.
+    pub fn show(&mut self) {
+        for row in 0..8u8 {
+            let y = if self.flipped.1 { 7 - row } else { row };
+            let values: Vec<u8> = self
+                .columns
+                .chunks(8)
+                .map(|columns| {
+                    columns.iter().enumerate().fold(0u8, |value, (x, column)| {
+                        let bit = if self.flipped.0 { x } else { 7 - x };
+                        if column & (1 << y) != 0 {
+                            value | 1 << bit
+                        } else {
+                            value
+                        }
+                    })
+                })
+                .collect();
+            self.chain.write(MAX7219_DIGIT0 + row, &values);
+        }
+    }
+
+    /// Scroll text across the display from right to left in a 5x7 font, until it has
+    /// scrolled off. Blocks while scrolling, and leaves the display blank.
+    /// * `text` - The text
+    /// * `column_time` - Number of seconds before moving the text one column along
+    pub fn scroll_text(&mut self, text: &str, column_time: f64) {
+        if !column_time.is_finite() || column_time < 0.0 {
+            println!("Column time must be a positive number");
+            return;
+        }
+        let length = text_columns(text).len() as isize;
+        let width = self.width() as isize;
+        for x in (-length..=width).rev() {
+            self.clear();
+            self.draw_text(x, text);
+            self.show();
+            thread::sleep(Duration::from_secs_f64(column_time));
+        }
+    }
+
+    /// The brightness of the display, between 0.0 and 1.0.
+    pub fn brightness(&self) -> f64 {
+        self.brightness
+    }
+
+    /// Set the brightness of the display, in 16 steps. 0.0 is dim rather than off.
+    /// * `brightness` - Between 0.0 and 1.0
+    pub fn set_brightness(&mut self, brightness: f64) {
+        if self.chain.set_brightness(brightness) {
+            self.brightness = brightness;
+        }
+    }
+
+    /// Mirror every matrix, for modules wired the other way round, e.g. a generic module
+    /// mounted upside down.
+    /// * `horizontal` - ``True`` to swap the left and right of each matrix
+    /// * `vertical` - ``True`` to swap the top and bottom of each matrix
+    pub fn set_flipped(&mut self, horizontal: bool, vertical: bool) {
+        self.flipped = (horizontal, vertical);
+        self.show();
+    }
+
+    /// The error counters of the SPI transfers.
+    pub fn bus_stats(&self) -> BusStats {
+        self.chain.link.stats()
+    }
+
+    /// Change how failed SPI transfers are retried.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.chain.link.set_policy(policy)
+    }
+}
@@ -0,0 +1,839 @@
+//! Addressable LED strips, such as WS2812 (NeoPixel) and APA102 (DotStar) strips
+//!
+//! Every pixel of a strip has its own colour, held in a framebuffer: `set_pixel` and `fill`
+//! change the framebuffer, and `show` sends it to the strip in one go, so a frame is never
+//! seen half drawn. Brightness scales the colours as they are sent, leaving the framebuffer
+//! untouched. Strips implement `PixelStrip`, so effects can be written once for any of them.
+//!
+//! WS2812 pixels read a single data line whose pulses must be timed to a fraction of a
+//! microsecond, which a program cannot do reliably on Linux without root and DMA tricks.
+//! `NeoPixelStrip` sends the pulses as bits on the MOSI line of the SPI bus (GPIO10)
+//! instead, three SPI bits per pixel bit, clocked by the SPI controller. APA102 pixels
+//! have a clock line as well as a data line, so `DotStarStrip` needs no timing tricks and
+//! also runs on any two GPIO pins, as on the Pimoroni Blinkt!.
+//!
+//! An `Animator` plays effects such as a rainbow or a scanner on any strip, drawing frames
+//! in a background thread at a steady rate until it is stopped or given another effect.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::*;
+//!
+//! let mut strip = NeoPixelStrip::new(30);
+//! strip.set_brightness(0.2);
+//! strip.fill(Color::rgb(0, 0, 255));
+//! strip.set_pixel(0, Color::rgb(255, 0, 0));
+//! strip.show();
+//! ```
+use crate::bus::{BusLink, BusStats, RetryPolicy, SharedSpi};
+use crate::output_devices::sleep_while_running;
+use rppal::gpio::{Gpio, OutputPin};
+use rppal::spi::{Bus, Mode, SlaveSelect};
+use std::f64::consts::PI;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// The SPI clock giving 417ns per SPI bit, so three bits make one 1.25µs WS2812 bit.
+const WS2812_CLOCK_SPEED: u32 = 2_400_000;
+
+/// Zero bytes after a frame, holding the line low for 300µs so the pixels latch it, longer
+/// than the 280µs newer WS2812B pixels need.
+const WS2812_RESET_BYTES: usize = 90;
+
+/// The colour of a pixel, each component from 0 to 255. `white` is only sent to RGBW
+/// pixels.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Color {
+    /// The red component
+    pub red: u8,
+    /// The green component
+    pub green: u8,
+    /// The blue component
+    pub blue: u8,
+    /// The white component of RGBW pixels
+    pub white: u8,
+}
+
+impl Color {
+    /// Off.
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+
+    /// Returns a colour from its red, green and blue components
+    pub const fn rgb(red: u8, green: u8, blue: u8) -> Color {
+        Color {
+            red,
+            green,
+            blue,
+            white: 0,
+        }
+    }
+
+    /// Returns a colour for RGBW pixels, with the white LED of the pixel lit as given
+    pub const fn rgbw(red: u8, green: u8, blue: u8, white: u8) -> Color {
+        Color {
+            red,
+            green,
+            blue,
+            white,
+        }
+    }
+
+    /// Returns a colour from its hue, saturation and value.
+    /// * `hue` - The hue in degrees, 0.0 being red, 120.0 green and 240.0 blue
+    /// * `saturation` - Between 0.0 (grey) and 1.0 (fully saturated)
+    /// * `value` - Between 0.0 (black) and 1.0 (full brightness)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rust_gpiozero::Color;
+    ///
+    /// assert_eq!(Color::hsv(0.0, 1.0, 1.0), Color::rgb(255, 0, 0));
+    /// assert_eq!(Color::hsv(240.0, 1.0, 0.5), Color::rgb(0, 0, 128));
+    /// assert_eq!(Color::hsv(420.0, 0.0, 1.0), Color::rgb(255, 255, 255));
+    /// ```
+    pub fn hsv(hue: f64, saturation: f64, value: f64) -> Color {
+        let saturation = saturation.clamp(0.0, 1.0);
+        let value = value.clamp(0.0, 1.0);
+        let sector = hue.rem_euclid(360.0) / 60.0;
+        let chroma = value * saturation;
+        let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+        let (red, green, blue) = match sector as u8 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        let component = |c: f64| ((c + value - chroma) * 255.0).round() as u8;
+        Color::rgb(component(red), component(green), component(blue))
+    }
+
+    /// The colour with every component scaled, e.g. by a brightness between 0.0 and 1.0.
+    pub fn scaled(self, factor: f64) -> Color {
+        let scale = |c: u8| (f64::from(c) * factor.clamp(0.0, 1.0)).round() as u8;
+        Color::rgbw(
+            scale(self.red),
+            scale(self.green),
+            scale(self.blue),
+            scale(self.white),
+        )
+    }
+}
+
+impl From<(u8, u8, u8)> for Color {
+    fn from((red, green, blue): (u8, u8, u8)) -> Self {
+        Color::rgb(red, green, blue)
+    }
+}
+
+/// The order a strip expects the components of each pixel in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorOrder {
+    /// Red, green, blue
+    RGB,
+    /// Green, red, blue, as on most WS2812B strips
+    #[default]
+    GRB,
+    /// Blue, green, red, as on APA102 strips
+    BGR,
+    /// Red, green, blue, white, as on SK6812 RGBW strips
+    RGBW,
+    /// Green, red, blue, white
+    GRBW,
+}
+
+impl ColorOrder {
+    /// The components of a colour in this order.
+    pub(crate) fn components(self, color: Color) -> Vec<u8> {
+        let Color {
+            red,
+            green,
+            blue,
+            white,
+        } = color;
+        match self {
+            ColorOrder::RGB => vec![red, green, blue],
+            ColorOrder::GRB => vec![green, red, blue],
+            ColorOrder::BGR => vec![blue, green, red],
+            ColorOrder::RGBW => vec![red, green, blue, white],
+            ColorOrder::GRBW => vec![green, red, blue, white],
+        }
+    }
+}
+
+/// An addressable LED strip: a framebuffer of pixels sent to the strip by `show`.
+pub trait PixelStrip: Send {
+    /// Number of pixels of the strip.
+    fn len(&self) -> usize;
+
+    /// Returns ``True`` if the strip has no pixels.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The colour of a pixel in the framebuffer, or None if there is no such pixel.
+    fn pixel(&self, index: usize) -> Option<Color>;
+
+    /// Set the colour of a pixel in the framebuffer. Pixels off the strip are ignored.
+    fn set_pixel(&mut self, index: usize, color: Color);
+
+    /// Set every pixel of the framebuffer to one colour.
+    fn fill(&mut self, color: Color) {
+        for index in 0..self.len() {
+            self.set_pixel(index, color);
+        }
+    }
+
+    /// Set every pixel of the framebuffer to black.
+    fn clear(&mut self) {
+        self.fill(Color::BLACK)
+    }
+
+    /// Send the framebuffer to the strip.
+    fn show(&mut self);
+
+    /// The brightness the colours are scaled by when sent, between 0.0 and 1.0.
+    fn brightness(&self) -> f64;
+
+    /// Set the brightness the colours are scaled by when sent, taking effect on the next
+    /// `show`. Long strips at full brightness draw several amps.
+    /// * `brightness` - Between 0.0 and 1.0
+    fn set_brightness(&mut self, brightness: f64);
+}
+
+/// The SPI bits of WS2812 data: a pulse of 1 SPI bit for a 0 and 2 SPI bits for a 1, in
+/// periods of 3 SPI bits, followed by the low reset time.
+fn ws2812_encode(bytes: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(bytes.len() * 3 + WS2812_RESET_BYTES);
+    for &byte in bytes {
+        let bits = (0..8).rev().fold(0u32, |bits, bit| {
+            bits << 3 | if byte & (1 << bit) != 0 { 0b110 } else { 0b100 }
+        });
+        encoded.extend_from_slice(&bits.to_be_bytes()[1..]);
+    }
+    encoded.resize(encoded.len() + WS2812_RESET_BYTES, 0);
+    encoded
+}
+
+/// Represents a strip of WS2812, WS2812B or SK6812 (NeoPixel) pixels with its data line
+/// connected to MOSI (GPIO10) of the SPI bus, through a 3.3V to 5V level shifter on most
+/// strips.
+///
+/// No root access or DMA channel is needed, but SPI must be enabled, and on a Pi 3 the
+/// core clock must be fixed with `core_freq=250` in `config.txt` to keep the SPI clock
+/// steady. A frame is one SPI transfer of 9 bytes per RGB pixel, and the kernel limits
+/// transfers to 4096 bytes unless `spidev.bufsiz` is raised, so about 440 pixels.
+pub struct NeoPixelStrip {
+    spi: SharedSpi,
+    link: BusLink,
+    pixels: Vec<Color>,
+    order: ColorOrder,
+    brightness: f64,
+}
+
+impl NeoPixelStrip {
+    /// Returns a strip of GRB pixels on SPI0, all off
+    /// * `pixels` - The number of pixels of the strip
+    pub fn new(pixels: usize) -> NeoPixelStrip {
+        NeoPixelStrip::with_bus(Bus::Spi0, SlaveSelect::Ss0, pixels, ColorOrder::GRB)
+    }
+
+    /// Returns a strip on the SPI bus given, all off. The chip select is not used by the
+    /// strip, but must be one that is enabled.
+    /// * `bus` - The SPI bus, `Bus::Spi0` with its MOSI on GPIO10
+    /// * `slave_select` - An enabled chip select of the bus
+    /// * `pixels` - The number of pixels of the strip
+    /// * `order` - The order of the components of each pixel
+    pub fn with_bus(
+        bus: Bus,
+        slave_select: SlaveSelect,
+        pixels: usize,
+        order: ColorOrder,
+    ) -> NeoPixelStrip {
+        match SharedSpi::open(bus, slave_select, WS2812_CLOCK_SPEED, Mode::Mode0) {
+            Err(e) => panic!("{:?}", e),
+            Ok(spi) => {
+                let mut strip = NeoPixelStrip {
+                    spi,
+                    link: BusLink::default(),
+                    pixels: vec![Color::BLACK; pixels],
+                    order,
+                    brightness: 1.0,
+                };
+                strip.show();
+                strip
+            }
+        }
+    }
+
+    /// The order of the components of each pixel.
+    pub fn order(&self) -> ColorOrder {
+        self.order
+    }
+
+    /// Change the order of the components of each pixel, for strips whose colours come
+    /// out swapped.
+    pub fn set_order(&mut self, order: ColorOrder) {
+        self.order = order
+    }
+
+    /// The error counters of the SPI transfers.
+    pub fn bus_stats(&self) -> BusStats {
+        self.link.stats()
+    }
+
+    /// Change how failed SPI transfers are retried.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.link.set_policy(policy)
+    }
+}
+
+impl PixelStrip for NeoPixelStrip {
+    fn len(&self) -> usize {
+        self.pixels.len()
+    }
+
+    fn pixel(&self, index: usize) -> Option<Color> {
+        self.pixels.get(index).copied()
+    }
+
+    fn set_pixel(&mut self, index: usize, color: Color) {
+        if let Some(pixel) = self.pixels.get_mut(index) {
+            *pixel = color;
+        }
+    }
+
+    fn show(&mut self) {
+        let bytes: Vec<u8> = self
+            .pixels
+            .iter()
+            .flat_map(|&color| self.order.components(color.scaled(self.brightness)))
+            .collect();
+        let encoded = ws2812_encode(&bytes);
+        let NeoPixelStrip { spi, link, .. } = self;
+        if link.run(|| spi.lock().write(&encoded)).is_err() {
+            println!("Could not write to the NeoPixel strip");
+        }
+    }
+
+    fn brightness(&self) -> f64 {
+        self.brightness
+    }
+
+    fn set_brightness(&mut self, brightness: f64) {
+        if !(0.0..=1.0).contains(&brightness) {
+            println!("Brightness must be between 0.0 and 1.0");
+            return;
+        }
+        self.brightness = brightness
+    }
+}
+
+impl Drop for NeoPixelStrip {
+    fn drop(&mut self) {
+        self.clear();
+        self.show();
+    }
+}
+
+/// The SPI clock of `DotStarStrip::new`, well within what APA102 pixels accept over a few
+/// metres of strip.
+const APA102_CLOCK_SPEED: u32 = 4_000_000;
+
+/// How the data and clock lines of a DotStar strip are driven.
+enum DotStarBus {
+    Spi { spi: SharedSpi, link: BusLink },
+    Gpio { data: OutputPin, clock: OutputPin },
+}
+
+impl DotStarBus {
+    fn write(&mut self, bytes: &[u8]) -> bool {
+        match self {
+            DotStarBus::Spi { spi, link } => link.run(|| spi.lock().write(bytes)).is_ok(),
+            DotStarBus::Gpio { data, clock } => {
+                // the pixels read data on the rising edge of the clock, most significant bit first
+                for &byte in bytes {
+                    for bit in (0..8).rev() {
+                        if byte & (1 << bit) != 0 {
+                            data.set_high();
+                        } else {
+                            data.set_low();
+                        }
+                        clock.set_high();
+                        clock.set_low();
+                    }
+                }
+                true
+            }
+        }
+    }
+}
+
+/// Represents a strip of APA102 or SK9822 (DotStar) pixels, with its data and clock lines
+/// on MOSI (GPIO10) and SCLK (GPIO11) of the SPI bus, or on any two GPIO pins.
+///
+/// Besides its colour, every pixel has a brightness of its own in 32 steps, applied by the
+/// pixel itself, so dim colours keep their hue better than when scaled down. The
+/// brightness of the strip scales the colours of every pixel on top of it.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut blinkt = DotStarStrip::blinkt();
+/// blinkt.fill(Color::rgb(255, 64, 0));
+/// blinkt.set_pixel_brightness(0, 0.1);
+/// blinkt.show();
+/// ```
+pub struct DotStarStrip {
+    bus: DotStarBus,
+    pixels: Vec<(Color, f64)>,
+    order: ColorOrder,
+    brightness: f64,
+}
+
+impl DotStarStrip {
+    /// Returns a strip of BGR pixels on SPI0 clocked at 4MHz, all off
+    /// * `pixels` - The number of pixels of the strip
+    pub fn new(pixels: usize) -> DotStarStrip {
+        DotStarStrip::with_bus(
+            Bus::Spi0,
+            SlaveSelect::Ss0,
+            APA102_CLOCK_SPEED,
+            pixels,
+            ColorOrder::BGR,
+        )
+    }
+
+    /// Returns a strip on the SPI bus given, all off. The chip select is not used by the
+    /// strip, but must be one that is enabled.
+    /// * `bus` - The SPI bus, `Bus::Spi0` with MOSI on GPIO10 and SCLK on GPIO11
+    /// * `slave_select` - An enabled chip select of the bus
+    /// * `clock_speed` - The SPI clock in Hz; lower it for long strips
+    /// * `pixels` - The number of pixels of the strip
+    /// * `order` - The order of the components of each pixel, one of RGB, GRB and BGR
+    pub fn with_bus(
+        bus: Bus,
+        slave_select: SlaveSelect,
+        clock_speed: u32,
+        pixels: usize,
+        order: ColorOrder,
+    ) -> DotStarStrip {
+        match SharedSpi::open(bus, slave_select, clock_speed, Mode::Mode0) {
+            Err(e) => panic!("{:?}", e),
+            Ok(spi) => DotStarStrip::open(
+                DotStarBus::Spi {
+                    spi,
+                    link: BusLink::default(),
+                },
+                pixels,
+                order,
+            ),
+        }
+    }
+
+    /// Returns a strip with its data and clock lines on the GPIO pins given, all off
+    /// * `data` - The GPIO pin the data input of the strip is connected to
+    /// * `clock` - The GPIO pin the clock input of the strip is connected to
+    /// * `pixels` - The number of pixels of the strip
+    /// * `order` - The order of the components of each pixel, one of RGB, GRB and BGR
+    pub fn with_pins(data: u8, clock: u8, pixels: usize, order: ColorOrder) -> DotStarStrip {
+        let gpio = match Gpio::new() {
+            Err(e) => panic!("{:?}", e),
+            Ok(gpio) => gpio,
+        };
+        let pin = |pin: u8| match gpio.get(pin) {
+            Err(e) => panic!("{:?}", e),
+            Ok(pin) => {
+                let mut pin = pin.into_output();
+                pin.set_low();
+                pin
+            }
+        };
+        let bus = DotStarBus::Gpio {
+            data: pin(data),
+            clock: pin(clock),
+        };
+        DotStarStrip::open(bus, pixels, order)
+    }
+
+    /// Returns the 8 pixels of a Pimoroni Blinkt!, on GPIO23 (data) and GPIO24 (clock), all off
+    pub fn blinkt() -> DotStarStrip {
+        DotStarStrip::with_pins(23, 24, 8, ColorOrder::BGR)
+    }
+
+    fn open(bus: DotStarBus, pixels: usize, order: ColorOrder) -> DotStarStrip {
+        let order = match order {
+            ColorOrder::RGBW | ColorOrder::GRBW => {
+                println!("DotStar pixels have no white LED, using BGR");
+                ColorOrder::BGR
+            }
+            order => order,
+        };
+        let mut strip = DotStarStrip {
+            bus,
+            pixels: vec![(Color::BLACK, 1.0); pixels],
+            order,
+            brightness: 1.0,
+        };
+        strip.show();
+        strip
+    }
+
+    /// The brightness of a pixel, between 0.0 and 1.0, or None if there is no such pixel.
+    pub fn pixel_brightness(&self, index: usize) -> Option<f64> {
+        self.pixels.get(index).map(|&(_, brightness)| brightness)
+    }
+
+    /// Set the brightness of a pixel in the framebuffer, in 32 steps. Pixels off the strip
+    /// are ignored.
+    /// * `index` - The pixel, 0 being the first on the strip
+    /// * `brightness` - Between 0.0 and 1.0
+    pub fn set_pixel_brightness(&mut self, index: usize, brightness: f64) {
+        if !(0.0..=1.0).contains(&brightness) {
+            println!("Brightness must be between 0.0 and 1.0");
+            return;
+        }
+        if let Some(pixel) = self.pixels.get_mut(index) {
+            pixel.1 = brightness;
+        }
+    }
+
+    /// The order of the components of each pixel.
+    pub fn order(&self) -> ColorOrder {
+        self.order
+    }
+
+    /// The error counters of the SPI transfers, all zero for a strip on GPIO pins.
+    pub fn bus_stats(&self) -> BusStats {
+        match &self.bus {
+            DotStarBus::Spi { link, .. } => link.stats(),
+            DotStarBus::Gpio { .. } => BusStats::default(),
+        }
+    }
+
+    /// Change how failed SPI transfers are retried.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        if let DotStarBus::Spi { link, .. } = &mut self.bus {
+            link.set_policy(policy)
+        }
+    }
+}
+
+impl PixelStrip for DotStarStrip {
+    fn len(&self) -> usize {
+        self.pixels.len()
+    }
+
+    fn pixel(&self, index: usize) -> Option<Color> {
+        self.pixels.get(index).map(|&(color, _)| color)
+    }
+
+    fn set_pixel(&mut self, index: usize, color: Color) {
+        if let Some(pixel) = self.pixels.get_mut(index) {
+            pixel.0 = color;
+        }
+    }
+
+    // a start frame of 32 zero bits, 32 bits per pixel, and an end frame with a clock edge
+    // for every 2 pixels to push the last data through the strip
+    fn show(&mut self) {
+        let mut frame = vec![0u8; 4];
+        for &(color, brightness) in &self.pixels {
+            frame.push(0xe0 | (brightness * 31.0).round() as u8);
+            frame.extend(self.order.components(color.scaled(self.brightness)));
+        }
+        frame.resize(frame.len() + self.pixels.len().div_ceil(16).max(1), 0xff);
+        if !self.bus.write(&frame) {
+            println!("Could not write to the DotStar strip");
+        }
+    }
+
+    fn brightness(&self) -> f64 {
+        self.brightness
+    }
+
+    fn set_brightness(&mut self, brightness: f64) {
+        if !(0.0..=1.0).contains(&brightness) {
+            println!("Brightness must be between 0.0 and 1.0");
+            return;
+        }
+        self.brightness = brightness
+    }
+}
+
+impl Drop for DotStarStrip {
+    fn drop(&mut self) {
+        self.clear();
+        self.show();
+    }
+}
+
+/// An effect played by an `Animator`. Wipe, theater chase and scanner move one pixel per
+/// frame, so their speed follows the frame rate; rainbow and breathe run at their period.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Animation {
+    /// Light the pixels one by one from the start of the strip, then hold
+    Wipe {
+        /// The colour the strip is filled with
+        color: Color,
+    },
+    /// Light every third pixel, moving along the strip like a theatre marquee
+    TheaterChase {
+        /// The colour of the lit pixels
+        color: Color,
+    },
+    /// Spread the hues of the rainbow along the strip, rotating them
+    Rainbow {
+        /// The time for the colours to go once round the hue circle
+        period: Duration,
+    },
+    /// Fade the whole strip in and out
+    Breathe {
+        /// The colour at the top of each breath
+        color: Color,
+        /// The time of a breath, in and out
+        period: Duration,
+    },
+    /// Bounce a lit pixel from end to end, leaving a fading trail, like KITT
+    Scanner {
+        /// The colour of the lit pixel
+        color: Color,
+    },
+}
+
+impl Animation {
+    /// Draw frame `frame` of the effect, `elapsed` after it started, into the framebuffer.
+    fn render(self, strip: &mut dyn PixelStrip, frame: u64, elapsed: Duration) {
+        let len = strip.len();
+        match self {
+            Animation::Wipe { color } => {
+                for index in 0..len.min(frame as usize + 1) {
+                    strip.set_pixel(index, color);
+                }
+            }
+            Animation::TheaterChase { color } => {
+                for index in 0..len {
+                    let lit = index % 3 == (frame % 3) as usize;
+                    strip.set_pixel(index, if lit { color } else { Color::BLACK });
+                }
+            }
+            Animation::Rainbow { period } => {
+                let offset = 360.0 * elapsed.as_secs_f64() / period.as_secs_f64();
+                for index in 0..len {
+                    let hue = offset + 360.0 * index as f64 / len as f64;
+                    strip.set_pixel(index, Color::hsv(hue, 1.0, 1.0));
+                }
+            }
+            Animation::Breathe { color, period } => {
+                let phase = 2.0 * PI * elapsed.as_secs_f64() / period.as_secs_f64();
+                strip.fill(color.scaled((1.0 - phase.cos()) / 2.0));
+            }
+            Animation::Scanner { color } => {
+                for index in 0..len {
+                    if let Some(pixel) = strip.pixel(index) {
+                        strip.set_pixel(index, pixel.scaled(0.5));
+                    }
+                }
+                let bounce = 2 * len.saturating_sub(1).max(1) as u64;
+                let step = (frame % bounce) as usize;
+                strip.set_pixel(step.min(bounce as usize - step), color);
+            }
+        }
+    }
+}
+
+/// The effect an `Animator` plays, and a count of changes so the thread notices a new one.
+#[derive(Debug, Default)]
+struct Slot {
+    animation: Option<Animation>,
+    changes: u64,
+}
+
+/// Handle to the effect an `Animator` plays, which can be used to replace or stop it from
+/// any thread. The change takes effect at the next frame, never in the middle of one.
+#[derive(Debug, Clone)]
+pub struct AnimationHandle {
+    slot: Arc<Mutex<Slot>>,
+}
+
+impl AnimationHandle {
+    /// Play an effect from its start, replacing the current one.
+    pub fn play(&self, animation: Animation) {
+        let mut slot = self.slot.lock().unwrap();
+        slot.animation = Some(animation);
+        slot.changes += 1;
+    }
+
+    /// Stop the current effect and turn the strip off.
+    pub fn stop(&self) {
+        let mut slot = self.slot.lock().unwrap();
+        slot.animation = None;
+        slot.changes += 1;
+    }
+
+    /// The effect being played, or None if stopped.
+    pub fn animation(&self) -> Option<Animation> {
+        self.slot.lock().unwrap().animation
+    }
+
+    /// Returns ``True`` while an effect is being played.
+    pub fn is_playing(&self) -> bool {
+        self.animation().is_some()
+    }
+}
+
+/// Plays effects on a strip in a background thread, drawing and showing a frame at a
+/// steady rate. The thread stops when the animator is dropped, and the strip is turned off
+/// when it is dropped in turn.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+/// use std::thread::sleep;
+/// use std::time::Duration;
+///
+/// let mut animator = Animator::new(NeoPixelStrip::new(30));
+/// animator.set_fps(60.0);
+/// animator.play(Animation::Rainbow {
+///     period: Duration::from_secs(5),
+/// });
+/// sleep(Duration::from_secs(10));
+/// animator.play(Animation::Scanner {
+///     color: Color::rgb(255, 0, 0),
+/// });
+/// sleep(Duration::from_secs(10));
+/// ```
+pub struct Animator<S: PixelStrip + 'static> {
+    strip: Arc<Mutex<S>>,
+    handle: AnimationHandle,
+    fps: Arc<AtomicU64>,
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<S: PixelStrip + 'static> Animator<S> {
+    /// Returns an animator drawing 30 frames per second on the strip given, stopped
+    /// * `strip` - The strip to draw on
+    pub fn new(strip: S) -> Animator<S> {
+        let strip = Arc::new(Mutex::new(strip));
+        let handle = AnimationHandle {
+            slot: Arc::new(Mutex::new(Slot::default())),
+        };
+        let fps = Arc::new(AtomicU64::new(30f64.to_bits()));
+        let running = Arc::new(AtomicBool::new(true));
+        let thread = {
+            let strip = Arc::clone(&strip);
+            let slot = Arc::clone(&handle.slot);
+            let fps = Arc::clone(&fps);
+            let running = Arc::clone(&running);
+            thread::spawn(move || {
+                let mut changes = 0;
+                let mut start = Instant::now();
+                let mut frame = 0;
+                let mut next = Instant::now();
+                while running.load(Ordering::SeqCst) {
+                    // holding the slot while drawing keeps a replaced effect from drawing
+                    // after `play` or `stop` returns
+                    {
+                        let slot = slot.lock().unwrap();
+                        let mut strip = strip.lock().unwrap();
+                        if slot.changes != changes {
+                            changes = slot.changes;
+                            start = Instant::now();
+                            frame = 0;
+                            strip.clear();
+                            if slot.animation.is_none() {
+                                strip.show();
+                            }
+                        }
+                        if let Some(animation) = slot.animation {
+                            animation.render(&mut *strip, frame, start.elapsed());
+                            strip.show();
+                            frame += 1;
+                        }
+                    }
+                    next +=
+                        Duration::from_secs_f64(1.0 / f64::from_bits(fps.load(Ordering::SeqCst)));
+                    let now = Instant::now();
+                    if next < now {
+                        // a long strip or a slow bus made us miss frames; do not catch up
+                        next = now;
+                    }
+                    sleep_while_running(&running, next - now);
+                }
+            })
+        };
+        Animator {
+            strip,
+            handle,
+            fps,
+            running,
+            thread: Some(thread),
+        }
+    }
+
+    /// Returns a handle to replace or stop the effect from other threads.
+    pub fn handle(&self) -> AnimationHandle {
+        self.handle.clone()
+    }
+
+    /// Play an effect from its start, replacing the current one.
+    pub fn play(&mut self, animation: Animation) {
+        self.handle.play(animation)
+    }
+
+    /// Stop the current effect and turn the strip off.
+    pub fn stop(&mut self) {
+        self.handle.stop()
+    }
+
+    /// The effect being played, or None if stopped.
+    pub fn animation(&self) -> Option<Animation> {
+        self.handle.animation()
+    }
+
+    /// Returns ``True`` while an effect is being played.
+    pub fn is_playing(&self) -> bool {
+        self.handle.is_playing()
+    }
+
+    /// The number of frames drawn per second.
+    pub fn fps(&self) -> f64 {
+        f64::from_bits(self.fps.load(Ordering::SeqCst))
+    }
+
+    /// Set the number of frames drawn per second, taking effect from the next frame.
+    /// * `fps` - Between 1.0 and 1000.0; long strips take longer to send than a frame at
+    ///   high rates
+    pub fn set_fps(&mut self, fps: f64) {
+        if !(1.0..=1000.0).contains(&fps) {
+            println!("Frames per second must be between 1.0 and 1000.0");
+            return;
+        }
+        self.fps.store(fps.to_bits(), Ordering::SeqCst)
+    }
+
+    /// Lock the strip, e.g. to change its brightness. While an effect plays, anything drawn
+    /// is replaced by the next frame, and holding the lock delays it.
+    pub fn strip(&self) -> MutexGuard<'_, S> {
+        self.strip.lock().unwrap()
+    }
+}
+
+impl<S: PixelStrip + 'static> Drop for Animator<S> {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            thread.join().expect("Could not join spawned thread");
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! Musical tones for `TonalBuzzer`
+use std::fmt;
+
+/// A musical tone, stored as a frequency in Hz. A frequency of zero is a rest.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tone {
+    frequency: f64,
+}
+
+impl Tone {
+    /// Returns a Tone with the given frequency in Hz
+    pub fn from_frequency(frequency: f64) -> Tone {
+        Tone {
+            frequency: frequency.max(0.0),
+        }
+    }
+
+    /// Returns a Tone for the given MIDI note number (A4 is 69)
+    pub fn from_midi(note: u8) -> Tone {
+        Tone::from_frequency(440.0 * 2f64.powf((f64::from(note) - 69.0) / 12.0))
+    }
+
+    /// Returns a Tone for a note name such as `"A4"`, `"C#5"` or `"Bb3"`, or None if the name is not valid.
+    /// Middle C is `"C4"`.
+    pub fn from_note(note: &str) -> Option<Tone> {
+        let mut chars = note.chars();
+        let semitone: i32 = match chars.next()?.to_ascii_uppercase() {
+            'C' => 0,
+            'D' => 2,
+            'E' => 4,
+            'F' => 5,
+            'G' => 7,
+            'A' => 9,
+            'B' => 11,
+            _ => return None,
+        };
+        let rest = chars.as_str();
+        let (accidental, octave) = if let Some(octave) = rest.strip_prefix('#') {
+            (1, octave)
+        } else if let Some(octave) = rest.strip_prefix('b') {
+            (-1, octave)
+        } else {
+            (0, rest)
+        };
+        let octave: i32 = octave.parse().ok()?;
+        let midi = (octave + 1) * 12 + semitone + accidental;
+        if (0..=127).contains(&midi) {
+            Some(Tone::from_midi(midi as u8))
+        } else {
+            None
+        }
+    }
+
+    /// Returns a silent Tone, used for rests in a melody
+    pub fn rest() -> Tone {
+        Tone { frequency: 0.0 }
+    }
+
+    /// The frequency of the tone in Hz
+    pub fn frequency(&self) -> f64 {
+        self.frequency
+    }
+
+    /// Returns ``True`` if the tone is a rest
+    pub fn is_rest(&self) -> bool {
+        self.frequency <= 0.0
+    }
+
+    /// The nearest MIDI note number, or None for a rest
+    pub fn midi(&self) -> Option<u8> {
+        if self.is_rest() {
+            return None;
+        }
+        let note = (69.0 + 12.0 * (self.frequency / 440.0).log2()).round();
+        if (0.0..=127.0).contains(&note) {
+            Some(note as u8)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Tone {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const NAMES: [&str; 12] = [
+            "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+        ];
+        match self.midi() {
+            None if self.is_rest() => write!(f, "rest"),
+            None => write!(f, "{}Hz", self.frequency),
+            Some(note) => write!(
+                f,
+                "{}{}",
+                NAMES[usize::from(note % 12)],
+                i32::from(note / 12) - 1
+            ),
+        }
+    }
+}
@@ -0,0 +1,487 @@
+//! Tools for working with streams of device values
+//!
+//! A value stream is any iterator of readings, for example a closure polling a sensor
+//! wrapped in `std::iter::repeat_with`. The helpers here wrap such iterators to summarise
+//! or check the values flowing through them.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::tools;
+//! use rust_gpiozero::*;
+//! use std::thread;
+//! use std::time::Duration;
+//!
+//! let sensor = InputDevice::new(4);
+//! let readings = std::iter::repeat_with(|| {
+//!     thread::sleep(Duration::from_millis(100));
+//!     if sensor.value() { 1.0 } else { 0.0 }
+//! });
+//!
+//! // log one summary per minute instead of ten samples a second
+//! for summary in tools::stats(readings, Duration::from_secs(60)) {
+//!     println!("active {:.0}% of the time", summary.mean * 100.0);
+//! }
+//! ```
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Summary statistics for the samples in a window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    /// Number of samples
+    pub count: usize,
+    /// Smallest sample
+    pub min: f64,
+    /// Largest sample
+    pub max: f64,
+    /// Arithmetic mean of the samples
+    pub mean: f64,
+    /// Population standard deviation of the samples
+    pub stddev: f64,
+}
+
+impl Summary {
+    /// Returns the Summary of the given samples, or None if there are none.
+    pub fn of<'a, I: IntoIterator<Item = &'a f64>>(samples: I) -> Option<Summary> {
+        let mut count = 0;
+        let mut min = f64::INFINITY;
+        let mut max = f64::NEG_INFINITY;
+        let mut sum = 0.0;
+        let mut sum_sq = 0.0;
+        for &value in samples {
+            count += 1;
+            min = min.min(value);
+            max = max.max(value);
+            sum += value;
+            sum_sq += value * value;
+        }
+        if count == 0 {
+            return None;
+        }
+        let mean = sum / count as f64;
+        let variance = (sum_sq / count as f64 - mean * mean).max(0.0);
+        Some(Summary {
+            count,
+            min,
+            max,
+            mean,
+            stddev: variance.sqrt(),
+        })
+    }
+}
+
+/// Keeps the samples seen in the last `window` and summarises them on request.
+#[derive(Debug, Clone)]
+pub struct RollingStats {
+    window: Duration,
+    samples: VecDeque<(Instant, f64)>,
+}
+
+impl RollingStats {
+    /// Returns an empty RollingStats
+    /// * `window` - How long samples are kept
+    pub fn new(window: Duration) -> RollingStats {
+        RollingStats {
+            window,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Add a sample taken now.
+    pub fn push(&mut self, value: f64) {
+        self.push_at(Instant::now(), value)
+    }
+
+    /// Add a sample taken at the given time. Samples must be added in time order.
+    pub fn push_at(&mut self, at: Instant, value: f64) {
+        self.samples.push_back((at, value));
+        self.expire(at);
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while let Some(&(at, _)) = self.samples.front() {
+            if now.duration_since(at) > self.window {
+                self.samples.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Summary of the samples currently in the window, or None if the window is empty.
+    pub fn summary(&self) -> Option<Summary> {
+        Summary::of(self.samples.iter().map(|(_, value)| value))
+    }
+
+    /// The samples currently in the window, oldest first.
+    pub fn values(&self) -> impl Iterator<Item = f64> + '_ {
+        self.samples.iter().map(|&(_, value)| value)
+    }
+
+    /// Number of samples currently in the window.
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Returns ``True`` if the window holds no samples.
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Discard all samples.
+    pub fn clear(&mut self) {
+        self.samples.clear()
+    }
+}
+
+/// Iterator returned by `stats`, yielding one `Summary` per elapsed window.
+#[derive(Debug)]
+pub struct Stats<I> {
+    values: I,
+    window: Duration,
+}
+
+impl<I: Iterator<Item = f64>> Iterator for Stats<I> {
+    type Item = Summary;
+
+    fn next(&mut self) -> Option<Summary> {
+        let start = Instant::now();
+        let mut samples = Vec::new();
+        for value in self.values.by_ref() {
+            samples.push(value);
+            if start.elapsed() >= self.window {
+                break;
+            }
+        }
+        Summary::of(&samples)
+    }
+}
+
+/// Consume a value stream and emit a summary of each consecutive `window` of samples,
+/// e.g. one-minute aggregates of a sensor read ten times a second.
+/// The last, partial window is emitted when the stream ends.
+/// * `values` - The value stream
+/// * `window` - Length of each window
+pub fn stats<I: IntoIterator<Item = f64>>(values: I, window: Duration) -> Stats<I::IntoIter> {
+    Stats {
+        values: values.into_iter(),
+        window,
+    }
+}
+
+/// How an `AnomalyDetector` decides that a sample is out of the ordinary.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnomalyMethod {
+    /// Flag samples more than `threshold` standard deviations from the mean of the previous `window` samples.
+    ZScore { window: usize, threshold: f64 },
+    /// Flag samples outside `band` standard deviations of an exponentially weighted moving average
+    /// with smoothing factor `alpha` (0.0 to 1.0, higher reacts faster).
+    Ewma { alpha: f64, band: f64 },
+}
+
+/// A sample flagged by an `AnomalyDetector`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Anomaly {
+    /// The offending sample
+    pub value: f64,
+    /// The value the detector expected (the mean)
+    pub expected: f64,
+    /// How many standard deviations the sample was away from `expected`
+    pub score: f64,
+    /// The samples leading up to the anomaly, oldest first
+    pub context: Vec<f64>,
+}
+
+type AnomalyCallback = Box<dyn FnMut(&Anomaly) + Send>;
+
+/// Online anomaly detector for a value stream, for spotting stuck or failing sensors in long deployments.
+pub struct AnomalyDetector {
+    method: AnomalyMethod,
+    context_len: usize,
+    history: VecDeque<f64>,
+    mean: f64,
+    variance: f64,
+    seen: usize,
+    when_anomalous: Option<AnomalyCallback>,
+}
+
+impl AnomalyDetector {
+    /// Returns an AnomalyDetector using the z-score of each sample against the previous samples
+    /// * `window` - Number of previous samples to compare against
+    /// * `threshold` - Number of standard deviations considered anomalous, e.g. 3.0
+    pub fn zscore(window: usize, threshold: f64) -> AnomalyDetector {
+        AnomalyDetector::new(AnomalyMethod::ZScore {
+            window: window.max(2),
+            threshold,
+        })
+    }
+
+    /// Returns an AnomalyDetector using a band around an exponentially weighted moving average
+    /// * `alpha` - Smoothing factor between 0.0 and 1.0
+    /// * `band` - Width of the band in standard deviations, e.g. 3.0
+    pub fn ewma(alpha: f64, band: f64) -> AnomalyDetector {
+        AnomalyDetector::new(AnomalyMethod::Ewma {
+            alpha: alpha.clamp(f64::EPSILON, 1.0),
+            band,
+        })
+    }
+
+    /// Returns an AnomalyDetector using the given method
+    pub fn new(method: AnomalyMethod) -> AnomalyDetector {
+        let context_len = match method {
+            AnomalyMethod::ZScore { window, .. } => window,
+            AnomalyMethod::Ewma { .. } => 16,
+        };
+        AnomalyDetector {
+            method,
+            context_len,
+            history: VecDeque::new(),
+            mean: 0.0,
+            variance: 0.0,
+            seen: 0,
+            when_anomalous: None,
+        }
+    }
+
+    /// Set how many preceding samples are reported in `Anomaly::context`.
+    /// For the z-score method this never goes below the comparison window.
+    pub fn set_context_len(&mut self, n: usize) {
+        self.context_len = match self.method {
+            AnomalyMethod::ZScore { window, .. } => n.max(window),
+            AnomalyMethod::Ewma { .. } => n,
+        };
+    }
+
+    /// Set the function called with every anomalous sample.
+    pub fn when_anomalous<C>(&mut self, callback: C)
+    where
+        C: FnMut(&Anomaly) + Send + 'static,
+    {
+        self.when_anomalous = Some(Box::new(callback));
+    }
+
+    fn score(value: f64, mean: f64, stddev: f64) -> f64 {
+        let distance = (value - mean).abs();
+        if stddev > f64::EPSILON {
+            distance / stddev
+        } else if distance > f64::EPSILON {
+            f64::INFINITY
+        } else {
+            0.0
+        }
+    }
+
+    /// Feed one sample to the detector, returning the anomaly if the sample is one.
+    /// NaN samples are always anomalous.
+    pub fn check(&mut self, value: f64) -> Option<Anomaly> {
+        let flagged = match self.method {
+            AnomalyMethod::ZScore { window, threshold } => {
+                let previous = self
+                    .history
+                    .iter()
+                    .skip(self.history.len().saturating_sub(window));
+                match Summary::of(previous) {
+                    Some(summary) if summary.count >= 2 => {
+                        let score = AnomalyDetector::score(value, summary.mean, summary.stddev);
+                        Some((summary.mean, score, score > threshold))
+                    }
+                    _ => None,
+                }
+            }
+            AnomalyMethod::Ewma { alpha, band } => {
+                let warm = self.seen as f64 >= (1.0 / alpha).ceil();
+                let result = if warm {
+                    let score = AnomalyDetector::score(value, self.mean, self.variance.sqrt());
+                    Some((self.mean, score, score > band))
+                } else {
+                    None
+                };
+                if !value.is_nan() {
+                    if self.seen == 0 {
+                        self.mean = value;
+                    } else {
+                        let diff = value - self.mean;
+                        self.mean += alpha * diff;
+                        self.variance = (1.0 - alpha) * (self.variance + alpha * diff * diff);
+                    }
+                }
+                result
+            }
+        };
+
+        let context: Vec<f64> = self.history.iter().cloned().collect();
+        let anomaly = if value.is_nan() {
+            Some(Anomaly {
+                value,
+                expected: f64::NAN,
+                score: f64::INFINITY,
+                context,
+            })
+        } else {
+            match flagged {
+                Some((expected, score, true)) => Some(Anomaly {
+                    value,
+                    expected,
+                    score,
+                    context,
+                }),
+                _ => None,
+            }
+        };
+
+        if !value.is_nan() {
+            self.seen += 1;
+            self.history.push_back(value);
+            while self.history.len() > self.context_len {
+                self.history.pop_front();
+            }
+        }
+        if let (Some(anomaly), Some(callback)) = (&anomaly, self.when_anomalous.as_mut()) {
+            callback(anomaly);
+        }
+        anomaly
+    }
+
+    /// Wrap a value stream, passing every value through unchanged while checking it.
+    pub fn watch<I: IntoIterator<Item = f64>>(self, values: I) -> Watch<I::IntoIter> {
+        Watch {
+            values: values.into_iter(),
+            detector: self,
+        }
+    }
+}
+
+/// Iterator returned by `AnomalyDetector::watch`.
+pub struct Watch<I> {
+    values: I,
+    detector: AnomalyDetector,
+}
+
+impl<I: Iterator<Item = f64>> Iterator for Watch<I> {
+    type Item = f64;
+
+    fn next(&mut self) -> Option<f64> {
+        let value = self.values.next()?;
+        self.detector.check(value);
+        Some(value)
+    }
+}
+
+/// How `voted` combines readings from redundant sensors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VotingStrategy {
+    /// The median of the readings (the mean of the middle two for an even number of sensors)
+    Median,
+    /// The reading closest to the mean of all readings, so the result is always a real sensor's value
+    MidValueSelect,
+    /// For boolean sensors reporting 0.0 or 1.0: 1.0 if more than half of the readings are above 0.5, otherwise 0.0
+    Majority,
+}
+
+type DisagreementCallback = Box<dyn FnMut(&[f64]) + Send>;
+
+/// Iterator returned by `voted`.
+pub struct Voted {
+    sources: Vec<Box<dyn Iterator<Item = f64> + Send>>,
+    strategy: VotingStrategy,
+    tolerance: f64,
+    when_disagreement: Option<DisagreementCallback>,
+}
+
+impl Voted {
+    /// Set the largest spread between readings that still counts as agreement. Defaults to 0.0.
+    /// For `Majority` any split vote is a disagreement.
+    pub fn set_tolerance(mut self, tolerance: f64) -> Voted {
+        self.tolerance = tolerance.abs();
+        self
+    }
+
+    /// Set the function called with all readings whenever the sensors disagree.
+    pub fn when_disagreement<C>(mut self, callback: C) -> Voted
+    where
+        C: FnMut(&[f64]) + Send + 'static,
+    {
+        self.when_disagreement = Some(Box::new(callback));
+        self
+    }
+
+    fn vote(&self, readings: &[f64]) -> f64 {
+        let mut valid: Vec<f64> = readings.iter().cloned().filter(|v| v.is_finite()).collect();
+        if valid.is_empty() {
+            return f64::NAN;
+        }
+        valid.sort_by(f64::total_cmp);
+        let n = valid.len();
+        match self.strategy {
+            VotingStrategy::Median if n % 2 == 1 => valid[n / 2],
+            VotingStrategy::Median => (valid[n / 2 - 1] + valid[n / 2]) / 2.0,
+            VotingStrategy::MidValueSelect => {
+                let mean = valid.iter().sum::<f64>() / n as f64;
+                valid
+                    .iter()
+                    .cloned()
+                    .min_by(|a, b| (a - mean).abs().total_cmp(&(b - mean).abs()))
+                    .unwrap()
+            }
+            VotingStrategy::Majority => {
+                if valid.iter().filter(|&&v| v > 0.5).count() * 2 > n {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+
+    fn disagree(&self, readings: &[f64]) -> bool {
+        if readings.iter().any(|v| !v.is_finite()) {
+            return true;
+        }
+        match self.strategy {
+            VotingStrategy::Majority => {
+                let high = readings.iter().filter(|&&v| v > 0.5).count();
+                high != 0 && high != readings.len()
+            }
+            _ => {
+                let min = readings.iter().cloned().fold(f64::INFINITY, f64::min);
+                let max = readings.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+                max - min > self.tolerance
+            }
+        }
+    }
+}
+
+impl Iterator for Voted {
+    type Item = f64;
+
+    fn next(&mut self) -> Option<f64> {
+        let mut readings = Vec::with_capacity(self.sources.len());
+        for source in self.sources.iter_mut() {
+            readings.push(source.next()?);
+        }
+        if self.disagree(&readings) {
+            if let Some(callback) = self.when_disagreement.as_mut() {
+                callback(&readings);
+            }
+        }
+        Some(self.vote(&readings))
+    }
+}
+
+/// Combine the value streams of redundant sensors into one, so a single flaky sensor can't
+/// drive the result on its own. NaN and infinite readings are ignored by the vote and count
+/// as a disagreement. The stream ends when any of the sources ends.
+/// * `sources` - The value streams of the redundant sensors, usually 2 or 3
+/// * `strategy` - How readings are combined
+pub fn voted(
+    sources: Vec<Box<dyn Iterator<Item = f64> + Send>>,
+    strategy: VotingStrategy,
+) -> Voted {
+    Voted {
+        sources,
+        strategy,
+        tolerance: 0.0,
+        when_disagreement: None,
+    }
+}
@@ -0,0 +1,346 @@
+//! Apply state changes to several output devices as a single unit
+//!
+//! A `Transaction` collects the desired state of each output, checks every
+//! interlock and device limit before anything is touched, and then applies the
+//! changes in order. If a device rejects its change, the outputs that were already
+//! switched are returned to their previous state.
+//!
+//! Outputs on the Pi's own pins cannot fail, but those on a GPIO expander fail when the
+//! expander cannot be reached, and a `PumpControllerR` refuses to start while a fault is
+//! latched, the tank is low or its duty-cycle limit is used up. Those limits are checked
+//! before anything is switched.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::*;
+//!
+//! let mut heater = LED::new(17);
+//! let mut fan = LED::new(27);
+//!
+//! // never run the heater without the fan
+//! Transaction::new()
+//!     .set(&mut heater, true)
+//!     .set(&mut fan, true)
+//!     .interlock("heater needs fan", |states| !states[0] || states[1])
+//!     .apply()
+//!     .unwrap();
+//! ```
+use crate::composites::PumpControllerR;
+use crate::devices::{Component, OutputComponent};
+use crate::output_devices::{Buzzer, DigitalOutputDevice, OutputDevice, LED};
+use std::error::Error;
+use std::fmt;
+
+/// An output device that can be switched on or off as part of a `Transaction`.
+pub trait Switch {
+    /// Returns ``True`` if the device is currently active and ``False`` otherwise.
+    fn is_active(&self) -> bool;
+
+    /// Checks whether the device would accept the given state, without changing anything.
+    fn check(&self, _active: bool) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Drives the device to the given state.
+    fn set_active(&mut self, active: bool) -> Result<(), String>;
+}
+
+macro_rules! impl_switch {
+    ($($device:ty => $write:ident),*) => {
+        $(
+            impl Switch for $device {
+                fn is_active(&self) -> bool {
+                    <$device>::is_active(self)
+                }
+
+                fn set_active(&mut self, active: bool) -> Result<(), String> {
+                    self.$write(active).map_err(|e| e.to_string())
+                }
+            }
+        )*
+    };
+}
+
+impl_switch!(
+    OutputDevice => try_write_state,
+    DigitalOutputDevice => try_set_active,
+    LED => try_set_active,
+    Buzzer => try_set_active
+);
+
+impl Switch for Box<dyn OutputComponent> {
+    fn is_active(&self) -> bool {
+        Component::is_active(&**self)
+    }
+
+    fn set_active(&mut self, active: bool) -> Result<(), String> {
+        if active {
+            self.on()
+        } else {
+            self.off()
+        }
+        Ok(())
+    }
+}
+
+impl Switch for PumpControllerR {
+    fn is_active(&self) -> bool {
+        self.is_running()
+    }
+
+    fn check(&self, active: bool) -> Result<(), String> {
+        if active && !self.is_running() {
+            self.can_start().map_err(|e| e.to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn set_active(&mut self, active: bool) -> Result<(), String> {
+        if active {
+            self.start().map_err(|e| e.to_string())
+        } else {
+            self.stop();
+            Ok(())
+        }
+    }
+}
+
+/// Reasons a `Transaction` was not applied.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransactionError {
+    /// The named interlock did not allow the requested combination of states.
+    Interlock(String),
+    /// The device at `index` refused its new state; `rolled_back` reports whether
+    /// every already-applied change could be reverted.
+    Rejected {
+        index: usize,
+        reason: String,
+        rolled_back: bool,
+    },
+}
+
+impl fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransactionError::Interlock(name) => write!(f, "interlock '{}' tripped", name),
+            TransactionError::Rejected {
+                index,
+                reason,
+                rolled_back,
+            } => {
+                write!(f, "device {} rejected change: {}", index, reason)?;
+                if !rolled_back {
+                    write!(f, " (rollback incomplete)")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for TransactionError {}
+
+type Interlock<'a> = (String, Box<dyn Fn(&[bool]) -> bool + 'a>);
+
+/// A set of output changes that are validated together and applied with rollback.
+#[derive(Default)]
+pub struct Transaction<'a> {
+    changes: Vec<(&'a mut dyn Switch, bool)>,
+    interlocks: Vec<Interlock<'a>>,
+}
+
+impl<'a> Transaction<'a> {
+    /// Returns an empty Transaction
+    pub fn new() -> Transaction<'a> {
+        Transaction {
+            changes: Vec::new(),
+            interlocks: Vec::new(),
+        }
+    }
+
+    /// Request a new state for a device. Changes are applied in the order they were added.
+    /// * `device` - The output device to change
+    /// * `active` - ``True`` to turn the device on, ``False`` to turn it off
+    pub fn set(mut self, device: &'a mut dyn Switch, active: bool) -> Transaction<'a> {
+        self.changes.push((device, active));
+        self
+    }
+
+    /// Add a check that must hold for the requested states before anything is applied.
+    /// * `name` - Name reported when the interlock trips
+    /// * `check` - Receives the requested states in the order the devices were added
+    pub fn interlock<F>(mut self, name: &str, check: F) -> Transaction<'a>
+    where
+        F: Fn(&[bool]) -> bool + 'a,
+    {
+        self.interlocks.push((name.to_string(), Box::new(check)));
+        self
+    }
+
+    /// Validate every interlock and device limit, then apply all changes.
+    /// If a device rejects its change, the devices already changed are restored
+    /// to their previous state in reverse order.
+    pub fn apply(mut self) -> Result<(), TransactionError> {
+        let states: Vec<bool> = self.changes.iter().map(|(_, active)| *active).collect();
+        for (name, check) in &self.interlocks {
+            if !check(&states) {
+                return Err(TransactionError::Interlock(name.clone()));
+            }
+        }
+        for (index, (device, active)) in self.changes.iter().enumerate() {
+            if let Err(reason) = device.check(*active) {
+                return Err(TransactionError::Rejected {
+                    index,
+                    reason,
+                    rolled_back: true,
+                });
+            }
+        }
+
+        let mut applied: Vec<(usize, bool)> = Vec::new();
+        for index in 0..self.changes.len() {
+            let (device, active) = &mut self.changes[index];
+            let previous = device.is_active();
+            if let Err(reason) = device.set_active(*active) {
+                let mut rolled_back = true;
+                for &(done, previous) in applied.iter().rev() {
+                    if self.changes[done].0.set_active(previous).is_err() {
+                        rolled_back = false;
+                    }
+                }
+                return Err(TransactionError::Rejected {
+                    index,
+                    reason,
+                    rolled_back,
+                });
+            }
+            applied.push((index, previous));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A switch that remembers every state written to it and fails as configured.
+    struct FakeSwitch {
+        active: bool,
+        writes: Vec<bool>,
+        fail_on: Option<bool>,
+        refuse: bool,
+    }
+
+    impl FakeSwitch {
+        fn new(active: bool) -> FakeSwitch {
+            FakeSwitch {
+                active,
+                writes: Vec::new(),
+                fail_on: None,
+                refuse: false,
+            }
+        }
+    }
+
+    impl Switch for FakeSwitch {
+        fn is_active(&self) -> bool {
+            self.active
+        }
+
+        fn check(&self, _active: bool) -> Result<(), String> {
+            if self.refuse {
+                Err("limit reached".to_string())
+            } else {
+                Ok(())
+            }
+        }
+
+        fn set_active(&mut self, active: bool) -> Result<(), String> {
+            if self.fail_on == Some(active) {
+                return Err("bus error".to_string());
+            }
+            self.active = active;
+            self.writes.push(active);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failing_device_rolls_back_applied_changes() {
+        let mut first = FakeSwitch::new(false);
+        let mut second = FakeSwitch::new(true);
+        let mut failing = FakeSwitch::new(false);
+        failing.fail_on = Some(true);
+
+        let result = Transaction::new()
+            .set(&mut first, true)
+            .set(&mut second, false)
+            .set(&mut failing, true)
+            .apply();
+
+        assert_eq!(
+            result,
+            Err(TransactionError::Rejected {
+                index: 2,
+                reason: "bus error".to_string(),
+                rolled_back: true,
+            })
+        );
+        assert_eq!(first.writes, vec![true, false]);
+        assert_eq!(second.writes, vec![false, true]);
+        assert!(!first.active && second.active && !failing.active);
+    }
+
+    #[test]
+    fn failed_rollback_is_reported() {
+        let mut stuck = FakeSwitch::new(false);
+        stuck.fail_on = Some(false);
+        let mut failing = FakeSwitch::new(false);
+        failing.fail_on = Some(true);
+
+        let result = Transaction::new()
+            .set(&mut stuck, true)
+            .set(&mut failing, true)
+            .apply();
+
+        assert_eq!(
+            result,
+            Err(TransactionError::Rejected {
+                index: 1,
+                reason: "bus error".to_string(),
+                rolled_back: false,
+            })
+        );
+        assert!(stuck.active);
+    }
+
+    #[test]
+    fn limits_and_interlocks_are_checked_before_anything_changes() {
+        let mut first = FakeSwitch::new(false);
+        let mut limited = FakeSwitch::new(false);
+        limited.refuse = true;
+        let result = Transaction::new()
+            .set(&mut first, true)
+            .set(&mut limited, true)
+            .apply();
+        assert!(matches!(
+            result,
+            Err(TransactionError::Rejected { index: 1, .. })
+        ));
+
+        let mut second = FakeSwitch::new(false);
+        let result = Transaction::new()
+            .set(&mut first, true)
+            .set(&mut second, true)
+            .interlock("one at a time", |states| !(states[0] && states[1]))
+            .apply();
+        assert_eq!(
+            result,
+            Err(TransactionError::Interlock("one at a time".to_string()))
+        );
+        assert!(first.writes.is_empty() && second.writes.is_empty());
+    }
+}
@@ -0,0 +1,957 @@
+//! The device tree of an installation
+//!
+//! A `DeviceTree` is built from a `Config`. Before any hardware is touched the
+//! configuration can be checked with `DeviceTree::validate`, which reports pin
+//! conflicts, functions the board cannot provide and missing kernel interfaces,
+//! so that deployment scripts can fail fast.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::*;
+//!
+//! let config = Config::load("/etc/greenhouse.conf").unwrap();
+//! if let Err(errors) = DeviceTree::validate(&config) {
+//!     for error in errors {
+//!         eprintln!("{}", error);
+//!     }
+//!     std::process::exit(1);
+//! }
+//! ```
+use crate::boards::{Energenie, LEDBarGraph, LEDBoard};
+use crate::composites::{PumpControllerR, TankLevel};
+use crate::config::{Config, DeviceConfig};
+use crate::devices::{Capabilities, Component, HARDWARE_TOGGLE_RATE, SOFTWARE_TOGGLE_RATE};
+use crate::drivers;
+use crate::input_devices::{
+    Button, DigitalInputDevice, DigitalInputDeviceR, DistanceSensor, InputDevice, LightSensor,
+};
+use crate::output_devices::{
+    Buzzer, DigitalOutputDevice, OutputDevice, PWMOutputDevice, LED, PWMLED,
+};
+use rppal::system::{DeviceInfo, Model};
+use std::any::Any;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A function a device needs from a pin.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PinFunction {
+    /// Digital input
+    Input,
+    /// Digital output
+    Output,
+    /// Software PWM output, available on every pin
+    Pwm,
+    /// Hardware PWM output, only available on some pins
+    HardwarePwm,
+}
+
+impl fmt::Display for PinFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PinFunction::Input => write!(f, "digital input"),
+            PinFunction::Output => write!(f, "digital output"),
+            PinFunction::Pwm => write!(f, "PWM"),
+            PinFunction::HardwarePwm => write!(f, "hardware PWM"),
+        }
+    }
+}
+
+/// A kernel interface required by a configuration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    /// GPIO register access through `/dev/gpiomem`
+    Gpio,
+    /// The I2C bus through `/dev/i2c-*`
+    I2c,
+    /// The SPI bus through `/dev/spidev*`
+    Spi,
+    /// Hardware PWM through `/sys/class/pwm`
+    Pwm,
+}
+
+impl Backend {
+    /// How to enable the interface, used in error messages.
+    fn hint(self) -> &'static str {
+        match self {
+            Backend::Gpio => "is this a Raspberry Pi with /dev/gpiomem?",
+            Backend::I2c => "enable it with dtparam=i2c_arm=on in /boot/config.txt",
+            Backend::Spi => "enable it with dtparam=spi=on in /boot/config.txt",
+            Backend::Pwm => "enable it with dtoverlay=pwm-2chan in /boot/config.txt",
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Backend::Gpio => write!(f, "GPIO"),
+            Backend::I2c => write!(f, "I2C"),
+            Backend::Spi => write!(f, "SPI"),
+            Backend::Pwm => write!(f, "hardware PWM"),
+        }
+    }
+}
+
+/// The pins and interfaces offered by a board.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Platform {
+    /// The detected model, or None when not running on a known Raspberry Pi
+    pub model: Option<Model>,
+    /// GPIO pins (BCM numbering) broken out on the header
+    pub gpio_pins: Vec<u8>,
+    /// Pins able to output hardware PWM
+    pub hardware_pwm_pins: Vec<u8>,
+    /// SDA and SCL of the I2C bus
+    pub i2c_pins: Vec<u8>,
+    /// CE1, CE0, MISO, MOSI and SCLK of the SPI bus
+    pub spi_pins: Vec<u8>,
+    /// Kernel interfaces that are present
+    pub backends: Vec<Backend>,
+}
+
+impl Platform {
+    /// Describes the board this program is running on. Only reads system information,
+    /// no pins are claimed.
+    pub fn detect() -> Platform {
+        let model = DeviceInfo::new().ok().map(|info| info.model());
+        let mut platform = Platform::for_model(model);
+        platform.backends = Vec::new();
+        if Path::new("/dev/gpiomem").exists() || Path::new("/dev/mem").exists() {
+            platform.backends.push(Backend::Gpio);
+        }
+        if Path::new("/dev/i2c-1").exists() || Path::new("/dev/i2c-0").exists() {
+            platform.backends.push(Backend::I2c);
+        }
+        if Path::new("/dev/spidev0.0").exists() {
+            platform.backends.push(Backend::Spi);
+        }
+        if Path::new("/sys/class/pwm/pwmchip0").exists() {
+            platform.backends.push(Backend::Pwm);
+        }
+        platform
+    }
+
+    /// Describes the given model with every backend available. An unknown model is
+    /// treated as a board with the 40-pin header.
+    pub fn for_model(model: Option<Model>) -> Platform {
+        let (gpio_pins, hardware_pwm_pins, i2c_pins): (Vec<u8>, Vec<u8>, Vec<u8>) = match model {
+            Some(Model::RaspberryPiBRev1) => (
+                vec![0, 1, 4, 7, 8, 9, 10, 11, 14, 15, 17, 18, 21, 22, 23, 24, 25],
+                vec![18],
+                vec![0, 1],
+            ),
+            Some(Model::RaspberryPiA) | Some(Model::RaspberryPiBRev2) => (
+                vec![2, 3, 4, 7, 8, 9, 10, 11, 14, 15, 17, 18, 22, 23, 24, 25, 27],
+                vec![18],
+                vec![2, 3],
+            ),
+            Some(Model::RaspberryPiComputeModule)
+            | Some(Model::RaspberryPiComputeModule3)
+            | Some(Model::RaspberryPiComputeModule3Plus) => (
+                (0..=45).collect(),
+                vec![12, 13, 18, 19, 40, 41, 45],
+                vec![2, 3],
+            ),
+            _ => ((0..=27).collect(), vec![12, 13, 18, 19], vec![2, 3]),
+        };
+        Platform {
+            model,
+            gpio_pins,
+            hardware_pwm_pins,
+            i2c_pins,
+            spi_pins: vec![7, 8, 9, 10, 11],
+            backends: vec![Backend::Gpio, Backend::I2c, Backend::Spi, Backend::Pwm],
+        }
+    }
+
+    /// Returns ``True`` if the pin can provide the function.
+    pub fn supports(&self, pin: u8, function: PinFunction) -> bool {
+        match function {
+            PinFunction::HardwarePwm => self.hardware_pwm_pins.contains(&pin),
+            _ => self.gpio_pins.contains(&pin),
+        }
+    }
+
+    /// What a pin can do with the interfaces that are present, or None if the pin is not
+    /// broken out on the header. `max_toggle_rate` is that of hardware PWM when the pin has
+    /// it, and of software PWM otherwise.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rust_gpiozero::*;
+    ///
+    /// let platform = Platform::detect();
+    /// match platform.capabilities(18) {
+    ///     Some(pin) if pin.hardware_pwm => println!("using hardware PWM"),
+    ///     Some(_) => println!("falling back to software PWM"),
+    ///     None => println!("GPIO18 is not on the header"),
+    /// }
+    /// ```
+    pub fn capabilities(&self, pin: u8) -> Option<Capabilities> {
+        if !self.gpio_pins.contains(&pin) {
+            return None;
+        }
+        let hardware_pwm =
+            self.hardware_pwm_pins.contains(&pin) && self.backends.contains(&Backend::Pwm);
+        Some(Capabilities {
+            hardware_pwm,
+            interrupts: self.backends.contains(&Backend::Gpio),
+            max_toggle_rate: Some(if hardware_pwm {
+                HARDWARE_TOGGLE_RATE
+            } else {
+                SOFTWARE_TOGGLE_RATE
+            }),
+            adc_bits: None,
+        })
+    }
+
+    fn name(&self) -> String {
+        match self.model {
+            Some(model) => model.to_string(),
+            None => "this board".to_string(),
+        }
+    }
+}
+
+/// A problem found by `DeviceTree::validate`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    /// The device kind is not known
+    UnknownKind { device: String, kind: String },
+    /// The device has the wrong number of pins
+    PinCount {
+        device: String,
+        expected: String,
+        found: usize,
+    },
+    /// Two devices, or a device and an enabled bus, use the same pin
+    PinConflict {
+        pin: u8,
+        device: String,
+        other: String,
+    },
+    /// The pin does not exist on the board or cannot provide the function
+    Unsupported {
+        device: String,
+        pin: u8,
+        function: PinFunction,
+        board: String,
+    },
+    /// A kernel interface needed by the device is not available
+    MissingBackend { device: String, backend: Backend },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::UnknownKind { device, kind } => {
+                write!(f, "{}: unknown device kind '{}'", device, kind)
+            }
+            ValidationError::PinCount {
+                device,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{}: expected {} pin(s) but {} are configured",
+                device, expected, found
+            ),
+            ValidationError::PinConflict { pin, device, other } => write!(
+                f,
+                "{}: GPIO{} is already used by {}; move one of them to a free pin",
+                device, pin, other
+            ),
+            ValidationError::Unsupported {
+                device,
+                pin,
+                function,
+                board,
+            } => write!(
+                f,
+                "{}: GPIO{} does not support {} on {}",
+                device, pin, function, board
+            ),
+            ValidationError::MissingBackend { device, backend } => write!(
+                f,
+                "{}: {} is not available; {}",
+                device,
+                backend,
+                backend.hint()
+            ),
+        }
+    }
+}
+
+impl Error for ValidationError {}
+
+/// The pin functions needed by a device kind. A None count means one or more pins.
+fn pin_functions(device: &DeviceConfig) -> Option<(PinFunction, Option<usize>)> {
+    let pwm = if device.option("pwm") == Some("hardware") {
+        PinFunction::HardwarePwm
+    } else {
+        PinFunction::Pwm
+    };
+    match device.kind.as_str() {
+        "led" | "buzzer" | "relay" | "output" | "digital_output" => {
+            Some((PinFunction::Output, Some(1)))
+        }
+        "pwmled" | "pwm_output" | "tonal_buzzer" | "servo" => Some((pwm, Some(1))),
+        "distance_sensor" | "wiegand" => Some((PinFunction::Input, Some(2))),
+        "motor" => Some((pwm, Some(2))),
+        "ir_transmitter" => Some((PinFunction::HardwarePwm, Some(1))),
+        "traffic_lights" => Some((PinFunction::Output, Some(3))),
+        "button" | "input" | "digital_input" | "light_sensor" | "motion_sensor"
+        | "pulse_counter" | "tachometer" | "pwm_input" | "ir_receiver" | "dht" => {
+            Some((PinFunction::Input, Some(1)))
+        }
+        "buttonboard" | "keypad" => Some((PinFunction::Input, None)),
+        "ledboard" | "ledbargraph" => Some((PinFunction::Output, None)),
+        "pwm_ledboard" | "pwm_ledbargraph" => Some((pwm, None)),
+        _ => None,
+    }
+}
+
+/// Problems when adding devices or dependencies to a `DeviceTree`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TreeError {
+    /// A device with the name is already in the tree
+    DuplicateName(String),
+    /// No device with the name is in the tree
+    UnknownDevice(String),
+    /// The dependency would make the named devices depend on each other
+    Cycle(Vec<String>),
+}
+
+impl fmt::Display for TreeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TreeError::DuplicateName(name) => write!(f, "device '{}' already exists", name),
+            TreeError::UnknownDevice(name) => write!(f, "no device named '{}'", name),
+            TreeError::Cycle(names) => write!(f, "dependency cycle: {}", names.join(" -> ")),
+        }
+    }
+}
+
+impl Error for TreeError {}
+
+/// A device whose value can be read, changed and exercised by name, for example from
+/// the REPL. Values are exchanged as text.
+pub trait Inspect {
+    /// The current value of the device.
+    fn value(&self) -> String;
+
+    /// Change the value of the device.
+    fn set_value(&mut self, _value: &str) -> Result<(), String> {
+        Err("device is read-only".to_string())
+    }
+
+    /// Fire a synthetic event, such as `pressed` on a `Button`, for testing.
+    fn fire(&mut self, event: &str) -> Result<(), String> {
+        Err(format!("device has no event '{}'", event))
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "on" | "true" | "1" => Ok(true),
+        "off" | "false" | "0" => Ok(false),
+        _ => Err(format!("expected on or off, found '{}'", value)),
+    }
+}
+
+fn parse_f64(value: &str) -> Result<f64, String> {
+    value
+        .parse()
+        .map_err(|_| format!("expected a number, found '{}'", value))
+}
+
+macro_rules! impl_inspect_digital_output {
+    ($($device:ty),*) => {
+        $(
+            impl Inspect for $device {
+                fn value(&self) -> String {
+                    <$device>::is_active(self).to_string()
+                }
+
+                fn set_value(&mut self, value: &str) -> Result<(), String> {
+                    if parse_bool(value)? {
+                        self.on()
+                    } else {
+                        self.off()
+                    }
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+impl_inspect_digital_output!(OutputDevice, DigitalOutputDevice, LED, Buzzer, Energenie);
+
+macro_rules! impl_inspect_pwm_output {
+    ($($device:ty),*) => {
+        $(
+            impl Inspect for $device {
+                fn value(&self) -> String {
+                    <$device>::value(self).to_string()
+                }
+
+                fn set_value(&mut self, value: &str) -> Result<(), String> {
+                    let value = parse_f64(value)?;
+                    if !(0.0..=1.0).contains(&value) {
+                        return Err("value must be between 0.0 and 1.0".to_string());
+                    }
+                    <$device>::set_value(self, value);
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+impl_inspect_pwm_output!(PWMOutputDevice, PWMLED);
+
+macro_rules! impl_inspect_input {
+    ($($device:ty),*) => {
+        $(
+            impl Inspect for $device {
+                fn value(&self) -> String {
+                    <$device>::is_active(self).to_string()
+                }
+
+                #[cfg(feature = "inject")]
+                fn fire(&mut self, event: &str) -> Result<(), String> {
+                    match event {
+                        "active" | "pressed" => self.inject_value(true),
+                        "inactive" | "released" => self.inject_value(false),
+                        "clear" => self.clear_injection(),
+                        _ => return Err(format!("unknown event '{}', expected active, inactive or clear", event)),
+                    }
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+impl_inspect_input!(InputDevice, DigitalInputDevice, DigitalInputDeviceR, Button);
+
+impl Inspect for TankLevel {
+    fn value(&self) -> String {
+        match self.level() {
+            Some(level) => level.to_string(),
+            None => "unknown".to_string(),
+        }
+    }
+}
+
+impl Inspect for PumpControllerR {
+    fn value(&self) -> String {
+        self.is_running().to_string()
+    }
+
+    fn set_value(&mut self, value: &str) -> Result<(), String> {
+        if parse_bool(value)? {
+            self.start().map_err(|e| e.to_string())
+        } else {
+            self.stop();
+            Ok(())
+        }
+    }
+}
+
+impl Inspect for DistanceSensor {
+    fn value(&self) -> String {
+        match self.try_distance() {
+            Ok(distance) => distance.to_string(),
+            Err(_) => "no echo".to_string(),
+        }
+    }
+}
+
+impl Inspect for LightSensor {
+    fn value(&self) -> String {
+        LightSensor::value(self).to_string()
+    }
+}
+
+impl Inspect for LEDBoard {
+    fn value(&self) -> String {
+        format!("{:?}", Component::values(self))
+    }
+
+    fn set_value(&mut self, value: &str) -> Result<(), String> {
+        let values = value
+            .split(',')
+            .map(|v| parse_bool(v.trim()))
+            .collect::<Result<Vec<bool>, String>>()?;
+        LEDBoard::set_value(self, &values);
+        Ok(())
+    }
+}
+
+impl Inspect for LEDBarGraph {
+    fn value(&self) -> String {
+        LEDBarGraph::value(self).to_string()
+    }
+
+    fn set_value(&mut self, value: &str) -> Result<(), String> {
+        let value = parse_f64(value)?;
+        if !(-1.0..=1.0).contains(&value) {
+            return Err("value must be between -1.0 and 1.0".to_string());
+        }
+        LEDBarGraph::set_value(self, value);
+        Ok(())
+    }
+}
+
+/// An entry of the event log of a `DeviceTree`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    /// When the event was logged
+    pub time: SystemTime,
+    /// Name of the device the event is about
+    pub device: String,
+    /// What happened
+    pub message: String,
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let since_epoch = self.time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        write!(
+            f,
+            "{}.{:03} {}: {}",
+            since_epoch.as_secs(),
+            since_epoch.subsec_millis(),
+            self.device,
+            self.message
+        )
+    }
+}
+
+type InspectFn = fn(&mut (dyn Any + Send)) -> Option<&mut dyn Inspect>;
+
+struct Node {
+    name: String,
+    device: Box<dyn Any + Send>,
+    inspect: Option<InspectFn>,
+    depends_on: Vec<String>,
+}
+
+/// The devices of an installation, described by a `Config`.
+///
+/// Devices added to the tree are owned by it and closed in dependency order: a device
+/// is always closed before the devices it depends on, so a controller is stopped before
+/// the outputs it drives and a composite device before its children. Devices without a
+/// dependency between them are closed in the reverse of the order they were added.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut tree = DeviceTree::default();
+/// tree.add("fan", LED::new(17)).unwrap();
+/// tree.add("status", LEDBoard::new(&[5, 6])).unwrap();
+/// tree.depends_on("status", "fan").unwrap();
+/// tree.get::<LED>("fan").unwrap().on();
+///
+/// // closes "status" first, then "fan"
+/// tree.close();
+/// ```
+pub struct DeviceTree {
+    config: Config,
+    nodes: Vec<Node>,
+    events: VecDeque<Event>,
+    logged: u64,
+    log_capacity: usize,
+}
+
+impl Default for DeviceTree {
+    fn default() -> Self {
+        DeviceTree::new(Config::default())
+    }
+}
+
+impl DeviceTree {
+    /// Returns a DeviceTree for the configuration
+    pub fn new(config: Config) -> DeviceTree {
+        DeviceTree {
+            config,
+            nodes: Vec::new(),
+            events: VecDeque::new(),
+            logged: 0,
+            log_capacity: 1000,
+        }
+    }
+
+    /// Add a device to the tree, which takes ownership of it.
+    /// * `name` - Unique name of the device
+    /// * `device` - The device
+    pub fn add<T: Any + Send>(&mut self, name: &str, device: T) -> Result<(), TreeError> {
+        self.insert(name, Box::new(device), None)
+    }
+
+    /// Add a device whose value can be read and changed by name with `value`, `set_value` and `fire`.
+    /// * `name` - Unique name of the device
+    /// * `device` - The device
+    pub fn add_inspectable<T: Any + Send + Inspect>(
+        &mut self,
+        name: &str,
+        device: T,
+    ) -> Result<(), TreeError> {
+        fn inspect<T: Any + Inspect>(device: &mut (dyn Any + Send)) -> Option<&mut dyn Inspect> {
+            match device.downcast_mut::<T>() {
+                Some(device) => Some(device),
+                None => None,
+            }
+        }
+        self.insert(name, Box::new(device), Some(inspect::<T>))
+    }
+
+    fn insert(
+        &mut self,
+        name: &str,
+        device: Box<dyn Any + Send>,
+        inspect: Option<InspectFn>,
+    ) -> Result<(), TreeError> {
+        if self.position(name).is_some() {
+            return Err(TreeError::DuplicateName(name.to_string()));
+        }
+        self.nodes.push(Node {
+            name: name.to_string(),
+            device,
+            inspect,
+            depends_on: Vec::new(),
+        });
+        Ok(())
+    }
+
+    fn inspect(&mut self, name: &str) -> Result<&mut dyn Inspect, String> {
+        let index = self
+            .position(name)
+            .ok_or_else(|| format!("no device named '{}'", name))?;
+        let node = &mut self.nodes[index];
+        match node.inspect {
+            Some(inspect) => {
+                inspect(node.device.as_mut()).ok_or_else(|| "device type changed".to_string())
+            }
+            None => Err(format!("device '{}' cannot be inspected", name)),
+        }
+    }
+
+    /// The value of a device added with `add_inspectable`, as text.
+    pub fn value(&mut self, name: &str) -> Result<String, String> {
+        Ok(self.inspect(name)?.value())
+    }
+
+    /// Change the value of a device added with `add_inspectable`. The change is logged.
+    pub fn set_value(&mut self, name: &str, value: &str) -> Result<(), String> {
+        self.inspect(name)?.set_value(value)?;
+        self.log_event(name, &format!("set to {}", value));
+        Ok(())
+    }
+
+    /// Fire a synthetic event on a device added with `add_inspectable`. The event is logged.
+    pub fn fire(&mut self, name: &str, event: &str) -> Result<(), String> {
+        self.inspect(name)?.fire(event)?;
+        self.log_event(name, &format!("test event {}", event));
+        Ok(())
+    }
+
+    /// Append an entry to the event log, dropping the oldest entry when the log is full.
+    pub fn log_event(&mut self, device: &str, message: &str) {
+        if self.log_capacity == 0 {
+            return;
+        }
+        while self.events.len() >= self.log_capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back(Event {
+            time: SystemTime::now(),
+            device: device.to_string(),
+            message: message.to_string(),
+        });
+        self.logged += 1;
+    }
+
+    /// Number of entries logged since the tree was created, including entries no longer kept.
+    pub fn event_count(&self) -> u64 {
+        self.logged
+    }
+
+    /// The entries logged after the first `count` entries, as far as they are still kept.
+    /// Pass the result of `event_count` to receive only entries logged since then.
+    pub fn events_since(&self, count: u64) -> Vec<&Event> {
+        let new = self.logged.saturating_sub(count) as usize;
+        self.recent_events(new)
+    }
+
+    /// The most recent entries of the event log, oldest first.
+    /// * `count` - Maximum number of entries to return
+    pub fn recent_events(&self, count: usize) -> Vec<&Event> {
+        let skip = self.events.len().saturating_sub(count);
+        self.events.iter().skip(skip).collect()
+    }
+
+    /// Set how many entries the event log keeps (1000 by default).
+    pub fn set_log_capacity(&mut self, capacity: usize) {
+        self.log_capacity = capacity;
+        while self.events.len() > capacity {
+            self.events.pop_front();
+        }
+    }
+
+    /// Record that a device uses another one, so that it is closed first.
+    /// * `name` - The dependent device, such as a controller
+    /// * `on` - The device it uses, such as an output it drives
+    pub fn depends_on(&mut self, name: &str, on: &str) -> Result<(), TreeError> {
+        let index = self
+            .position(name)
+            .ok_or_else(|| TreeError::UnknownDevice(name.to_string()))?;
+        if self.position(on).is_none() {
+            return Err(TreeError::UnknownDevice(on.to_string()));
+        }
+        if let Some(mut path) = self.path(on, name) {
+            path.insert(0, name.to_string());
+            return Err(TreeError::Cycle(path));
+        }
+        if !self.nodes[index].depends_on.iter().any(|d| d == on) {
+            self.nodes[index].depends_on.push(on.to_string());
+        }
+        Ok(())
+    }
+
+    /// The device with the given name, or None if there is no such device or it has another type.
+    pub fn get<T: Any>(&mut self, name: &str) -> Option<&mut T> {
+        let index = self.position(name)?;
+        self.nodes[index].device.downcast_mut::<T>()
+    }
+
+    /// The names of the devices, in the order they were added.
+    pub fn names(&self) -> Vec<&str> {
+        self.nodes.iter().map(|node| node.name.as_str()).collect()
+    }
+
+    /// The names of the devices in the order they will be closed.
+    pub fn shutdown_order(&self) -> Vec<&str> {
+        let mut open: Vec<usize> = (0..self.nodes.len()).collect();
+        let mut order = Vec::new();
+        while !open.is_empty() {
+            // the last added device that no open device depends on
+            let next = open
+                .iter()
+                .rposition(|&candidate| {
+                    let name = &self.nodes[candidate].name;
+                    !open
+                        .iter()
+                        .any(|&other| self.nodes[other].depends_on.contains(name))
+                })
+                .unwrap_or(open.len() - 1);
+            order.push(self.nodes[open.remove(next)].name.as_str());
+        }
+        order
+    }
+
+    /// Close a device, after first closing every device that depends on it.
+    /// Returns the names of the closed devices in the order they were closed.
+    pub fn close_device(&mut self, name: &str) -> Result<Vec<String>, TreeError> {
+        if self.position(name).is_none() {
+            return Err(TreeError::UnknownDevice(name.to_string()));
+        }
+        let order: Vec<String> = self
+            .shutdown_order()
+            .into_iter()
+            .filter(|&other| other == name || self.path(other, name).is_some())
+            .map(String::from)
+            .collect();
+        for closing in &order {
+            self.remove(closing);
+        }
+        Ok(order)
+    }
+
+    /// Close every device in dependency order.
+    pub fn close(mut self) {
+        self.close_all();
+    }
+
+    fn close_all(&mut self) {
+        let order: Vec<String> = self
+            .shutdown_order()
+            .into_iter()
+            .map(String::from)
+            .collect();
+        for name in &order {
+            self.remove(name);
+        }
+    }
+
+    fn remove(&mut self, name: &str) {
+        if let Some(index) = self.position(name) {
+            let node = self.nodes.remove(index);
+            drop(node.device);
+            for other in self.nodes.iter_mut() {
+                other.depends_on.retain(|d| d != name);
+            }
+        }
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.nodes.iter().position(|node| node.name == name)
+    }
+
+    /// The chain of dependencies leading from one device to another, if any.
+    fn path(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        if from == to {
+            return Some(vec![to.to_string()]);
+        }
+        let node = &self.nodes[self.position(from)?];
+        for next in &node.depends_on {
+            if let Some(mut path) = self.path(next, to) {
+                path.insert(0, from.to_string());
+                return Some(path);
+            }
+        }
+        None
+    }
+
+    /// The configuration of the tree.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Create every configured device whose kind has a registered `Driver` and add it to the
+    /// tree. Devices already in the tree are skipped.
+    pub fn build_drivers(&mut self) -> Result<(), Box<dyn Error>> {
+        for device in self.config.devices.clone() {
+            if self.position(&device.name).is_some() {
+                continue;
+            }
+            if let Some(driver) = drivers::driver(&device.kind) {
+                driver
+                    .build(&device, self)
+                    .map_err(|e| format!("{}: {}", device.name, e))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check a configuration against the board this program is running on without
+    /// touching any pin. Returns every problem found.
+    pub fn validate(config: &Config) -> Result<(), Vec<ValidationError>> {
+        DeviceTree::validate_for(config, &Platform::detect())
+    }
+
+    /// Check a configuration against the given platform, for example to validate a
+    /// deployment on a development machine.
+    pub fn validate_for(config: &Config, platform: &Platform) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        let mut used: Vec<(u8, String)> = Vec::new();
+
+        let buses = [
+            ("i2c", Backend::I2c, &platform.i2c_pins),
+            ("spi", Backend::Spi, &platform.spi_pins),
+        ];
+        for (setting, backend, pins) in buses.iter() {
+            if !config.enabled(setting) {
+                continue;
+            }
+            if !platform.backends.contains(backend) {
+                errors.push(ValidationError::MissingBackend {
+                    device: setting.to_string(),
+                    backend: *backend,
+                });
+            }
+            for &pin in pins.iter() {
+                used.push((pin, format!("the {} bus", backend)));
+            }
+        }
+
+        for device in &config.devices {
+            // pipelines read other devices and use no pins of their own
+            if device.kind == "pipeline" {
+                continue;
+            }
+            let (function, count) = match pin_functions(device) {
+                Some(functions) => functions,
+                None => match drivers::driver(&device.kind) {
+                    Some(driver) => match driver.pins(device) {
+                        Some(functions) => functions,
+                        None => continue,
+                    },
+                    None => {
+                        errors.push(ValidationError::UnknownKind {
+                            device: device.name.clone(),
+                            kind: device.kind.clone(),
+                        });
+                        continue;
+                    }
+                },
+            };
+            let count_ok = match count {
+                Some(count) => device.pins.len() == count,
+                None => !device.pins.is_empty(),
+            };
+            if !count_ok {
+                errors.push(ValidationError::PinCount {
+                    device: device.name.clone(),
+                    expected: match count {
+                        Some(count) => count.to_string(),
+                        None => "at least 1".to_string(),
+                    },
+                    found: device.pins.len(),
+                });
+            }
+
+            let backend = match function {
+                PinFunction::HardwarePwm => Backend::Pwm,
+                _ => Backend::Gpio,
+            };
+            if !platform.backends.contains(&backend) {
+                errors.push(ValidationError::MissingBackend {
+                    device: device.name.clone(),
+                    backend,
+                });
+            }
+
+            for &pin in &device.pins {
+                if !platform.supports(pin, function) {
+                    errors.push(ValidationError::Unsupported {
+                        device: device.name.clone(),
+                        pin,
+                        function,
+                        board: platform.name(),
+                    });
+                }
+                match used.iter().find(|(p, _)| *p == pin) {
+                    Some((_, other)) => errors.push(ValidationError::PinConflict {
+                        pin,
+                        device: device.name.clone(),
+                        other: other.clone(),
+                    }),
+                    None => used.push((pin, device.name.clone())),
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl Drop for DeviceTree {
+    fn drop(&mut self) {
+        self.close_all();
+    }
+}
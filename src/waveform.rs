@@ -0,0 +1,978 @@
+//! Timing verification for bit-banged drivers
+//!
+//! Drivers that time their own pulses, rather than leaving it to an SPI or I2C controller,
+//! break in ways that only show on a scope: a bit stretched by a refactor, a leader shorter
+//! than the spec. A `Waveform` records the levels of a few signals against time in
+//! microseconds and is read from and written to Value Change Dump (VCD) files, the format
+//! of logic analysers, PulseView and GTKWave. `compare` checks a waveform against a golden
+//! one within a tolerance and reports the first edge that differs.
+//!
+//! Each driver of the crate that times a protocol in software has a generator, giving the
+//! waveform the driver sends or expects to receive, and a decoder, replaying a waveform
+//! through the code the driver runs on its pins. Their golden waveforms are in `golden`,
+//! and the examples below check every driver against them, so a change to a driver that
+//! alters its timing fails `cargo test`. Drivers added later get the same regression test by
+//! adding a generator, a decoder and a golden VCD.
+//!
+//! `MockClock` stands in for `Instant::now` in decoders that time out between frames, so a
+//! replay runs as fast as the code allows.
+//!
+//! # Example
+//!
+//! ```
+//! use rust_gpiozero::waveform::{self, golden, Waveform};
+//!
+//! let golden = Waveform::from_vcd(golden::NEC).unwrap();
+//! waveform::nec(0x04, 0x08, 1).compare(&golden, 0).unwrap();
+//!
+//! // a receiver tolerates 50µs of jitter on every edge
+//! let received = golden.jittered(50);
+//! assert!(received.compare(&golden, 20).is_err());
+//! received.compare(&golden, 50).unwrap();
+//! assert_eq!(waveform::decode_nec(&received, "ir"), vec![(0x04, 0x08), (0x04, 0x08)]);
+//! ```
+use crate::expander::shift_steps;
+use crate::input_devices::{
+    dht_receive, hx711_shift, DHTError, DHTModel, DHTReading, HX711Gain, NecDecoder, NecEvent,
+    WiegandBits, WiegandCard, WiegandFrame, DHT_LEVEL_TIMEOUT, HX711_PULSE, NEC_HOLD,
+};
+use crate::output_devices::{nec_frame, NEC_PERIOD, NEC_REPEAT};
+use rppal::gpio::Level;
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Golden waveforms of the drivers of the crate, as VCD text.
+pub mod golden {
+    /// An NEC frame with address 0x04 and command 0x08 followed by one repeat code, on the
+    /// signal `ir`, high during bursts.
+    pub const NEC: &str = include_str!("waveform/nec.vcd");
+
+    /// A 26 bit Wiegand card with facility code 18 and number 12345, on the signals `d0`
+    /// and `d1`.
+    pub const WIEGAND_26: &str = include_str!("waveform/wiegand_26.vcd");
+
+    /// A DHT22 read with a 1.1ms start signal, answered with 65.2% humidity and 35.1°C, on
+    /// the signal `data`.
+    pub const DHT22: &str = include_str!("waveform/dht22.vcd");
+
+    /// Two chained shift registers latching pins 0, 3, 8 and 15 high, on the signals
+    /// `data`, `clock` and `latch`.
+    pub const SHIFT_REGISTER_16: &str = include_str!("waveform/shift_register_16.vcd");
+
+    /// An HX711 conversion of -123456 read with 25 pulses, keeping channel A at a gain of
+    /// 128, on the signals `dout` and `sck`.
+    pub const HX711: &str = include_str!("waveform/hx711.vcd");
+}
+
+/// The length of a data pulse sent by `wiegand`.
+const WIEGAND_PULSE: u64 = 50;
+
+/// The time from one data pulse sent by `wiegand` to the next.
+const WIEGAND_INTERVAL: u64 = 2000;
+
+/// The inter-frame timeout of a `WiegandReader`.
+const WIEGAND_TIMEOUT: Duration = Duration::from_millis(25);
+
+/// The shortest start signal each DHT sensor answers, from their datasheets.
+fn dht_wake_time(model: DHTModel) -> u64 {
+    match model {
+        DHTModel::DHT11 => 18_000,
+        DHTModel::DHT22 => 800,
+    }
+}
+
+/// The low and high levels of a 0 and of a 1 sent by a DHT sensor, in microseconds.
+const DHT_BITS: [[(bool, u64); 2]; 2] = [[(false, 50), (true, 27)], [(false, 50), (true, 70)]];
+
+/// A clock for decoders and drivers that read the time, moved on by hand.
+///
+/// Clones share the time, so a test can hold one and give another to the code under test.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    origin: Instant,
+    elapsed: Arc<Mutex<Duration>>,
+}
+
+impl MockClock {
+    /// Returns a clock stopped at the time it was created.
+    pub fn new() -> MockClock {
+        MockClock {
+            origin: Instant::now(),
+            elapsed: Arc::new(Mutex::new(Duration::from_secs(0))),
+        }
+    }
+
+    /// The time of the clock, to use in place of `Instant::now()`.
+    pub fn now(&self) -> Instant {
+        self.origin + self.elapsed()
+    }
+
+    /// How far the clock has been moved on since it was created.
+    pub fn elapsed(&self) -> Duration {
+        *self.elapsed.lock().unwrap()
+    }
+
+    /// Move the clock on.
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().unwrap() += duration
+    }
+
+    /// Set the time since the clock was created.
+    pub fn set(&self, elapsed: Duration) {
+        *self.elapsed.lock().unwrap() = elapsed
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        MockClock::new()
+    }
+}
+
+/// Problems reading or comparing waveforms.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WaveformError {
+    /// The VCD text could not be read, with the reason
+    Parse(String),
+    /// A signal of the golden waveform is missing
+    MissingSignal(String),
+    /// A signal starts at a different level than in the golden waveform
+    Initial {
+        /// The name of the signal
+        signal: String,
+        /// The level of the signal in the golden waveform
+        expected: bool,
+    },
+    /// An edge is missing, extra, or further from the golden one than the tolerance
+    Edge {
+        /// The name of the signal
+        signal: String,
+        /// The number of the edge, from 0
+        edge: usize,
+        /// The time of the edge in the golden waveform in µs
+        expected: Option<u64>,
+        /// The time of the edge in the waveform compared in µs
+        found: Option<u64>,
+    },
+}
+
+impl fmt::Display for WaveformError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let time = |at: &Option<u64>| match at {
+            Some(at) => format!("{}µs", at),
+            None => "none".to_string(),
+        };
+        match self {
+            WaveformError::Parse(reason) => write!(f, "invalid VCD: {}", reason),
+            WaveformError::MissingSignal(signal) => write!(f, "no signal named {}", signal),
+            WaveformError::Initial { signal, expected } => write!(
+                f,
+                "{} should start {}",
+                signal,
+                if *expected { "high" } else { "low" }
+            ),
+            WaveformError::Edge {
+                signal,
+                edge,
+                expected,
+                found,
+            } => write!(
+                f,
+                "edge {} of {}: expected {}, found {}",
+                edge,
+                signal,
+                time(expected),
+                time(found)
+            ),
+        }
+    }
+}
+
+impl Error for WaveformError {}
+
+/// A signal of a `Waveform`: its level at time 0 and the times in µs it changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Signal {
+    name: String,
+    initial: bool,
+    edges: Vec<(u64, bool)>,
+}
+
+impl Signal {
+    fn level(&self) -> bool {
+        self.edges.last().map_or(self.initial, |&(_, level)| level)
+    }
+}
+
+/// The levels of a few digital signals over time, in microseconds from the start.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Waveform {
+    signals: Vec<Signal>,
+}
+
+impl Waveform {
+    /// Returns a waveform with the signals given, and no edges.
+    /// * `signals` - The name of each signal and its level at time 0
+    pub fn new(signals: &[(&str, bool)]) -> Waveform {
+        Waveform {
+            signals: signals
+                .iter()
+                .map(|&(name, initial)| Signal {
+                    name: name.to_string(),
+                    initial,
+                    edges: Vec::new(),
+                })
+                .collect(),
+        }
+    }
+
+    /// The names of the signals.
+    pub fn signals(&self) -> Vec<&str> {
+        self.signals
+            .iter()
+            .map(|signal| signal.name.as_str())
+            .collect()
+    }
+
+    fn signal(&self, name: &str) -> Option<&Signal> {
+        self.signals.iter().find(|signal| signal.name == name)
+    }
+
+    /// Set the level of a signal from the time given. Setting a signal to the level it is
+    /// already at has no effect.
+    ///
+    /// Panics if there is no such signal or `at` is before its last edge, as a harness that
+    /// records edges out of order is broken.
+    /// * `signal` - The name of the signal
+    /// * `at` - The time in µs
+    /// * `level` - ``True`` for high
+    pub fn set(&mut self, signal: &str, at: u64, level: bool) {
+        let signal = match self.signals.iter_mut().find(|s| s.name == signal) {
+            Some(signal) => signal,
+            None => panic!("no signal named {}", signal),
+        };
+        if let Some(&(last, _)) = signal.edges.last() {
+            if at < last {
+                panic!("edge of {} at {}µs is before {}µs", signal.name, at, last);
+            }
+        }
+        if signal.level() != level {
+            signal.edges.push((at, level));
+        }
+    }
+
+    /// Drive a signal through a run of levels, returning the time the run ends. The signal
+    /// is left at the last level.
+    /// * `signal` - The name of the signal
+    /// * `start` - The time in µs of the first level
+    /// * `levels` - Each level and how many µs it lasts
+    pub fn pulses(&mut self, signal: &str, start: u64, levels: &[(bool, u64)]) -> u64 {
+        let mut at = start;
+        for &(level, micros) in levels {
+            self.set(signal, at, level);
+            at += micros;
+        }
+        at
+    }
+
+    /// The level of a signal at the time given in µs.
+    pub fn level_at(&self, signal: &str, at: u64) -> Option<bool> {
+        let signal = self.signal(signal)?;
+        let level = signal
+            .edges
+            .iter()
+            .take_while(|&&(time, _)| time <= at)
+            .last()
+            .map_or(signal.initial, |&(_, level)| level);
+        Some(level)
+    }
+
+    /// The times in µs a signal changes, and the level it changes to.
+    pub fn edges(&self, signal: &str) -> Option<&[(u64, bool)]> {
+        self.signal(signal).map(|signal| signal.edges.as_slice())
+    }
+
+    /// The level of a signal at time 0 and after each edge, and how many µs it lasts. The
+    /// level after the last edge lasts forever and is left out.
+    pub fn runs(&self, signal: &str) -> Option<Vec<(bool, u64)>> {
+        let signal = self.signal(signal)?;
+        let mut runs = Vec::with_capacity(signal.edges.len());
+        let (mut level, mut from) = (signal.initial, 0);
+        for &(at, next) in &signal.edges {
+            if at > from {
+                runs.push((level, at - from));
+            }
+            level = next;
+            from = at;
+        }
+        Some(runs)
+    }
+
+    /// The time in µs of the last edge of any signal.
+    pub fn end(&self) -> u64 {
+        self.signals
+            .iter()
+            .filter_map(|signal| signal.edges.last())
+            .map(|&(at, _)| at)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// A copy of the waveform with every edge moved by `micros`, alternately later and
+    /// earlier, as a receiver with a slow interrupt might see it.
+    pub fn jittered(&self, micros: u64) -> Waveform {
+        let mut jittered = self.clone();
+        for signal in &mut jittered.signals {
+            let mut previous = 0;
+            for (i, edge) in signal.edges.iter_mut().enumerate() {
+                let at = if i % 2 == 0 {
+                    edge.0 + micros
+                } else {
+                    edge.0.saturating_sub(micros)
+                };
+                edge.0 = at.max(previous);
+                previous = edge.0;
+            }
+        }
+        jittered
+    }
+
+    /// Check the waveform against a golden one. Every signal of the golden waveform must be
+    /// present and start at the same level, with the same number of edges, each within
+    /// `tolerance` µs. Signals only in this waveform are ignored.
+    pub fn compare(&self, golden: &Waveform, tolerance: u64) -> Result<(), WaveformError> {
+        for expected in &golden.signals {
+            let found = match self.signal(&expected.name) {
+                Some(found) => found,
+                None => return Err(WaveformError::MissingSignal(expected.name.clone())),
+            };
+            if found.initial != expected.initial {
+                return Err(WaveformError::Initial {
+                    signal: expected.name.clone(),
+                    expected: expected.initial,
+                });
+            }
+            let count = expected.edges.len().max(found.edges.len());
+            for edge in 0..count {
+                let golden_at = expected.edges.get(edge).map(|&(at, _)| at);
+                let found_at = found.edges.get(edge).map(|&(at, _)| at);
+                let within = match (golden_at, found_at) {
+                    (Some(golden_at), Some(found_at)) => golden_at.abs_diff(found_at) <= tolerance,
+                    _ => false,
+                };
+                if !within {
+                    return Err(WaveformError::Edge {
+                        signal: expected.name.clone(),
+                        edge,
+                        expected: golden_at,
+                        found: found_at,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The waveform as VCD text with a timescale of 1µs.
+    pub fn to_vcd(&self) -> String {
+        let ids: Vec<String> = (0..self.signals.len()).map(vcd_id).collect();
+        let mut vcd = String::from("$timescale 1us $end\n$scope module rust_gpiozero $end\n");
+        for (signal, id) in self.signals.iter().zip(&ids) {
+            vcd += &format!("$var wire 1 {} {} $end\n", id, signal.name);
+        }
+        vcd += "$upscope $end\n$enddefinitions $end\n#0\n$dumpvars\n";
+        for (signal, id) in self.signals.iter().zip(&ids) {
+            vcd += &format!("{}{}\n", u8::from(signal.initial), id);
+        }
+        vcd += "$end\n";
+        let mut changes: Vec<(u64, usize, bool)> = self
+            .signals
+            .iter()
+            .enumerate()
+            .flat_map(|(i, signal)| signal.edges.iter().map(move |&(at, level)| (at, i, level)))
+            .collect();
+        changes.sort_by_key(|&(at, i, _)| (at, i));
+        let mut time = None;
+        for (at, i, level) in changes {
+            if time != Some(at) {
+                vcd += &format!("#{}\n", at);
+                time = Some(at);
+            }
+            vcd += &format!("{}{}\n", u8::from(level), ids[i]);
+        }
+        vcd
+    }
+
+    /// Read a waveform from VCD text, e.g. exported from a logic analyser. Only 1 bit
+    /// signals with the levels 0 and 1 are supported; times are rounded down to whole µs.
+    pub fn from_vcd(text: &str) -> Result<Waveform, WaveformError> {
+        let mut tokens = text.split_whitespace();
+        let mut waveform = Waveform {
+            signals: Vec::new(),
+        };
+        let mut ids: Vec<String> = Vec::new();
+        // the first value of a signal at time 0 is its initial level
+        let mut initialised: Vec<bool> = Vec::new();
+        let mut nanos_per_tick = 1_000u64;
+        let mut time = 0u64;
+        // skip the rest of a declaration, returning its words
+        let until_end = |tokens: &mut std::str::SplitWhitespace| {
+            let mut words = Vec::new();
+            for token in tokens.by_ref() {
+                if token == "$end" {
+                    return Ok(words);
+                }
+                words.push(token.to_string());
+            }
+            Err(WaveformError::Parse("declaration without $end".to_string()))
+        };
+        while let Some(token) = tokens.next() {
+            match token {
+                "$timescale" => {
+                    let scale = until_end(&mut tokens)?.concat();
+                    let digits = scale.trim_end_matches(char::is_alphabetic);
+                    let number: u64 = digits
+                        .parse()
+                        .map_err(|_| WaveformError::Parse(format!("timescale {}", scale)))?;
+                    let unit = match &scale[digits.len()..] {
+                        "s" => 1_000_000_000,
+                        "ms" => 1_000_000,
+                        "us" => 1_000,
+                        "ns" => 1,
+                        _ => return Err(WaveformError::Parse(format!("timescale {}", scale))),
+                    };
+                    nanos_per_tick = number * unit;
+                }
+                "$var" => {
+                    let words = until_end(&mut tokens)?;
+                    match words.as_slice() {
+                        [_, size, id, name, ..] if size == "1" => {
+                            ids.push(id.clone());
+                            initialised.push(false);
+                            waveform.signals.push(Signal {
+                                name: name.clone(),
+                                initial: false,
+                                edges: Vec::new(),
+                            });
+                        }
+                        _ => {
+                            return Err(WaveformError::Parse(format!(
+                                "unsupported $var {}",
+                                words.join(" ")
+                            )))
+                        }
+                    }
+                }
+                // value changes follow these, up to an $end of their own
+                "$dumpvars" | "$dumpall" | "$dumpon" | "$dumpoff" | "$end" => (),
+                _ if token.starts_with('$') => {
+                    until_end(&mut tokens)?;
+                }
+                _ if token.starts_with('#') => {
+                    let ticks: u64 = token[1..]
+                        .parse()
+                        .map_err(|_| WaveformError::Parse(format!("time {}", token)))?;
+                    time = ticks * nanos_per_tick / 1_000;
+                }
+                _ => {
+                    let mut chars = token.chars();
+                    let level = match chars.next() {
+                        Some('0') => false,
+                        Some('1') => true,
+                        _ => {
+                            return Err(WaveformError::Parse(format!(
+                                "unsupported value {}",
+                                token
+                            )))
+                        }
+                    };
+                    let id = chars.as_str();
+                    let i = match ids.iter().position(|known| known == id) {
+                        Some(i) => i,
+                        None => return Err(WaveformError::Parse(format!("unknown signal {}", id))),
+                    };
+                    let signal = &mut waveform.signals[i];
+                    if time == 0 && !initialised[i] {
+                        signal.initial = level;
+                        initialised[i] = true;
+                    } else if signal.edges.last().is_some_and(|&(last, _)| time < last) {
+                        return Err(WaveformError::Parse(format!("time goes back at #{}", time)));
+                    } else if signal.level() != level {
+                        signal.edges.push((time, level));
+                    }
+                }
+            }
+        }
+        Ok(waveform)
+    }
+}
+
+/// The VCD identifier of the signal given: printable characters from `!`, as many as
+/// needed.
+fn vcd_id(mut index: usize) -> String {
+    let mut id = String::new();
+    loop {
+        id.push(char::from(b'!' + (index % 94) as u8));
+        index /= 94;
+        if index == 0 {
+            return id;
+        }
+        index -= 1;
+    }
+}
+
+/// The waveform an `IRTransmitter` sends for `send_held`, on the signal `ir`, high during
+/// bursts.
+/// * `address` - The address of the device
+/// * `command` - The command
+/// * `repeats` - The number of repeat codes after the frame
+///
+/// # Example
+///
+/// ```
+/// use rust_gpiozero::waveform::{self, golden, Waveform};
+///
+/// let golden = Waveform::from_vcd(golden::NEC).unwrap();
+/// waveform::nec(0x04, 0x08, 1).compare(&golden, 0).unwrap();
+/// assert_eq!(Waveform::from_vcd(&golden.to_vcd()).unwrap(), golden);
+///
+/// // a 9ms leader, and the stop burst ending 67.98ms into this frame
+/// assert_eq!(golden.edges("ir").unwrap()[1], (9000, false));
+/// assert_eq!(golden.edges("ir").unwrap()[67], (67980, false));
+/// assert!(waveform::nec(0x04, 0x09, 1).compare(&golden, 0).is_err());
+///
+/// // extended addresses, and codes an IRReceiver should reject
+/// let extended = waveform::nec(0x1234, 0x56, 0);
+/// assert_eq!(waveform::decode_nec(&extended, "ir"), vec![(0x1234, 0x56)]);
+/// assert_eq!(waveform::decode_nec(&golden.jittered(300), "ir"), vec![]);
+/// ```
+pub fn nec(address: u16, command: u8, repeats: u32) -> Waveform {
+    let mut waveform = Waveform::new(&[("ir", false)]);
+    let end = waveform.pulses("ir", 0, &nec_frame(address, command));
+    waveform.set("ir", end, false);
+    let period = NEC_PERIOD.as_micros() as u64;
+    for repeat in 1..=u64::from(repeats) {
+        let end = waveform.pulses("ir", repeat * period, &NEC_REPEAT);
+        waveform.set("ir", end, false);
+    }
+    waveform
+}
+
+/// Replay a signal through the decoder of an `IRReceiver`, returning the address and
+/// command of every frame, and of the frame held for every repeat code.
+/// * `waveform` - The waveform
+/// * `signal` - The signal, high during bursts
+pub fn decode_nec(waveform: &Waveform, signal: &str) -> Vec<(u16, u8)> {
+    let runs = match waveform.runs(signal) {
+        Some(runs) => runs,
+        None => return Vec::new(),
+    };
+    let mut decoder = NecDecoder::new();
+    let mut codes = Vec::new();
+    let (mut at, mut last) = (0, None);
+    for (burst, micros) in runs {
+        at += micros;
+        match decoder.feed(burst, Duration::from_micros(micros)) {
+            Some(NecEvent::Code(address, command)) => {
+                last = Some((address, command, at));
+                codes.push((address, command));
+            }
+            Some(NecEvent::Repeat) => {
+                let held = NEC_HOLD.as_micros() as u64;
+                if let Some((address, command, seen)) = last {
+                    if at - seen < held {
+                        last = Some((address, command, at));
+                        codes.push((address, command));
+                    }
+                }
+            }
+            None => (),
+        }
+    }
+    codes
+}
+
+/// What a frame replayed with `decode_wiegand` decodes to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WiegandEvent {
+    /// A card
+    Card(WiegandCard),
+    /// A key pressed on a keypad
+    Key(char),
+    /// A frame of the bit count given that is not a valid card or key
+    Invalid(u32),
+}
+
+/// The waveform of a Wiegand frame on the signals `d0` and `d1`: a 50µs low pulse on D1
+/// for each 1 and on D0 for each 0, every 2ms.
+/// * `bits` - The bits of the frame, the first in the most significant place
+/// * `count` - The number of bits
+///
+/// # Example
+///
+/// ```
+/// use rust_gpiozero::waveform::{self, golden, Waveform, WiegandEvent};
+///
+/// let golden = Waveform::from_vcd(golden::WIEGAND_26).unwrap();
+/// waveform::wiegand_26(18, 12345).compare(&golden, 0).unwrap();
+/// match waveform::decode_wiegand(&golden.jittered(40)).as_slice() {
+///     [WiegandEvent::Card(card)] => assert_eq!((card.facility, card.number), (18, 12345)),
+///     events => panic!("{:?}", events),
+/// }
+///
+/// // a flipped bit fails the parity check; a key is sent as 4 bits
+/// let bits = waveform::wiegand(0x246073 ^ 0x100, 26);
+/// assert_eq!(waveform::decode_wiegand(&bits), vec![WiegandEvent::Invalid(26)]);
+/// let key = waveform::wiegand(0xB, 4);
+/// assert_eq!(waveform::decode_wiegand(&key), vec![WiegandEvent::Key('#')]);
+/// ```
+pub fn wiegand(bits: u64, count: u32) -> Waveform {
+    let mut waveform = Waveform::new(&[("d0", true), ("d1", true)]);
+    for i in 0..count {
+        let line = if bits >> (count - 1 - i) & 1 == 1 {
+            "d1"
+        } else {
+            "d0"
+        };
+        let at = u64::from(i) * WIEGAND_INTERVAL;
+        let end = waveform.pulses(line, at, &[(false, WIEGAND_PULSE)]);
+        waveform.set(line, end, true);
+    }
+    waveform
+}
+
+/// The waveform of a 26 bit Wiegand card, with its parity bits.
+/// * `facility` - The facility code
+/// * `number` - The card number
+pub fn wiegand_26(facility: u8, number: u16) -> Waveform {
+    let data = u64::from(facility) << 16 | u64::from(number);
+    let even = u64::from(!(data >> 12).count_ones().is_multiple_of(2));
+    let odd = u64::from((data & 0xFFF).count_ones().is_multiple_of(2));
+    wiegand(even << 25 | data << 1 | odd, 26)
+}
+
+/// Replay the signals `d0` and `d1` through the decoder of a `WiegandReader`, with its
+/// inter-frame timeout of 25ms, returning every frame.
+pub fn decode_wiegand(waveform: &Waveform) -> Vec<WiegandEvent> {
+    let falling = |signal: &str, bit: bool| {
+        waveform
+            .edges(signal)
+            .unwrap_or_default()
+            .iter()
+            .filter(|&&(_, level)| !level)
+            .map(move |&(at, _)| (at, bit))
+            .collect::<Vec<_>>()
+    };
+    let mut edges = falling("d0", false);
+    edges.extend(falling("d1", true));
+    edges.sort_unstable();
+
+    let clock = MockClock::new();
+    let mut frame = WiegandBits::new(WIEGAND_TIMEOUT);
+    let mut events = Vec::new();
+    let mut take = |frame: &mut WiegandBits, now: Instant| {
+        let count = frame.count();
+        match frame.take(now) {
+            Some(WiegandFrame::Card(card)) => events.push(WiegandEvent::Card(card)),
+            Some(WiegandFrame::Key(key)) => events.push(WiegandEvent::Key(key)),
+            Some(WiegandFrame::Invalid) => events.push(WiegandEvent::Invalid(count)),
+            None => (),
+        }
+    };
+    for (at, bit) in edges {
+        clock.set(Duration::from_micros(at));
+        take(&mut frame, clock.now());
+        frame.push(bit, clock.now());
+    }
+    clock.advance(WIEGAND_TIMEOUT);
+    take(&mut frame, clock.now());
+    events
+}
+
+/// The waveform of a DHT sensor being read, on the signal `data`: the host holds the line
+/// low for the start signal of the model and releases it, and after 30µs the sensor
+/// answers with 80µs low, 80µs high and the 40 bits of `bytes`.
+/// * `model` - The sensor
+/// * `bytes` - The humidity, temperature and checksum bytes sent
+///
+/// # Example
+///
+/// ```
+/// use rust_gpiozero::waveform::{self, golden, Waveform};
+/// use rust_gpiozero::{DHTError, DHTModel};
+///
+/// let golden = Waveform::from_vcd(golden::DHT22).unwrap();
+/// let bytes = [0x02, 0x8C, 0x01, 0x5F, 0xEE];
+/// waveform::dht(DHTModel::DHT22, bytes).compare(&golden, 0).unwrap();
+///
+/// let reading = waveform::decode_dht(&golden.jittered(5), DHTModel::DHT22).unwrap();
+/// assert_eq!((reading.humidity, reading.temperature), (65.2, 35.1));
+///
+/// // a bad checksum, and a start signal too short to wake a DHT11
+/// let corrupt = waveform::dht(DHTModel::DHT22, [0x02, 0x8C, 0x01, 0x5F, 0xEF]);
+/// assert_eq!(waveform::decode_dht(&corrupt, DHTModel::DHT22), Err(DHTError::Checksum));
+/// assert_eq!(waveform::decode_dht(&golden, DHTModel::DHT11), Err(DHTError::Timeout));
+/// ```
+pub fn dht(model: DHTModel, bytes: [u8; 5]) -> Waveform {
+    let mut waveform = Waveform::new(&[("data", true)]);
+    let start = model.start_signal().as_micros() as u64;
+    let mut at = waveform.pulses("data", 0, &[(false, start), (true, 30)]);
+    at = waveform.pulses("data", at, &[(false, 80), (true, 80)]);
+    for bit in 0..40 {
+        let one = bytes[bit / 8] & (0x80 >> (bit % 8)) != 0;
+        at = waveform.pulses("data", at, &DHT_BITS[usize::from(one)]);
+    }
+    at = waveform.pulses("data", at, &[(false, 50)]);
+    waveform.set("data", at, true);
+    waveform
+}
+
+/// Replay the signal `data` through the receiver of a `DHTSensor`, from the end of the start
+/// signal, which must be long enough to wake the model: 18ms for a DHT11 and 800µs for a
+/// DHT22.
+pub fn decode_dht(waveform: &Waveform, model: DHTModel) -> Result<DHTReading, DHTError> {
+    let runs = waveform.runs("data").ok_or(DHTError::Timeout)?;
+    let rest = match runs.iter().position(|&(high, _)| !high) {
+        Some(start) if runs[start].1 >= dht_wake_time(model) => &runs[start + 1..],
+        _ => return Err(DHTError::Timeout),
+    };
+    let forever = waveform.level_at("data", u64::MAX).unwrap_or(true);
+    let mut runs = rest.iter().peekable();
+    let bytes = dht_receive(|level| {
+        let high = level == Level::High;
+        match runs.peek() {
+            Some(&&(run, micros)) if run == high => {
+                runs.next();
+                let duration = Duration::from_micros(micros);
+                if duration > DHT_LEVEL_TIMEOUT {
+                    Err(DHTError::Timeout)
+                } else {
+                    Ok(duration)
+                }
+            }
+            None if forever == high => Err(DHTError::Timeout),
+            // the line is already at the other level
+            _ => Ok(Duration::from_secs(0)),
+        }
+    })?;
+    model.decode(bytes)
+}
+
+/// The waveform of a `ShiftRegister` updating its outputs, on the signals `data`, `clock`
+/// and `latch`, a step every microsecond. The driver itself steps as fast as the pins
+/// switch.
+/// * `outputs` - The level of each output, from pin 0
+///
+/// # Example
+///
+/// ```
+/// use rust_gpiozero::waveform::{self, golden, Waveform};
+///
+/// let golden = Waveform::from_vcd(golden::SHIFT_REGISTER_16).unwrap();
+/// let mut outputs = [false; 16];
+/// for &pin in &[0, 3, 8, 15] {
+///     outputs[pin] = true;
+/// }
+/// waveform::shift_register(&outputs).compare(&golden, 0).unwrap();
+/// assert_eq!(waveform::decode_shift_register(&golden, 16), vec![outputs.to_vec()]);
+///
+/// // 16 clock pulses and one latch pulse
+/// assert_eq!(golden.edges("clock").unwrap().len(), 32);
+/// assert_eq!(golden.edges("latch").unwrap().len(), 2);
+///
+/// // on a chain of three, the 16 bits only reach the first two registers
+/// let longer = waveform::decode_shift_register(&golden, 24);
+/// assert_eq!(&longer[0][..16], &outputs[..]);
+/// assert_eq!(&longer[0][16..], &[false; 8]);
+/// ```
+pub fn shift_register(outputs: &[bool]) -> Waveform {
+    let names = ["data", "clock", "latch"];
+    let mut waveform = Waveform::new(&[("data", false), ("clock", false), ("latch", false)]);
+    for (at, step) in shift_steps(outputs).iter().enumerate() {
+        for (name, &level) in names.iter().zip(step) {
+            waveform.set(name, at as u64, level);
+        }
+    }
+    waveform
+}
+
+/// Replay the signals `data`, `clock` and `latch` through a model of a chain of 74HC595
+/// shift registers, returning the outputs latched on every rising edge of `latch`.
+/// * `waveform` - The waveform
+/// * `pins` - The number of outputs of the chain, 8 for each register
+pub fn decode_shift_register(waveform: &Waveform, pins: usize) -> Vec<Vec<bool>> {
+    let rising = |signal: &str| {
+        waveform
+            .edges(signal)
+            .unwrap_or_default()
+            .iter()
+            .filter(|&&(_, level)| level)
+            .map(|&(at, _)| (at, signal == "latch"))
+            .collect::<Vec<_>>()
+    };
+    let mut edges = rising("clock");
+    edges.extend(rising("latch"));
+    edges.sort();
+    let mut register = vec![false; pins];
+    let mut latched = Vec::new();
+    for (at, latch) in edges {
+        if latch {
+            latched.push(register.clone());
+        } else if pins > 0 {
+            register.pop();
+            register.insert(0, waveform.level_at("data", at).unwrap_or(false));
+        }
+    }
+    latched
+}
+
+/// How long the clock of an HX711 may stay high before it powers down, in µs.
+const HX711_POWER_DOWN: u64 = 60;
+
+/// The waveform of an `HX711` being read, on the signals `dout` and `sck`: DOUT falls when
+/// the conversion is ready, then each clock pulse, 1µs high and 1µs low, shifts out a bit on
+/// its rising edge, the most significant first. DOUT rises on the 25th pulse, and the pulses
+/// after it select the gain of the next conversion.
+/// * `raw` - The conversion, a 24 bit signed value
+/// * `gain` - The gain selected for the next conversion
+///
+/// # Example
+///
+/// ```
+/// use rust_gpiozero::waveform::{self, golden, Waveform};
+/// use rust_gpiozero::HX711Gain;
+///
+/// let golden = Waveform::from_vcd(golden::HX711).unwrap();
+/// waveform::hx711(-123456, HX711Gain::A128).compare(&golden, 0).unwrap();
+/// assert_eq!(waveform::decode_hx711(&golden), Some((-123456, HX711Gain::A128)));
+///
+/// // the pulses select the gain; a clock held high too long powers the HX711 down
+/// let b32 = waveform::hx711(0x7FFFFF, HX711Gain::B32);
+/// assert_eq!(waveform::decode_hx711(&b32), Some((0x7FFFFF, HX711Gain::B32)));
+/// assert_eq!(golden.edges("sck").unwrap().len(), 50);
+/// ```
+pub fn hx711(raw: i32, gain: HX711Gain) -> Waveform {
+    let pulse = HX711_PULSE.as_micros() as u64;
+    let mut waveform = Waveform::new(&[("dout", true), ("sck", false)]);
+    waveform.set("dout", 0, false);
+    for i in 0..gain.pulses() {
+        let at = pulse + u64::from(i) * 2 * pulse;
+        waveform.set("sck", at, true);
+        let level = i >= 24 || raw >> (23 - i) & 1 == 1;
+        waveform.set("dout", at, level);
+        waveform.set("sck", at + pulse, false);
+    }
+    waveform
+}
+
+/// Replay the signals `dout` and `sck` through the read of an `HX711`, returning the
+/// conversion and the gain its pulses select, or None if DOUT was not low before the first
+/// pulse, the clock was held high long enough to power the HX711 down, or the number of
+/// pulses selects no gain.
+pub fn decode_hx711(waveform: &Waveform) -> Option<(i32, HX711Gain)> {
+    let runs = waveform.runs("sck")?;
+    if runs
+        .iter()
+        .any(|&(high, micros)| high && micros >= HX711_POWER_DOWN)
+    {
+        return None;
+    }
+    let rising: Vec<u64> = waveform
+        .edges("sck")?
+        .iter()
+        .filter(|&&(_, level)| level)
+        .map(|&(at, _)| at)
+        .collect();
+    let gain = [HX711Gain::A128, HX711Gain::B32, HX711Gain::A64]
+        .iter()
+        .copied()
+        .find(|gain| gain.pulses() as usize == rising.len())?;
+    if waveform.level_at("dout", rising[0].checked_sub(1)?)? {
+        return None;
+    }
+    let mut edges = rising.iter();
+    let raw = hx711_shift(gain, || {
+        let at = edges.next().copied().unwrap_or(u64::MAX);
+        waveform.level_at("dout", at).unwrap_or(true)
+    });
+    Some((raw, gain))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Read a golden waveform, check it survives a round trip through VCD, and check the
+    /// generator of its driver still matches it exactly.
+    fn golden(vcd: &str, generated: Waveform) -> Waveform {
+        let golden = Waveform::from_vcd(vcd).unwrap();
+        assert_eq!(Waveform::from_vcd(&golden.to_vcd()).unwrap(), golden);
+        generated.compare(&golden, 0).unwrap();
+        golden
+    }
+
+    #[test]
+    fn nec_matches_golden() {
+        let golden = golden(golden::NEC, nec(0x04, 0x08, 1));
+        assert_eq!(decode_nec(&golden, "ir"), vec![(0x04, 0x08), (0x04, 0x08)]);
+        assert_eq!(
+            decode_nec(&golden.jittered(50), "ir"),
+            vec![(0x04, 0x08), (0x04, 0x08)]
+        );
+        assert!(nec(0x04, 0x09, 1).compare(&golden, 0).is_err());
+    }
+
+    #[test]
+    fn wiegand_matches_golden() {
+        let golden = golden(golden::WIEGAND_26, wiegand_26(18, 12345));
+        match decode_wiegand(&golden.jittered(40)).as_slice() {
+            [WiegandEvent::Card(card)] => assert_eq!((card.facility, card.number), (18, 12345)),
+            events => panic!("{:?}", events),
+        }
+        assert!(wiegand_26(18, 12346).compare(&golden, 0).is_err());
+    }
+
+    #[test]
+    fn dht_matches_golden() {
+        let bytes = [0x02, 0x8C, 0x01, 0x5F, 0xEE];
+        let golden = golden(golden::DHT22, dht(DHTModel::DHT22, bytes));
+        let reading = decode_dht(&golden.jittered(5), DHTModel::DHT22).unwrap();
+        assert_eq!((reading.humidity, reading.temperature), (65.2, 35.1));
+        assert!(dht(DHTModel::DHT11, bytes).compare(&golden, 0).is_err());
+    }
+
+    #[test]
+    fn shift_register_matches_golden() {
+        let mut outputs = [false; 16];
+        for &pin in &[0, 3, 8, 15] {
+            outputs[pin] = true;
+        }
+        let golden = golden(golden::SHIFT_REGISTER_16, shift_register(&outputs));
+        assert_eq!(decode_shift_register(&golden, 16), vec![outputs.to_vec()]);
+        outputs[1] = true;
+        assert!(shift_register(&outputs).compare(&golden, 0).is_err());
+    }
+
+    #[test]
+    fn hx711_matches_golden() {
+        let golden = golden(golden::HX711, hx711(-123456, HX711Gain::A128));
+        assert_eq!(decode_hx711(&golden), Some((-123456, HX711Gain::A128)));
+        assert!(hx711(-123457, HX711Gain::A128).compare(&golden, 0).is_err());
+        assert!(hx711(-123456, HX711Gain::A64).compare(&golden, 0).is_err());
+    }
+
+    #[test]
+    fn hx711_decodes_every_gain_and_rejects_power_down() {
+        for &gain in &[HX711Gain::A128, HX711Gain::B32, HX711Gain::A64] {
+            for &raw in &[0, 1, -1, 0x7FFFFF, -0x800000] {
+                assert_eq!(decode_hx711(&hx711(raw, gain)), Some((raw, gain)));
+            }
+        }
+        let mut stretched = hx711(42, HX711Gain::A128);
+        stretched.set("sck", 100, true);
+        stretched.set("sck", 100 + HX711_POWER_DOWN, false);
+        assert_eq!(decode_hx711(&stretched), None);
+    }
+}
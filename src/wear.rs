@@ -0,0 +1,341 @@
+//! Wear tracking for relays, valves and other outputs
+//!
+//! A `WearTracker` adds up how often outputs are switched and how long they are on, across
+//! restarts of the program, by sampling their `OutputStats` and keeping the totals in a
+//! file. Service thresholds raise an event when a part is due for maintenance, for example
+//! a contactor rated for 100 000 operations or a pump to be serviced every 2 000 hours.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::*;
+//! use std::time::Duration;
+//!
+//! let valve = DigitalOutputDevice::new(17);
+//! let mut tracker = WearTracker::open("/var/lib/irrigation/wear.json").unwrap();
+//! tracker.track("valve", valve.stats_fn());
+//! tracker.set_service_threshold("valve", ServiceThreshold::Actuations(50_000));
+//! tracker.when_service_due(|name, wear| {
+//!     println!("{} is due for service after {} operations", name, wear.actuations)
+//! });
+//!
+//! // after replacing the valve
+//! tracker.serviced("valve");
+//! ```
+use crate::json::Json;
+use crate::output_devices::{sleep_while_running, OutputStats};
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// The wear of an output since it was last serviced.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Wear {
+    /// The number of times the output was turned on
+    pub actuations: u64,
+    /// The total time the output was on
+    pub on_time: Duration,
+}
+
+/// When an output is due for service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceThreshold {
+    /// After it was turned on this many times
+    Actuations(u64),
+    /// After it was on for this long
+    OnTime(Duration),
+}
+
+impl ServiceThreshold {
+    fn reached(&self, wear: &Wear) -> bool {
+        match *self {
+            ServiceThreshold::Actuations(actuations) => wear.actuations >= actuations,
+            ServiceThreshold::OnTime(on_time) => wear.on_time >= on_time,
+        }
+    }
+}
+
+type StatsFn = Box<dyn Fn() -> OutputStats + Send>;
+type ServiceCallback = Box<dyn FnMut(&str, Wear) + Send>;
+
+/// An output whose wear is tracked.
+struct Tracked {
+    name: String,
+    stats: Option<StatsFn>,
+    last: OutputStats,
+    wear: Wear,
+    thresholds: Vec<(ServiceThreshold, bool)>,
+}
+
+impl Tracked {
+    fn new(name: &str, wear: Wear) -> Tracked {
+        Tracked {
+            name: name.to_string(),
+            stats: None,
+            last: OutputStats::default(),
+            wear,
+            thresholds: Vec::new(),
+        }
+    }
+
+    /// Add the switching since the last sample, returning ``True`` if it changed.
+    fn sample(&mut self) -> bool {
+        let stats = match &self.stats {
+            Some(stats) => stats(),
+            None => return false,
+        };
+        // counts going down mean the device stats were reset, so all of them are new
+        let since = if stats.on_count < self.last.on_count || stats.on_time < self.last.on_time {
+            OutputStats::default()
+        } else {
+            self.last
+        };
+        self.wear.actuations += stats.on_count - since.on_count;
+        self.wear.on_time += stats.on_time - since.on_time;
+        let changed = stats != self.last;
+        self.last = stats;
+        changed
+    }
+
+    /// The thresholds reached since the last check.
+    fn due(&mut self) -> bool {
+        let wear = self.wear;
+        let mut due = false;
+        for (threshold, raised) in &mut self.thresholds {
+            if !*raised && threshold.reached(&wear) {
+                *raised = true;
+                due = true;
+            }
+        }
+        due
+    }
+}
+
+struct WearState {
+    path: PathBuf,
+    devices: Vec<Tracked>,
+}
+
+impl WearState {
+    fn device(&mut self, name: &str) -> &mut Tracked {
+        match self.devices.iter().position(|device| device.name == name) {
+            Some(index) => &mut self.devices[index],
+            None => {
+                self.devices.push(Tracked::new(name, Wear::default()));
+                self.devices.last_mut().unwrap()
+            }
+        }
+    }
+
+    /// Sample every output, returning the ones due for service.
+    fn update(&mut self) -> (bool, Vec<(String, Wear)>) {
+        let mut changed = false;
+        let mut due = Vec::new();
+        for device in &mut self.devices {
+            changed |= device.sample();
+            if device.due() {
+                due.push((device.name.clone(), device.wear));
+            }
+        }
+        (changed, due)
+    }
+
+    /// Write the totals to a temporary file and move it over the old one, so a power cut
+    /// while saving does not lose them.
+    fn save(&self) -> io::Result<()> {
+        let members = self
+            .devices
+            .iter()
+            .map(|device| {
+                let wear = Json::object(vec![
+                    ("actuations", Json::Number(device.wear.actuations as f64)),
+                    (
+                        "on_seconds",
+                        Json::Number(device.wear.on_time.as_secs_f64()),
+                    ),
+                ]);
+                (device.name.clone(), wear)
+            })
+            .collect();
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+        fs::write(&temporary, Json::Object(members).to_string())?;
+        fs::rename(&temporary, &self.path)
+    }
+}
+
+/// Tracks the wear of outputs and keeps it in a file.
+///
+/// The outputs are sampled and the file is written every minute, when `save` is called and
+/// when the tracker is dropped. Each threshold raises its event once, until the output is
+/// marked as serviced.
+pub struct WearTracker {
+    state: Arc<Mutex<WearState>>,
+    callback: Arc<Mutex<Option<ServiceCallback>>>,
+    interval: Arc<Mutex<Duration>>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl WearTracker {
+    /// Returns a WearTracker keeping its totals in the file given, loading them if the file
+    /// exists.
+    /// * `path` - The file the totals are kept in
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<WearTracker, Box<dyn Error>> {
+        let path = path.as_ref().to_path_buf();
+        let mut devices = Vec::new();
+        match fs::read_to_string(&path) {
+            Ok(text) => match Json::parse(&text)? {
+                Json::Object(members) => {
+                    for (name, wear) in members {
+                        let number = |key| wear.get(key).and_then(Json::as_f64).unwrap_or(0.0);
+                        let wear = Wear {
+                            actuations: number("actuations").max(0.0) as u64,
+                            on_time: Duration::try_from_secs_f64(number("on_seconds"))
+                                .unwrap_or_default(),
+                        };
+                        devices.push(Tracked::new(&name, wear));
+                    }
+                }
+                _ => return Err(format!("{}: expected an object", path.display()).into()),
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(e) => return Err(e.into()),
+        }
+
+        let state = Arc::new(Mutex::new(WearState { path, devices }));
+        let callback: Arc<Mutex<Option<ServiceCallback>>> = Arc::new(Mutex::new(None));
+        let interval = Arc::new(Mutex::new(Duration::from_secs(60)));
+        let running = Arc::new(AtomicBool::new(true));
+        let handle = {
+            let state = Arc::clone(&state);
+            let callback = Arc::clone(&callback);
+            let interval = Arc::clone(&interval);
+            let running = Arc::clone(&running);
+            thread::spawn(move || loop {
+                let period = *interval.lock().unwrap();
+                sleep_while_running(&running, period);
+                if !running.load(Ordering::SeqCst) {
+                    break;
+                }
+                WearTracker::update_state(&state, &callback);
+            })
+        };
+        Ok(WearTracker {
+            state,
+            callback,
+            interval,
+            running,
+            handle: Some(handle),
+        })
+    }
+
+    fn update_state(state: &Mutex<WearState>, callback: &Mutex<Option<ServiceCallback>>) {
+        let due = {
+            let mut state = state.lock().unwrap();
+            let (changed, due) = state.update();
+            if changed {
+                if let Err(e) = state.save() {
+                    println!("Could not save wear to {}: {}", state.path.display(), e);
+                }
+            }
+            due
+        };
+        if let Some(callback) = callback.lock().unwrap().as_mut() {
+            for (name, wear) in due {
+                callback(&name, wear);
+            }
+        }
+    }
+
+    /// Track an output, carrying on from the totals in the file if it was tracked before.
+    /// * `name` - The name the output is kept under in the file
+    /// * `stats` - A function returning the stats of the output, e.g. from `stats_fn`
+    pub fn track<F>(&mut self, name: &str, stats: F)
+    where
+        F: Fn() -> OutputStats + Send + 'static,
+    {
+        let mut state = self.state.lock().unwrap();
+        let device = state.device(name);
+        device.last = stats();
+        device.stats = Some(Box::new(stats));
+    }
+
+    /// Raise the service event when the output reaches the threshold. An output can have
+    /// several thresholds, e.g. one for actuations and one for hours.
+    pub fn set_service_threshold(&mut self, name: &str, threshold: ServiceThreshold) {
+        let mut state = self.state.lock().unwrap();
+        state.device(name).thresholds.push((threshold, false));
+    }
+
+    /// Set the function called from a background thread with the name and wear of an
+    /// output when it reaches a service threshold.
+    pub fn when_service_due<C>(&mut self, callback: C)
+    where
+        C: FnMut(&str, Wear) + Send + 'static,
+    {
+        *self.callback.lock().unwrap() = Some(Box::new(callback));
+    }
+
+    /// The wear of the output since it was last serviced, or None if it is not tracked.
+    pub fn wear(&self, name: &str) -> Option<Wear> {
+        let mut state = self.state.lock().unwrap();
+        let device = state
+            .devices
+            .iter_mut()
+            .find(|device| device.name == name)?;
+        device.sample();
+        Some(device.wear)
+    }
+
+    /// Set the wear of the output back to zero and re-arm its thresholds, e.g. after the
+    /// part was replaced.
+    pub fn serviced(&mut self, name: &str) {
+        let mut state = self.state.lock().unwrap();
+        let device = state.device(name);
+        device.sample();
+        device.wear = Wear::default();
+        for (_, raised) in &mut device.thresholds {
+            *raised = false;
+        }
+        if let Err(e) = state.save() {
+            println!("Could not save wear to {}: {}", state.path.display(), e);
+        }
+    }
+
+    /// Sample the outputs, raising any service events due, and write the file.
+    pub fn save(&mut self) -> io::Result<()> {
+        WearTracker::update_state(&self.state, &self.callback);
+        self.state.lock().unwrap().save()
+    }
+
+    /// Set how often the outputs are sampled and the file is written. Defaults to 1 minute.
+    pub fn set_interval(&mut self, interval: Duration) {
+        if interval.is_zero() {
+            println!("Interval must be greater than 0");
+            return;
+        }
+        *self.interval.lock().unwrap() = interval;
+        if let Some(handle) = &self.handle {
+            handle.thread().unpark();
+        }
+    }
+}
+
+impl Drop for WearTracker {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+        if let Err(e) = self.save() {
+            println!("Could not save wear: {}", e);
+        }
+    }
+}
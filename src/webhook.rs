@@ -0,0 +1,395 @@
+//! Webhook notifications for device events
+//!
+//! A `Webhook` POSTs a JSON payload to a URL whenever it is told of an event, which covers
+//! services such as ntfy, Slack and IFTTT without code for each of them. Payloads are sent
+//! from a background thread, so device callbacks are not held up by the network, and
+//! failed deliveries are retried with exponential backoff.
+//!
+//! `http://` URLs are sent directly; `https://` URLs are sent with the `curl` program, which
+//! must be installed.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::*;
+//!
+//! let mut slack = Webhook::new("https://hooks.slack.com/services/T000/B000/XXXX");
+//! slack.set_template(r#"{"text": "{device}: {event}"}"#);
+//!
+//! let mut button = Button::new(2);
+//! button.when_pressed(slack.on("front gate", "opened"));
+//!
+//! let mut doorbell = Doorbell::new(3, Buzzer::new(4));
+//! doorbell.add_notifier(slack.clone());
+//! ```
+use crate::composites::{DoorbellEvent, Notifier};
+use crate::json::Json;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The payload sent when no template is set.
+const DEFAULT_TEMPLATE: &str =
+    r#"{"device": "{device}", "event": "{event}", "value": {value}, "time": {time}}"#;
+
+/// How long a delivery may take before it counts as failed.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The longest wait between retries.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Why a webhook delivery failed.
+#[derive(Debug)]
+pub enum WebhookError {
+    /// The URL is not an `http://` or `https://` URL
+    Url(String),
+    /// The connection failed, or `curl` could not be run
+    Io(io::Error),
+    /// The server answered with an HTTP status other than 2xx
+    Status(u16),
+    /// `curl` failed with the message given
+    Curl(String),
+}
+
+impl fmt::Display for WebhookError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WebhookError::Url(url) => write!(f, "not an http or https URL: {}", url),
+            WebhookError::Io(e) => write!(f, "{}", e),
+            WebhookError::Status(status) => write!(f, "server answered with status {}", status),
+            WebhookError::Curl(message) => write!(f, "curl failed: {}", message),
+        }
+    }
+}
+
+impl Error for WebhookError {}
+
+impl From<io::Error> for WebhookError {
+    fn from(e: io::Error) -> Self {
+        WebhookError::Io(e)
+    }
+}
+
+impl WebhookError {
+    /// Returns ``True`` if sending again might succeed.
+    fn is_transient(&self) -> bool {
+        match self {
+            WebhookError::Url(_) => false,
+            WebhookError::Status(status) => *status == 429 || *status >= 500,
+            WebhookError::Io(_) | WebhookError::Curl(_) => true,
+        }
+    }
+}
+
+struct WebhookConfig {
+    url: String,
+    template: String,
+    headers: Vec<(String, String)>,
+    retries: u32,
+    backoff: Duration,
+    last_error: Option<String>,
+}
+
+struct Counters {
+    delivered: AtomicU64,
+    failed: AtomicU64,
+}
+
+/// Sends device events to a URL as templated JSON payloads.
+///
+/// The template is any text, usually JSON, in which these placeholders are replaced:
+///
+/// * `{device}` - The name of the device, escaped for a JSON string
+/// * `{event}` - The name of the event, escaped for a JSON string
+/// * `{value}` - The value of the event as a JSON number, or `null`
+/// * `{time}` - The time of the event in seconds since the Unix epoch
+///
+/// Clones of a webhook share its settings and background thread, so one webhook can be
+/// handed to the callbacks of many devices.
+#[derive(Clone)]
+pub struct Webhook {
+    config: Arc<Mutex<WebhookConfig>>,
+    counters: Arc<Counters>,
+    payloads: mpsc::Sender<String>,
+}
+
+impl Webhook {
+    /// Returns a Webhook retrying failed deliveries 3 times, first after 1 second
+    /// * `url` - The `http://` or `https://` URL the payloads are POSTed to
+    pub fn new(url: &str) -> Webhook {
+        let config = Arc::new(Mutex::new(WebhookConfig {
+            url: url.to_string(),
+            template: DEFAULT_TEMPLATE.to_string(),
+            headers: Vec::new(),
+            retries: 3,
+            backoff: Duration::from_secs(1),
+            last_error: None,
+        }));
+        let counters = Arc::new(Counters {
+            delivered: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+        });
+        let (payloads, received) = mpsc::channel::<String>();
+        {
+            let config = Arc::clone(&config);
+            let counters = Arc::clone(&counters);
+            // runs until the last clone of the webhook is dropped
+            thread::spawn(move || {
+                for payload in received {
+                    match Webhook::deliver(&config, &payload) {
+                        Ok(()) => counters.delivered.fetch_add(1, Ordering::SeqCst),
+                        Err(e) => {
+                            config.lock().unwrap().last_error = Some(e.to_string());
+                            counters.failed.fetch_add(1, Ordering::SeqCst)
+                        }
+                    };
+                }
+            });
+        }
+        Webhook {
+            config,
+            counters,
+            payloads,
+        }
+    }
+
+    /// Set the template of the payloads.
+    pub fn set_template(&mut self, template: &str) {
+        self.config.lock().unwrap().template = template.to_string();
+    }
+
+    /// Add a header sent with every payload, such as an `Authorization` token. A
+    /// `Content-Type` header replaces the default of `application/json`.
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        let mut config = self.config.lock().unwrap();
+        config
+            .headers
+            .retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+        config.headers.push((name.to_string(), value.to_string()));
+    }
+
+    /// Set how often a failed delivery is retried and how long to wait before the first
+    /// retry; the wait doubles after every retry, up to a minute. Only failures that might
+    /// go away are retried: network errors, and server errors or rate limiting.
+    pub fn set_retries(&mut self, retries: u32, backoff: Duration) {
+        let mut config = self.config.lock().unwrap();
+        config.retries = retries;
+        config.backoff = backoff;
+    }
+
+    /// Send an event from a background thread.
+    /// * `device` - The name of the device
+    /// * `event` - The name of the event, such as `pressed` or `low level`
+    /// * `value` - The value of the event, if it has one
+    pub fn send(&self, device: &str, event: &str, value: Option<f64>) {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let payload = render(
+            &self.config.lock().unwrap().template,
+            device,
+            event,
+            value,
+            (time * 1000.0).round() / 1000.0,
+        );
+        let _ = self.payloads.send(payload);
+    }
+
+    /// Returns a callback sending an event without a value, for callbacks such as
+    /// `when_activated` or `when_pressed`.
+    pub fn on(&self, device: &str, event: &str) -> impl FnMut() + Send + 'static {
+        let webhook = self.clone();
+        let (device, event) = (device.to_string(), event.to_string());
+        move || webhook.send(&device, &event, None)
+    }
+
+    /// The number of payloads delivered.
+    pub fn delivered(&self) -> u64 {
+        self.counters.delivered.load(Ordering::SeqCst)
+    }
+
+    /// The number of payloads given up on after every retry failed.
+    pub fn failed(&self) -> u64 {
+        self.counters.failed.load(Ordering::SeqCst)
+    }
+
+    /// Why the last payload given up on failed.
+    pub fn last_error(&self) -> Option<String> {
+        self.config.lock().unwrap().last_error.clone()
+    }
+
+    fn deliver(config: &Mutex<WebhookConfig>, payload: &str) -> Result<(), WebhookError> {
+        let (url, headers, retries, mut backoff) = {
+            let config = config.lock().unwrap();
+            (
+                config.url.clone(),
+                config.headers.clone(),
+                config.retries,
+                config.backoff,
+            )
+        };
+        let mut attempt = 0;
+        loop {
+            match post_once(&url, &headers, payload) {
+                Err(e) if e.is_transient() && attempt < retries => {
+                    attempt += 1;
+                    thread::sleep(backoff);
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl Notifier for Webhook {
+    fn notify(&mut self, event: &DoorbellEvent) {
+        self.send("doorbell", "ring", Some(event.count as f64));
+    }
+}
+
+/// Fill in the placeholders of a template.
+fn render(template: &str, device: &str, event: &str, value: Option<f64>, time: f64) -> String {
+    // a JSON string without its quotes
+    let escape = |text: &str| {
+        let quoted = Json::String(text.to_string()).to_string();
+        quoted[1..quoted.len() - 1].to_string()
+    };
+    let value = match value {
+        Some(value) if value.is_finite() => Json::Number(value).to_string(),
+        _ => "null".to_string(),
+    };
+    template
+        .replace("{device}", &escape(device))
+        .replace("{event}", &escape(event))
+        .replace("{value}", &value)
+        .replace("{time}", &Json::Number(time).to_string())
+}
+
+fn content_type(headers: &[(String, String)]) -> Option<(String, String)> {
+    let custom = headers
+        .iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case("Content-Type"));
+    if custom {
+        None
+    } else {
+        Some(("Content-Type".to_string(), "application/json".to_string()))
+    }
+}
+
+/// POST a payload once, without retrying, to an `http://` or `https://` URL.
+pub(crate) fn post_once(
+    url: &str,
+    headers: &[(String, String)],
+    payload: &str,
+) -> Result<(), WebhookError> {
+    if url.starts_with("https://") {
+        post_with_curl(url, headers, payload)
+    } else {
+        post(url, headers, payload)
+    }
+}
+
+fn post(url: &str, headers: &[(String, String)], payload: &str) -> Result<(), WebhookError> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| WebhookError::Url(url.to_string()))?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => match port.parse::<u16>() {
+            Ok(port) => (host, port),
+            Err(_) => return Err(WebhookError::Url(url.to_string())),
+        },
+        None => (authority, 80),
+    };
+    let address = (host, port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| WebhookError::Url(url.to_string()))?;
+    let mut stream = TcpStream::connect_timeout(&address, TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    let mut request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        path,
+        authority,
+        payload.len()
+    );
+    for (name, value) in headers.iter().cloned().chain(content_type(headers)) {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request.push_str("\r\n");
+    request.push_str(payload);
+    stream.write_all(request.as_bytes())?;
+
+    let mut status_line = String::new();
+    BufReader::new(stream).read_line(&mut status_line)?;
+    // e.g. "HTTP/1.1 204 No Content"
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or_else(|| {
+            WebhookError::Io(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "malformed HTTP response",
+            ))
+        })?;
+    if (200..300).contains(&status) {
+        Ok(())
+    } else {
+        Err(WebhookError::Status(status))
+    }
+}
+
+/// POST with the `curl` program, for HTTPS. The payload is written to its standard input,
+/// as curl reads a file named by a payload starting with `@`.
+fn post_with_curl(
+    url: &str,
+    headers: &[(String, String)],
+    payload: &str,
+) -> Result<(), WebhookError> {
+    let mut command = Command::new("curl");
+    command
+        .args(["--silent", "--show-error", "--max-time"])
+        .arg(TIMEOUT.as_secs().to_string())
+        .args(["--output", "/dev/null", "--write-out", "%{http_code}"])
+        .args(["--request", "POST", "--data-binary", "@-"]);
+    for (name, value) in headers.iter().cloned().chain(content_type(headers)) {
+        command.arg("--header").arg(format!("{}: {}", name, value));
+    }
+    let mut child = command
+        .arg("--url")
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(payload.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(WebhookError::Curl(message));
+    }
+    match String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse::<u16>()
+    {
+        Ok(status) if (200..300).contains(&status) => Ok(()),
+        Ok(status) => Err(WebhookError::Status(status)),
+        Err(_) => Err(WebhookError::Curl("no HTTP status".to_string())),
+    }
+}